@@ -82,6 +82,9 @@ export interface StatusResponse {
   data_dir_size: number;
   home_dir: string;
   bin_dir: string;
+  // null when the daemon's IPC socket can't be reached.
+  pending_approvals: number | null;
+  oldest_pending_approval_age_ms: number | null;
 }
 
 export type PluginStatus =
@@ -125,6 +128,8 @@ export interface LogEntry {
   seq: number;
   ack: number;
   payload_type: string;
+  /** Summary or full payload, when `store.trace_payloads` is enabled. */
+  body?: unknown;
 }
 
 export interface LogsResponse {
@@ -132,9 +137,45 @@ export interface LogsResponse {
   entries: LogEntry[];
 }
 
+export interface AuditEntry {
+  ts_ms: number;
+  source: string;
+  caller_uid: string;
+  job_id: string;
+  tool: string;
+  args_hash: string;
+  decision: string;
+  reason: string;
+  rule?: string;
+}
+
+export interface AuditResponse {
+  total: number;
+  entries: AuditEntry[];
+}
+
 export interface RunEntry {
   job_id: string;
-  created_at: number;
+  /** "browser" for files left by browser commands; `tool` is then the action. */
+  kind: "exec" | "browser";
+  tool: string;
+  caller_uid: string;
+  started_ms: number;
+  finished_ms: number | null;
+  exit_code: number | null;
+  error: string | null;
+  bytes_stdout: number;
+  bytes_stderr: number;
+}
+
+/** Filters for `GET /runs`; times are ms since the epoch. */
+export interface RunSearch {
+  tool?: string;
+  caller?: string;
+  exitCode?: number;
+  failed?: boolean;
+  since?: number;
+  until?: number;
 }
 
 export interface RunsResponse {
@@ -142,10 +183,20 @@ export interface RunsResponse {
   runs: RunEntry[];
 }
 
+/** How a run ended; `result.outcome` in a run's result.json. */
+export interface RunOutcome {
+  kind: "finished" | "cancelled" | "timeout" | "rejected";
+  approved_by?: string;
+  approval_latency_ms?: number;
+  session_mode?: string;
+  rejection_reason?: string;
+}
+
 export interface RunDetail {
   job_id: string;
+  run: RunEntry;
   request: any;
-  result: any | null;
+  result: { outcome?: RunOutcome; [key: string]: any } | null;
   files: string[];
 }
 
@@ -177,8 +228,26 @@ export const api = {
     return fetchAPI(`/logs?limit=${limit}&offset=${offset}`);
   },
 
-  async getRuns(limit: number = 20, offset: number = 0): Promise<RunsResponse> {
-    return fetchAPI(`/runs?limit=${limit}&offset=${offset}`);
+  async getAudit(limit: number = 50, offset: number = 0): Promise<AuditResponse> {
+    return fetchAPI(`/audit?limit=${limit}&offset=${offset}`);
+  },
+
+  async getRuns(
+    limit: number = 20,
+    offset: number = 0,
+    search: RunSearch = {}
+  ): Promise<RunsResponse> {
+    const params = new URLSearchParams({
+      limit: String(limit),
+      offset: String(offset),
+    });
+    if (search.tool) params.set("tool", search.tool);
+    if (search.caller) params.set("caller", search.caller);
+    if (search.exitCode !== undefined) params.set("exit_code", String(search.exitCode));
+    if (search.failed) params.set("failed", "true");
+    if (search.since !== undefined) params.set("since", String(search.since));
+    if (search.until !== undefined) params.set("until", String(search.until));
+    return fetchAPI(`/runs?${params}`);
   },
 
   async getRunDetail(jobId: string): Promise<RunDetail> {
@@ -188,6 +257,12 @@ export const api = {
   async getRunFile(jobId: string, filename: string): Promise<string> {
     return fetchText(`/runs/${jobId}/${filename}`);
   },
+
+  /** Download link for a run's tarball; carries the token as a query param. */
+  runExportUrl(jobId: string): string {
+    const token = encodeURIComponent(getToken() ?? "");
+    return `/api/runs/${encodeURIComponent(jobId)}/export?token=${token}`;
+  },
 };
 
 export { getToken };
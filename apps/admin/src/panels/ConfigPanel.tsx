@@ -17,6 +17,7 @@ interface ConfigData {
     denied_tools?: string[];
     denied_paths?: string[];
     allowed_domains?: string[];
+    denied_domains?: string[];
     approval_timeout_secs?: number;
   };
   browser?: {
@@ -389,6 +390,7 @@ export default function ConfigPanel() {
               >
                 <option value="auto_accept">Auto Accept (Trust All)</option>
                 <option value="trust">Trust (With Timeout)</option>
+                <option value="read_only">Read Only (Approve Changes)</option>
                 <option value="strict">Strict (Require Approval)</option>
                 <option value="inactive">Inactive (Deny All)</option>
               </select>
@@ -472,6 +474,18 @@ export default function ConfigPanel() {
                 placeholder="github.com&#10;*.example.com&#10;..."
               />
             </div>
+
+            <div class="form-field form-field-full">
+              <label>Denied Domains (one per line)</label>
+              <textarea
+                rows={4}
+                value={(formData().policy?.denied_domains || []).join("\n")}
+                onInput={(e) =>
+                  updateArrayField("policy", "denied_domains", e.currentTarget.value)
+                }
+                placeholder="*.internal.corp&#10;..."
+              />
+            </div>
           </div>
         </section>
 
@@ -69,7 +69,15 @@ export default function LogsPanel() {
                         <td class="device-id">{entry.device_id}</td>
                         <td class="msg-id">{entry.msg_id}</td>
                         <td>{entry.seq}/{entry.ack}</td>
-                        <td class="payload-type">{entry.payload_type}</td>
+                        <td class="payload-type">
+                          {entry.payload_type}
+                          <Show when={entry.body !== undefined}>
+                            <details class="payload-body">
+                              <summary>body</summary>
+                              <pre>{JSON.stringify(entry.body, null, 2)}</pre>
+                            </details>
+                          </Show>
+                        </td>
                       </tr>
                     )}
                   </For>
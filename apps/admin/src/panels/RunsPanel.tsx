@@ -1,5 +1,5 @@
 import { createResource, createSignal, For, Show } from "solid-js";
-import { api, RunEntry, RunDetail } from "../lib/api";
+import { api, RunEntry, RunDetail, RunSearch } from "../lib/api";
 
 export default function RunsPanel() {
   const [offset, setOffset] = createSignal(0);
@@ -10,12 +10,20 @@ export default function RunsPanel() {
     content: string;
   } | null>(null);
 
+  const [search, setSearch] = createSignal<RunSearch>({});
+
   const limit = 20;
 
-  const [runs] = createResource(offset, (offset) =>
-    api.getRuns(limit, offset)
+  const [runs] = createResource(
+    () => ({ offset: offset(), search: search() }),
+    ({ offset, search }) => api.getRuns(limit, offset, search)
   );
 
+  function updateSearch(change: RunSearch) {
+    setSearch({ ...search(), ...change });
+    setOffset(0);
+  }
+
   const [runDetail] = createResource(selectedJobId, (jobId) =>
     api.getRunDetail(jobId)
   );
@@ -64,6 +72,25 @@ export default function RunsPanel() {
           <>
             <h2>Job Runs</h2>
 
+            <div class="runs-search">
+              <input
+                type="text"
+                placeholder="Tool"
+                value={search().tool ?? ""}
+                onChange={(e) => updateSearch({ tool: e.currentTarget.value })}
+              />
+              <label>
+                <input
+                  type="checkbox"
+                  checked={search().failed ?? false}
+                  onChange={(e) =>
+                    updateSearch({ failed: e.currentTarget.checked })
+                  }
+                />
+                Failed only
+              </label>
+            </div>
+
             <Show when={runs.loading}>
               <p>Loading...</p>
             </Show>
@@ -88,8 +115,23 @@ export default function RunsPanel() {
                           onClick={() => handleSelectRun(run.job_id)}
                         >
                           <div class="run-id">{run.job_id}</div>
+                          <div class="run-tool">
+                            <Show
+                              when={run.kind === "browser"}
+                              fallback={
+                                <>
+                                  {run.tool}
+                                  {run.exit_code === null
+                                    ? " (running)"
+                                    : ` (exit ${run.exit_code})`}
+                                </>
+                              }
+                            >
+                              browser {run.tool}
+                            </Show>
+                          </div>
                           <div class="run-time">
-                            {formatTimestamp(run.created_at)}
+                            {formatTimestamp(run.started_ms)}
                           </div>
                         </div>
                       )}
@@ -144,6 +186,36 @@ export default function RunsPanel() {
                     <pre>{JSON.stringify(detail().request, null, 2)}</pre>
                   </section>
 
+                  <Show when={detail().result?.outcome}>
+                    {(outcome) => (
+                      <section>
+                        <h3>Outcome</h3>
+                        <dl class="run-outcome">
+                          <dt>Kind</dt>
+                          <dd>{outcome().kind}</dd>
+                          <Show when={outcome().rejection_reason}>
+                            <dt>Rejection reason</dt>
+                            <dd>{outcome().rejection_reason}</dd>
+                          </Show>
+                          <Show when={outcome().session_mode}>
+                            <dt>Session mode</dt>
+                            <dd>{outcome().session_mode}</dd>
+                          </Show>
+                          <Show when={outcome().approved_by}>
+                            <dt>Approved by</dt>
+                            <dd>{outcome().approved_by}</dd>
+                          </Show>
+                          <Show when={outcome().approval_latency_ms !== undefined}>
+                            <dt>Approval latency</dt>
+                            <dd>
+                              {(outcome().approval_latency_ms! / 1000).toFixed(1)}s
+                            </dd>
+                          </Show>
+                        </dl>
+                      </section>
+                    )}
+                  </Show>
+
                   <Show when={detail().result}>
                     <section>
                       <h3>Result</h3>
@@ -166,6 +238,15 @@ export default function RunsPanel() {
                           </button>
                         )}
                       </For>
+                      <Show when={detail().run.kind !== "browser"}>
+                        <a
+                          class="file-item"
+                          href={api.runExportUrl(detail().job_id)}
+                          download
+                        >
+                          Export .tar.gz
+                        </a>
+                      </Show>
                     </div>
                   </section>
                 </div>
@@ -25,6 +25,13 @@ export default function StatusPanel() {
     return Math.round(bytes / Math.pow(k, i) * 100) / 100 + " " + sizes[i];
   }
 
+  function formatAge(ms: number): string {
+    const mins = Math.floor(ms / 60000);
+    if (mins < 1) return `${Math.floor(ms / 1000)}s`;
+    if (mins < 60) return `${mins}m`;
+    return `${Math.floor(mins / 60)}h ${mins % 60}m`;
+  }
+
   function hasUpdate(): boolean {
     const current = status()?.version;
     const latest = latestVersion();
@@ -60,6 +67,17 @@ export default function StatusPanel() {
                 {data().daemon_pid && ` (PID: ${data().daemon_pid})`}
               </span>
             </div>
+            <Show when={data().pending_approvals !== null}>
+              <div class="status-item">
+                <span class="label">Pending Approvals</span>
+                <span class={data().pending_approvals ? "value error" : "value"}>
+                  {data().pending_approvals}
+                  {data().pending_approvals
+                    ? ` (oldest ${formatAge(data().oldest_pending_approval_age_ms ?? 0)})`
+                    : ""}
+                </span>
+              </div>
+            </Show>
             <div class="status-item">
               <span class="label">Config Path</span>
               <span class="value">{data().config_path}</span>
@@ -232,6 +232,8 @@ async fn create_job(
                 env: req.env.clone(),
                 timeout_ms,
                 interactive: req.interactive,
+                priority: 0,
+                output_cap_bytes: 0,
             },
         )),
         ..Default::default()
@@ -127,6 +127,8 @@ impl JobRuntime {
                     env: job.env.clone(),
                     timeout_ms: job.timeout_ms,
                     interactive: job.interactive,
+                    priority: 0,
+                    output_cap_bytes: 0,
                 },
             )),
             ..Default::default()
@@ -185,7 +187,8 @@ impl JobRuntime {
     }
 
     pub async fn handle_device_frame(&self, device_id: &str, frame: &[u8]) -> anyhow::Result<()> {
-        let envelope = ahand_protocol::Envelope::decode(frame)?;
+        let mut envelope = ahand_protocol::Envelope::decode(frame)?;
+        ahand_protocol::compression::decompress(&mut envelope)?;
         if self.connections.has_seen_inbound(device_id, envelope.seq) {
             self.connections
                 .observe_ack(device_id, envelope.ack)
@@ -1261,6 +1264,7 @@ mod tests {
                     job_id: job.id.to_string(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1299,6 +1303,7 @@ mod tests {
                     job_id: stale_job_id,
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1423,6 +1428,7 @@ mod tests {
                     job_id: job.id.to_string(),
                     exit_code: -1,
                     error: "cancelled".into(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1470,6 +1476,7 @@ mod tests {
                     job_id: job.id.to_string(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -632,6 +632,18 @@ async fn run_device_socket(socket: WebSocket, state: AppState) -> anyhow::Result
                         ahand_protocol::HelloAccepted {
                             auth_method: verified.auth_method.into(),
                             update_suggestion: None,
+                            // Job output may now arrive compressed; see
+                            // `JobRuntime::handle_device_frame`.
+                            compression: if hello
+                                .accepts
+                                .iter()
+                                .any(|c| c == ahand_protocol::compression::ZSTD)
+                            {
+                                ahand_protocol::compression::ZSTD.into()
+                            } else {
+                                String::new()
+                            },
+                            ..Default::default()
                         },
                     )),
                     ..Default::default()
@@ -804,6 +816,15 @@ async fn run_device_socket(socket: WebSocket, state: AppState) -> anyhow::Result
                     if state.connections.has_seen_inbound(&device_id, envelope.seq) {
                         state.connections.observe_ack(&device_id, envelope.ack).await?;
                         queue_ack_only(&control_tx, &device_id, envelope.seq)?;
+                    } else if let Some(ahand_protocol::envelope::Payload::StandaloneAck(ref ack)) =
+                        envelope.payload
+                    {
+                        // Standalone acks are unsequenced: trim the outbox
+                        // and nothing else, so they never get an ack back.
+                        state
+                            .connections
+                            .observe_ack(&device_id, ack.ack.max(envelope.ack))
+                            .await?;
                     } else if let Some(ahand_protocol::envelope::Payload::Heartbeat(ref hb)) =
                         envelope.payload
                     {
@@ -1165,6 +1186,29 @@ fn envelope_payload_variant_name(p: &Option<ahand_protocol::envelope::Payload>)
         Some(AppToolsUpdate(_)) => "AppToolsUpdate",
         Some(AppToolRequest(_)) => "AppToolRequest",
         Some(AppToolResponse(_)) => "AppToolResponse",
+        Some(JobSubscribe(_)) => "JobSubscribe",
+        Some(Shutdown(_)) => "Shutdown",
+        Some(Ping(_)) => "Ping",
+        Some(Pong(_)) => "Pong",
+        Some(Subscribe(_)) => "Subscribe",
+        Some(StandaloneAck(_)) => "StandaloneAck",
+        Some(CancelAll(_)) => "CancelAll",
+        Some(CancelAllResult(_)) => "CancelAllResult",
+        Some(JobQueued(_)) => "JobQueued",
+        Some(PolicyCheckRequest(_)) => "PolicyCheckRequest",
+        Some(PolicyCheckResult(_)) => "PolicyCheckResult",
+        Some(SetPolicyPreset(_)) => "SetPolicyPreset",
+        Some(Error(_)) => "Error",
+        Some(ClearSession(_)) => "ClearSession",
+        Some(PendingApprovalsQuery(_)) => "PendingApprovalsQuery",
+        Some(PendingApprovalsState(_)) => "PendingApprovalsState",
+        Some(ApprovalExpired(_)) => "ApprovalExpired",
+        Some(ApprovalResolveResult(_)) => "ApprovalResolveResult",
+        Some(ApprovalBulkResponse(_)) => "ApprovalBulkResponse",
+        Some(ApprovalBulkResult(_)) => "ApprovalBulkResult",
+        Some(DaemonStatusQuery(_)) => "DaemonStatusQuery",
+        Some(DaemonStatus(_)) => "DaemonStatus",
+        Some(ApprovalResolved(_)) => "ApprovalResolved",
     }
 }
 
@@ -1395,6 +1439,8 @@ mod tests {
                     env: Default::default(),
                     timeout_ms: 30_000,
                     interactive: false,
+                    priority: 0,
+                    output_cap_bytes: 0,
                 },
             )),
             ..Default::default()
@@ -283,6 +283,7 @@ async fn create_job_happy_path_dispatches_and_streams_events() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -967,6 +968,7 @@ async fn two_concurrent_sse_clients_receive_all_events() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1051,6 +1053,7 @@ async fn sse_client_disconnect_cleans_up_on_terminal_event() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1125,6 +1128,7 @@ async fn many_sse_disconnects_do_not_leak_entries() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1229,6 +1233,7 @@ async fn large_stdout_chunk_delivered_intact() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1396,6 +1401,7 @@ async fn exit_code_non_zero_reports_error_event() {
                     job_id: job_id.clone(),
                     exit_code: 42,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1466,6 +1472,7 @@ async fn cancelled_finish_reports_cancelled_error_code() {
                     job_id: job_id.clone(),
                     exit_code: -1,
                     error: "cancelled".into(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1626,6 +1633,7 @@ async fn stderr_and_progress_with_message_render_correctly() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1684,6 +1692,7 @@ async fn sse_late_joiner_after_terminal_event_gets_empty_stream() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -1773,6 +1782,7 @@ async fn control_job_output_stream_replays_after_terminal_event() {
                     job_id: job_id.clone(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 },
             )),
             ..Default::default()
@@ -67,6 +67,7 @@ async fn stale_control_plane_job_finished_is_acked_without_closing_socket() {
                         job_id: "01KRDSZ20BRER8PT5SMK9CYC9N".into(),
                         exit_code: 0,
                         error: String::new(),
+                        ..Default::default()
                     },
                 )),
                 ..Default::default()
@@ -226,6 +227,7 @@ async fn daemon_heartbeats_keep_connection_online_past_staleness_timeout() {
                             ahand_protocol::Heartbeat {
                                 sent_at_ms: now_ms(),
                                 daemon_version: "0.1.2".into(),
+                                ..Default::default()
                             },
                         )),
                         ..Default::default()
@@ -893,6 +895,7 @@ async fn superseded_connection_cannot_mutate_job_state() {
                         job_id: job_id.clone(),
                         exit_code: 0,
                         error: String::new(),
+                        ..Default::default()
                     },
                 )),
                 ..Default::default()
@@ -1022,6 +1025,7 @@ async fn invalid_frame_ack_does_not_clear_replay_buffer() {
                         job_id: "missing-job".into(),
                         exit_code: 0,
                         error: String::new(),
+                        ..Default::default()
                     },
                 )),
                 ..Default::default()
@@ -115,6 +115,7 @@ async fn send_heartbeat<S>(
         payload: Some(envelope::Payload::Heartbeat(Heartbeat {
             sent_at_ms,
             daemon_version: "0.1.2".into(),
+            ..Default::default()
         })),
         ..Default::default()
     };
@@ -593,6 +593,7 @@ impl TestDevice {
                 job_id: job_id.into(),
                 exit_code,
                 error: error.into(),
+                ..Default::default()
             })),
             ..Default::default()
         };
@@ -784,6 +785,11 @@ pub fn signed_hello_with_key_at(
         os: "linux".into(),
         capabilities: vec!["exec".into()],
         last_ack,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let signature = signing_key
@@ -819,6 +825,11 @@ pub fn signed_hello_with_browser(device_id: &str, challenge_nonce: &[u8]) -> Env
         os: "linux".into(),
         capabilities: vec!["exec".into(), "browser-playwright-cli".into()],
         last_ack: 0,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let signature = signing_key
@@ -854,6 +865,11 @@ pub fn bootstrap_hello(device_id: &str, token: &str, challenge_nonce: &[u8]) ->
         os: "linux".into(),
         capabilities: vec!["exec".into()],
         last_ack: 0,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let signature = signing_key
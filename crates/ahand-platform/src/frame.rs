@@ -0,0 +1,231 @@
+//! Length-prefixed IPC frames, shared by `ahandd` and `ahandctl`.
+//!
+//! A frame is a 4-byte big-endian length followed by that many bytes. On a
+//! connection that negotiated compression in its `Hello`, the body starts
+//! with a flag byte: `0x00` for a raw payload, `0x01` for a zstd-compressed
+//! one. Only payloads of at least [`COMPRESS_THRESHOLD_BYTES`] are
+//! compressed. Connections that didn't negotiate keep the flagless format.
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Largest frame either side will read, before and after decompression.
+pub const MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Name of the zstd codec in `Hello.accepts` / `HelloAccepted.compression`.
+pub const ZSTD: &str = "zstd";
+
+/// Payloads smaller than this are sent raw even when compression is on.
+pub const COMPRESS_THRESHOLD_BYTES: usize = 1024;
+
+const FLAG_RAW: u8 = 0x00;
+const FLAG_ZSTD: u8 = 0x01;
+
+/// zstd level: fast enough to keep up with a chatty job's output.
+const ZSTD_LEVEL: i32 = 3;
+
+/// How frames on one connection are encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameCodec {
+    /// `[len][payload]`, as spoken before compression existed.
+    #[default]
+    Plain,
+    /// `[len][flag][payload]`, with large payloads zstd-compressed.
+    Zstd,
+}
+
+impl FrameCodec {
+    /// The codec named by a `HelloAccepted.compression`, if known.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "" => Some(Self::Plain),
+            ZSTD => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The name to put in `HelloAccepted.compression`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "",
+            Self::Zstd => ZSTD,
+        }
+    }
+
+    /// Read one frame and return its decoded payload.
+    pub async fn read_frame<R: AsyncReadExt + Unpin>(self, reader: &mut R) -> Result<Vec<u8>> {
+        let len = reader.read_u32().await? as usize;
+        if len > MAX_FRAME_BYTES {
+            return Err(Error::new(ErrorKind::InvalidData, "frame too large"));
+        }
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf).await?;
+        match self {
+            Self::Plain => Ok(buf),
+            Self::Zstd => decode_flagged(buf),
+        }
+    }
+
+    /// Encode `data` and write it as one frame.
+    pub async fn write_frame<W: AsyncWriteExt + Unpin>(
+        self,
+        writer: &mut W,
+        data: &[u8],
+    ) -> Result<()> {
+        let compressed;
+        let (flag, body) = match self {
+            Self::Plain => (None, data),
+            Self::Zstd if data.len() >= COMPRESS_THRESHOLD_BYTES => {
+                compressed = zstd::bulk::compress(data, ZSTD_LEVEL)?;
+                (Some(FLAG_ZSTD), compressed.as_slice())
+            }
+            Self::Zstd => (Some(FLAG_RAW), data),
+        };
+        let len = body.len() + usize::from(flag.is_some());
+        writer.write_u32(len as u32).await?;
+        if let Some(flag) = flag {
+            writer.write_u8(flag).await?;
+        }
+        writer.write_all(body).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Read one frame in the plain format.
+pub async fn read_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    FrameCodec::Plain.read_frame(reader).await
+}
+
+/// Write one frame in the plain format.
+pub async fn write_frame<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) -> Result<()> {
+    FrameCodec::Plain.write_frame(writer, data).await
+}
+
+fn decode_flagged(mut buf: Vec<u8>) -> Result<Vec<u8>> {
+    if buf.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "frame without flag byte",
+        ));
+    }
+    match buf.remove(0) {
+        FLAG_RAW => Ok(buf),
+        FLAG_ZSTD => {
+            // Bound the output so a small frame can't inflate without limit.
+            let mut out = Vec::new();
+            zstd::stream::read::Decoder::new(buf.as_slice())?
+                .take(MAX_FRAME_BYTES as u64 + 1)
+                .read_to_end(&mut out)?;
+            if out.len() > MAX_FRAME_BYTES {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "decompressed frame too large",
+                ));
+            }
+            Ok(out)
+        }
+        flag => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unknown frame flag {flag:#04x}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(codec: FrameCodec, data: &[u8]) -> (Vec<u8>, usize) {
+        let mut wire = Vec::new();
+        codec.write_frame(&mut wire, data).await.unwrap();
+        let wire_len = wire.len();
+        let decoded = codec.read_frame(&mut wire.as_slice()).await.unwrap();
+        (decoded, wire_len)
+    }
+
+    /// Job output a UI would tail: repetitive, line-oriented text.
+    fn log_output(bytes: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(bytes);
+        let mut n = 0u64;
+        while out.len() < bytes {
+            out.extend_from_slice(
+                format!(
+                    "2026-10-17T12:00:{:02}Z INFO build step {n} finished ok\n",
+                    n % 60
+                )
+                .as_bytes(),
+            );
+            n += 1;
+        }
+        out.truncate(bytes);
+        out
+    }
+
+    #[tokio::test]
+    async fn plain_frames_keep_the_flagless_format() {
+        let (decoded, wire_len) = roundtrip(FrameCodec::Plain, b"hello").await;
+        assert_eq!(decoded, b"hello");
+        assert_eq!(wire_len, 4 + 5);
+    }
+
+    #[tokio::test]
+    async fn small_frames_are_flagged_raw() {
+        let mut wire = Vec::new();
+        FrameCodec::Zstd
+            .write_frame(&mut wire, b"hi")
+            .await
+            .unwrap();
+        assert_eq!(wire, [0, 0, 0, 3, FLAG_RAW, b'h', b'i']);
+        let decoded = FrameCodec::Zstd.read_frame(&mut wire.as_slice()).await;
+        assert_eq!(decoded.unwrap(), b"hi");
+    }
+
+    #[tokio::test]
+    async fn large_output_is_compressed() {
+        let output = log_output(4096);
+        let (decoded, wire_len) = roundtrip(FrameCodec::Zstd, &output).await;
+        assert_eq!(decoded, output);
+        assert!(wire_len < output.len() / 4, "{wire_len} bytes on the wire");
+    }
+
+    #[tokio::test]
+    async fn ten_megabytes_of_output_shrinks_on_the_wire() {
+        // 10 MiB streamed as the 4 KiB chunks the executor reads.
+        let output = log_output(10 * 1024 * 1024);
+        let (mut plain, mut zstd) = (0, 0);
+        for chunk in output.chunks(4096) {
+            plain += roundtrip(FrameCodec::Plain, chunk).await.1;
+            zstd += roundtrip(FrameCodec::Zstd, chunk).await.1;
+        }
+        assert!(zstd * 4 < plain, "zstd {zstd} bytes vs plain {plain} bytes");
+    }
+
+    #[tokio::test]
+    async fn oversized_and_malformed_frames_are_rejected() {
+        let too_long = ((MAX_FRAME_BYTES + 1) as u32).to_be_bytes();
+        let err = FrameCodec::Zstd
+            .read_frame(&mut too_long.as_slice())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let unknown_flag = [0, 0, 0, 2, 0x7f, 0];
+        let err = FrameCodec::Zstd
+            .read_frame(&mut unknown_flag.as_slice())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("0x7f"));
+
+        let bomb = zstd::bulk::compress(&vec![0u8; MAX_FRAME_BYTES + 1], 1).unwrap();
+        let mut wire = ((bomb.len() + 1) as u32).to_be_bytes().to_vec();
+        wire.push(FLAG_ZSTD);
+        wire.extend_from_slice(&bomb);
+        let err = FrameCodec::Zstd
+            .read_frame(&mut wire.as_slice())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("decompressed frame too large"));
+    }
+}
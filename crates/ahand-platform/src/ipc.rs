@@ -8,7 +8,8 @@
 //! user, via the Owner-Rights SID), SYSTEM, and Builtin-Administrators — with
 //! no `Everyone`/`World` ACE, so cross-user clients are denied by omission.
 //! See [`create_secured_pipe`]. The `mode` argument is ignored on Windows and
-//! peer identity is reported as `"pipe:local"`.
+//! peer identity is the client process's account, `"user:<DOMAIN>\<name>"`,
+//! or `"pipe:local"` when Windows won't name it.
 
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
@@ -280,10 +281,20 @@ impl IpcListener {
 
     /// Accept one incoming connection.
     ///
-    /// Returns the per-platform stream and a peer-identity string:
+    /// Like [`accept_peer`](Self::accept_peer), returning only the peer's
+    /// identity string.
+    pub async fn accept(&mut self) -> Result<(IpcServerStream, String)> {
+        let (stream, peer) = self.accept_peer().await?;
+        Ok((stream, peer.id))
+    }
+
+    /// Accept one incoming connection.
+    ///
+    /// Returns the per-platform stream and the peer, whose identity string is:
     /// - Unix: `"uid:<n>"` (from `peer_cred`)
-    /// - Windows: `"pipe:local"` (Windows named pipes do not expose per-client
-    ///   UIDs through Tokio's API; the SD restricts callers anyway)
+    /// - Windows: `"user:<DOMAIN>\<name>"`, the account of the client
+    ///   process's token (see [`pipe_client_account`]), else `"pipe:local"`;
+    ///   the SD restricts callers anyway
     ///
     /// # Windows error semantics
     ///
@@ -294,13 +305,21 @@ impl IpcListener {
     /// recreated lazily so a log-and-continue accept loop self-heals.
     /// If the pre-creation fails after a successful connect we return that
     /// error, leaving `next` as `None` for the next lazy-recreate attempt.
-    pub async fn accept(&mut self) -> Result<(IpcServerStream, String)> {
+    pub async fn accept_peer(&mut self) -> Result<(IpcServerStream, IpcPeer)> {
         #[cfg(unix)]
         {
             let (stream, _addr) = self.inner.accept().await.context("IPC accept")?;
             let peer = match stream.peer_cred() {
-                Ok(cred) => format!("uid:{}", cred.uid()),
-                Err(_) => "uid:unknown".to_string(),
+                Ok(cred) => IpcPeer {
+                    id: format!("uid:{}", cred.uid()),
+                    uid: Some(cred.uid()),
+                    gid: Some(cred.gid()),
+                },
+                Err(_) => IpcPeer {
+                    id: "uid:unknown".to_string(),
+                    uid: None,
+                    gid: None,
+                },
             };
             Ok((stream, peer))
         }
@@ -346,11 +365,142 @@ impl IpcListener {
             // Propagate any connect error (next is already repopulated above).
             connect_result?;
 
-            Ok((server, "pipe:local".to_string()))
+            let id = pipe_peer_id(pipe_client_account(&server));
+            Ok((
+                server,
+                IpcPeer {
+                    id,
+                    uid: None,
+                    gid: None,
+                },
+            ))
         }
     }
 }
 
+/// The process on the other end of an accepted IPC connection.
+#[derive(Clone, Debug)]
+pub struct IpcPeer {
+    /// Peer-identity string: `"uid:<n>"` on Unix, `"user:<DOMAIN>\<name>"`
+    /// (or `"pipe:local"`) on Windows.
+    pub id: String,
+    /// Effective UID, when the platform reports peer credentials.
+    pub uid: Option<u32>,
+    /// Effective GID, when the platform reports peer credentials.
+    pub gid: Option<u32>,
+}
+
+/// Peer-identity string for a pipe client whose account is `account`.
+#[cfg(windows)]
+fn pipe_peer_id(account: Option<String>) -> String {
+    match account {
+        Some(account) => format!("user:{account}"),
+        None => "pipe:local".to_string(),
+    }
+}
+
+/// `DOMAIN\name` of the account the process on the client end of `pipe`
+/// runs as, read from its access token. `None` if Windows won't say (the
+/// client already exited, or its process can't be opened for query).
+#[cfg(windows)]
+fn pipe_client_account(pipe: &tokio::net::windows::named_pipe::NamedPipeServer) -> Option<String> {
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, LookupAccountSidW, SID_NAME_USE, TOKEN_QUERY, TOKEN_USER, TokenUser,
+    };
+    use windows_sys::Win32::System::Pipes::GetNamedPipeClientProcessId;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let mut pid = 0u32;
+    // SAFETY: the pipe handle is live for the call (`pipe` is borrowed) and
+    // `pid` is a valid out-pointer.
+    if unsafe { GetNamedPipeClientProcessId(pipe.as_raw_handle(), &mut pid) } == 0 {
+        return None;
+    }
+    // SAFETY: no pointers are passed; a null result is checked below.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return None;
+    }
+    // SAFETY: `process` is a fresh, non-null handle that nothing else owns;
+    // OwnedHandle closes it exactly once.
+    let process = unsafe { OwnedHandle::from_raw_handle(process) };
+
+    let mut token = std::ptr::null_mut();
+    // SAFETY: `process` is live for the call and `token` is a valid
+    // out-pointer.
+    if unsafe { OpenProcessToken(process.as_raw_handle(), TOKEN_QUERY, &mut token) } == 0 {
+        return None;
+    }
+    // SAFETY: on success `token` is a fresh handle that we own.
+    let token = unsafe { OwnedHandle::from_raw_handle(token) };
+
+    // First call sizes the buffer; it "fails" with the length filled in.
+    let mut len = 0u32;
+    // SAFETY: a null buffer with length 0 is the documented size query.
+    unsafe {
+        GetTokenInformation(
+            token.as_raw_handle(),
+            TokenUser,
+            std::ptr::null_mut(),
+            0,
+            &mut len,
+        )
+    };
+    if len == 0 {
+        return None;
+    }
+    // u64 elements keep the TOKEN_USER (pointer fields) aligned.
+    let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+    // SAFETY: `buf` holds at least `len` writable bytes for the call.
+    if unsafe {
+        GetTokenInformation(
+            token.as_raw_handle(),
+            TokenUser,
+            buf.as_mut_ptr().cast(),
+            len,
+            &mut len,
+        )
+    } == 0
+    {
+        return None;
+    }
+    // SAFETY: on success `buf` starts with an initialized TOKEN_USER whose
+    // SID points into `buf`, which outlives every use of `sid` below.
+    let sid = unsafe { (*buf.as_ptr().cast::<TOKEN_USER>()).User.Sid };
+
+    let mut name = [0u16; 256];
+    let mut domain = [0u16; 256];
+    let mut name_len = name.len() as u32;
+    let mut domain_len = domain.len() as u32;
+    let mut kind: SID_NAME_USE = 0;
+    // SAFETY: `sid` is valid (see above); the name buffers and their lengths
+    // (in u16s) match, and every out-pointer is valid for the call.
+    if unsafe {
+        LookupAccountSidW(
+            std::ptr::null(),
+            sid,
+            name.as_mut_ptr(),
+            &mut name_len,
+            domain.as_mut_ptr(),
+            &mut domain_len,
+            &mut kind,
+        )
+    } == 0
+    {
+        return None;
+    }
+    let name = String::from_utf16_lossy(&name[..name_len as usize]);
+    let domain = String::from_utf16_lossy(&domain[..domain_len as usize]);
+    Some(if domain.is_empty() {
+        name
+    } else {
+        format!("{domain}\\{name}")
+    })
+}
+
 /// Connect to the daemon IPC endpoint as a client.
 pub async fn ipc_connect(endpoint: &IpcEndpoint) -> Result<IpcClientStream> {
     #[cfg(unix)]
@@ -432,6 +582,22 @@ mod tests {
         server.await.unwrap();
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_peer_reports_credentials() {
+        let ep = test_endpoint("peer-cred");
+        let mut listener = IpcListener::bind(&ep, 0o660).expect("bind");
+        let server = tokio::spawn(async move {
+            let (_stream, peer) = listener.accept_peer().await.expect("accept");
+            peer
+        });
+        let _client = ipc_connect(&ep).await.expect("connect");
+        let peer = server.await.unwrap();
+        let uid = peer.uid.expect("peer uid");
+        assert_eq!(peer.id, format!("uid:{uid}"));
+        assert!(peer.gid.is_some());
+    }
+
     #[tokio::test]
     async fn sequential_connections_are_accepted() {
         // Pins the windows next-instance pre-creation logic: a second client
@@ -490,6 +656,27 @@ mod tests {
         );
     }
 
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn pipe_peer_is_the_connecting_account() {
+        let ep = test_endpoint("peer-account");
+        let mut listener = IpcListener::bind(&ep, 0o660).expect("bind");
+        let server = tokio::spawn(async move {
+            let (_stream, peer) = listener.accept_peer().await.expect("accept");
+            peer
+        });
+        let _client = ipc_connect(&ep).await.expect("connect");
+        let peer = server.await.unwrap();
+        // The test connects to itself, so the account is our own.
+        let user = std::env::var("USERNAME").unwrap();
+        assert!(
+            peer.id.starts_with("user:") && peer.id.ends_with(&format!("\\{user}")),
+            "{}",
+            peer.id
+        );
+        assert_eq!(super::pipe_peer_id(None), "pipe:local");
+    }
+
     #[test]
     fn default_endpoint_shape() {
         let ep = IpcEndpoint::default_for_user();
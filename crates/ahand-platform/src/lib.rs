@@ -3,8 +3,9 @@
 //! All OS-conditional behavior lives here so the rest of the codebase stays
 //! `#[cfg]`-free. Each module documents the Unix and Windows semantics it
 //! guarantees; anything it cannot make equivalent is documented at the call
-//! site it serves.
+//! site it serves. [`frame`] is the IPC wire framing both binaries share.
 
+pub mod frame;
 pub mod ipc;
 pub mod paths;
 pub mod process;
@@ -33,6 +33,16 @@ pub fn configure_detached(cmd: &mut std::process::Command) {
     }
 }
 
+/// Start a command as the leader of a new process group, so
+/// [`terminate_group`] also reaches whatever it spawns. A no-op on Windows,
+/// where `terminate_group` kills the process tree instead.
+pub fn configure_group(cmd: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
 #[cfg(unix)]
 pub fn is_process_running(pid: u32) -> bool {
     // kill(pid, 0) probes existence without signaling. EPERM means it exists
@@ -73,6 +83,44 @@ pub fn terminate(pid: u32, mode: TerminateMode) -> Result<()> {
     Ok(())
 }
 
+/// Signal the process group led by `pid` (see [`configure_group`]).
+#[cfg(unix)]
+pub fn terminate_group(pid: u32, mode: TerminateMode) -> Result<()> {
+    let sig = match mode {
+        TerminateMode::Graceful => libc::SIGTERM,
+        TerminateMode::Force => libc::SIGKILL,
+    };
+    let r = unsafe { libc::kill(-(pid as libc::pid_t), sig) };
+    if r != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            return Err(e).context(format!("kill(-{pid})"));
+        }
+    }
+    Ok(())
+}
+
+/// Kill `pid` and its child processes (`taskkill /T`).
+#[cfg(windows)]
+pub fn terminate_group(pid: u32, mode: TerminateMode) -> Result<()> {
+    let mut cmd = std::process::Command::new("taskkill");
+    cmd.arg("/T");
+    if matches!(mode, TerminateMode::Force) {
+        cmd.arg("/F");
+    }
+    let output = cmd
+        .args(["/PID", &pid.to_string()])
+        .output()
+        .context("failed to run taskkill")?;
+    if !output.status.success() {
+        let msg = String::from_utf8_lossy(&output.stderr);
+        if !msg.contains("not found") && !msg.contains("128") {
+            anyhow::bail!("taskkill /T /PID {pid} failed: {msg}");
+        }
+    }
+    Ok(())
+}
+
 #[cfg(windows)]
 pub fn terminate(pid: u32, mode: TerminateMode) -> Result<()> {
     let mut cmd = std::process::Command::new("taskkill");
@@ -140,6 +188,36 @@ mod tests {
         }
     }
 
+    // A group kill also reaches the grandchild a shell leaves behind.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn terminate_group_kills_the_whole_group() {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "sleep 60 & echo $!; wait"]);
+        cmd.stdout(std::process::Stdio::piped());
+        configure_group(&mut cmd);
+        let mut child = cmd.spawn().expect("spawn shell");
+        let mut stdout = child.stdout.take().unwrap();
+        let mut line = String::new();
+        tokio::io::AsyncBufReadExt::read_line(
+            &mut tokio::io::BufReader::new(&mut stdout),
+            &mut line,
+        )
+        .await
+        .unwrap();
+        let grandchild: u32 = line.trim().parse().expect("grandchild pid");
+
+        terminate_group(child.id().unwrap(), TerminateMode::Force).expect("terminate group");
+        child.wait().await.unwrap();
+        for _ in 0..50 {
+            if !is_process_running(grandchild) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        panic!("grandchild {grandchild} still running after terminate_group");
+    }
+
     // ── Graceful terminate (#2) ───────────────────────────────────────────────
 
     // Unix: SIGTERM must actually stop the sleeper.
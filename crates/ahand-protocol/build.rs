@@ -7,14 +7,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=../../proto/ahand/v1/file_ops.proto");
     println!("cargo:rerun-if-changed=../../proto/ahand/v1/app_tool.proto");
     println!("cargo:rerun-if-changed=../../proto/ahand/v1");
-    prost_build::compile_protos(
-        &[
-            "../../proto/ahand/v1/envelope.proto",
-            "../../proto/ahand/v1/browser.proto",
-            "../../proto/ahand/v1/file_ops.proto",
-            "../../proto/ahand/v1/app_tool.proto",
-        ],
-        &["../../proto"],
-    )?;
+    // Serialize only: lets the daemon render payloads as JSON for its
+    // trace log. The wire format stays protobuf.
+    prost_build::Config::new()
+        .type_attribute(".", "#[derive(serde::Serialize)]")
+        .compile_protos(
+            &[
+                "../../proto/ahand/v1/envelope.proto",
+                "../../proto/ahand/v1/browser.proto",
+                "../../proto/ahand/v1/file_ops.proto",
+                "../../proto/ahand/v1/app_tool.proto",
+            ],
+            &["../../proto"],
+        )?;
     Ok(())
 }
@@ -0,0 +1,185 @@
+//! Envelope-level compression of job output.
+//!
+//! A peer that can read compressed envelopes lists [`ZSTD`] in
+//! `Hello.accepts`; the hub confirms with `HelloAccepted.compression`. After
+//! that, `JobEvent` stdout/stderr chunks of at least the configured size go
+//! out zstd-compressed with `Envelope.compressed` set. Everything else — and
+//! everything sent to a peer that didn't negotiate — stays as before.
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+use prost::Message;
+
+use crate::{Envelope, envelope, job_event};
+
+/// Codec name in `Hello.accepts` / `HelloAccepted.compression`.
+pub const ZSTD: &str = "zstd";
+
+/// Largest chunk [`decompress`] will inflate to.
+pub const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+/// zstd level: fast enough to keep up with a chatty job's output.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Encode `envelope`, compressing its job output chunk if that is at least
+/// `threshold` bytes and shrinks. `envelope` itself is left untouched so
+/// callers can still log the plain form.
+pub fn encode(envelope: &Envelope, threshold: usize) -> Vec<u8> {
+    let compressed = output_chunk(envelope)
+        .filter(|chunk| !envelope.compressed && chunk.len() >= threshold)
+        .and_then(|chunk| {
+            zstd::bulk::compress(chunk, ZSTD_LEVEL)
+                .ok()
+                .filter(|packed| packed.len() < chunk.len())
+        });
+    let Some(packed) = compressed else {
+        return envelope.encode_to_vec();
+    };
+    let mut copy = envelope.clone();
+    *output_chunk_mut(&mut copy).expect("chunk checked above") = packed;
+    copy.compressed = true;
+    copy.encode_to_vec()
+}
+
+/// Undo [`encode`]'s compression in place. A no-op for plain envelopes.
+pub fn decompress(envelope: &mut Envelope) -> Result<()> {
+    if !envelope.compressed {
+        return Ok(());
+    }
+    let chunk = output_chunk_mut(envelope).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            "compressed envelope without job output",
+        )
+    })?;
+    // Bound the output so a small chunk can't inflate without limit.
+    let mut out = Vec::new();
+    zstd::stream::read::Decoder::new(chunk.as_slice())?
+        .take(MAX_CHUNK_BYTES as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > MAX_CHUNK_BYTES {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "decompressed chunk too large",
+        ));
+    }
+    *chunk = out;
+    envelope.compressed = false;
+    Ok(())
+}
+
+fn output_chunk(envelope: &Envelope) -> Option<&Vec<u8>> {
+    match &envelope.payload {
+        Some(envelope::Payload::JobEvent(event)) => match &event.event {
+            Some(job_event::Event::StdoutChunk(chunk) | job_event::Event::StderrChunk(chunk)) => {
+                Some(chunk)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn output_chunk_mut(envelope: &mut Envelope) -> Option<&mut Vec<u8>> {
+    match &mut envelope.payload {
+        Some(envelope::Payload::JobEvent(event)) => match &mut event.event {
+            Some(job_event::Event::StdoutChunk(chunk) | job_event::Event::StderrChunk(chunk)) => {
+                Some(chunk)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobEvent;
+
+    fn output(event: job_event::Event) -> Envelope {
+        Envelope {
+            device_id: "dev-1".into(),
+            seq: 7,
+            payload: Some(envelope::Payload::JobEvent(JobEvent {
+                job_id: "job-1".into(),
+                event: Some(event),
+            })),
+            ..Default::default()
+        }
+    }
+
+    /// Build-log style output: repetitive, line-oriented text.
+    fn log_output(bytes: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(bytes);
+        let mut n = 0u64;
+        while out.len() < bytes {
+            out.extend_from_slice(
+                format!("[{n:>6}] Compiling crate-{} v0.1.0\n", n % 40).as_bytes(),
+            );
+            n += 1;
+        }
+        out.truncate(bytes);
+        out
+    }
+
+    #[test]
+    fn large_chunks_round_trip_byte_identically() {
+        for event in [
+            job_event::Event::StdoutChunk(log_output(64 * 1024)),
+            job_event::Event::StderrChunk(log_output(4096)),
+        ] {
+            let original = output(event);
+            let wire = encode(&original, 1024);
+            assert!(
+                wire.len() * 4 < original.encoded_len(),
+                "{} bytes",
+                wire.len()
+            );
+
+            let mut received = Envelope::decode(wire.as_slice()).unwrap();
+            assert!(received.compressed);
+            decompress(&mut received).unwrap();
+            assert_eq!(received, original);
+        }
+    }
+
+    #[test]
+    fn small_chunks_and_other_payloads_stay_plain() {
+        let small = output(job_event::Event::StdoutChunk(b"ok\n".to_vec()));
+        assert_eq!(encode(&small, 1024), small.encode_to_vec());
+
+        let progress = output(job_event::Event::Progress(50));
+        assert_eq!(encode(&progress, 0), progress.encode_to_vec());
+
+        // Incompressible output isn't worth the flag.
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        let random = output(job_event::Event::StdoutChunk(noise));
+        assert!(encode(&random, 1024) == random.encode_to_vec());
+    }
+
+    #[test]
+    fn malformed_compressed_envelopes_are_rejected() {
+        let mut bomb = output(job_event::Event::StdoutChunk(
+            zstd::bulk::compress(&vec![0u8; MAX_CHUNK_BYTES + 1], 1).unwrap(),
+        ));
+        bomb.compressed = true;
+        let err = decompress(&mut bomb).unwrap_err();
+        assert!(err.to_string().contains("decompressed chunk too large"));
+
+        let mut progress = output(job_event::Event::Progress(1));
+        progress.compressed = true;
+        assert_eq!(
+            decompress(&mut progress).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -6,6 +6,13 @@ pub mod ahand {
 
 pub use ahand::v1::*;
 
+pub mod compression;
+
+/// Major version of the IPC protocol between ahandctl and ahandd, sent in
+/// `Hello.protocol_version` and `HelloAccepted.protocol_version`. Bump it
+/// when an IPC change breaks older peers.
+pub const IPC_PROTOCOL_VERSION: u32 = 1;
+
 pub fn build_hello_auth_payload(
     device_id: &str,
     hello: &Hello,
@@ -27,13 +27,17 @@
 //! satisfied by adding an arm without writing the matching golden.
 
 use ahand_protocol::{
-    AppToolDescriptor, AppToolError, AppToolRequest, AppToolResponse, AppToolsUpdate,
-    ApprovalRequest, ApprovalResponse, BootstrapAuth, BrowserRequest, BrowserResponse, CancelJob,
-    Ed25519Auth, Envelope, FileRequest, FileResponse, Heartbeat, Hello, HelloAccepted,
-    HelloChallenge, JobEvent, JobFinished, JobRejected, JobRequest, PolicyQuery, PolicyState,
-    PolicyUpdate, RefusalContext, SessionMode, SessionQuery, SessionState, SetSessionMode,
-    StdinChunk, TerminalResize, UpdateCommand, UpdateState, UpdateStatus, UpdateSuggestion,
-    app_tool_response, envelope, hello, job_event,
+    Ack, AppToolDescriptor, AppToolError, AppToolRequest, AppToolResponse, AppToolsUpdate,
+    ApprovalBulkResponse, ApprovalBulkResult, ApprovalContext, ApprovalExpired, ApprovalRequest,
+    ApprovalResolveResult, ApprovalResolved, ApprovalResponse, BootstrapAuth, BrowserRequest,
+    BrowserResponse, CancelAll, CancelAllResult, CancelJob, ClearSession, ConnectionPhase,
+    ConnectionStatus, DaemonStatus, DaemonStatusQuery, Ed25519Auth, Envelope, FileRequest,
+    FileResponse, Heartbeat, Hello, HelloAccepted, HelloChallenge, JobEvent, JobFinished,
+    JobQueued, JobRejected, JobRequest, JobSubscribe, PendingApprovalsQuery, PendingApprovalsState,
+    Ping, PolicyCheckRequest, PolicyCheckResult, PolicyQuery, PolicyState, PolicyUpdate, Pong,
+    RefusalContext, SessionMode, SessionQuery, SessionState, SetPolicyPreset, SetSessionMode,
+    Shutdown, StdinChunk, Subscribe, TerminalResize, UpdateCommand, UpdateState, UpdateStatus,
+    UpdateSuggestion, app_tool_response, envelope, hello, job_event,
 };
 use prost::Message;
 use std::path::{Path, PathBuf};
@@ -57,6 +61,7 @@ fn base_envelope(payload: envelope::Payload) -> Envelope {
         seq: FX_SEQ,
         ack: FX_ACK,
         ts_ms: FX_TS_MS,
+        compressed: false,
         payload: Some(payload),
     }
 }
@@ -146,6 +151,11 @@ fn golden_hello_ed25519() {
         os: "linux".into(),
         capabilities: vec!["exec".into(), "browser".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: Some(hello::Auth::Ed25519(Ed25519Auth {
             public_key: vec![0x01; 32],
             signature: vec![0x02; 64],
@@ -163,6 +173,11 @@ fn golden_hello_bootstrap() {
         os: "linux".into(),
         capabilities: vec!["exec".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: Some(hello::Auth::Bootstrap(BootstrapAuth {
             bearer_token: "bootstrap-golden".into(),
             public_key: vec![0x03; 32],
@@ -185,6 +200,10 @@ fn golden_hello_accepted() {
             signature: vec![0x05; 64],
             release_notes: "bug fixes".into(),
         }),
+        daemon_version: String::new(),
+        protocol_version: 0,
+        capabilities: vec![],
+        compression: String::new(),
     }));
     assert_golden("hello_accepted", &env);
 }
@@ -205,6 +224,8 @@ fn golden_job_request() {
         env: env_map,
         timeout_ms: 30_000,
         interactive: false,
+        priority: 0,
+        output_cap_bytes: 0,
     }));
     assert_golden("job_request", &env);
 }
@@ -227,6 +248,20 @@ fn golden_job_event_stderr() {
     assert_golden("job_event_stderr", &env);
 }
 
+#[test]
+fn golden_job_event_stdout_compressed() {
+    // Locks down the `compressed` flag's tag; the chunk bytes are opaque
+    // here, so they needn't be real zstd output.
+    let env = Envelope {
+        compressed: true,
+        ..base_envelope(envelope::Payload::JobEvent(JobEvent {
+            job_id: FX_JOB_ID.into(),
+            event: Some(job_event::Event::StdoutChunk(b"\x28\xb5\x2f\xfd".to_vec())),
+        }))
+    };
+    assert_golden("job_event_stdout_compressed", &env);
+}
+
 #[test]
 fn golden_job_event_progress() {
     let env = base_envelope(envelope::Payload::JobEvent(JobEvent {
@@ -254,10 +289,22 @@ fn golden_job_finished() {
         job_id: FX_JOB_ID.into(),
         exit_code: 0,
         error: String::new(),
+        ..Default::default()
     }));
     assert_golden("job_finished", &env);
 }
 
+#[test]
+fn golden_job_queued() {
+    let env = base_envelope(envelope::Payload::JobQueued(JobQueued {
+        job_id: FX_JOB_ID.into(),
+        position: 3,
+        waited_ms: 2_500,
+        estimated_wait_ms: 40_000,
+    }));
+    assert_golden("job_queued", &env);
+}
+
 #[test]
 fn golden_job_rejected() {
     let env = base_envelope(envelope::Payload::JobRejected(JobRejected {
@@ -275,6 +322,72 @@ fn golden_cancel_job() {
     assert_golden("cancel_job", &env);
 }
 
+#[test]
+fn golden_job_subscribe() {
+    let env = base_envelope(envelope::Payload::JobSubscribe(JobSubscribe {
+        job_id: FX_JOB_ID.into(),
+        from_offset: 4096,
+    }));
+    assert_golden("job_subscribe", &env);
+}
+
+#[test]
+fn golden_shutdown() {
+    let env = base_envelope(envelope::Payload::Shutdown(Shutdown {
+        reason: "ahandd is shutting down".into(),
+    }));
+    assert_golden("shutdown", &env);
+}
+
+#[test]
+fn golden_ping() {
+    let env = base_envelope(envelope::Payload::Ping(Ping {}));
+    assert_golden("ping", &env);
+}
+
+#[test]
+fn golden_pong() {
+    let env = base_envelope(envelope::Payload::Pong(Pong {
+        daemon_version: "0.3.1".into(),
+        uptime_ms: 3_600_000,
+        connected_to_cloud: true,
+        active_jobs: 2,
+        cloud_url: "wss://eu.ahand.example/ws".into(),
+        cloud_failover: true,
+    }));
+    assert_golden("pong", &env);
+}
+
+#[test]
+fn golden_subscribe() {
+    let env = base_envelope(envelope::Payload::Subscribe(Subscribe {
+        topics: vec!["jobs".into(), "approvals".into()],
+    }));
+    assert_golden("subscribe", &env);
+}
+
+#[test]
+fn golden_standalone_ack() {
+    let env = base_envelope(envelope::Payload::StandaloneAck(Ack { ack: 42 }));
+    assert_golden("standalone_ack", &env);
+}
+
+#[test]
+fn golden_cancel_all() {
+    let env = base_envelope(envelope::Payload::CancelAll(CancelAll {
+        caller_uid: "uid:501".into(),
+    }));
+    assert_golden("cancel_all", &env);
+}
+
+#[test]
+fn golden_cancel_all_result() {
+    let env = base_envelope(envelope::Payload::CancelAllResult(CancelAllResult {
+        cancelled: 3,
+    }));
+    assert_golden("cancel_all_result", &env);
+}
+
 #[test]
 fn golden_approval_request() {
     let env = base_envelope(envelope::Payload::ApprovalRequest(ApprovalRequest {
@@ -291,6 +404,13 @@ fn golden_approval_request() {
             reason: "too risky".into(),
             refused_at_ms: 1_699_999_900_000,
         }],
+        previous_approvals: vec![ApprovalContext {
+            tool: "git".into(),
+            args: vec!["push".into(), "origin".into(), "dev".into()],
+            approved_at_ms: 1_699_999_800_000,
+        }],
+        replayed: false,
+        request_hash: String::new(),
     }));
     assert_golden("approval_request", &env);
 }
@@ -302,6 +422,8 @@ fn golden_approval_response() {
         approved: true,
         remember: false,
         reason: String::new(),
+        remember_ttl_secs: 0,
+        resolved_by: String::new(),
     }));
     assert_golden("approval_response", &env);
 }
@@ -320,10 +442,52 @@ fn golden_policy_state() {
         denied_paths: vec!["/etc".into()],
         allowed_domains: vec!["github.com".into()],
         approval_timeout_secs: 60,
+        ..Default::default()
     }));
     assert_golden("policy_state", &env);
 }
 
+#[test]
+fn golden_error() {
+    let env = base_envelope(envelope::Payload::Error(ahand_protocol::Error {
+        code: "policy.empty_entry".into(),
+        message: "add_allowed_tools: entries must not be empty".into(),
+        ref_msg_id: "policy-update-0".into(),
+    }));
+    assert_golden("error", &env);
+}
+
+#[test]
+fn golden_set_policy_preset() {
+    let env = base_envelope(envelope::Payload::SetPolicyPreset(SetPolicyPreset {
+        name: "developer".into(),
+        keep_approvals: true,
+    }));
+    assert_golden("set_policy_preset", &env);
+}
+
+#[test]
+fn golden_policy_check_request() {
+    let env = base_envelope(envelope::Payload::PolicyCheckRequest(PolicyCheckRequest {
+        tool: "curl".into(),
+        args: vec!["https://example.com".into()],
+        cwd: "/tmp".into(),
+        caller_uid: "uid:501".into(),
+    }));
+    assert_golden("policy_check_request", &env);
+}
+
+#[test]
+fn golden_policy_check_result() {
+    let env = base_envelope(envelope::Payload::PolicyCheckResult(PolicyCheckResult {
+        decision: "needs_approval".into(),
+        reason: "domain(s) example.com not in allowed domains".into(),
+        detected_domains: vec!["example.com".into()],
+        matched_rule: "allowed_domains".into(),
+    }));
+    assert_golden("policy_check_result", &env);
+}
+
 #[test]
 fn golden_policy_update() {
     let env = base_envelope(envelope::Payload::PolicyUpdate(PolicyUpdate {
@@ -336,16 +500,132 @@ fn golden_policy_update() {
         add_denied_paths: vec!["/var".into()],
         remove_denied_paths: vec!["/opt".into()],
         approval_timeout_secs: 120,
+        ..Default::default()
     }));
     assert_golden("policy_update", &env);
 }
 
+#[test]
+fn golden_clear_session() {
+    let env = base_envelope(envelope::Payload::ClearSession(ClearSession {
+        caller_uid: "ctl-4242".into(),
+    }));
+    assert_golden("clear_session", &env);
+}
+
+#[test]
+fn golden_pending_approvals_query() {
+    let env = base_envelope(envelope::Payload::PendingApprovalsQuery(
+        PendingApprovalsQuery {},
+    ));
+    assert_golden("pending_approvals_query", &env);
+}
+
+#[test]
+fn golden_pending_approvals_state() {
+    let env = base_envelope(envelope::Payload::PendingApprovalsState(
+        PendingApprovalsState {
+            requests: vec![ApprovalRequest {
+                job_id: "job-golden".into(),
+                tool: "git".into(),
+                args: vec!["push".into()],
+                reason: "not in allowed tools".into(),
+                expires_ms: 1_700_000_060_000,
+                caller_uid: "cloud".into(),
+                ..Default::default()
+            }],
+        },
+    ));
+    assert_golden("pending_approvals_state", &env);
+}
+
+#[test]
+fn golden_approval_expired() {
+    let env = base_envelope(envelope::Payload::ApprovalExpired(ApprovalExpired {
+        job_id: "job-golden".into(),
+    }));
+    assert_golden("approval_expired", &env);
+}
+
+#[test]
+fn golden_approval_resolve_result() {
+    let env = base_envelope(envelope::Payload::ApprovalResolveResult(
+        ApprovalResolveResult {
+            job_id: "job-golden".into(),
+            accepted: true,
+        },
+    ));
+    assert_golden("approval_resolve_result", &env);
+}
+
+#[test]
+fn golden_approval_bulk_response() {
+    let env = base_envelope(envelope::Payload::ApprovalBulkResponse(
+        ApprovalBulkResponse {
+            tool: "git".into(),
+            domain_pattern: "*.github.com".into(),
+            approved: true,
+            remember: false,
+        },
+    ));
+    assert_golden("approval_bulk_response", &env);
+}
+
+#[test]
+fn golden_approval_bulk_result() {
+    let env = base_envelope(envelope::Payload::ApprovalBulkResult(ApprovalBulkResult {
+        tool: "git".into(),
+        resolved: 3,
+    }));
+    assert_golden("approval_bulk_result", &env);
+}
+
+#[test]
+fn golden_approval_resolved() {
+    let env = base_envelope(envelope::Payload::ApprovalResolved(ApprovalResolved {
+        job_id: FX_JOB_ID.into(),
+        approved: true,
+        resolved_by: "uid:501".into(),
+    }));
+    assert_golden("approval_resolved", &env);
+}
+
+#[test]
+fn golden_daemon_status_query() {
+    let env = base_envelope(envelope::Payload::DaemonStatusQuery(DaemonStatusQuery {}));
+    assert_golden("daemon_status_query", &env);
+}
+
+#[test]
+fn golden_daemon_status() {
+    let env = base_envelope(envelope::Payload::DaemonStatus(DaemonStatus {
+        pending_approvals: 3,
+        oldest_pending_approval_age_ms: 3_240_000,
+        runs_gc: None,
+        connection: Some(ConnectionStatus {
+            phase: ConnectionPhase::Connected as i32,
+            url: "wss://hub.example.com/ws".into(),
+            failover: false,
+            since_ms: 1_700_000_000_000,
+            next_attempt_ms: 0,
+            last_inbound_ms: 1_700_000_030_000,
+            outbox_depth: 2,
+            dropped_chunks: 0,
+            send_timeouts: 0,
+        }),
+        gateways: vec![],
+        browser_sessions: vec![],
+    }));
+    assert_golden("daemon_status", &env);
+}
+
 #[test]
 fn golden_set_session_mode() {
     let env = base_envelope(envelope::Payload::SetSessionMode(SetSessionMode {
         caller_uid: "uid:501".into(),
         mode: SessionMode::Strict as i32,
         trust_timeout_mins: 30,
+        ..Default::default()
     }));
     assert_golden("set_session_mode", &env);
 }
@@ -357,6 +637,7 @@ fn golden_session_state() {
         mode: SessionMode::Trust as i32,
         trust_expires_ms: 1_700_003_600_000,
         trust_timeout_mins: 60,
+        ..Default::default()
     }));
     assert_golden("session_state", &env);
 }
@@ -445,6 +726,7 @@ fn golden_heartbeat() {
     let env = base_envelope(envelope::Payload::Heartbeat(Heartbeat {
         sent_at_ms: FX_TS_MS,
         daemon_version: "0.1.2".into(),
+        ..Default::default()
     }));
     assert_golden("heartbeat", &env);
 }
@@ -566,6 +848,29 @@ fn payload_fixture_name(p: &envelope::Payload) -> &'static str {
         AppToolsUpdate(_) => "app_tools_update",
         AppToolRequest(_) => "app_tool_request",
         AppToolResponse(_) => "app_tool_response",
+        JobSubscribe(_) => "job_subscribe",
+        Shutdown(_) => "shutdown",
+        Ping(_) => "ping",
+        Pong(_) => "pong",
+        Subscribe(_) => "subscribe",
+        StandaloneAck(_) => "standalone_ack",
+        CancelAll(_) => "cancel_all",
+        CancelAllResult(_) => "cancel_all_result",
+        JobQueued(_) => "job_queued",
+        PolicyCheckRequest(_) => "policy_check_request",
+        PolicyCheckResult(_) => "policy_check_result",
+        SetPolicyPreset(_) => "set_policy_preset",
+        Error(_) => "error",
+        ClearSession(_) => "clear_session",
+        PendingApprovalsQuery(_) => "pending_approvals_query",
+        PendingApprovalsState(_) => "pending_approvals_state",
+        ApprovalExpired(_) => "approval_expired",
+        ApprovalResolveResult(_) => "approval_resolve_result",
+        ApprovalBulkResponse(_) => "approval_bulk_response",
+        ApprovalBulkResult(_) => "approval_bulk_result",
+        DaemonStatusQuery(_) => "daemon_status_query",
+        DaemonStatus(_) => "daemon_status",
+        ApprovalResolved(_) => "approval_resolved",
     }
 }
 
@@ -611,6 +916,29 @@ fn every_payload_variant_has_a_fixture_file() {
         envelope::Payload::AppToolsUpdate(AppToolsUpdate::default()),
         envelope::Payload::AppToolRequest(AppToolRequest::default()),
         envelope::Payload::AppToolResponse(AppToolResponse::default()),
+        envelope::Payload::JobSubscribe(JobSubscribe::default()),
+        envelope::Payload::Shutdown(Shutdown::default()),
+        envelope::Payload::Ping(Ping {}),
+        envelope::Payload::Pong(Pong::default()),
+        envelope::Payload::Subscribe(Subscribe::default()),
+        envelope::Payload::StandaloneAck(Ack::default()),
+        envelope::Payload::CancelAll(CancelAll::default()),
+        envelope::Payload::CancelAllResult(CancelAllResult::default()),
+        envelope::Payload::JobQueued(JobQueued::default()),
+        envelope::Payload::PolicyCheckRequest(PolicyCheckRequest::default()),
+        envelope::Payload::PolicyCheckResult(PolicyCheckResult::default()),
+        envelope::Payload::SetPolicyPreset(SetPolicyPreset::default()),
+        envelope::Payload::Error(ahand_protocol::Error::default()),
+        envelope::Payload::ClearSession(ClearSession::default()),
+        envelope::Payload::PendingApprovalsQuery(PendingApprovalsQuery {}),
+        envelope::Payload::PendingApprovalsState(PendingApprovalsState::default()),
+        envelope::Payload::ApprovalExpired(ApprovalExpired::default()),
+        envelope::Payload::ApprovalResolveResult(ApprovalResolveResult::default()),
+        envelope::Payload::ApprovalBulkResponse(ApprovalBulkResponse::default()),
+        envelope::Payload::ApprovalBulkResult(ApprovalBulkResult::default()),
+        envelope::Payload::DaemonStatusQuery(DaemonStatusQuery {}),
+        envelope::Payload::DaemonStatus(DaemonStatus::default()),
+        envelope::Payload::ApprovalResolved(ApprovalResolved::default()),
     ];
 
     let mut missing: Vec<String> = Vec::new();
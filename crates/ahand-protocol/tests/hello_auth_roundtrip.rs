@@ -15,6 +15,11 @@ fn hello_auth_roundtrip() {
             os: "macos".into(),
             capabilities: vec!["exec".into()],
             last_ack: 7,
+            protocol_version: 0,
+            ipc_token: String::new(),
+            client_name: String::new(),
+            instance_id: String::new(),
+            accepts: vec![],
             auth: Some(hello::Auth::Ed25519(Ed25519Auth {
                 public_key: vec![1; 32],
                 signature: vec![2; 64],
@@ -53,6 +58,11 @@ fn hello_bootstrap_auth_roundtrip() {
             os: "macos".into(),
             capabilities: vec!["exec".into()],
             last_ack: 9,
+            protocol_version: 0,
+            ipc_token: String::new(),
+            client_name: String::new(),
+            instance_id: String::new(),
+            accepts: vec![],
             auth: Some(hello::Auth::Bootstrap(BootstrapAuth {
                 bearer_token: "token-456".into(),
                 public_key: vec![3; 32],
@@ -116,6 +126,7 @@ fn hello_accepted_roundtrip() {
             HelloAccepted {
                 auth_method: "ed25519".into(),
                 update_suggestion: None,
+                ..Default::default()
             },
         )),
         ..Default::default()
@@ -140,6 +151,11 @@ fn hello_auth_payload_is_canonical_for_known_input() {
         os: "macos".into(),
         capabilities: vec!["exec".into(), "browser".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let payload =
@@ -165,6 +181,11 @@ fn hello_auth_payload_changes_when_nonce_changes() {
         os: "macos".into(),
         capabilities: vec!["exec".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let first =
@@ -183,6 +204,11 @@ fn hello_auth_payload_changes_when_hostname_changes() {
         os: "macos".into(),
         capabilities: vec!["exec".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let mut second_hello = first_hello.clone();
@@ -212,6 +238,11 @@ fn hello_auth_payload_changes_when_last_ack_changes() {
         os: "macos".into(),
         capabilities: vec!["exec".into()],
         last_ack: 7,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     };
     let mut second_hello = first_hello.clone();
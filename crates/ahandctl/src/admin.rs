@@ -1,4 +1,6 @@
 use ahand_platform::process;
+use ahandd::redact::EnvRedactor;
+use ahandd::run_index::{BROWSER_RUNS_DIR, RunKind, RunQuery, RunRecord};
 use anyhow::{Context, Result};
 use serde::Serialize;
 use std::convert::Infallible;
@@ -21,6 +23,9 @@ struct StatusResponse {
     data_dir_size: u64,
     home_dir: String,
     bin_dir: String,
+    /// `None` when the daemon's IPC socket can't be reached.
+    pending_approvals: Option<u32>,
+    oldest_pending_approval_age_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -32,6 +37,9 @@ struct LogEntry {
     seq: u64,
     ack: u64,
     payload_type: String,
+    /// Payload summary or body, when the daemon traces them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,20 +49,21 @@ struct LogsResponse {
 }
 
 #[derive(Debug, Serialize)]
-struct RunEntry {
-    job_id: String,
-    created_at: u64,
+struct AuditResponse {
+    total: usize,
+    entries: Vec<ahandd::audit::AuditEntry>,
 }
 
 #[derive(Debug, Serialize)]
 struct RunsResponse {
     total: usize,
-    runs: Vec<RunEntry>,
+    runs: Vec<RunRecord>,
 }
 
 #[derive(Debug, Serialize)]
 struct RunDetail {
     job_id: String,
+    run: RunRecord,
     request: serde_json::Value,
     result: Option<serde_json::Value>,
     files: Vec<String>,
@@ -97,9 +106,12 @@ pub async fn serve(port: u16, config_path: Option<String>, no_open: bool) -> Res
             .or(config_get_route(token_arc.clone(), config_arc.clone()))
             .or(config_put_route(token_arc.clone(), config_arc.clone()))
             .or(logs_route(token_arc.clone()))
+            .or(audit_route(token_arc.clone()))
             .or(runs_list_route(token_arc.clone()))
-            .or(runs_get_route(token_arc.clone()))
-            .or(runs_file_route(token_arc.clone()))
+            .or(runs_get_route(token_arc.clone(), config_arc.clone()))
+            .or(browser_run_get_route(token_arc.clone(), config_arc.clone()))
+            .or(runs_export_route(token_arc.clone(), config_arc.clone()))
+            .or(runs_file_route(token_arc.clone(), config_arc.clone()))
             .or(browser_init_route(token_arc.clone())),
     );
 
@@ -292,6 +304,33 @@ fn logs_route(token: Arc<String>) -> impl Filter<Extract = impl Reply, Error = R
         )
 }
 
+fn audit_route(token: Arc<String>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("audit")
+        .and(warp::get())
+        .and(with_auth(token))
+        .and(warp::query::<std::collections::HashMap<String, String>>())
+        .and_then(
+            |query: std::collections::HashMap<String, String>| async move {
+                let limit = query
+                    .get("limit")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(50);
+                let offset = query
+                    .get("offset")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(0);
+
+                match get_audit(limit, offset).await {
+                    Ok(audit) => Ok::<_, Rejection>(warp::reply::json(&audit)),
+                    Err(e) => {
+                        eprintln!("Audit error: {}", e);
+                        Err(reject::reject())
+                    }
+                }
+            },
+        )
+}
+
 fn runs_list_route(
     token: Arc<String>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -310,7 +349,7 @@ fn runs_list_route(
                     .and_then(|s| s.parse::<usize>().ok())
                     .unwrap_or(0);
 
-                match list_runs(limit, offset).await {
+                match list_runs(&run_query(&query), limit, offset) {
                     Ok(runs) => Ok::<_, Rejection>(warp::reply::json(&runs)),
                     Err(e) => {
                         eprintln!("Runs list error: {}", e);
@@ -323,16 +362,86 @@ fn runs_list_route(
 
 fn runs_get_route(
     token: Arc<String>,
+    config_path: Arc<PathBuf>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("runs" / String)
         .and(warp::get())
         .and(with_auth(token))
-        .and_then(|job_id: String| async move {
-            match get_run_detail(&job_id).await {
-                Ok(detail) => Ok::<_, Rejection>(warp::reply::json(&detail)),
-                Err(e) => {
-                    eprintln!("Run detail error: {}", e);
-                    Err(reject::reject())
+        .and_then(move |job_id: String| {
+            let config_path = config_path.clone();
+            async move {
+                match get_run_detail(&job_id, &env_redactor(&config_path)).await {
+                    Ok(detail) => Ok::<_, Rejection>(warp::reply::json(&detail)),
+                    Err(e) => {
+                        eprintln!("Run detail error: {}", e);
+                        Err(reject::reject())
+                    }
+                }
+            }
+        })
+}
+
+/// Detail of a browser artifact record, whose job_id is
+/// `browser/<session_id>/<entry>`.
+fn browser_run_get_route(
+    token: Arc<String>,
+    config_path: Arc<PathBuf>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("runs" / "browser" / String / String)
+        .and(warp::get())
+        .and(with_auth(token))
+        .and_then(move |session_id: String, entry: String| {
+            let config_path = config_path.clone();
+            async move {
+                if !ahandd::store::is_valid_job_id(&session_id)
+                    || !ahandd::store::is_valid_job_id(&entry)
+                {
+                    return Err(reject::reject());
+                }
+                let job_id = format!("{BROWSER_RUNS_DIR}/{session_id}/{entry}");
+                match get_run_detail(&job_id, &env_redactor(&config_path)).await {
+                    Ok(detail) => Ok::<_, Rejection>(warp::reply::json(&detail)),
+                    Err(e) => {
+                        eprintln!("Run detail error: {}", e);
+                        Err(reject::reject())
+                    }
+                }
+            }
+        })
+}
+
+fn runs_export_route(
+    token: Arc<String>,
+    config_path: Arc<PathBuf>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("runs" / String / "export")
+        .and(warp::get())
+        .and(with_auth(token))
+        .and_then(move |job_id: String| {
+            let config_path = config_path.clone();
+            async move {
+                match export_run(&job_id, &env_redactor(&config_path)).await {
+                    Ok(export) => {
+                        let disposition = format!(
+                            "attachment; filename=\"job_{}.tar.gz\"",
+                            job_id.replace(['"', '\\'], "_")
+                        );
+                        let body = warp::hyper::Body::wrap_stream(export_stream(export));
+                        let reply = warp::reply::with_header(
+                            warp::reply::Response::new(body),
+                            "Content-Type",
+                            "application/gzip",
+                        );
+                        Ok::<_, Rejection>(warp::reply::with_header(
+                            reply,
+                            "Content-Disposition",
+                            disposition,
+                        ))
+                    }
+                    Err(e) => {
+                        eprintln!("Run export error: {}", e);
+                        Err(reject::reject())
+                    }
                 }
             }
         })
@@ -340,20 +449,24 @@ fn runs_get_route(
 
 fn runs_file_route(
     token: Arc<String>,
+    config_path: Arc<PathBuf>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("runs" / String / String)
         .and(warp::get())
         .and(with_auth(token))
-        .and_then(|job_id: String, filename: String| async move {
-            match get_run_file(&job_id, &filename).await {
-                Ok(content) => Ok::<_, Rejection>(warp::reply::with_header(
-                    content,
-                    "Content-Type",
-                    "text/plain; charset=utf-8",
-                )),
-                Err(e) => {
-                    eprintln!("Run file error: {}", e);
-                    Err(reject::reject())
+        .and_then(move |job_id: String, filename: String| {
+            let config_path = config_path.clone();
+            async move {
+                match get_run_file(&job_id, &filename, &env_redactor(&config_path)).await {
+                    Ok(content) => Ok::<_, Rejection>(warp::reply::with_header(
+                        content,
+                        "Content-Type",
+                        "text/plain; charset=utf-8",
+                    )),
+                    Err(e) => {
+                        eprintln!("Run file error: {}", e);
+                        Err(reject::reject())
+                    }
                 }
             }
         })
@@ -447,6 +560,15 @@ async fn get_status(config_path: &Path) -> Result<StatusResponse> {
     let home = dirs::home_dir().context("Failed to find home directory")?;
     let bin_dir = ahand_bin_dir(&home);
 
+    let daemon_status = if daemon_running {
+        let endpoint = ahandd::config::Config::load(config_path)
+            .map(|config| config.ipc_socket_path())
+            .unwrap_or_else(|_| ahand_platform::ipc::IpcEndpoint::default_for_user());
+        ahandctl::daemon::query_status(&endpoint).await.ok()
+    } else {
+        None
+    };
+
     Ok(StatusResponse {
         version: env!("CARGO_PKG_VERSION").to_string(),
         daemon_running,
@@ -456,6 +578,10 @@ async fn get_status(config_path: &Path) -> Result<StatusResponse> {
         data_dir_size,
         home_dir: home.display().to_string(),
         bin_dir: bin_dir.display().to_string(),
+        pending_approvals: daemon_status.as_ref().map(|s| s.pending_approvals),
+        oldest_pending_approval_age_ms: daemon_status
+            .as_ref()
+            .map(|s| s.oldest_pending_approval_age_ms),
     })
 }
 
@@ -494,26 +620,28 @@ async fn put_config(config_path: &Path, json_value: serde_json::Value) -> Result
 
 async fn get_logs(limit: usize, offset: usize) -> Result<LogsResponse> {
     let data_dir = get_data_dir()?;
-    let trace_file = data_dir.join("trace.jsonl");
 
-    if !trace_file.exists() {
-        return Ok(LogsResponse {
-            total: 0,
-            entries: vec![],
-        });
+    // Live file first, then trace.jsonl.1, .2, ... so offsets past the live
+    // file continue into the rotated archives.
+    let mut lines = Vec::new();
+    for path in ahandd::store::trace_files(&data_dir) {
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let mut file_lines: Vec<String> = content.lines().map(str::to_string).collect();
+        file_lines.reverse(); // Most recent first
+        lines.extend(file_lines);
     }
 
-    let content = tokio::fs::read_to_string(&trace_file).await?;
-    let mut lines: Vec<_> = content.lines().collect();
-    lines.reverse(); // Most recent first
-
     let total = lines.len();
     let entries: Vec<LogEntry> = lines
         .into_iter()
         .skip(offset)
         .take(limit)
         .filter_map(|line| {
-            serde_json::from_str::<serde_json::Value>(line)
+            serde_json::from_str::<serde_json::Value>(&line)
                 .ok()
                 .and_then(|v| {
                     Some(LogEntry {
@@ -523,7 +651,8 @@ async fn get_logs(limit: usize, offset: usize) -> Result<LogsResponse> {
                         msg_id: v.get("msg_id")?.as_str()?.to_string(),
                         seq: v.get("seq")?.as_u64()?,
                         ack: v.get("ack")?.as_u64()?,
-                        payload_type: v.get("payload")?.as_object()?.keys().next()?.to_string(),
+                        payload_type: payload_type(v.get("payload")?)?,
+                        body: v.get("body").cloned(),
                     })
                 })
         })
@@ -532,55 +661,83 @@ async fn get_logs(limit: usize, offset: usize) -> Result<LogsResponse> {
     Ok(LogsResponse { total, entries })
 }
 
-async fn list_runs(limit: usize, offset: usize) -> Result<RunsResponse> {
-    let data_dir = get_data_dir()?;
-    let runs_dir = data_dir.join("runs");
-
-    if !runs_dir.exists() {
-        return Ok(RunsResponse {
-            total: 0,
-            runs: vec![],
-        });
+/// The payload type of a trace line: a string, or the single key of the
+/// object older daemons wrote.
+fn payload_type(payload: &serde_json::Value) -> Option<String> {
+    match payload {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Object(map) => map.keys().next().cloned(),
+        _ => None,
     }
+}
 
-    let mut entries = tokio::fs::read_dir(&runs_dir).await?;
-    let mut runs = Vec::new();
+async fn get_audit(limit: usize, offset: usize) -> Result<AuditResponse> {
+    let audit_file = get_data_dir()?.join(ahandd::audit::AUDIT_FILE_NAME);
+    let mut entries =
+        tokio::task::spawn_blocking(move || ahandd::audit::read_tail(&audit_file, usize::MAX))
+            .await??;
+    entries.reverse(); // Most recent first
 
-    while let Some(entry) = entries.next_entry().await? {
-        if entry.file_type().await?.is_dir() {
-            let job_id = entry.file_name().to_string_lossy().to_string();
-            let metadata = entry.metadata().await?;
-            let created_at = metadata
-                .modified()?
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_millis() as u64;
+    let total = entries.len();
+    let entries = entries.into_iter().skip(offset).take(limit).collect();
+    Ok(AuditResponse { total, entries })
+}
 
-            runs.push(RunEntry { job_id, created_at });
-        }
+/// Build a run search from `/runs` query parameters: `tool` (substring),
+/// `caller`, `exit_code`, `failed=true`, and `since`/`until` in ms since the
+/// epoch. Empty or unparsable values are ignored.
+fn run_query(params: &std::collections::HashMap<String, String>) -> RunQuery {
+    let get = |key: &str| params.get(key).filter(|v| !v.is_empty());
+    RunQuery {
+        tool: get("tool").cloned(),
+        caller_uid: get("caller").cloned(),
+        exit_code: get("exit_code").and_then(|v| v.parse().ok()),
+        failed: get("failed").is_some_and(|v| v == "true" || v == "1"),
+        since_ms: get("since").and_then(|v| v.parse().ok()),
+        until_ms: get("until").and_then(|v| v.parse().ok()),
     }
+}
 
-    // Sort by created_at descending
-    runs.sort_by_key(|b| std::cmp::Reverse(b.created_at));
-
-    let total = runs.len();
-    let runs = runs.into_iter().skip(offset).take(limit).collect();
-
+fn list_runs(query: &RunQuery, limit: usize, offset: usize) -> Result<RunsResponse> {
+    let Some(index) = crate::runs::open_index()? else {
+        return Ok(RunsResponse {
+            total: 0,
+            runs: vec![],
+        });
+    };
+    let (total, runs) = index.search(query, limit, offset)?;
     Ok(RunsResponse { total, runs })
 }
 
-async fn get_run_detail(job_id: &str) -> Result<RunDetail> {
+/// Redactor for the daemon's configured `store.redact_env_patterns`. Runs
+/// written before redaction existed still hold plaintext env on disk, so
+/// request env is redacted again on the way out.
+pub(crate) fn env_redactor(config_path: &Path) -> EnvRedactor {
+    match ahandd::config::Config::load(config_path) {
+        Ok(config) => EnvRedactor::new(&config.store_config().redact_env_patterns),
+        Err(_) => EnvRedactor::default(),
+    }
+}
+
+async fn get_run_detail(job_id: &str, redactor: &EnvRedactor) -> Result<RunDetail> {
     let data_dir = get_data_dir()?;
     let run_dir = data_dir.join("runs").join(job_id);
 
-    if !run_dir.exists() {
+    let run = match crate::runs::open_index()? {
+        Some(index) => index.get_run(job_id)?,
+        None => None,
+    };
+    let Some(run) = run else {
         anyhow::bail!("Run not found: {}", job_id);
-    }
+    };
 
     // Read request.json
     let request_path = run_dir.join("request.json");
     let request: serde_json::Value = if request_path.exists() {
         let content = tokio::fs::read_to_string(&request_path).await?;
-        serde_json::from_str(&content)?
+        let mut request = serde_json::from_str(&content)?;
+        redactor.redact_request(&mut request);
+        request
     } else {
         serde_json::json!({})
     };
@@ -594,37 +751,108 @@ async fn get_run_detail(job_id: &str) -> Result<RunDetail> {
         None
     };
 
-    // List all files
-    let mut entries = tokio::fs::read_dir(&run_dir).await?;
+    // List all files. Compressed outputs are listed under their plain name;
+    // `get_run_file` decompresses them. A browser record's request and
+    // result are already in the detail, and its artifact lives elsewhere.
     let mut files = Vec::new();
-    while let Some(entry) = entries.next_entry().await? {
-        if entry.file_type().await?.is_file() {
-            files.push(entry.file_name().to_string_lossy().to_string());
+    if run.kind == RunKind::Exec {
+        let mut entries = tokio::fs::read_dir(&run_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                files.push(match name.as_str() {
+                    "stdout.gz" | "stderr.gz" => name.trim_end_matches(".gz").to_string(),
+                    _ => name,
+                });
+            }
         }
     }
 
     Ok(RunDetail {
         job_id: job_id.to_string(),
+        run,
         request,
         result,
         files,
     })
 }
 
-async fn get_run_file(job_id: &str, filename: &str) -> Result<String> {
-    let data_dir = get_data_dir()?;
-    let file_path = data_dir.join("runs").join(job_id).join(filename);
+/// A run tarball being streamed to the browser. Deleted once the response
+/// is done with it, whether or not the download finished.
+struct ExportFile {
+    file: Option<tokio::fs::File>,
+    path: PathBuf,
+}
 
-    if !file_path.exists() {
-        anyhow::bail!("File not found: {}/{}", job_id, filename);
+impl Drop for ExportFile {
+    fn drop(&mut self) {
+        // Close before removing; Windows can't delete an open file.
+        drop(self.file.take());
+        let _ = std::fs::remove_file(&self.path);
     }
+}
+
+async fn export_run(job_id: &str, redactor: &EnvRedactor) -> Result<ExportFile> {
+    let data_dir = get_data_dir()?;
+    let job_id = job_id.to_string();
+    let redactor = redactor.clone();
+    let path = tokio::task::spawn_blocking(move || {
+        ahandd::store::export_run(&data_dir, &job_id, &redactor)
+    })
+    .await??;
+    let file = tokio::fs::File::open(&path).await?;
+    Ok(ExportFile {
+        file: Some(file),
+        path,
+    })
+}
+
+fn export_stream(export: ExportFile) -> impl futures_util::Stream<Item = std::io::Result<Vec<u8>>> {
+    use tokio::io::AsyncReadExt;
+    futures_util::stream::unfold(Some(export), |export| async move {
+        let mut export = export?;
+        let mut buf = vec![0; 64 * 1024];
+        let read = export.file.as_mut()?.read(&mut buf).await;
+        match read {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), Some(export)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+async fn get_run_file(job_id: &str, filename: &str, redactor: &EnvRedactor) -> Result<String> {
+    let data_dir = get_data_dir()?;
+    let run_dir = data_dir.join("runs").join(job_id);
 
     // Security: ensure filename doesn't contain path traversal
     if filename.contains("..") || filename.contains('/') || filename.contains('\\') {
         anyhow::bail!("Invalid filename");
     }
 
+    if filename == "stdout" || filename == "stderr" {
+        let name = filename.to_string();
+        let content =
+            tokio::task::spawn_blocking(move || ahandd::store::read_run_output(&run_dir, &name))
+                .await?
+                .with_context(|| format!("File not found: {}/{}", job_id, filename))?;
+        return Ok(String::from_utf8_lossy(&content).into_owned());
+    }
+
+    let file_path = run_dir.join(filename);
+    if !file_path.exists() {
+        anyhow::bail!("File not found: {}/{}", job_id, filename);
+    }
+
     let content = tokio::fs::read_to_string(&file_path).await?;
+    if filename == "request.json" {
+        let mut request: serde_json::Value = serde_json::from_str(&content)?;
+        redactor.redact_request(&mut request);
+        return Ok(serde_json::to_string_pretty(&request)?);
+    }
     Ok(content)
 }
 
@@ -666,7 +894,7 @@ fn generate_token() -> String {
         .collect::<String>()
 }
 
-fn resolve_config_path(path: Option<String>) -> Result<PathBuf> {
+pub(crate) fn resolve_config_path(path: Option<String>) -> Result<PathBuf> {
     if let Some(p) = path {
         Ok(PathBuf::from(p))
     } else {
@@ -1002,6 +1230,28 @@ mod sse_adapter_tests {
     // -------------------------------------------------------------------------
 
     /// `ahand_bin_dir` appends `.ahand/bin` to the given home directory.
+    #[test]
+    fn run_query_reads_search_parameters() {
+        let params: std::collections::HashMap<String, String> = [
+            ("tool", "curl"),
+            ("failed", "true"),
+            ("since", "1700000000000"),
+            ("exit_code", ""),
+            ("until", "soon"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let query = run_query(&params);
+        assert_eq!(query.tool.as_deref(), Some("curl"));
+        assert!(query.failed);
+        assert_eq!(query.since_ms, Some(1_700_000_000_000));
+        assert_eq!(query.exit_code, None);
+        assert_eq!(query.until_ms, None);
+        assert_eq!(query.caller_uid, None);
+    }
+
     #[test]
     fn ahand_bin_dir_appends_dot_ahand_bin() {
         use std::path::Path;
@@ -1012,7 +1262,7 @@ mod sse_adapter_tests {
 
     // -------------------------------------------------------------------------
     // StatusResponse serde-contract test: the admin SPA reads these exact JSON
-    // keys (config_path, data_dir, home_dir, bin_dir). Pin the wire names so a
+    // keys (config_path, data_dir, home_dir, bin_dir, pending approvals). Pin the wire names so a
     // field rename/drop is caught here instead of silently breaking the panel.
     // -------------------------------------------------------------------------
 
@@ -1028,6 +1278,8 @@ mod sse_adapter_tests {
             data_dir_size: 1024,
             home_dir: "/home/alice".to_string(),
             bin_dir: "/home/alice/.ahand/bin".to_string(),
+            pending_approvals: Some(3),
+            oldest_pending_approval_age_ms: Some(3_240_000),
         };
 
         let value = serde_json::to_value(&resp).expect("StatusResponse must serialize");
@@ -1053,5 +1305,16 @@ mod sse_adapter_tests {
             Some("/home/alice/.ahand/bin"),
             "bin_dir key/value must match"
         );
+        assert_eq!(
+            obj.get("pending_approvals").and_then(|v| v.as_u64()),
+            Some(3),
+            "pending_approvals key/value must match"
+        );
+        assert_eq!(
+            obj.get("oldest_pending_approval_age_ms")
+                .and_then(|v| v.as_u64()),
+            Some(3_240_000),
+            "oldest_pending_approval_age_ms key/value must match"
+        );
     }
 }
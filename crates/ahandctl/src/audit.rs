@@ -0,0 +1,98 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ahandd::audit::{AUDIT_FILE_NAME, AuditEntry, read_tail};
+use anyhow::{Context, Result};
+
+/// How often `--follow` checks the audit log for new entries.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Print the last `lines` audit entries, then keep printing new ones if
+/// `follow` is set.
+pub async fn tail(lines: usize, follow: bool) -> Result<()> {
+    let path = audit_path()?;
+    for entry in
+        read_tail(&path, lines).with_context(|| format!("Failed to read {}", path.display()))?
+    {
+        println!("{}", format_entry(&entry));
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let mut partial = String::new();
+    loop {
+        tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+        let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if len < offset {
+            // Rotated: the live file starts over.
+            offset = 0;
+            partial.clear();
+        }
+        if len == offset {
+            continue;
+        }
+        partial.push_str(&read_from(&path, offset)?);
+        offset = len;
+        while let Some(end) = partial.find('\n') {
+            let line: String = partial.drain(..=end).collect();
+            if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) {
+                println!("{}", format_entry(&entry));
+            }
+        }
+    }
+}
+
+fn read_from(path: &Path, offset: u64) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = String::new();
+    file.read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+fn format_entry(entry: &AuditEntry) -> String {
+    let mut line = format!(
+        "{} {:<8} {:<14} caller={} job={} tool={}",
+        entry.ts_ms, entry.source, entry.decision, entry.caller_uid, entry.job_id, entry.tool
+    );
+    if let Some(rule) = &entry.rule {
+        line.push_str(&format!(" rule={rule}"));
+    }
+    if !entry.reason.is_empty() {
+        line.push_str(&format!(" reason={:?}", entry.reason));
+    }
+    line
+}
+
+fn audit_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to find home directory")?;
+    Ok(home.join(".ahand").join("data").join(AUDIT_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_entry_includes_rule_and_quoted_reason() {
+        let entry = AuditEntry {
+            ts_ms: 1_700_000_000_000,
+            source: "policy".to_string(),
+            caller_uid: "uid:501".to_string(),
+            job_id: "job-1".to_string(),
+            tool: "rm".to_string(),
+            args_hash: String::new(),
+            decision: "deny".to_string(),
+            reason: "tool \"rm\" is in the deny list".to_string(),
+            rule: Some("denied_tools".to_string()),
+        };
+        assert_eq!(
+            format_entry(&entry),
+            "1700000000000 policy   deny           caller=uid:501 job=job-1 tool=rm \
+             rule=denied_tools reason=\"tool \\\"rm\\\" is in the deny list\""
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use ahand_platform::ipc::IpcEndpoint;
 use ahand_platform::process::{self, TerminateMode};
+use ahand_protocol::{DaemonStatus, DaemonStatusQuery, Envelope, envelope};
 use anyhow::{Context, Result};
+use prost::Message;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long [`query_status`] waits for the daemon to answer.
+const STATUS_QUERY_TIMEOUT: Duration = Duration::from_secs(3);
 
 fn get_data_dir() -> Result<PathBuf> {
     let home = dirs::home_dir().context("Failed to find home directory")?;
@@ -55,7 +62,7 @@ pub(crate) fn find_ahandd_binary_in(
 }
 
 /// Read PID file and check if the process is still alive.
-fn read_running_pid() -> Result<Option<u32>> {
+pub fn read_running_pid() -> Result<Option<u32>> {
     let pid_path = get_pid_path()?;
     read_running_pid_at(&pid_path)
 }
@@ -193,12 +200,46 @@ pub async fn restart(config: Option<String>) -> Result<()> {
     start(config).await
 }
 
-pub async fn status() -> Result<()> {
+/// Print whether the daemon is running. Returns true if it is.
+pub async fn status() -> Result<bool> {
     match read_running_pid()? {
-        Some(pid) => println!("Daemon is running (PID {}).", pid),
-        None => println!("Daemon is not running."),
+        Some(pid) => {
+            println!("Daemon is running (PID {}).", pid);
+            Ok(true)
+        }
+        None => {
+            println!("Daemon is not running.");
+            Ok(false)
+        }
+    }
+}
+
+/// Ask the daemon listening on `endpoint` for a [`DaemonStatus`] snapshot.
+pub async fn query_status(endpoint: &IpcEndpoint) -> Result<DaemonStatus> {
+    tokio::time::timeout(STATUS_QUERY_TIMEOUT, query_status_inner(endpoint))
+        .await
+        .context("timed out waiting for daemon status")?
+}
+
+async fn query_status_inner(endpoint: &IpcEndpoint) -> Result<DaemonStatus> {
+    let target = crate::ipc::IpcTarget::Local(endpoint.clone());
+    let (mut reader, mut writer) = crate::ipc::connect(&target).await?;
+    let query = Envelope {
+        device_id: format!("ctl-{}", std::process::id()),
+        payload: Some(envelope::Payload::DaemonStatusQuery(DaemonStatusQuery {})),
+        ..Default::default()
+    };
+    crate::ipc::write_frame(&mut writer, &query.encode_to_vec()).await?;
+
+    loop {
+        let frame = crate::ipc::read_frame(&mut reader).await?;
+        // Broadcasts (e.g. replayed approval requests) can arrive first.
+        if let Some(envelope::Payload::DaemonStatus(status)) =
+            Envelope::decode(frame.as_slice())?.payload
+        {
+            return Ok(status);
+        }
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -0,0 +1,206 @@
+//! IPC connection to ahandd: length-prefixed protobuf frames over the
+//! daemon's socket (or its optional TCP listener), opened with a `Hello`
+//! handshake so a protocol mismatch fails with a clear message instead of
+//! silent decode errors.
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use ahand_platform::frame::{self, FrameCodec};
+use ahand_platform::ipc::{IpcEndpoint, ipc_connect};
+use ahand_protocol::{Envelope, Hello, IPC_PROTOCOL_VERSION, envelope};
+use anyhow::{Context, Result, anyhow};
+use prost::Message;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, BufReader, Chain, ReadHalf, WriteHalf};
+
+/// How long to wait for ahandd to accept the `Hello`. Daemons that
+/// predate the handshake never answer it.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Error code ahandd answers a `Hello` with when protocol major versions
+/// differ.
+pub const PROTOCOL_MISMATCH_CODE: &str = "ipc.protocol_mismatch";
+
+/// Where to reach ahandd's IPC server.
+#[derive(Debug, Clone)]
+pub enum IpcTarget {
+    /// The daemon's Unix socket (named pipe on Windows).
+    Local(IpcEndpoint),
+    /// The daemon's `ipc_tcp_listen` address, with the shared secret its
+    /// `Hello` needs when remote peers are allowed.
+    Tcp { addr: String, token: Option<String> },
+}
+
+/// A connection to either kind of IPC listener.
+pub trait IpcStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> IpcStream for S {}
+
+type ReadStream = BufReader<ReadHalf<Box<dyn IpcStream>>>;
+
+/// Read half of an IPC connection. A frame that a pre-handshake daemon sent
+/// while we waited for `HelloAccepted` is read again first.
+pub struct IpcReader {
+    inner: Chain<Cursor<Vec<u8>>, ReadStream>,
+    codec: FrameCodec,
+    /// What the daemon advertised in `HelloAccepted`; empty for a daemon
+    /// without the handshake.
+    pub daemon_capabilities: Vec<String>,
+}
+
+/// Write half of an IPC connection.
+pub struct IpcWriter {
+    inner: WriteHalf<Box<dyn IpcStream>>,
+    codec: FrameCodec,
+}
+
+/// The `Hello` ahandctl opens a connection with, over IPC or WebSocket.
+pub fn hello_envelope(device_id: &str) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: "hello-0".to_string(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::Hello(Hello {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: gethostname::gethostname().to_string_lossy().to_string(),
+            os: std::env::consts::OS.to_string(),
+            capabilities: vec!["ctl".to_string()],
+            last_ack: 0,
+            protocol_version: IPC_PROTOCOL_VERSION,
+            ipc_token: String::new(),
+            client_name: "ahandctl".to_string(),
+            instance_id: String::new(),
+            accepts: vec![],
+            auth: None,
+        })),
+        ..Default::default()
+    }
+}
+
+/// Connect to ahandd at `target` and complete the `Hello` handshake.
+pub async fn connect(target: &IpcTarget) -> Result<(IpcReader, IpcWriter)> {
+    let (stream, token): (Box<dyn IpcStream>, _) = match target {
+        IpcTarget::Local(endpoint) => {
+            let stream = ipc_connect(endpoint).await.context(
+                "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
+            )?;
+            (Box::new(stream), None)
+        }
+        IpcTarget::Tcp { addr, token } => {
+            let stream = tokio::net::TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("could not reach ahandd's IPC listener at {addr}"))?;
+            (Box::new(stream), token.as_deref())
+        }
+    };
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let mut hello = hello_envelope(&format!("ctl-{}", std::process::id()));
+    if let Some(envelope::Payload::Hello(h)) = &mut hello.payload {
+        h.ipc_token = token.unwrap_or_default().to_string();
+        h.accepts = vec![frame::ZSTD.to_string()];
+    }
+    frame::write_frame(&mut writer, &hello.encode_to_vec()).await?;
+
+    let mut codec = FrameCodec::Plain;
+    let mut daemon_capabilities = Vec::new();
+    let replay = match tokio::time::timeout(HELLO_TIMEOUT, frame::read_frame(&mut reader)).await {
+        // A daemon without the handshake that has nothing to push yet.
+        Err(_) => Vec::new(),
+        Ok(frame) => {
+            let frame = frame.context("ahandd closed the IPC connection during the handshake")?;
+            match Envelope::decode(frame.as_slice())?.payload {
+                Some(envelope::Payload::HelloAccepted(accepted)) => {
+                    tracing::debug!(
+                        daemon_version = %accepted.daemon_version,
+                        protocol_version = accepted.protocol_version,
+                        compression = %accepted.compression,
+                        "IPC handshake accepted"
+                    );
+                    codec = FrameCodec::from_name(&accepted.compression).ok_or_else(|| {
+                        anyhow!(
+                            "ahandd picked unknown frame compression {:?}",
+                            accepted.compression
+                        )
+                    })?;
+                    daemon_capabilities = accepted.capabilities;
+                    Vec::new()
+                }
+                Some(envelope::Payload::Error(err)) => return Err(handshake_error(&err)),
+                // A daemon without the handshake pushing something (e.g. a
+                // replayed approval request): hand it to the caller.
+                _ => {
+                    let mut replay = (frame.len() as u32).to_be_bytes().to_vec();
+                    replay.extend_from_slice(&frame);
+                    replay
+                }
+            }
+        }
+    };
+    Ok((
+        IpcReader {
+            inner: Cursor::new(replay).chain(reader),
+            codec,
+            daemon_capabilities,
+        },
+        IpcWriter {
+            inner: writer,
+            codec,
+        },
+    ))
+}
+
+/// The error to report when ahandd answers the `Hello` with `err`.
+fn handshake_error(err: &ahand_protocol::Error) -> anyhow::Error {
+    if err.code == PROTOCOL_MISMATCH_CODE {
+        anyhow!("{} — run `ahandctl upgrade`", err.message)
+    } else {
+        anyhow!("ahandd refused the IPC connection: {}", err.message)
+    }
+}
+
+/// Read one frame, decoded with the codec agreed in the handshake.
+pub async fn read_frame(reader: &mut IpcReader) -> std::io::Result<Vec<u8>> {
+    reader.codec.read_frame(&mut reader.inner).await
+}
+
+/// Write one frame, encoded with the codec agreed in the handshake.
+pub async fn write_frame(writer: &mut IpcWriter, data: &[u8]) -> std::io::Result<()> {
+    writer.codec.write_frame(&mut writer.inner, data).await
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hello_declares_the_ipc_protocol_version() {
+        let Some(envelope::Payload::Hello(hello)) = hello_envelope("ctl-1").payload else {
+            panic!("expected Hello");
+        };
+        assert_eq!(hello.protocol_version, IPC_PROTOCOL_VERSION);
+        assert_eq!(hello.capabilities, vec!["ctl"]);
+        assert_eq!(hello.client_name, "ahandctl");
+    }
+
+    #[test]
+    fn protocol_mismatch_suggests_an_upgrade() {
+        let err = handshake_error(&ahand_protocol::Error {
+            code: PROTOCOL_MISMATCH_CODE.to_string(),
+            message: "daemon speaks protocol 3, this client speaks 2".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(
+            err.to_string(),
+            "daemon speaks protocol 3, this client speaks 2 — run `ahandctl upgrade`"
+        );
+    }
+}
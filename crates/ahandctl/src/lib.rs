@@ -3,3 +3,6 @@ pub mod upgrade;
 
 /// Daemon lifecycle management (start / stop / restart / status).
 pub mod daemon;
+
+/// IPC connection to the daemon, with the protocol handshake.
+pub mod ipc;
@@ -1,18 +1,23 @@
 use ahand_protocol::{
-    ApprovalResponse, CancelJob, Envelope, Hello, JobRequest, PolicyQuery, PolicyUpdate,
-    SessionQuery, SetSessionMode, envelope,
+    ApprovalResponse, CancelAll, CancelJob, ClearSession, Envelope, JobRequest, JobSubscribe,
+    PendingApprovalsQuery, Ping, PolicyCheckRequest, PolicyQuery, PolicyUpdate, SessionQuery,
+    SetPolicyPreset, SetSessionMode, envelope,
 };
 use anyhow::Context as _;
 use clap::{Parser, Subcommand};
 use futures_util::{SinkExt, StreamExt};
 use prost::Message;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio_tungstenite::tungstenite;
 use tracing::info;
 
 mod admin;
+mod audit;
 mod browser_init;
+mod runs;
+mod watch;
 use ahandctl::daemon;
+use ahandctl::ipc::{self, read_frame, write_frame};
 use ahandctl::upgrade;
 
 #[derive(Parser)]
@@ -26,6 +31,14 @@ struct Args {
     #[arg(long)]
     ipc: Option<String>,
 
+    /// IPC over ahandd's TCP listener (`ipc_tcp_listen`), as an alternative to --ipc
+    #[arg(long, value_name = "HOST:PORT", conflicts_with = "ipc")]
+    ipc_tcp: Option<String>,
+
+    /// Shared secret for --ipc-tcp when ahandd allows remote peers (`ipc_tcp_token`)
+    #[arg(long, requires = "ipc_tcp")]
+    ipc_token: Option<String>,
+
     #[command(subcommand)]
     command: Cmd,
 }
@@ -34,20 +47,65 @@ struct Args {
 enum Cmd {
     /// Send a job and stream its output
     Exec {
+        /// Queue priority; higher runs sooner when jobs are waiting for a slot
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
         /// Tool to execute
         tool: String,
         /// Arguments to the tool
         args: Vec<String>,
     },
-    /// Cancel a running job
+    /// Stream the output of a job started elsewhere, exiting with its exit code
+    Attach {
+        /// Job ID to attach to
+        job_id: String,
+        /// Skip this many bytes of already-captured stdout and stderr
+        #[arg(long, default_value_t = 0)]
+        from_offset: u64,
+    },
+    /// Cancel a running job, or every running job with --all
     Cancel {
         /// Job ID to cancel
-        job_id: String,
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        job_id: Option<String>,
+        /// Cancel every running job
+        #[arg(long)]
+        all: bool,
+        /// With --all, only cancel jobs submitted by this caller (e.g. uid:501)
+        #[arg(long, requires = "all")]
+        caller: Option<String>,
     },
-    /// Ping the server (connect, send Hello, disconnect)
+    /// Ping the server (connect, send Hello, disconnect); over IPC, print
+    /// ahandd's health and capabilities or exit 3 if it doesn't answer
+    /// within 2s
     Ping,
-    /// Listen for approval requests and respond interactively
-    Approve,
+    /// Listen for approval requests and respond interactively, or answer
+    /// one pending request with --job-id
+    Approve {
+        /// Answer this pending job and exit (0 = answered, 2 = not pending)
+        #[arg(long)]
+        job_id: Option<String>,
+        /// Deny instead of approve
+        #[arg(long, requires = "job_id")]
+        deny: bool,
+        /// Remember the approval for future identical jobs
+        #[arg(long, requires = "job_id", conflicts_with = "deny")]
+        remember: bool,
+        /// With --remember, forget it again after this long (e.g. 1h, 24h)
+        #[arg(long, requires = "remember", value_parser = parse_ttl_arg)]
+        remember_for: Option<u64>,
+        /// Refusal reason passed back to the caller
+        #[arg(long, requires = "job_id")]
+        reason: Option<String>,
+    },
+    /// List approval requests still waiting for an answer
+    Approvals {
+        /// Print the requests as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(subcommand)]
+        action: Option<ApprovalsAction>,
+    },
     /// Query or update the daemon's policy
     Policy {
         #[command(subcommand)]
@@ -58,6 +116,12 @@ enum Cmd {
         #[command(subcommand)]
         action: SessionAction,
     },
+    /// Print daemon activity as it happens, whoever started it
+    Watch {
+        /// Comma-separated topics: jobs, jobs.output, approvals, sessions, policy
+        #[arg(long, value_delimiter = ',', default_value = "jobs,approvals")]
+        topics: Vec<String>,
+    },
     /// Start local admin panel HTTP server
     Configure {
         /// HTTP server port
@@ -100,7 +164,102 @@ enum Cmd {
         config: Option<String>,
     },
     /// Show daemon status
-    Status,
+    Status {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read the policy/session decision audit log
+    Audit {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+    /// List job runs recorded by the daemon, newest first
+    Runs {
+        /// Number of runs to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// Skip this many of the newest runs
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Only runs whose tool name contains this
+        #[arg(long)]
+        tool: Option<String>,
+        /// Only runs submitted by this caller (e.g. uid:501)
+        #[arg(long)]
+        caller: Option<String>,
+        /// Only runs that exited with this code
+        #[arg(long, allow_negative_numbers = true, conflicts_with = "failed")]
+        exit_code: Option<i32>,
+        /// Only runs that exited with a non-zero code
+        #[arg(long)]
+        failed: bool,
+        /// Only runs started within this long ago (e.g. 30m, 24h, 7d)
+        #[arg(long, value_parser = parse_ttl_arg)]
+        since: Option<u64>,
+        /// Only runs started more than this long ago
+        #[arg(long, value_parser = parse_ttl_arg)]
+        until: Option<u64>,
+        /// Print the runs as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(subcommand)]
+        action: Option<RunsAction>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RunsAction {
+    /// Show one run (exit 2 if it isn't recorded)
+    Show {
+        /// Job ID of the run
+        job_id: String,
+        /// Also print the run's stdout and stderr
+        #[arg(long, conflicts_with = "json")]
+        output: bool,
+    },
+    /// Pack one run into a .tar.gz for sharing (env secrets redacted)
+    Export {
+        /// Job ID of the run
+        job_id: String,
+        /// Where to write the tarball (default: ./job_<id>.tar.gz)
+        #[arg(short = 'o', long)]
+        output: Option<std::path::PathBuf>,
+        /// Daemon config file whose redaction patterns apply (defaults to
+        /// ~/.ahand/config.toml)
+        #[arg(long)]
+        config: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditAction {
+    /// Print the most recent audit entries
+    Tail {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing new entries as they are written
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ApprovalsAction {
+    /// Approve every pending request for a tool and exit (0 = at least one
+    /// approved, 2 = none pending)
+    ApproveAll {
+        /// Tool whose pending requests to approve (e.g. git)
+        #[arg(long)]
+        tool: String,
+        /// Only requests reaching a matching domain (e.g. *.github.com)
+        #[arg(long)]
+        domain: Option<String>,
+        /// Remember the approval for future identical jobs
+        #[arg(long)]
+        remember: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -137,11 +296,56 @@ enum PolicyAction {
         /// Domain names to remove from allowlist
         domains: Vec<String>,
     },
+    /// Add domains to the denylist (supports *.example.com)
+    DenyDomain {
+        /// Domain names or patterns to deny
+        domains: Vec<String>,
+    },
+    /// Remove domains from the denylist
+    UndenyDomain {
+        /// Domain names or patterns to remove from denylist
+        domains: Vec<String>,
+    },
     /// Set approval timeout in seconds
     SetTimeout {
         /// Timeout in seconds (0 = no change)
         seconds: u64,
     },
+    /// Refill every caller's job rate-limit bucket
+    ResetRateLimits,
+    /// List remembered approvals
+    Remembered,
+    /// Revoke a remembered approval
+    Forget {
+        /// Caller the approval was remembered for (e.g. uid:501)
+        caller: String,
+        /// Approval key (e.g. tool:git or domain:github.com)
+        key: String,
+    },
+    /// List available policy presets
+    Presets,
+    /// Replace the policy with a named preset
+    Preset {
+        /// Preset name (see `ahandctl policy presets`)
+        name: String,
+        /// Keep remembered approvals instead of clearing them
+        #[arg(long)]
+        keep_approvals: bool,
+    },
+    /// Dry-run a command against the session mode and policy without running it
+    Check {
+        /// Working directory the command would run in
+        #[arg(long, default_value = "")]
+        cwd: String,
+        /// Caller to evaluate as (default: this connection)
+        #[arg(long, default_value = "")]
+        caller: String,
+        /// Tool to check (e.g. curl)
+        tool: String,
+        /// Tool arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -154,7 +358,7 @@ enum SessionAction {
     },
     /// Set session mode for a caller
     Set {
-        /// Session mode: inactive, strict, trust, auto_accept
+        /// Session mode: inactive, strict, trust, auto_accept, read_only
         mode: String,
         /// Caller UID
         #[arg(long, default_value = "cloud")]
@@ -162,6 +366,16 @@ enum SessionAction {
         /// Trust timeout in minutes (only for trust mode)
         #[arg(long, default_value = "0")]
         timeout: u64,
+        /// Revert after this many jobs (only for trust mode, 0 = no limit)
+        #[arg(long, default_value = "0")]
+        jobs: u32,
+    },
+    /// Print session changes as they happen (IPC only)
+    Watch,
+    /// Forget a caller's session and refusal history
+    Clear {
+        /// Caller UID
+        caller: String,
     },
 }
 
@@ -205,36 +419,137 @@ async fn main() -> anyhow::Result<()> {
         Cmd::Restart { config } => {
             return daemon::restart(config.clone()).await;
         }
-        Cmd::Status => {
-            return daemon::status().await;
+        Cmd::Status { json: true } => {
+            return print_daemon_status_json(args.ipc.as_deref()).await;
+        }
+        Cmd::Status { json: false } => {
+            if daemon::status().await? {
+                print_daemon_status(args.ipc.as_deref()).await;
+            }
+            return Ok(());
+        }
+        Cmd::Audit {
+            action: AuditAction::Tail { lines, follow },
+        } => {
+            return audit::tail(*lines, *follow).await;
+        }
+        Cmd::Runs {
+            action: Some(RunsAction::Show { job_id, output }),
+            json,
+            ..
+        } => {
+            return runs::show(job_id, *json, *output);
+        }
+        Cmd::Runs {
+            action:
+                Some(RunsAction::Export {
+                    job_id,
+                    output,
+                    config,
+                }),
+            ..
+        } => {
+            let redactor = admin::env_redactor(&admin::resolve_config_path(config.clone())?);
+            return runs::export(job_id, output.clone(), &redactor);
+        }
+        Cmd::Runs {
+            limit,
+            offset,
+            tool,
+            caller,
+            exit_code,
+            failed,
+            since,
+            until,
+            json,
+            action: None,
+        } => {
+            let ago_ms = |secs: &u64| now_ms().saturating_sub(secs * 1000);
+            let query = ahandd::run_index::RunQuery {
+                tool: tool.clone(),
+                caller_uid: caller.clone(),
+                exit_code: *exit_code,
+                failed: *failed,
+                since_ms: since.as_ref().map(ago_ms),
+                until_ms: until.as_ref().map(ago_ms),
+            };
+            return runs::list(&query, *limit, *offset, *json);
         }
         _ => {}
     }
 
-    if let Some(ipc_path) = &args.ipc {
+    let ipc_target = match (&args.ipc, &args.ipc_tcp) {
+        (Some(path), _) => Some(ipc::IpcTarget::Local(
+            ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(path)),
+        )),
+        (None, Some(addr)) => Some(ipc::IpcTarget::Tcp {
+            addr: addr.clone(),
+            token: args.ipc_token.clone(),
+        }),
+        (None, None) => None,
+    };
+    if let Some(target) = &ipc_target {
         // IPC mode — connect via Unix socket.
         match args.command {
             Cmd::Exec {
+                priority,
                 tool,
                 args: tool_args,
             } => {
-                ipc_exec(ipc_path, &tool, &tool_args).await?;
+                ipc_exec(target, &tool, &tool_args, priority).await?;
+            }
+            Cmd::Attach {
+                job_id,
+                from_offset,
+            } => {
+                ipc_attach(target, &job_id, from_offset).await?;
             }
-            Cmd::Cancel { job_id } => {
-                ipc_cancel(ipc_path, &job_id).await?;
+            Cmd::Cancel {
+                job_id: Some(job_id),
+                ..
+            } => {
+                ipc_cancel(target, &job_id).await?;
+            }
+            Cmd::Cancel { caller, .. } => {
+                ipc_cancel_all(target, caller.as_deref()).await?;
             }
             Cmd::Ping => {
-                eprintln!("Ping is not supported in IPC mode");
-                std::process::exit(1);
+                ipc_ping(target).await;
+            }
+            Cmd::Approve {
+                job_id: Some(job_id),
+                deny,
+                remember,
+                remember_for,
+                reason,
+            } => {
+                ipc_approve_job(target, &job_id, !deny, remember, remember_for, reason).await?;
             }
-            Cmd::Approve => {
-                ipc_approve(ipc_path).await?;
+            Cmd::Approve { .. } => {
+                ipc_approve(target).await?;
+            }
+            Cmd::Approvals {
+                action:
+                    Some(ApprovalsAction::ApproveAll {
+                        tool,
+                        domain,
+                        remember,
+                    }),
+                ..
+            } => {
+                ipc_approve_all(target, &tool, domain.unwrap_or_default(), remember).await?;
+            }
+            Cmd::Approvals { json, .. } => {
+                ipc_approvals(target, json).await?;
             }
             Cmd::Policy { action } => {
-                ipc_policy(ipc_path, action).await?;
+                ipc_policy(target, action).await?;
             }
             Cmd::Session { action } => {
-                ipc_session(ipc_path, action).await?;
+                ipc_session(target, action).await?;
+            }
+            Cmd::Watch { topics } => {
+                watch::run(target, &topics).await?;
             }
             Cmd::Configure { .. }
             | Cmd::BrowserInit { .. }
@@ -242,7 +557,9 @@ async fn main() -> anyhow::Result<()> {
             | Cmd::Start { .. }
             | Cmd::Stop
             | Cmd::Restart { .. }
-            | Cmd::Status => {
+            | Cmd::Status { .. }
+            | Cmd::Audit { .. }
+            | Cmd::Runs { .. } => {
                 unreachable!("Handled early, should not reach here");
             }
         }
@@ -250,34 +567,68 @@ async fn main() -> anyhow::Result<()> {
         // WS mode.
         match args.command {
             Cmd::Exec {
+                priority,
                 tool,
                 args: tool_args,
             } => {
-                ws_exec(&args.url, &tool, &tool_args).await?;
+                ws_exec(&args.url, &tool, &tool_args, priority).await?;
             }
-            Cmd::Cancel { job_id } => {
+            Cmd::Attach { .. } => {
+                eprintln!("Attach is only supported in IPC mode (use --ipc <socket>)");
+                std::process::exit(1);
+            }
+            Cmd::Cancel {
+                job_id: Some(job_id),
+                ..
+            } => {
                 ws_cancel(&args.url, &job_id).await?;
             }
+            Cmd::Cancel { caller, .. } => {
+                ws_cancel_all(&args.url, caller.as_deref()).await?;
+            }
             Cmd::Ping => {
                 ws_ping(&args.url).await?;
             }
-            Cmd::Approve => {
+            Cmd::Approve { .. } => {
                 eprintln!("Approve is only supported in IPC mode (use --ipc <socket>)");
                 std::process::exit(1);
             }
+            Cmd::Approvals {
+                action: Some(_), ..
+            } => {
+                eprintln!(
+                    "approvals approve-all is only supported in IPC mode (use --ipc <socket>)"
+                );
+                std::process::exit(1);
+            }
+            Cmd::Approvals { json, .. } => {
+                ws_approvals(&args.url, json).await?;
+            }
             Cmd::Policy { action } => {
                 ws_policy(&args.url, action).await?;
             }
+            Cmd::Session {
+                action: SessionAction::Watch,
+            } => {
+                eprintln!("Session watch is only supported in IPC mode (use --ipc <socket>)");
+                std::process::exit(1);
+            }
             Cmd::Session { action } => {
                 ws_session(&args.url, action).await?;
             }
+            Cmd::Watch { .. } => {
+                eprintln!("Watch is only supported in IPC mode (use --ipc <socket>)");
+                std::process::exit(1);
+            }
             Cmd::Configure { .. }
             | Cmd::BrowserInit { .. }
             | Cmd::Upgrade { .. }
             | Cmd::Start { .. }
             | Cmd::Stop
             | Cmd::Restart { .. }
-            | Cmd::Status => {
+            | Cmd::Status { .. }
+            | Cmd::Audit { .. }
+            | Cmd::Runs { .. } => {
                 unreachable!("Handled early, should not reach here");
             }
         }
@@ -286,37 +637,15 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-// ── IPC frame helpers ────────────────────────────────────────────────
-
-async fn read_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> std::io::Result<Vec<u8>> {
-    let len = reader.read_u32().await? as usize;
-    if len > 16 * 1024 * 1024 {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "frame too large",
-        ));
-    }
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf).await?;
-    Ok(buf)
-}
-
-async fn write_frame<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) -> std::io::Result<()> {
-    writer.write_u32(data.len() as u32).await?;
-    writer.write_all(data).await?;
-    writer.flush().await?;
-    Ok(())
-}
-
 // ── IPC exec ─────────────────────────────────────────────────────────
 
-async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result<()> {
-    let endpoint = ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(ipc_path));
-    let stream = ahand_platform::ipc::ipc_connect(&endpoint).await.context(
-        "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
-    )?;
-    let (mut reader, mut writer) = tokio::io::split(stream);
-    let mut reader = tokio::io::BufReader::new(&mut reader);
+async fn ipc_exec(
+    target: &ipc::IpcTarget,
+    tool: &str,
+    args: &[String],
+    priority: i32,
+) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
 
     let device_id = format!("ctl-{}", std::process::id());
     let job_id = format!("ctl-job-{}", std::process::id());
@@ -330,6 +659,7 @@ async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result
             job_id: job_id.clone(),
             tool: tool.to_string(),
             args: args.to_vec(),
+            priority,
             ..Default::default()
         })),
         ..Default::default()
@@ -338,9 +668,40 @@ async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result
 
     info!(job_id = %job_id, "IPC: job submitted, waiting for output...");
 
-    // Read responses.
+    ipc_follow_job(&mut reader, &job_id, "req-0").await
+}
+
+// ── IPC attach ───────────────────────────────────────────────────────
+
+async fn ipc_attach(target: &ipc::IpcTarget, job_id: &str, from_offset: u64) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let sub = Envelope {
+        device_id: format!("ctl-{}", std::process::id()),
+        msg_id: "subscribe-0".to_string(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobSubscribe(JobSubscribe {
+            job_id: job_id.to_string(),
+            from_offset,
+        })),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &sub.encode_to_vec()).await?;
+
+    info!(job_id = %job_id, from_offset, "IPC: attached, waiting for output...");
+
+    ipc_follow_job(&mut reader, job_id, "subscribe-0").await
+}
+
+/// Print `job_id`'s output until it finishes, then exit with its exit
+/// code. An `Error` answering `msg_id` ends the process with exit code 1.
+async fn ipc_follow_job(
+    reader: &mut ipc::IpcReader,
+    job_id: &str,
+    msg_id: &str,
+) -> anyhow::Result<()> {
     loop {
-        let data = match read_frame(&mut reader).await {
+        let data = match read_frame(reader).await {
             Ok(d) => d,
             Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
             Err(e) => return Err(e.into()),
@@ -349,6 +710,14 @@ async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result
         let envelope = Envelope::decode(data.as_slice())?;
 
         match envelope.payload {
+            Some(envelope::Payload::Error(err)) if err.ref_msg_id == msg_id => {
+                eprintln!("[error] {}", err.message);
+                std::process::exit(1);
+            }
+            Some(envelope::Payload::Shutdown(shutdown)) => {
+                eprintln!("[shutdown] {}", shutdown.reason);
+                std::process::exit(1);
+            }
             Some(envelope::Payload::JobEvent(ev)) => {
                 if ev.job_id != job_id {
                     continue;
@@ -368,14 +737,24 @@ async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result
                     None => {}
                 }
             }
+            Some(envelope::Payload::JobQueued(queued)) => {
+                if queued.job_id != job_id {
+                    continue;
+                }
+                eprintln!("[queued] {}", format_queued(&queued));
+            }
             Some(envelope::Payload::JobFinished(fin)) => {
                 if fin.job_id != job_id {
                     continue;
                 }
+                let timing = format_timing(fin.duration_ms, fin.queued_ms);
                 if fin.error.is_empty() {
-                    eprintln!("[finished] exit_code={}", fin.exit_code);
+                    eprintln!("[finished] exit_code={} {timing}", fin.exit_code);
                 } else {
-                    eprintln!("[finished] exit_code={} error={}", fin.exit_code, fin.error);
+                    eprintln!(
+                        "[finished] exit_code={} {timing} error={}",
+                        fin.exit_code, fin.error
+                    );
                 }
                 std::process::exit(fin.exit_code);
             }
@@ -407,13 +786,8 @@ async fn ipc_exec(ipc_path: &str, tool: &str, args: &[String]) -> anyhow::Result
 
 // ── IPC cancel ───────────────────────────────────────────────────────
 
-async fn ipc_cancel(ipc_path: &str, job_id: &str) -> anyhow::Result<()> {
-    let endpoint = ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(ipc_path));
-    let stream = ahand_platform::ipc::ipc_connect(&endpoint).await.context(
-        "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
-    )?;
-    let (mut reader, mut writer) = tokio::io::split(stream);
-    let mut reader = tokio::io::BufReader::new(&mut reader);
+async fn ipc_cancel(target: &ipc::IpcTarget, job_id: &str) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
 
     let device_id = format!("ctl-{}", std::process::id());
 
@@ -455,6 +829,115 @@ async fn ipc_cancel(ipc_path: &str, job_id: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn ipc_cancel_all(target: &ipc::IpcTarget, caller: Option<&str>) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let device_id = format!("ctl-{}", std::process::id());
+    let cancel_env = build_cancel_all_envelope(&device_id, caller);
+
+    write_frame(&mut writer, &cancel_env.encode_to_vec()).await?;
+
+    // Wait for the CancelAllResult reply.
+    loop {
+        let data = match read_frame(&mut reader).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let envelope = Envelope::decode(data.as_slice())?;
+
+        if let Some(envelope::Payload::CancelAllResult(result)) = envelope.payload {
+            eprintln!("[cancel] cancelled {} job(s)", result.cancelled);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn build_cancel_all_envelope(device_id: &str, caller: Option<&str>) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: "cancel-all-0".to_string(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::CancelAll(CancelAll {
+            caller_uid: caller.unwrap_or_default().to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+// ── IPC ping ─────────────────────────────────────────────────────────
+
+/// How long `ahandctl ping` waits for ahandd's `Pong`.
+const PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Print ahandd's `Pong`, or exit 3 if it doesn't answer in time.
+async fn ipc_ping(target: &ipc::IpcTarget) {
+    match tokio::time::timeout(PING_TIMEOUT, request_pong(target)).await {
+        Ok(Ok((pong, capabilities))) => {
+            println!("{}", format_pong(&pong));
+            if !capabilities.is_empty() {
+                println!("capabilities: {}", capabilities.join(", "));
+            }
+        }
+        Ok(Err(e)) => {
+            eprintln!("daemon not responding: {e:#}");
+            std::process::exit(3);
+        }
+        Err(_) => {
+            eprintln!("daemon not responding");
+            std::process::exit(3);
+        }
+    }
+}
+
+/// Ping the daemon; returns its `Pong` and the capabilities it advertised
+/// in the handshake.
+async fn request_pong(
+    target: &ipc::IpcTarget,
+) -> anyhow::Result<(ahand_protocol::Pong, Vec<String>)> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let ping_env = Envelope {
+        device_id: format!("ctl-{}", std::process::id()),
+        msg_id: "ping-0".to_string(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::Ping(Ping {})),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &ping_env.encode_to_vec()).await?;
+
+    // Skip anything pushed before the reply, e.g. replayed approvals.
+    loop {
+        let data = read_frame(&mut reader).await?;
+        if let Some(envelope::Payload::Pong(pong)) = Envelope::decode(data.as_slice())?.payload {
+            return Ok((pong, std::mem::take(&mut reader.daemon_capabilities)));
+        }
+    }
+}
+
+/// Render e.g. `ahandd 0.3.1 up 2h 5m, cloud connected to
+/// wss://eu.ahand.example/ws (primary down), 2 active jobs`.
+fn format_pong(pong: &ahand_protocol::Pong) -> String {
+    let cloud = match (pong.connected_to_cloud, pong.cloud_url.as_str()) {
+        (false, _) => "disconnected".to_string(),
+        // Daemons from before failover don't report the URL.
+        (true, "") => "connected".to_string(),
+        (true, url) if pong.cloud_failover => format!("connected to {url} (primary down)"),
+        (true, url) => format!("connected to {url}"),
+    };
+    format!(
+        "ahandd {} up {}, cloud {}, {} active job{}",
+        pong.daemon_version,
+        humanize_duration(pong.uptime_ms / 1000),
+        cloud,
+        pong.active_jobs,
+        if pong.active_jobs == 1 { "" } else { "s" }
+    )
+}
+
 // ── WS functions (existing) ──────────────────────────────────────────
 
 async fn connect_and_hello(
@@ -478,20 +961,7 @@ async fn connect_and_hello(
 
     let device_id = format!("ctl-{}", std::process::id());
 
-    let hello = Envelope {
-        device_id: device_id.clone(),
-        msg_id: "hello-0".to_string(),
-        ts_ms: now_ms(),
-        payload: Some(envelope::Payload::Hello(Hello {
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            hostname: gethostname::gethostname().to_string_lossy().to_string(),
-            os: std::env::consts::OS.to_string(),
-            capabilities: vec!["ctl".to_string()],
-            last_ack: 0,
-            auth: None,
-        })),
-        ..Default::default()
-    };
+    let hello = ipc::hello_envelope(&device_id);
 
     sink.send(tungstenite::Message::Binary(hello.encode_to_vec()))
         .await?;
@@ -499,7 +969,7 @@ async fn connect_and_hello(
     Ok((sink, stream, device_id))
 }
 
-async fn ws_exec(url: &str, tool: &str, args: &[String]) -> anyhow::Result<()> {
+async fn ws_exec(url: &str, tool: &str, args: &[String], priority: i32) -> anyhow::Result<()> {
     let (mut sink, mut stream, device_id) = connect_and_hello(url).await?;
 
     let job_id = format!("ctl-job-{}", std::process::id());
@@ -512,6 +982,7 @@ async fn ws_exec(url: &str, tool: &str, args: &[String]) -> anyhow::Result<()> {
             job_id: job_id.clone(),
             tool: tool.to_string(),
             args: args.to_vec(),
+            priority,
             ..Default::default()
         })),
         ..Default::default()
@@ -552,14 +1023,24 @@ async fn ws_exec(url: &str, tool: &str, args: &[String]) -> anyhow::Result<()> {
                     None => {}
                 }
             }
+            Some(envelope::Payload::JobQueued(queued)) => {
+                if queued.job_id != job_id {
+                    continue;
+                }
+                eprintln!("[queued] {}", format_queued(&queued));
+            }
             Some(envelope::Payload::JobFinished(fin)) => {
                 if fin.job_id != job_id {
                     continue;
                 }
+                let timing = format_timing(fin.duration_ms, fin.queued_ms);
                 if fin.error.is_empty() {
-                    eprintln!("[finished] exit_code={}", fin.exit_code);
+                    eprintln!("[finished] exit_code={} {timing}", fin.exit_code);
                 } else {
-                    eprintln!("[finished] exit_code={} error={}", fin.exit_code, fin.error);
+                    eprintln!(
+                        "[finished] exit_code={} {timing} error={}",
+                        fin.exit_code, fin.error
+                    );
                 }
                 std::process::exit(fin.exit_code);
             }
@@ -622,6 +1103,34 @@ async fn ws_cancel(url: &str, job_id: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn ws_cancel_all(url: &str, caller: Option<&str>) -> anyhow::Result<()> {
+    let (mut sink, mut stream, device_id) = connect_and_hello(url).await?;
+
+    let cancel_env = build_cancel_all_envelope(&device_id, caller);
+    sink.send(tungstenite::Message::Binary(cancel_env.encode_to_vec()))
+        .await?;
+
+    // Wait for the CancelAllResult reply.
+    while let Some(msg) = stream.next().await {
+        let msg = msg?;
+        let data = match msg {
+            tungstenite::Message::Binary(b) => b,
+            tungstenite::Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let envelope = Envelope::decode(data.as_ref())?;
+
+        if let Some(envelope::Payload::CancelAllResult(result)) = envelope.payload {
+            eprintln!("[cancel] cancelled {} job(s)", result.cancelled);
+            break;
+        }
+    }
+
+    sink.close().await?;
+    Ok(())
+}
+
 async fn ws_ping(url: &str) -> anyhow::Result<()> {
     let (mut sink, _stream, device_id) = connect_and_hello(url).await?;
     println!("connected as {device_id}");
@@ -632,131 +1141,512 @@ async fn ws_ping(url: &str) -> anyhow::Result<()> {
 
 // ── IPC approve ──────────────────────────────────────────────────────
 
-async fn ipc_approve(ipc_path: &str) -> anyhow::Result<()> {
-    let endpoint = ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(ipc_path));
-    let stream = ahand_platform::ipc::ipc_connect(&endpoint).await.context(
-        "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
-    )?;
-    let (mut reader, mut writer) = tokio::io::split(stream);
-    let mut reader = tokio::io::BufReader::new(&mut reader);
+async fn ipc_approve(target: &ipc::IpcTarget) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
 
     let device_id = format!("ctl-{}", std::process::id());
     eprintln!("[approve] Connected as {device_id}. Listening for approval requests...");
 
+    // Frames are read on their own task so an ApprovalExpired or
+    // ApprovalResolved can clear the prompt while we wait on stdin.
+    let (frames_tx, mut frames_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let result = read_frame(&mut reader).await;
+            let done = result.is_err();
+            if frames_tx.send(result).is_err() || done {
+                break;
+            }
+        }
+    });
+
     let stdin = tokio::io::BufReader::new(tokio::io::stdin());
     let mut stdin_lines = stdin.lines();
+    let mut queue: std::collections::VecDeque<ahand_protocol::ApprovalRequest> =
+        std::collections::VecDeque::new();
 
     loop {
-        let data = match read_frame(&mut reader).await {
-            Ok(d) => d,
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                eprintln!("[approve] Connection closed.");
-                break;
+        let req = match queue.pop_front() {
+            Some(req) => req,
+            None => {
+                let Some(envelope) = next_approve_frame(&mut frames_rx).await? else {
+                    break;
+                };
+                handle_approve_envelope(envelope, &mut queue, None);
+                continue;
             }
-            Err(e) => return Err(e.into()),
         };
 
-        let envelope = Envelope::decode(data.as_slice())?;
+        print_approval_prompt(&req);
+        // Flush stderr to ensure prompt is visible.
+        let _ = tokio::io::stderr().flush().await;
 
-        if let Some(envelope::Payload::ApprovalRequest(req)) = envelope.payload {
-            eprintln!();
-            eprintln!(
-                "[approval] Job {} (from {}) wants to run: {} {}",
-                req.job_id,
-                req.caller_uid,
-                req.tool,
-                req.args.join(" ")
-            );
-            if !req.cwd.is_empty() {
-                eprintln!("  Working directory: {}", req.cwd);
+        let line = loop {
+            tokio::select! {
+                line = stdin_lines.next_line() => break Ok(line?),
+                frame = next_approve_frame(&mut frames_rx) => {
+                    let Some(envelope) = frame? else {
+                        return Ok(());
+                    };
+                    if let Some(dismissed) = handle_approve_envelope(envelope, &mut queue, Some(&req.job_id)) {
+                        break Err(dismissed);
+                    }
+                }
             }
-            eprintln!("  Reason: {}", req.reason);
-            if !req.detected_domains.is_empty() {
-                eprintln!("  Detected domains: {}", req.detected_domains.join(", "));
+        };
+        let line = match line {
+            Ok(Some(l)) => l,
+            Ok(None) => break,
+            Err(dismissed) => {
+                eprintln!();
+                eprintln!("[approval] Job {} {dismissed}", req.job_id);
+                continue;
             }
-            if req.expires_ms > 0 {
-                let remaining = req.expires_ms.saturating_sub(now_ms());
-                eprintln!("  Expires in: {}s", remaining / 1000);
+        };
+        let choice = line.trim().to_lowercase();
+
+        // `r` remembers for the policy's default TTL, `r1h`/`r24h`/... for
+        // the given time.
+        let remember_ttl = choice.strip_prefix('r').and_then(parse_ttl);
+        if choice == "a" || choice == "all" {
+            // Approve every pending request for this tool; the daemon
+            // answers with how many that was.
+            let bulk_env = Envelope {
+                device_id: device_id.clone(),
+                msg_id: format!("approve-all-{}", now_ms()),
+                ts_ms: now_ms(),
+                payload: Some(envelope::Payload::ApprovalBulkResponse(
+                    ahand_protocol::ApprovalBulkResponse {
+                        tool: req.tool.clone(),
+                        approved: true,
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            };
+            write_frame(&mut writer, &bulk_env.encode_to_vec()).await?;
+            queue.retain(|r| r.tool != req.tool);
+            continue;
+        }
+
+        let (approved, remember, reason) = match choice.as_str() {
+            "y" | "yes" => (true, false, String::new()),
+            "r" | "remember" => (true, true, String::new()),
+            _ if remember_ttl.is_some() => (true, true, String::new()),
+            _ => {
+                // If the input is longer than a single char, treat it as a refusal reason.
+                let reason = if choice.len() > 1 && choice != "n" && choice != "no" {
+                    choice.clone()
+                } else {
+                    String::new()
+                };
+                (false, false, reason)
             }
-            eprint!("Approve? [y/N/r(emember)]: ");
+        };
 
-            // Flush stderr to ensure prompt is visible.
-            let _ = tokio::io::stderr().flush().await;
+        let resp_env = Envelope {
+            device_id: device_id.clone(),
+            msg_id: format!("approve-{}", now_ms()),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::ApprovalResponse(ApprovalResponse {
+                job_id: req.job_id.clone(),
+                approved,
+                remember,
+                reason: reason.clone(),
+                remember_ttl_secs: remember_ttl.unwrap_or(0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        write_frame(&mut writer, &resp_env.encode_to_vec()).await?;
 
-            let line = match stdin_lines.next_line().await? {
-                Some(l) => l,
-                None => break,
-            };
-            let choice = line.trim().to_lowercase();
-
-            let (approved, remember, reason) = match choice.as_str() {
-                "y" | "yes" => (true, false, String::new()),
-                "r" | "remember" => (true, true, String::new()),
-                _ => {
-                    // If the input is longer than a single char, treat it as a refusal reason.
-                    let reason = if choice.len() > 1 && choice != "n" && choice != "no" {
-                        choice.clone()
-                    } else {
-                        String::new()
-                    };
-                    (false, false, reason)
-                }
+        if approved {
+            let remembered = match remember_ttl {
+                Some(ttl) => format!(" (remembered for {})", humanize_duration(ttl)),
+                None if remember => " (remembered)".to_string(),
+                None => String::new(),
             };
+            eprintln!("[approval] Approved job {}{remembered}", req.job_id);
+        } else if reason.is_empty() {
+            eprintln!("[approval] Denied job {}", req.job_id);
+        } else {
+            eprintln!(
+                "[approval] Denied job {} with reason: {}",
+                req.job_id, reason
+            );
+        }
+    }
 
-            let resp_env = Envelope {
-                device_id: device_id.clone(),
-                msg_id: format!("approve-{}", now_ms()),
-                ts_ms: now_ms(),
-                payload: Some(envelope::Payload::ApprovalResponse(ApprovalResponse {
-                    job_id: req.job_id.clone(),
-                    approved,
-                    remember,
-                    reason: reason.clone(),
-                })),
-                ..Default::default()
-            };
-            write_frame(&mut writer, &resp_env.encode_to_vec()).await?;
+    Ok(())
+}
 
-            if approved {
-                eprintln!(
-                    "[approval] Approved job {}{}",
-                    req.job_id,
-                    if remember { " (remembered)" } else { "" }
-                );
-            } else if reason.is_empty() {
-                eprintln!("[approval] Denied job {}", req.job_id);
-            } else {
-                eprintln!(
-                    "[approval] Denied job {} with reason: {}",
-                    req.job_id, reason
-                );
+/// How long `ahandctl approve --job-id` waits for the daemon's answer.
+const APPROVE_ACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Answer a single pending approval and exit: 0 if the daemon resolved it,
+/// 2 if nothing was pending for `job_id`.
+async fn ipc_approve_job(
+    target: &ipc::IpcTarget,
+    job_id: &str,
+    approved: bool,
+    remember: bool,
+    remember_ttl: Option<u64>,
+    reason: Option<String>,
+) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let device_id = format!("ctl-{}", std::process::id());
+    let resp_env = Envelope {
+        device_id,
+        msg_id: format!("approve-{}", now_ms()),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::ApprovalResponse(ApprovalResponse {
+            job_id: job_id.to_string(),
+            approved,
+            remember,
+            reason: reason.unwrap_or_default(),
+            remember_ttl_secs: remember_ttl.unwrap_or(0),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &resp_env.encode_to_vec()).await?;
+
+    let result = tokio::time::timeout(APPROVE_ACK_TIMEOUT, async {
+        loop {
+            let data = read_frame(&mut reader).await?;
+            let envelope = Envelope::decode(data.as_slice())?;
+            if let Some(envelope::Payload::ApprovalResolveResult(result)) = envelope.payload
+                && result.job_id == job_id
+            {
+                return anyhow::Ok(result);
             }
         }
+    })
+    .await
+    .context("timed out waiting for the daemon to confirm the approval")??;
+
+    if !result.accepted {
+        eprintln!("[approval] No pending approval for job {job_id}");
+        std::process::exit(2);
     }
+    if approved {
+        eprintln!(
+            "[approval] Approved job {job_id}{}",
+            if remember { " (remembered)" } else { "" }
+        );
+    } else {
+        eprintln!("[approval] Denied job {job_id}");
+    }
+    Ok(())
+}
 
+/// Approve every pending request for `tool` (reaching a domain matching
+/// `domain_pattern`, if set) and exit 2 when there were none.
+async fn ipc_approve_all(
+    target: &ipc::IpcTarget,
+    tool: &str,
+    domain_pattern: String,
+    remember: bool,
+) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let bulk_env = Envelope {
+        device_id: format!("ctl-{}", std::process::id()),
+        msg_id: format!("approve-all-{}", now_ms()),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::ApprovalBulkResponse(
+            ahand_protocol::ApprovalBulkResponse {
+                tool: tool.to_string(),
+                domain_pattern,
+                approved: true,
+                remember,
+            },
+        )),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &bulk_env.encode_to_vec()).await?;
+
+    let result = tokio::time::timeout(APPROVE_ACK_TIMEOUT, async {
+        loop {
+            let data = read_frame(&mut reader).await?;
+            let envelope = Envelope::decode(data.as_slice())?;
+            if let Some(envelope::Payload::ApprovalBulkResult(result)) = envelope.payload {
+                return anyhow::Ok(result);
+            }
+        }
+    })
+    .await
+    .context("timed out waiting for the daemon to confirm the approvals")??;
+
+    eprintln!(
+        "[approval] Approved {} pending {} request(s)",
+        result.resolved, result.tool
+    );
+    if result.resolved == 0 {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+/// Apply an envelope received while `ipc_approve` is idle or prompting for
+/// `current`. Returns why `current` no longer needs an answer, if it
+/// expired or was answered by another approver.
+fn handle_approve_envelope(
+    envelope: Envelope,
+    queue: &mut std::collections::VecDeque<ahand_protocol::ApprovalRequest>,
+    current: Option<&str>,
+) -> Option<String> {
+    match envelope.payload {
+        Some(envelope::Payload::ApprovalRequest(req)) if Some(req.job_id.as_str()) != current => {
+            queue_approval(queue, req);
+        }
+        Some(envelope::Payload::ApprovalExpired(expired)) => {
+            if Some(expired.job_id.as_str()) == current {
+                return Some("expired before an answer".to_string());
+            }
+            queue.retain(|r| r.job_id != expired.job_id);
+        }
+        Some(envelope::Payload::ApprovalResolved(resolved)) => {
+            if Some(resolved.job_id.as_str()) == current {
+                return Some(format!(
+                    "(handled elsewhere: {} by {})",
+                    if resolved.approved {
+                        "approved"
+                    } else {
+                        "denied"
+                    },
+                    resolved.resolved_by
+                ));
+            }
+            queue.retain(|r| r.job_id != resolved.job_id);
+        }
+        Some(envelope::Payload::ApprovalBulkResult(result)) => {
+            eprintln!(
+                "[approval] Approved {} pending {} request(s)",
+                result.resolved, result.tool
+            );
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Queue `req` for prompting unless it is already queued — a request can
+/// arrive both as a replay and as a live broadcast.
+fn queue_approval(
+    queue: &mut std::collections::VecDeque<ahand_protocol::ApprovalRequest>,
+    req: ahand_protocol::ApprovalRequest,
+) {
+    if !queue.iter().any(|r| r.job_id == req.job_id) {
+        queue.push_back(req);
+    }
+}
+
+/// Next envelope from the `ipc_approve` reader task; `None` once the daemon
+/// closes the connection.
+async fn next_approve_frame(
+    frames_rx: &mut tokio::sync::mpsc::UnboundedReceiver<std::io::Result<Vec<u8>>>,
+) -> anyhow::Result<Option<Envelope>> {
+    match frames_rx.recv().await {
+        Some(Ok(data)) => Ok(Some(Envelope::decode(data.as_slice())?)),
+        Some(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            eprintln!("[approve] Connection closed.");
+            Ok(None)
+        }
+        Some(Err(e)) => Err(e.into()),
+        None => Ok(None),
+    }
+}
+
+fn print_approval_prompt(req: &ahand_protocol::ApprovalRequest) {
+    eprintln!();
+    eprintln!(
+        "[approval] Job {} (from {}) wants to run: {} {}{}",
+        req.job_id,
+        req.caller_uid,
+        req.tool,
+        req.args.join(" "),
+        if req.replayed {
+            " [pending before you connected]"
+        } else {
+            ""
+        }
+    );
+    if !req.cwd.is_empty() {
+        eprintln!("  Working directory: {}", req.cwd);
+    }
+    eprintln!("  Reason: {}", req.reason);
+    if !req.detected_domains.is_empty() {
+        eprintln!("  Detected domains: {}", req.detected_domains.join(", "));
+    }
+    if let Some(last) = req.previous_approvals.last() {
+        eprintln!(
+            "  Previously approved {}× in the last day (latest: {} {})",
+            req.previous_approvals.len(),
+            last.tool,
+            last.args.join(" ")
+        );
+    }
+    if req.expires_ms > 0 {
+        let remaining = req.expires_ms.saturating_sub(now_ms());
+        eprintln!("  Expires in: {}s", remaining / 1000);
+    }
+    eprint!(
+        "Approve? [y/N/r(emember)/r1h/r24h/a(ll for {})]: ",
+        req.tool
+    );
+}
+
+// ── Pending approvals ────────────────────────────────────────────────
+
+async fn ipc_approvals(target: &ipc::IpcTarget, json: bool) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    let device_id = format!("ctl-{}", std::process::id());
+    let request_env = build_pending_approvals_envelope(&device_id);
+    write_frame(&mut writer, &request_env.encode_to_vec()).await?;
+
+    loop {
+        let data = match read_frame(&mut reader).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        let envelope = Envelope::decode(data.as_slice())?;
+        if let Some(envelope::Payload::PendingApprovalsState(state)) = envelope.payload {
+            print_pending_approvals(&state, json)?;
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn ws_approvals(url: &str, json: bool) -> anyhow::Result<()> {
+    let (mut sink, mut stream, device_id) = connect_and_hello(url).await?;
+
+    let request_env = build_pending_approvals_envelope(&device_id);
+    sink.send(tungstenite::Message::Binary(request_env.encode_to_vec()))
+        .await?;
+
+    while let Some(msg) = stream.next().await {
+        let data = match msg? {
+            tungstenite::Message::Binary(b) => b,
+            tungstenite::Message::Close(_) => break,
+            _ => continue,
+        };
+        let envelope = Envelope::decode(data.as_ref())?;
+        if let Some(envelope::Payload::PendingApprovalsState(state)) = envelope.payload {
+            print_pending_approvals(&state, json)?;
+            break;
+        }
+    }
+
+    sink.close().await?;
+    Ok(())
+}
+
+fn build_pending_approvals_envelope(device_id: &str) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: format!("approvals-{}", now_ms()),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::PendingApprovalsQuery(
+            PendingApprovalsQuery {},
+        )),
+        ..Default::default()
+    }
+}
+
+/// Print pending requests in the daemon's order (soonest expiry first).
+fn print_pending_approvals(
+    state: &ahand_protocol::PendingApprovalsState,
+    json: bool,
+) -> anyhow::Result<()> {
+    let now = now_ms();
+    if json {
+        let requests: Vec<_> = state
+            .requests
+            .iter()
+            .map(|req| {
+                serde_json::json!({
+                    "job_id": req.job_id,
+                    "tool": req.tool,
+                    "args": req.args,
+                    "cwd": req.cwd,
+                    "caller_uid": req.caller_uid,
+                    "reason": req.reason,
+                    "expires_ms": req.expires_ms,
+                    "expires_in_secs": req.expires_ms.saturating_sub(now) / 1000,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&requests)?);
+        return Ok(());
+    }
+
+    if state.requests.is_empty() {
+        println!("No pending approvals.");
+        return Ok(());
+    }
+    for req in &state.requests {
+        println!("{}", req.job_id);
+        println!("  Command:    {} {}", req.tool, req.args.join(" "));
+        println!("  Caller:     {}", req.caller_uid);
+        println!(
+            "  Expires in: {}",
+            humanize_duration(req.expires_ms.saturating_sub(now) / 1000)
+        );
+    }
     Ok(())
 }
 
 // ── IPC policy ───────────────────────────────────────────────────────
 
-async fn ipc_policy(ipc_path: &str, action: PolicyAction) -> anyhow::Result<()> {
-    let endpoint = ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(ipc_path));
-    let stream = ahand_platform::ipc::ipc_connect(&endpoint).await.context(
-        "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
-    )?;
-    let (mut reader, mut writer) = tokio::io::split(stream);
-    let mut reader = tokio::io::BufReader::new(&mut reader);
+async fn ipc_policy(target: &ipc::IpcTarget, action: PolicyAction) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
 
     let device_id = format!("ctl-{}", std::process::id());
 
     let request_env = match &action {
-        PolicyAction::Show => Envelope {
+        PolicyAction::Show | PolicyAction::Remembered | PolicyAction::Presets => Envelope {
             device_id: device_id.clone(),
             msg_id: "policy-query-0".to_string(),
             ts_ms: now_ms(),
             payload: Some(envelope::Payload::PolicyQuery(PolicyQuery {})),
             ..Default::default()
         },
+        PolicyAction::Preset {
+            name,
+            keep_approvals,
+        } => Envelope {
+            device_id: device_id.clone(),
+            msg_id: "policy-preset-0".to_string(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::SetPolicyPreset(SetPolicyPreset {
+                name: name.clone(),
+                keep_approvals: *keep_approvals,
+            })),
+            ..Default::default()
+        },
+        PolicyAction::Check {
+            cwd,
+            caller,
+            tool,
+            args,
+        } => Envelope {
+            device_id: device_id.clone(),
+            msg_id: "policy-check-0".to_string(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::PolicyCheckRequest(PolicyCheckRequest {
+                tool: tool.clone(),
+                args: args.clone(),
+                cwd: cwd.clone(),
+                caller_uid: caller.clone(),
+            })),
+            ..Default::default()
+        },
         _ => {
             let update = build_policy_update(&action);
             Envelope {
@@ -771,7 +1661,7 @@ async fn ipc_policy(ipc_path: &str, action: PolicyAction) -> anyhow::Result<()>
 
     write_frame(&mut writer, &request_env.encode_to_vec()).await?;
 
-    // Wait for PolicyState response.
+    // Wait for PolicyState (or PolicyCheckResult) response.
     loop {
         let data = match read_frame(&mut reader).await {
             Ok(d) => d,
@@ -784,9 +1674,19 @@ async fn ipc_policy(ipc_path: &str, action: PolicyAction) -> anyhow::Result<()>
 
         let envelope = Envelope::decode(data.as_slice())?;
 
-        if let Some(envelope::Payload::PolicyState(state)) = envelope.payload {
-            print_policy_state(&state);
-            break;
+        match envelope.payload {
+            Some(envelope::Payload::PolicyState(state)) => {
+                print_policy_response(&action, &state);
+                break;
+            }
+            Some(envelope::Payload::PolicyCheckResult(result)) => {
+                print_policy_check(&result);
+                break;
+            }
+            Some(envelope::Payload::Error(err)) if err.ref_msg_id == request_env.msg_id => {
+                anyhow::bail!("policy update rejected: {} ({})", err.message, err.code);
+            }
+            _ => {}
         }
     }
 
@@ -799,13 +1699,43 @@ async fn ws_policy(url: &str, action: PolicyAction) -> anyhow::Result<()> {
     let (mut sink, mut stream, device_id) = connect_and_hello(url).await?;
 
     let request_env = match &action {
-        PolicyAction::Show => Envelope {
+        PolicyAction::Show | PolicyAction::Remembered | PolicyAction::Presets => Envelope {
             device_id: device_id.clone(),
             msg_id: "policy-query-0".to_string(),
             ts_ms: now_ms(),
             payload: Some(envelope::Payload::PolicyQuery(PolicyQuery {})),
             ..Default::default()
         },
+        PolicyAction::Preset {
+            name,
+            keep_approvals,
+        } => Envelope {
+            device_id: device_id.clone(),
+            msg_id: "policy-preset-0".to_string(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::SetPolicyPreset(SetPolicyPreset {
+                name: name.clone(),
+                keep_approvals: *keep_approvals,
+            })),
+            ..Default::default()
+        },
+        PolicyAction::Check {
+            cwd,
+            caller,
+            tool,
+            args,
+        } => Envelope {
+            device_id: device_id.clone(),
+            msg_id: "policy-check-0".to_string(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::PolicyCheckRequest(PolicyCheckRequest {
+                tool: tool.clone(),
+                args: args.clone(),
+                cwd: cwd.clone(),
+                caller_uid: caller.clone(),
+            })),
+            ..Default::default()
+        },
         _ => {
             let update = build_policy_update(&action);
             Envelope {
@@ -821,7 +1751,7 @@ async fn ws_policy(url: &str, action: PolicyAction) -> anyhow::Result<()> {
     sink.send(tungstenite::Message::Binary(request_env.encode_to_vec()))
         .await?;
 
-    // Wait for PolicyState response.
+    // Wait for PolicyState (or PolicyCheckResult) response.
     while let Some(msg) = stream.next().await {
         let msg = msg?;
         let data = match msg {
@@ -832,9 +1762,19 @@ async fn ws_policy(url: &str, action: PolicyAction) -> anyhow::Result<()> {
 
         let envelope = Envelope::decode(data.as_ref())?;
 
-        if let Some(envelope::Payload::PolicyState(state)) = envelope.payload {
-            print_policy_state(&state);
-            break;
+        match envelope.payload {
+            Some(envelope::Payload::PolicyState(state)) => {
+                print_policy_response(&action, &state);
+                break;
+            }
+            Some(envelope::Payload::PolicyCheckResult(result)) => {
+                print_policy_check(&result);
+                break;
+            }
+            Some(envelope::Payload::Error(err)) if err.ref_msg_id == request_env.msg_id => {
+                anyhow::bail!("policy update rejected: {} ({})", err.message, err.code);
+            }
+            _ => {}
         }
     }
 
@@ -844,13 +1784,26 @@ async fn ws_policy(url: &str, action: PolicyAction) -> anyhow::Result<()> {
 
 // ── IPC session ─────────────────────────────────────────────────────
 
-async fn ipc_session(ipc_path: &str, action: SessionAction) -> anyhow::Result<()> {
-    let endpoint = ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(ipc_path));
-    let stream = ahand_platform::ipc::ipc_connect(&endpoint).await.context(
-        "could not reach ahandd over IPC — is the daemon running? (try: ahandctl start)",
-    )?;
-    let (mut reader, mut writer) = tokio::io::split(stream);
-    let mut reader = tokio::io::BufReader::new(&mut reader);
+async fn ipc_session(target: &ipc::IpcTarget, action: SessionAction) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+
+    if matches!(action, SessionAction::Watch) {
+        // The daemon pushes every session change to each IPC connection;
+        // nothing needs to be sent to start receiving them.
+        println!("Watching session changes (Ctrl-C to stop)...");
+        loop {
+            let data = match read_frame(&mut reader).await {
+                Ok(d) => d,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            let envelope = Envelope::decode(data.as_slice())?;
+            if let Some(envelope::Payload::SessionState(state)) = envelope.payload {
+                print_session_state(&state);
+            }
+        }
+        return Ok(());
+    }
 
     let device_id = format!("ctl-{}", std::process::id());
 
@@ -910,6 +1863,16 @@ async fn ws_session(url: &str, action: SessionAction) -> anyhow::Result<()> {
 
 fn build_session_envelope(device_id: &str, action: &SessionAction) -> Envelope {
     match action {
+        SessionAction::Watch => unreachable!("session watch sends no request"),
+        SessionAction::Clear { caller } => Envelope {
+            device_id: device_id.to_string(),
+            msg_id: "session-clear-0".to_string(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::ClearSession(ClearSession {
+                caller_uid: caller.clone(),
+            })),
+            ..Default::default()
+        },
         SessionAction::Show { caller } => Envelope {
             device_id: device_id.to_string(),
             msg_id: "session-query-0".to_string(),
@@ -923,14 +1886,18 @@ fn build_session_envelope(device_id: &str, action: &SessionAction) -> Envelope {
             mode,
             caller,
             timeout,
+            jobs,
         } => {
             let mode_val = match mode.as_str() {
                 "inactive" => 0,
                 "strict" => 1,
                 "trust" => 2,
                 "auto_accept" | "auto" => 3,
+                "read_only" | "readonly" => 4,
                 other => {
-                    eprintln!("Unknown mode: {other}. Use: inactive, strict, trust, auto_accept");
+                    eprintln!(
+                        "Unknown mode: {other}. Use: inactive, strict, trust, auto_accept, read_only"
+                    );
                     std::process::exit(1);
                 }
             };
@@ -942,6 +1909,7 @@ fn build_session_envelope(device_id: &str, action: &SessionAction) -> Envelope {
                     caller_uid: caller.clone(),
                     mode: mode_val,
                     trust_timeout_mins: *timeout,
+                    trust_job_budget: *jobs,
                 })),
                 ..Default::default()
             }
@@ -955,9 +1923,20 @@ fn print_session_state(state: &ahand_protocol::SessionState) {
         1 => "strict",
         2 => "trust",
         3 => "auto_accept",
+        4 => "read_only",
         _ => "unknown",
     };
-    println!("Session: caller={} mode={}", state.caller_uid, mode_name);
+    if state.origin.is_empty() {
+        println!("Session: caller={} mode={}", state.caller_uid, mode_name);
+    } else {
+        println!(
+            "Session: caller={} mode={} (from {})",
+            state.caller_uid, mode_name, state.origin
+        );
+    }
+    if state.expiry_warning {
+        println!("  Trust is about to expire");
+    }
     if state.trust_expires_ms > 0 {
         let remaining = state.trust_expires_ms.saturating_sub(now_ms());
         println!("  Trust expires in: {}s", remaining / 1000);
@@ -965,13 +1944,20 @@ fn print_session_state(state: &ahand_protocol::SessionState) {
     if state.trust_timeout_mins > 0 {
         println!("  Trust timeout: {}min", state.trust_timeout_mins);
     }
+    if state.trust_jobs_remaining > 0 {
+        println!("  Trusted jobs left: {}", state.trust_jobs_remaining);
+    }
 }
 
 // ── Policy helpers ───────────────────────────────────────────────────
 
 fn build_policy_update(action: &PolicyAction) -> PolicyUpdate {
     match action {
-        PolicyAction::Show => unreachable!(),
+        PolicyAction::Show
+        | PolicyAction::Remembered
+        | PolicyAction::Presets
+        | PolicyAction::Preset { .. }
+        | PolicyAction::Check { .. } => unreachable!(),
         PolicyAction::AllowTool { tools } => PolicyUpdate {
             add_allowed_tools: tools.clone(),
             ..Default::default()
@@ -996,24 +1982,117 @@ fn build_policy_update(action: &PolicyAction) -> PolicyUpdate {
             remove_allowed_domains: domains.clone(),
             ..Default::default()
         },
+        PolicyAction::DenyDomain { domains } => PolicyUpdate {
+            add_denied_domains: domains.clone(),
+            ..Default::default()
+        },
+        PolicyAction::UndenyDomain { domains } => PolicyUpdate {
+            remove_denied_domains: domains.clone(),
+            ..Default::default()
+        },
         PolicyAction::SetTimeout { seconds } => PolicyUpdate {
             approval_timeout_secs: *seconds,
             ..Default::default()
         },
+        PolicyAction::ResetRateLimits => PolicyUpdate {
+            reset_rate_limits: true,
+            ..Default::default()
+        },
+        PolicyAction::Forget { caller, key } => PolicyUpdate {
+            forget_remembered: vec![ahand_protocol::RememberedApproval {
+                caller_uid: caller.clone(),
+                key: key.clone(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    }
+}
+
+fn print_policy_response(action: &PolicyAction, state: &ahand_protocol::PolicyState) {
+    match action {
+        PolicyAction::Remembered | PolicyAction::Forget { .. } => print_remembered(state),
+        PolicyAction::Presets => print_presets(state),
+        PolicyAction::Preset { name, .. } if state.active_preset != *name => {
+            eprintln!(
+                "[policy] Preset {name:?} was not applied. Available: {}",
+                format_list(&state.available_presets)
+            );
+        }
+        _ => print_policy_state(state),
+    }
+}
+
+fn print_presets(state: &ahand_protocol::PolicyState) {
+    for name in &state.available_presets {
+        let marker = if *name == state.active_preset {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {name}");
+    }
+}
+
+fn print_policy_check(result: &ahand_protocol::PolicyCheckResult) {
+    println!("Decision: {}", result.decision);
+    if !result.reason.is_empty() {
+        println!("Reason:   {}", result.reason);
+    }
+    if !result.matched_rule.is_empty() {
+        println!("Rule:     {}", result.matched_rule);
+    }
+    if !result.detected_domains.is_empty() {
+        println!("Domains:  {}", result.detected_domains.join(", "));
+    }
+}
+
+fn print_remembered(state: &ahand_protocol::PolicyState) {
+    if state.remembered.is_empty() {
+        println!("No remembered approvals.");
+        return;
+    }
+    println!("{:<16} {:<32} {:<10} EXPIRES", "CALLER", "KEY", "AGE");
+    let now = now_ms();
+    for r in &state.remembered {
+        let age = humanize_duration(now.saturating_sub(r.created_at_ms) / 1000);
+        let expires = if r.expires_at_ms == 0 {
+            "never".to_string()
+        } else {
+            format!(
+                "in {}",
+                humanize_duration(r.expires_at_ms.saturating_sub(now) / 1000)
+            )
+        };
+        println!("{:<16} {:<32} {:<10} {expires}", r.caller_uid, r.key, age);
     }
 }
 
 fn print_policy_state(state: &ahand_protocol::PolicyState) {
-    println!("Policy:");
+    if state.active_preset.is_empty() {
+        println!("Policy:");
+    } else {
+        println!("Policy (preset {}):", state.active_preset);
+    }
     println!("  Allowed tools:   {}", format_list(&state.allowed_tools));
     println!("  Denied tools:    {}", format_list(&state.denied_tools));
     println!("  Denied paths:    {}", format_list(&state.denied_paths));
     println!("  Allowed domains: {}", format_list(&state.allowed_domains));
+    println!("  Denied domains:  {}", format_list(&state.denied_domains));
     println!(
         "  Approval timeout: {}s ({})",
         state.approval_timeout_secs,
         humanize_duration(state.approval_timeout_secs)
     );
+    for limit in &state.rate_limits {
+        println!(
+            "  Rate limit:      {} has {} job(s) left",
+            limit.caller_uid, limit.tokens
+        );
+    }
+    for err in &state.pattern_errors {
+        println!("  Invalid pattern: {err}");
+    }
 }
 
 fn format_list(items: &[String]) -> String {
@@ -1024,6 +2103,194 @@ fn format_list(items: &[String]) -> String {
     }
 }
 
+/// Parse a TTL such as `30m`, `1h` or `7d` into seconds. A bare number is
+/// seconds.
+fn parse_ttl(s: &str) -> Option<u64> {
+    let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let n: u64 = digits.parse().ok()?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n.checked_mul(60)?,
+        "h" => n.checked_mul(3600)?,
+        "d" => n.checked_mul(86400)?,
+        _ => return None,
+    };
+    (secs > 0).then_some(secs)
+}
+
+fn parse_ttl_arg(s: &str) -> Result<u64, String> {
+    parse_ttl(s).ok_or_else(|| format!("invalid duration {s:?} (expected e.g. 30m, 1h, 7d)"))
+}
+
+fn status_endpoint(ipc_path: Option<&str>) -> ahand_platform::ipc::IpcEndpoint {
+    match ipc_path {
+        Some(path) => ahand_platform::ipc::IpcEndpoint::from_path(std::path::PathBuf::from(path)),
+        None => ahand_platform::ipc::IpcEndpoint::default_for_user(),
+    }
+}
+
+/// Print the running daemon's cloud (or OpenClaw gateway) connections,
+/// pending approvals and open browser sessions. Stays quiet if the IPC
+/// socket can't be reached — the PID line already says the daemon is up.
+async fn print_daemon_status(ipc_path: Option<&str>) {
+    match daemon::query_status(&status_endpoint(ipc_path)).await {
+        Ok(status) => {
+            let now = now_ms();
+            if let Some(conn) = &status.connection
+                && (status.gateways.is_empty()
+                    || conn.phase() != ahand_protocol::ConnectionPhase::Disabled)
+            {
+                println!("{}", format_connection("Cloud", conn, now));
+            }
+            for gateway in &status.gateways {
+                println!("{}", format_connection("Gateway", gateway, now));
+            }
+            println!("{}", format_pending_approvals(&status));
+            if let Some(gc) = &status.runs_gc {
+                println!("{}", format_runs_gc(gc));
+            }
+            for session in &status.browser_sessions {
+                println!("{}", format_browser_session(session, now));
+            }
+        }
+        Err(e) => tracing::debug!(error = %e, "daemon status query failed"),
+    }
+}
+
+/// `status --json`: the PID plus whatever the daemon reports over IPC
+/// (`null` when it isn't running or the socket can't be reached).
+async fn print_daemon_status_json(ipc_path: Option<&str>) -> anyhow::Result<()> {
+    let pid = daemon::read_running_pid()?;
+    let status = match pid {
+        Some(_) => daemon::query_status(&status_endpoint(ipc_path))
+            .await
+            .map_err(|e| tracing::debug!(error = %e, "daemon status query failed"))
+            .ok(),
+        None => None,
+    };
+    let connection = status
+        .as_ref()
+        .and_then(|s| s.connection.as_ref())
+        .map(connection_json);
+    let gateways: Vec<_> = status
+        .as_ref()
+        .map(|s| s.gateways.iter().map(connection_json).collect())
+        .unwrap_or_default();
+    let browser_sessions: Vec<_> = status
+        .as_ref()
+        .map(|s| {
+            s.browser_sessions
+                .iter()
+                .map(|session| {
+                    serde_json::json!({
+                        "session_id": session.session_id,
+                        "created_ms": session.created_ms,
+                        "last_used_ms": session.last_used_ms,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let out = serde_json::json!({
+        "running": pid.is_some(),
+        "pid": pid,
+        "connection": connection,
+        "gateways": gateways,
+        "browser_sessions": browser_sessions,
+        "pending_approvals": status.as_ref().map(|s| s.pending_approvals),
+        "oldest_pending_approval_age_ms": status.as_ref().map(|s| s.oldest_pending_approval_age_ms),
+    });
+    println!("{}", serde_json::to_string_pretty(&out)?);
+    Ok(())
+}
+
+fn connection_json(conn: &ahand_protocol::ConnectionStatus) -> serde_json::Value {
+    let phase = conn.phase();
+    serde_json::json!({
+        "phase": phase.as_str_name().trim_start_matches("CONNECTION_PHASE_").to_lowercase(),
+        "url": (!conn.url.is_empty()).then_some(&conn.url),
+        "failover": conn.failover,
+        "since_ms": (conn.since_ms > 0).then_some(conn.since_ms),
+        "next_attempt_ms": (conn.next_attempt_ms > 0).then_some(conn.next_attempt_ms),
+        "last_inbound_ms": (conn.last_inbound_ms > 0).then_some(conn.last_inbound_ms),
+        "outbox_depth": conn.outbox_depth,
+        "dropped_chunks": conn.dropped_chunks,
+        "send_timeouts": conn.send_timeouts,
+    })
+}
+
+/// Render a connection, e.g. `Cloud: connected to wss://… for 2h
+/// (last message 3s ago, 0 unacked)` or `Gateway: reconnecting in 12s (…)`.
+fn format_connection(label: &str, conn: &ahand_protocol::ConnectionStatus, now: u64) -> String {
+    use ahand_protocol::ConnectionPhase;
+    let ago = |ms: u64| humanize_duration(now.saturating_sub(ms) / 1000);
+    let head = match conn.phase() {
+        ConnectionPhase::Disabled => return format!("{label}: not connected (disabled)"),
+        ConnectionPhase::Connecting => format!("{label}: connecting to {}", conn.url),
+        ConnectionPhase::Connected => format!(
+            "{label}: connected to {}{} for {}",
+            conn.url,
+            if conn.failover { " (primary down)" } else { "" },
+            ago(conn.since_ms)
+        ),
+        ConnectionPhase::Backoff => format!(
+            "{label}: reconnecting in {}",
+            humanize_duration(conn.next_attempt_ms.saturating_sub(now).div_ceil(1000))
+        ),
+    };
+    let last = match conn.last_inbound_ms {
+        0 => "no messages yet".to_string(),
+        ms => format!("last message {} ago", ago(ms)),
+    };
+    let mut line = format!("{head} ({last}, {} unacked)", conn.outbox_depth);
+    if conn.dropped_chunks > 0 || conn.send_timeouts > 0 {
+        line.push_str(&format!(
+            "; since start: {} output chunks dropped, {} stalled sends",
+            conn.dropped_chunks, conn.send_timeouts
+        ));
+    }
+    line
+}
+
+/// Render e.g. `Browser session agent-1: open 2h, last used 3m ago`.
+fn format_browser_session(session: &ahand_protocol::BrowserSession, now: u64) -> String {
+    let ago = |ms: u64| humanize_duration(now.saturating_sub(ms) / 1000);
+    format!(
+        "Browser session {}: open {}, last used {} ago",
+        session.session_id,
+        ago(session.created_ms),
+        ago(session.last_used_ms)
+    )
+}
+
+/// Render e.g. `3 approvals pending (oldest 54m)`.
+fn format_pending_approvals(status: &ahand_protocol::DaemonStatus) -> String {
+    match status.pending_approvals {
+        0 => "No approvals pending.".to_string(),
+        n => format!(
+            "{n} approval{} pending (oldest {})",
+            if n == 1 { "" } else { "s" },
+            humanize_duration(status.oldest_pending_approval_age_ms / 1000)
+        ),
+    }
+}
+
+/// Render e.g. `Runs cleanup 12m ago: deleted 4 runs (120.5 MB), 812.0 MB kept`.
+fn format_runs_gc(gc: &ahand_protocol::RunsGcStats) -> String {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    format!(
+        "Runs cleanup {} ago: deleted {} run{} ({:.1} MB), {:.1} MB kept",
+        humanize_duration(now_ms().saturating_sub(gc.ran_at_ms) / 1000),
+        gc.deleted_runs,
+        if gc.deleted_runs == 1 { "" } else { "s" },
+        mb(gc.freed_bytes),
+        mb(gc.retained_bytes)
+    )
+}
+
 fn humanize_duration(secs: u64) -> String {
     if secs >= 86400 {
         let days = secs / 86400;
@@ -1049,6 +2316,27 @@ fn humanize_duration(secs: u64) -> String {
     }
 }
 
+/// Render a `JobQueued` notice as `position #2, about 3m to go`.
+fn format_queued(queued: &ahand_protocol::JobQueued) -> String {
+    if queued.estimated_wait_ms == 0 {
+        return format!("position #{}", queued.position);
+    }
+    format!(
+        "position #{}, about {} to go",
+        queued.position,
+        humanize_duration(queued.estimated_wait_ms.div_ceil(1000))
+    )
+}
+
+/// Render `JobFinished` timing as `(2.3s, queued 0.1s)`.
+fn format_timing(duration_ms: u64, queued_ms: u64) -> String {
+    format!(
+        "({:.1}s, queued {:.1}s)",
+        duration_ms as f64 / 1000.0,
+        queued_ms as f64 / 1000.0
+    )
+}
+
 fn now_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
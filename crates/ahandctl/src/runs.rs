@@ -0,0 +1,238 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use ahandd::redact::EnvRedactor;
+use ahandd::run_index::{RUNS_DB_FILE_NAME, RunIndex, RunKind, RunQuery, RunRecord};
+use ahandd::store::{OutcomeKind, RunOutcome, export_run, read_run_outcome, read_run_output};
+use anyhow::{Context, Result};
+
+/// Print indexed runs matching `query`, newest first.
+pub fn list(query: &RunQuery, limit: usize, offset: usize, json: bool) -> Result<()> {
+    let (total, runs) = match open_index()? {
+        Some(index) => index.search(query, limit, offset)?,
+        None => (0, Vec::new()),
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&runs)?);
+        return Ok(());
+    }
+    if runs.is_empty() {
+        println!("No runs.");
+        return Ok(());
+    }
+    for run in &runs {
+        println!("{}", format_run(run));
+    }
+    if offset + runs.len() < total {
+        println!(
+            "({} of {total} shown; use --offset {} for more)",
+            runs.len(),
+            offset + runs.len()
+        );
+    }
+    Ok(())
+}
+
+/// Print one indexed run, and with `output` its stdout and stderr. Exits
+/// with status 2 if it isn't indexed.
+pub fn show(job_id: &str, json: bool, output: bool) -> Result<()> {
+    let run = match open_index()? {
+        Some(index) => index.get_run(job_id)?,
+        None => None,
+    };
+    let Some(run) = run else {
+        eprintln!("Run not found: {job_id}");
+        std::process::exit(2);
+    };
+    let run_dir = data_dir()?.join("runs").join(job_id);
+    let outcome = read_run_outcome(&run_dir);
+    if json {
+        let mut value = serde_json::to_value(&run)?;
+        if let Some(outcome) = &outcome {
+            value["outcome"] = serde_json::to_value(outcome)?;
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        println!("{}", format_run(&run));
+        if let Some(error) = run.error.as_deref().filter(|e| !e.is_empty()) {
+            println!("  error: {error}");
+        }
+        if let Some(outcome) = &outcome {
+            println!("  {}", format_outcome(outcome));
+        }
+        println!(
+            "  stdout: {} bytes, stderr: {} bytes",
+            run.bytes_stdout, run.bytes_stderr
+        );
+    }
+    if output {
+        for name in ["stdout", "stderr"] {
+            match read_run_output(&run_dir, name) {
+                Ok(content) => {
+                    println!("--- {name} ---");
+                    std::io::stdout().write_all(&content)?;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {name}")),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write `job_id`'s tarball to `output` (default `./job_<id>.tar.gz`).
+pub fn export(job_id: &str, output: Option<PathBuf>, redactor: &EnvRedactor) -> Result<()> {
+    let tarball = export_run(&data_dir()?, job_id, redactor)
+        .with_context(|| format!("Failed to export run {job_id}"))?;
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("job_{job_id}.tar.gz")));
+    // The data dir may be on another filesystem than `output`.
+    if std::fs::rename(&tarball, &output).is_err() {
+        let copied = std::fs::copy(&tarball, &output);
+        let _ = std::fs::remove_file(&tarball);
+        copied.with_context(|| format!("Failed to write {}", output.display()))?;
+    }
+    println!("Exported run {job_id} to {}", output.display());
+    Ok(())
+}
+
+/// Open the daemon's runs index, or `None` if the daemon has never written
+/// to the data directory.
+pub fn open_index() -> Result<Option<RunIndex>> {
+    let data_dir = data_dir()?;
+    if !data_dir.join(RUNS_DB_FILE_NAME).exists() && !data_dir.join("runs").exists() {
+        return Ok(None);
+    }
+    let index = RunIndex::open(&data_dir)
+        .with_context(|| format!("Failed to open runs index in {}", data_dir.display()))?;
+    Ok(Some(index))
+}
+
+fn format_run(run: &RunRecord) -> String {
+    let status = match (run.finished_ms, run.exit_code) {
+        (Some(_), Some(code)) => format!("exit={code}"),
+        _ => "running".to_string(),
+    };
+    let duration = match run.finished_ms {
+        Some(end) => format!(
+            " {:.1}s",
+            end.saturating_sub(run.started_ms) as f64 / 1000.0
+        ),
+        None => String::new(),
+    };
+    let caller = if run.caller_uid.is_empty() {
+        "-"
+    } else {
+        &run.caller_uid
+    };
+    let tool = match run.kind {
+        RunKind::Exec => "tool",
+        RunKind::Browser => "browser",
+    };
+    format!(
+        "{} {:<9} caller={caller} job={} {tool}={}{duration}",
+        run.started_ms, status, run.job_id, run.tool
+    )
+}
+
+fn format_outcome(outcome: &RunOutcome) -> String {
+    let kind = match outcome.kind {
+        OutcomeKind::Finished => "finished",
+        OutcomeKind::Cancelled => "cancelled",
+        OutcomeKind::Timeout => "timeout",
+        OutcomeKind::Rejected => "rejected",
+    };
+    let mut line = format!("outcome: {kind}");
+    if let Some(reason) = &outcome.rejection_reason {
+        line.push_str(&format!(" ({reason})"));
+    }
+    if let Some(mode) = &outcome.session_mode {
+        line.push_str(&format!(", session={mode}"));
+    }
+    if let Some(by) = &outcome.approved_by {
+        line.push_str(&format!(", approved by {by}"));
+    }
+    if let Some(ms) = outcome.approval_latency_ms {
+        line.push_str(&format!(", approval took {:.1}s", ms as f64 / 1000.0));
+    }
+    line
+}
+
+fn data_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to find home directory")?;
+    Ok(home.join(".ahand").join("data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_run_shows_exit_code_and_duration() {
+        let run = RunRecord {
+            job_id: "job-1".to_string(),
+            tool: "git".to_string(),
+            caller_uid: "uid:501".to_string(),
+            started_ms: 1_700_000_000_000,
+            finished_ms: Some(1_700_000_002_300),
+            exit_code: Some(1),
+            error: None,
+            bytes_stdout: 0,
+            bytes_stderr: 0,
+            kind: RunKind::Exec,
+        };
+        assert_eq!(
+            format_run(&run),
+            "1700000000000 exit=1    caller=uid:501 job=job-1 tool=git 2.3s"
+        );
+
+        let screenshot = RunRecord {
+            job_id: "browser/s1/1700000000000_screenshot".to_string(),
+            tool: "screenshot".to_string(),
+            finished_ms: Some(1_700_000_000_000),
+            exit_code: Some(0),
+            kind: RunKind::Browser,
+            ..run.clone()
+        };
+        assert_eq!(
+            format_run(&screenshot),
+            "1700000000000 exit=0    caller=uid:501 job=browser/s1/1700000000000_screenshot \
+             browser=screenshot 0.0s"
+        );
+
+        let running = RunRecord {
+            finished_ms: None,
+            exit_code: None,
+            caller_uid: String::new(),
+            ..run
+        };
+        assert_eq!(
+            format_run(&running),
+            "1700000000000 running   caller=- job=job-1 tool=git"
+        );
+    }
+
+    #[test]
+    fn format_outcome_shows_approval_and_rejection_context() {
+        let approved = RunOutcome {
+            kind: OutcomeKind::Finished,
+            approved_by: Some("uid:501".to_string()),
+            approval_latency_ms: Some(4200),
+            session_mode: Some("strict".to_string()),
+            rejection_reason: None,
+        };
+        assert_eq!(
+            format_outcome(&approved),
+            "outcome: finished, session=strict, approved by uid:501, approval took 4.2s"
+        );
+
+        let rejected = RunOutcome {
+            kind: OutcomeKind::Rejected,
+            rejection_reason: Some("approval denied".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_outcome(&rejected),
+            "outcome: rejected (approval denied)"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use ahand_protocol::{Envelope, SessionMode, Subscribe, envelope, job_event};
+use ahandctl::ipc::{self, read_frame, write_frame};
+use anyhow::{Result, bail};
+use prost::Message;
+
+/// Subscribe to `topics` on ahandd and print what it reports until the
+/// daemon goes away.
+pub async fn run(target: &ipc::IpcTarget, topics: &[String]) -> Result<()> {
+    let (mut reader, mut writer) = ipc::connect(target).await?;
+    let subscribe = Envelope {
+        device_id: format!("ctl-{}", std::process::id()),
+        msg_id: "subscribe-0".to_string(),
+        payload: Some(envelope::Payload::Subscribe(Subscribe {
+            topics: topics.to_vec(),
+        })),
+        ..Default::default()
+    };
+    write_frame(&mut writer, &subscribe.encode_to_vec()).await?;
+    println!("Watching {} (Ctrl-C to stop)...", topics.join(", "));
+
+    loop {
+        let data = match read_frame(&mut reader).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let envelope = Envelope::decode(data.as_slice())?;
+        match &envelope.payload {
+            Some(envelope::Payload::Error(err)) => bail!("{}", err.message),
+            Some(envelope::Payload::Shutdown(shutdown)) => {
+                println!("ahandd closed the connection: {}", shutdown.reason);
+                return Ok(());
+            }
+            _ => {}
+        }
+        if let Some(line) = format_observed(&envelope) {
+            println!("{line}");
+        }
+    }
+}
+
+/// One line describing an observed envelope; `None` for ones a watcher
+/// doesn't show.
+fn format_observed(envelope: &Envelope) -> Option<String> {
+    use envelope::Payload;
+    let line = match envelope.payload.as_ref()? {
+        Payload::JobRequest(req) => {
+            format!(
+                "job {} started: {}",
+                req.job_id,
+                command_line(&req.tool, &req.args)
+            )
+        }
+        Payload::JobQueued(queued) => {
+            format!(
+                "job {} queued at position {}",
+                queued.job_id, queued.position
+            )
+        }
+        Payload::JobEvent(event) => match event.event.as_ref()? {
+            job_event::Event::StdoutChunk(chunk) => {
+                prefixed_lines(&event.job_id, "", &String::from_utf8_lossy(chunk))
+            }
+            job_event::Event::StderrChunk(chunk) => {
+                prefixed_lines(&event.job_id, " stderr", &String::from_utf8_lossy(chunk))
+            }
+            job_event::Event::Progress(progress) => {
+                format!("job {} progress {progress}%", event.job_id)
+            }
+        },
+        Payload::JobFinished(finished) if finished.error.is_empty() => {
+            format!(
+                "job {} finished: exit {}",
+                finished.job_id, finished.exit_code
+            )
+        }
+        Payload::JobFinished(finished) => format!(
+            "job {} finished: exit {} ({})",
+            finished.job_id, finished.exit_code, finished.error
+        ),
+        Payload::JobRejected(rejected) => {
+            format!("job {} rejected: {}", rejected.job_id, rejected.reason)
+        }
+        Payload::ApprovalRequest(req) => format!(
+            "approval requested for job {} by {}: {} ({})",
+            req.job_id,
+            req.caller_uid,
+            command_line(&req.tool, &req.args),
+            req.reason
+        ),
+        Payload::ApprovalResolved(resolved) => format!(
+            "approval for job {} {} by {}",
+            resolved.job_id,
+            if resolved.approved {
+                "granted"
+            } else {
+                "denied"
+            },
+            resolved.resolved_by
+        ),
+        Payload::ApprovalExpired(expired) => {
+            format!("approval for job {} expired", expired.job_id)
+        }
+        Payload::SessionState(state) => format!(
+            "session {} is now {}",
+            state.caller_uid,
+            SessionMode::try_from(state.mode)
+                .map(|mode| format!("{mode:?}").to_lowercase())
+                .unwrap_or_else(|_| "unknown".to_string())
+        ),
+        Payload::PolicyState(state) if state.active_preset.is_empty() => format!(
+            "policy updated: {} allowed, {} denied tools",
+            state.allowed_tools.len(),
+            state.denied_tools.len()
+        ),
+        Payload::PolicyState(state) => format!(
+            "policy updated to preset {}: {} allowed, {} denied tools",
+            state.active_preset,
+            state.allowed_tools.len(),
+            state.denied_tools.len()
+        ),
+        _ => return None,
+    };
+    Some(line)
+}
+
+fn command_line(tool: &str, args: &[String]) -> String {
+    std::iter::once(tool)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `[job-1] line` for each line of `text`.
+fn prefixed_lines(job_id: &str, stream: &str, text: &str) -> String {
+    text.trim_end_matches('\n')
+        .lines()
+        .map(|line| format!("[{job_id}{stream}] {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahand_protocol::{JobEvent, JobFinished, JobRequest};
+
+    fn observed(payload: envelope::Payload) -> Option<String> {
+        format_observed(&Envelope {
+            payload: Some(payload),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn job_lifecycle_is_one_line_per_event() {
+        let started = observed(envelope::Payload::JobRequest(JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "git".to_string(),
+            args: vec!["status".to_string()],
+            ..Default::default()
+        }));
+        assert_eq!(started.unwrap(), "job job-1 started: git status");
+
+        let finished = observed(envelope::Payload::JobFinished(JobFinished {
+            job_id: "job-1".to_string(),
+            exit_code: 1,
+            error: "killed".to_string(),
+            ..Default::default()
+        }));
+        assert_eq!(finished.unwrap(), "job job-1 finished: exit 1 (killed)");
+    }
+
+    #[test]
+    fn output_lines_are_prefixed_with_their_job() {
+        let stdout = observed(envelope::Payload::JobEvent(JobEvent {
+            job_id: "job-1".to_string(),
+            event: Some(job_event::Event::StdoutChunk(b"one\ntwo\n".to_vec())),
+        }));
+        assert_eq!(stdout.unwrap(), "[job-1] one\n[job-1] two");
+
+        let stderr = observed(envelope::Payload::JobEvent(JobEvent {
+            job_id: "job-1".to_string(),
+            event: Some(job_event::Event::StderrChunk(b"oops".to_vec())),
+        }));
+        assert_eq!(stderr.unwrap(), "[job-1 stderr] oops");
+    }
+
+    #[test]
+    fn unrelated_payloads_are_not_shown() {
+        assert!(observed(envelope::Payload::Ping(ahand_protocol::Ping {})).is_none());
+        assert!(format_observed(&Envelope::default()).is_none());
+    }
+}
@@ -1,30 +1,139 @@
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use ahand_protocol::{
     BrowserResponse, Envelope, Heartbeat, Hello, HelloAccepted, HelloChallenge, JobFinished,
-    JobRejected, envelope, hello,
+    JobRejected, compression, envelope, hello,
 };
 use futures_util::{SinkExt, StreamExt};
 use prost::Message;
 use tokio::sync::{broadcast, mpsc};
-use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
+use tokio_tungstenite::{Connector, tungstenite};
 use tracing::{debug, error, info, warn};
 
 use tokio::sync::watch;
 
 use crate::app_tool_registry::AppToolRegistry;
-use crate::approval::ApprovalManager;
+use crate::approval::{ApprovalManager, EXPIRED_REASON};
 use crate::browser::BrowserManager;
-use crate::config::Config;
+use crate::config::{Config, HubConfig};
+use crate::connection::{ConnectionMonitor, ConnectionState};
 use crate::device_identity::DeviceIdentity;
 use crate::executor::{self, EnvelopeSink as _};
 use crate::file_manager::FileManager;
-use crate::outbox::{Outbox, prepare_outbound};
+use crate::outbox::{ACK_IDLE, OUTBOX_FILE_NAME, OUTBOX_FLUSH_INTERVAL, Outbox, prepare_outbound};
 use crate::plugin_runtime::{CapabilityKind, CapabilityUnavailable, JobProvider};
-use crate::registry::{IsKnown, JobRegistry};
+use crate::policy::PolicyChecker;
+use crate::proxy::Proxy;
+use crate::registry::{AtCapacity, IsKnown, JOB_ID_REUSE_REASON, JobRegistry, params_hash};
+use crate::send_queue::{self, Sheddable};
 use crate::session::{SessionDecision, SessionManager};
-use crate::store::{Direction, RunStore};
+use crate::store::{Direction, RunContext, RunStore};
+
+/// Unacked messages kept for replay; the oldest are dropped beyond this.
+const OUTBOX_CAPACITY: usize = 10_000;
+
+/// Default for `[hub] compress_job_output_over_bytes`.
+const DEFAULT_COMPRESS_OVER_BYTES: usize = 1024;
+
+/// Defaults for `[hub] send_queue_capacity` and `send_timeout_secs`.
+const DEFAULT_SEND_QUEUE_CAPACITY: usize = 1024;
+const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Bounds on one connection's send path.
+#[derive(Debug, Clone, Copy)]
+struct SendLimits {
+    /// Frames queued for the socket before job output chunks are dropped.
+    queue_capacity: usize,
+    /// Longest a single WebSocket write may block before the connection is
+    /// given up as stuck.
+    timeout: Duration,
+}
+
+impl SendLimits {
+    fn from_config(hub: &HubConfig) -> Self {
+        Self {
+            queue_capacity: hub
+                .send_queue_capacity
+                .unwrap_or(DEFAULT_SEND_QUEUE_CAPACITY),
+            timeout: hub.send_timeout_secs.map_or(DEFAULT_SEND_TIMEOUT, |secs| {
+                Duration::from_secs(secs.max(1))
+            }),
+        }
+    }
+}
+
+/// How long a graceful shutdown waits for the hub to answer our Close.
+const CLOSE_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The hub ended the connection with a Close frame.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("websocket closed by hub: code={code} reason={reason:?}")]
+pub struct ClosedByPeer {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl ClosedByPeer {
+    fn from_frame(frame: Option<&CloseFrame<'_>>) -> Self {
+        match frame {
+            Some(frame) => Self {
+                code: frame.code.into(),
+                reason: frame.reason.to_string(),
+            },
+            None => Self {
+                code: CloseCode::Status.into(),
+                reason: String::new(),
+            },
+        }
+    }
+
+    /// How long the hub asked us to stay away. Only honoured with 1013
+    /// (Try Again Later), whose reason may carry `retry-after=<secs>`.
+    pub fn retry_after(&self) -> Option<Duration> {
+        if self.code != u16::from(CloseCode::Again) {
+            return None;
+        }
+        self.reason
+            .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+            .find_map(|token| token.strip_prefix("retry-after="))
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+    }
+}
+
+/// The Close we send when a read fails in a way the hub should hear about;
+/// `None` for transport failures where there's no one left to tell.
+fn close_for_read_error(err: &tungstenite::Error) -> Option<(CloseCode, &'static str)> {
+    match err {
+        tungstenite::Error::Protocol(_) => Some((CloseCode::Protocol, "protocol error")),
+        tungstenite::Error::Capacity(_) => Some((CloseCode::Size, "message too big")),
+        tungstenite::Error::Utf8 => Some((CloseCode::Invalid, "invalid utf-8")),
+        _ => None,
+    }
+}
+
+/// A WebSocket write that didn't complete within [`SendLimits::timeout`].
+#[derive(Debug, thiserror::Error)]
+#[error("websocket send stalled for {0:?}")]
+struct SendStalled(Duration);
+
+/// `sink.send(msg)`, failing with [`SendStalled`] if the peer hasn't taken
+/// it within `timeout`.
+async fn send_within<S>(
+    sink: &mut S,
+    msg: tungstenite::Message,
+    timeout: Duration,
+) -> anyhow::Result<()>
+where
+    S: futures_util::Sink<tungstenite::Message, Error = tungstenite::Error> + Unpin,
+{
+    tokio::time::timeout(timeout, sink.send(msg))
+        .await
+        .map_err(|_| SendStalled(timeout))??;
+    Ok(())
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HelloAuthMode {
@@ -48,8 +157,9 @@ impl Drop for CloseGuard {
 
 #[derive(Clone)]
 struct BufferedEnvelopeSender {
-    tx: mpsc::UnboundedSender<OutboundFrame>,
+    tx: send_queue::Sender<OutboundFrame>,
     outbox: Arc<Mutex<Outbox>>,
+    monitor: Arc<ConnectionMonitor>,
 }
 
 struct QueuedEnvelope {
@@ -70,22 +180,76 @@ struct QueuedEnvelope {
 /// but is still trace-logged to RunStore so the operator can observe it.
 /// Encoding happens inside the send task so the unencoded `Envelope` is
 /// available for store logging.
+///
+/// `Close` is queued behind everything already in the channel, so frames
+/// emitted before shutdown (e.g. final `JobFinished`s) reach the hub
+/// before the WebSocket Close frame does.
 #[allow(clippy::large_enum_variant)] // Envelope is the hot path; boxing adds indirection cost
 enum OutboundFrame {
     Envelope(QueuedEnvelope),
     WsPing(Vec<u8>),
     DirectEnvelope(Envelope),
+    Close(CloseFrame<'static>),
+}
+
+/// Only job output may be dropped from a full send queue; completions,
+/// rejections, approvals and the rest always go out.
+impl Sheddable for OutboundFrame {
+    fn sheddable(&self) -> bool {
+        matches!(
+            self,
+            OutboundFrame::Envelope(QueuedEnvelope {
+                envelope: Envelope {
+                    payload: Some(envelope::Payload::JobEvent(ahand_protocol::JobEvent {
+                        event: Some(
+                            ahand_protocol::job_event::Event::StdoutChunk(_)
+                                | ahand_protocol::job_event::Event::StderrChunk(_)
+                        ),
+                        ..
+                    })),
+                    ..
+                },
+                ..
+            })
+        )
+    }
 }
 
 impl BufferedEnvelopeSender {
-    fn new(tx: mpsc::UnboundedSender<OutboundFrame>, outbox: Arc<Mutex<Outbox>>) -> Self {
-        Self { tx, outbox }
+    fn new(
+        tx: send_queue::Sender<OutboundFrame>,
+        outbox: Arc<Mutex<Outbox>>,
+        monitor: Arc<ConnectionMonitor>,
+    ) -> Self {
+        Self {
+            tx,
+            outbox,
+            monitor,
+        }
+    }
+
+    /// Queue `frame` for the send task, counting any output chunk the full
+    /// queue dropped. Returns Err only if the send task has already exited
+    /// (session tearing down).
+    fn push(&self, frame: OutboundFrame) -> Result<(), ()> {
+        if self.tx.send(frame).map_err(|_| ())?.is_some() {
+            let dropped = self.monitor.chunk_dropped();
+            if dropped == 1 || dropped.is_multiple_of(1000) {
+                warn!(dropped, "cloud send queue full, dropping job output chunks");
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve once the send task has exited.
+    async fn closed(&self) {
+        self.tx.closed().await
     }
 
     /// Send a raw WebSocket Ping. Returns Err only if the receiver task has
     /// already exited (session tearing down).
     fn send_ping(&self, payload: Vec<u8>) -> Result<(), ()> {
-        self.tx.send(OutboundFrame::WsPing(payload)).map_err(|_| ())
+        self.push(OutboundFrame::WsPing(payload))
     }
 
     /// Send an envelope WITHOUT going through the outbox sequencing/replay
@@ -99,21 +263,73 @@ impl BufferedEnvelopeSender {
     /// that must survive reconnect goes through send(); direct frames carry
     /// seq=0 and sit outside the ack/dedup window.
     fn send_direct(&self, envelope: Envelope) -> Result<(), ()> {
-        self.tx
-            .send(OutboundFrame::DirectEnvelope(envelope))
-            .map_err(|_| ())
+        self.push(OutboundFrame::DirectEnvelope(envelope))
+    }
+
+    /// Send a standalone `Ack` if the outbox says one is due at `now`.
+    fn send_ack_if_due(&self, device_id: &str, now: Instant) -> Result<(), ()> {
+        let ack = {
+            let mut outbox = self.outbox.lock().expect("outbox mutex poisoned");
+            if !outbox.needs_ack(now) {
+                return Ok(());
+            }
+            outbox.take_ack()
+        };
+        self.send_ack_frame(device_id, ack)
+    }
+
+    /// Send a standalone `Ack` for everything received so far, due or not.
+    fn send_ack(&self, device_id: &str) -> Result<(), ()> {
+        let ack = self
+            .outbox
+            .lock()
+            .expect("outbox mutex poisoned")
+            .take_ack();
+        self.send_ack_frame(device_id, ack)
+    }
+
+    /// Like heartbeats, acks go out direct: a stale ack is useless to replay.
+    fn send_ack_frame(&self, device_id: &str, ack: u64) -> Result<(), ()> {
+        self.send_direct(Envelope {
+            device_id: device_id.to_string(),
+            msg_id: new_msg_id(),
+            ts_ms: now_ms(),
+            ack,
+            payload: Some(envelope::Payload::StandaloneAck(ahand_protocol::Ack {
+                ack,
+            })),
+            ..Default::default()
+        })
+    }
+
+    /// Queue a WebSocket Close frame after every frame already queued.
+    fn send_close(&self, code: CloseCode, reason: &'static str) -> Result<(), ()> {
+        self.push(OutboundFrame::Close(CloseFrame {
+            code,
+            reason: reason.into(),
+        }))
     }
 }
 
 impl crate::executor::EnvelopeSink for BufferedEnvelopeSender {
     fn send(&self, mut envelope: Envelope) -> Result<(), ()> {
+        if matches!(envelope.payload, Some(envelope::Payload::Heartbeat(_))) {
+            // A heartbeat is a point-in-time snapshot; replaying one after a
+            // reconnect would report stale trust countdowns, so it skips the
+            // outbox. It still piggybacks our ack so the hub can trim its
+            // own buffer.
+            envelope.ack = self
+                .outbox
+                .lock()
+                .expect("outbox mutex poisoned")
+                .take_ack();
+            return self.send_direct(envelope);
+        }
         let frame = {
             let mut outbox = self.outbox.lock().expect("outbox mutex poisoned");
             prepare_outbound(&mut outbox, &mut envelope)
         };
-        self.tx
-            .send(OutboundFrame::Envelope(QueuedEnvelope { frame, envelope }))
-            .map_err(|_| ())
+        self.push(OutboundFrame::Envelope(QueuedEnvelope { frame, envelope }))
     }
 }
 
@@ -122,8 +338,9 @@ impl crate::executor::EnvelopeSink for BufferedEnvelopeSender {
 #[derive(Debug, Clone)]
 #[allow(dead_code)] // variant fields carried for future observers / SDK consumers
 pub enum ConnectOutcome {
-    /// The Hello handshake was accepted.
-    HandshakeAccepted,
+    /// The Hello handshake was accepted by the hub at `url`; `primary` is
+    /// false when that's a fallback from `server_urls`.
+    HandshakeAccepted { url: String, primary: bool },
     /// Every configured auth mode was rejected by the hub.
     HandshakeRejected(String),
     /// Transport-level failure (dial, TLS, malformed frame, …).
@@ -160,10 +377,13 @@ pub async fn run(
     store: Option<Arc<RunStore>>,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
+    policy: Arc<PolicyChecker>,
     approval_broadcast_tx: broadcast::Sender<Envelope>,
     browser_mgr: Arc<BrowserManager>,
     file_mgr: Arc<FileManager>,
     app_tools: Arc<AppToolRegistry>,
+    monitor: Arc<ConnectionMonitor>,
+    shutdown_rx: watch::Receiver<bool>,
 ) -> anyhow::Result<()> {
     run_with_reporter(
         config,
@@ -172,19 +392,26 @@ pub async fn run(
         store,
         session_mgr,
         approval_mgr,
+        policy,
         approval_broadcast_tx,
         browser_mgr,
         file_mgr,
         app_tools,
         Arc::new(NoopReporter),
+        monitor,
+        shutdown_rx,
     )
     .await
 }
 
-/// Variant of [`run`] that pushes every handshake outcome into `reporter`.
+/// Variant of [`run`] that pushes every handshake outcome into `reporter`
+/// and keeps `monitor` up to date with the connection state.
 ///
 /// Library callers (e.g. `public_api::spawn`) use this to drive a status
 /// channel without modifying the reconnect loop.
+///
+/// When `shutdown_rx` flips to `true` the current connection is closed with
+/// a WebSocket Close frame and the loop returns instead of reconnecting.
 #[allow(clippy::too_many_arguments)]
 pub async fn run_with_reporter(
     config: Config,
@@ -193,11 +420,14 @@ pub async fn run_with_reporter(
     store: Option<Arc<RunStore>>,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
+    policy: Arc<PolicyChecker>,
     approval_broadcast_tx: broadcast::Sender<Envelope>,
     browser_mgr: Arc<BrowserManager>,
     file_mgr: Arc<FileManager>,
     app_tools: Arc<AppToolRegistry>,
     reporter: Arc<dyn ClientReporter>,
+    monitor: Arc<ConnectionMonitor>,
+    mut shutdown_rx: watch::Receiver<bool>,
 ) -> anyhow::Result<()> {
     let hub_config = config.hub_config();
     let identity_path = hub_config
@@ -207,101 +437,191 @@ pub async fn run_with_reporter(
         .unwrap_or_else(crate::device_identity::default_identity_path);
     let identity = DeviceIdentity::load_or_create(&identity_path)?;
     let bearer_token = hub_config.bootstrap_token.clone();
+    // Heartbeats report uptime across reconnects, not per connection.
+    let started_at = Instant::now();
     // Prefer millisecond precision when provided (library callers thread
     // a `Duration` through), else fall back to the TOML-friendly
-    // `heartbeat_interval_secs`, else default to 60s.
+    // `heartbeat_interval_secs`, else default to 30s.
     let heartbeat_interval = match hub_config.heartbeat_interval_ms {
         Some(ms) => Duration::from_millis(ms.max(1)),
-        None => Duration::from_secs(hub_config.heartbeat_interval_secs.unwrap_or(60).max(1)),
+        None => Duration::from_secs(hub_config.heartbeat_interval_secs.unwrap_or(30).max(1)),
     };
+    let send_limits = SendLimits::from_config(&hub_config);
+    // `[tls]` switches the hub connection to rustls. Unreadable certificates
+    // or a malformed pin fail here rather than on every reconnect.
+    let tls = config.tls.as_ref().map(crate::tls::connector).transpose()?;
+    let urls = config.server_urls();
+    let proxies = urls
+        .iter()
+        .map(|url| Proxy::for_url(url, config.proxy_url.as_deref()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (url, proxy) in urls.iter().zip(&proxies) {
+        if let Some(proxy) = proxy {
+            info!(%url, %proxy, "connecting to cloud through proxy");
+        }
+    }
 
-    // Outbox survives across reconnects.
-    let outbox = Arc::new(Mutex::new(Outbox::new(10_000)));
-
-    let mut backoff = 1u64;
-
-    loop {
-        info!(url = %config.server_url, "connecting to cloud");
-
-        let attempt = connect_reporting(
-            &config.server_url,
-            &device_id,
-            &identity,
-            bearer_token.clone(),
-            heartbeat_interval,
-            &session_mgr,
-            &registry,
-            &store,
-            &outbox,
-            &approval_mgr,
-            &approval_broadcast_tx,
-            &browser_mgr,
-            &file_mgr,
-            &app_tools,
-            reporter.as_ref(),
-        )
-        .await;
-
-        match attempt {
-            Ok(()) => {
-                info!("disconnected from cloud");
-                reporter.report(ConnectOutcome::Disconnected);
-                backoff = 1;
-            }
-            Err(e) => {
-                warn!(error = %e, "connection failed");
-            }
+    // Outbox survives across reconnects, and with a data dir across restarts.
+    let outbox = match config.data_dir() {
+        Some(dir) => {
+            let path = dir.join(OUTBOX_FILE_NAME);
+            let outbox = Outbox::open(&path, OUTBOX_CAPACITY).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "failed to open outbox journal; keeping unacked messages in memory only");
+                Outbox::new(OUTBOX_CAPACITY)
+            });
+            let outbox = Arc::new(Mutex::new(outbox));
+            // Journal writes are batched; this bounds how long one waits.
+            let weak = Arc::downgrade(&outbox);
+            tokio::spawn(async move {
+                let mut tick = tokio::time::interval(OUTBOX_FLUSH_INTERVAL);
+                loop {
+                    tick.tick().await;
+                    let Some(outbox) = weak.upgrade() else { break };
+                    outbox.lock().expect("outbox mutex poisoned").flush();
+                }
+            });
+            outbox
         }
+        None => Arc::new(Mutex::new(Outbox::new(OUTBOX_CAPACITY))),
+    };
+    outbox
+        .lock()
+        .expect("outbox mutex poisoned")
+        .set_compression((hub_config.compress_job_output != Some(false)).then(|| {
+            hub_config
+                .compress_job_output_over_bytes
+                .unwrap_or(DEFAULT_COMPRESS_OVER_BYTES)
+        }));
+    monitor.attach_outbox(Arc::clone(&outbox));
+
+    // Plain references so each attempt's future borrows from this frame,
+    // not from the closure.
+    let (tls, proxies, identity, outbox) = (tls.as_ref(), &proxies, &identity, &outbox);
+    let (session_mgr, registry, store) = (&session_mgr, &registry, &store);
+    let (approval_mgr, policy, approval_broadcast_tx) =
+        (&approval_mgr, &policy, &approval_broadcast_tx);
+    let (browser_mgr, file_mgr, app_tools) = (&browser_mgr, &file_mgr, &app_tools);
+    let (device_id, bearer_token, reporter) = (&device_id, &bearer_token, &reporter);
+    let (urls, monitor) = (&urls, &monitor);
+    let client_shutdown_rx = shutdown_rx.clone();
+    let client_shutdown_rx = &client_shutdown_rx;
+
+    crate::reconnect::run(
+        urls,
+        crate::reconnect::Backoff::from_config(&hub_config),
+        monitor,
+        &mut shutdown_rx,
+        |index| async move {
+            let url = &urls[index];
+            info!(%url, "connecting to cloud");
+
+            let attempt = connect_reporting(
+                url,
+                index == 0,
+                tls,
+                proxies[index].as_ref(),
+                device_id,
+                identity,
+                bearer_token.clone(),
+                heartbeat_interval,
+                send_limits,
+                started_at,
+                session_mgr,
+                registry,
+                store,
+                outbox,
+                approval_mgr,
+                policy,
+                approval_broadcast_tx,
+                browser_mgr,
+                file_mgr,
+                app_tools,
+                reporter.as_ref(),
+                monitor,
+                client_shutdown_rx,
+            )
+            .await;
 
-        let delay = std::time::Duration::from_secs(backoff);
-        info!(delay_secs = backoff, "reconnecting after delay");
-        tokio::time::sleep(delay).await;
-        backoff = (backoff * 2).min(30);
-    }
+            let peer_close = match attempt {
+                Ok(peer_close) => {
+                    info!("disconnected from cloud");
+                    reporter.report(ConnectOutcome::Disconnected);
+                    peer_close
+                }
+                Err(e) => {
+                    warn!(%url, error = %e, "connection failed");
+                    e.downcast::<ClosedByPeer>().ok()
+                }
+            };
+            crate::reconnect::Attempt {
+                // Still `Connected` if the handshake went through: the loop
+                // only moves on to `Backoff` once this returns.
+                accepted: matches!(monitor.state(), ConnectionState::Connected { .. }),
+                retry_after: peer_close.as_ref().and_then(ClosedByPeer::retry_after),
+            }
+        },
+    )
+    .await
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn connect_reporting(
     url: &str,
+    primary: bool,
+    tls: Option<&Connector>,
+    proxy: Option<&Proxy>,
     device_id: &str,
     identity: &DeviceIdentity,
     bearer_token: Option<String>,
     heartbeat_interval: Duration,
+    send_limits: SendLimits,
+    started_at: Instant,
     session_mgr: &Arc<SessionManager>,
     registry: &Arc<JobRegistry>,
     store: &Option<Arc<RunStore>>,
     outbox: &Arc<Mutex<Outbox>>,
     approval_mgr: &Arc<ApprovalManager>,
+    policy: &Arc<PolicyChecker>,
     approval_broadcast_tx: &broadcast::Sender<Envelope>,
     browser_mgr: &Arc<BrowserManager>,
     file_mgr: &Arc<FileManager>,
     app_tools: &Arc<AppToolRegistry>,
     reporter: &dyn ClientReporter,
-) -> anyhow::Result<()> {
+    monitor: &Arc<ConnectionMonitor>,
+    shutdown_rx: &watch::Receiver<bool>,
+) -> anyhow::Result<Option<ClosedByPeer>> {
     let auth_modes = hello_auth_modes(bearer_token.as_deref());
     let mut last_handshake_error = None;
 
     for auth_mode in auth_modes {
         let outcome = connect_with_auth(
             url,
+            primary,
+            tls,
+            proxy,
             device_id,
             identity,
             &auth_mode,
             heartbeat_interval,
+            send_limits,
+            started_at,
             session_mgr,
             registry,
             store,
             outbox,
             approval_mgr,
+            policy,
             approval_broadcast_tx,
             browser_mgr,
             file_mgr,
             app_tools,
             reporter,
+            monitor,
+            shutdown_rx,
         )
         .await;
         match outcome {
-            Ok(()) => return Ok(()),
+            Ok(peer_close) => return Ok(peer_close),
             Err(ConnectError::HandshakeRejected(err)) => {
                 warn!(?auth_mode, error = %err, "hello auth rejected");
                 last_handshake_error = Some(err);
@@ -321,33 +641,42 @@ async fn connect_reporting(
 #[allow(clippy::too_many_arguments)]
 async fn connect_with_auth(
     url: &str,
+    primary: bool,
+    tls: Option<&Connector>,
+    proxy: Option<&Proxy>,
     device_id: &str,
     identity: &DeviceIdentity,
     auth_mode: &HelloAuthMode,
     heartbeat_interval: Duration,
+    send_limits: SendLimits,
+    started_at: Instant,
     session_mgr: &Arc<SessionManager>,
     registry: &Arc<JobRegistry>,
     store: &Option<Arc<RunStore>>,
     outbox: &Arc<Mutex<Outbox>>,
     approval_mgr: &Arc<ApprovalManager>,
+    policy: &Arc<PolicyChecker>,
     approval_broadcast_tx: &broadcast::Sender<Envelope>,
     browser_mgr: &Arc<BrowserManager>,
     file_mgr: &Arc<FileManager>,
     app_tools: &Arc<AppToolRegistry>,
     reporter: &dyn ClientReporter,
-) -> Result<(), ConnectError> {
+    monitor: &Arc<ConnectionMonitor>,
+    shutdown_rx: &watch::Receiver<bool>,
+) -> Result<Option<ClosedByPeer>, ConnectError> {
     // OS-level TCP keepalive is the lower-tier twin of the WS Ping/Pong
     // watchdog: the watchdog catches application-level zombies in
     // 2× heartbeat_interval; TCP keepalive catches OS-level zombies (NAT
     // rewrite, sleep/wake) in ~60s regardless of WS traffic, and is
     // critical on macOS where the default keepalive idle is 2 hours.
-    let tcp = connect_tcp_with_keepalive(url)
+    let tcp = connect_tcp_with_keepalive(url, proxy)
         .await
         .map_err(ConnectError::Session)?;
-    let (ws_stream, _) = tokio_tungstenite::client_async_tls_with_config(url, tcp, None, None)
-        .await
-        .map_err(anyhow::Error::from)
-        .map_err(ConnectError::Session)?;
+    let (ws_stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(url, tcp, None, tls.cloned())
+            .await
+            .map_err(anyhow::Error::from)
+            .map_err(ConnectError::Session)?;
     let (mut sink, mut stream) = ws_stream.split();
 
     let challenge = recv_hello_challenge(&mut stream).await?;
@@ -358,15 +687,15 @@ async fn connect_with_auth(
     let capability_router = crate::plugin_runtime::build_router(browser_mgr, file_mgr)
         .await
         .map_err(ConnectError::Session)?;
+    let capabilities = crate::capabilities::daemon_capabilities(
+        capability_router.active_wire_capabilities(),
+        store.is_some(),
+    );
     let hello = build_hello_envelope_with_capabilities(
         device_id,
         identity,
         last_ack,
-        capability_router
-            .active_wire_capabilities()
-            .into_iter()
-            .map(str::to_string)
-            .collect(),
+        capabilities.into_wire(),
         &challenge.nonce,
         match auth_mode {
             HelloAuthMode::Ed25519 => None,
@@ -377,13 +706,24 @@ async fn connect_with_auth(
     if let Some(s) = store {
         s.log_envelope(&hello, Direction::Outbound).await;
     }
-    sink.send(tungstenite::Message::Binary(data))
-        .await
-        .map_err(anyhow::Error::from)
-        .map_err(ConnectError::Session)?;
+    send_within(
+        &mut sink,
+        tungstenite::Message::Binary(data),
+        send_limits.timeout,
+    )
+    .await
+    .map_err(ConnectError::Session)?;
     let accepted = recv_hello_accepted(&mut stream).await?;
-    info!(auth_method = %accepted.auth_method, "hello accepted");
-    reporter.report(ConnectOutcome::HandshakeAccepted);
+    info!(auth_method = %accepted.auth_method, compression = %accepted.compression, "hello accepted");
+    outbox
+        .lock()
+        .expect("outbox mutex poisoned")
+        .set_peer_decompresses(accepted.compression == compression::ZSTD);
+    monitor.connected(url, primary);
+    reporter.report(ConnectOutcome::HandshakeAccepted {
+        url: url.to_string(),
+        primary,
+    });
 
     // Replay unacked messages from previous connection.
     let unacked = outbox
@@ -393,18 +733,26 @@ async fn connect_with_auth(
     if !unacked.is_empty() {
         info!(count = unacked.len(), "replaying unacked messages");
         for data in unacked {
-            sink.send(tungstenite::Message::Binary(data))
-                .await
-                .map_err(anyhow::Error::from)
-                .map_err(ConnectError::Session)?;
+            send_within(
+                &mut sink,
+                tungstenite::Message::Binary(data),
+                send_limits.timeout,
+            )
+            .await
+            .map_err(|e| {
+                if e.is::<SendStalled>() {
+                    monitor.send_timed_out();
+                }
+                ConnectError::Session(e)
+            })?;
         }
     }
 
     // Channel: executor sends Envelope objects, send task stamps + encodes + sends.
     // Multiplexed with WS-level Ping frames so the same task is the sole writer
     // to `sink` (avoids needing a Mutex around the sink).
-    let (raw_tx, mut rx) = mpsc::unbounded_channel::<OutboundFrame>();
-    let tx = BufferedEnvelopeSender::new(raw_tx, Arc::clone(outbox));
+    let (raw_tx, mut rx) = send_queue::channel::<OutboundFrame>(send_limits.queue_capacity);
+    let tx = BufferedEnvelopeSender::new(raw_tx, Arc::clone(outbox), Arc::clone(monitor));
     let store_send = store.clone();
 
     // R20: a watch channel signals connection close to detached approval
@@ -488,8 +836,28 @@ async fn connect_with_auth(
         });
     }
 
+    // Re-announce approvals still pending from before this connection. Sent
+    // direct: a fresh list is built on every reconnect.
+    for env in crate::approval::replay_pending_envelopes(approval_mgr, device_id).await {
+        let _ = tx.send_direct(env);
+    }
+    arm_restored_approvals(
+        device_id,
+        &tx,
+        session_mgr,
+        registry,
+        store,
+        approval_mgr,
+        browser_mgr,
+        file_mgr,
+    )
+    .await;
+
     // Task: receive OutboundFrame from executors + ws-ping task, stamp + encode
-    // + send over WS. Multiplexed so the sink stays single-owner.
+    // + send over WS. Multiplexed so the sink stays single-owner. A write
+    // that stalls past `send_limits.timeout` ends the task, which the read
+    // loop below takes as the end of the connection.
+    let send_monitor = Arc::clone(monitor);
     let send_handle = tokio::spawn(async move {
         while let Some(frame) = rx.recv().await {
             let msg = match frame {
@@ -509,8 +877,17 @@ async fn connect_with_auth(
                     }
                     tungstenite::Message::Binary(envelope.encode_to_vec())
                 }
+                OutboundFrame::Close(frame) => {
+                    let close = tungstenite::Message::Close(Some(frame));
+                    let _ = send_within(&mut sink, close, send_limits.timeout).await;
+                    break;
+                }
             };
-            if sink.send(msg).await.is_err() {
+            if let Err(e) = send_within(&mut sink, msg, send_limits.timeout).await {
+                if e.is::<SendStalled>() {
+                    warn!(error = %e, "cloud connection stuck, reconnecting");
+                    send_monitor.send_timed_out();
+                }
                 break;
             }
         }
@@ -522,14 +899,27 @@ async fn connect_with_auth(
     // path and happens cooperatively without a separate shutdown signal.
     let heartbeat_sender = tx.clone();
     let heartbeat_device_id = device_id.to_string();
-    let daemon_version = env!("CARGO_PKG_VERSION").to_string();
+    let heartbeat_source = HeartbeatSource {
+        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+        server_url: url.to_string(),
+        started_at,
+        session_mgr: Arc::clone(session_mgr),
+        registry: Arc::clone(registry),
+        approval_mgr: Arc::clone(approval_mgr),
+        store: store.clone(),
+    };
     let heartbeat_task = spawn_heartbeat_task(
         heartbeat_sender,
         heartbeat_device_id,
-        daemon_version,
+        heartbeat_source,
         heartbeat_interval,
     );
 
+    // Task: acknowledge received messages when nothing else we send has
+    // carried the ack for `ACK_IDLE`. The read loop covers the
+    // message-count threshold as soon as it is crossed.
+    let ack_task = spawn_ack_task(tx.clone(), device_id.to_string());
+
     // Task: WS-level Ping every `heartbeat_interval`. Tungstenite-spec-compliant
     // peers (including our hub) auto-reply with Pong, so the read loop's
     // watchdog timeout (2× heartbeat_interval) is reset on every successful
@@ -564,6 +954,33 @@ async fn connect_with_auth(
     #[cfg(feature = "disable-ws-ping")]
     let ws_ping_task: tokio::task::JoinHandle<()> = tokio::spawn(async {});
 
+    // Task: relay session changes from the broadcast channel to the cloud.
+    // Approval requests on the same channel are sent directly by the code
+    // that creates them, and changes the cloud made itself already reached
+    // it as the SetSessionMode reply, so only other SessionStates go out.
+    let session_relay_task = {
+        let relay_tx = tx.clone();
+        let mut broadcast_rx = approval_broadcast_tx.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match broadcast_rx.recv().await {
+                    Ok(env) => {
+                        let relay = matches!(
+                            &env.payload,
+                            Some(envelope::Payload::SessionState(state))
+                                if state.origin != crate::session::ORIGIN_CLOUD
+                        );
+                        if relay && relay_tx.send(env).is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    };
+
     let caller_uid = "cloud";
 
     // Register the cloud caller so session queries return it.
@@ -573,10 +990,28 @@ async fn connect_with_auth(
     // 2× heartbeat_interval, we're talking to a zombie connection. Break
     // out so the outer reconnect loop can dial a fresh socket.
     let read_timeout = heartbeat_interval.saturating_mul(2);
+    let mut shutdown_rx = shutdown_rx.clone();
+
+    // Set once we've queued a Close the hub should answer.
+    let mut closing = false;
+    // The hub's Close, if it was the one to end the connection.
+    let mut peer_close = None;
 
     // Process incoming messages.
     loop {
-        let msg = match tokio::time::timeout(read_timeout, stream.next()).await {
+        let next = tokio::select! {
+            next = tokio::time::timeout(read_timeout, stream.next()) => next,
+            _ = shutdown_requested(&mut shutdown_rx) => {
+                info!("daemon shutting down, closing cloud connection");
+                closing = tx.send_close(CloseCode::Normal, "daemon shutting down").is_ok();
+                break;
+            }
+            _ = tx.closed() => {
+                warn!("cloud send task exited, closing connection");
+                break;
+            }
+        };
+        let msg = match next {
             Ok(Some(m)) => m,
             Ok(None) => break, // stream ended
             Err(_) => {
@@ -591,23 +1026,39 @@ async fn connect_with_auth(
             Ok(m) => m,
             Err(e) => {
                 error!(error = %e, "websocket read error");
+                if let Some((code, reason)) = close_for_read_error(&e) {
+                    let _ = tx.send_close(code, reason);
+                }
                 break;
             }
         };
+        monitor.saw_inbound();
 
         let data = match msg {
             tungstenite::Message::Binary(b) => b,
-            tungstenite::Message::Close(_) => break,
+            tungstenite::Message::Close(frame) => {
+                let close = ClosedByPeer::from_frame(frame.as_ref());
+                info!(code = close.code, reason = %close.reason, "hub closed the connection");
+                peer_close = Some(close);
+                break;
+            }
+            // tungstenite queues the Pong for a hub Ping itself and flushes
+            // it on our next read, so the hub's own watchdog is answered
+            // as long as this loop keeps polling the stream.
             _ => continue,
         };
 
-        let envelope = match Envelope::decode(data.as_ref()) {
+        let mut envelope = match Envelope::decode(data.as_ref()) {
             Ok(e) => e,
             Err(e) => {
                 warn!(error = %e, "failed to decode envelope");
                 continue;
             }
         };
+        if let Err(e) = compression::decompress(&mut envelope) {
+            warn!(error = %e, "failed to decompress envelope");
+            continue;
+        }
 
         // Log inbound envelope to trace.
         if let Some(s) = store {
@@ -615,15 +1066,21 @@ async fn connect_with_auth(
         }
 
         // Update outbox with peer's seq and ack.
-        {
+        let fresh = {
             let mut ob = outbox.lock().expect("outbox mutex poisoned");
-            if envelope.seq > 0 {
-                ob.on_recv(envelope.seq);
-            }
             if envelope.ack > 0 {
                 ob.on_peer_ack(envelope.ack);
             }
+            ob.on_recv(envelope.seq)
+        };
+        if !fresh {
+            // A retransmission of something already handled (e.g. replayed
+            // after a reconnect). Re-ack it so the peer stops resending.
+            debug!(seq = envelope.seq, "dropping duplicate inbound envelope");
+            let _ = tx.send_ack(device_id);
+            continue;
         }
+        let _ = tx.send_ack_if_due(device_id, Instant::now());
 
         match envelope.payload {
             Some(envelope::Payload::JobRequest(req)) => {
@@ -631,11 +1088,12 @@ async fn connect_with_auth(
                     req,
                     device_id,
                     caller_uid,
-                    &tx,
+                    &registry.observed(tx.clone()),
                     session_mgr,
                     registry,
                     store,
                     approval_mgr,
+                    policy,
                     approval_broadcast_tx,
                     browser_mgr,
                     file_mgr,
@@ -646,6 +1104,20 @@ async fn connect_with_auth(
                 info!(job_id = %cancel.job_id, "received cancel request");
                 registry.cancel(&cancel.job_id).await;
             }
+            Some(envelope::Payload::CancelAll(cancel)) => {
+                info!(caller_uid = %cancel.caller_uid, "received cancel-all request");
+                let filter = (!cancel.caller_uid.is_empty()).then_some(cancel.caller_uid.as_str());
+                let cancelled = registry.cancel_all(filter).await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::CancelAllResult(
+                        ahand_protocol::CancelAllResult { cancelled },
+                    )),
+                    ..Default::default()
+                });
+            }
             Some(envelope::Payload::ApprovalResponse(resp)) => {
                 info!(job_id = %resp.job_id, approved = resp.approved, "received approval response from cloud");
                 crate::approval::apply_approval_response(
@@ -653,15 +1125,134 @@ async fn connect_with_auth(
                     session_mgr,
                     &resp,
                     caller_uid,
+                    device_id,
+                    approval_broadcast_tx,
+                )
+                .await;
+            }
+            Some(envelope::Payload::ApprovalBulkResponse(bulk)) => {
+                info!(tool = %bulk.tool, approved = bulk.approved, "received bulk approval response from cloud");
+                let resolved = crate::approval::apply_bulk_approval_response(
+                    approval_mgr,
+                    session_mgr,
+                    &bulk,
+                    caller_uid,
+                    device_id,
+                    approval_broadcast_tx,
                 )
                 .await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::ApprovalBulkResult(
+                        ahand_protocol::ApprovalBulkResult {
+                            tool: bulk.tool,
+                            resolved,
+                        },
+                    )),
+                    ..Default::default()
+                });
             }
             Some(envelope::Payload::SetSessionMode(msg)) => {
-                handle_set_session_mode(device_id, session_mgr, &msg, &tx).await;
+                handle_set_session_mode(device_id, session_mgr, approval_broadcast_tx, &msg, &tx)
+                    .await;
             }
             Some(envelope::Payload::SessionQuery(query)) => {
                 handle_session_query(device_id, session_mgr, &query, &tx).await;
             }
+            Some(envelope::Payload::ClearSession(msg)) => {
+                handle_clear_session(device_id, session_mgr, &msg, &tx).await;
+            }
+            Some(envelope::Payload::PendingApprovalsQuery(_)) => {
+                info!("received pending approvals query");
+                let requests = approval_mgr.list_pending().await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::PendingApprovalsState(
+                        ahand_protocol::PendingApprovalsState { requests },
+                    )),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::StandaloneAck(ack)) => {
+                outbox
+                    .lock()
+                    .expect("outbox mutex poisoned")
+                    .on_peer_ack(ack.ack);
+            }
+            Some(envelope::Payload::Ping(_)) => {
+                let _ = tx.send(Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::Pong(ahand_protocol::Pong {
+                        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+                        uptime_ms: started_at.elapsed().as_millis() as u64,
+                        connected_to_cloud: true,
+                        active_jobs: registry.active_count().await as u32,
+                        cloud_url: url.to_string(),
+                        cloud_failover: !primary,
+                    })),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::PolicyQuery(_)) => {
+                info!("received policy query");
+                send_policy_state(device_id, policy, &tx).await;
+            }
+            Some(envelope::Payload::PolicyUpdate(update)) => {
+                info!("received policy update");
+                let payload = match policy.apply_update(&update).await {
+                    Ok(state) => envelope::Payload::PolicyState(state),
+                    Err(e) => {
+                        warn!(error = %e, "policy update rejected");
+                        envelope::Payload::Error(ahand_protocol::Error {
+                            code: e.code().to_string(),
+                            message: e.to_string(),
+                            ref_msg_id: envelope.msg_id.clone(),
+                        })
+                    }
+                };
+                let reply = Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(payload),
+                    ..Default::default()
+                };
+                if matches!(reply.payload, Some(envelope::Payload::PolicyState(_))) {
+                    registry.publish(&reply);
+                }
+                let _ = tx.send(reply);
+            }
+            Some(envelope::Payload::SetPolicyPreset(msg)) => {
+                info!(preset = %msg.name, "received set policy preset");
+                match policy.apply_preset(&msg.name, msg.keep_approvals).await {
+                    Ok(()) => registry.publish(&Envelope {
+                        device_id: device_id.to_string(),
+                        msg_id: new_msg_id(),
+                        ts_ms: now_ms(),
+                        payload: Some(envelope::Payload::PolicyState(policy.get_state().await)),
+                        ..Default::default()
+                    }),
+                    Err(e) => warn!(error = %e, "policy preset not applied"),
+                }
+                send_policy_state(device_id, policy, &tx).await;
+            }
+            Some(envelope::Payload::PolicyCheckRequest(check)) => {
+                let result =
+                    crate::policy::dry_run_check(policy, session_mgr, &check, caller_uid).await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::PolicyCheckResult(result)),
+                    ..Default::default()
+                });
+            }
             Some(envelope::Payload::BrowserRequest(req)) => {
                 handle_browser_request(
                     device_id,
@@ -671,6 +1262,7 @@ async fn connect_with_auth(
                     session_mgr,
                     browser_mgr,
                     file_mgr,
+                    store,
                 )
                 .await;
             }
@@ -760,7 +1352,7 @@ async fn connect_with_auth(
     //    task's mpsc receiver would still see a live sender and block on
     //    `rx.recv()` indefinitely until the WS broke naturally. Aborting
     //    the task drops its clone.
-    // 3. Same logic for `ws_ping_task`.
+    // 3. Same logic for `ws_ping_task` and `session_relay_task`.
     // 4. The app-tools watcher task holds `watcher_tx` (a clone of `tx`)
     //    and exits via `watcher_close_rx.changed()`. Dropping `_close_guard`
     //    fires `close_tx`, which wakes the watcher so it releases its clone
@@ -769,12 +1361,75 @@ async fn connect_with_auth(
     drop(_close_guard);
     heartbeat_task.abort();
     let _ = heartbeat_task.await;
+    ack_task.abort();
+    let _ = ack_task.await;
     ws_ping_task.abort();
     let _ = ws_ping_task.await;
+    session_relay_task.abort();
+    let _ = session_relay_task.await;
     drop(tx);
     let _ = send_handle.await;
 
-    Ok(())
+    // Give the hub a moment to answer our Close so it sees a clean closing
+    // handshake rather than a dropped socket.
+    if closing {
+        let _ = tokio::time::timeout(CLOSE_HANDSHAKE_TIMEOUT, async {
+            while let Some(Ok(msg)) = stream.next().await {
+                if let tungstenite::Message::Close(frame) = msg {
+                    let close = ClosedByPeer::from_frame(frame.as_ref());
+                    debug!(code = close.code, reason = %close.reason, "hub acknowledged close");
+                    break;
+                }
+            }
+        })
+        .await;
+    }
+
+    Ok(peer_close)
+}
+
+/// Resolve once the daemon-wide shutdown flag becomes `true`. A dropped
+/// sender means shutdown can no longer be requested, so this never resolves.
+pub(crate) async fn shutdown_requested(shutdown_rx: &mut watch::Receiver<bool>) {
+    if shutdown_rx.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// State sampled into every `Heartbeat` so the cloud can show uptime, load
+/// and trust countdowns without polling.
+struct HeartbeatSource {
+    daemon_version: String,
+    /// The hub URL this connection went to.
+    server_url: String,
+    started_at: Instant,
+    session_mgr: Arc<SessionManager>,
+    registry: Arc<JobRegistry>,
+    approval_mgr: Arc<ApprovalManager>,
+    store: Option<Arc<RunStore>>,
+}
+
+impl HeartbeatSource {
+    async fn snapshot(&self) -> Heartbeat {
+        let (pending_approvals, oldest_pending_approval_age_ms) =
+            self.approval_mgr.pending_summary().await;
+        let runs_gc = match &self.store {
+            Some(store) => store.last_runs_gc().await,
+            None => None,
+        };
+        Heartbeat {
+            sent_at_ms: now_ms(),
+            daemon_version: self.daemon_version.clone(),
+            uptime_ms: self.started_at.elapsed().as_millis() as u64,
+            active_jobs: self.registry.active_count().await as u32,
+            sessions: self.session_mgr.query_sessions("").await,
+            connection_mode: "ahand-cloud".to_string(),
+            server_url: self.server_url.clone(),
+            pending_approvals,
+            oldest_pending_approval_age_ms,
+            runs_gc,
+        }
+    }
 }
 
 /// Spawn the heartbeat-emission task.
@@ -785,7 +1440,7 @@ async fn connect_with_auth(
 fn spawn_heartbeat_task<S>(
     heartbeat_sender: S,
     device_id: String,
-    daemon_version: String,
+    source: HeartbeatSource,
     interval: Duration,
 ) -> tokio::task::JoinHandle<()>
 where
@@ -803,10 +1458,7 @@ where
                 device_id: device_id.clone(),
                 msg_id: new_msg_id(),
                 ts_ms: now_ms(),
-                payload: Some(envelope::Payload::Heartbeat(Heartbeat {
-                    sent_at_ms: now_ms(),
-                    daemon_version: daemon_version.clone(),
-                })),
+                payload: Some(envelope::Payload::Heartbeat(source.snapshot().await)),
                 ..Default::default()
             };
             if heartbeat_sender.send(envelope).is_err() {
@@ -818,7 +1470,25 @@ where
     })
 }
 
-/// Dial a TcpStream to the host:port from `url_str` and apply OS-level
+/// Spawn the task that sends standalone `Ack`s once `ACK_IDLE` has passed
+/// without outbound traffic. Exits when the sender closes or on abort.
+fn spawn_ack_task(
+    sender: BufferedEnvelopeSender,
+    device_id: String,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(ACK_IDLE / 5);
+        loop {
+            ticker.tick().await;
+            if sender.send_ack_if_due(&device_id, Instant::now()).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Dial a TcpStream to the host:port from `url_str` (through `proxy` when
+/// one is configured) and apply OS-level
 /// keepalive: 30s idle, 10s probe interval, 3 retries (≈60s to detect a
 /// dead peer). On macOS the kernel default is 2h idle, so without this
 /// the daemon would stay attached to a zombie socket for hours after a
@@ -826,20 +1496,12 @@ where
 ///
 /// Returns the tokio TcpStream so the caller can layer WebSocket (and
 /// optional TLS) on top via `client_async_tls_with_config`.
-async fn connect_tcp_with_keepalive(url_str: &str) -> anyhow::Result<tokio::net::TcpStream> {
+async fn connect_tcp_with_keepalive(
+    url_str: &str,
+    proxy: Option<&Proxy>,
+) -> anyhow::Result<tokio::net::TcpStream> {
     use anyhow::Context;
-    let parsed = url::Url::parse(url_str).context("invalid websocket url")?;
-    let host = parsed
-        .host_str()
-        .ok_or_else(|| anyhow::anyhow!("websocket url missing host: {url_str}"))?;
-    let port = parsed.port().unwrap_or_else(|| match parsed.scheme() {
-        "wss" => 443,
-        _ => 80,
-    });
-    let addr = format!("{host}:{port}");
-    let stream = tokio::net::TcpStream::connect(&addr)
-        .await
-        .with_context(|| format!("failed to dial {addr}"))?;
+    let stream = crate::proxy::connect(url_str, proxy).await?;
     apply_tcp_keepalive(&stream).context("set TCP keepalive options")?;
     Ok(stream)
 }
@@ -960,6 +1622,11 @@ pub fn build_hello_envelope_with_capabilities(
         os: std::env::consts::OS.to_string(),
         capabilities: hello_capabilities_from_wire_names(capabilities),
         last_ack,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![compression::ZSTD.to_string()],
         auth: None,
     };
 
@@ -1072,10 +1739,56 @@ fn managed_runtime_interactive_rejection(
     }
 }
 
+fn shutting_down_rejection(device_id: &str, req: &ahand_protocol::JobRequest) -> Envelope {
+    warn!(job_id = %req.job_id, "job rejected because the daemon is shutting down");
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRejected(JobRejected {
+            job_id: req.job_id.clone(),
+            reason: "daemon shutting down".to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn at_capacity_rejection(
+    device_id: &str,
+    req: &ahand_protocol::JobRequest,
+    full: &AtCapacity,
+) -> Envelope {
+    warn!(job_id = %req.job_id, queued = full.queued, "job rejected: queue limit reached");
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRejected(JobRejected {
+            job_id: req.job_id.clone(),
+            reason: full.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn job_id_reuse_rejection(device_id: &str, req: &ahand_protocol::JobRequest) -> Envelope {
+    warn!(job_id = %req.job_id, "job rejected: job_id reused with different parameters");
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRejected(JobRejected {
+            job_id: req.job_id.clone(),
+            reason: JOB_ID_REUSE_REASON.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
 /// Handle an incoming JobRequest with idempotency + session mode check.
 #[allow(clippy::too_many_arguments)]
 async fn handle_job_request<T>(
-    req: ahand_protocol::JobRequest,
+    mut req: ahand_protocol::JobRequest,
     device_id: &str,
     caller_uid: &str,
     tx: &T,
@@ -1083,6 +1796,7 @@ async fn handle_job_request<T>(
     registry: &Arc<JobRegistry>,
     store: &Option<Arc<RunStore>>,
     approval_mgr: &Arc<ApprovalManager>,
+    policy: &Arc<PolicyChecker>,
     approval_broadcast_tx: &broadcast::Sender<Envelope>,
     browser_mgr: &Arc<BrowserManager>,
     file_mgr: &Arc<FileManager>,
@@ -1115,7 +1829,11 @@ async fn handle_job_request<T>(
     }
 
     // Idempotency check.
-    match registry.is_known(&req.job_id).await {
+    match registry.is_known(&req.job_id, &params_hash(&req)).await {
+        IsKnown::Conflict => {
+            let _ = tx.send(job_id_reuse_rejection(device_id, &req));
+            return;
+        }
         IsKnown::Running => {
             warn!(job_id = %req.job_id, "duplicate job_id, already running — ignoring");
             return;
@@ -1130,6 +1848,7 @@ async fn handle_job_request<T>(
                     job_id: req.job_id.clone(),
                     exit_code: c.exit_code,
                     error: c.error,
+                    ..Default::default()
                 })),
                 ..Default::default()
             };
@@ -1139,10 +1858,22 @@ async fn handle_job_request<T>(
         IsKnown::Unknown => {}
     }
 
-    // Session mode check.
-    match session_mgr.check(&req, caller_uid).await {
+    req.priority = session_mgr
+        .effective_priority(caller_uid, req.priority)
+        .await;
+
+    // Policy, then session mode.
+    match crate::policy::check_job(policy, session_mgr, &req, caller_uid).await {
         SessionDecision::Deny(reason) => {
-            warn!(job_id = %req.job_id, reason = %reason, "job rejected by session mode");
+            warn!(job_id = %req.job_id, reason = %reason, "job rejected by policy or session mode");
+            if let Some(st) = store {
+                let context = RunContext {
+                    session_mode: Some(session_mgr.mode_name(caller_uid).await.to_string()),
+                    ..RunContext::new(caller_uid)
+                };
+                st.reject_run(&req, caller_uid, &context.rejected(&reason))
+                    .await;
+            }
             let reject_env = Envelope {
                 device_id: device_id.to_string(),
                 msg_id: new_msg_id(),
@@ -1156,97 +1887,250 @@ async fn handle_job_request<T>(
             let _ = tx.send(reject_env);
         }
         SessionDecision::Allow => {
-            spawn_job(device_id, req, job_provider, tx, registry, store).await;
+            let context = RunContext {
+                session_mode: Some(session_mgr.mode_name(caller_uid).await.to_string()),
+                ..RunContext::new(caller_uid)
+            };
+            spawn_job(device_id, context, req, job_provider, tx, registry, store).await;
         }
         SessionDecision::NeedsApproval {
             reason,
             previous_refusals,
+            previous_approvals,
         } => {
-            info!(job_id = %req.job_id, reason = %reason, "job needs approval (strict mode)");
+            info!(job_id = %req.job_id, reason = %reason, "job needs approval");
 
             let (approval_req, approval_rx) = approval_mgr
-                .submit(req.clone(), caller_uid, reason, previous_refusals)
+                .submit(
+                    req.clone(),
+                    caller_uid,
+                    reason,
+                    crate::policy::extract_destinations(&req),
+                    previous_refusals,
+                    previous_approvals,
+                )
                 .await;
 
-            // Send ApprovalRequest to cloud via WS.
-            let approval_env = Envelope {
-                device_id: device_id.to_string(),
-                msg_id: new_msg_id(),
-                ts_ms: now_ms(),
-                payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
-                ..Default::default()
-            };
-            let _ = tx.send(approval_env.clone());
-
-            // Broadcast to all IPC clients.
-            let _ = approval_broadcast_tx.send(approval_env);
+            // Send ApprovalRequest to cloud via WS and broadcast to all IPC
+            // clients — unless it was attached to an identical pending one.
+            if approval_req.job_id == req.job_id {
+                approval_mgr.persist_across_restart(&req.job_id).await;
+                let approval_env = Envelope {
+                    device_id: device_id.to_string(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
+                    ..Default::default()
+                };
+                let _ = tx.send(approval_env.clone());
+                let _ = approval_broadcast_tx.send(approval_env);
+            }
 
             // Spawn a task to wait for approval.
-            let tx_clone = (*tx).clone();
-            let did = device_id.to_string();
-            let reg = Arc::clone(registry);
-            let st = store.clone();
-            let amgr = Arc::clone(approval_mgr);
-            let smgr = Arc::clone(session_mgr);
-            let timeout = amgr.default_timeout();
-            let job_id = req.job_id.clone();
-            let cuid = caller_uid.to_string();
-            let job_provider = job_provider.clone();
+            let context = RunContext {
+                session_mode: Some(session_mgr.mode_name(caller_uid).await.to_string()),
+                ..RunContext::new(caller_uid)
+            };
+            spawn_approval_waiter(
+                device_id,
+                context,
+                req,
+                job_provider,
+                approval_rx,
+                now_ms(),
+                approval_mgr.default_timeout(),
+                tx,
+                registry,
+                store,
+                approval_mgr,
+                session_mgr,
+            );
+        }
+    }
+}
 
-            tokio::spawn(async move {
-                let result = tokio::time::timeout(timeout, approval_rx).await;
-                match result {
-                    Ok(Ok(resp)) if resp.approved => {
-                        info!(job_id = %job_id, "approval granted");
-                        spawn_job(&did, req, job_provider, &tx_clone, &reg, &st).await;
-                    }
-                    Ok(Ok(resp)) => {
-                        // Denied — record refusal if reason provided.
-                        info!(job_id = %job_id, "approval denied");
-                        if !resp.reason.is_empty() {
-                            smgr.record_refusal(&cuid, &req.tool, &resp.reason).await;
-                        }
-                        amgr.expire(&job_id).await;
-                        let reject_env = Envelope {
-                            device_id: did,
-                            msg_id: new_msg_id(),
-                            ts_ms: now_ms(),
-                            payload: Some(envelope::Payload::JobRejected(JobRejected {
-                                job_id,
-                                reason: if resp.reason.is_empty() {
-                                    "approval denied".to_string()
-                                } else {
-                                    format!("approval denied: {}", resp.reason)
-                                },
-                            })),
-                            ..Default::default()
-                        };
-                        let _ = tx_clone.send(reject_env);
-                    }
-                    _ => {
-                        info!(job_id = %job_id, "approval timed out");
-                        amgr.expire(&job_id).await;
-                        let reject_env = Envelope {
-                            device_id: did,
-                            msg_id: new_msg_id(),
-                            ts_ms: now_ms(),
-                            payload: Some(envelope::Payload::JobRejected(JobRejected {
-                                job_id,
-                                reason: "approval timed out".to_string(),
-                            })),
-                            ..Default::default()
-                        };
-                        let _ = tx_clone.send(reject_env);
-                    }
+/// Wait for the answer to a job's approval request, then spawn the job or
+/// reject it. Shared by fresh requests and ones restored after a restart.
+/// `submitted_ms` is when the approval was requested, for the run's
+/// recorded approval latency.
+#[allow(clippy::too_many_arguments)]
+fn spawn_approval_waiter<T>(
+    device_id: &str,
+    mut context: RunContext,
+    req: ahand_protocol::JobRequest,
+    job_provider: JobProvider,
+    approval_rx: tokio::sync::oneshot::Receiver<ahand_protocol::ApprovalResponse>,
+    submitted_ms: u64,
+    timeout: Duration,
+    tx: &T,
+    registry: &Arc<JobRegistry>,
+    store: &Option<Arc<RunStore>>,
+    approval_mgr: &Arc<ApprovalManager>,
+    session_mgr: &Arc<SessionManager>,
+) where
+    T: crate::executor::EnvelopeSink,
+{
+    let tx_clone = (*tx).clone();
+    let did = device_id.to_string();
+    let reg = Arc::clone(registry);
+    let st = store.clone();
+    let amgr = Arc::clone(approval_mgr);
+    let smgr = Arc::clone(session_mgr);
+    let job_id = req.job_id.clone();
+
+    tokio::spawn(async move {
+        let result = tokio::time::timeout(timeout, approval_rx).await;
+        context.approval_latency_ms = Some(now_ms().saturating_sub(submitted_ms));
+        match result {
+            Ok(Ok(resp)) if resp.approved => {
+                info!(job_id = %job_id, "approval granted");
+                context.approved_by = Some(resp.resolved_by).filter(|by| !by.is_empty());
+                spawn_job(&did, context, req, job_provider, &tx_clone, &reg, &st).await;
+            }
+            Ok(Ok(resp)) if resp.reason != EXPIRED_REASON => {
+                // Denied — record refusal if reason provided.
+                info!(job_id = %job_id, "approval denied");
+                if !resp.reason.is_empty() {
+                    smgr.record_refusal(&context.caller_uid, &req.tool, &resp.reason)
+                        .await;
                 }
-            });
+                amgr.expire(&job_id).await;
+                let reason = if resp.reason.is_empty() {
+                    "approval denied".to_string()
+                } else {
+                    format!("approval denied: {}", resp.reason)
+                };
+                if let Some(st) = &st {
+                    st.reject_run(&req, &context.caller_uid, &context.rejected(&reason))
+                        .await;
+                }
+                let reject_env = Envelope {
+                    device_id: did,
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::JobRejected(JobRejected {
+                        job_id,
+                        reason,
+                    })),
+                    ..Default::default()
+                };
+                let _ = tx_clone.send(reject_env);
+            }
+            _ => {
+                info!(job_id = %job_id, "approval timed out");
+                amgr.expire(&job_id).await;
+                if let Some(st) = &st {
+                    st.reject_run(
+                        &req,
+                        &context.caller_uid,
+                        &context.rejected("approval timed out"),
+                    )
+                    .await;
+                }
+                let reject_env = Envelope {
+                    device_id: did,
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::JobRejected(JobRejected {
+                        job_id,
+                        reason: "approval timed out".to_string(),
+                    })),
+                    ..Default::default()
+                };
+                let _ = tx_clone.send(reject_env);
+            }
         }
+    });
+}
+
+/// Re-arm approvals a previous run persisted: wait on the unexpired ones as
+/// `handle_job_request` would have, and reject the ones that expired while
+/// the daemon was down. Only the first connection after startup gets any.
+#[allow(clippy::too_many_arguments)]
+async fn arm_restored_approvals<T>(
+    device_id: &str,
+    tx: &T,
+    session_mgr: &Arc<SessionManager>,
+    registry: &Arc<JobRegistry>,
+    store: &Option<Arc<RunStore>>,
+    approval_mgr: &Arc<ApprovalManager>,
+    browser_mgr: &Arc<BrowserManager>,
+    file_mgr: &Arc<FileManager>,
+) where
+    T: crate::executor::EnvelopeSink,
+{
+    let restored = approval_mgr.take_restored().await;
+    for dropped in &restored.dropped {
+        let req = &dropped.request;
+        info!(job_id = %req.job_id, reason = dropped.reason, "approval not restored after daemon restart");
+        if let Some(st) = store {
+            let context = RunContext {
+                session_mode: Some(session_mgr.mode_name(&dropped.caller_uid).await.to_string()),
+                ..RunContext::new(&dropped.caller_uid)
+            };
+            st.reject_run(req, &dropped.caller_uid, &context.rejected(dropped.reason))
+                .await;
+        }
+        let _ = tx.send(Envelope {
+            device_id: device_id.to_string(),
+            msg_id: new_msg_id(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::JobRejected(JobRejected {
+                job_id: req.job_id.clone(),
+                reason: dropped.reason.to_string(),
+            })),
+            ..Default::default()
+        });
+    }
+    if restored.waiting.is_empty() {
+        return;
+    }
+
+    let provider_registry =
+        crate::plugin_runtime::build_provider_registry(browser_mgr, file_mgr).await;
+    for waiting in restored.waiting {
+        let job_provider = match &provider_registry {
+            Ok(registry) => registry
+                .resolve_job_provider(&waiting.request.tool)
+                .map_err(|err| err.to_protocol_message()),
+            Err(err) => Err(format!(
+                "exec capability unavailable: failed to inspect host resources: {err}"
+            )),
+        };
+        let job_provider = match job_provider {
+            Ok(provider) => provider,
+            Err(reason) => {
+                approval_mgr.expire(&waiting.request.job_id).await;
+                reject_job_for_capability_error(device_id, &waiting.request, tx, reason);
+                continue;
+            }
+        };
+        info!(job_id = %waiting.request.job_id, "re-armed approval restored from disk");
+        let context = RunContext {
+            session_mode: Some(session_mgr.mode_name(&waiting.caller_uid).await.to_string()),
+            ..RunContext::new(&waiting.caller_uid)
+        };
+        spawn_approval_waiter(
+            device_id,
+            context,
+            waiting.request,
+            job_provider,
+            waiting.approval_rx,
+            waiting.submitted_ms,
+            Duration::from_millis(waiting.expires_ms.saturating_sub(now_ms())),
+            tx,
+            registry,
+            store,
+            approval_mgr,
+            session_mgr,
+        );
     }
 }
 
 /// Spawn a job execution task.
 async fn spawn_job<T>(
     device_id: &str,
+    context: RunContext,
     req: ahand_protocol::JobRequest,
     provider: JobProvider,
     tx: &T,
@@ -1260,7 +2144,10 @@ async fn spawn_job<T>(
     let did = device_id.to_string();
     let reg = Arc::clone(registry);
     let st = store.clone();
+    let caller_uid = context.caller_uid.clone();
     let interactive = req.interactive;
+    let params_hash = params_hash(&req);
+    let priority = req.priority;
 
     let (cancel_tx, cancel_rx) = mpsc::channel(1);
 
@@ -1270,38 +2157,88 @@ async fn spawn_job<T>(
             return;
         }
 
-        let (stdin_tx, stdin_rx) = mpsc::unbounded_channel::<executor::StdinInput>();
-        reg.register_interactive(job_id.clone(), cancel_tx, stdin_tx)
-            .await;
-
+        let admission = match reg.admit(priority) {
+            Ok(admission) => admission,
+            Err(full) => {
+                let _ = tx.send(at_capacity_rejection(device_id, &req, &full));
+                return;
+            }
+        };
+        let (stdin_tx, stdin_rx) = mpsc::unbounded_channel::<executor::StdinInput>();
+        if !reg
+            .register_interactive(
+                job_id.clone(),
+                &caller_uid,
+                params_hash,
+                cancel_tx,
+                stdin_tx,
+            )
+            .await
+        {
+            let _ = tx.send(shutting_down_rejection(device_id, &req));
+            return;
+        }
+
         let active = reg.active_count().await;
-        info!(job_id = %job_id, active_jobs = active, interactive = true, "interactive job accepted, acquiring permit");
+        let queue_position = admission.position();
+        info!(job_id = %job_id, active_jobs = active, ?queue_position, interactive = true, "interactive job accepted");
 
         tokio::spawn(async move {
-            let _permit = reg.acquire_permit().await;
-            let (exit_code, error) =
-                executor::run_job_pty(did, req, tx_clone, cancel_rx, stdin_rx, st).await;
+            reg.publish_started(&did, &req);
+            let tx_clone = reg.tee(&job_id, tx_clone);
+            let permit = reg
+                .wait_for_permit(admission, &did, &job_id, &tx_clone)
+                .await;
+            let queued_ms = permit.queued_ms();
+            let (exit_code, error) = executor::run_job_pty(
+                did, req, context, tx_clone, cancel_rx, stdin_rx, st, queued_ms,
+            )
+            .await;
             reg.remove(&job_id).await;
-            reg.mark_completed(job_id, exit_code, error).await;
+            reg.mark_completed(job_id, params_hash, exit_code, error)
+                .await;
         });
     } else {
-        reg.register(job_id.clone(), cancel_tx).await;
+        let admission = match reg.admit(priority) {
+            Ok(admission) => admission,
+            Err(full) => {
+                let _ = tx.send(at_capacity_rejection(device_id, &req, &full));
+                return;
+            }
+        };
+        if !reg
+            .register(job_id.clone(), &caller_uid, params_hash, cancel_tx)
+            .await
+        {
+            let _ = tx.send(shutting_down_rejection(device_id, &req));
+            return;
+        }
 
         let active = reg.active_count().await;
-        info!(job_id = %job_id, active_jobs = active, "job accepted, acquiring permit");
+        let queue_position = admission.position();
+        info!(job_id = %job_id, active_jobs = active, ?queue_position, "job accepted");
 
         tokio::spawn(async move {
-            let _permit = reg.acquire_permit().await;
+            reg.publish_started(&did, &req);
+            let tx_clone = reg.tee(&job_id, tx_clone);
+            let permit = reg
+                .wait_for_permit(admission, &did, &job_id, &tx_clone)
+                .await;
+            let queued_ms = permit.queued_ms();
             let (exit_code, error) = match provider {
                 JobProvider::DefaultExec => {
-                    executor::run_job(did, req, tx_clone, cancel_rx, st).await
+                    executor::run_job(did, req, context, tx_clone, cancel_rx, st, queued_ms).await
                 }
                 JobProvider::ManagedRuntime { target, .. } => {
-                    executor::run_job_with_target(did, req, target, tx_clone, cancel_rx, st).await
+                    executor::run_job_with_target(
+                        did, req, context, target, tx_clone, cancel_rx, st, queued_ms,
+                    )
+                    .await
                 }
             };
             reg.remove(&job_id).await;
-            reg.mark_completed(job_id, exit_code, error).await;
+            reg.mark_completed(job_id, params_hash, exit_code, error)
+                .await;
         });
     }
 }
@@ -1309,6 +2246,7 @@ async fn spawn_job<T>(
 async fn handle_set_session_mode<T>(
     device_id: &str,
     session_mgr: &Arc<SessionManager>,
+    approval_broadcast_tx: &broadcast::Sender<Envelope>,
     msg: &ahand_protocol::SetSessionMode,
     tx: &T,
 ) where
@@ -1318,8 +2256,19 @@ async fn handle_set_session_mode<T>(
         .unwrap_or(ahand_protocol::SessionMode::Inactive);
     info!(caller_uid = %msg.caller_uid, ?mode, "received set session mode");
     let state = session_mgr
-        .set_mode(&msg.caller_uid, mode, msg.trust_timeout_mins)
+        .set_mode_with_budget(
+            &msg.caller_uid,
+            mode,
+            msg.trust_timeout_mins,
+            msg.trust_job_budget,
+        )
         .await;
+    crate::session::broadcast_session_state(
+        approval_broadcast_tx,
+        device_id,
+        state.clone(),
+        crate::session::ORIGIN_CLOUD,
+    );
     let state_env = Envelope {
         device_id: device_id.to_string(),
         msg_id: new_msg_id(),
@@ -1330,6 +2279,26 @@ async fn handle_set_session_mode<T>(
     let _ = tx.send(state_env);
 }
 
+async fn handle_clear_session<T>(
+    device_id: &str,
+    session_mgr: &Arc<SessionManager>,
+    msg: &ahand_protocol::ClearSession,
+    tx: &T,
+) where
+    T: crate::executor::EnvelopeSink,
+{
+    info!(caller_uid = %msg.caller_uid, "received clear session");
+    session_mgr.clear_session(&msg.caller_uid).await;
+    let state = session_mgr.get_session_state(&msg.caller_uid).await;
+    let _ = tx.send(Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::SessionState(state)),
+        ..Default::default()
+    });
+}
+
 async fn handle_session_query<T>(
     device_id: &str,
     session_mgr: &Arc<SessionManager>,
@@ -1352,6 +2321,20 @@ async fn handle_session_query<T>(
     }
 }
 
+async fn send_policy_state<T>(device_id: &str, policy: &PolicyChecker, tx: &T)
+where
+    T: crate::executor::EnvelopeSink,
+{
+    let _ = tx.send(Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::PolicyState(policy.get_state().await)),
+        ..Default::default()
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_browser_request<T>(
     device_id: &str,
     caller_uid: &str,
@@ -1360,6 +2343,7 @@ async fn handle_browser_request<T>(
     session_mgr: &Arc<SessionManager>,
     browser_mgr: &Arc<BrowserManager>,
     file_mgr: &Arc<FileManager>,
+    store: &Option<Arc<RunStore>>,
 ) where
     T: crate::executor::EnvelopeSink,
 {
@@ -1474,6 +2458,19 @@ async fn handle_browser_request<T>(
                 )
                 .await;
 
+            if let (Some(store), Ok(r)) = (store, &result)
+                && let (Some(path), Some(mime)) = (&r.output_path, r.output_mime())
+            {
+                store.record_browser_artifact(
+                    &req.session_id,
+                    &req.action,
+                    &req.params_json,
+                    caller_uid,
+                    path,
+                    mime,
+                );
+            }
+
             let resp = match result {
                 Ok(r) => BrowserResponse {
                     request_id: req.request_id.clone(),
@@ -1626,7 +2623,7 @@ async fn handle_file_request<T>(
 
     let session_decision = session_mgr.check(&synthetic_req, caller_uid).await;
 
-    let (approval_reason, previous_refusals) = match session_decision {
+    let (approval_reason, previous_refusals, previous_approvals) = match session_decision {
         SessionDecision::Deny(reason) => {
             warn!(request_id = %req.request_id, reason = %reason, "file request denied by session mode");
             send_file_response(crate::file_manager::error_response(
@@ -1659,18 +2656,19 @@ async fn handle_file_request<T>(
                 reason = %escalation.reason,
                 "file request escalated to approval by policy pre-check"
             );
-            (escalation.reason.clone(), Vec::new())
+            (escalation.reason.clone(), Vec::new(), Vec::new())
         }
         SessionDecision::NeedsApproval {
             reason,
             previous_refusals,
+            previous_approvals,
         } => {
             info!(
                 request_id = %req.request_id,
                 reason = %reason,
                 "file request needs approval (strict mode)"
             );
-            (reason, previous_refusals)
+            (reason, previous_refusals, previous_approvals)
         }
     };
 
@@ -1680,20 +2678,25 @@ async fn handle_file_request<T>(
             synthetic_req,
             caller_uid,
             approval_reason,
+            Vec::new(),
             previous_refusals,
+            previous_approvals,
         )
         .await;
 
-    // Send ApprovalRequest to cloud via WS and broadcast to IPC clients.
-    let approval_env = Envelope {
-        device_id: device_id.to_string(),
-        msg_id: new_msg_id(),
-        ts_ms: now_ms(),
-        payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
-        ..Default::default()
-    };
-    let _ = tx.send(approval_env.clone());
-    let _ = approval_broadcast_tx.send(approval_env);
+    // Send ApprovalRequest to cloud via WS and broadcast to IPC clients,
+    // unless it was attached to an identical pending one.
+    if approval_req.job_id == approval_job_id {
+        let approval_env = Envelope {
+            device_id: device_id.to_string(),
+            msg_id: new_msg_id(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
+            ..Default::default()
+        };
+        let _ = tx.send(approval_env.clone());
+        let _ = approval_broadcast_tx.send(approval_env);
+    }
 
     // Wait for the approval response (or timeout, or connection close) in a
     // detached task so the dispatch loop keeps draining inbound frames
@@ -1743,7 +2746,7 @@ async fn handle_file_request<T>(
             result = tokio::time::timeout(timeout, approval_rx) => {
                 match result {
                     Ok(Ok(resp)) if resp.approved => Outcome::Approved,
-                    Ok(Ok(resp)) => {
+                    Ok(Ok(resp)) if resp.reason != EXPIRED_REASON => {
                         let reason = if resp.reason.is_empty() {
                             "approval denied".to_string()
                         } else {
@@ -2013,13 +3016,15 @@ async fn handle_app_tool_request<T>(
         }
         // Allow but requires_approval=true: upgrade to NeedsApproval.
         SessionDecision::Allow if descriptor.requires_approval => {
-            let previous_refusals = session_mgr.get_refusals(&synthetic.tool).await;
+            let previous_refusals = session_mgr.get_refusals(caller_uid, &synthetic.tool).await;
+            let previous_approvals = session_mgr.get_approvals(caller_uid, &synthetic.tool).await;
             SessionDecision::NeedsApproval {
                 reason: format!(
                     "app tool {:?} is registered with requires_approval",
                     req.name
                 ),
                 previous_refusals,
+                previous_approvals,
             }
         }
         other => other,
@@ -2028,6 +3033,7 @@ async fn handle_app_tool_request<T>(
     if let SessionDecision::NeedsApproval {
         reason,
         previous_refusals,
+        previous_approvals,
     } = decision
     {
         info!(
@@ -2065,23 +3071,26 @@ async fn handle_app_tool_request<T>(
                 synthetic.clone(),
                 caller_uid,
                 reason,
+                Vec::new(),
                 previous_refusals,
+                previous_approvals,
                 timeout,
             )
             .await;
 
-        // Send ApprovalRequest to cloud via WS.
-        let approval_env = Envelope {
-            device_id: device_id.to_string(),
-            msg_id: new_msg_id(),
-            ts_ms: now_ms(),
-            payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
-            ..Default::default()
-        };
-        let _ = tx.send(approval_env.clone());
-
-        // Broadcast to all IPC clients.
-        let _ = approval_broadcast_tx.send(approval_env);
+        // Send ApprovalRequest to cloud via WS and broadcast to all IPC
+        // clients — unless it was attached to an identical pending one.
+        if approval_req.job_id == approval_job_id {
+            let approval_env = Envelope {
+                device_id: device_id.to_string(),
+                msg_id: new_msg_id(),
+                ts_ms: now_ms(),
+                payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
+                ..Default::default()
+            };
+            let _ = tx.send(approval_env.clone());
+            let _ = approval_broadcast_tx.send(approval_env);
+        }
 
         // Spawn a task to wait for approval without blocking the WS read loop.
         let tx_clone = (*tx).clone();
@@ -2126,7 +3135,7 @@ async fn handle_app_tool_request<T>(
                     )
                     .await;
                 }
-                Ok(Ok(resp)) => {
+                Ok(Ok(resp)) if resp.reason != EXPIRED_REASON => {
                     // Denied.
                     // refusal is recorded at the ApprovalResponse resolve site
                     // (see ~line 650); recording here would duplicate it.
@@ -2156,7 +3165,7 @@ async fn handle_app_tool_request<T>(
                     .await;
                 }
                 _ => {
-                    // Timeout (or channel closed).
+                    // Timeout, swept as expired, or channel closed.
                     let approval_wait_ms = started.elapsed().as_millis() as u64;
                     warn!(
                         tool_call_id = %tool_call_id,
@@ -2575,23 +3584,16 @@ fn classify_hello_accepted_message(
     let tungstenite::Message::Binary(data) = message else {
         return match message {
             tungstenite::Message::Close(Some(frame))
-                if frame.code == tungstenite::protocol::frame::coding::CloseCode::Policy
-                    && frame.reason == "auth-rejected" =>
+                if frame.code == CloseCode::Policy && frame.reason == "auth-rejected" =>
             {
                 Err(ConnectError::HandshakeRejected(anyhow::anyhow!(
                     "hello auth rejected"
                 )))
             }
-            tungstenite::Message::Close(Some(frame)) => {
-                Err(ConnectError::Session(anyhow::anyhow!(
-                    "websocket closed before hello accepted: code={} reason={}",
-                    frame.code,
-                    frame.reason
-                )))
-            }
-            tungstenite::Message::Close(None) => Err(ConnectError::Session(anyhow::anyhow!(
-                "websocket closed before hello accepted"
-            ))),
+            tungstenite::Message::Close(frame) => Err(ConnectError::Session(
+                anyhow::Error::new(ClosedByPeer::from_frame(frame.as_ref()))
+                    .context("websocket closed before hello accepted"),
+            )),
             _ => Err(ConnectError::Session(anyhow::anyhow!(
                 "expected binary hello accepted frame"
             ))),
@@ -2623,20 +3625,24 @@ impl From<anyhow::Error> for ConnectError {
 mod tests {
     use std::borrow::Cow;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
-    use ahand_protocol::{Envelope, JobFinished, envelope};
+    use ahand_protocol::{Envelope, Heartbeat, JobFinished, envelope};
     use tokio::sync::mpsc;
     use tokio_tungstenite::tungstenite::{
-        Message,
+        self, Message,
         protocol::{CloseFrame, frame::coding::CloseCode},
     };
 
     use crate::executor::EnvelopeSink;
     use crate::outbox::Outbox;
+    use crate::registry::JobRegistry;
+    use crate::session::SessionManager;
 
     use super::{
-        BufferedEnvelopeSender, ConnectError, OutboundFrame, classify_hello_accepted_message,
-        connect_tcp_with_keepalive, hello_capabilities_from_wire_names,
+        BufferedEnvelopeSender, ClosedByPeer, ConnectError, OutboundFrame, SendStalled,
+        classify_hello_accepted_message, close_for_read_error, connect_tcp_with_keepalive,
+        hello_capabilities_from_wire_names, send_queue, send_within,
     };
 
     #[test]
@@ -2762,7 +3768,9 @@ mod tests {
         });
 
         let url = format!("ws://127.0.0.1:{port}/ws");
-        let stream = connect_tcp_with_keepalive(&url).await.expect("dial ok");
+        let stream = connect_tcp_with_keepalive(&url, None)
+            .await
+            .expect("dial ok");
         let sock = socket2::SockRef::from(&stream);
 
         assert!(
@@ -2863,6 +3871,59 @@ mod tests {
         assert!(matches!(err, ConnectError::Session(_)));
     }
 
+    #[test]
+    fn only_try_again_later_closes_carry_a_retry_hint() {
+        let close = |code: CloseCode, reason: &'static str| {
+            ClosedByPeer::from_frame(Some(&CloseFrame {
+                code,
+                reason: Cow::Borrowed(reason),
+            }))
+        };
+
+        assert_eq!(
+            close(CloseCode::Again, "overloaded; retry-after=90").retry_after(),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(close(CloseCode::Again, "overloaded").retry_after(), None);
+        assert_eq!(close(CloseCode::Away, "retry-after=90").retry_after(), None);
+
+        // A hint on a handshake-time close still reaches the reconnect loop.
+        let ConnectError::Session(err) =
+            classify_hello_accepted_message(Message::Close(Some(CloseFrame {
+                code: CloseCode::Again,
+                reason: Cow::Borrowed("retry-after=5"),
+            })))
+            .unwrap_err()
+        else {
+            panic!("expected a session error");
+        };
+        let close = err.downcast_ref::<ClosedByPeer>().expect("ClosedByPeer");
+        assert_eq!(close.retry_after(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn protocol_violations_are_answered_with_a_close_code() {
+        use tungstenite::error::{CapacityError, ProtocolError};
+
+        let code = |err: tungstenite::Error| close_for_read_error(&err).map(|(code, _)| code);
+        assert_eq!(
+            code(tungstenite::Error::Protocol(
+                ProtocolError::UnmaskedFrameFromClient
+            )),
+            Some(CloseCode::Protocol)
+        );
+        assert_eq!(
+            code(tungstenite::Error::Capacity(
+                CapacityError::MessageTooLong {
+                    size: 2,
+                    max_size: 1
+                }
+            )),
+            Some(CloseCode::Size)
+        );
+        assert_eq!(code(tungstenite::Error::ConnectionClosed), None);
+    }
+
     // ── Heartbeat task exit paths ──────────────────────────────────
     //
     // The task spawned by `spawn_heartbeat_task` must exit cleanly via
@@ -2887,6 +3948,136 @@ mod tests {
         }
     }
 
+    fn test_heartbeat_source() -> super::HeartbeatSource {
+        super::HeartbeatSource {
+            daemon_version: "test-version".into(),
+            server_url: "ws://hub/ws".into(),
+            started_at: std::time::Instant::now(),
+            session_mgr: Arc::new(SessionManager::new(60)),
+            registry: Arc::new(JobRegistry::new(4)),
+            approval_mgr: Arc::new(crate::approval::ApprovalManager::new(60)),
+            store: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn heartbeat_snapshot_reports_sessions_with_trust_countdown() {
+        let source = test_heartbeat_source();
+        source
+            .session_mgr
+            .set_mode("uid:1", ahand_protocol::SessionMode::Trust, 30)
+            .await;
+        source.session_mgr.register_caller("uid:2").await;
+
+        let hb = source.snapshot().await;
+        assert_eq!(hb.daemon_version, "test-version");
+        assert_eq!(hb.connection_mode, "ahand-cloud");
+        assert_eq!(hb.active_jobs, 0);
+        let callers: Vec<_> = hb.sessions.iter().map(|s| s.caller_uid.as_str()).collect();
+        assert_eq!(callers, ["uid:1", "uid:2"]);
+        assert!(hb.sessions[0].trust_expires_ms > hb.sent_at_ms);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_snapshot_reports_pending_approvals() {
+        let source = test_heartbeat_source();
+        let _ = source
+            .approval_mgr
+            .submit(
+                ahand_protocol::JobRequest {
+                    job_id: "job-1".into(),
+                    tool: "git".into(),
+                    ..Default::default()
+                },
+                "uid:1",
+                "strict".into(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+
+        let hb = source.snapshot().await;
+        assert_eq!(hb.pending_approvals, 1);
+        assert!(hb.oldest_pending_approval_age_ms < 60_000);
+    }
+
+    #[test]
+    fn buffered_envelope_sender_never_stores_heartbeats() {
+        let outbox = Arc::new(Mutex::new(Outbox::new(16)));
+        outbox.lock().unwrap().on_recv(5);
+        let (tx, mut rx) = send_queue::channel::<OutboundFrame>(16);
+        let sender = BufferedEnvelopeSender::new(tx, outbox.clone(), Arc::default());
+
+        sender
+            .send(Envelope {
+                payload: Some(envelope::Payload::Heartbeat(Heartbeat::default())),
+                ..Default::default()
+            })
+            .expect("send should enqueue");
+
+        assert!(outbox.lock().unwrap().drain_unacked().is_empty());
+        match rx.try_recv() {
+            Some(OutboundFrame::DirectEnvelope(env)) => {
+                assert_eq!(env.seq, 0);
+                assert_eq!(env.ack, 5);
+            }
+            _ => panic!("heartbeat should be sent as a direct frame"),
+        }
+    }
+
+    #[test]
+    fn acks_go_out_direct_once_due() {
+        let outbox = Arc::new(Mutex::new(Outbox::new(16)));
+        let (tx, mut rx) = send_queue::channel::<OutboundFrame>(16);
+        let sender = BufferedEnvelopeSender::new(tx, outbox.clone(), Arc::default());
+        let idle = crate::outbox::ACK_IDLE;
+
+        let now = std::time::Instant::now();
+        sender.send_ack_if_due("dev", now + idle).unwrap();
+        assert!(rx.try_recv().is_none(), "nothing received, nothing to ack");
+
+        outbox.lock().unwrap().on_recv(3);
+        let now = std::time::Instant::now();
+        sender.send_ack_if_due("dev", now).unwrap();
+        assert!(rx.try_recv().is_none(), "one message waits for the timer");
+
+        sender.send_ack_if_due("dev", now + idle).unwrap();
+        match rx.try_recv() {
+            Some(OutboundFrame::DirectEnvelope(env)) => {
+                assert_eq!(env.seq, 0);
+                assert_eq!(env.ack, 3);
+                assert!(matches!(
+                    env.payload,
+                    Some(envelope::Payload::StandaloneAck(ahand_protocol::Ack {
+                        ack: 3
+                    }))
+                ));
+            }
+            _ => panic!("ack should be sent as a direct frame"),
+        }
+        assert!(outbox.lock().unwrap().drain_unacked().is_empty());
+
+        sender.send_ack_if_due("dev", now + idle).unwrap();
+        assert!(rx.try_recv().is_none(), "the ack is not repeated");
+    }
+
+    #[tokio::test]
+    async fn ack_task_exits_when_the_session_closes() {
+        let outbox = Arc::new(Mutex::new(Outbox::new(16)));
+        let (tx, rx) = send_queue::channel::<OutboundFrame>(16);
+        let handle = super::spawn_ack_task(
+            BufferedEnvelopeSender::new(tx, outbox.clone(), Arc::default()),
+            "dev".into(),
+        );
+        outbox.lock().unwrap().on_recv(1);
+        drop(rx);
+        let joined = tokio::time::timeout(std::time::Duration::from_secs(2), handle)
+            .await
+            .expect("ack task did not exit after the channel closed");
+        assert!(joined.is_ok(), "ack task panicked: {joined:?}");
+    }
+
     #[tokio::test]
     async fn heartbeat_task_exits_when_send_errors() {
         let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
@@ -2897,7 +4088,7 @@ mod tests {
         let handle = super::spawn_heartbeat_task(
             sink,
             "device-exit-send".into(),
-            "test-version".into(),
+            test_heartbeat_source(),
             std::time::Duration::from_millis(10),
         );
 
@@ -2924,7 +4115,7 @@ mod tests {
         let handle = super::spawn_heartbeat_task(
             sink,
             "device-exit-abort".into(),
-            "test-version".into(),
+            test_heartbeat_source(),
             std::time::Duration::from_millis(10),
         );
         // Let the ticker fire at least once so we exercise the tick arm.
@@ -2945,8 +4136,8 @@ mod tests {
     #[test]
     fn buffered_envelope_sender_stores_frames_before_transport_send() {
         let outbox = Arc::new(Mutex::new(Outbox::new(16)));
-        let (tx, rx) = mpsc::unbounded_channel::<OutboundFrame>();
-        let sender = BufferedEnvelopeSender::new(tx, outbox.clone());
+        let (tx, rx) = send_queue::channel::<OutboundFrame>(16);
+        let sender = BufferedEnvelopeSender::new(tx, outbox.clone(), Arc::default());
 
         sender
             .send(Envelope {
@@ -2955,6 +4146,7 @@ mod tests {
                     job_id: "job-1".into(),
                     exit_code: 0,
                     error: String::new(),
+                    ..Default::default()
                 })),
                 ..Default::default()
             })
@@ -2964,4 +4156,320 @@ mod tests {
         let buffered = outbox.lock().unwrap().drain_unacked();
         assert_eq!(buffered.len(), 1);
     }
+
+    #[test]
+    fn full_send_queue_drops_only_job_output() {
+        use ahand_protocol::{ApprovalRequest, JobEvent, JobRejected, job_event};
+
+        let outbox = Arc::new(Mutex::new(Outbox::new(64)));
+        let monitor = Arc::new(crate::connection::ConnectionMonitor::default());
+        let (tx, mut rx) = send_queue::channel::<OutboundFrame>(3);
+        let sender = BufferedEnvelopeSender::new(tx, outbox, Arc::clone(&monitor));
+        let send = |payload| {
+            sender
+                .send(Envelope {
+                    payload: Some(payload),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        let chunk = |n: u8| {
+            envelope::Payload::JobEvent(JobEvent {
+                job_id: "job-1".into(),
+                event: Some(job_event::Event::StdoutChunk(vec![n])),
+            })
+        };
+
+        send(chunk(1));
+        send(chunk(2));
+        send(envelope::Payload::ApprovalRequest(
+            ApprovalRequest::default(),
+        ));
+        send(envelope::Payload::JobFinished(JobFinished::default()));
+        send(envelope::Payload::JobRejected(JobRejected::default()));
+        send(chunk(3));
+        send(envelope::Payload::JobEvent(JobEvent {
+            job_id: "job-1".into(),
+            event: Some(job_event::Event::Progress(50)),
+        }));
+
+        let seqs_and_kinds: Vec<_> = std::iter::from_fn(|| rx.try_recv())
+            .map(|frame| match frame {
+                OutboundFrame::Envelope(queued) => {
+                    let kind = match queued.envelope.payload {
+                        Some(envelope::Payload::JobEvent(JobEvent {
+                            event: Some(job_event::Event::StdoutChunk(data)),
+                            ..
+                        })) => format!("chunk {}", data[0]),
+                        Some(envelope::Payload::JobEvent(_)) => "progress".into(),
+                        Some(envelope::Payload::ApprovalRequest(_)) => "approval".into(),
+                        Some(envelope::Payload::JobFinished(_)) => "finished".into(),
+                        Some(envelope::Payload::JobRejected(_)) => "rejected".into(),
+                        other => panic!("unexpected payload {other:?}"),
+                    };
+                    (queued.envelope.seq, kind)
+                }
+                _ => panic!("expected envelope frames"),
+            })
+            .collect();
+        // Chunks 1, 2 and 3 made room, oldest first; everything else went
+        // through in order even past capacity.
+        assert_eq!(
+            seqs_and_kinds,
+            vec![
+                (3, "approval".to_string()),
+                (4, "finished".to_string()),
+                (5, "rejected".to_string()),
+                (7, "progress".to_string()),
+            ]
+        );
+        assert_eq!(monitor.status().dropped_chunks, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_stalled_write_times_out() {
+        struct Stuck;
+        impl futures_util::Sink<Message> for Stuck {
+            type Error = tokio_tungstenite::tungstenite::Error;
+            fn poll_ready(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<Result<(), Self::Error>> {
+                std::task::Poll::Pending
+            }
+            fn start_send(self: std::pin::Pin<&mut Self>, _: Message) -> Result<(), Self::Error> {
+                unreachable!("never ready")
+            }
+            fn poll_flush(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<Result<(), Self::Error>> {
+                std::task::Poll::Pending
+            }
+            fn poll_close(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<Result<(), Self::Error>> {
+                std::task::Poll::Pending
+            }
+        }
+
+        let timeout = std::time::Duration::from_secs(30);
+        let started = tokio::time::Instant::now();
+        let err = send_within(&mut Stuck, Message::Ping(vec![]), timeout)
+            .await
+            .unwrap_err();
+        assert!(err.is::<SendStalled>(), "{err:#}");
+        assert_eq!(started.elapsed(), timeout);
+    }
+
+    fn reuse_request(job_id: &str, args: &[&str]) -> ahand_protocol::JobRequest {
+        ahand_protocol::JobRequest {
+            job_id: job_id.to_string(),
+            tool: "echo".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Feed `req` through `handle_job_request` and return the first envelope
+    /// it emits.
+    async fn handle_and_recv(
+        req: ahand_protocol::JobRequest,
+        registry: &Arc<crate::registry::JobRegistry>,
+    ) -> Envelope {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        let (approval_broadcast_tx, _) = tokio::sync::broadcast::channel(8);
+        super::handle_job_request(
+            req,
+            "dev-1",
+            "cloud",
+            &tx,
+            &Arc::new(crate::session::SessionManager::new(5)),
+            registry,
+            &None,
+            &Arc::new(crate::approval::ApprovalManager::new(60)),
+            &Arc::new(crate::policy::PolicyChecker::new(
+                &crate::config::PolicyConfig::default(),
+            )),
+            &approval_broadcast_tx,
+            &Arc::new(crate::browser::BrowserManager::new(
+                crate::config::BrowserConfig::default(),
+            )),
+            &Arc::new(crate::file_manager::FileManager::new(
+                &crate::config::FilePolicyConfig::default(),
+            )),
+        )
+        .await;
+        rx.try_recv().expect("expected an envelope")
+    }
+
+    /// Save → reload in a fresh ApprovalManager → approve: the restored job
+    /// runs as the original waiter would have run it.
+    #[tokio::test]
+    async fn restored_approval_spawns_the_job_once_approved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let before = crate::approval::ApprovalManager::new(60).with_pending_file(path.clone());
+        let _ = before
+            .submit(
+                reuse_request("job-1", &["hello"]),
+                "cloud",
+                "strict".into(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+        before.persist_across_restart("job-1").await;
+        drop(before);
+
+        let approval_mgr =
+            Arc::new(crate::approval::ApprovalManager::new(60).with_pending_file(path));
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        super::arm_restored_approvals(
+            "dev-1",
+            &tx,
+            &Arc::new(SessionManager::new(5)),
+            &Arc::new(JobRegistry::new(4)),
+            &None,
+            &approval_mgr,
+            &Arc::new(crate::browser::BrowserManager::new(
+                crate::config::BrowserConfig::default(),
+            )),
+            &Arc::new(crate::file_manager::FileManager::new(
+                &crate::config::FilePolicyConfig::default(),
+            )),
+        )
+        .await;
+        approval_mgr
+            .resolve(&ahand_protocol::ApprovalResponse {
+                job_id: "job-1".into(),
+                approved: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        loop {
+            let env = tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv())
+                .await
+                .expect("timed out waiting for the restored job to finish")
+                .unwrap();
+            match env.payload {
+                Some(envelope::Payload::JobFinished(JobFinished {
+                    job_id, exit_code, ..
+                })) => {
+                    assert_eq!(job_id, "job-1");
+                    assert_eq!(exit_code, 0);
+                    break;
+                }
+                Some(envelope::Payload::JobRejected(rej)) => panic!("job rejected: {rej:?}"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn approval_expired_during_restart_is_rejected_on_connect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let before = crate::approval::ApprovalManager::new(60).with_pending_file(path.clone());
+        let _ = before
+            .submit_with_timeout(
+                reuse_request("job-1", &["hello"]),
+                "cloud",
+                "strict".into(),
+                vec![],
+                vec![],
+                vec![],
+                std::time::Duration::ZERO,
+            )
+            .await;
+        before.persist_across_restart("job-1").await;
+        drop(before);
+
+        let approval_mgr =
+            Arc::new(crate::approval::ApprovalManager::new(60).with_pending_file(path));
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        super::arm_restored_approvals(
+            "dev-1",
+            &tx,
+            &Arc::new(SessionManager::new(5)),
+            &Arc::new(JobRegistry::new(4)),
+            &None,
+            &approval_mgr,
+            &Arc::new(crate::browser::BrowserManager::new(
+                crate::config::BrowserConfig::default(),
+            )),
+            &Arc::new(crate::file_manager::FileManager::new(
+                &crate::config::FilePolicyConfig::default(),
+            )),
+        )
+        .await;
+
+        match rx.try_recv().expect("expected a rejection").payload {
+            Some(envelope::Payload::JobRejected(rej)) => {
+                assert_eq!(rej.job_id, "job-1");
+                assert_eq!(rej.reason, "approval expired during daemon restart");
+            }
+            other => panic!("expected JobRejected, got {other:?}"),
+        }
+    }
+
+    fn expect_reuse_rejection(env: Envelope) {
+        match env.payload {
+            Some(envelope::Payload::JobRejected(rej)) => {
+                assert_eq!(rej.job_id, "job-1");
+                assert_eq!(rej.reason, "job_id reuse with different parameters");
+            }
+            other => panic!("expected JobRejected, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn running_job_id_reuse_with_different_args_is_rejected() {
+        let registry = Arc::new(crate::registry::JobRegistry::new(4));
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        let original = reuse_request("job-1", &["hello"]);
+        assert!(
+            registry
+                .register(
+                    "job-1".to_string(),
+                    "cloud",
+                    crate::registry::params_hash(&original),
+                    cancel_tx,
+                )
+                .await
+        );
+
+        let env = handle_and_recv(reuse_request("job-1", &["goodbye"]), &registry).await;
+        expect_reuse_rejection(env);
+    }
+
+    #[tokio::test]
+    async fn completed_job_id_reuse_with_different_args_is_rejected() {
+        let registry = Arc::new(crate::registry::JobRegistry::new(4));
+        let original = reuse_request("job-1", &["hello"]);
+        registry
+            .mark_completed(
+                "job-1".to_string(),
+                crate::registry::params_hash(&original),
+                0,
+                String::new(),
+            )
+            .await;
+
+        let env = handle_and_recv(reuse_request("job-1", &["goodbye"]), &registry).await;
+        expect_reuse_rejection(env);
+
+        // An identical retry still gets the cached result.
+        let env = handle_and_recv(original, &registry).await;
+        match env.payload {
+            Some(envelope::Payload::JobFinished(JobFinished { job_id, .. })) => {
+                assert_eq!(job_id, "job-1");
+            }
+            other => panic!("expected cached JobFinished, got {other:?}"),
+        }
+    }
 }
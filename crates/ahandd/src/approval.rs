@@ -1,18 +1,207 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
-use ahand_protocol::{ApprovalRequest, ApprovalResponse, JobRequest, RefusalContext};
-use tokio::sync::{Mutex, oneshot};
-use tracing::info;
+use ahand_protocol::{
+    ApprovalBulkResponse, ApprovalContext, ApprovalExpired, ApprovalRequest, ApprovalResolved,
+    ApprovalResponse, Envelope, JobRequest, RefusalContext, envelope,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, broadcast, oneshot};
+use tracing::{info, warn};
+
+use crate::audit::{AuditEntry, AuditLog};
+use crate::notify::DesktopNotifier;
+use crate::policy::PolicyChecker;
+use crate::redact::EnvRedactor;
+
+/// How often [`sweep_expired_approvals`] looks for requests past `expires_ms`.
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reason on the denial a swept request's waiter receives; waiters handle
+/// it like their own timeout.
+pub const EXPIRED_REASON: &str = "expired";
+
+/// Rejection reason for a persisted request whose expiry passed while the
+/// daemon was down.
+pub const EXPIRED_DURING_RESTART_REASON: &str = "approval expired during daemon restart";
+
+/// Rejection reason for a persisted request whose secret env values were
+/// redacted on disk, so it can't run as approved after a restart.
+pub const SECRET_ENV_NOT_RESTORED_REASON: &str =
+    "approval lost during daemon restart: secret env values are not persisted";
+
+/// Programs that run code passed in their arguments (`sh -c`, `python -c`,
+/// `env bash ...`). Approvals are remembered by tool name, so remembering
+/// one of these would approve every later command it runs.
+const CODE_INTERPRETERS: &[&str] = &[
+    "sh",
+    "bash",
+    "zsh",
+    "dash",
+    "ksh",
+    "mksh",
+    "ash",
+    "fish",
+    "csh",
+    "tcsh",
+    "cmd",
+    "powershell",
+    "pwsh",
+    "python",
+    "node",
+    "deno",
+    "bun",
+    "perl",
+    "ruby",
+    "php",
+    "lua",
+    "osascript",
+    "env",
+];
 
 /// A pending approval entry.
 struct PendingApproval {
     request: JobRequest,
     caller_uid: String,
-    #[allow(dead_code)]
     approval_request: ApprovalRequest,
     result_tx: oneshot::Sender<ApprovalResponse>,
+    submitted_ms: u64,
+    /// Saved to the pending-approvals file, with its followers, so it
+    /// survives a restart.
+    persistent: bool,
+    /// Later jobs with the same request hash, answered together with this one.
+    followers: Vec<Follower>,
+}
+
+/// A job attached to an identical pending request instead of prompting again.
+struct Follower {
+    job_id: String,
+    result_tx: oneshot::Sender<ApprovalResponse>,
+}
+
+impl PendingApproval {
+    /// Hand `response` to this entry's waiter and every follower's, each
+    /// under its own job_id. Returns the entry's (JobRequest, caller_uid).
+    fn answer(self, response: &ApprovalResponse) -> (JobRequest, String) {
+        // First-response-wins: if send fails, somebody else already resolved it.
+        let _ = self.result_tx.send(ApprovalResponse {
+            job_id: self.request.job_id.clone(),
+            ..response.clone()
+        });
+        for follower in self.followers {
+            let _ = follower.result_tx.send(ApprovalResponse {
+                job_id: follower.job_id,
+                ..response.clone()
+            });
+        }
+        (self.request, self.caller_uid)
+    }
+}
+
+/// One persisted entry in `pending_approvals.json`. The protobuf messages
+/// are stored hex-encoded so every other field round-trips unchanged;
+/// secret env values are redacted first.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedApproval {
+    caller_uid: String,
+    expires_ms: u64,
+    submitted_ms: u64,
+    job_request: String,
+    approval_request: String,
+    /// job_ids of the followers attached to the entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    followers: Vec<String>,
+    /// `job_request` had secret env values, which are redacted on disk.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    secret_env: bool,
+}
+
+impl PersistedApproval {
+    fn from_entry(entry: &PendingApproval, redactor: &EnvRedactor) -> Self {
+        let mut request = entry.request.clone();
+        let secret_env = request.env.keys().any(|key| redactor.is_secret(key));
+        if secret_env {
+            request.env = redactor.redact_env(&request.env).into_iter().collect();
+        }
+        Self {
+            caller_uid: entry.caller_uid.clone(),
+            expires_ms: entry.approval_request.expires_ms,
+            submitted_ms: entry.submitted_ms,
+            job_request: hex::encode(request.encode_to_vec()),
+            approval_request: hex::encode(entry.approval_request.encode_to_vec()),
+            followers: entry.followers.iter().map(|f| f.job_id.clone()).collect(),
+            secret_env,
+        }
+    }
+
+    fn decode(&self) -> Option<(JobRequest, ApprovalRequest)> {
+        let request = JobRequest::decode(hex::decode(&self.job_request).ok()?.as_slice()).ok()?;
+        let approval_request =
+            ApprovalRequest::decode(hex::decode(&self.approval_request).ok()?.as_slice()).ok()?;
+        Some((request, approval_request))
+    }
+}
+
+/// A persisted request reloaded at startup, waiting for an answer again.
+pub struct RestoredApproval {
+    pub request: JobRequest,
+    pub caller_uid: String,
+    pub expires_ms: u64,
+    pub submitted_ms: u64,
+    pub approval_rx: oneshot::Receiver<ApprovalResponse>,
+}
+
+/// A persisted request that can't be re-armed after a restart; its job is
+/// rejected with `reason`.
+pub struct DroppedApproval {
+    pub request: JobRequest,
+    pub caller_uid: String,
+    pub reason: &'static str,
+}
+
+/// What [`ApprovalManager::with_pending_file`] reloaded, handed out once by
+/// [`ApprovalManager::take_restored`]. Followers come back as entries of
+/// their own, under their own job_id.
+#[derive(Default)]
+pub struct RestoredApprovals {
+    /// Still unexpired; pending again under their original job_id.
+    pub waiting: Vec<RestoredApproval>,
+    /// Expired while the daemon was down, or not restorable because their
+    /// secret env values weren't persisted.
+    pub dropped: Vec<DroppedApproval>,
+}
+
+/// Hash what an approver is asked to approve — caller, tool, args, cwd and
+/// env — so a retried job with a new job_id can share the pending prompt.
+/// Env is included because it changes what runs (proxy destinations,
+/// loader and PATH variables). Every field is length-prefixed to keep the
+/// encoding unambiguous.
+pub fn request_hash(caller_uid: &str, req: &JobRequest) -> String {
+    fn field(hasher: &mut Sha256, value: &str) {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    }
+
+    let mut hasher = Sha256::new();
+    field(&mut hasher, caller_uid);
+    field(&mut hasher, &req.tool);
+    hasher.update((req.args.len() as u64).to_le_bytes());
+    for arg in &req.args {
+        field(&mut hasher, arg);
+    }
+    field(&mut hasher, &req.cwd);
+    let mut env: Vec<_> = req.env.iter().collect();
+    env.sort_unstable();
+    hasher.update((env.len() as u64).to_le_bytes());
+    for (key, value) in env {
+        field(&mut hasher, key);
+        field(&mut hasher, value);
+    }
+    hex::encode(hasher.finalize())
 }
 
 /// Manages pending approval requests. Shared between WS client and IPC server.
@@ -30,6 +219,12 @@ struct PendingApproval {
 pub struct ApprovalManager {
     pending: Mutex<HashMap<String, PendingApproval>>,
     default_timeout: Duration,
+    audit: Option<Arc<AuditLog>>,
+    notifier: Option<Arc<DesktopNotifier>>,
+    policy: Option<Arc<PolicyChecker>>,
+    pending_path: Option<PathBuf>,
+    env_redactor: EnvRedactor,
+    restored: Mutex<Option<RestoredApprovals>>,
 }
 
 impl ApprovalManager {
@@ -37,12 +232,134 @@ impl ApprovalManager {
         Self {
             pending: Mutex::new(HashMap::new()),
             default_timeout: Duration::from_secs(timeout_secs),
+            audit: None,
+            notifier: None,
+            policy: None,
+            pending_path: None,
+            env_redactor: EnvRedactor::default(),
+            restored: Mutex::new(None),
+        }
+    }
+
+    /// Record every resolved or expired approval in `audit`.
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Store approvals answered with `remember` in `policy`.
+    pub fn with_policy(mut self, policy: Arc<PolicyChecker>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Redact secret env values with `redactor` before a request is written
+    /// to the pending-approvals file. Set it before
+    /// [`Self::with_pending_file`], which rewrites the file right away.
+    pub fn with_env_redaction(mut self, redactor: EnvRedactor) -> Self {
+        self.env_redactor = redactor;
+        self
+    }
+
+    /// Persist requests marked with [`Self::persist_across_restart`] to
+    /// `path`, and reload the ones a previous run left there. Unexpired
+    /// entries are pending again right away; [`Self::take_restored`] hands
+    /// out their receivers and the entries that can't be restored.
+    pub fn with_pending_file(mut self, path: PathBuf) -> Self {
+        let now = now_ms();
+        let mut restored = RestoredApprovals::default();
+        let pending = self.pending.get_mut();
+        for persisted in load_pending(&path) {
+            let Some((request, approval_request)) = persisted.decode() else {
+                warn!(path = %path.display(), "ignoring undecodable pending approval");
+                continue;
+            };
+            let follower_requests: Vec<JobRequest> = persisted
+                .followers
+                .iter()
+                .map(|job_id| JobRequest {
+                    job_id: job_id.clone(),
+                    ..request.clone()
+                })
+                .collect();
+            let dropped_reason = if persisted.expires_ms <= now {
+                Some(EXPIRED_DURING_RESTART_REASON)
+            } else if persisted.secret_env {
+                Some(SECRET_ENV_NOT_RESTORED_REASON)
+            } else {
+                None
+            };
+            if let Some(reason) = dropped_reason {
+                for request in std::iter::once(request).chain(follower_requests) {
+                    restored.dropped.push(DroppedApproval {
+                        request,
+                        caller_uid: persisted.caller_uid.clone(),
+                        reason,
+                    });
+                }
+                continue;
+            }
+
+            let mut restore = |request: JobRequest| {
+                let (tx, rx) = oneshot::channel();
+                restored.waiting.push(RestoredApproval {
+                    request,
+                    caller_uid: persisted.caller_uid.clone(),
+                    expires_ms: persisted.expires_ms,
+                    submitted_ms: persisted.submitted_ms,
+                    approval_rx: rx,
+                });
+                tx
+            };
+            let result_tx = restore(request.clone());
+            let followers = follower_requests
+                .into_iter()
+                .map(|request| Follower {
+                    job_id: request.job_id.clone(),
+                    result_tx: restore(request),
+                })
+                .collect();
+            pending.insert(
+                request.job_id.clone(),
+                PendingApproval {
+                    request,
+                    caller_uid: persisted.caller_uid,
+                    approval_request,
+                    result_tx,
+                    submitted_ms: persisted.submitted_ms,
+                    persistent: true,
+                    followers,
+                },
+            );
         }
+        if !restored.waiting.is_empty() || !restored.dropped.is_empty() {
+            info!(
+                waiting = restored.waiting.len(),
+                dropped = restored.dropped.len(),
+                "restored pending approvals"
+            );
+        }
+        save_pending(&path, pending, &self.env_redactor);
+        self.pending_path = Some(path);
+        *self.restored.get_mut() = Some(restored);
+        self
+    }
+
+    /// Show a desktop notification for each newly submitted request. A job
+    /// re-submitted while still pending is not notified again.
+    pub fn with_notifier(mut self, notifier: Arc<DesktopNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
     }
 
     /// Submit a job that needs approval. Returns the ApprovalRequest to broadcast
     /// and a oneshot Receiver that the caller awaits (with timeout).
     ///
+    /// If a different job with the same [`request_hash`] is already pending,
+    /// the new job is attached to it and answered with it; the returned
+    /// ApprovalRequest is then the existing one (its `job_id` differs from
+    /// `req.job_id`) and must not be broadcast again.
+    ///
     /// The advertised `expires_ms` is set to `now + default_timeout`, matching
     /// the window used for job and file requests.
     pub async fn submit(
@@ -50,13 +367,17 @@ impl ApprovalManager {
         req: JobRequest,
         caller_uid: &str,
         reason: String,
+        detected_domains: Vec<String>,
         previous_refusals: Vec<RefusalContext>,
+        previous_approvals: Vec<ApprovalContext>,
     ) -> (ApprovalRequest, oneshot::Receiver<ApprovalResponse>) {
         self.submit_with_timeout(
             req,
             caller_uid,
             reason,
+            detected_domains,
             previous_refusals,
+            previous_approvals,
             self.default_timeout,
         )
         .await
@@ -65,16 +386,41 @@ impl ApprovalManager {
     /// Like `submit`, but with an explicit wait bound for this request — the
     /// advertised `expires_ms` must match when the waiter actually gives up.
     /// Job/file callers keep using `submit` (default_timeout applies).
+    #[allow(clippy::too_many_arguments)]
     pub async fn submit_with_timeout(
         &self,
         req: JobRequest,
         caller_uid: &str,
         reason: String,
+        detected_domains: Vec<String>,
         previous_refusals: Vec<RefusalContext>,
+        previous_approvals: Vec<ApprovalContext>,
         timeout: Duration,
     ) -> (ApprovalRequest, oneshot::Receiver<ApprovalResponse>) {
         let (tx, rx) = oneshot::channel();
         let expires_ms = now_ms() + timeout.as_millis() as u64;
+        let hash = request_hash(caller_uid, &req);
+
+        let mut pending = self.pending.lock().await;
+        if let Some(existing) = pending
+            .values_mut()
+            .find(|p| p.approval_request.request_hash == hash && p.request.job_id != req.job_id)
+        {
+            info!(
+                job_id = %req.job_id,
+                pending_job_id = %existing.request.job_id,
+                "approval request attached to an identical pending one"
+            );
+            existing.followers.push(Follower {
+                job_id: req.job_id,
+                result_tx: tx,
+            });
+            let approval_req = existing.approval_request.clone();
+            if existing.persistent {
+                self.persist(&pending);
+            }
+            return (approval_req, rx);
+        }
 
         let approval_req = ApprovalRequest {
             job_id: req.job_id.clone(),
@@ -82,10 +428,13 @@ impl ApprovalManager {
             args: req.args.clone(),
             cwd: req.cwd.clone(),
             reason,
-            detected_domains: Vec::new(),
+            detected_domains,
             expires_ms,
             caller_uid: caller_uid.to_string(),
             previous_refusals,
+            previous_approvals,
+            replayed: false,
+            request_hash: hash,
         };
 
         let entry = PendingApproval {
@@ -93,10 +442,21 @@ impl ApprovalManager {
             caller_uid: caller_uid.to_string(),
             approval_request: approval_req.clone(),
             result_tx: tx,
+            submitted_ms: now_ms(),
+            persistent: false,
+            followers: Vec::new(),
         };
 
         let job_id = entry.request.job_id.clone();
-        self.pending.lock().await.insert(job_id.clone(), entry);
+        let replaced = pending.insert(job_id.clone(), entry);
+        let resubmitted = replaced.is_some();
+        if replaced.is_some_and(|p| p.persistent) {
+            self.persist(&pending);
+        }
+        drop(pending);
+        if !resubmitted && let Some(notifier) = &self.notifier {
+            notifier.notify(&approval_req);
+        }
 
         info!(
             job_id = %job_id,
@@ -108,54 +468,370 @@ impl ApprovalManager {
     }
 
     /// Resolve a pending approval. Sends the response through the oneshot channel
-    /// to unblock the waiting task. Returns the (JobRequest, caller_uid) if the
-    /// job_id was found, or None if already resolved or expired.
+    /// to unblock the waiting task and those of any jobs attached to it;
+    /// `response.job_id` may name either. Returns the entry's
+    /// (JobRequest, caller_uid) if found, or None if already resolved or expired.
     pub async fn resolve(&self, response: &ApprovalResponse) -> Option<(JobRequest, String)> {
-        let entry = self.pending.lock().await.remove(&response.job_id)?;
-        let req = entry.request;
-        let caller_uid = entry.caller_uid;
-        // First-response-wins: if send fails, somebody else already resolved it.
-        let _ = entry.result_tx.send(response.clone());
+        let entry = {
+            let mut pending = self.pending.lock().await;
+            let key = if pending.contains_key(&response.job_id) {
+                response.job_id.clone()
+            } else {
+                pending
+                    .iter()
+                    .find(|(_, p)| p.followers.iter().any(|f| f.job_id == response.job_id))
+                    .map(|(key, _)| key.clone())?
+            };
+            let entry = pending.remove(&key)?;
+            if entry.persistent {
+                self.persist(&pending);
+            }
+            entry
+        };
+        let detected_domains = entry.approval_request.detected_domains.clone();
+        let (req, caller_uid) = entry.answer(response);
+        if response.approved
+            && response.remember
+            && let Some(policy) = &self.policy
+            && rememberable(&req)
+        {
+            let ttl_secs = (response.remember_ttl_secs > 0).then_some(response.remember_ttl_secs);
+            policy
+                .remember_approval(&caller_uid, &req.tool, &detected_domains, ttl_secs)
+                .await;
+        }
+        if let Some(audit) = &self.audit {
+            let decision = if response.approved {
+                "approved"
+            } else {
+                "denied"
+            };
+            let rule = response.remember.then(|| "remember".to_string());
+            audit
+                .record(AuditEntry::for_job(
+                    "approval",
+                    &req,
+                    &caller_uid,
+                    decision,
+                    &response.reason,
+                    rule,
+                ))
+                .await;
+        }
         Some((req, caller_uid))
     }
 
-    /// Remove a timed-out entry. Returns true if it was still pending.
+    /// Remove a timed-out entry, or detach a timed-out job attached to one.
+    /// Returns true if it was still pending. Jobs still attached to a
+    /// removed entry see their channel close, as on their own timeout.
     pub async fn expire(&self, job_id: &str) -> bool {
-        self.pending.lock().await.remove(job_id).is_some()
+        let entry = {
+            let mut pending = self.pending.lock().await;
+            match pending.remove(job_id) {
+                Some(entry) => {
+                    if entry.persistent {
+                        self.persist(&pending);
+                    }
+                    entry
+                }
+                None => {
+                    let Some(persistent) = pending.values_mut().find_map(|p| {
+                        let attached = p.followers.len();
+                        p.followers.retain(|f| f.job_id != job_id);
+                        (p.followers.len() != attached).then_some(p.persistent)
+                    }) else {
+                        return false;
+                    };
+                    if persistent {
+                        self.persist(&pending);
+                    }
+                    return true;
+                }
+            }
+        };
+        if let Some(audit) = &self.audit {
+            audit
+                .record(AuditEntry::for_job(
+                    "approval",
+                    &entry.request,
+                    &entry.caller_uid,
+                    "expired",
+                    "approval timed out",
+                    None,
+                ))
+                .await;
+        }
+        true
+    }
+
+    /// Drop every request whose advertised `expires_ms` has passed, handing
+    /// its waiters a denial with reason [`EXPIRED_REASON`]. Returns the
+    /// dropped job_ids. A response resolving the same entry concurrently
+    /// wins or loses as a whole: the entry leaves `pending` exactly once.
+    pub async fn sweep_expired(&self) -> Vec<String> {
+        let now = now_ms();
+        let expired: Vec<PendingApproval> = {
+            let mut pending = self.pending.lock().await;
+            let job_ids: Vec<String> = pending
+                .iter()
+                .filter(|(_, p)| p.approval_request.expires_ms <= now)
+                .map(|(job_id, _)| job_id.clone())
+                .collect();
+            let expired: Vec<PendingApproval> = job_ids
+                .iter()
+                .filter_map(|job_id| pending.remove(job_id))
+                .collect();
+            if expired.iter().any(|p| p.persistent) {
+                self.persist(&pending);
+            }
+            expired
+        };
+
+        let mut job_ids = Vec::with_capacity(expired.len());
+        for entry in expired {
+            let (request, caller_uid) = entry.answer(&ApprovalResponse {
+                approved: false,
+                reason: EXPIRED_REASON.to_string(),
+                ..Default::default()
+            });
+            let job_id = request.job_id.clone();
+            if let Some(audit) = &self.audit {
+                audit
+                    .record(AuditEntry::for_job(
+                        "approval",
+                        &request,
+                        &caller_uid,
+                        "expired",
+                        "approval expired",
+                        None,
+                    ))
+                    .await;
+            }
+            info!(job_id = %job_id, "approval request expired");
+            job_ids.push(job_id);
+        }
+        job_ids
+    }
+
+    /// Save `job_id`'s pending entry to the pending-approvals file so a
+    /// restart re-arms it instead of dropping the job. Only callers that
+    /// can re-arm the job after a restart should mark it. No-op without
+    /// [`Self::with_pending_file`].
+    pub async fn persist_across_restart(&self, job_id: &str) {
+        if self.pending_path.is_none() {
+            return;
+        }
+        let mut pending = self.pending.lock().await;
+        if let Some(entry) = pending.get_mut(job_id) {
+            entry.persistent = true;
+            self.persist(&pending);
+        }
+    }
+
+    /// Take what [`Self::with_pending_file`] reloaded. Returns it once;
+    /// later calls get nothing. Entries that couldn't be restored are
+    /// audited here as expired.
+    pub async fn take_restored(&self) -> RestoredApprovals {
+        let restored = self.restored.lock().await.take().unwrap_or_default();
+        if let Some(audit) = &self.audit {
+            for dropped in &restored.dropped {
+                audit
+                    .record(AuditEntry::for_job(
+                        "approval",
+                        &dropped.request,
+                        &dropped.caller_uid,
+                        "expired",
+                        dropped.reason,
+                        None,
+                    ))
+                    .await;
+            }
+        }
+        restored
+    }
+
+    /// Rewrite the pending-approvals file from `pending`. Called with the
+    /// lock held so concurrent writes land in order.
+    fn persist(&self, pending: &HashMap<String, PendingApproval>) {
+        if let Some(path) = &self.pending_path {
+            save_pending(path, pending, &self.env_redactor);
+        }
+    }
+
+    /// job_ids of pending requests for `tool` that, when `domain_pattern` is
+    /// non-empty, reach at least one domain matching it.
+    pub async fn matching(&self, tool: &str, domain_pattern: &str) -> Vec<String> {
+        let pending = self.pending.lock().await;
+        let mut job_ids: Vec<String> = pending
+            .values()
+            .map(|p| &p.approval_request)
+            .filter(|r| r.tool == tool)
+            .filter(|r| {
+                domain_pattern.is_empty()
+                    || r.detected_domains
+                        .iter()
+                        .any(|d| crate::config::domain_matches(d, domain_pattern))
+            })
+            .map(|r| r.job_id.clone())
+            .collect();
+        job_ids.sort();
+        job_ids
     }
 
-    /// List all currently pending approval requests.
-    #[allow(dead_code)]
+    /// List all currently pending approval requests, soonest expiry first.
     pub async fn list_pending(&self) -> Vec<ApprovalRequest> {
-        self.pending
+        let mut requests: Vec<_> = self
+            .pending
             .lock()
             .await
             .values()
             .map(|p| p.approval_request.clone())
-            .collect()
+            .collect();
+        requests.sort_by_key(|r| r.expires_ms);
+        requests
+    }
+
+    /// How many requests are pending and how long the oldest has waited,
+    /// in milliseconds (0 when none are).
+    pub async fn pending_summary(&self) -> (u32, u64) {
+        let pending = self.pending.lock().await;
+        let oldest_age_ms = pending
+            .values()
+            .map(|p| now_ms().saturating_sub(p.submitted_ms))
+            .max()
+            .unwrap_or(0);
+        (pending.len() as u32, oldest_age_ms)
     }
 
     /// The default timeout duration for approval requests.
     pub fn default_timeout(&self) -> Duration {
         self.default_timeout
     }
+
+    /// Store an approval granted outside this manager (an OpenClaw gateway's
+    /// allow-always) in the policy, as if it had been answered with
+    /// `remember`. Does nothing without a policy, or for a code interpreter
+    /// (the approval then covers this run only).
+    pub async fn remember(&self, caller_uid: &str, req: &JobRequest) {
+        if let Some(policy) = &self.policy
+            && rememberable(req)
+        {
+            let domains = crate::policy::extract_destinations(req);
+            policy
+                .remember_approval(caller_uid, &req.tool, &domains, None)
+                .await;
+        }
+    }
+}
+
+/// Whether an approval of `req` may be remembered for its tool. Code
+/// interpreters are refused, whatever path or version suffix they carry
+/// (`/bin/bash`, `python3.12`, `pwsh.exe`).
+fn rememberable(req: &JobRequest) -> bool {
+    let name = req
+        .tool
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-');
+    if CODE_INTERPRETERS.contains(&name) {
+        info!(tool = %req.tool, "not remembering an approval for a code interpreter");
+        return false;
+    }
+    true
+}
+
+/// Run [`ApprovalManager::sweep_expired`] periodically and announce each
+/// dropped request as `ApprovalExpired` on the approval broadcast channel,
+/// which reaches IPC clients and the cloud connection.
+pub async fn sweep_expired_approvals(
+    approval_mgr: Arc<ApprovalManager>,
+    device_id: String,
+    broadcast_tx: broadcast::Sender<Envelope>,
+) {
+    let mut interval = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        for job_id in approval_mgr.sweep_expired().await {
+            let _ = broadcast_tx.send(approval_expired_envelope(&device_id, job_id));
+        }
+    }
+}
+
+/// Every pending request as an `ApprovalRequest` envelope marked
+/// `replayed`, for an approver that connected after it was broadcast.
+pub async fn replay_pending_envelopes(
+    approval_mgr: &ApprovalManager,
+    device_id: &str,
+) -> Vec<Envelope> {
+    approval_mgr
+        .list_pending()
+        .await
+        .into_iter()
+        .map(|req| Envelope {
+            device_id: device_id.to_string(),
+            msg_id: format!("approval-replay-{}", req.job_id),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::ApprovalRequest(ApprovalRequest {
+                replayed: true,
+                ..req
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn approval_expired_envelope(device_id: &str, job_id: String) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: format!("approval-expired-{job_id}"),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::ApprovalExpired(ApprovalExpired {
+            job_id,
+        })),
+        ..Default::default()
+    }
+}
+
+fn approval_resolved_envelope(
+    device_id: &str,
+    job_id: &str,
+    approved: bool,
+    resolved_by: &str,
+) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: format!("approval-resolved-{job_id}"),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::ApprovalResolved(ApprovalResolved {
+            job_id: job_id.to_string(),
+            approved,
+            resolved_by: resolved_by.to_string(),
+        })),
+        ..Default::default()
+    }
 }
 
 /// Shared terminal handling for an [`ApprovalResponse`] arriving from any
 /// surface (cloud WS, local IPC, in-process embedder): resolve the pending
-/// entry; a denial carrying a non-empty reason records a refusal for the
-/// resolved tool. Returns `true` when a pending entry was resolved.
+/// entry and record the outcome against the caller that submitted the job —
+/// an approval always, a denial only when it carries a non-empty reason.
+/// Returns `true` when a pending entry was resolved.
 ///
 /// Callers that want a surface-specific log line (e.g. "received approval
 /// response from cloud") should emit it **before** calling this helper.
-/// The `principal` field identifies the source for future per-principal
-/// refusal tracking (currently ignored by `SessionManager::record_refusal`,
-/// which is tool-keyed today).
+/// `principal` identifies who answered: a resolved request is announced on
+/// `broadcast_tx` as `ApprovalResolved` with it as `resolved_by`, so other
+/// approvers can dismiss their prompts.
 pub(crate) async fn apply_approval_response(
     approval_mgr: &Arc<ApprovalManager>,
     session_mgr: &Arc<crate::session::SessionManager>,
     resp: &ApprovalResponse,
     principal: &str,
+    device_id: &str,
+    broadcast_tx: &broadcast::Sender<Envelope>,
 ) -> bool {
     info!(
         job_id = %resp.job_id,
@@ -163,16 +839,108 @@ pub(crate) async fn apply_approval_response(
         principal,
         "applying approval response"
     );
-    if !resp.approved && !resp.reason.is_empty() {
-        if let Some((req, _)) = approval_mgr.resolve(resp).await {
-            session_mgr
-                .record_refusal(principal, &req.tool, &resp.reason)
-                .await;
-            return true;
+    let resp = ApprovalResponse {
+        resolved_by: principal.to_string(),
+        ..resp.clone()
+    };
+    let Some((req, caller_uid)) = approval_mgr.resolve(&resp).await else {
+        return false;
+    };
+    let _ = broadcast_tx.send(approval_resolved_envelope(
+        device_id,
+        &req.job_id,
+        resp.approved,
+        principal,
+    ));
+    if resp.approved {
+        session_mgr
+            .record_approval(&caller_uid, &req.tool, &req.args)
+            .await;
+    } else if !resp.reason.is_empty() {
+        session_mgr
+            .record_refusal(&caller_uid, &req.tool, &resp.reason)
+            .await;
+    }
+    true
+}
+
+/// Apply one [`ApprovalBulkResponse`] to every matching pending request, as
+/// if each had been answered through [`apply_approval_response`]. Returns
+/// how many were resolved.
+pub(crate) async fn apply_bulk_approval_response(
+    approval_mgr: &Arc<ApprovalManager>,
+    session_mgr: &Arc<crate::session::SessionManager>,
+    bulk: &ApprovalBulkResponse,
+    principal: &str,
+    device_id: &str,
+    broadcast_tx: &broadcast::Sender<Envelope>,
+) -> u32 {
+    let mut resolved = 0;
+    for job_id in approval_mgr
+        .matching(&bulk.tool, &bulk.domain_pattern)
+        .await
+    {
+        let resp = ApprovalResponse {
+            job_id,
+            approved: bulk.approved,
+            remember: bulk.remember,
+            ..Default::default()
+        };
+        if apply_approval_response(
+            approval_mgr,
+            session_mgr,
+            &resp,
+            principal,
+            device_id,
+            broadcast_tx,
+        )
+        .await
+        {
+            resolved += 1;
         }
-        false
-    } else {
-        approval_mgr.resolve(resp).await.is_some()
+    }
+    info!(
+        tool = %bulk.tool,
+        domain_pattern = %bulk.domain_pattern,
+        approved = bulk.approved,
+        resolved,
+        principal,
+        "applied bulk approval response"
+    );
+    resolved
+}
+
+/// Read `pending_approvals.json`. A missing file is empty; an unreadable
+/// one is logged and treated as empty.
+fn load_pending(path: &Path) -> Vec<PersistedApproval> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "failed to read pending approvals");
+            return Vec::new();
+        }
+    };
+    serde_json::from_slice(&data).unwrap_or_else(|e| {
+        warn!(path = %path.display(), error = %e, "ignoring malformed pending approvals");
+        Vec::new()
+    })
+}
+
+/// Write the persistent entries of `pending`, owner-only and atomically so
+/// a crash never leaves the file half-written.
+fn save_pending(path: &Path, pending: &HashMap<String, PendingApproval>, redactor: &EnvRedactor) {
+    let mut entries: Vec<PersistedApproval> = pending
+        .values()
+        .filter(|p| p.persistent)
+        .map(|p| PersistedApproval::from_entry(p, redactor))
+        .collect();
+    entries.sort_by_key(|p| p.expires_ms);
+    let result = serde_json::to_vec_pretty(&entries)
+        .map_err(anyhow::Error::from)
+        .and_then(|data| ahand_platform::secure_file::write_secure_file(path, &data));
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "failed to persist pending approvals");
     }
 }
 
@@ -186,12 +954,14 @@ fn now_ms() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ahand_protocol::JobRequest;
+    use prost::Message;
 
+    /// Distinct job_ids get distinct args, so their requests aren't merged.
     fn make_job_request(job_id: &str) -> JobRequest {
         JobRequest {
             job_id: job_id.to_string(),
             tool: "test_tool".to_string(),
+            args: vec![job_id.to_string()],
             cwd: "/tmp".to_string(),
             ..Default::default()
         }
@@ -209,6 +979,8 @@ mod tests {
                 "uid-1",
                 "reason".to_string(),
                 vec![],
+                vec![],
+                vec![],
                 bound,
             )
             .await;
@@ -230,6 +1002,643 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn list_pending_orders_by_expiry() {
+        let mgr = ApprovalManager::new(60);
+        for (job_id, secs) in [("job-late", 30), ("job-soon", 5), ("job-mid", 10)] {
+            let _ = mgr
+                .submit_with_timeout(
+                    make_job_request(job_id),
+                    "uid-1",
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                    Duration::from_secs(secs),
+                )
+                .await;
+        }
+
+        let ids: Vec<_> = mgr
+            .list_pending()
+            .await
+            .into_iter()
+            .map(|r| r.job_id)
+            .collect();
+        assert_eq!(ids, ["job-soon", "job-mid", "job-late"]);
+    }
+
+    /// The sweep goes by each request's own expires_ms, not default_timeout,
+    /// and hands the waiter an "expired" denial.
+    #[tokio::test]
+    async fn sweep_expired_drops_requests_past_their_expiry() {
+        let mgr = ApprovalManager::new(86400);
+        let (_, mut stale_rx) = mgr
+            .submit_with_timeout(
+                make_job_request("job-stale"),
+                "uid-1",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+                Duration::ZERO,
+            )
+            .await;
+        let (_, mut fresh_rx) = mgr
+            .submit(
+                make_job_request("job-fresh"),
+                "uid-1",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+
+        assert_eq!(mgr.sweep_expired().await, ["job-stale"]);
+        let resp = stale_rx.try_recv().unwrap();
+        assert!(!resp.approved);
+        assert_eq!(resp.reason, EXPIRED_REASON);
+        assert!(fresh_rx.try_recv().is_err());
+
+        let ids: Vec<_> = mgr
+            .list_pending()
+            .await
+            .into_iter()
+            .map(|r| r.job_id)
+            .collect();
+        assert_eq!(ids, ["job-fresh"]);
+    }
+
+    /// A response racing the sweep for the same entry: exactly one of them
+    /// takes it, and the waiter sees only that one's answer.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn sweep_and_resolve_race_resolves_exactly_once() {
+        for _ in 0..50 {
+            let mgr = Arc::new(ApprovalManager::new(60));
+            let (_, rx) = mgr
+                .submit_with_timeout(
+                    make_job_request("job-race"),
+                    "uid-1",
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                    Duration::ZERO,
+                )
+                .await;
+            let response = ApprovalResponse {
+                job_id: "job-race".to_string(),
+                approved: true,
+                ..Default::default()
+            };
+
+            let sweep = tokio::spawn({
+                let mgr = Arc::clone(&mgr);
+                async move { mgr.sweep_expired().await }
+            });
+            let resolve = tokio::spawn({
+                let mgr = Arc::clone(&mgr);
+                async move { mgr.resolve(&response).await }
+            });
+            let swept = sweep.await.unwrap();
+            let resolved = resolve.await.unwrap();
+
+            assert_ne!(swept.is_empty(), resolved.is_none());
+            let resp = rx.await.unwrap();
+            assert_eq!(resp.approved, resolved.is_some());
+            assert!(mgr.list_pending().await.is_empty());
+        }
+    }
+
+    /// An approval answered with `remember` is stored in the policy for the
+    /// job's tool and domains, with the response's TTL.
+    #[tokio::test]
+    async fn remembered_approval_is_stored_with_its_ttl() {
+        let policy = Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default()));
+        let mgr = ApprovalManager::new(60).with_policy(Arc::clone(&policy));
+        let _ = mgr
+            .submit(
+                make_job_request("job-1"),
+                "uid-1",
+                "reason".to_string(),
+                vec!["example.com".to_string()],
+                vec![],
+                vec![],
+            )
+            .await;
+        let before_ms = now_ms();
+        mgr.resolve(&ApprovalResponse {
+            job_id: "job-1".to_string(),
+            approved: true,
+            remember: true,
+            remember_ttl_secs: 3600,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let remembered = policy.remembered().await;
+        let keys: Vec<_> = remembered.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["domain:example.com", "tool:test_tool"]);
+        assert!(remembered.iter().all(|r| r.caller_uid == "uid-1"
+            && r.expires_at_ms >= before_ms + 3_600_000
+            && r.expires_at_ms <= now_ms() + 3_600_000));
+    }
+
+    /// Remembering `sh` would approve every later `sh -c ...`, so code
+    /// interpreters are never remembered; other tools still are.
+    #[tokio::test]
+    async fn approvals_for_code_interpreters_are_not_remembered() {
+        let policy = Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default()));
+        let mgr = ApprovalManager::new(60).with_policy(Arc::clone(&policy));
+        let job = |tool: &str, args: &[&str]| JobRequest {
+            tool: tool.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        for req in [
+            job("sh", &["-c", "ls"]),
+            job("/bin/bash", &["-lc", "ls"]),
+            job("python3.12", &["-c", "print(1)"]),
+            job("C:\\Windows\\System32\\cmd.exe", &["/C", "dir"]),
+            job("/usr/bin/env", &["bash", "-c", "ls"]),
+        ] {
+            mgr.remember("uid-1", &req).await;
+        }
+        assert!(policy.remembered().await.is_empty());
+
+        mgr.remember("uid-1", &job("/usr/bin/jq", &[".name"])).await;
+        let keys: Vec<_> = policy
+            .remembered()
+            .await
+            .into_iter()
+            .map(|r| r.key)
+            .collect();
+        assert_eq!(keys, ["tool:/usr/bin/jq"]);
+    }
+
+    #[tokio::test]
+    async fn bulk_response_resolves_pending_requests_for_the_tool_and_domain() {
+        let mgr = Arc::new(ApprovalManager::new(60));
+        let session_mgr = Arc::new(crate::session::SessionManager::new(60));
+        let (broadcast_tx, mut broadcast_rx) = broadcast::channel(8);
+        let mut receivers = Vec::new();
+        for (job_id, tool, domain) in [
+            ("job-gh", "git", "github.com"),
+            ("job-gl", "git", "gitlab.com"),
+            ("job-curl", "curl", "github.com"),
+        ] {
+            let req = JobRequest {
+                tool: tool.to_string(),
+                ..make_job_request(job_id)
+            };
+            let (_, rx) = mgr
+                .submit(
+                    req,
+                    "uid-1",
+                    "reason".to_string(),
+                    vec![domain.to_string()],
+                    vec![],
+                    vec![],
+                )
+                .await;
+            receivers.push(rx);
+        }
+
+        let bulk = ApprovalBulkResponse {
+            tool: "git".to_string(),
+            domain_pattern: "*.github.com".to_string(),
+            approved: true,
+            remember: false,
+        };
+        assert_eq!(
+            apply_bulk_approval_response(
+                &mgr,
+                &session_mgr,
+                &bulk,
+                "uid:501",
+                "dev-1",
+                &broadcast_tx
+            )
+            .await,
+            1
+        );
+        let bulk = ApprovalBulkResponse {
+            domain_pattern: String::new(),
+            ..bulk
+        };
+        assert_eq!(
+            apply_bulk_approval_response(
+                &mgr,
+                &session_mgr,
+                &bulk,
+                "uid:501",
+                "dev-1",
+                &broadcast_tx
+            )
+            .await,
+            1
+        );
+
+        for rx in &mut receivers[..2] {
+            let resp = rx.try_recv().unwrap();
+            assert!(resp.approved);
+            // The waiting job learns who answered, for its run record.
+            assert_eq!(resp.resolved_by, "uid:501");
+        }
+        for job_id in ["job-gh", "job-gl"] {
+            match broadcast_rx.try_recv().unwrap().payload {
+                Some(envelope::Payload::ApprovalResolved(resolved)) => {
+                    assert_eq!(resolved.job_id, job_id);
+                    assert!(resolved.approved);
+                    assert_eq!(resolved.resolved_by, "uid:501");
+                }
+                other => panic!("expected ApprovalResolved, got {other:?}"),
+            }
+        }
+        let ids: Vec<_> = mgr
+            .list_pending()
+            .await
+            .into_iter()
+            .map(|r| r.job_id)
+            .collect();
+        assert_eq!(ids, ["job-curl"]);
+    }
+
+    #[tokio::test]
+    async fn pending_summary_counts_requests_and_ages_the_oldest() {
+        let mgr = ApprovalManager::new(60);
+        assert_eq!(mgr.pending_summary().await, (0, 0));
+
+        for job_id in ["job-1", "job-2"] {
+            let _ = mgr
+                .submit(
+                    make_job_request(job_id),
+                    "uid-1",
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                )
+                .await;
+        }
+        mgr.pending
+            .lock()
+            .await
+            .get_mut("job-1")
+            .unwrap()
+            .submitted_ms -= 54 * 60 * 1000;
+
+        let (count, oldest_age_ms) = mgr.pending_summary().await;
+        assert_eq!(count, 2);
+        assert!((54 * 60 * 1000..55 * 60 * 1000).contains(&oldest_age_ms));
+    }
+
+    /// Only entries marked persistent survive a restart; reloaded ones are
+    /// pending again and resolve like the original would have.
+    #[tokio::test]
+    async fn persisted_request_is_reloaded_and_resolvable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        for job_id in ["job-kept", "job-unmarked"] {
+            let _ = mgr
+                .submit(
+                    make_job_request(job_id),
+                    "cloud",
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                )
+                .await;
+        }
+        mgr.persist_across_restart("job-kept").await;
+        drop(mgr);
+
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let mut restored = mgr.take_restored().await;
+        assert!(restored.dropped.is_empty());
+        assert_eq!(restored.waiting.len(), 1);
+        let waiting = restored.waiting.pop().unwrap();
+        assert_eq!(waiting.request, make_job_request("job-kept"));
+        assert_eq!(waiting.caller_uid, "cloud");
+        assert!(mgr.take_restored().await.waiting.is_empty());
+
+        let pending: Vec<_> = mgr
+            .list_pending()
+            .await
+            .into_iter()
+            .map(|r| r.job_id)
+            .collect();
+        assert_eq!(pending, ["job-kept"]);
+
+        mgr.resolve(&ApprovalResponse {
+            job_id: "job-kept".to_string(),
+            approved: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(waiting.approval_rx.await.unwrap().approved);
+        assert!(load_pending(&path).is_empty());
+    }
+
+    #[tokio::test]
+    async fn persisted_request_past_its_expiry_is_reported_as_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let _ = mgr
+            .submit_with_timeout(
+                make_job_request("job-stale"),
+                "cloud",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+                Duration::ZERO,
+            )
+            .await;
+        mgr.persist_across_restart("job-stale").await;
+        drop(mgr);
+
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let restored = mgr.take_restored().await;
+        assert!(restored.waiting.is_empty());
+        let dropped: Vec<_> = restored
+            .dropped
+            .iter()
+            .map(|d| (d.request.job_id.as_str(), d.reason))
+            .collect();
+        assert_eq!(dropped, [("job-stale", EXPIRED_DURING_RESTART_REASON)]);
+        assert!(mgr.list_pending().await.is_empty());
+        assert!(load_pending(&path).is_empty());
+    }
+
+    /// Jobs attached to a persisted request come back with it and are
+    /// answered together again.
+    #[tokio::test]
+    async fn followers_of_a_persisted_request_are_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let same_args = |job_id: &str| JobRequest {
+            args: vec!["status".to_string()],
+            ..make_job_request(job_id)
+        };
+        for job_id in ["job-1", "job-2"] {
+            let _ = mgr
+                .submit(
+                    same_args(job_id),
+                    "cloud",
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                )
+                .await;
+            mgr.persist_across_restart(job_id).await;
+        }
+        drop(mgr);
+
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let restored = mgr.take_restored().await;
+        assert!(restored.dropped.is_empty());
+        let requests: Vec<_> = restored.waiting.iter().map(|w| w.request.clone()).collect();
+        assert_eq!(requests, [same_args("job-1"), same_args("job-2")]);
+        assert_eq!(mgr.list_pending().await.len(), 1);
+
+        mgr.resolve(&ApprovalResponse {
+            job_id: "job-2".to_string(),
+            approved: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        for waiting in restored.waiting {
+            let resp = waiting.approval_rx.await.unwrap();
+            assert!(resp.approved);
+            assert_eq!(resp.job_id, waiting.request.job_id);
+        }
+        assert!(load_pending(&path).is_empty());
+    }
+
+    /// Secret env values never reach the owner-only pending file, so such
+    /// a request can't run as approved after a restart and is dropped.
+    #[tokio::test]
+    async fn persisted_secret_env_is_redacted_and_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pending_approvals.json");
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let req = JobRequest {
+            env: [("API_TOKEN", "s3cret-value"), ("LANG", "C")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..make_job_request("job-1")
+        };
+        let _ = mgr
+            .submit(req, "cloud", "reason".to_string(), vec![], vec![], vec![])
+            .await;
+        mgr.persist_across_restart("job-1").await;
+        drop(mgr);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600);
+        }
+        let persisted = load_pending(&path);
+        assert!(persisted[0].secret_env);
+        let (on_disk, _) = persisted[0].decode().unwrap();
+        assert_eq!(on_disk.env["LANG"], "C");
+        assert!(on_disk.env["API_TOKEN"].starts_with("<redacted:"));
+
+        let mgr = ApprovalManager::new(60).with_pending_file(path.clone());
+        let restored = mgr.take_restored().await;
+        assert!(restored.waiting.is_empty());
+        let dropped: Vec<_> = restored
+            .dropped
+            .iter()
+            .map(|d| (d.request.job_id.as_str(), d.reason))
+            .collect();
+        assert_eq!(dropped, [("job-1", SECRET_ENV_NOT_RESTORED_REASON)]);
+        assert!(mgr.list_pending().await.is_empty());
+    }
+
+    /// A retry of a pending request under a new job_id shares its prompt;
+    /// resolving it answers both jobs under their own job_ids.
+    #[tokio::test]
+    async fn identical_requests_share_one_entry_and_fan_out_on_resolve() {
+        let mgr = ApprovalManager::new(60);
+        let req = JobRequest {
+            args: vec!["status".to_string()],
+            ..make_job_request("job-1")
+        };
+        let (first, first_rx) = mgr
+            .submit(
+                req.clone(),
+                "uid-1",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+        let retry = JobRequest {
+            job_id: "job-2".to_string(),
+            ..req
+        };
+        let (second, second_rx) = mgr
+            .submit(retry, "uid-1", "reason".to_string(), vec![], vec![], vec![])
+            .await;
+
+        assert_eq!(second.job_id, "job-1");
+        assert_eq!(second.request_hash, first.request_hash);
+        assert_eq!(mgr.list_pending().await.len(), 1);
+
+        let (resolved, _) = mgr
+            .resolve(&ApprovalResponse {
+                job_id: "job-2".to_string(),
+                approved: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(resolved.job_id, "job-1");
+        let first_resp = first_rx.await.unwrap();
+        let second_resp = second_rx.await.unwrap();
+        assert!(first_resp.approved && second_resp.approved);
+        assert_eq!(first_resp.job_id, "job-1");
+        assert_eq!(second_resp.job_id, "job-2");
+        assert!(mgr.list_pending().await.is_empty());
+    }
+
+    /// Requests differing in caller, args or cwd each get their own prompt.
+    #[tokio::test]
+    async fn distinct_requests_are_not_merged() {
+        let mgr = ApprovalManager::new(60);
+        let base = JobRequest {
+            args: vec!["status".to_string()],
+            ..make_job_request("job-base")
+        };
+        let variants = [
+            ("uid-1", base.clone()),
+            (
+                "uid-2",
+                JobRequest {
+                    job_id: "job-caller".to_string(),
+                    ..base.clone()
+                },
+            ),
+            (
+                "uid-1",
+                JobRequest {
+                    job_id: "job-args".to_string(),
+                    args: vec!["stat".to_string(), "us".to_string()],
+                    ..base.clone()
+                },
+            ),
+            (
+                "uid-1",
+                JobRequest {
+                    job_id: "job-cwd".to_string(),
+                    cwd: "/srv".to_string(),
+                    ..base
+                },
+            ),
+        ];
+        let mut hashes = Vec::new();
+        for (caller_uid, req) in variants {
+            let job_id = req.job_id.clone();
+            let (approval_req, _rx) = mgr
+                .submit(
+                    req,
+                    caller_uid,
+                    "reason".to_string(),
+                    vec![],
+                    vec![],
+                    vec![],
+                )
+                .await;
+            assert_eq!(approval_req.job_id, job_id);
+            hashes.push(approval_req.request_hash);
+        }
+
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(mgr.list_pending().await.len(), 4);
+    }
+
+    /// A job differing only in env (here a proxy and a preloaded library)
+    /// runs something else, so it gets its own prompt.
+    #[tokio::test]
+    async fn requests_differing_only_in_env_are_not_merged() {
+        let mgr = ApprovalManager::new(60);
+        let with_env = |job_id: &str, env: &[(&str, &str)]| JobRequest {
+            args: vec!["status".to_string()],
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..make_job_request(job_id)
+        };
+        let submit = |req| mgr.submit(req, "uid-1", "reason".to_string(), vec![], vec![], vec![]);
+
+        let (leader, _rx) = submit(with_env("job-1", &[("LANG", "C")])).await;
+        let (proxied, _rx) = submit(with_env(
+            "job-2",
+            &[("LANG", "C"), ("HTTPS_PROXY", "http://evil.example:8080")],
+        ))
+        .await;
+        let (preloaded, _rx) = submit(with_env(
+            "job-3",
+            &[("LANG", "C"), ("LD_PRELOAD", "/tmp/x.so")],
+        ))
+        .await;
+
+        assert_eq!(leader.job_id, "job-1");
+        assert_eq!(proxied.job_id, "job-2");
+        assert_eq!(preloaded.job_id, "job-3");
+        assert_eq!(mgr.list_pending().await.len(), 3);
+
+        let (retry, _rx) = submit(with_env("job-4", &[("LANG", "C")])).await;
+        assert_eq!(retry.job_id, "job-1");
+    }
+
+    /// The hosts a job reaches travel with its approval request so the
+    /// approver can see them.
+    #[tokio::test]
+    async fn approval_request_carries_detected_domains_on_the_wire() {
+        let mgr = ApprovalManager::new(60);
+        let req = JobRequest {
+            job_id: "job-curl".to_string(),
+            tool: "curl".to_string(),
+            args: vec!["https://api.example.com/v1".to_string()],
+            ..Default::default()
+        };
+        let domains = crate::policy::extract_destinations(&req);
+        let (approval_req, _rx) = mgr
+            .submit(req, "uid-1", "reason".to_string(), domains, vec![], vec![])
+            .await;
+
+        let env = Envelope {
+            payload: Some(envelope::Payload::ApprovalRequest(approval_req)),
+            ..Default::default()
+        };
+        let decoded = Envelope::decode(env.encode_to_vec().as_slice()).unwrap();
+        match decoded.payload {
+            Some(envelope::Payload::ApprovalRequest(req)) => {
+                assert_eq!(req.detected_domains, ["api.example.com"]);
+            }
+            _ => panic!("expected an ApprovalRequest"),
+        }
+    }
+
     /// submit delegates to submit_with_timeout using default_timeout.
     /// Verify expires_ms ≈ now + default_timeout (not the explicit bound).
     #[tokio::test]
@@ -243,6 +1652,8 @@ mod tests {
                 "uid-2",
                 "reason".to_string(),
                 vec![],
+                vec![],
+                vec![],
             )
             .await;
         let after_ms = now_ms();
@@ -262,4 +1673,53 @@ mod tests {
             expected_hi
         );
     }
+
+    #[tokio::test]
+    async fn resolve_and_expire_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = Arc::new(AuditLog::open(dir.path(), 0).unwrap());
+        let mgr = ApprovalManager::new(60).with_audit_log(Arc::clone(&audit));
+        let _pending_a = mgr
+            .submit(
+                make_job_request("job-a"),
+                "uid-1",
+                "r".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+        let _pending_b = mgr
+            .submit(
+                make_job_request("job-b"),
+                "uid-1",
+                "r".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+
+        mgr.resolve(&ApprovalResponse {
+            job_id: "job-a".to_string(),
+            approved: false,
+            reason: "not now".to_string(),
+            ..Default::default()
+        })
+        .await;
+        assert!(mgr.expire("job-b").await);
+
+        let entries = crate::audit::read_tail(audit.path(), 10).unwrap();
+        let summary: Vec<(&str, &str, &str)> = entries
+            .iter()
+            .map(|e| (e.job_id.as_str(), e.decision.as_str(), e.reason.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("job-a", "denied", "not now"),
+                ("job-b", "expired", "approval timed out")
+            ]
+        );
+    }
 }
@@ -0,0 +1,269 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use ahand_protocol::JobRequest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+pub const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+/// Number of rotated files kept next to the live one.
+const ROTATED_FILES: u32 = 3;
+
+/// One audit record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub ts_ms: u64,
+    /// Component that decided: `policy`, `session` or `approval`.
+    pub source: String,
+    pub caller_uid: String,
+    pub job_id: String,
+    pub tool: String,
+    /// SHA-256 of the job arguments (hex).
+    pub args_hash: String,
+    /// `allow`, `deny` or `needs_approval`; for approvals `approved`,
+    /// `denied` or `expired`.
+    pub decision: String,
+    pub reason: String,
+    /// Rule or mode that produced the decision, when there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+impl AuditEntry {
+    /// Build an entry for a job request. Only the tool name and a hash of
+    /// the arguments are taken from `req`.
+    pub fn for_job(
+        source: &str,
+        req: &JobRequest,
+        caller_uid: &str,
+        decision: &str,
+        reason: &str,
+        rule: Option<String>,
+    ) -> Self {
+        Self {
+            ts_ms: now_ms(),
+            source: source.to_string(),
+            caller_uid: caller_uid.to_string(),
+            job_id: req.job_id.clone(),
+            tool: req.tool.clone(),
+            args_hash: args_hash(&req.args),
+            decision: decision.to_string(),
+            reason: reason.to_string(),
+            rule,
+        }
+    }
+}
+
+/// Hex SHA-256 over the length-prefixed arguments.
+pub fn args_hash(args: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update((args.len() as u64).to_le_bytes());
+    for arg in args {
+        hasher.update((arg.len() as u64).to_le_bytes());
+        hasher.update(arg.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+struct AuditFile {
+    file: File,
+    len: u64,
+}
+
+/// Append-only writer for `<data_dir>/audit.jsonl`, one line per policy,
+/// session or approval decision. Job arguments are stored only as a hash
+/// and env vars never, so the log can be handed to reviewers as-is.
+///
+/// Entries are written unbuffered so nothing is lost if the daemon dies.
+/// Past `max_bytes` the file rotates to `audit.jsonl.1` (older files shift
+/// up to `audit.jsonl.3`).
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Mutex<AuditFile>,
+}
+
+impl AuditLog {
+    /// Open (or create) `<data_dir>/audit.jsonl`. `max_bytes == 0` disables
+    /// rotation.
+    pub fn open(data_dir: &Path, max_bytes: u64) -> std::io::Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(AUDIT_FILE_NAME);
+        let file = open_append(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            file: Mutex::new(AuditFile { file, len }),
+        })
+    }
+
+    // Bin target never calls this directly; used by tests and ahandctl.
+    #[allow(dead_code)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one entry, rotating first if it would overflow the size cap.
+    pub async fn record(&self, entry: AuditEntry) {
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(error = %e, "failed to serialize audit entry");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut out = self.file.lock().await;
+        if self.max_bytes > 0
+            && out.len > 0
+            && out.len + line.len() as u64 > self.max_bytes
+            && let Err(e) = self.rotate(&mut out)
+        {
+            warn!(error = %e, "failed to rotate audit log");
+        }
+        match out.file.write_all(&line) {
+            Ok(()) => out.len += line.len() as u64,
+            Err(e) => warn!(error = %e, "failed to write audit entry"),
+        }
+    }
+
+    fn rotate(&self, out: &mut AuditFile) -> std::io::Result<()> {
+        for n in (1..ROTATED_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        out.file = open_append(&self.path)?;
+        out.len = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Read the last `n` entries of an audit file, oldest first. Lines that do
+/// not parse are skipped.
+// Bin target never calls this directly; `ahandctl audit tail` and the admin
+// panel read the log through the lib crate.
+#[allow(dead_code)]
+pub fn read_tail(path: &Path, n: usize) -> std::io::Result<Vec<AuditEntry>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let entries: Vec<AuditEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let skip = entries.len().saturating_sub(n);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> JobRequest {
+        JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "curl".to_string(),
+            args: vec!["-H".to_string(), "Authorization: hunter2".to_string()],
+            env: [("API_TOKEN".to_string(), "s3cr3t-value".to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn entries_carry_no_env_or_raw_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(dir.path(), 0).unwrap();
+        log.record(AuditEntry::for_job(
+            "policy",
+            &request(),
+            "uid:501",
+            "deny",
+            "tool \"curl\" is in the deny list",
+            Some("denied_tools".to_string()),
+        ))
+        .await;
+
+        let written = std::fs::read_to_string(log.path()).unwrap();
+        assert!(!written.contains("s3cr3t-value"), "{written}");
+        assert!(!written.contains("API_TOKEN"), "{written}");
+        assert!(!written.contains("hunter2"), "{written}");
+
+        let entries = read_tail(log.path(), 10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].job_id, "job-1");
+        assert_eq!(entries[0].args_hash, args_hash(&request().args));
+        assert_eq!(entries[0].rule.as_deref(), Some("denied_tools"));
+    }
+
+    #[tokio::test]
+    async fn rotates_when_size_cap_is_reached() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(dir.path(), 400).unwrap();
+        for _ in 0..10 {
+            log.record(AuditEntry::for_job(
+                "session",
+                &request(),
+                "uid:501",
+                "allow",
+                "",
+                None,
+            ))
+            .await;
+        }
+
+        assert!(std::fs::metadata(log.path()).unwrap().len() <= 400);
+        assert!(rotated_path(log.path(), 1).exists());
+        assert!(rotated_path(log.path(), 3).exists());
+        assert!(!rotated_path(log.path(), 4).exists());
+    }
+
+    #[test]
+    fn read_tail_returns_last_entries_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE_NAME);
+        let lines: Vec<String> = (0..5)
+            .map(|i| {
+                let mut entry =
+                    AuditEntry::for_job("policy", &request(), "uid:501", "allow", "", None);
+                entry.job_id = format!("job-{i}");
+                serde_json::to_string(&entry).unwrap()
+            })
+            .collect();
+        std::fs::write(&path, lines.join("\nnot json\n")).unwrap();
+
+        let ids: Vec<String> = read_tail(&path, 2)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.job_id)
+            .collect();
+        assert_eq!(ids, ["job-3", "job-4"]);
+    }
+}
@@ -1,12 +1,18 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use tokio::sync::Mutex;
 use tracing::{info, warn};
 
-use crate::config::BrowserConfig;
+use crate::config::{BrowserConfig, domain_matches};
+
+/// How often [`sweep_sessions`] looks for idle or expired sessions.
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+const DEFAULT_SESSION_IDLE_SECS: u64 = 900;
 
 /// Result of executing a browser command via playwright-cli.
 #[derive(Default)]
@@ -16,18 +22,39 @@ pub struct BrowserCommandResult {
     pub error: String,
     pub binary_data: Vec<u8>,
     pub binary_mime: String,
+    /// File the command left in the session's downloads directory
+    /// (screenshot, pdf, snapshot or download), if any.
+    pub output_path: Option<PathBuf>,
+}
+
+impl BrowserCommandResult {
+    /// MIME type of [`BrowserCommandResult::output_path`], from its extension.
+    pub fn output_mime(&self) -> Option<&'static str> {
+        let path = self.output_path.as_ref()?;
+        Some(mime_from_extension(&path.to_string_lossy()))
+    }
+}
+
+/// A browser session the daemon is tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserSession {
+    pub id: String,
+    /// When its first command ran, in ms since the Unix epoch.
+    pub created_ms: u64,
+    /// When its latest command ran, in ms since the Unix epoch.
+    pub last_used_ms: u64,
 }
 
 pub struct BrowserManager {
     config: BrowserConfig,
-    active_sessions: Mutex<HashSet<String>>,
+    active_sessions: Mutex<HashMap<String, BrowserSession>>,
 }
 
 impl BrowserManager {
     pub fn new(config: BrowserConfig) -> Self {
         let mgr = Self {
             config,
-            active_sessions: Mutex::new(HashSet::new()),
+            active_sessions: Mutex::new(HashMap::new()),
         };
         if mgr.is_enabled() {
             mgr.check_prerequisites();
@@ -46,15 +73,7 @@ impl BrowserManager {
 
     /// Resolve the downloads directory (for download/pdf output files).
     fn downloads_dir(&self, session_id: &str) -> PathBuf {
-        let base = match &self.config.downloads_dir {
-            Some(p) => PathBuf::from(p),
-            None => dirs::home_dir()
-                .unwrap_or_else(std::env::temp_dir)
-                .join(".ahand")
-                .join("browser")
-                .join("downloads"),
-        };
-        base.join(session_id)
+        self.config.downloads_root().join(session_id)
     }
 
     /// Generate a default output path when the caller doesn't provide one.
@@ -111,14 +130,22 @@ impl BrowserManager {
         {
             let mut sessions = self.active_sessions.lock().await;
             let max = self.config.max_sessions.unwrap_or(4);
-            if !sessions.contains(session_id) && sessions.len() >= max {
+            if !sessions.contains_key(session_id) && sessions.len() >= max {
                 return Ok(BrowserCommandResult {
                     success: false,
                     error: format!("max browser sessions ({}) reached", max),
                     ..Default::default()
                 });
             }
-            sessions.insert(session_id.to_string());
+            let now = now_ms();
+            sessions
+                .entry(session_id.to_string())
+                .or_insert_with(|| BrowserSession {
+                    id: session_id.to_string(),
+                    created_ms: now,
+                    last_used_ms: now,
+                })
+                .last_used_ms = now;
         }
 
         // Determine output file path for actions that produce files.
@@ -158,6 +185,7 @@ impl BrowserManager {
             .envs(envs)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn();
 
         let child = match child {
@@ -216,6 +244,7 @@ impl BrowserManager {
             .envs(envs)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn();
 
         let child = match child {
@@ -302,6 +331,7 @@ impl BrowserManager {
                         result_json: format!("Downloaded: {}", path_str),
                         binary_data,
                         binary_mime,
+                        output_path: Some(file.clone()),
                         ..Default::default()
                     });
                 }
@@ -410,6 +440,60 @@ impl BrowserManager {
         self.active_sessions.lock().await.remove(session_id);
     }
 
+    /// The sessions being tracked, oldest first.
+    pub async fn list_sessions(&self) -> Vec<BrowserSession> {
+        let mut sessions: Vec<_> = self
+            .active_sessions
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect();
+        sessions.sort_by(|a, b| (a.created_ms, &a.id).cmp(&(b.created_ms, &b.id)));
+        sessions
+    }
+
+    /// Stop tracking sessions idle longer than `session_idle_secs` or older
+    /// than `session_max_age_secs` and close their browsers. Returns the
+    /// ids closed.
+    pub async fn close_stale_sessions(&self) -> Vec<String> {
+        let idle = match self
+            .config
+            .session_idle_secs
+            .unwrap_or(DEFAULT_SESSION_IDLE_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let max_age = self
+            .config
+            .session_max_age_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+
+        let stale = {
+            let mut sessions = self.active_sessions.lock().await;
+            let stale = stale_sessions(sessions.values(), now_ms(), idle, max_age);
+            for id in &stale {
+                sessions.remove(id);
+            }
+            stale
+        };
+
+        for id in &stale {
+            match self.execute_single(id, "close", "{}", 0).await {
+                Ok(result) if result.success => {}
+                Ok(result) => {
+                    warn!(session_id = %id, error = %result.error, "failed to close stale browser session")
+                }
+                Err(e) => {
+                    warn!(session_id = %id, error = %e, "failed to close stale browser session")
+                }
+            }
+        }
+        stale
+    }
+
     /// Resolve playwright-cli as `(program, leading_args)`.
     ///
     /// Thin wrapper over [`cli_invocation_with`] that reads the live config
@@ -512,6 +596,9 @@ impl BrowserManager {
             error: if success { String::new() } else { stderr },
             binary_data,
             binary_mime,
+            output_path: output_file
+                .filter(|path| success && path.is_file())
+                .map(Path::to_path_buf),
         })
     }
 
@@ -532,6 +619,47 @@ impl BrowserManager {
     }
 }
 
+/// Ids of the `sessions` to close at `now_ms`: idle for at least `idle`,
+/// or open for at least `max_age`.
+fn stale_sessions<'a>(
+    sessions: impl IntoIterator<Item = &'a BrowserSession>,
+    now_ms: u64,
+    idle: Option<Duration>,
+    max_age: Option<Duration>,
+) -> Vec<String> {
+    let reached = |since_ms: u64, limit: Option<Duration>| {
+        limit.is_some_and(|limit| now_ms.saturating_sub(since_ms) >= limit.as_millis() as u64)
+    };
+    sessions
+        .into_iter()
+        .filter(|session| {
+            reached(session.last_used_ms, idle) || reached(session.created_ms, max_age)
+        })
+        .map(|session| session.id.clone())
+        .collect()
+}
+
+/// Run [`BrowserManager::close_stale_sessions`] every
+/// `SESSION_SWEEP_INTERVAL`, so sessions whose agent went away without
+/// sending "close" don't hold a `max_sessions` slot forever.
+pub async fn sweep_sessions(browser_mgr: Arc<BrowserManager>) {
+    let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let closed = browser_mgr.close_stale_sessions().await;
+        if !closed.is_empty() {
+            info!(sessions = ?closed, "closed stale browser sessions");
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 /// Pure resolution of playwright-cli invocation — the testable core of the
 /// three-priority chain:
 ///
@@ -790,6 +918,8 @@ fn mime_from_extension(path: &str) -> &'static str {
         "text/html"
     } else if lower.ends_with(".xml") {
         "application/xml"
+    } else if lower.ends_with(".yaml") || lower.ends_with(".yml") {
+        "application/yaml"
     } else if lower.ends_with(".zip") {
         "application/zip"
     } else if lower.ends_with(".xlsx") {
@@ -825,19 +955,81 @@ fn extract_domain(url: &str) -> String {
         .to_lowercase()
 }
 
-/// Check if a domain matches a pattern (supports wildcard prefix like "*.example.com").
-fn domain_matches(domain: &str, pattern: &str) -> bool {
-    if let Some(suffix) = pattern.strip_prefix("*.") {
-        domain == suffix || domain.ends_with(&format!(".{}", suffix))
-    } else {
-        domain == pattern
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn session(id: &str, created_ms: u64, last_used_ms: u64) -> BrowserSession {
+        BrowserSession {
+            id: id.to_string(),
+            created_ms,
+            last_used_ms,
+        }
+    }
+
+    #[test]
+    fn idle_and_overage_sessions_are_stale() {
+        let idle = Some(Duration::from_secs(900));
+        let max_age = Some(Duration::from_secs(3600));
+        let now = 10_000_000;
+        let sessions = [
+            session("fresh", now - 60_000, now - 1_000),
+            session("idle", now - 1_000_000, now - 900_000),
+            session("old-but-busy", now - 3_600_000, now),
+        ];
+
+        assert_eq!(
+            stale_sessions(&sessions, now, idle, max_age),
+            ["idle", "old-but-busy"]
+        );
+        assert_eq!(stale_sessions(&sessions, now, idle, None), ["idle"]);
+        assert!(stale_sessions(&sessions, now, None, None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn closing_stale_sessions_frees_their_slots() {
+        let mgr = BrowserManager::new(crate::config::BrowserConfig {
+            // Never spawned successfully: the close is best effort.
+            binary_path: Some("/nonexistent/playwright-cli".to_string()),
+            max_sessions: Some(2),
+            session_idle_secs: Some(60),
+            ..Default::default()
+        });
+        let now = now_ms();
+        {
+            let mut sessions = mgr.active_sessions.lock().await;
+            for s in [
+                session("crashed", now - 600_000, now - 120_000),
+                session("live", now - 30_000, now),
+            ] {
+                sessions.insert(s.id.clone(), s);
+            }
+        }
+
+        assert_eq!(mgr.close_stale_sessions().await, ["crashed"]);
+        let remaining = mgr.list_sessions().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "live");
+    }
+
+    #[tokio::test]
+    async fn list_sessions_is_oldest_first() {
+        let mgr = BrowserManager::new(Default::default());
+        {
+            let mut sessions = mgr.active_sessions.lock().await;
+            for s in [session("b", 2_000, 5_000), session("a", 1_000, 9_000)] {
+                sessions.insert(s.id.clone(), s);
+            }
+        }
+        let ids: Vec<_> = mgr
+            .list_sessions()
+            .await
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+
     // ─────────────────────────────────────────────────────────────────────────
     // check_domain deny-wins test (audit TOP-3)
     // ─────────────────────────────────────────────────────────────────────────
@@ -0,0 +1,97 @@
+//! What the daemon tells its peers it can do, as the wire names sent in
+//! `Hello.capabilities` to the hub and `HelloAccepted.capabilities` to IPC
+//! clients. Peers feature-gate on these, so they have to follow the live
+//! configuration rather than a fixed list.
+
+/// Protocol features every daemon build handles, whatever its plugins.
+const CORE: [&str; 4] = ["cancel", "approval", "policy", "session"];
+
+/// Version of the stdin protocol for interactive jobs.
+const STDIN_VERSION: u32 = 1;
+
+/// Wire capability names in insertion order, without duplicates. A feature
+/// whose protocol may change is advertised as `<feature>/<version>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilitySet {
+    names: Vec<String>,
+}
+
+impl CapabilitySet {
+    pub fn insert(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if !self.contains(&name) {
+            self.names.push(name);
+        }
+    }
+
+    pub fn insert_versioned(&mut self, feature: &str, version: u32) {
+        self.insert(format!("{feature}/{version}"));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+    }
+
+    pub fn into_wire(self) -> Vec<String> {
+        self.names
+    }
+}
+
+/// The daemon's capabilities: `plugins`, the capability router's active wire
+/// names (exec, file, browser-…), then the core protocol features, then
+/// "persistence" when a run store keeps job history.
+pub fn daemon_capabilities<'a>(
+    plugins: impl IntoIterator<Item = &'a str>,
+    persistence: bool,
+) -> CapabilitySet {
+    let mut set = CapabilitySet::default();
+    for name in plugins {
+        set.insert(name);
+    }
+    for name in CORE {
+        set.insert(name);
+    }
+    set.insert_versioned("stdin", STDIN_VERSION);
+    if persistence {
+        set.insert("persistence");
+    }
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugins_come_first_and_persistence_follows_the_store() {
+        let with_store = daemon_capabilities(["exec", "browser-playwright-cli"], true);
+        assert_eq!(
+            with_store.into_wire(),
+            vec![
+                "exec",
+                "browser-playwright-cli",
+                "cancel",
+                "approval",
+                "policy",
+                "session",
+                "stdin/1",
+                "persistence",
+            ]
+        );
+
+        let without = daemon_capabilities(["exec"], false);
+        assert!(without.contains("exec"));
+        assert!(!without.contains("browser-playwright-cli"));
+        assert!(!without.contains("persistence"));
+    }
+
+    #[test]
+    fn names_are_deduplicated_in_insertion_order() {
+        let mut set = CapabilitySet::default();
+        set.insert("exec");
+        set.insert_versioned("stdin", 2);
+        set.insert("exec");
+        set.insert_versioned("stdin", 2);
+        assert_eq!(set.into_wire(), vec!["exec", "stdin/2"]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Live state of the cloud connection. The client loop publishes every
+//! transition; IPC `DaemonStatusQuery` and `Ping` read it back so
+//! `ahandctl status` can tell "running" from "running but offline".
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ahand_protocol::{ConnectionPhase, ConnectionStatus};
+use tokio::sync::watch;
+
+use crate::outbox::Outbox;
+
+/// Where the cloud connection stands. Times are Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// No cloud client: OpenClaw mode, shutting down, or gave up.
+    Disabled,
+    Connecting {
+        url: String,
+    },
+    Connected {
+        url: String,
+        /// False when `url` is a fallback from `server_urls`.
+        primary: bool,
+        since_ms: u64,
+    },
+    /// Waiting out the reconnect delay.
+    Backoff {
+        next_attempt_ms: u64,
+    },
+}
+
+/// Shared between the client loop, which writes, and IPC, which reads.
+pub struct ConnectionMonitor {
+    state: watch::Sender<ConnectionState>,
+    last_inbound_ms: AtomicU64,
+    outbox: Mutex<Option<Arc<Mutex<Outbox>>>>,
+    dropped_chunks: AtomicU64,
+    send_timeouts: AtomicU64,
+}
+
+impl Default for ConnectionMonitor {
+    fn default() -> Self {
+        Self {
+            state: watch::Sender::new(ConnectionState::Disabled),
+            last_inbound_ms: AtomicU64::new(0),
+            outbox: Mutex::new(None),
+            dropped_chunks: AtomicU64::new(0),
+            send_timeouts: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ConnectionMonitor {
+    pub fn state(&self) -> ConnectionState {
+        self.state.borrow().clone()
+    }
+
+    pub fn connecting(&self, url: &str) {
+        self.state.send_replace(ConnectionState::Connecting {
+            url: url.to_string(),
+        });
+    }
+
+    pub fn connected(&self, url: &str, primary: bool) {
+        self.state.send_replace(ConnectionState::Connected {
+            url: url.to_string(),
+            primary,
+            since_ms: now_ms(),
+        });
+    }
+
+    pub fn backoff(&self, wait: Duration) {
+        self.state.send_replace(ConnectionState::Backoff {
+            next_attempt_ms: now_ms() + wait.as_millis() as u64,
+        });
+    }
+
+    pub fn disable(&self) {
+        self.state.send_replace(ConnectionState::Disabled);
+    }
+
+    /// Note a frame from the hub.
+    pub fn saw_inbound(&self) {
+        self.last_inbound_ms.store(now_ms(), Ordering::Relaxed);
+    }
+
+    /// Count a job output chunk dropped from a full send queue; returns the
+    /// total so far.
+    pub fn chunk_dropped(&self) -> u64 {
+        self.dropped_chunks.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Count a connection given up because a write stalled.
+    pub fn send_timed_out(&self) {
+        self.send_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Report this outbox's unacknowledged messages as the outbox depth.
+    pub fn attach_outbox(&self, outbox: Arc<Mutex<Outbox>>) {
+        *self.outbox.lock().expect("monitor mutex poisoned") = Some(outbox);
+    }
+
+    /// The wire form for `DaemonStatus.connection`.
+    pub fn status(&self) -> ConnectionStatus {
+        let outbox_depth = self
+            .outbox
+            .lock()
+            .expect("monitor mutex poisoned")
+            .as_ref()
+            .map_or(0, |outbox| {
+                outbox
+                    .lock()
+                    .expect("outbox mutex poisoned")
+                    .pending_count() as u32
+            });
+        let mut status = ConnectionStatus {
+            last_inbound_ms: self.last_inbound_ms.load(Ordering::Relaxed),
+            outbox_depth,
+            dropped_chunks: self.dropped_chunks.load(Ordering::Relaxed),
+            send_timeouts: self.send_timeouts.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        match self.state() {
+            ConnectionState::Disabled => status.set_phase(ConnectionPhase::Disabled),
+            ConnectionState::Connecting { url } => {
+                status.set_phase(ConnectionPhase::Connecting);
+                status.url = url;
+            }
+            ConnectionState::Connected {
+                url,
+                primary,
+                since_ms,
+            } => {
+                status.set_phase(ConnectionPhase::Connected);
+                status.url = url;
+                status.failover = !primary;
+                status.since_ms = since_ms;
+            }
+            ConnectionState::Backoff { next_attempt_ms } => {
+                status.set_phase(ConnectionPhase::Backoff);
+                status.next_attempt_ms = next_attempt_ms;
+            }
+        }
+        status
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_reflects_the_latest_transition() {
+        let monitor = ConnectionMonitor::default();
+        assert_eq!(monitor.status().phase(), ConnectionPhase::Disabled);
+
+        monitor.connected("wss://backup/ws", false);
+        monitor.saw_inbound();
+        let status = monitor.status();
+        assert_eq!(status.phase(), ConnectionPhase::Connected);
+        assert_eq!(status.url, "wss://backup/ws");
+        assert!(status.failover);
+        assert!(status.since_ms > 0 && status.last_inbound_ms >= status.since_ms);
+
+        monitor.backoff(Duration::from_secs(5));
+        let status = monitor.status();
+        assert_eq!(status.phase(), ConnectionPhase::Backoff);
+        assert!(status.url.is_empty());
+        assert!(status.next_attempt_ms >= now_ms() + 4_000);
+        // The last frame seen outlives the connection it arrived on.
+        assert!(status.last_inbound_ms > 0);
+    }
+}
@@ -1,14 +1,15 @@
 use std::io::{Read, Write};
 use std::sync::Arc;
+use std::time::Instant;
 
-use ahand_protocol::{Envelope, JobEvent, JobFinished, JobRequest, envelope, job_event};
+use ahand_protocol::{Envelope, JobEvent, JobFinished, JobQueued, JobRequest, envelope, job_event};
 use portable_pty::{CommandBuilder, PtySize, native_pty_system};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tokio::sync::mpsc;
 use tracing::{info, warn};
 
-use crate::store::RunStore;
+use crate::store::{OutcomeKind, RunContext, RunOutcome, RunStore};
 
 /// Messages that can be sent to the PTY stdin channel.
 pub enum StdinInput {
@@ -21,6 +22,26 @@ pub enum StdinInput {
 /// Sender half of the PTY stdin channel.
 pub type StdinSender = mpsc::UnboundedSender<StdinInput>;
 
+/// Why a running job is being cancelled. Carried on the cancel channel so
+/// the executor can report the cause in `JobFinished.error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// A caller explicitly cancelled the job (`CancelJob`).
+    Requested,
+    /// The daemon is shutting down and is draining its running jobs.
+    Shutdown,
+}
+
+impl CancelReason {
+    /// The `JobFinished.error` string reported for this reason.
+    pub fn as_error(self) -> &'static str {
+        match self {
+            CancelReason::Requested => "cancelled",
+            CancelReason::Shutdown => "cancelled: daemon shutting down",
+        }
+    }
+}
+
 pub trait EnvelopeSink: Clone + Send + Sync + 'static {
     #[allow(clippy::result_unit_err)] // () is sufficient; no error info needed across channel boundary
     fn send(&self, envelope: Envelope) -> Result<(), ()>;
@@ -97,17 +118,24 @@ pub fn resolve_tool(tool: &str, shell_env: Option<&str>) -> ResolvedTool {
 /// Runs a job and sends Envelope-wrapped events back via the channel.
 ///
 /// Listens on `cancel_rx` for a cancellation signal.  When received the child
-/// process is killed and a `JobFinished` with `error = "cancelled"` is sent.
+/// process is killed and a `JobFinished` whose `error` describes the
+/// [`CancelReason`] (e.g. `"cancelled"`) is sent.
 ///
 /// If a `RunStore` is provided, stdout/stderr chunks and the final result are
 /// persisted to disk.
+///
+/// Call this once the job holds its concurrency permit: the run duration is
+/// measured from here, and `queued_ms` (time spent waiting for the permit) is
+/// passed through to `JobFinished`.
 /// Returns `(exit_code, error)` for the caller to use (e.g. for idempotency caching).
 pub async fn run_job<T>(
     device_id: String,
     req: JobRequest,
+    context: RunContext,
     tx: T,
-    cancel_rx: mpsc::Receiver<()>,
+    cancel_rx: mpsc::Receiver<CancelReason>,
     store: Option<Arc<RunStore>>,
+    queued_ms: u64,
 ) -> (i32, String)
 where
     T: EnvelopeSink,
@@ -116,25 +144,32 @@ where
         &req.tool,
         ahand_platform::shell::env_shell().as_deref(),
     ));
-    run_job_with_target(device_id, req, target, tx, cancel_rx, store).await
+    run_job_with_target(
+        device_id, req, context, target, tx, cancel_rx, store, queued_ms,
+    )
+    .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run_job_with_target<T>(
     device_id: String,
     req: JobRequest,
+    context: RunContext,
     target: ExecutionTarget,
     tx: T,
-    mut cancel_rx: mpsc::Receiver<()>,
+    mut cancel_rx: mpsc::Receiver<CancelReason>,
     store: Option<Arc<RunStore>>,
+    queued_ms: u64,
 ) -> (i32, String)
 where
     T: EnvelopeSink,
 {
     let job_id = req.job_id.clone();
     info!(job_id = %job_id, tool = %req.tool, "starting job");
+    let mut timing = JobTiming::start(queued_ms);
 
     if let Some(s) = &store {
-        s.start_run(&job_id, &req);
+        s.start_run(&job_id, &context.caller_uid, &req);
     }
 
     let mut cmd = Command::new(&target.path);
@@ -163,9 +198,20 @@ where
         Ok(c) => c,
         Err(e) => {
             warn!(job_id = %job_id, error = %e, "failed to spawn");
-            return finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store);
+            return finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await;
         }
     };
+    timing.spawned = true;
 
     let stdout = child.stdout.take();
     let stderr = child.stderr.take();
@@ -243,27 +289,56 @@ where
                         let _ = child.kill().await;
                         let _ = stdout_handle.await;
                         let _ = stderr_handle.await;
-                        return finish(&device_id, &job_id, -1, "timeout", &tx, &store);
+                        return finish(
+                            &device_id,
+                            &job_id,
+                            -1,
+                            "timeout",
+                            context.outcome(OutcomeKind::Timeout),
+                            &timing,
+                            &tx,
+                            &store,
+                        ).await;
                     }
                 }
             }
-            _ = cancel_rx.recv() => {
-                warn!(job_id = %job_id, "job cancelled, killing process");
+            reason = cancel_rx.recv() => {
+                let reason = reason.unwrap_or(CancelReason::Requested);
+                warn!(job_id = %job_id, ?reason, "job cancelled, killing process");
                 let _ = child.kill().await;
                 let _ = stdout_handle.await;
                 let _ = stderr_handle.await;
-                return finish(&device_id, &job_id, -1, "cancelled", &tx, &store);
+                return finish(
+                    &device_id,
+                    &job_id,
+                    -1,
+                    reason.as_error(),
+                    context.outcome(OutcomeKind::Cancelled),
+                    &timing,
+                    &tx,
+                    &store,
+                ).await;
             }
         }
     } else {
         tokio::select! {
             r = child.wait() => Some(r),
-            _ = cancel_rx.recv() => {
-                warn!(job_id = %job_id, "job cancelled, killing process");
+            reason = cancel_rx.recv() => {
+                let reason = reason.unwrap_or(CancelReason::Requested);
+                warn!(job_id = %job_id, ?reason, "job cancelled, killing process");
                 let _ = child.kill().await;
                 let _ = stdout_handle.await;
                 let _ = stderr_handle.await;
-                return finish(&device_id, &job_id, -1, "cancelled", &tx, &store);
+                return finish(
+                    &device_id,
+                    &job_id,
+                    -1,
+                    reason.as_error(),
+                    context.outcome(OutcomeKind::Cancelled),
+                    &timing,
+                    &tx,
+                    &store,
+                ).await;
             }
         }
     };
@@ -275,15 +350,45 @@ where
         Some(Ok(status)) => {
             let code = status.code().unwrap_or(-1);
             info!(job_id = %job_id, exit_code = code, "job finished");
-            finish(&device_id, &job_id, code, "", &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                code,
+                "",
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
         Some(Err(e)) => {
             warn!(job_id = %job_id, error = %e, "job wait error");
-            finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
         None => {
             // Should not happen, but handle gracefully.
-            finish(&device_id, &job_id, -1, "unknown error", &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                -1,
+                "unknown error",
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
     }
 }
@@ -298,22 +403,26 @@ where
 /// child, or resize requests.
 ///
 /// Returns `(exit_code, error)` just like `run_job`.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_job_pty<T>(
     device_id: String,
     req: JobRequest,
+    context: RunContext,
     tx: T,
-    mut cancel_rx: mpsc::Receiver<()>,
+    mut cancel_rx: mpsc::Receiver<CancelReason>,
     mut stdin_rx: mpsc::UnboundedReceiver<StdinInput>,
     store: Option<Arc<RunStore>>,
+    queued_ms: u64,
 ) -> (i32, String)
 where
     T: EnvelopeSink,
 {
     let job_id = req.job_id.clone();
     info!(job_id = %job_id, tool = %req.tool, "starting pty job");
+    let mut timing = JobTiming::start(queued_ms);
 
     if let Some(s) = &store {
-        s.start_run(&job_id, &req);
+        s.start_run(&job_id, &context.caller_uid, &req);
     }
 
     // --- Allocate PTY ---------------------------------------------------
@@ -327,7 +436,17 @@ where
         Ok(p) => p,
         Err(e) => {
             warn!(job_id = %job_id, error = %e, "failed to open pty");
-            return finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store);
+            return finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await;
         }
     };
 
@@ -357,9 +476,20 @@ where
         Ok(c) => c,
         Err(e) => {
             warn!(job_id = %job_id, error = %e, "failed to spawn in pty");
-            return finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store);
+            return finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await;
         }
     };
+    timing.spawned = true;
 
     // Drop the slave so the reader gets EOF when the child exits.
     drop(pair.slave);
@@ -370,7 +500,17 @@ where
         Err(e) => {
             warn!(job_id = %job_id, error = %e, "failed to clone pty reader");
             let _ = child.kill();
-            return finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store);
+            return finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await;
         }
     };
 
@@ -379,7 +519,17 @@ where
         Err(e) => {
             warn!(job_id = %job_id, error = %e, "failed to take pty writer");
             let _ = child.kill();
-            return finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store);
+            return finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await;
         }
     };
 
@@ -462,27 +612,56 @@ where
                         drop(master);
                         stdin_handle.abort();
                         let _ = output_handle.await;
-                        return finish(&device_id, &job_id, -1, "timeout", &tx, &store);
+                        return finish(
+                            &device_id,
+                            &job_id,
+                            -1,
+                            "timeout",
+                            context.outcome(OutcomeKind::Timeout),
+                            &timing,
+                            &tx,
+                            &store,
+                        ).await;
                     }
                 }
             }
-            _ = cancel_rx.recv() => {
-                warn!(job_id = %job_id_wait, "pty job cancelled");
+            reason = cancel_rx.recv() => {
+                let reason = reason.unwrap_or(CancelReason::Requested);
+                warn!(job_id = %job_id_wait, ?reason, "pty job cancelled");
                 drop(master);
                 stdin_handle.abort();
                 let _ = output_handle.await;
-                return finish(&device_id, &job_id, -1, "cancelled", &tx, &store);
+                return finish(
+                    &device_id,
+                    &job_id,
+                    -1,
+                    reason.as_error(),
+                    context.outcome(OutcomeKind::Cancelled),
+                    &timing,
+                    &tx,
+                    &store,
+                ).await;
             }
         }
     } else {
         tokio::select! {
             r = wait_future => Some(r),
-            _ = cancel_rx.recv() => {
-                warn!(job_id = %job_id_wait, "pty job cancelled");
+            reason = cancel_rx.recv() => {
+                let reason = reason.unwrap_or(CancelReason::Requested);
+                warn!(job_id = %job_id_wait, ?reason, "pty job cancelled");
                 drop(master);
                 stdin_handle.abort();
                 let _ = output_handle.await;
-                return finish(&device_id, &job_id, -1, "cancelled", &tx, &store);
+                return finish(
+                    &device_id,
+                    &job_id,
+                    -1,
+                    reason.as_error(),
+                    context.outcome(OutcomeKind::Cancelled),
+                    &timing,
+                    &tx,
+                    &store,
+                ).await;
             }
         }
     };
@@ -497,31 +676,113 @@ where
         Some(Ok(Ok(status))) => {
             let code = status.exit_code() as i32;
             info!(job_id = %job_id, exit_code = code, "pty job finished");
-            finish(&device_id, &job_id, code, "", &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                code,
+                "",
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
         Some(Ok(Err(e))) => {
             warn!(job_id = %job_id, error = %e, "pty job wait error");
-            finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
         Some(Err(e)) => {
             // JoinError from spawn_blocking
             warn!(job_id = %job_id, error = %e, "pty job join error");
-            finish(&device_id, &job_id, -1, &e.to_string(), &tx, &store)
+            finish(
+                &device_id,
+                &job_id,
+                -1,
+                &e.to_string(),
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
+        }
+        None => {
+            finish(
+                &device_id,
+                &job_id,
+                -1,
+                "unknown error",
+                context.outcome(OutcomeKind::Finished),
+                &timing,
+                &tx,
+                &store,
+            )
+            .await
         }
-        None => finish(&device_id, &job_id, -1, "unknown error", &tx, &store),
     }
 }
 
-fn finish(
+/// Wall-clock bookkeeping for one run, reported in `JobFinished`.
+struct JobTiming {
+    /// When the executor started, i.e. right after the permit was acquired.
+    started: Instant,
+    queued_ms: u64,
+    /// Whether the child process was actually spawned. Runs that fail before
+    /// spawning report a zero duration.
+    spawned: bool,
+}
+
+impl JobTiming {
+    fn start(queued_ms: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            queued_ms,
+            spawned: false,
+        }
+    }
+
+    fn duration_ms(&self) -> u64 {
+        if self.spawned {
+            self.started.elapsed().as_millis() as u64
+        } else {
+            0
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn finish(
     device_id: &str,
     job_id: &str,
     exit_code: i32,
     error: &str,
+    outcome: RunOutcome,
+    timing: &JobTiming,
     tx: &impl EnvelopeSink,
     store: &Option<Arc<RunStore>>,
 ) -> (i32, String) {
+    let duration_ms = timing.duration_ms();
     if let Some(s) = &store {
-        s.finish_run(job_id, exit_code, error);
+        s.finish_run(
+            job_id,
+            exit_code,
+            error,
+            duration_ms,
+            timing.queued_ms,
+            &outcome,
+        )
+        .await;
     }
 
     let envelope = Envelope {
@@ -532,6 +793,8 @@ fn finish(
             job_id: job_id.to_string(),
             exit_code,
             error: error.to_string(),
+            duration_ms,
+            queued_ms: timing.queued_ms,
         })),
         ..Default::default()
     };
@@ -539,7 +802,44 @@ fn finish(
     (exit_code, error.to_string())
 }
 
-fn make_event_envelope(
+/// Build the `JobQueued` notice sent while a job waits for a concurrency slot.
+pub(crate) fn make_queued_envelope(
+    device_id: &str,
+    job_id: &str,
+    position: u32,
+    waited_ms: u64,
+    estimated_wait_ms: u64,
+) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobQueued(JobQueued {
+            job_id: job_id.to_string(),
+            position,
+            waited_ms,
+            estimated_wait_ms,
+        })),
+        ..Default::default()
+    }
+}
+
+/// Build the `JobRequest` IPC observers are shown when a job is accepted.
+/// Its env is left out: observers may be read-only peers.
+pub(crate) fn make_started_envelope(device_id: &str, req: &JobRequest) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRequest(JobRequest {
+            env: Default::default(),
+            ..req.clone()
+        })),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn make_event_envelope(
     device_id: &str,
     job_id: &str,
     stdout_chunk: Option<Vec<u8>>,
@@ -576,8 +876,11 @@ fn new_msg_id() -> String {
 
 #[cfg(test)]
 mod tool_resolution_tests {
-    use super::{ExecutionTarget, ResolvedTool, resolve_tool, run_job_with_target};
-    use ahand_protocol::JobRequest;
+    use super::{
+        CancelReason, ExecutionTarget, ResolvedTool, RunContext, resolve_tool, run_job,
+        run_job_with_target,
+    };
+    use ahand_protocol::{Envelope, JobFinished, JobRequest, envelope};
 
     #[test]
     fn dollar_shell_sentinel_resolves_to_shell_env_with_login_flag() {
@@ -712,6 +1015,7 @@ mod tool_resolution_tests {
         let (exit_code, error) = run_job_with_target(
             "device-1".to_string(),
             req,
+            RunContext::new("uid:501"),
             ExecutionTarget {
                 path: script.to_string_lossy().to_string(),
                 leading_args: Vec::new(),
@@ -719,6 +1023,7 @@ mod tool_resolution_tests {
             tx,
             cancel_rx,
             None,
+            0,
         )
         .await;
 
@@ -762,6 +1067,7 @@ mod tool_resolution_tests {
         let (exit_code, error) = run_job_with_target(
             "device-timeout".to_string(),
             req,
+            RunContext::new("uid:501"),
             ExecutionTarget {
                 path: cmd.to_string(),
                 leading_args: vec![],
@@ -769,6 +1075,7 @@ mod tool_resolution_tests {
             tx,
             cancel_rx,
             None,
+            0,
         )
         .await;
 
@@ -805,12 +1112,13 @@ mod tool_resolution_tests {
         // Send cancel after a brief delay.
         tokio::spawn(async move {
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-            let _ = cancel_tx.send(()).await;
+            let _ = cancel_tx.send(CancelReason::Requested).await;
         });
 
         let (exit_code, error) = run_job_with_target(
             "device-cancel".to_string(),
             req,
+            RunContext::new("uid:501"),
             ExecutionTarget {
                 path: cmd.to_string(),
                 leading_args: vec![],
@@ -818,6 +1126,7 @@ mod tool_resolution_tests {
             tx,
             cancel_rx,
             None,
+            0,
         )
         .await;
 
@@ -827,4 +1136,75 @@ mod tool_resolution_tests {
             "cancelled job must return error=\"cancelled\""
         );
     }
+
+    // ── timing ────────────────────────────────────────────────────────────────
+
+    fn finished_from(rx: &mut tokio::sync::mpsc::UnboundedReceiver<Envelope>) -> JobFinished {
+        while let Ok(env) = rx.try_recv() {
+            if let Some(envelope::Payload::JobFinished(fin)) = env.payload {
+                return fin;
+            }
+        }
+        panic!("expected JobFinished");
+    }
+
+    #[tokio::test]
+    async fn spawn_failure_reports_zero_duration() {
+        use tokio::sync::mpsc;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (_cancel_tx, cancel_rx) = mpsc::channel(1);
+        let req = JobRequest {
+            job_id: "missing-tool".to_string(),
+            tool: "ahand-definitely-not-a-real-binary".to_string(),
+            ..Default::default()
+        };
+
+        let (exit_code, _) = run_job(
+            "device-1".to_string(),
+            req,
+            RunContext::new("uid:501"),
+            tx,
+            cancel_rx,
+            None,
+            250,
+        )
+        .await;
+
+        assert_eq!(exit_code, -1);
+        let fin = finished_from(&mut rx);
+        assert_eq!(fin.duration_ms, 0);
+        assert_eq!(fin.queued_ms, 250);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn finished_reports_run_duration() {
+        use tokio::sync::mpsc;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (_cancel_tx, cancel_rx) = mpsc::channel(1);
+        let req = JobRequest {
+            job_id: "sleep-job".to_string(),
+            tool: "sleep".to_string(),
+            args: vec!["0.2".to_string()],
+            ..Default::default()
+        };
+
+        let (exit_code, _) = run_job(
+            "device-1".to_string(),
+            req,
+            RunContext::new("uid:501"),
+            tx,
+            cancel_rx,
+            None,
+            0,
+        )
+        .await;
+
+        assert_eq!(exit_code, 0);
+        let fin = finished_from(&mut rx);
+        assert!(fin.duration_ms >= 200, "duration_ms = {}", fin.duration_ms);
+        assert_eq!(fin.queued_ms, 0);
+    }
 }
@@ -516,7 +516,7 @@ impl FileManager {
 /// the symlink-target string as a literal reference, not as something
 /// to follow on disk (which would also fail when the target doesn't
 /// exist yet, which is the common case).
-fn lexically_normalize(path: &Path) -> PathBuf {
+pub(crate) fn lexically_normalize(path: &Path) -> PathBuf {
     let mut out = PathBuf::new();
     for comp in path.components() {
         match comp {
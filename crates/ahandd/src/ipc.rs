@@ -1,52 +1,766 @@
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use ahand_platform::ipc::{IpcEndpoint, IpcListener};
-use ahand_protocol::{BrowserResponse, Envelope, JobFinished, JobRejected, SessionMode, envelope};
+use ahand_platform::frame::{self, FrameCodec, read_frame, write_frame};
+use ahand_platform::ipc::{IpcEndpoint, IpcListener, IpcPeer};
+use ahand_protocol::{
+    BrowserResponse, CancelAllResult, Envelope, Hello, HelloAccepted, IPC_PROTOCOL_VERSION,
+    JobFinished, JobRejected, SessionMode, envelope, job_event,
+};
 use prost::Message;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::{broadcast, mpsc};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, broadcast, mpsc, watch};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-use crate::approval::ApprovalManager;
+use crate::approval::{ApprovalManager, EXPIRED_REASON};
 use crate::browser::BrowserManager;
-use crate::executor;
+use crate::config::Config;
+use crate::connection::{ConnectionMonitor, ConnectionState};
+use crate::executor::{self, CancelReason, EnvelopeSink as _};
 use crate::file_manager::FileManager;
 use crate::plugin_runtime::{CapabilityKind, CapabilityUnavailable, JobProvider};
-use crate::registry::{IsKnown, JobRegistry};
+use crate::policy::PolicyChecker;
+use crate::registry::{
+    AtCapacity, IsKnown, JOB_ID_REUSE_REASON, JobRegistry, Subscription, params_hash,
+};
 use crate::session::{SessionDecision, SessionManager};
-use crate::store::RunStore;
+use crate::store::{RunContext, RunStore};
 
-/// Start the IPC server on the given endpoint.
+/// Error code sent to a peer that isn't allowed on the IPC socket.
+const UNAUTHORIZED_CODE: &str = "ipc.unauthorized";
+
+/// Error code for a mutating request from a read-only peer.
+const READ_ONLY_CODE: &str = "ipc.read_only";
+
+/// Error code for a `JobSubscribe` naming a job the registry doesn't know.
+const JOB_NOT_FOUND_CODE: &str = "ipc.job_not_found";
+
+/// Size of the `JobEvent` chunks stored output is replayed in.
+const REPLAY_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Error code for a `Hello` whose protocol major version isn't ours.
+const PROTOCOL_MISMATCH_CODE: &str = "ipc.protocol_mismatch";
+
+/// Error code for a client that skipped the `Hello` when one is required.
+const HELLO_REQUIRED_CODE: &str = "ipc.hello_required";
+
+/// How long a new connection has to send its `Hello` before it is treated
+/// as a pre-handshake client.
+const HELLO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long connections get to deliver their `Shutdown` and close once
+/// the IPC server is told to stop.
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// `Shutdown.reason` sent to IPC clients when ahandd exits.
+const SHUTDOWN_REASON: &str = "ahandd is shutting down";
+
+/// Error code for a frame over the connection's `ipc_max_frames_per_sec`.
+const RATE_LIMITED_CODE: &str = "ipc.rate_limited";
+
+/// Error code for a `JobRequest` over the connection's in-flight job limit.
+const TOO_MANY_JOBS_CODE: &str = "ipc.too_many_jobs";
+
+/// Error code for a `JobRequest` that fails [`validate_job_request`].
+const INVALID_REQUEST_CODE: &str = "ipc.invalid_request";
+
+/// Error code for a `Subscribe` naming a topic we don't publish.
+const UNKNOWN_TOPIC_CODE: &str = "ipc.unknown_topic";
+
+/// Limit violations after which a connection is closed.
+const MAX_VIOLATIONS: u32 = 10;
+
+/// Jobs one connection may have running or awaiting approval at once.
+const DEFAULT_MAX_INFLIGHT_JOBS: usize = 64;
+
+const MAX_JOB_ID_CHARS: usize = 128;
+const MAX_TOOL_CHARS: usize = 4096;
+const MAX_JOB_ARGS: usize = 1024;
+const MAX_JOB_ENV: usize = 512;
+
+/// Which peers may use the IPC socket, by peer credentials.
+#[derive(Debug, Clone, Default)]
+pub struct IpcAccess {
+    allowed_uids: Vec<u32>,
+    allowed_gids: Vec<u32>,
+    readonly_uids: Vec<u32>,
+    require_hello: bool,
+    /// Shared secret a client's `Hello` must carry; set for TCP listeners
+    /// that accept remote peers.
+    hello_token: Option<String>,
+    limits: IpcLimits,
+}
+
+/// How hard one connection may push the daemon.
+#[derive(Debug, Clone)]
+struct IpcLimits {
+    /// 0 = unlimited.
+    frames_per_sec: u32,
+    inflight_jobs: usize,
+}
+
+impl Default for IpcLimits {
+    fn default() -> Self {
+        Self {
+            frames_per_sec: 200,
+            inflight_jobs: DEFAULT_MAX_INFLIGHT_JOBS,
+        }
+    }
+}
+
+impl IpcAccess {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            allowed_uids: cfg.ipc_allowed_uids.clone().unwrap_or_default(),
+            allowed_gids: cfg.ipc_allowed_gids.clone().unwrap_or_default(),
+            readonly_uids: cfg.ipc_readonly_uids.clone().unwrap_or_default(),
+            require_hello: !cfg.ipc_legacy_clients(),
+            hello_token: None,
+            limits: IpcLimits {
+                frames_per_sec: cfg.ipc_max_frames_per_sec(),
+                ..IpcLimits::default()
+            },
+        }
+    }
+
+    /// Whether clients must open with a `Hello`.
+    fn requires_hello(&self) -> bool {
+        self.require_hello || self.hello_token.is_some()
+    }
+
+    /// Whether `hello` carries the shared secret, if one is required.
+    fn check_token(&self, hello: &Hello) -> Result<(), ahand_protocol::Error> {
+        use subtle::ConstantTimeEq;
+
+        match &self.hello_token {
+            Some(token) if !bool::from(token.as_bytes().ct_eq(hello.ipc_token.as_bytes())) => {
+                Err(ahand_protocol::Error {
+                    code: UNAUTHORIZED_CODE.to_string(),
+                    message: "missing or wrong IPC token".to_string(),
+                    ..Default::default()
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a peer may connect at all. Without allow-lists every peer
+    /// may; with them, a peer whose credentials are unknown may not.
+    /// Read-only UIDs are always admitted.
+    fn allows_connection(&self, uid: Option<u32>, gid: Option<u32>) -> bool {
+        if self.allowed_uids.is_empty() && self.allowed_gids.is_empty() {
+            return true;
+        }
+        uid.is_some_and(|uid| self.allowed_uids.contains(&uid) || self.readonly_uids.contains(&uid))
+            || gid.is_some_and(|gid| self.allowed_gids.contains(&gid))
+    }
+
+    /// Whether a peer may send `payload` over an admitted connection.
+    fn allows_payload(&self, uid: Option<u32>, payload: &envelope::Payload) -> bool {
+        let read_only = uid.is_some_and(|uid| self.readonly_uids.contains(&uid));
+        !read_only || is_query(payload)
+    }
+}
+
+/// Payloads that only read daemon state.
+fn is_query(payload: &envelope::Payload) -> bool {
+    matches!(
+        payload,
+        envelope::Payload::SessionQuery(_)
+            | envelope::Payload::PendingApprovalsQuery(_)
+            | envelope::Payload::DaemonStatusQuery(_)
+            | envelope::Payload::PolicyQuery(_)
+            | envelope::Payload::PolicyCheckRequest(_)
+            | envelope::Payload::JobSubscribe(_)
+            | envelope::Payload::Ping(_)
+            | envelope::Payload::Subscribe(_)
+    )
+}
+
+/// What an observer asked to see with its `Subscribe`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Topics {
+    jobs: bool,
+    /// Job stdout/stderr; implies `jobs`.
+    output: bool,
+    approvals: bool,
+    sessions: bool,
+    policy: bool,
+}
+
+impl Topics {
+    fn parse(names: &[String]) -> Result<Self, String> {
+        let mut topics = Self::default();
+        for name in names {
+            match name.as_str() {
+                "jobs" => topics.jobs = true,
+                "jobs.output" => {
+                    topics.jobs = true;
+                    topics.output = true;
+                }
+                "approvals" => topics.approvals = true,
+                "sessions" => topics.sessions = true,
+                "policy" => topics.policy = true,
+                other => return Err(format!("unknown topic {other:?}")),
+            }
+        }
+        Ok(topics)
+    }
+
+    /// Whether `envelope` belongs to a subscribed topic.
+    fn wants(&self, envelope: &Envelope) -> bool {
+        use envelope::Payload;
+        match &envelope.payload {
+            Some(Payload::JobEvent(event)) => match &event.event {
+                Some(job_event::Event::StdoutChunk(_) | job_event::Event::StderrChunk(_)) => {
+                    self.output
+                }
+                _ => self.jobs,
+            },
+            Some(
+                Payload::JobRequest(_)
+                | Payload::JobQueued(_)
+                | Payload::JobFinished(_)
+                | Payload::JobRejected(_),
+            ) => self.jobs,
+            Some(
+                Payload::ApprovalRequest(_)
+                | Payload::ApprovalExpired(_)
+                | Payload::ApprovalResolved(_),
+            ) => self.approvals,
+            Some(Payload::SessionState(_)) => self.sessions,
+            Some(Payload::PolicyState(_)) => self.policy,
+            _ => false,
+        }
+    }
+}
+
+/// Forward the registry's observed envelopes in `topics` to `tx` until the
+/// connection goes away.
+fn spawn_observer(
+    registry: &JobRegistry,
+    topics: Topics,
+    tx: mpsc::UnboundedSender<Envelope>,
+    caller_id: String,
+) -> tokio::task::JoinHandle<()> {
+    let mut observed = registry.observe();
+    tokio::spawn(async move {
+        loop {
+            match observed.recv().await {
+                Ok(envelope) => {
+                    if topics.wants(&envelope) && tx.send(envelope).is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(caller_id = %caller_id, missed = n, "IPC: observer lagged, missed events");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+/// What a `Pong` or `DaemonStatus` reports beyond the job registry: how
+/// long the daemon has been up and where its cloud connection (or its
+/// OpenClaw gateway connections) stand.
+pub struct DaemonHealth {
+    started_at: Instant,
+    connection: Arc<ConnectionMonitor>,
+    gateways: Mutex<Vec<Arc<ConnectionMonitor>>>,
+}
+
+impl Default for DaemonHealth {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            connection: Arc::default(),
+            gateways: Mutex::default(),
+        }
+    }
+}
+
+impl DaemonHealth {
+    /// The monitor for the cloud client to keep up to date.
+    pub fn connection(&self) -> Arc<ConnectionMonitor> {
+        Arc::clone(&self.connection)
+    }
+
+    /// A new monitor for one OpenClaw gateway client to keep up to date,
+    /// reported in `DaemonStatus.gateways` in the order they were added.
+    pub fn add_gateway(&self) -> Arc<ConnectionMonitor> {
+        let monitor = Arc::<ConnectionMonitor>::default();
+        self.gateways
+            .lock()
+            .expect("gateway monitors mutex poisoned")
+            .push(Arc::clone(&monitor));
+        monitor
+    }
+
+    fn gateway_statuses(&self) -> Vec<ahand_protocol::ConnectionStatus> {
+        self.gateways
+            .lock()
+            .expect("gateway monitors mutex poisoned")
+            .iter()
+            .map(|monitor| monitor.status())
+            .collect()
+    }
+
+    async fn pong(&self, registry: &JobRegistry) -> ahand_protocol::Pong {
+        let (cloud_url, cloud_failover) = match self.connection.state() {
+            ConnectionState::Connected { url, primary, .. } => (url, !primary),
+            _ => (String::new(), false),
+        };
+        ahand_protocol::Pong {
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_ms: self.started_at.elapsed().as_millis() as u64,
+            connected_to_cloud: !cloud_url.is_empty(),
+            active_jobs: registry.active_count().await as u32,
+            cloud_url,
+            cloud_failover,
+        }
+    }
+}
+
+/// Token bucket for a connection's frames: a second's worth of burst,
+/// refilled at the configured rate.
+struct FrameBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl FrameBucket {
+    fn new(frames_per_sec: u32, now: Instant) -> Self {
+        Self {
+            rate: f64::from(frames_per_sec),
+            tokens: f64::from(frames_per_sec),
+            last: now,
+        }
+    }
+
+    /// Whether a frame arriving at `now` is within the rate.
+    fn take(&mut self, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+        let refill = now.saturating_duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate);
+        self.last = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// A connection's [`IpcLimits`] state.
+struct ConnLimits {
+    frames: FrameBucket,
+    jobs: Arc<Semaphore>,
+    inflight_jobs: usize,
+    violations: u32,
+}
+
+impl ConnLimits {
+    fn new(limits: &IpcLimits) -> Self {
+        Self {
+            frames: FrameBucket::new(limits.frames_per_sec, Instant::now()),
+            jobs: Arc::new(Semaphore::new(limits.inflight_jobs)),
+            inflight_jobs: limits.inflight_jobs,
+            violations: 0,
+        }
+    }
+
+    /// Check `req` and take one of the connection's job slots for it. The
+    /// slot is free again once the returned permit drops.
+    fn admit_job(
+        &self,
+        req: &ahand_protocol::JobRequest,
+    ) -> Result<OwnedSemaphorePermit, ahand_protocol::Error> {
+        validate_job_request(req).map_err(|message| ahand_protocol::Error {
+            code: INVALID_REQUEST_CODE.to_string(),
+            message,
+            ..Default::default()
+        })?;
+        Arc::clone(&self.jobs)
+            .try_acquire_owned()
+            .map_err(|_| ahand_protocol::Error {
+                code: TOO_MANY_JOBS_CODE.to_string(),
+                message: format!(
+                    "at most {} jobs may be in flight per IPC connection",
+                    self.inflight_jobs
+                ),
+                ..Default::default()
+            })
+    }
+
+    /// Count a violation; true once the connection has had too many.
+    fn strike(&mut self) -> bool {
+        self.violations += 1;
+        self.violations >= MAX_VIOLATIONS
+    }
+}
+
+/// Structural checks on a `JobRequest` before it reaches the registry or
+/// policy.
+fn validate_job_request(req: &ahand_protocol::JobRequest) -> Result<(), String> {
+    let job_id_chars = req.job_id.chars().count();
+    if job_id_chars == 0 || job_id_chars > MAX_JOB_ID_CHARS {
+        return Err(format!("job_id must be 1 to {MAX_JOB_ID_CHARS} characters"));
+    }
+    if req.tool.chars().count() > MAX_TOOL_CHARS {
+        return Err(format!("tool must be at most {MAX_TOOL_CHARS} characters"));
+    }
+    if req.args.len() > MAX_JOB_ARGS {
+        return Err(format!("at most {MAX_JOB_ARGS} args are allowed"));
+    }
+    if req.env.len() > MAX_JOB_ENV {
+        return Err(format!("at most {MAX_JOB_ENV} env entries are allowed"));
+    }
+    Ok(())
+}
+
+/// Where an IPC server listens.
+#[derive(Debug, Clone)]
+pub enum IpcBind {
+    /// The Unix socket (named pipe on Windows), created with `socket_mode`.
+    Local {
+        endpoint: IpcEndpoint,
+        socket_mode: u32,
+    },
+    /// A TCP listener, for setups where the socket is awkward to reach.
+    Tcp(IpcTcp),
+}
+
+/// The TCP IPC listener configured by `ipc_tcp_listen`.
+#[derive(Debug, Clone)]
+pub struct IpcTcp {
+    addr: SocketAddr,
+    token: Option<String>,
+}
+
+impl IpcTcp {
+    /// The configured TCP listener, if any. Non-loopback addresses need
+    /// `ipc_tcp_allow_remote`, and that in turn needs `ipc_tcp_token`.
+    pub fn from_config(cfg: &Config) -> anyhow::Result<Option<Self>> {
+        let Some(listen) = &cfg.ipc_tcp_listen else {
+            return Ok(None);
+        };
+        let addr: SocketAddr = listen
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid ipc_tcp_listen {listen:?}: {e}"))?;
+        if !cfg.ipc_tcp_allow_remote.unwrap_or(false) {
+            if !addr.ip().is_loopback() {
+                anyhow::bail!(
+                    "refusing to serve IPC on non-loopback address {addr}; set ipc_tcp_allow_remote = true to allow it"
+                );
+            }
+            return Ok(Some(Self { addr, token: None }));
+        }
+        match cfg.ipc_tcp_token.as_deref() {
+            Some(token) if !token.is_empty() => Ok(Some(Self {
+                addr,
+                token: Some(token.to_string()),
+            })),
+            _ => anyhow::bail!("ipc_tcp_allow_remote requires ipc_tcp_token"),
+        }
+    }
+}
+
+/// A connection accepted by either kind of listener.
+trait IpcStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> IpcStream for S {}
+
+enum Listener {
+    Local(IpcListener),
+    Tcp(tokio::net::TcpListener),
+}
+
+impl Listener {
+    async fn bind(bind: &IpcBind) -> anyhow::Result<Self> {
+        match bind {
+            IpcBind::Local {
+                endpoint,
+                socket_mode,
+            } => Ok(Self::Local(IpcListener::bind(endpoint, *socket_mode)?)),
+            IpcBind::Tcp(tcp) => Ok(Self::Tcp(tokio::net::TcpListener::bind(tcp.addr).await?)),
+        }
+    }
+
+    /// Accept the next connection. TCP peers have no credentials; they are
+    /// identified as `tcp:<peer addr>`.
+    async fn accept(&mut self) -> anyhow::Result<(Box<dyn IpcStream>, IpcPeer)> {
+        match self {
+            Self::Local(listener) => {
+                let (stream, peer) = listener.accept_peer().await?;
+                Ok((Box::new(stream), peer))
+            }
+            Self::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                let peer = IpcPeer {
+                    id: format!("tcp:{addr}"),
+                    uid: None,
+                    gid: None,
+                };
+                Ok((Box::new(stream), peer))
+            }
+        }
+    }
+}
+
+/// Longest `client_name` or `instance_id` kept in a caller identity.
+const MAX_CLIENT_NAME_CHARS: usize = 64;
+
+/// How a client named itself in its `Hello`, so several local agents
+/// running as one user get separate sessions, approvals and audit entries.
+#[derive(Debug, Default, PartialEq)]
+struct ClientName {
+    name: String,
+    instance: String,
+}
+
+impl ClientName {
+    /// The names from `hello`, keeping only characters that can't be
+    /// confused with the identity's separators.
+    fn from_hello(hello: &Hello) -> Self {
+        let clean = |s: &str| -> String {
+            s.chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                .take(MAX_CLIENT_NAME_CHARS)
+                .collect()
+        };
+        Self {
+            name: clean(&hello.client_name),
+            instance: clean(&hello.instance_id),
+        }
+    }
+
+    /// `peer_id` qualified by the client's name and instance, e.g.
+    /// `uid:501/claude-code#a1b2`. Unnamed clients keep the bare `peer_id`.
+    fn caller_id(&self, peer_id: &str) -> String {
+        match (self.name.is_empty(), self.instance.is_empty()) {
+            (true, _) => peer_id.to_string(),
+            (false, true) => format!("{peer_id}/{}", self.name),
+            (false, false) => format!("{peer_id}/{}#{}", self.name, self.instance),
+        }
+    }
+}
+
+/// How a connection's first frame went.
+enum Handshake {
+    /// The client sent a compatible `Hello` and got `HelloAccepted`; the
+    /// rest of the connection uses the agreed frame codec.
+    Accepted(ClientName, FrameCodec),
+    /// A pre-handshake client; its first frame, if any, still needs
+    /// handling.
+    Legacy(Option<Vec<u8>>),
+    /// The client was sent an `Error` and must be disconnected.
+    Refused,
+}
+
+/// Wait for the client's `Hello` and answer it, before anything else is
+/// written to the connection.
+async fn handshake<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    device_id: &str,
+    access: &IpcAccess,
+    browser_mgr: &BrowserManager,
+    file_mgr: &FileManager,
+    persistence: bool,
+) -> std::io::Result<Handshake>
+where
+    R: AsyncReadExt + Unpin,
+    W: AsyncWriteExt + Unpin,
+{
+    let (payload, ref_msg_id, first) =
+        match tokio::time::timeout(HELLO_TIMEOUT, read_frame(reader)).await {
+            Err(_) => (None, String::new(), None),
+            Ok(frame) => {
+                let frame = frame?;
+                match Envelope::decode(frame.as_slice()) {
+                    Ok(env) => (env.payload, env.msg_id, Some(frame)),
+                    Err(_) => (None, String::new(), Some(frame)),
+                }
+            }
+        };
+    let mut client = ClientName::default();
+    let mut codec = FrameCodec::Plain;
+    let reply = match payload {
+        Some(envelope::Payload::Hello(hello)) => {
+            match check_hello(&hello).and_then(|()| access.check_token(&hello)) {
+                Ok(()) => {
+                    client = ClientName::from_hello(&hello);
+                    if hello.accepts.iter().any(|c| c == frame::ZSTD) {
+                        codec = FrameCodec::Zstd;
+                    }
+                    let plugins =
+                        crate::plugin_runtime::build_provider_registry(browser_mgr, file_mgr)
+                            .await
+                            .map(|registry| registry.active_wire_capabilities())
+                            .unwrap_or_default();
+                    let capabilities =
+                        crate::capabilities::daemon_capabilities(plugins, persistence).into_wire();
+                    envelope::Payload::HelloAccepted(HelloAccepted {
+                        daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+                        protocol_version: IPC_PROTOCOL_VERSION,
+                        capabilities,
+                        compression: codec.name().to_string(),
+                        ..Default::default()
+                    })
+                }
+                Err(err) => envelope::Payload::Error(ahand_protocol::Error { ref_msg_id, ..err }),
+            }
+        }
+        _ if !access.requires_hello() => return Ok(Handshake::Legacy(first)),
+        _ => envelope::Payload::Error(ahand_protocol::Error {
+            code: HELLO_REQUIRED_CODE.to_string(),
+            message: "this ahandd requires a Hello first — run `ahandctl upgrade`".to_string(),
+            ref_msg_id,
+        }),
+    };
+    let accepted = matches!(reply, envelope::Payload::HelloAccepted(_));
+    let env = Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(reply),
+        ..Default::default()
+    };
+    write_frame(writer, &env.encode_to_vec()).await?;
+    if accepted {
+        Ok(Handshake::Accepted(client, codec))
+    } else {
+        let _ = writer.shutdown().await;
+        Ok(Handshake::Refused)
+    }
+}
+
+/// Whether a client's `Hello` speaks our IPC protocol major version.
+fn check_hello(hello: &Hello) -> Result<(), ahand_protocol::Error> {
+    if hello.protocol_version == IPC_PROTOCOL_VERSION {
+        return Ok(());
+    }
+    Err(ahand_protocol::Error {
+        code: PROTOCOL_MISMATCH_CODE.to_string(),
+        message: format!(
+            "daemon speaks protocol {IPC_PROTOCOL_VERSION}, this client speaks {}",
+            hello.protocol_version
+        ),
+        ..Default::default()
+    })
+}
+
+/// Start the IPC server, listening at each of `binds`. All listeners
+/// share the same handlers.
+///
+/// Runs until `shutdown` is cancelled; then it stops accepting, sends each
+/// connected client a `Shutdown`, gives connections [`SHUTDOWN_GRACE`] to
+/// close and removes the socket file.
 #[allow(clippy::too_many_arguments)]
 pub async fn serve_ipc(
-    endpoint: IpcEndpoint,
-    socket_mode: u32,
+    binds: Vec<IpcBind>,
+    access: IpcAccess,
+    registry: Arc<JobRegistry>,
+    store: Option<Arc<RunStore>>,
+    session_mgr: Arc<SessionManager>,
+    approval_mgr: Arc<ApprovalManager>,
+    policy: Arc<PolicyChecker>,
+    approval_broadcast_tx: broadcast::Sender<Envelope>,
+    device_id: String,
+    browser_mgr: Arc<BrowserManager>,
+    file_mgr: Arc<FileManager>,
+    health: Arc<DaemonHealth>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let listeners = binds.into_iter().map(|bind| {
+        serve_listener(
+            bind,
+            access.clone(),
+            Arc::clone(&registry),
+            store.clone(),
+            Arc::clone(&session_mgr),
+            Arc::clone(&approval_mgr),
+            Arc::clone(&policy),
+            approval_broadcast_tx.clone(),
+            device_id.clone(),
+            Arc::clone(&browser_mgr),
+            Arc::clone(&file_mgr),
+            Arc::clone(&health),
+            shutdown.clone(),
+        )
+    });
+    futures_util::future::try_join_all(listeners).await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn serve_listener(
+    bind: IpcBind,
+    mut access: IpcAccess,
     registry: Arc<JobRegistry>,
     store: Option<Arc<RunStore>>,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
+    policy: Arc<PolicyChecker>,
     approval_broadcast_tx: broadcast::Sender<Envelope>,
     device_id: String,
     browser_mgr: Arc<BrowserManager>,
     file_mgr: Arc<FileManager>,
+    health: Arc<DaemonHealth>,
+    shutdown: CancellationToken,
 ) -> anyhow::Result<()> {
-    let mut listener = IpcListener::bind(&endpoint, socket_mode)?;
-    info!(endpoint = %endpoint.as_path().display(), "IPC server listening");
+    let mut listener = Listener::bind(&bind).await?;
+    match &bind {
+        IpcBind::Local { endpoint, .. } => {
+            info!(endpoint = %endpoint.as_path().display(), "IPC server listening")
+        }
+        IpcBind::Tcp(tcp) => {
+            info!(addr = %tcp.addr, token = tcp.token.is_some(), "IPC TCP server listening");
+            access.hello_token = tcp.token.clone();
+        }
+    }
+    let mut conns = JoinSet::new();
 
     loop {
-        match listener.accept().await {
-            Ok((stream, caller_id)) => {
+        let accepted = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            // Reap finished connections so the set doesn't grow forever.
+            Some(_) = conns.join_next(), if !conns.is_empty() => continue,
+            accepted = listener.accept() => accepted,
+        };
+        match accepted {
+            Ok((stream, peer)) => {
+                if !access.allows_connection(peer.uid, peer.gid) {
+                    warn!(caller_id = %peer.id, gid = ?peer.gid, "IPC: connection refused");
+                    let did = device_id.clone();
+                    tokio::spawn(refuse_connection(stream, did, peer));
+                    continue;
+                }
+                let caller_id = peer.id;
+                let access = access.clone();
+                let uid = peer.uid;
                 let reg = Arc::clone(&registry);
                 let st = store.clone();
                 let smgr = Arc::clone(&session_mgr);
                 let amgr = Arc::clone(&approval_mgr);
+                let pol = Arc::clone(&policy);
                 let bcast = approval_broadcast_tx.clone();
                 let did = device_id.clone();
                 let bmgr = Arc::clone(&browser_mgr);
                 let fmgr = Arc::clone(&file_mgr);
-                tokio::spawn(async move {
+                let health = Arc::clone(&health);
+                let stop = shutdown.clone();
+                conns.spawn(async move {
                     if let Err(e) = handle_ipc_conn(
-                        stream, reg, st, smgr, amgr, bcast, did, caller_id, bmgr, fmgr,
+                        stream, reg, st, smgr, amgr, pol, bcast, did, caller_id, bmgr, fmgr,
+                        health, access, uid, stop,
                     )
                     .await
                     {
@@ -59,6 +773,28 @@ pub async fn serve_ipc(
             }
         }
     }
+
+    drop(listener);
+    info!(connections = conns.len(), "IPC server shutting down");
+    let drained = tokio::time::timeout(SHUTDOWN_GRACE, async {
+        while conns.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            connections = conns.len(),
+            "IPC connections did not close within the shutdown grace period"
+        );
+        conns.shutdown().await;
+    }
+    #[cfg(unix)]
+    if let IpcBind::Local { endpoint, .. } = &bind
+        && let Err(e) = std::fs::remove_file(endpoint.as_path())
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!(error = %e, "failed to remove IPC socket");
+    }
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -68,18 +804,52 @@ async fn handle_ipc_conn<S>(
     store: Option<Arc<RunStore>>,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
+    policy: Arc<PolicyChecker>,
     approval_broadcast_tx: broadcast::Sender<Envelope>,
     device_id: String,
-    caller_id: String,
+    mut caller_id: String,
     browser_mgr: Arc<BrowserManager>,
     file_mgr: Arc<FileManager>,
+    health: Arc<DaemonHealth>,
+    access: IpcAccess,
+    uid: Option<u32>,
+    shutdown: CancellationToken,
 ) -> anyhow::Result<()>
 where
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
-    let (reader, writer) = tokio::io::split(stream);
+    let (reader, mut writer) = tokio::io::split(stream);
     let mut reader = tokio::io::BufReader::new(reader);
 
+    let mut codec = FrameCodec::Plain;
+    let mut pending = match handshake(
+        &mut reader,
+        &mut writer,
+        &device_id,
+        &access,
+        &browser_mgr,
+        &file_mgr,
+        store.is_some(),
+    )
+    .await
+    {
+        Ok(Handshake::Accepted(client, agreed)) => {
+            caller_id = client.caller_id(&caller_id);
+            codec = agreed;
+            None
+        }
+        Ok(Handshake::Legacy(first)) => {
+            info!(caller_id = %caller_id, "IPC: client skipped the Hello handshake");
+            first
+        }
+        Ok(Handshake::Refused) => {
+            warn!(caller_id = %caller_id, "IPC: handshake refused");
+            return Ok(());
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
     info!(caller_id = %caller_id, "IPC: new connection");
 
     // Register the IPC caller so session queries return it.
@@ -90,17 +860,69 @@ where
 
     // Subscribe to the approval broadcast channel.
     let mut approval_rx = approval_broadcast_tx.subscribe();
+    // Set once the client sends a `Subscribe`: broadcasts outside its
+    // topics are then held back, and `observer` feeds it job activity.
+    let (topics_tx, topics_rx) = watch::channel(None::<Topics>);
+    let mut observer: Option<tokio::task::JoinHandle<()>> = None;
+
+    // Requests broadcast before this client connected; subscribing first
+    // means one submitted in between may arrive twice, never not at all.
+    for env in crate::approval::replay_pending_envelopes(&approval_mgr, &device_id).await {
+        let _ = tx.send(env);
+    }
+
+    let mut limits = ConnLimits::new(&access.limits);
+    // Cancelled when the client has broken its limits too often.
+    let kicked = CancellationToken::new();
 
     // Task: forward outgoing envelopes and broadcast approval requests to the IPC stream.
+    let shutdown_device_id = device_id.clone();
+    let stop = shutdown.clone();
+    let kick = kicked.clone();
     let send_handle = tokio::spawn(async move {
         let mut writer = writer;
         loop {
             tokio::select! {
+                // The read loop below also stops on shutdown and drops its
+                // sender; the goodbye must win over that.
+                biased;
+                _ = stop.cancelled() => {
+                    // Deliver what is already queued, then say goodbye and
+                    // close our side so the client reads EOF after it.
+                    while let Ok(envelope) = rx.try_recv() {
+                        if codec.write_frame(&mut writer, &envelope.encode_to_vec()).await.is_err() {
+                            return;
+                        }
+                    }
+                    let goodbye = Envelope {
+                        device_id: shutdown_device_id,
+                        msg_id: new_msg_id(),
+                        ts_ms: now_ms(),
+                        payload: Some(envelope::Payload::Shutdown(ahand_protocol::Shutdown {
+                            reason: SHUTDOWN_REASON.to_string(),
+                        })),
+                        ..Default::default()
+                    };
+                    if codec.write_frame(&mut writer, &goodbye.encode_to_vec()).await.is_ok() {
+                        let _ = writer.shutdown().await;
+                    }
+                    break;
+                }
+                _ = kick.cancelled() => {
+                    // Deliver the final error, then close our side.
+                    while let Ok(envelope) = rx.try_recv() {
+                        if codec.write_frame(&mut writer, &envelope.encode_to_vec()).await.is_err() {
+                            return;
+                        }
+                    }
+                    let _ = writer.shutdown().await;
+                    break;
+                }
                 msg = rx.recv() => {
                     match msg {
                         Some(envelope) => {
                             let data = envelope.encode_to_vec();
-                            if write_frame(&mut writer, &data).await.is_err() {
+                            if codec.write_frame(&mut writer, &data).await.is_err() {
                                 break;
                             }
                         }
@@ -110,8 +932,11 @@ where
                 bcast = approval_rx.recv() => {
                     match bcast {
                         Ok(envelope) => {
+                            if topics_rx.borrow().is_some_and(|t| !t.wants(&envelope)) {
+                                continue;
+                            }
                             let data = envelope.encode_to_vec();
-                            if write_frame(&mut writer, &data).await.is_err() {
+                            if codec.write_frame(&mut writer, &data).await.is_err() {
                                 break;
                             }
                         }
@@ -127,7 +952,14 @@ where
 
     // Read frames from the IPC stream.
     loop {
-        let data = match read_frame(&mut reader).await {
+        let frame = match pending.take() {
+            Some(frame) => Ok(frame),
+            None => tokio::select! {
+                _ = shutdown.cancelled() => break,
+                frame = codec.read_frame(&mut reader) => frame,
+            },
+        };
+        let data = match frame {
             Ok(d) => d,
             Err(e) => {
                 if e.kind() == std::io::ErrorKind::UnexpectedEof {
@@ -139,6 +971,22 @@ where
             }
         };
 
+        // Checked before decoding, so a flood costs as little as possible.
+        if !limits.frames.take(Instant::now()) {
+            let err = ahand_protocol::Error {
+                code: RATE_LIMITED_CODE.to_string(),
+                message: format!(
+                    "more than {} frames per second; frame dropped",
+                    access.limits.frames_per_sec
+                ),
+                ..Default::default()
+            };
+            if report_violation(&tx, &device_id, &caller_id, &mut limits, err) {
+                break;
+            }
+            continue;
+        }
+
         let envelope = match Envelope::decode(data.as_slice()) {
             Ok(e) => e,
             Err(e) => {
@@ -147,8 +995,35 @@ where
             }
         };
 
+        if let Some(payload) = &envelope.payload
+            && !access.allows_payload(uid, payload)
+        {
+            warn!(caller_id = %caller_id, "IPC: request denied for read-only caller");
+            let _ = tx.send(read_only_rejection_envelope(
+                &device_id,
+                &envelope.msg_id,
+                payload,
+            ));
+            continue;
+        }
+
         match envelope.payload {
-            Some(envelope::Payload::JobRequest(req)) => {
+            Some(envelope::Payload::JobRequest(mut req)) => {
+                let slot = match limits.admit_job(&req) {
+                    Ok(slot) => slot,
+                    Err(err) => {
+                        let err = ahand_protocol::Error {
+                            ref_msg_id: envelope.msg_id.clone(),
+                            ..err
+                        };
+                        if report_violation(&tx, &device_id, &caller_id, &mut limits, err) {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                // Rejections of this request reach observers too.
+                let tx = registry.observed(tx.clone());
                 let provider_registry = match crate::plugin_runtime::build_provider_registry(
                     &browser_mgr,
                     &file_mgr,
@@ -199,7 +1074,23 @@ where
                 }
 
                 // Idempotency check.
-                match registry.is_known(&req.job_id).await {
+                let params_hash = params_hash(&req);
+                match registry.is_known(&req.job_id, &params_hash).await {
+                    IsKnown::Conflict => {
+                        warn!(job_id = %req.job_id, "IPC: job_id reused with different parameters");
+                        let reject_env = Envelope {
+                            device_id: device_id.clone(),
+                            msg_id: new_msg_id(),
+                            ts_ms: now_ms(),
+                            payload: Some(envelope::Payload::JobRejected(JobRejected {
+                                job_id: req.job_id.clone(),
+                                reason: JOB_ID_REUSE_REASON.to_string(),
+                            })),
+                            ..Default::default()
+                        };
+                        let _ = tx.send(reject_env);
+                        continue;
+                    }
                     IsKnown::Running => {
                         warn!(job_id = %req.job_id, "IPC: duplicate job_id, already running");
                         continue;
@@ -214,6 +1105,7 @@ where
                                 job_id: req.job_id.clone(),
                                 exit_code: c.exit_code,
                                 error: c.error,
+                                ..Default::default()
                             })),
                             ..Default::default()
                         };
@@ -223,10 +1115,24 @@ where
                     IsKnown::Unknown => {}
                 }
 
-                // Session mode check.
-                match session_mgr.check(&req, &caller_id).await {
+                req.priority = session_mgr
+                    .effective_priority(&caller_id, req.priority)
+                    .await;
+
+                // Policy, then session mode.
+                match crate::policy::check_job(&policy, &session_mgr, &req, &caller_id).await {
                     SessionDecision::Deny(reason) => {
-                        warn!(job_id = %req.job_id, reason = %reason, "IPC: job rejected by session mode");
+                        warn!(job_id = %req.job_id, reason = %reason, "IPC: job rejected by policy or session mode");
+                        if let Some(st) = &store {
+                            let context = RunContext {
+                                session_mode: Some(
+                                    session_mgr.mode_name(&caller_id).await.to_string(),
+                                ),
+                                ..RunContext::new(&caller_id)
+                            };
+                            st.reject_run(&req, &caller_id, &context.rejected(&reason))
+                                .await;
+                        }
                         let reject_env = Envelope {
                             device_id: device_id.clone(),
                             msg_id: new_msg_id(),
@@ -246,44 +1152,90 @@ where
                         let reg = Arc::clone(&registry);
                         let st = store.clone();
                         let provider = job_provider.clone();
+                        let context = RunContext {
+                            session_mode: Some(session_mgr.mode_name(&caller_id).await.to_string()),
+                            ..RunContext::new(&caller_id)
+                        };
 
+                        let admission = match reg.admit(req.priority) {
+                            Ok(admission) => admission,
+                            Err(full) => {
+                                let _ = tx.send(at_capacity_rejection_envelope(
+                                    &device_id, &job_id, &full,
+                                ));
+                                continue;
+                            }
+                        };
                         let (cancel_tx, cancel_rx) = mpsc::channel(1);
-                        reg.register(job_id.clone(), cancel_tx).await;
+                        if !reg
+                            .register(job_id.clone(), &caller_id, params_hash, cancel_tx)
+                            .await
+                        {
+                            let _ = tx.send(shutting_down_rejection_envelope(&device_id, &job_id));
+                            continue;
+                        }
 
                         let active = reg.active_count().await;
-                        info!(job_id = %job_id, active_jobs = active, "IPC: job accepted");
+                        let queue_position = admission.position();
+                        info!(job_id = %job_id, active_jobs = active, ?queue_position, "IPC: job accepted");
 
                         tokio::spawn(async move {
-                            let _permit = reg.acquire_permit().await;
-                            let (exit_code, error) =
-                                run_job_with_provider(did, req, provider, tx_clone, cancel_rx, st)
-                                    .await;
+                            let _slot = slot;
+                            reg.publish_started(&did, &req);
+                            let tx_clone = reg.tee(&job_id, tx_clone);
+                            let permit = reg
+                                .wait_for_permit(admission, &did, &job_id, &tx_clone)
+                                .await;
+                            let (exit_code, error) = run_job_with_provider(
+                                did,
+                                req,
+                                context,
+                                provider,
+                                tx_clone,
+                                cancel_rx,
+                                st,
+                                permit.queued_ms(),
+                            )
+                            .await;
                             reg.remove(&job_id).await;
-                            reg.mark_completed(job_id, exit_code, error).await;
+                            reg.mark_completed(job_id, params_hash, exit_code, error)
+                                .await;
                         });
                     }
                     SessionDecision::NeedsApproval {
                         reason,
                         previous_refusals,
+                        previous_approvals,
                     } => {
-                        info!(job_id = %req.job_id, reason = %reason, "IPC: job needs approval (strict mode)");
+                        info!(job_id = %req.job_id, reason = %reason, "IPC: job needs approval");
 
                         let (approval_req, approval_rx) = approval_mgr
-                            .submit(req.clone(), &caller_id, reason, previous_refusals)
+                            .submit(
+                                req.clone(),
+                                &caller_id,
+                                reason,
+                                crate::policy::extract_destinations(&req),
+                                previous_refusals,
+                                previous_approvals,
+                            )
                             .await;
 
-                        // Send ApprovalRequest to this IPC client.
-                        let approval_env = Envelope {
-                            device_id: device_id.clone(),
-                            msg_id: new_msg_id(),
-                            ts_ms: now_ms(),
-                            payload: Some(envelope::Payload::ApprovalRequest(approval_req.clone())),
-                            ..Default::default()
-                        };
-                        let _ = tx.send(approval_env.clone());
-
-                        // Also broadcast to other IPC clients.
-                        let _ = approval_broadcast_tx.send(approval_env);
+                        // Send ApprovalRequest to this IPC client and broadcast
+                        // to the others — unless it was attached to an identical
+                        // pending one, which they have already seen.
+                        if approval_req.job_id == req.job_id {
+                            let approval_env = Envelope {
+                                device_id: device_id.clone(),
+                                msg_id: new_msg_id(),
+                                ts_ms: now_ms(),
+                                payload: Some(envelope::Payload::ApprovalRequest(
+                                    approval_req.clone(),
+                                )),
+                                ..Default::default()
+                            };
+                            let _ = tx.send(approval_env.clone());
+                            let _ = approval_broadcast_tx.send(approval_env);
+                        }
 
                         // Spawn a task to wait for approval.
                         let tx_clone = tx.clone();
@@ -296,41 +1248,81 @@ where
                         let job_id = req.job_id.clone();
                         let cuid = caller_id.clone();
                         let provider = job_provider.clone();
+                        let mut context = RunContext {
+                            session_mode: Some(session_mgr.mode_name(&caller_id).await.to_string()),
+                            ..RunContext::new(&caller_id)
+                        };
+                        let submitted_ms = now_ms();
 
                         tokio::spawn(async move {
+                            let _slot = slot;
                             let result = tokio::time::timeout(timeout, approval_rx).await;
+                            context.approval_latency_ms =
+                                Some(now_ms().saturating_sub(submitted_ms));
                             match result {
                                 Ok(Ok(resp)) if resp.approved => {
                                     info!(job_id = %job_id, "IPC: approval granted");
+                                    context.approved_by =
+                                        Some(resp.resolved_by).filter(|by| !by.is_empty());
+                                    let admission = match reg.admit(req.priority) {
+                                        Ok(admission) => admission,
+                                        Err(full) => {
+                                            let _ = tx_clone.send(at_capacity_rejection_envelope(
+                                                &did, &job_id, &full,
+                                            ));
+                                            return;
+                                        }
+                                    };
                                     let (cancel_tx, cancel_rx) = mpsc::channel(1);
-                                    reg.register(job_id.clone(), cancel_tx).await;
-                                    let _permit = reg.acquire_permit().await;
+                                    if !reg
+                                        .register(job_id.clone(), &cuid, params_hash, cancel_tx)
+                                        .await
+                                    {
+                                        let _ = tx_clone
+                                            .send(shutting_down_rejection_envelope(&did, &job_id));
+                                        return;
+                                    }
+                                    reg.publish_started(&did, &req);
+                                    let tx_clone = reg.tee(&job_id, tx_clone);
+                                    let permit = reg
+                                        .wait_for_permit(admission, &did, &job_id, &tx_clone)
+                                        .await;
                                     let (exit_code, error) = run_job_with_provider(
-                                        did, req, provider, tx_clone, cancel_rx, st,
+                                        did,
+                                        req,
+                                        context,
+                                        provider,
+                                        tx_clone,
+                                        cancel_rx,
+                                        st,
+                                        permit.queued_ms(),
                                     )
                                     .await;
                                     reg.remove(&job_id).await;
-                                    reg.mark_completed(job_id, exit_code, error).await;
+                                    reg.mark_completed(job_id, params_hash, exit_code, error)
+                                        .await;
                                 }
-                                Ok(Ok(resp)) => {
+                                Ok(Ok(resp)) if resp.reason != EXPIRED_REASON => {
                                     info!(job_id = %job_id, "IPC: approval denied");
                                     if !resp.reason.is_empty() {
                                         smgr.record_refusal(&cuid, &req.tool, &resp.reason).await;
                                     }
                                     amgr.expire(&job_id).await;
+                                    let reason = if resp.reason.is_empty() {
+                                        "approval denied".to_string()
+                                    } else {
+                                        format!("approval denied: {}", resp.reason)
+                                    };
+                                    if let Some(st) = &st {
+                                        st.reject_run(&req, &cuid, &context.rejected(&reason))
+                                            .await;
+                                    }
                                     let reject_env = Envelope {
                                         device_id: did,
                                         msg_id: new_msg_id(),
                                         ts_ms: now_ms(),
                                         payload: Some(envelope::Payload::JobRejected(
-                                            JobRejected {
-                                                job_id,
-                                                reason: if resp.reason.is_empty() {
-                                                    "approval denied".to_string()
-                                                } else {
-                                                    format!("approval denied: {}", resp.reason)
-                                                },
-                                            },
+                                            JobRejected { job_id, reason },
                                         )),
                                         ..Default::default()
                                     };
@@ -339,6 +1331,14 @@ where
                                 _ => {
                                     info!(job_id = %job_id, "IPC: approval timed out");
                                     amgr.expire(&job_id).await;
+                                    if let Some(st) = &st {
+                                        st.reject_run(
+                                            &req,
+                                            &cuid,
+                                            &context.rejected("approval timed out"),
+                                        )
+                                        .await;
+                                    }
                                     let reject_env = Envelope {
                                         device_id: did,
                                         msg_id: new_msg_id(),
@@ -362,22 +1362,112 @@ where
                 info!(job_id = %cancel.job_id, "IPC: received cancel request");
                 registry.cancel(&cancel.job_id).await;
             }
+            Some(envelope::Payload::JobSubscribe(sub)) => {
+                info!(job_id = %sub.job_id, from_offset = sub.from_offset, "IPC: received job subscribe");
+                let (live_tx, live_rx) = mpsc::unbounded_channel();
+                let subscription = registry.subscribe(&sub.job_id, live_tx).await;
+                if matches!(subscription, Subscription::Unknown) {
+                    let _ = tx.send(Envelope {
+                        device_id: device_id.clone(),
+                        msg_id: new_msg_id(),
+                        ts_ms: now_ms(),
+                        payload: Some(envelope::Payload::Error(ahand_protocol::Error {
+                            code: JOB_NOT_FOUND_CODE.to_string(),
+                            message: format!("no running or recent job {}", sub.job_id),
+                            ref_msg_id: envelope.msg_id.clone(),
+                        })),
+                        ..Default::default()
+                    });
+                    continue;
+                }
+                tokio::spawn(forward_subscription(
+                    device_id.clone(),
+                    sub,
+                    subscription,
+                    store.clone(),
+                    live_rx,
+                    tx.clone(),
+                ));
+            }
+            Some(envelope::Payload::CancelAll(cancel)) => {
+                info!(caller_uid = %cancel.caller_uid, "IPC: received cancel-all request");
+                let filter = (!cancel.caller_uid.is_empty()).then_some(cancel.caller_uid.as_str());
+                let cancelled = registry.cancel_all(filter).await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::CancelAllResult(CancelAllResult {
+                        cancelled,
+                    })),
+                    ..Default::default()
+                });
+            }
             Some(envelope::Payload::ApprovalResponse(resp)) => {
                 info!(job_id = %resp.job_id, approved = resp.approved, "IPC: received approval response");
-                crate::approval::apply_approval_response(
+                let accepted = crate::approval::apply_approval_response(
                     &approval_mgr,
                     &session_mgr,
                     &resp,
                     &caller_id,
+                    &device_id,
+                    &approval_broadcast_tx,
+                )
+                .await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::ApprovalResolveResult(
+                        ahand_protocol::ApprovalResolveResult {
+                            job_id: resp.job_id.clone(),
+                            accepted,
+                        },
+                    )),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::ApprovalBulkResponse(bulk)) => {
+                info!(tool = %bulk.tool, approved = bulk.approved, "IPC: received bulk approval response");
+                let resolved = crate::approval::apply_bulk_approval_response(
+                    &approval_mgr,
+                    &session_mgr,
+                    &bulk,
+                    &caller_id,
+                    &device_id,
+                    &approval_broadcast_tx,
                 )
                 .await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::ApprovalBulkResult(
+                        ahand_protocol::ApprovalBulkResult {
+                            tool: bulk.tool.clone(),
+                            resolved,
+                        },
+                    )),
+                    ..Default::default()
+                });
             }
             Some(envelope::Payload::SetSessionMode(msg)) => {
                 let mode = SessionMode::try_from(msg.mode).unwrap_or(SessionMode::Inactive);
                 info!(caller_uid = %msg.caller_uid, ?mode, "IPC: received set session mode");
                 let state = session_mgr
-                    .set_mode(&msg.caller_uid, mode, msg.trust_timeout_mins)
+                    .set_mode_with_budget(
+                        &msg.caller_uid,
+                        mode,
+                        msg.trust_timeout_mins,
+                        msg.trust_job_budget,
+                    )
                     .await;
+                crate::session::broadcast_session_state(
+                    &approval_broadcast_tx,
+                    &device_id,
+                    state.clone(),
+                    crate::session::ORIGIN_IPC,
+                );
                 let state_env = Envelope {
                     device_id: device_id.clone(),
                     msg_id: new_msg_id(),
@@ -387,6 +1477,18 @@ where
                 };
                 let _ = tx.send(state_env);
             }
+            Some(envelope::Payload::ClearSession(msg)) => {
+                info!(caller_uid = %msg.caller_uid, "IPC: received clear session");
+                session_mgr.clear_session(&msg.caller_uid).await;
+                let state = session_mgr.get_session_state(&msg.caller_uid).await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::SessionState(state)),
+                    ..Default::default()
+                });
+            }
             Some(envelope::Payload::SessionQuery(query)) => {
                 info!(caller_uid = %query.caller_uid, "IPC: received session query");
                 let states = session_mgr.query_sessions(&query.caller_uid).await;
@@ -401,6 +1503,155 @@ where
                     let _ = tx.send(state_env);
                 }
             }
+            Some(envelope::Payload::PendingApprovalsQuery(_)) => {
+                info!("IPC: received pending approvals query");
+                let requests = approval_mgr.list_pending().await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::PendingApprovalsState(
+                        ahand_protocol::PendingApprovalsState { requests },
+                    )),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::Ping(_)) => {
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::Pong(health.pong(&registry).await)),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::Subscribe(subscribe)) => {
+                let topics = match Topics::parse(&subscribe.topics) {
+                    Ok(topics) => topics,
+                    Err(message) => {
+                        let _ = tx.send(Envelope {
+                            device_id: device_id.clone(),
+                            msg_id: new_msg_id(),
+                            ts_ms: now_ms(),
+                            payload: Some(envelope::Payload::Error(ahand_protocol::Error {
+                                code: UNKNOWN_TOPIC_CODE.to_string(),
+                                message,
+                                ref_msg_id: envelope.msg_id.clone(),
+                            })),
+                            ..Default::default()
+                        });
+                        continue;
+                    }
+                };
+                info!(caller_id = %caller_id, topics = ?subscribe.topics, "IPC: client subscribed");
+                if let Some(previous) = observer.take() {
+                    previous.abort();
+                }
+                if subscribe.topics.is_empty() {
+                    topics_tx.send_replace(None);
+                } else {
+                    topics_tx.send_replace(Some(topics));
+                    if topics.jobs || topics.policy {
+                        observer = Some(spawn_observer(
+                            &registry,
+                            topics,
+                            tx.clone(),
+                            caller_id.clone(),
+                        ));
+                    }
+                }
+            }
+            Some(envelope::Payload::DaemonStatusQuery(_)) => {
+                let (pending_approvals, oldest_pending_approval_age_ms) =
+                    approval_mgr.pending_summary().await;
+                let runs_gc = match &store {
+                    Some(store) => store.last_runs_gc().await,
+                    None => None,
+                };
+                let browser_sessions = browser_mgr
+                    .list_sessions()
+                    .await
+                    .into_iter()
+                    .map(|session| ahand_protocol::BrowserSession {
+                        session_id: session.id,
+                        created_ms: session.created_ms,
+                        last_used_ms: session.last_used_ms,
+                    })
+                    .collect();
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::DaemonStatus(
+                        ahand_protocol::DaemonStatus {
+                            pending_approvals,
+                            oldest_pending_approval_age_ms,
+                            runs_gc,
+                            connection: Some(health.connection.status()),
+                            gateways: health.gateway_statuses(),
+                            browser_sessions,
+                        },
+                    )),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::PolicyQuery(_)) => {
+                info!("IPC: received policy query");
+                let _ = tx.send(policy_state_envelope(&device_id, &policy).await);
+            }
+            Some(envelope::Payload::PolicyUpdate(update)) => {
+                info!("IPC: received policy update");
+                let payload = match policy.apply_update(&update).await {
+                    Ok(state) => {
+                        let payload = envelope::Payload::PolicyState(state);
+                        registry.publish(&Envelope {
+                            device_id: device_id.clone(),
+                            msg_id: new_msg_id(),
+                            ts_ms: now_ms(),
+                            payload: Some(payload.clone()),
+                            ..Default::default()
+                        });
+                        payload
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "IPC: policy update rejected");
+                        envelope::Payload::Error(ahand_protocol::Error {
+                            code: e.code().to_string(),
+                            message: e.to_string(),
+                            ref_msg_id: envelope.msg_id.clone(),
+                        })
+                    }
+                };
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(payload),
+                    ..Default::default()
+                });
+            }
+            Some(envelope::Payload::SetPolicyPreset(msg)) => {
+                info!(preset = %msg.name, "IPC: received set policy preset");
+                let applied = policy.apply_preset(&msg.name, msg.keep_approvals).await;
+                let state = policy_state_envelope(&device_id, &policy).await;
+                match applied {
+                    Ok(()) => registry.publish(&state),
+                    Err(e) => warn!(error = %e, "IPC: policy preset not applied"),
+                }
+                let _ = tx.send(state);
+            }
+            Some(envelope::Payload::PolicyCheckRequest(check)) => {
+                info!(tool = %check.tool, "IPC: received policy check");
+                let result =
+                    crate::policy::dry_run_check(&policy, &session_mgr, &check, &caller_id).await;
+                let _ = tx.send(Envelope {
+                    device_id: device_id.clone(),
+                    msg_id: new_msg_id(),
+                    ts_ms: now_ms(),
+                    payload: Some(envelope::Payload::PolicyCheckResult(result)),
+                    ..Default::default()
+                });
+            }
             Some(envelope::Payload::BrowserRequest(req)) => {
                 info!(
                     request_id = %req.request_id,
@@ -512,11 +1763,51 @@ where
         }
     }
 
+    if limits.violations >= MAX_VIOLATIONS {
+        kicked.cancel();
+    }
+    if let Some(observer) = observer {
+        observer.abort();
+    }
     drop(tx);
     let _ = send_handle.await;
     Ok(())
 }
 
+/// Answer a limit violation with an `Error`; returns whether the
+/// connection has now broken its limits too often and must be closed.
+fn report_violation(
+    tx: &mpsc::UnboundedSender<Envelope>,
+    device_id: &str,
+    caller_id: &str,
+    limits: &mut ConnLimits,
+    err: ahand_protocol::Error,
+) -> bool {
+    warn!(caller_id = %caller_id, code = %err.code, message = %err.message, "IPC: limit violation");
+    let _ = tx.send(Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::Error(err)),
+        ..Default::default()
+    });
+    let close = limits.strike();
+    if close {
+        warn!(caller_id = %caller_id, "IPC: closing connection after repeated limit violations");
+    }
+    close
+}
+
+async fn policy_state_envelope(device_id: &str, policy: &PolicyChecker) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::PolicyState(policy.get_state().await)),
+        ..Default::default()
+    }
+}
+
 fn job_capability_rejection_envelope(
     device_id: &str,
     req: &ahand_protocol::JobRequest,
@@ -553,18 +1844,181 @@ fn managed_runtime_interactive_rejection_envelope(
     }
 }
 
-async fn run_job_with_provider(
+/// Tell a refused peer why, then close the connection.
+async fn refuse_connection<S>(mut stream: S, device_id: String, peer: IpcPeer)
+where
+    S: tokio::io::AsyncWrite + Unpin,
+{
+    let env = Envelope {
+        device_id,
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::Error(ahand_protocol::Error {
+            code: UNAUTHORIZED_CODE.to_string(),
+            message: format!("{} is not allowed on this IPC socket", peer.id),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let _ = write_frame(&mut stream, &env.encode_to_vec()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Deny a mutating request from a read-only caller: a job gets a
+/// `JobRejected`, anything else an `Error`.
+fn read_only_rejection_envelope(
+    device_id: &str,
+    ref_msg_id: &str,
+    payload: &envelope::Payload,
+) -> Envelope {
+    let message = "caller is read-only on this IPC socket".to_string();
+    let payload = match payload {
+        envelope::Payload::JobRequest(req) => envelope::Payload::JobRejected(JobRejected {
+            job_id: req.job_id.clone(),
+            reason: message,
+        }),
+        _ => envelope::Payload::Error(ahand_protocol::Error {
+            code: READ_ONLY_CODE.to_string(),
+            message,
+            ref_msg_id: ref_msg_id.to_string(),
+        }),
+    };
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(payload),
+        ..Default::default()
+    }
+}
+
+fn shutting_down_rejection_envelope(device_id: &str, job_id: &str) -> Envelope {
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRejected(JobRejected {
+            job_id: job_id.to_string(),
+            reason: "daemon shutting down".to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+fn at_capacity_rejection_envelope(device_id: &str, job_id: &str, full: &AtCapacity) -> Envelope {
+    warn!(job_id = %job_id, queued = full.queued, "IPC: job rejected: queue limit reached");
+    Envelope {
+        device_id: device_id.to_string(),
+        msg_id: new_msg_id(),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::JobRejected(JobRejected {
+            job_id: job_id.to_string(),
+            reason: full.to_string(),
+        })),
+        ..Default::default()
+    }
+}
+
+/// Replay a subscribed job's stored stdout, then stderr, from
+/// `from_offset`; then forward its live events until it finishes, or send
+/// the cached `JobFinished` if it already has.
+///
+/// For a running job the replay stops where the live events the
+/// subscriber receives begin, so nothing is sent twice.
+async fn forward_subscription(
+    device_id: String,
+    sub: ahand_protocol::JobSubscribe,
+    subscription: Subscription,
+    store: Option<Arc<RunStore>>,
+    mut live_rx: mpsc::UnboundedReceiver<Envelope>,
+    tx: mpsc::UnboundedSender<Envelope>,
+) {
+    let sent = match subscription {
+        Subscription::Running {
+            stdout_bytes,
+            stderr_bytes,
+        } => Some((stdout_bytes, stderr_bytes)),
+        _ => None,
+    };
+    if let Some(store) = &store {
+        // Output is written by a background thread; make sure everything
+        // counted in `sent` is on disk before reading it back.
+        store.flush().await;
+        for (name, sent) in [
+            ("stdout", sent.map(|(stdout, _)| stdout)),
+            ("stderr", sent.map(|(_, stderr)| stderr)),
+        ] {
+            let output = store.read_output(&sub.job_id, name);
+            let range = replay_range(output.len(), sent, sub.from_offset);
+            for chunk in output[range].chunks(REPLAY_CHUNK_BYTES) {
+                let (stdout, stderr) = match name {
+                    "stdout" => (Some(chunk.to_vec()), None),
+                    _ => (None, Some(chunk.to_vec())),
+                };
+                let event = executor::make_event_envelope(&device_id, &sub.job_id, stdout, stderr);
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    if let Subscription::Finished(job) = subscription {
+        let _ = tx.send(Envelope {
+            device_id,
+            msg_id: new_msg_id(),
+            ts_ms: now_ms(),
+            payload: Some(envelope::Payload::JobFinished(JobFinished {
+                job_id: sub.job_id,
+                exit_code: job.exit_code,
+                error: job.error,
+                ..Default::default()
+            })),
+            ..Default::default()
+        });
+        return;
+    }
+    loop {
+        tokio::select! {
+            event = live_rx.recv() => {
+                let Some(event) = event else { return };
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            _ = tx.closed() => return,
+        }
+    }
+}
+
+/// The part of a stored output `len` bytes long to replay: from
+/// `from_offset` up to the `sent` bytes already streamed live, or to the
+/// end for a finished job.
+fn replay_range(len: usize, sent: Option<u64>, from_offset: u64) -> std::ops::Range<usize> {
+    let end = sent.map_or(len, |sent| len.min(sent as usize));
+    end.min(from_offset as usize)..end
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_job_with_provider<T: executor::EnvelopeSink>(
     device_id: String,
     req: ahand_protocol::JobRequest,
+    context: RunContext,
     provider: JobProvider,
-    tx: mpsc::UnboundedSender<Envelope>,
-    cancel_rx: mpsc::Receiver<()>,
+    tx: T,
+    cancel_rx: mpsc::Receiver<CancelReason>,
     store: Option<Arc<RunStore>>,
+    queued_ms: u64,
 ) -> (i32, String) {
     match provider {
-        JobProvider::DefaultExec => executor::run_job(device_id, req, tx, cancel_rx, store).await,
+        JobProvider::DefaultExec => {
+            executor::run_job(device_id, req, context, tx, cancel_rx, store, queued_ms).await
+        }
         JobProvider::ManagedRuntime { target, .. } => {
-            executor::run_job_with_target(device_id, req, target, tx, cancel_rx, store).await
+            executor::run_job_with_target(
+                device_id, req, context, target, tx, cancel_rx, store, queued_ms,
+            )
+            .await
         }
     }
 }
@@ -582,28 +2036,6 @@ fn browser_unavailable_response(
     }
 }
 
-/// Read a length-prefixed frame: [4 bytes big-endian u32 length][N bytes payload].
-async fn read_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> std::io::Result<Vec<u8>> {
-    let len = reader.read_u32().await? as usize;
-    if len > 16 * 1024 * 1024 {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "frame too large",
-        ));
-    }
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf).await?;
-    Ok(buf)
-}
-
-/// Write a length-prefixed frame.
-async fn write_frame<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) -> std::io::Result<()> {
-    writer.write_u32(data.len() as u32).await?;
-    writer.write_all(data).await?;
-    writer.flush().await?;
-    Ok(())
-}
-
 fn now_ms() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -620,6 +2052,7 @@ fn new_msg_id() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::executor::EnvelopeSink;
 
     #[test]
     fn ipc_browser_unavailable_response_preserves_ids() {
@@ -731,4 +2164,1606 @@ mod tests {
         let got = read_frame(&mut buf_reader).await.unwrap();
         assert_eq!(got, payload);
     }
+
+    // ── job_id reuse ──────────────────────────────────────────────────────────
+
+    fn reuse_request(args: &[&str]) -> ahand_protocol::JobRequest {
+        ahand_protocol::JobRequest {
+            job_id: "ipc-job-1".to_string(),
+            tool: "echo".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Send `req` over a fresh IPC connection and return the first reply.
+    async fn submit_and_recv(
+        req: ahand_protocol::JobRequest,
+        registry: &Arc<JobRegistry>,
+    ) -> Envelope {
+        submit_with_policy(
+            req,
+            registry,
+            &Arc::new(SessionManager::new(5)),
+            &Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default())),
+        )
+        .await
+    }
+
+    /// Like [`submit_and_recv`], sharing `session_mgr` and `policy`.
+    async fn submit_with_policy(
+        req: ahand_protocol::JobRequest,
+        registry: &Arc<JobRegistry>,
+        session_mgr: &Arc<SessionManager>,
+        policy: &Arc<PolicyChecker>,
+    ) -> Envelope {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        tokio::spawn(handle_ipc_conn(
+            server,
+            Arc::clone(registry),
+            None,
+            Arc::clone(session_mgr),
+            Arc::new(ApprovalManager::new(60)),
+            Arc::clone(policy),
+            approval_broadcast_tx,
+            "device-1".to_string(),
+            "uid:501".to_string(),
+            Arc::new(BrowserManager::new(crate::config::BrowserConfig::default())),
+            Arc::new(FileManager::new(&crate::config::FilePolicyConfig::default())),
+            Arc::new(DaemonHealth::default()),
+            IpcAccess::default(),
+            Some(501),
+            CancellationToken::new(),
+        ));
+
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut reader = tokio::io::BufReader::new(reader);
+        let env = Envelope {
+            device_id: "device-1".to_string(),
+            payload: Some(envelope::Payload::JobRequest(req)),
+            ..Default::default()
+        };
+        write_frame(&mut writer, &env.encode_to_vec())
+            .await
+            .unwrap();
+        let data = tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut reader))
+            .await
+            .expect("timed out waiting for IPC reply")
+            .unwrap();
+        Envelope::decode(data.as_slice()).unwrap()
+    }
+
+    fn expect_reuse_rejection(env: Envelope) {
+        match env.payload {
+            Some(envelope::Payload::JobRejected(rejected)) => {
+                assert_eq!(rejected.job_id, "ipc-job-1");
+                assert_eq!(rejected.reason, JOB_ID_REUSE_REASON);
+            }
+            other => panic!("expected JobRejected envelope, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_running_job_id_reuse_with_different_args_is_rejected() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register(
+                    "ipc-job-1".to_string(),
+                    "uid:501",
+                    params_hash(&reuse_request(&["hello"])),
+                    cancel_tx,
+                )
+                .await
+        );
+
+        let env = submit_and_recv(reuse_request(&["goodbye"]), &registry).await;
+        expect_reuse_rejection(env);
+    }
+
+    #[tokio::test]
+    async fn ipc_completed_job_id_reuse_with_different_args_is_rejected() {
+        let registry = Arc::new(JobRegistry::new(4));
+        registry
+            .mark_completed(
+                "ipc-job-1".to_string(),
+                params_hash(&reuse_request(&["hello"])),
+                0,
+                String::new(),
+            )
+            .await;
+
+        let env = submit_and_recv(reuse_request(&["goodbye"]), &registry).await;
+        expect_reuse_rejection(env);
+
+        let env = submit_and_recv(reuse_request(&["hello"]), &registry).await;
+        assert!(matches!(
+            env.payload,
+            Some(envelope::Payload::JobFinished(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn ipc_jobs_are_checked_against_the_policy() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let session_mgr = Arc::new(SessionManager::new(5));
+        session_mgr.set_default_mode(SessionMode::AutoAccept).await;
+        let policy = Arc::new(PolicyChecker::new(&crate::config::PolicyConfig {
+            denied_tools: vec!["rm".to_string()],
+            rate_limit: Some(crate::config::RateLimitConfig {
+                max_jobs_per_minute: 2,
+                burst: None,
+            }),
+            ..Default::default()
+        }));
+        let job = |job_id: &str, tool: &str, args: &[&str]| ahand_protocol::JobRequest {
+            job_id: job_id.to_string(),
+            tool: tool.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        let rejection = |env: Envelope| match env.payload {
+            Some(envelope::Payload::JobRejected(rejected)) => rejected.reason,
+            other => panic!("expected JobRejected envelope, got {other:?}"),
+        };
+
+        let env = submit_with_policy(
+            job("ipc-rm", "rm", &["-rf", "/"]),
+            &registry,
+            &session_mgr,
+            &policy,
+        )
+        .await;
+        assert_eq!(rejection(env), "tool \"rm\" is in the deny list");
+
+        let env = submit_with_policy(
+            job("ipc-echo-1", "echo", &["hi"]),
+            &registry,
+            &session_mgr,
+            &policy,
+        )
+        .await;
+        assert!(
+            !matches!(env.payload, Some(envelope::Payload::JobRejected(_))),
+            "{env:?}"
+        );
+
+        let env = submit_with_policy(
+            job("ipc-echo-2", "echo", &["hi"]),
+            &registry,
+            &session_mgr,
+            &policy,
+        )
+        .await;
+        assert_eq!(rejection(env), "rate limit exceeded: 2 jobs/min");
+    }
+
+    type IpcClient = (
+        tokio::io::BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>,
+        tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    );
+
+    /// Open an IPC connection for `caller_id` sharing the given managers.
+    fn connect(
+        caller_id: &str,
+        session_mgr: &Arc<SessionManager>,
+        approval_broadcast_tx: &broadcast::Sender<Envelope>,
+    ) -> IpcClient {
+        connect_with_approvals(
+            caller_id,
+            session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            approval_broadcast_tx,
+        )
+    }
+
+    /// Like [`connect`], also sharing `approval_mgr`.
+    fn connect_with_approvals(
+        caller_id: &str,
+        session_mgr: &Arc<SessionManager>,
+        approval_mgr: &Arc<ApprovalManager>,
+        approval_broadcast_tx: &broadcast::Sender<Envelope>,
+    ) -> IpcClient {
+        connect_with_access(
+            caller_id,
+            session_mgr,
+            approval_mgr,
+            approval_broadcast_tx,
+            IpcAccess::default(),
+        )
+    }
+
+    /// Like [`connect_with_approvals`], with the peer's UID parsed from
+    /// `caller_id` and checked against `access`.
+    fn connect_with_access(
+        caller_id: &str,
+        session_mgr: &Arc<SessionManager>,
+        approval_mgr: &Arc<ApprovalManager>,
+        approval_broadcast_tx: &broadcast::Sender<Envelope>,
+        access: IpcAccess,
+    ) -> IpcClient {
+        connect_with_jobs(
+            caller_id,
+            &Arc::new(JobRegistry::new(4)),
+            None,
+            session_mgr,
+            approval_mgr,
+            approval_broadcast_tx,
+            access,
+        )
+    }
+
+    /// Like [`connect_with_access`], also sharing `registry` and `store`.
+    fn connect_with_jobs(
+        caller_id: &str,
+        registry: &Arc<JobRegistry>,
+        store: Option<Arc<RunStore>>,
+        session_mgr: &Arc<SessionManager>,
+        approval_mgr: &Arc<ApprovalManager>,
+        approval_broadcast_tx: &broadcast::Sender<Envelope>,
+        access: IpcAccess,
+    ) -> IpcClient {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(handle_ipc_conn(
+            server,
+            Arc::clone(registry),
+            store,
+            Arc::clone(session_mgr),
+            Arc::clone(approval_mgr),
+            Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default())),
+            approval_broadcast_tx.clone(),
+            "device-1".to_string(),
+            caller_id.to_string(),
+            Arc::new(BrowserManager::new(crate::config::BrowserConfig::default())),
+            Arc::new(FileManager::new(&crate::config::FilePolicyConfig::default())),
+            Arc::new(DaemonHealth::default()),
+            access,
+            caller_id
+                .strip_prefix("uid:")
+                .and_then(|uid| uid.parse().ok()),
+            CancellationToken::new(),
+        ));
+        let (reader, writer) = tokio::io::split(client);
+        (tokio::io::BufReader::new(reader), writer)
+    }
+
+    async fn send(client: &mut IpcClient, payload: envelope::Payload) {
+        let env = Envelope {
+            device_id: "device-1".to_string(),
+            payload: Some(payload),
+            ..Default::default()
+        };
+        write_frame(&mut client.1, &env.encode_to_vec())
+            .await
+            .unwrap();
+    }
+
+    async fn recv_session_state(client: &mut IpcClient) -> ahand_protocol::SessionState {
+        loop {
+            let data =
+                tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                    .await
+                    .expect("timed out waiting for SessionState")
+                    .unwrap();
+            if let Some(envelope::Payload::SessionState(state)) =
+                Envelope::decode(data.as_slice()).unwrap().payload
+            {
+                return state;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_mode_change_is_broadcast_to_other_connections() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut setter = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        let mut watcher = connect("uid:502", &session_mgr, &approval_broadcast_tx);
+
+        // A query round-trip guarantees the watcher has subscribed.
+        send(
+            &mut watcher,
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: "uid:502".to_string(),
+            }),
+        )
+        .await;
+        recv_session_state(&mut watcher).await;
+
+        send(
+            &mut setter,
+            envelope::Payload::SetSessionMode(ahand_protocol::SetSessionMode {
+                caller_uid: "uid:501".to_string(),
+                mode: SessionMode::Strict as i32,
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        let state = recv_session_state(&mut watcher).await;
+        assert_eq!(state.caller_uid, "uid:501");
+        assert_eq!(state.mode, SessionMode::Strict as i32);
+        assert_eq!(state.origin, crate::session::ORIGIN_IPC);
+    }
+
+    #[tokio::test]
+    async fn ipc_trust_set_on_one_connection_is_seen_by_a_query_on_another() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut setter = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+
+        send(
+            &mut setter,
+            envelope::Payload::SetSessionMode(ahand_protocol::SetSessionMode {
+                caller_uid: "uid:501".to_string(),
+                mode: SessionMode::Trust as i32,
+                ..Default::default()
+            }),
+        )
+        .await;
+        let reply = recv_session_state(&mut setter).await;
+        assert_eq!(reply.mode, SessionMode::Trust as i32);
+
+        let mut reader = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        send(
+            &mut reader,
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: "uid:501".to_string(),
+            }),
+        )
+        .await;
+        let state = recv_session_state(&mut reader).await;
+        assert_eq!(state.caller_uid, "uid:501");
+        assert_eq!(state.mode, SessionMode::Trust as i32);
+    }
+
+    // ── job subscriptions ─────────────────────────────────────────────────────
+
+    fn subscribe(job_id: &str, from_offset: u64) -> envelope::Payload {
+        envelope::Payload::JobSubscribe(ahand_protocol::JobSubscribe {
+            job_id: job_id.to_string(),
+            from_offset,
+        })
+    }
+
+    fn connect_to_jobs(registry: &Arc<JobRegistry>, store: Option<Arc<RunStore>>) -> IpcClient {
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        connect_with_jobs(
+            "uid:501",
+            registry,
+            store,
+            &Arc::new(SessionManager::new(5)),
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            IpcAccess::default(),
+        )
+    }
+
+    fn stdout_of(payload: Option<envelope::Payload>) -> Vec<u8> {
+        match payload {
+            Some(envelope::Payload::JobEvent(ahand_protocol::JobEvent {
+                event: Some(ahand_protocol::job_event::Event::StdoutChunk(chunk)),
+                ..
+            })) => chunk,
+            other => panic!("expected a stdout JobEvent, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn replay_range_stops_where_live_events_begin() {
+        assert_eq!(replay_range(10, None, 0), 0..10);
+        assert_eq!(replay_range(10, None, 4), 4..10);
+        assert_eq!(replay_range(10, Some(6), 4), 4..6);
+        assert_eq!(replay_range(10, Some(6), 8), 6..6);
+        assert_eq!(replay_range(10, Some(20), 0), 0..10);
+        assert_eq!(replay_range(0, None, 5), 0..0);
+    }
+
+    #[tokio::test]
+    async fn ipc_subscribe_to_unknown_job_gets_an_error() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let mut client = connect_to_jobs(&registry, None);
+        send(&mut client, subscribe("missing", 0)).await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::Error(err)) => assert_eq!(err.code, JOB_NOT_FOUND_CODE),
+            other => panic!("expected Error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_subscribe_to_finished_job_replays_output_then_finishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(RunStore::new(dir.path(), 0, 0).unwrap());
+        store.start_run("job-1", "uid:501", &reuse_request(&[]));
+        store.append_stdout("job-1", b"hello world");
+        let registry = Arc::new(JobRegistry::new(4));
+        registry
+            .mark_completed("job-1".to_string(), [0; 32], 7, String::new())
+            .await;
+
+        let mut client = connect_to_jobs(&registry, Some(store));
+        send(&mut client, subscribe("job-1", 6)).await;
+        assert_eq!(stdout_of(recv(&mut client).await), b"world");
+        match recv(&mut client).await {
+            Some(envelope::Payload::JobFinished(finished)) => {
+                assert_eq!(finished.job_id, "job-1");
+                assert_eq!(finished.exit_code, 7);
+            }
+            other => panic!("expected JobFinished, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_subscribe_to_running_job_replays_then_follows_live_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(RunStore::new(dir.path(), 0, 0).unwrap());
+        store.start_run("job-1", "uid:501", &reuse_request(&[]));
+        let registry = Arc::new(JobRegistry::new(4));
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("job-1".to_string(), "uid:501", [0; 32], cancel_tx)
+                .await
+        );
+        let (owner_tx, _owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        let tee = registry.tee("job-1", owner_tx);
+        store.append_stdout("job-1", b"before ");
+        tee.send(executor::make_event_envelope(
+            "device-1",
+            "job-1",
+            Some(b"before ".to_vec()),
+            None,
+        ))
+        .unwrap();
+
+        let mut client = connect_to_jobs(&registry, Some(store.clone()));
+        send(&mut client, subscribe("job-1", 0)).await;
+        assert_eq!(stdout_of(recv(&mut client).await), b"before ");
+
+        store.append_stdout("job-1", b"after");
+        tee.send(executor::make_event_envelope(
+            "device-1",
+            "job-1",
+            Some(b"after".to_vec()),
+            None,
+        ))
+        .unwrap();
+        assert_eq!(stdout_of(recv(&mut client).await), b"after");
+
+        tee.send(Envelope {
+            payload: Some(envelope::Payload::JobFinished(JobFinished {
+                job_id: "job-1".to_string(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(matches!(
+            recv(&mut client).await,
+            Some(envelope::Payload::JobFinished(_))
+        ));
+    }
+
+    // ── peer-credential access ────────────────────────────────────────────────
+
+    fn access(allowed_uids: &[u32], allowed_gids: &[u32], readonly_uids: &[u32]) -> IpcAccess {
+        IpcAccess {
+            allowed_uids: allowed_uids.to_vec(),
+            allowed_gids: allowed_gids.to_vec(),
+            readonly_uids: readonly_uids.to_vec(),
+            require_hello: false,
+            hello_token: None,
+            limits: IpcLimits::default(),
+        }
+    }
+
+    #[test]
+    fn ipc_access_without_allow_lists_admits_everyone() {
+        let open = IpcAccess::default();
+        assert!(open.allows_connection(Some(502), Some(20)));
+        assert!(open.allows_connection(None, None));
+    }
+
+    #[test]
+    fn ipc_access_admits_listed_uids_and_gids_only() {
+        let access = access(&[501], &[300], &[600]);
+        assert!(access.allows_connection(Some(501), Some(20)));
+        assert!(access.allows_connection(Some(502), Some(300)));
+        assert!(access.allows_connection(Some(600), Some(20)));
+        assert!(!access.allows_connection(Some(502), Some(20)));
+        assert!(!access.allows_connection(None, None));
+    }
+
+    #[test]
+    fn ipc_access_read_only_uids_may_only_query() {
+        let access = access(&[], &[], &[600]);
+        let job = envelope::Payload::JobRequest(reuse_request(&["hi"]));
+        let policy = envelope::Payload::PolicyUpdate(Default::default());
+        let mode = envelope::Payload::SetSessionMode(Default::default());
+        let query = envelope::Payload::SessionQuery(Default::default());
+        let status = envelope::Payload::DaemonStatusQuery(Default::default());
+        for denied in [&job, &policy, &mode] {
+            assert!(!access.allows_payload(Some(600), denied));
+            assert!(access.allows_payload(Some(501), denied));
+        }
+        for allowed in [&query, &status] {
+            assert!(access.allows_payload(Some(600), allowed));
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_read_only_caller_gets_job_rejected_but_can_query() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect_with_access(
+            "uid:600",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            access(&[501], &[], &[600]),
+        );
+
+        send(
+            &mut client,
+            envelope::Payload::JobRequest(reuse_request(&["hi"])),
+        )
+        .await;
+        let data =
+            tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                .await
+                .expect("timed out waiting for JobRejected")
+                .unwrap();
+        match Envelope::decode(data.as_slice()).unwrap().payload {
+            Some(envelope::Payload::JobRejected(rejected)) => {
+                assert_eq!(rejected.job_id, "ipc-job-1");
+            }
+            other => panic!("expected JobRejected, got {other:?}"),
+        }
+
+        send(
+            &mut client,
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: "uid:600".to_string(),
+            }),
+        )
+        .await;
+        let state = recv_session_state(&mut client).await;
+        assert_eq!(state.caller_uid, "uid:600");
+    }
+
+    // ── Hello handshake ───────────────────────────────────────────────────────
+
+    fn hello(protocol_version: u32) -> envelope::Payload {
+        envelope::Payload::Hello(Hello {
+            version: "0.0.0-test".to_string(),
+            protocol_version,
+            ..Default::default()
+        })
+    }
+
+    async fn recv(client: &mut IpcClient) -> Option<envelope::Payload> {
+        let data = recv_raw(client).await;
+        Envelope::decode(data.as_slice()).unwrap().payload
+    }
+
+    /// The next frame's body, as sent on the wire.
+    async fn recv_raw(client: &mut IpcClient) -> Vec<u8> {
+        tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+            .await
+            .expect("timed out waiting for a frame")
+            .unwrap()
+    }
+
+    #[test]
+    fn check_hello_rejects_other_major_versions() {
+        let current = Hello {
+            protocol_version: IPC_PROTOCOL_VERSION,
+            ..Default::default()
+        };
+        assert!(check_hello(&current).is_ok());
+        let newer = Hello {
+            protocol_version: IPC_PROTOCOL_VERSION + 1,
+            ..current
+        };
+        let err = check_hello(&newer).unwrap_err();
+        assert_eq!(err.code, PROTOCOL_MISMATCH_CODE);
+        assert_eq!(
+            err.message,
+            format!(
+                "daemon speaks protocol {IPC_PROTOCOL_VERSION}, this client speaks {}",
+                IPC_PROTOCOL_VERSION + 1
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn ipc_hello_is_answered_before_anything_else() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let approval_mgr = Arc::new(ApprovalManager::new(60));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let _ = approval_mgr
+            .submit(
+                reuse_request(&["hi"]),
+                "uid:501",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+        let mut client = connect_with_approvals(
+            "uid:502",
+            &session_mgr,
+            &approval_mgr,
+            &approval_broadcast_tx,
+        );
+
+        send(&mut client, hello(IPC_PROTOCOL_VERSION)).await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::HelloAccepted(accepted)) => {
+                assert_eq!(accepted.protocol_version, IPC_PROTOCOL_VERSION);
+                assert_eq!(accepted.daemon_version, env!("CARGO_PKG_VERSION"));
+            }
+            other => panic!("expected HelloAccepted, got {other:?}"),
+        }
+        assert!(matches!(
+            recv(&mut client).await,
+            Some(envelope::Payload::ApprovalRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn ipc_mismatched_hello_gets_an_error_then_eof() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+
+        send(&mut client, hello(IPC_PROTOCOL_VERSION + 1)).await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::Error(err)) => assert_eq!(err.code, PROTOCOL_MISMATCH_CODE),
+            other => panic!("expected Error, got {other:?}"),
+        }
+        let eof = read_frame(&mut client.0).await.unwrap_err();
+        assert_eq!(eof.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn ipc_client_without_hello_is_refused_unless_legacy_clients_are_allowed() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let query = || {
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: "uid:501".to_string(),
+            })
+        };
+
+        let mut legacy = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        send(&mut legacy, query()).await;
+        assert_eq!(recv_session_state(&mut legacy).await.caller_uid, "uid:501");
+
+        let mut strict = connect_with_access(
+            "uid:501",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            IpcAccess {
+                require_hello: true,
+                ..Default::default()
+            },
+        );
+        send(&mut strict, query()).await;
+        match recv(&mut strict).await {
+            Some(envelope::Payload::Error(err)) => assert_eq!(err.code, HELLO_REQUIRED_CODE),
+            other => panic!("expected Error, got {other:?}"),
+        }
+    }
+
+    // ── client identity ───────────────────────────────────────────────────────
+
+    fn named_hello(client_name: &str, instance_id: &str) -> envelope::Payload {
+        envelope::Payload::Hello(Hello {
+            protocol_version: IPC_PROTOCOL_VERSION,
+            client_name: client_name.to_string(),
+            instance_id: instance_id.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn caller_id_adds_the_client_name_and_instance_to_the_peer() {
+        let name = |client_name: &str, instance_id: &str| {
+            let Some(envelope::Payload::Hello(hello)) = Some(named_hello(client_name, instance_id))
+            else {
+                unreachable!()
+            };
+            ClientName::from_hello(&hello)
+        };
+        assert_eq!(name("", "").caller_id("uid:501"), "uid:501");
+        assert_eq!(name("", "a1b2").caller_id("uid:501"), "uid:501");
+        assert_eq!(
+            name("ahandctl", "").caller_id("uid:501"),
+            "uid:501/ahandctl"
+        );
+        assert_eq!(
+            name("claude-code", "a1b2").caller_id("uid:501"),
+            "uid:501/claude-code#a1b2"
+        );
+        // Separators and other odd characters can't forge another identity.
+        assert_eq!(
+            name("evil/uid:0#x", "i d").caller_id("uid:501"),
+            "uid:501/eviluid0x#id"
+        );
+        assert_eq!(name(&"x".repeat(100), "").name.len(), MAX_CLIENT_NAME_CHARS);
+    }
+
+    #[tokio::test]
+    async fn ipc_named_clients_of_one_user_get_separate_sessions() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+
+        let mut agent = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        send(&mut agent, named_hello("claude-code", "a1b2")).await;
+        assert!(matches!(
+            recv(&mut agent).await,
+            Some(envelope::Payload::HelloAccepted(_))
+        ));
+        let mut ctl = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        send(&mut ctl, named_hello("ahandctl", "")).await;
+        assert!(matches!(
+            recv(&mut ctl).await,
+            Some(envelope::Payload::HelloAccepted(_))
+        ));
+
+        send(
+            &mut agent,
+            envelope::Payload::SetSessionMode(ahand_protocol::SetSessionMode {
+                caller_uid: "uid:501/claude-code#a1b2".to_string(),
+                mode: SessionMode::Trust as i32,
+                ..Default::default()
+            }),
+        )
+        .await;
+        recv_session_state(&mut agent).await;
+
+        send(
+            &mut ctl,
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: String::new(),
+            }),
+        )
+        .await;
+        let mut rows = Vec::new();
+        while rows.len() < 2 {
+            let state = recv_session_state(&mut ctl).await;
+            if state.caller_uid.starts_with("uid:501/") {
+                rows.push((state.caller_uid, state.mode));
+            }
+        }
+        rows.sort();
+        assert_eq!(rows[0].0, "uid:501/ahandctl");
+        assert_ne!(rows[0].1, SessionMode::Trust as i32);
+        assert_eq!(
+            rows[1],
+            (
+                "uid:501/claude-code#a1b2".to_string(),
+                SessionMode::Trust as i32
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn ipc_hello_can_negotiate_zstd_for_large_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(RunStore::new(dir.path(), 0, 0).unwrap());
+        store.start_run("job-1", "uid:501", &reuse_request(&[]));
+        let output = "cargo build: compiling crate ok\n".repeat(1024);
+        store.append_stdout("job-1", output.as_bytes());
+        let registry = Arc::new(JobRegistry::new(4));
+        registry
+            .mark_completed("job-1".to_string(), [0; 32], 0, String::new())
+            .await;
+
+        let mut client = connect_to_jobs(&registry, Some(store));
+        send(
+            &mut client,
+            envelope::Payload::Hello(Hello {
+                protocol_version: IPC_PROTOCOL_VERSION,
+                accepts: vec!["brotli".to_string(), frame::ZSTD.to_string()],
+                ..Default::default()
+            }),
+        )
+        .await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::HelloAccepted(accepted)) => {
+                assert_eq!(accepted.compression, frame::ZSTD);
+            }
+            other => panic!("expected HelloAccepted, got {other:?}"),
+        }
+
+        let env = Envelope {
+            payload: Some(subscribe("job-1", 0)),
+            ..Default::default()
+        };
+        FrameCodec::Zstd
+            .write_frame(&mut client.1, &env.encode_to_vec())
+            .await
+            .unwrap();
+        // The replayed output arrives flagged and far smaller than it is.
+        let body = recv_raw(&mut client).await;
+        assert_eq!(body[0], 0x01);
+        assert!(body.len() < output.len() / 4, "{} bytes", body.len());
+        let mut wire = (body.len() as u32).to_be_bytes().to_vec();
+        wire.extend_from_slice(&body);
+        let data = FrameCodec::Zstd
+            .read_frame(&mut wire.as_slice())
+            .await
+            .unwrap();
+        let payload = Envelope::decode(data.as_slice()).unwrap().payload;
+        assert_eq!(stdout_of(payload), output.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn ipc_hello_without_accepts_keeps_plain_frames() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+        send(&mut client, hello(IPC_PROTOCOL_VERSION)).await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::HelloAccepted(accepted)) => {
+                assert_eq!(accepted.compression, "");
+            }
+            other => panic!("expected HelloAccepted, got {other:?}"),
+        }
+        send(
+            &mut client,
+            envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                caller_uid: "uid:501".to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(recv_session_state(&mut client).await.caller_uid, "uid:501");
+    }
+
+    // ── Per-connection limits ─────────────────────────────────────────────────
+
+    fn limited(frames_per_sec: u32, inflight_jobs: usize) -> IpcAccess {
+        IpcAccess {
+            limits: IpcLimits {
+                frames_per_sec,
+                inflight_jobs,
+            },
+            ..IpcAccess::default()
+        }
+    }
+
+    #[test]
+    fn frame_bucket_allows_a_second_of_burst_then_the_rate() {
+        let start = Instant::now();
+        let mut bucket = FrameBucket::new(10, start);
+        assert!((0..10).all(|_| bucket.take(start)));
+        assert!(!bucket.take(start));
+        assert!(!bucket.take(start + std::time::Duration::from_millis(50)));
+        assert!(bucket.take(start + std::time::Duration::from_millis(100)));
+        // Idle time refills no more than one second's worth.
+        let later = start + std::time::Duration::from_secs(60);
+        assert_eq!((0..20).filter(|_| bucket.take(later)).count(), 10);
+
+        let mut unlimited = FrameBucket::new(0, start);
+        assert!((0..10_000).all(|_| unlimited.take(start)));
+    }
+
+    #[test]
+    fn validate_job_request_bounds_ids_tools_args_and_env() {
+        let ok = reuse_request(&["hi"]);
+        assert!(validate_job_request(&ok).is_ok());
+        let bad = [
+            ahand_protocol::JobRequest {
+                job_id: String::new(),
+                ..ok.clone()
+            },
+            ahand_protocol::JobRequest {
+                job_id: "j".repeat(MAX_JOB_ID_CHARS + 1),
+                ..ok.clone()
+            },
+            ahand_protocol::JobRequest {
+                tool: "t".repeat(MAX_TOOL_CHARS + 1),
+                ..ok.clone()
+            },
+            ahand_protocol::JobRequest {
+                args: vec![String::new(); MAX_JOB_ARGS + 1],
+                ..ok.clone()
+            },
+            ahand_protocol::JobRequest {
+                env: (0..=MAX_JOB_ENV)
+                    .map(|i| (format!("K{i}"), String::new()))
+                    .collect(),
+                ..ok.clone()
+            },
+        ];
+        for req in &bad {
+            assert!(validate_job_request(req).is_err(), "{req:?}");
+        }
+        let at_limit = ahand_protocol::JobRequest {
+            job_id: "j".repeat(MAX_JOB_ID_CHARS),
+            args: vec![String::new(); MAX_JOB_ARGS],
+            ..ok
+        };
+        assert!(validate_job_request(&at_limit).is_ok());
+    }
+
+    #[test]
+    fn admit_job_hands_out_slots_until_the_inflight_limit() {
+        let mut limits = ConnLimits::new(&limited(0, 2).limits);
+        let first = limits.admit_job(&reuse_request(&[])).unwrap();
+        let _second = limits.admit_job(&reuse_request(&[])).unwrap();
+        let err = limits.admit_job(&reuse_request(&[])).unwrap_err();
+        assert_eq!(err.code, TOO_MANY_JOBS_CODE);
+        drop(first);
+        assert!(limits.admit_job(&reuse_request(&[])).is_ok());
+
+        let err = limits
+            .admit_job(&ahand_protocol::JobRequest::default())
+            .unwrap_err();
+        assert_eq!(err.code, INVALID_REQUEST_CODE);
+
+        assert!((1..MAX_VIOLATIONS).all(|_| !limits.strike()));
+        assert!(limits.strike());
+    }
+
+    #[tokio::test]
+    async fn ipc_second_job_over_the_inflight_limit_gets_an_error() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        session_mgr
+            .set_mode("uid:501", SessionMode::Strict, 0)
+            .await;
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect_with_access(
+            "uid:501",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            limited(0, 1),
+        );
+
+        let job = |job_id: &str| {
+            envelope::Payload::JobRequest(ahand_protocol::JobRequest {
+                job_id: job_id.to_string(),
+                tool: "sh".to_string(),
+                args: vec!["-c".to_string(), "true".to_string()],
+                ..Default::default()
+            })
+        };
+        send(&mut client, job("job-1")).await;
+        assert!(matches!(
+            recv(&mut client).await,
+            Some(envelope::Payload::ApprovalRequest(_))
+        ));
+        send(&mut client, job("job-2")).await;
+        // job-1's request also comes back through the approval broadcast.
+        loop {
+            match recv(&mut client).await {
+                Some(envelope::Payload::ApprovalRequest(req)) => assert_eq!(req.job_id, "job-1"),
+                Some(envelope::Payload::Error(err)) => {
+                    assert_eq!(err.code, TOO_MANY_JOBS_CODE);
+                    break;
+                }
+                other => panic!("expected Error, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_flooding_client_gets_errors_then_is_disconnected() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect_with_access(
+            "uid:501",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            limited(5, 1),
+        );
+
+        // Extra frames in case the bucket refills a little mid-flood.
+        let flood = 25 + MAX_VIOLATIONS as usize;
+        for _ in 0..flood {
+            send(
+                &mut client,
+                envelope::Payload::SessionQuery(ahand_protocol::SessionQuery {
+                    caller_uid: "uid:501".to_string(),
+                }),
+            )
+            .await;
+        }
+        let (mut answered, mut limited) = (0, 0);
+        loop {
+            let frame =
+                tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                    .await
+                    .expect("timed out waiting for the disconnect");
+            let Ok(data) = frame else { break };
+            match Envelope::decode(data.as_slice()).unwrap().payload {
+                Some(envelope::Payload::SessionState(_)) => answered += 1,
+                Some(envelope::Payload::Error(err)) => {
+                    assert_eq!(err.code, RATE_LIMITED_CODE);
+                    limited += 1;
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert!(answered >= 5, "{answered} answered");
+        assert_eq!(limited, MAX_VIOLATIONS);
+    }
+
+    #[tokio::test]
+    async fn ipc_invalid_job_requests_get_errors_then_disconnect() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+
+        for _ in 0..MAX_VIOLATIONS {
+            send(
+                &mut client,
+                envelope::Payload::JobRequest(ahand_protocol::JobRequest {
+                    tool: "echo".to_string(),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        }
+        for _ in 0..MAX_VIOLATIONS {
+            match recv(&mut client).await {
+                Some(envelope::Payload::Error(err)) => {
+                    assert_eq!(err.code, INVALID_REQUEST_CODE);
+                    assert!(err.message.contains("job_id"));
+                }
+                other => panic!("expected Error, got {other:?}"),
+            }
+        }
+        let eof = read_frame(&mut client.0).await.unwrap_err();
+        assert_eq!(eof.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    // ── TCP listener ──────────────────────────────────────────────────────────
+
+    fn tcp_config(extra: &str) -> Config {
+        toml::from_str(&format!("server_url = \"ws://localhost:3000/ws\"\n{extra}")).unwrap()
+    }
+
+    #[test]
+    fn ipc_tcp_only_binds_loopback_unless_remote_is_allowed_with_a_token() {
+        assert!(IpcTcp::from_config(&tcp_config("")).unwrap().is_none());
+
+        let local = IpcTcp::from_config(&tcp_config(r#"ipc_tcp_listen = "127.0.0.1:9801""#))
+            .unwrap()
+            .unwrap();
+        assert_eq!(local.addr, "127.0.0.1:9801".parse().unwrap());
+        assert!(local.token.is_none());
+
+        let err =
+            IpcTcp::from_config(&tcp_config(r#"ipc_tcp_listen = "0.0.0.0:9801""#)).unwrap_err();
+        assert!(err.to_string().contains("ipc_tcp_allow_remote"));
+
+        let no_token = tcp_config("ipc_tcp_listen = \"0.0.0.0:9801\"\nipc_tcp_allow_remote = true");
+        assert!(IpcTcp::from_config(&no_token).is_err());
+
+        let remote = tcp_config(
+            "ipc_tcp_listen = \"0.0.0.0:9801\"\nipc_tcp_allow_remote = true\nipc_tcp_token = \"s3cret\"",
+        );
+        let remote = IpcTcp::from_config(&remote).unwrap().unwrap();
+        assert_eq!(remote.token.as_deref(), Some("s3cret"));
+
+        assert!(IpcTcp::from_config(&tcp_config(r#"ipc_tcp_listen = "nonsense""#)).is_err());
+    }
+
+    #[tokio::test]
+    async fn ipc_hello_must_carry_the_token_when_one_is_required() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let access = IpcAccess {
+            hello_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let with_token = |token: &str| {
+            envelope::Payload::Hello(Hello {
+                protocol_version: IPC_PROTOCOL_VERSION,
+                ipc_token: token.to_string(),
+                ..Default::default()
+            })
+        };
+
+        let mut wrong = connect_with_access(
+            "tcp:127.0.0.1:50000",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            access.clone(),
+        );
+        send(&mut wrong, with_token("guess")).await;
+        match recv(&mut wrong).await {
+            Some(envelope::Payload::Error(err)) => assert_eq!(err.code, UNAUTHORIZED_CODE),
+            other => panic!("expected Error, got {other:?}"),
+        }
+
+        let mut right = connect_with_access(
+            "tcp:127.0.0.1:50001",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            access,
+        );
+        send(&mut right, with_token("s3cret")).await;
+        assert!(matches!(
+            recv(&mut right).await,
+            Some(envelope::Payload::HelloAccepted(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn tcp_listener_serves_the_same_protocol() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let shutdown = CancellationToken::new();
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let server = tokio::spawn(serve_ipc(
+            vec![IpcBind::Tcp(IpcTcp { addr, token: None })],
+            IpcAccess::default(),
+            Arc::new(JobRegistry::new(4)),
+            None,
+            Arc::new(SessionManager::new(5)),
+            Arc::new(ApprovalManager::new(60)),
+            Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default())),
+            approval_broadcast_tx,
+            "device-1".to_string(),
+            Arc::new(BrowserManager::new(crate::config::BrowserConfig::default())),
+            Arc::new(FileManager::new(&crate::config::FilePolicyConfig::default())),
+            Arc::new(DaemonHealth::default()),
+            shutdown.clone(),
+        ));
+
+        let stream = loop {
+            match tokio::net::TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = tokio::io::BufReader::new(reader);
+        let env = Envelope {
+            device_id: "device-1".to_string(),
+            payload: Some(envelope::Payload::SessionQuery(
+                ahand_protocol::SessionQuery {
+                    caller_uid: String::new(),
+                },
+            )),
+            ..Default::default()
+        };
+        write_frame(&mut writer, &env.encode_to_vec())
+            .await
+            .unwrap();
+        let state = loop {
+            let data = read_frame(&mut reader).await.unwrap();
+            if let Some(envelope::Payload::SessionState(state)) =
+                Envelope::decode(data.as_slice()).unwrap().payload
+            {
+                break state;
+            }
+        };
+        assert!(state.caller_uid.starts_with("tcp:127.0.0.1:"));
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refused_peer_gets_an_error_then_eof() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let peer = IpcPeer {
+            id: "uid:502".to_string(),
+            uid: Some(502),
+            gid: Some(20),
+        };
+        tokio::spawn(refuse_connection(server, "device-1".to_string(), peer));
+
+        let mut reader = tokio::io::BufReader::new(client);
+        let data = read_frame(&mut reader).await.unwrap();
+        match Envelope::decode(data.as_slice()).unwrap().payload {
+            Some(envelope::Payload::Error(err)) => {
+                assert_eq!(err.code, UNAUTHORIZED_CODE);
+                assert!(err.message.contains("uid:502"));
+            }
+            other => panic!("expected Error, got {other:?}"),
+        }
+        let eof = read_frame(&mut reader).await.unwrap_err();
+        assert_eq!(eof.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shutdown_tells_clients_then_closes_and_removes_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = IpcEndpoint::from_path(dir.path().join("ahandd.sock"));
+        let shutdown = CancellationToken::new();
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let server = tokio::spawn(serve_ipc(
+            vec![IpcBind::Local {
+                endpoint: endpoint.clone(),
+                socket_mode: 0o600,
+            }],
+            IpcAccess::default(),
+            Arc::new(JobRegistry::new(4)),
+            None,
+            Arc::new(SessionManager::new(5)),
+            Arc::new(ApprovalManager::new(60)),
+            Arc::new(PolicyChecker::new(&crate::config::PolicyConfig::default())),
+            approval_broadcast_tx,
+            "device-1".to_string(),
+            Arc::new(BrowserManager::new(crate::config::BrowserConfig::default())),
+            Arc::new(FileManager::new(&crate::config::FilePolicyConfig::default())),
+            Arc::new(DaemonHealth::default()),
+            shutdown.clone(),
+        ));
+
+        let stream = loop {
+            match ahand_platform::ipc::ipc_connect(&endpoint).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = tokio::io::BufReader::new(reader);
+        let env = Envelope {
+            device_id: "device-1".to_string(),
+            payload: Some(hello(IPC_PROTOCOL_VERSION)),
+            ..Default::default()
+        };
+        write_frame(&mut writer, &env.encode_to_vec())
+            .await
+            .unwrap();
+        let data = read_frame(&mut reader).await.unwrap();
+        assert!(matches!(
+            Envelope::decode(data.as_slice()).unwrap().payload,
+            Some(envelope::Payload::HelloAccepted(_))
+        ));
+
+        shutdown.cancel();
+        let data = read_frame(&mut reader).await.unwrap();
+        match Envelope::decode(data.as_slice()).unwrap().payload {
+            Some(envelope::Payload::Shutdown(shutdown)) => {
+                assert_eq!(shutdown.reason, SHUTDOWN_REASON)
+            }
+            other => panic!("expected Shutdown, got {other:?}"),
+        }
+        let eof = read_frame(&mut reader).await.unwrap_err();
+        assert_eq!(eof.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("IPC server did not stop")
+            .unwrap()
+            .unwrap();
+        assert!(!endpoint.as_path().exists());
+    }
+
+    #[tokio::test]
+    async fn ipc_approval_response_for_unknown_job_is_not_accepted() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect("uid:501", &session_mgr, &approval_broadcast_tx);
+
+        send(
+            &mut client,
+            envelope::Payload::ApprovalResponse(ahand_protocol::ApprovalResponse {
+                job_id: "job-missing".to_string(),
+                approved: true,
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        let data =
+            tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                .await
+                .expect("timed out waiting for ApprovalResolveResult")
+                .unwrap();
+        match Envelope::decode(data.as_slice()).unwrap().payload {
+            Some(envelope::Payload::ApprovalResolveResult(result)) => {
+                assert_eq!(result.job_id, "job-missing");
+                assert!(!result.accepted);
+            }
+            other => panic!("expected ApprovalResolveResult, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_new_connection_receives_pending_approvals() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let approval_mgr = Arc::new(ApprovalManager::new(60));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let _ = approval_mgr
+            .submit(
+                reuse_request(&["hi"]),
+                "uid:501",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+
+        let mut client = connect_with_approvals(
+            "uid:502",
+            &session_mgr,
+            &approval_mgr,
+            &approval_broadcast_tx,
+        );
+
+        let data =
+            tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                .await
+                .expect("timed out waiting for the replayed ApprovalRequest")
+                .unwrap();
+        match Envelope::decode(data.as_slice()).unwrap().payload {
+            Some(envelope::Payload::ApprovalRequest(req)) => {
+                assert_eq!(req.job_id, "ipc-job-1");
+                assert_eq!(req.caller_uid, "uid:501");
+                assert!(req.replayed);
+            }
+            other => panic!("expected ApprovalRequest, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_daemon_status_reports_pending_approvals() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let approval_mgr = Arc::new(ApprovalManager::new(60));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let _ = approval_mgr
+            .submit(
+                reuse_request(&["hi"]),
+                "uid:501",
+                "reason".to_string(),
+                vec![],
+                vec![],
+                vec![],
+            )
+            .await;
+        let mut client = connect_with_approvals(
+            "uid:502",
+            &session_mgr,
+            &approval_mgr,
+            &approval_broadcast_tx,
+        );
+
+        send(
+            &mut client,
+            envelope::Payload::DaemonStatusQuery(ahand_protocol::DaemonStatusQuery {}),
+        )
+        .await;
+
+        loop {
+            let data =
+                tokio::time::timeout(std::time::Duration::from_secs(5), read_frame(&mut client.0))
+                    .await
+                    .expect("timed out waiting for DaemonStatus")
+                    .unwrap();
+            match Envelope::decode(data.as_slice()).unwrap().payload {
+                Some(envelope::Payload::DaemonStatus(status)) => {
+                    assert_eq!(status.pending_approvals, 1);
+                    assert!(status.oldest_pending_approval_age_ms < 60_000);
+                    break;
+                }
+                Some(envelope::Payload::ApprovalRequest(req)) => assert!(req.replayed),
+                other => panic!("expected DaemonStatus, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn daemon_health_follows_the_cloud_connection() {
+        let registry = JobRegistry::new(4);
+        let health = DaemonHealth::default();
+        assert!(!health.pong(&registry).await.connected_to_cloud);
+        health.connection().connected("wss://eu.example/ws", false);
+        let pong = health.pong(&registry).await;
+        assert!(pong.connected_to_cloud);
+        assert_eq!(pong.cloud_url, "wss://eu.example/ws");
+        assert!(pong.cloud_failover);
+        assert_eq!(pong.daemon_version, env!("CARGO_PKG_VERSION"));
+        health
+            .connection()
+            .backoff(std::time::Duration::from_secs(2));
+        assert!(!health.pong(&registry).await.connected_to_cloud);
+    }
+
+    #[test]
+    fn daemon_health_reports_each_gateway_in_order() {
+        let health = DaemonHealth::default();
+        assert!(health.gateway_statuses().is_empty());
+        let home = health.add_gateway();
+        let work = health.add_gateway();
+        home.connected("ws://home.lan:18789", true);
+        work.backoff(std::time::Duration::from_secs(5));
+
+        let statuses = health.gateway_statuses();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(
+            statuses[0].phase(),
+            ahand_protocol::ConnectionPhase::Connected
+        );
+        assert_eq!(statuses[0].url, "ws://home.lan:18789");
+        assert_eq!(
+            statuses[1].phase(),
+            ahand_protocol::ConnectionPhase::Backoff
+        );
+    }
+
+    #[tokio::test]
+    async fn ipc_ping_gets_a_pong_even_from_a_read_only_caller() {
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut client = connect_with_access(
+            "uid:600",
+            &session_mgr,
+            &Arc::new(ApprovalManager::new(60)),
+            &approval_broadcast_tx,
+            access(&[501], &[], &[600]),
+        );
+
+        send(
+            &mut client,
+            envelope::Payload::Ping(ahand_protocol::Ping {}),
+        )
+        .await;
+        match recv(&mut client).await {
+            Some(envelope::Payload::Pong(pong)) => {
+                assert_eq!(pong.daemon_version, env!("CARGO_PKG_VERSION"));
+                assert!(!pong.connected_to_cloud);
+                assert_eq!(pong.active_jobs, 0);
+            }
+            other => panic!("expected Pong, got {other:?}"),
+        }
+    }
+
+    // ── observers ─────────────────────────────────────────────────────────────
+
+    /// Send a `Subscribe` for `topics` and wait until it has taken effect.
+    async fn observe(client: &mut IpcClient, topics: &[&str]) {
+        send(
+            client,
+            envelope::Payload::Subscribe(ahand_protocol::Subscribe {
+                topics: topics.iter().map(|t| t.to_string()).collect(),
+            }),
+        )
+        .await;
+        // Requests are handled in order: once the Pong is back, so is the
+        // subscription.
+        send(client, envelope::Payload::Ping(ahand_protocol::Ping {})).await;
+        match recv(client).await {
+            Some(envelope::Payload::Pong(_)) => {}
+            other => panic!("expected Pong, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn topics_route_job_output_only_to_jobs_output() {
+        let jobs = Topics::parse(&["jobs".to_string()]).unwrap();
+        let output = Topics::parse(&["jobs.output".to_string()]).unwrap();
+        let stdout = executor::make_event_envelope("device-1", "job-1", Some(b"hi".to_vec()), None);
+        let finished = Envelope {
+            payload: Some(envelope::Payload::JobFinished(JobFinished::default())),
+            ..Default::default()
+        };
+        assert!(!jobs.wants(&stdout));
+        assert!(jobs.wants(&finished));
+        assert!(output.wants(&stdout));
+        assert!(output.wants(&finished));
+        assert!(Topics::parse(&["everything".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn ipc_observer_sees_other_clients_jobs_without_their_output() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let session_mgr = Arc::new(SessionManager::new(5));
+        let approval_mgr = Arc::new(ApprovalManager::new(60));
+        let (approval_broadcast_tx, _) = broadcast::channel(8);
+        let mut observer = connect_with_jobs(
+            "uid:502",
+            &registry,
+            None,
+            &session_mgr,
+            &approval_mgr,
+            &approval_broadcast_tx,
+            IpcAccess::default(),
+        );
+        observe(&mut observer, &["jobs"]).await;
+
+        // Outside the observer's topics: held back.
+        crate::session::broadcast_session_state(
+            &approval_broadcast_tx,
+            "device-1",
+            ahand_protocol::SessionState::default(),
+            crate::session::ORIGIN_IPC,
+        );
+        let req = reuse_request(&["hello"]);
+        registry.publish_started("device-1", &req);
+        let (owner_tx, _owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        let tee = registry.tee("ipc-job-1", owner_tx);
+        tee.send(executor::make_event_envelope(
+            "device-1",
+            "ipc-job-1",
+            Some(b"hello\n".to_vec()),
+            None,
+        ))
+        .unwrap();
+        tee.send(Envelope {
+            payload: Some(envelope::Payload::JobFinished(JobFinished {
+                job_id: "ipc-job-1".to_string(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+        .unwrap();
+        match recv(&mut observer).await {
+            Some(envelope::Payload::JobRequest(started)) => assert_eq!(started.job_id, "ipc-job-1"),
+            other => panic!("expected JobRequest, got {other:?}"),
+        }
+        match recv(&mut observer).await {
+            Some(envelope::Payload::JobFinished(finished)) => {
+                assert_eq!(finished.job_id, "ipc-job-1")
+            }
+            other => panic!("expected JobFinished, got {other:?}"),
+        }
+
+        // A rejection on another connection is mirrored too.
+        registry
+            .mark_completed("ipc-job-1".to_string(), params_hash(&req), 0, String::new())
+            .await;
+        expect_reuse_rejection(submit_and_recv(reuse_request(&["bye"]), &registry).await);
+        match recv(&mut observer).await {
+            Some(envelope::Payload::JobRejected(rejected)) => {
+                assert_eq!(rejected.reason, JOB_ID_REUSE_REASON)
+            }
+            other => panic!("expected JobRejected, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_observer_disconnecting_leaves_other_observers_subscribed() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let mut staying = connect_to_jobs(&registry, None);
+        let mut leaving = connect_to_jobs(&registry, None);
+        observe(&mut staying, &["jobs.output"]).await;
+        observe(&mut leaving, &["jobs"]).await;
+
+        send(
+            &mut staying,
+            envelope::Payload::Subscribe(ahand_protocol::Subscribe {
+                topics: vec!["jobs".to_string(), "everything".to_string()],
+            }),
+        )
+        .await;
+        match recv(&mut staying).await {
+            Some(envelope::Payload::Error(err)) => assert_eq!(err.code, UNKNOWN_TOPIC_CODE),
+            other => panic!("expected Error, got {other:?}"),
+        }
+        drop(leaving);
+
+        // The refused Subscribe kept the earlier topics.
+        let (owner_tx, _owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        registry
+            .tee("job-1", owner_tx)
+            .send(executor::make_event_envelope(
+                "device-1",
+                "job-1",
+                Some(b"still here".to_vec()),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(stdout_of(recv(&mut staying).await), b"still here");
+    }
 }
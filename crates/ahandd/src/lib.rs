@@ -1,18 +1,30 @@
 pub mod ahand_client;
 pub mod app_tool_registry;
 pub mod approval;
+pub mod audit;
 pub mod browser;
 pub mod browser_setup;
+pub mod capabilities;
 pub mod config;
+pub mod connection;
 pub mod device_identity;
 pub mod executor;
 pub mod file_manager;
+pub mod notify;
 pub mod outbox;
 pub mod plugin_runtime;
+pub mod policy;
+pub mod presets;
+pub mod proxy;
+pub mod reconnect;
+pub mod redact;
 pub mod registry;
+pub mod run_index;
 pub mod sandbox;
+pub mod send_queue;
 pub mod session;
 pub mod store;
+pub mod tls;
 pub mod updater;
 
 mod public_api;
@@ -1,21 +1,32 @@
 mod ahand_client;
 mod app_tool_registry;
 mod approval;
+mod audit;
 mod browser;
 mod browser_setup;
+mod capabilities;
 mod cli;
 mod config;
+mod connection;
 mod device_identity;
 mod executor;
 mod file_manager;
 mod ipc;
+mod notify;
 mod openclaw;
 mod outbox;
 mod plugin_runtime;
 mod policy;
+mod presets;
+mod proxy;
+mod reconnect;
+mod redact;
 mod registry;
+mod run_index;
+mod send_queue;
 mod session;
 mod store;
+mod tls;
 pub mod updater;
 
 use std::collections::BTreeSet;
@@ -26,7 +37,14 @@ use ahand_protocol::Envelope;
 use anyhow::Context as _;
 use clap::{Parser, Subcommand};
 use config::ConnectionMode;
-use tracing::info;
+use tracing::{Instrument as _, info};
+
+/// How long running jobs get to wind down after a shutdown signal.
+const SHUTDOWN_JOB_GRACE: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long the connection gets to flush queued frames and close after jobs
+/// have been drained.
+const SHUTDOWN_CLOSE_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
 
 #[derive(Parser)]
 #[command(name = "ahandd", about = "AHand local execution daemon")]
@@ -177,19 +195,42 @@ async fn main() -> anyhow::Result<()> {
                 config::Config {
                     mode: args.mode.clone(),
                     server_url: args.url.clone().unwrap(),
+                    server_urls: Vec::new(),
                     device_id: None,
                     max_concurrent_jobs: None,
+                    queue_limit: None,
+                    completed_retention_secs: None,
                     data_dir: None,
+                    trace_max_bytes: None,
+                    trace_keep_files: None,
+                    runs_retention_days: None,
+                    runs_max_total_bytes: None,
+                    compress_outputs_over_bytes: None,
+                    run_output_cap_bytes: None,
                     debug_ipc: None,
                     ipc_socket_path: None,
                     ipc_socket_mode: None,
+                    ipc_allowed_uids: None,
+                    ipc_allowed_gids: None,
+                    ipc_readonly_uids: None,
+                    ipc_legacy_clients: None,
+                    ipc_tcp_listen: None,
+                    ipc_tcp_allow_remote: None,
+                    ipc_tcp_token: None,
+                    ipc_max_frames_per_sec: None,
                     trust_timeout_mins: None,
+                    max_refusals_per_caller: None,
                     default_session_mode: None,
+                    session: None,
+                    approval: None,
+                    store: None,
                     policy: Default::default(),
                     openclaw: None,
                     browser: None,
                     hub: None,
                     file_policy: None,
+                    tls: None,
+                    proxy_url: None,
                 }
             }
         } else {
@@ -202,19 +243,42 @@ async fn main() -> anyhow::Result<()> {
             config::Config {
                 mode: args.mode.clone(),
                 server_url: args.url.clone().unwrap(),
+                server_urls: Vec::new(),
                 device_id: None,
                 max_concurrent_jobs: None,
+                queue_limit: None,
+                completed_retention_secs: None,
                 data_dir: None,
+                trace_max_bytes: None,
+                trace_keep_files: None,
+                runs_retention_days: None,
+                runs_max_total_bytes: None,
+                compress_outputs_over_bytes: None,
+                run_output_cap_bytes: None,
                 debug_ipc: None,
                 ipc_socket_path: None,
                 ipc_socket_mode: None,
+                ipc_allowed_uids: None,
+                ipc_allowed_gids: None,
+                ipc_readonly_uids: None,
+                ipc_legacy_clients: None,
+                ipc_tcp_listen: None,
+                ipc_tcp_allow_remote: None,
+                ipc_tcp_token: None,
+                ipc_max_frames_per_sec: None,
                 trust_timeout_mins: None,
+                max_refusals_per_caller: None,
                 default_session_mode: None,
+                session: None,
+                approval: None,
+                store: None,
                 policy: Default::default(),
                 openclaw: None,
                 browser: None,
                 hub: None,
                 file_policy: None,
+                tls: None,
+                proxy_url: None,
             }
         }
     };
@@ -225,6 +289,7 @@ async fn main() -> anyhow::Result<()> {
     }
     if let Some(url) = args.url {
         cfg.server_url = url;
+        cfg.server_urls.clear();
     }
     if let Some(max_jobs) = args.max_jobs {
         cfg.max_concurrent_jobs = Some(max_jobs);
@@ -276,26 +341,57 @@ async fn main() -> anyhow::Result<()> {
     let connection_mode = cfg.connection_mode();
     let debug_ipc = cfg.debug_ipc.unwrap_or(false);
     let ipc_socket_path = cfg.ipc_socket_path();
-    let ipc_socket_mode = cfg.ipc_socket_mode();
+    let ipc_access = ipc::IpcAccess::from_config(&cfg);
+    let ipc_binds: Vec<_> = std::iter::once(ipc::IpcBind::Local {
+        endpoint: ipc_socket_path.clone(),
+        socket_mode: cfg.ipc_socket_mode(),
+    })
+    .chain(ipc::IpcTcp::from_config(&cfg)?.map(ipc::IpcBind::Tcp))
+    .collect();
 
     // Shared resources.
     let max_jobs = cfg.max_concurrent_jobs.unwrap_or(8);
-    let registry = Arc::new(registry::JobRegistry::new(max_jobs));
+    let mut registry = registry::JobRegistry::new(max_jobs);
+    if let Some(limit) = cfg.queue_limit {
+        registry = registry.with_queue_limit(limit);
+    }
+    if let Some(secs) = cfg.completed_retention_secs {
+        registry = registry.with_completed_retention(std::time::Duration::from_secs(secs));
+    }
+    let registry = Arc::new(registry);
 
     let store_opt = match cfg.data_dir() {
-        Some(dir) => match store::RunStore::new(&dir) {
-            Ok(s) => {
-                info!(data_dir = %dir.display(), "run store initialised");
-                Some(Arc::new(s))
-            }
-            Err(e) => {
-                tracing::warn!(error = %e, "failed to initialise run store, persistence disabled");
-                None
+        Some(dir) => {
+            match store::RunStore::new(&dir, cfg.trace_max_bytes(), cfg.trace_keep_files()) {
+                Ok(s) => {
+                    info!(data_dir = %dir.display(), "run store initialised");
+                    Some(Arc::new(
+                        s.with_runs_retention(
+                            std::time::Duration::from_secs(cfg.runs_retention_days() * 24 * 3600),
+                            cfg.runs_max_total_bytes(),
+                        )
+                        .with_output_compression(cfg.compress_outputs_over_bytes())
+                        .with_run_output_cap(cfg.run_output_cap_bytes())
+                        .with_env_redaction(redact::EnvRedactor::new(
+                            &cfg.store_config().redact_env_patterns,
+                        ))
+                        .with_trace_payloads(cfg.store_config().trace_payloads)
+                        .with_browser_downloads(cfg.browser_config().downloads_root()),
+                    ))
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to initialise run store, persistence disabled");
+                    None
+                }
             }
-        },
+        }
         None => None,
     };
 
+    if let Some(store) = &store_opt {
+        tokio::spawn(store::sweep_runs(Arc::clone(store), Arc::clone(&registry)));
+    }
+
     // Write PID file so ahandctl can detect whether daemon is running.
     let pid_path = write_pid_file(&cfg.data_dir());
     if let Some(ref p) = pid_path {
@@ -305,9 +401,35 @@ async fn main() -> anyhow::Result<()> {
     // Clean up any stale binary left by a previous Windows self-update.
     updater::cleanup_old_binary();
 
-    let session_mgr = Arc::new(session::SessionManager::new(
-        cfg.trust_timeout_mins.unwrap_or(60),
-    ));
+    let audit_log = cfg.data_dir().and_then(|dir| {
+        match audit::AuditLog::open(&dir, cfg.policy.audit_max_bytes) {
+            Ok(log) => Some(Arc::new(log)),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to open audit log, auditing disabled");
+                None
+            }
+        }
+    });
+
+    // Trust expiry notifications; forwarded onto the broadcast channel once
+    // the device id is known.
+    let (session_events_tx, session_events_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let session_cfg = cfg.session_config();
+    let mut session_mgr = session::SessionManager::new(cfg.trust_timeout_mins.unwrap_or(60))
+        .with_state_events(session_events_tx.clone())
+        .with_priority_clamp(cfg.policy.clamp_untrusted_priority)
+        .with_read_only_tools(session_cfg.read_only_tools.clone());
+    if let Some(max) = cfg.max_refusals_per_caller {
+        session_mgr = session_mgr.with_max_refusals_per_caller(max);
+    }
+    if let Some(audit) = &audit_log {
+        session_mgr = session_mgr.with_audit_log(Arc::clone(audit));
+    }
+    if let Some(dir) = cfg.data_dir() {
+        session_mgr = session_mgr.with_sessions_file(dir.join("sessions.json"));
+    }
+    let session_mgr = Arc::new(session_mgr);
 
     // Apply default session mode from config.
     if let Some(mode_str) = &cfg.default_session_mode {
@@ -315,19 +437,51 @@ async fn main() -> anyhow::Result<()> {
             "auto_accept" | "auto" => ahand_protocol::SessionMode::AutoAccept,
             "trust" => ahand_protocol::SessionMode::Trust,
             "strict" => ahand_protocol::SessionMode::Strict,
+            "read_only" | "readonly" => ahand_protocol::SessionMode::ReadOnly,
             _ => ahand_protocol::SessionMode::Inactive,
         };
         session_mgr.set_default_mode(mode).await;
     }
 
-    let approval_mgr = Arc::new(approval::ApprovalManager::new(
-        cfg.policy.approval_timeout_secs,
-    ));
+    // PolicyChecker answers policy queries, updates, presets and dry-run
+    // checks. It gates system.run commands in openclaw-gateway mode, but
+    // not hub jobs yet.
+    let mut policy = policy::PolicyChecker::new(&cfg.policy);
+    if let Some(dir) = cfg.presets_dir() {
+        policy = policy.with_presets_dir(dir);
+    }
+    if let Some(dir) = cfg.data_dir() {
+        policy = policy.with_approvals_file(dir.join("approvals.json"));
+    }
+    if let Some(audit) = &audit_log {
+        policy = policy.with_audit_log(Arc::clone(audit));
+    }
+    let policy = Arc::new(policy);
+    tokio::spawn(policy::sweep_remembered_approvals(Arc::clone(&policy)));
 
-    // PolicyChecker preserved for future Mode 5 (preset) use.
-    let _policy = Arc::new(policy::PolicyChecker::new(&cfg.policy));
+    let mut approval_mgr = approval::ApprovalManager::new(cfg.policy.approval_timeout_secs)
+        .with_policy(Arc::clone(&policy));
+    if let Some(audit) = &audit_log {
+        approval_mgr = approval_mgr.with_audit_log(Arc::clone(audit));
+    }
+    if let Some(dir) = cfg.data_dir() {
+        approval_mgr = approval_mgr
+            .with_env_redaction(redact::EnvRedactor::new(
+                &cfg.store_config().redact_env_patterns,
+            ))
+            .with_pending_file(dir.join("pending_approvals.json"));
+    }
+    if cfg.approval_config().desktop_notifications {
+        approval_mgr = approval_mgr.with_notifier(Arc::new(notify::DesktopNotifier::new(
+            Arc::clone(&session_mgr),
+        )));
+    }
+    let approval_mgr = Arc::new(approval_mgr);
 
     let browser_mgr = Arc::new(browser::BrowserManager::new(cfg.browser_config()));
+    if browser_mgr.is_enabled() {
+        tokio::spawn(browser::sweep_sessions(Arc::clone(&browser_mgr)));
+    }
 
     let file_policy_cfg = cfg.file_policy.clone().unwrap_or_default();
     let file_mgr = Arc::new(file_manager::FileManager::new(&file_policy_cfg));
@@ -338,9 +492,33 @@ async fn main() -> anyhow::Result<()> {
     // Broadcast channel for pushing approval requests to all IPC clients.
     let (approval_broadcast_tx, _) = tokio::sync::broadcast::channel::<Envelope>(64);
 
+    tokio::spawn(session::watch_trust_expiry(
+        Arc::clone(&session_mgr),
+        session_events_tx,
+    ));
+    tokio::spawn(session::flush_sessions(Arc::clone(&session_mgr)));
+    if session_cfg.idle_session_days > 0 {
+        tokio::spawn(session::sweep_idle_sessions(
+            Arc::clone(&session_mgr),
+            std::time::Duration::from_secs(session_cfg.idle_session_days * 24 * 3600),
+        ));
+    }
+
     // Set up signal handlers for graceful shutdown (SIGTERM/SIGINT on Unix,
     // Ctrl-C on Windows).
     let shutdown = ahand_platform::signals::shutdown_signal()?;
+    // Flipped after running jobs are drained so the cloud client can flush
+    // its queue, send a Close frame and stop reconnecting.
+    let (client_shutdown_tx, client_shutdown_rx) = tokio::sync::watch::channel(false);
+    let shutdown_registry = Arc::clone(&registry);
+    let shutdown_store = store_opt.clone();
+    let shutdown_sessions = Arc::clone(&session_mgr);
+    let shutdown_ipc_socket = debug_ipc.then(|| ipc_socket_path.clone());
+    // Cancelled alongside `client_shutdown_tx`: the IPC server tells its
+    // clients, closes their connections and removes the socket.
+    let ipc_shutdown = tokio_util::sync::CancellationToken::new();
+    // Uptime and cloud link state for IPC `Pong`s.
+    let ipc_health = Arc::new(ipc::DaemonHealth::default());
 
     let main_future = async {
         match connection_mode {
@@ -354,9 +532,19 @@ async fn main() -> anyhow::Result<()> {
                 let identity = device_identity::DeviceIdentity::load_or_create(&identity_path)
                     .context("failed to load device identity")?;
                 let device_id = identity.device_id();
+                tokio::spawn(session::forward_session_events(
+                    session_events_rx,
+                    device_id.clone(),
+                    approval_broadcast_tx.clone(),
+                ));
+                tokio::spawn(approval::sweep_expired_approvals(
+                    Arc::clone(&approval_mgr),
+                    device_id.clone(),
+                    approval_broadcast_tx.clone(),
+                ));
 
                 info!(
-                    server_url = %cfg.server_url,
+                    server_urls = ?cfg.server_urls(),
                     device_id = %device_id,
                     max_concurrent_jobs = max_jobs,
                     debug_ipc,
@@ -365,25 +553,41 @@ async fn main() -> anyhow::Result<()> {
 
                 if debug_ipc {
                     let ipc_handle = tokio::spawn(ipc::serve_ipc(
-                        ipc_socket_path,
-                        ipc_socket_mode,
+                        ipc_binds,
+                        ipc_access.clone(),
                         Arc::clone(&registry),
                         store_opt.clone(),
                         Arc::clone(&session_mgr),
                         Arc::clone(&approval_mgr),
+                        Arc::clone(&policy),
                         approval_broadcast_tx.clone(),
                         device_id.clone(),
                         Arc::clone(&browser_mgr),
                         Arc::clone(&file_mgr),
+                        Arc::clone(&ipc_health),
+                        ipc_shutdown.clone(),
                     ));
 
-                    tokio::select! {
-                        r = ahand_client::run(cfg, device_id, registry, store_opt, session_mgr, approval_mgr, approval_broadcast_tx, Arc::clone(&browser_mgr), Arc::clone(&file_mgr), Arc::clone(&app_tools)) => r,
-                        r = ipc_handle => {
-                            r??;
-                            Ok(())
-                        }
-                    }
+                    run_with_ipc(
+                        ahand_client::run(
+                            cfg,
+                            device_id,
+                            registry,
+                            store_opt,
+                            session_mgr,
+                            approval_mgr,
+                            policy,
+                            approval_broadcast_tx,
+                            Arc::clone(&browser_mgr),
+                            Arc::clone(&file_mgr),
+                            Arc::clone(&app_tools),
+                            ipc_health.connection(),
+                            client_shutdown_rx.clone(),
+                        ),
+                        ipc_handle,
+                        &ipc_shutdown,
+                    )
+                    .await
                 } else {
                     ahand_client::run(
                         cfg,
@@ -392,83 +596,185 @@ async fn main() -> anyhow::Result<()> {
                         store_opt,
                         session_mgr,
                         approval_mgr,
+                        policy,
                         approval_broadcast_tx,
                         browser_mgr,
                         file_mgr,
                         app_tools,
+                        Arc::default(),
+                        client_shutdown_rx.clone(),
                     )
                     .await
                 }
             }
             ConnectionMode::OpenClawGateway => {
                 let device_id = cfg.device_id();
+                tokio::spawn(session::forward_session_events(
+                    session_events_rx,
+                    device_id.clone(),
+                    approval_broadcast_tx.clone(),
+                ));
+                tokio::spawn(approval::sweep_expired_approvals(
+                    Arc::clone(&approval_mgr),
+                    device_id.clone(),
+                    approval_broadcast_tx.clone(),
+                ));
                 let oc_config = cfg.openclaw_config();
-                let host = oc_config.gateway_host.as_deref().unwrap_or("127.0.0.1");
-                let port = oc_config.gateway_port.unwrap_or(18789);
+                // Several gateways each get their own pairing file; a lone
+                // top-level gateway keeps the original one.
+                let namespaced_pairing = !oc_config.gateways.is_empty();
+                let gateway_configs = oc_config.gateway_configs();
 
                 info!(
-                    gateway_host = %host,
-                    gateway_port = port,
-                    node_id = ?oc_config.node_id,
-                    display_name = ?oc_config.display_name,
+                    gateways = gateway_configs.len(),
                     max_concurrent_jobs = max_jobs,
                     debug_ipc,
                     "ahandd starting in openclaw-gateway mode"
                 );
 
-                let client = openclaw::OpenClawClient::new(
-                    oc_config,
-                    Arc::clone(&registry),
-                    Arc::clone(&session_mgr),
-                    Arc::clone(&approval_mgr),
-                    approval_broadcast_tx.clone(),
-                    store_opt.clone(),
-                    Arc::clone(&browser_mgr),
-                );
+                let clients: Vec<_> = gateway_configs
+                    .into_iter()
+                    .map(|gateway| {
+                        let host = gateway
+                            .gateway_host
+                            .clone()
+                            .unwrap_or_else(|| "127.0.0.1".to_string());
+                        let port = gateway.gateway_port.unwrap_or(18789);
+                        info!(
+                            gateway_host = %host,
+                            gateway_port = port,
+                            node_id = ?gateway.node_id,
+                            display_name = ?gateway.display_name,
+                            "OpenClaw gateway configured"
+                        );
+                        let mut client = openclaw::OpenClawClient::new(
+                            gateway,
+                            cfg.tls.clone(),
+                            cfg.proxy_url.clone(),
+                            Arc::clone(&registry),
+                            Arc::clone(&session_mgr),
+                            Arc::clone(&approval_mgr),
+                            approval_broadcast_tx.clone(),
+                            store_opt.clone(),
+                            Arc::clone(&browser_mgr),
+                            Arc::clone(&policy),
+                        )
+                        .with_desktop_notifications(cfg.approval_config().desktop_notifications)
+                        .with_monitor(ipc_health.add_gateway());
+                        if namespaced_pairing {
+                            client = client.with_pairing_path(
+                                openclaw::pairing::gateway_pairing_path(&host, port),
+                            );
+                        }
+                        let span = tracing::info_span!("gateway", host = %host, port);
+                        (client, span)
+                    })
+                    .collect();
+                let run_clients = async {
+                    futures_util::future::try_join_all(
+                        clients
+                            .iter()
+                            .map(|(client, span)| client.run().instrument(span.clone())),
+                    )
+                    .await
+                    .map(|_| ())
+                };
 
                 if debug_ipc {
                     let ipc_handle = tokio::spawn(ipc::serve_ipc(
-                        ipc_socket_path,
-                        ipc_socket_mode,
+                        ipc_binds,
+                        ipc_access.clone(),
                         Arc::clone(&registry),
                         store_opt.clone(),
                         Arc::clone(&session_mgr),
                         Arc::clone(&approval_mgr),
+                        Arc::clone(&policy),
                         approval_broadcast_tx.clone(),
                         device_id.clone(),
                         Arc::clone(&browser_mgr),
                         Arc::clone(&file_mgr),
+                        Arc::clone(&ipc_health),
+                        ipc_shutdown.clone(),
                     ));
 
-                    tokio::select! {
-                        r = client.run() => r,
-                        r = ipc_handle => {
-                            r??;
-                            Ok(())
-                        }
-                    }
+                    run_with_ipc(run_clients, ipc_handle, &ipc_shutdown).await
                 } else {
-                    client.run().await
+                    run_clients.await
                 }
             }
         }
     };
 
     // Race main event loop against shutdown signals.
+    tokio::pin!(main_future);
     let result = tokio::select! {
-        r = main_future => r,
+        r = &mut main_future => r,
         sig = shutdown => {
             info!(signal = sig, "received shutdown signal, shutting down");
+            let stuck = shutdown_registry.shutdown(SHUTDOWN_JOB_GRACE).await;
+            if !stuck.is_empty() {
+                tracing::warn!(job_ids = ?stuck, "jobs force-killed at shutdown");
+            }
+            let _ = client_shutdown_tx.send(true);
+            ipc_shutdown.cancel();
+            if tokio::time::timeout(SHUTDOWN_CLOSE_GRACE, &mut main_future)
+                .await
+                .is_err()
+            {
+                tracing::warn!("connection did not close within shutdown grace period");
+            }
+            if let Some(store) = &shutdown_store {
+                store.flush().await;
+            }
+            shutdown_sessions.flush().await;
             Ok(())
         }
     };
 
+    #[cfg(unix)]
+    if let Some(endpoint) = &shutdown_ipc_socket {
+        let _ = std::fs::remove_file(endpoint.as_path());
+    }
+    #[cfg(not(unix))]
+    let _ = shutdown_ipc_socket;
+
     // Clean up PID file on exit.
     cleanup_pid_file(&pid_path);
 
+    if let Err(err) = &result
+        && err.downcast_ref::<reconnect::GaveUp>().is_some()
+    {
+        tracing::error!(error = %err, "exiting");
+        std::process::exit(reconnect::EXIT_RECONNECT_GAVE_UP);
+    }
     result
 }
 
+/// Run the connection `client` next to the IPC server until both stop. If
+/// the client stops first the IPC server is shut down too, so IPC clients
+/// are told and the socket is removed; an IPC server error ends both.
+async fn run_with_ipc(
+    client: impl std::future::Future<Output = anyhow::Result<()>>,
+    mut ipc_handle: tokio::task::JoinHandle<anyhow::Result<()>>,
+    ipc_shutdown: &tokio_util::sync::CancellationToken,
+) -> anyhow::Result<()> {
+    tokio::pin!(client);
+    tokio::select! {
+        r = &mut client => {
+            ipc_shutdown.cancel();
+            if let Ok(Err(e)) = (&mut ipc_handle).await {
+                tracing::warn!(error = %e, "IPC server stopped with an error");
+            }
+            r
+        }
+        r = &mut ipc_handle => {
+            r??;
+            // The IPC server only returns cleanly once it has been shut down.
+            client.await
+        }
+    }
+}
+
 async fn run_plugin_command(command: &PluginCmd) -> anyhow::Result<()> {
     match command {
         PluginCmd::Doctor { plugin } => {
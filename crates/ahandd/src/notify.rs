@@ -0,0 +1,135 @@
+//! Desktop notifications for incoming approval requests.
+//!
+//! Enabled with `approval.desktop_notifications = true`. Uses `osascript` on
+//! macOS and `notify-send` on Linux; when the helper binary is missing (or
+//! the platform has none) notifications are silently skipped.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use ahand_protocol::{ApprovalRequest, SessionMode};
+use tracing::debug;
+
+use crate::session::SessionManager;
+
+/// Longest argument string shown in a notification body.
+const MAX_ARGS_CHARS: usize = 80;
+
+/// A notifier helper that hasn't exited by now is killed.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fires a desktop notification for each approval request.
+pub struct DesktopNotifier {
+    session_mgr: Arc<SessionManager>,
+}
+
+impl DesktopNotifier {
+    pub fn new(session_mgr: Arc<SessionManager>) -> Self {
+        Self { session_mgr }
+    }
+
+    /// Notify about `req` in the background. Callers in AutoAccept mode are
+    /// not notified. Never blocks the approval path.
+    pub fn notify(self: &Arc<Self>, req: &ApprovalRequest) {
+        let this = Arc::clone(self);
+        let req = req.clone();
+        tokio::spawn(async move {
+            let state = this.session_mgr.get_session_state(&req.caller_uid).await;
+            if state.mode == SessionMode::AutoAccept as i32 {
+                return;
+            }
+            let (title, body) = notification_text(&req);
+            show(&title, &body).await;
+        });
+    }
+}
+
+/// Title and body of the notification for `req`.
+fn notification_text(req: &ApprovalRequest) -> (String, String) {
+    let title = format!("aHand: approval needed for {}", req.tool);
+    let mut args = req.args.join(" ");
+    if args.chars().count() > MAX_ARGS_CHARS {
+        args = args.chars().take(MAX_ARGS_CHARS).collect::<String>() + "…";
+    }
+    let body = if args.is_empty() {
+        format!("job {}", req.job_id)
+    } else {
+        format!("{args}\njob {}", req.job_id)
+    };
+    (title, body)
+}
+
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Option<tokio::process::Command> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    );
+    let mut cmd = tokio::process::Command::new("osascript");
+    cmd.arg("-e").arg(script);
+    Some(cmd)
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn command(title: &str, body: &str) -> Option<tokio::process::Command> {
+    let mut cmd = tokio::process::Command::new("notify-send");
+    cmd.arg("--app-name=aHand").arg("--").arg(title).arg(body);
+    Some(cmd)
+}
+
+#[cfg(not(unix))]
+fn command(_title: &str, _body: &str) -> Option<tokio::process::Command> {
+    None
+}
+
+/// Show a desktop notification, waiting for the helper for at most
+/// `NOTIFY_TIMEOUT`.
+pub async fn show(title: &str, body: &str) {
+    let Some(mut cmd) = command(title, body) else {
+        return;
+    };
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            debug!(error = %e, "desktop notifier unavailable");
+            return;
+        }
+    };
+    if tokio::time::timeout(NOTIFY_TIMEOUT, child.wait())
+        .await
+        .is_err()
+    {
+        debug!("desktop notifier timed out");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notification_text_truncates_args_and_names_the_job() {
+        let req = ApprovalRequest {
+            job_id: "job-1".to_string(),
+            tool: "bash".to_string(),
+            args: vec!["-c".to_string(), "x".repeat(200)],
+            ..Default::default()
+        };
+        let (title, body) = notification_text(&req);
+        assert_eq!(title, "aHand: approval needed for bash");
+        let (args, job) = body.split_once('\n').unwrap();
+        assert_eq!(args.chars().count(), MAX_ARGS_CHARS + 1);
+        assert!(args.ends_with('…'));
+        assert_eq!(job, "job job-1");
+    }
+}
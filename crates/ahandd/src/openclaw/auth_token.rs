@@ -0,0 +1,153 @@
+//! Where the gateway auth token comes from.
+//!
+//! `auth_token_command` (a credential helper whose stdout is the token)
+//! wins over `auth_token_file`, which wins over `auth_token`. The command
+//! and the file are consulted again on every connect attempt, so a token
+//! the gateway rotated is picked up without restarting the daemon.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use tokio::process::Command;
+
+use crate::config::OpenClawConfig;
+
+/// A credential helper that hasn't answered by now is killed.
+const TOKEN_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenSource {
+    None,
+    Static(String),
+    File(PathBuf),
+    Command(String),
+}
+
+impl TokenSource {
+    pub fn from_config(config: &OpenClawConfig) -> Self {
+        if let Some(command) = &config.auth_token_command {
+            Self::Command(command.clone())
+        } else if let Some(path) = &config.auth_token_file {
+            Self::File(PathBuf::from(path))
+        } else if let Some(token) = &config.auth_token {
+            Self::Static(token.clone())
+        } else {
+            Self::None
+        }
+    }
+
+    /// The token to connect with now. A file or command that yields no
+    /// token is an error rather than a connect without one.
+    pub async fn token(&self) -> anyhow::Result<Option<String>> {
+        let token = match self {
+            Self::None => return Ok(None),
+            Self::Static(token) => return Ok(Some(token.clone())),
+            Self::File(path) => {
+                let content = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                let token = content.trim().to_string();
+                if token.is_empty() {
+                    bail!("auth token file {} is empty", path.display());
+                }
+                token
+            }
+            Self::Command(command) => run_token_command(command).await?,
+        };
+        Ok(Some(token))
+    }
+}
+
+/// Run `command` through the shell and take the first line it prints.
+async fn run_token_command(command: &str) -> anyhow::Result<String> {
+    let shell = ahand_platform::shell::env_shell()
+        .unwrap_or_else(|| ahand_platform::shell::default_shell().path);
+    let child = Command::new(shell)
+        .arg(ahand_platform::shell::shell_c_flag())
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to run auth_token_command")?;
+    let output = tokio::time::timeout(TOKEN_COMMAND_TIMEOUT, child.wait_with_output())
+        .await
+        .with_context(|| {
+            format!(
+                "auth_token_command timed out after {}s",
+                TOKEN_COMMAND_TIMEOUT.as_secs()
+            )
+        })?
+        .context("failed to run auth_token_command")?;
+    if !output.status.success() {
+        bail!(
+            "auth_token_command failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().next().map(str::trim) {
+        Some(token) if !token.is_empty() => Ok(token.to_string()),
+        _ => bail!("auth_token_command printed no token"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_command_wins_over_the_file_and_the_file_over_the_token() {
+        let mut config = OpenClawConfig {
+            auth_token: Some("static".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            TokenSource::from_config(&config),
+            TokenSource::Static("static".into())
+        );
+        config.auth_token_file = Some("/run/token".into());
+        assert_eq!(
+            TokenSource::from_config(&config),
+            TokenSource::File("/run/token".into())
+        );
+        config.auth_token_command = Some("pass show gateway".into());
+        assert_eq!(
+            TokenSource::from_config(&config),
+            TokenSource::Command("pass show gateway".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn token_files_are_reread_on_every_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let source = TokenSource::File(path.clone());
+
+        std::fs::write(&path, "first\n").unwrap();
+        assert_eq!(source.token().await.unwrap().as_deref(), Some("first"));
+        std::fs::write(&path, "rotated\n").unwrap();
+        assert_eq!(source.token().await.unwrap().as_deref(), Some("rotated"));
+
+        std::fs::write(&path, "\n").unwrap();
+        assert!(source.token().await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn token_commands_supply_the_first_line_of_stdout() {
+        let source = TokenSource::Command("printf 'from-helper\\nignored\\n'".into());
+        assert_eq!(
+            source.token().await.unwrap().as_deref(),
+            Some("from-helper")
+        );
+
+        let failing = TokenSource::Command("echo locked >&2; exit 3".into());
+        let err = failing.token().await.unwrap_err().to_string();
+        assert!(err.contains("locked"), "{err}");
+    }
+}
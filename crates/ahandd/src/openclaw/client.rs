@@ -2,70 +2,300 @@
 //!
 //! Manages the connection to an OpenClaw Gateway and handles message routing.
 
-use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
-use futures_util::{SinkExt, StreamExt};
+use anyhow::Context;
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, SinkExt, StreamExt};
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::{debug, error, info, warn};
 
 use crate::approval::ApprovalManager;
 use crate::browser::BrowserManager;
-use crate::config::OpenClawConfig;
+use crate::config::{OpenClawConfig, TlsConfig};
+use crate::connection::ConnectionMonitor;
+use crate::policy::PolicyChecker;
+use crate::proxy::Proxy;
+use crate::reconnect::Backoff;
 use crate::registry::JobRegistry;
 use crate::session::SessionManager;
 use crate::store::RunStore;
+use crate::tls::{GatewayTrust, ObservedFingerprint};
 
+use super::auth_token::TokenSource;
 use super::device_identity::{DeviceIdentity, build_auth_payload, default_identity_path};
-use super::handler::OpenClawHandler;
+use super::handler::{ExecEvent, OpenClawHandler};
+use super::host_info::HostInfoProbe;
 use super::pairing::{
-    GatewayInfo, default_pairing_path, generate_node_id, load_pairing_state, save_pairing_state,
+    GatewayInfo, PairingState, default_pairing_path, generate_node_id, load_pairing_state,
+    save_pairing_state,
 };
 use super::protocol::{
     AuthParams, ClientInfo, ConnectChallengePayload, ConnectParams, DeviceParams, GatewayFrame,
-    HelloOk, NodeEvent, NodeInvokeRequest, PROTOCOL_VERSION, RequestFrame, ResponseFrame,
+    HelloOk, NodeEvent, NodeInvokeRequest, NodeStatus, PROTOCOL_VERSION, RequestFrame,
+    ResponseFrame,
 };
+use super::requests::{REQUEST_TIMEOUT, RequestTracker};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The role this client connects as; it asks for no scopes.
+const NODE_ROLE: &str = "node";
+
+/// Ping interval until the gateway's HelloOk advertises `tickIntervalMs`.
+const DEFAULT_TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often exec-approvals.json is checked for changes made outside of
+/// system.execApprovals.set.
+const EXEC_APPROVALS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `node.status` interval when `status_interval_secs` is unset.
+const DEFAULT_STATUS_INTERVAL_SECS: u64 = 60;
+
+/// An authenticated session that lasted this long resets the reconnect
+/// backoff.
+const HEALTHY_SESSION: Duration = Duration::from_secs(30);
+
+const DEFAULT_RECONNECT_MAX_SECS: u64 = 30;
+const DEFAULT_PAIRING_POLL_SECS: u64 = 15;
+
+/// After this many connects in a row rejected for auth, retrying the usual
+/// way won't help: reconnects slow to [`AUTH_FAILED_RETRY`] and the user is
+/// told to fix the token.
+const AUTH_FAILURE_LIMIT: u32 = 3;
+const AUTH_FAILED_RETRY: Duration = Duration::from_secs(300);
+
+/// Why a gateway connection ended.
+#[derive(Debug)]
+enum Disconnect {
+    StreamEnded,
+    ReadError(String),
+    ClosedByGateway,
+    PairingApproved,
+    /// The connect request failed, was rejected, or went unanswered.
+    ConnectFailed(String),
+    /// Nothing arrived for this long.
+    Idle(Duration),
+}
+
+impl fmt::Display for Disconnect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StreamEnded => f.write_str("websocket stream ended"),
+            Self::ReadError(e) => write!(f, "websocket error: {e}"),
+            Self::ClosedByGateway => f.write_str("gateway closed the connection"),
+            Self::PairingApproved => f.write_str("pairing approved"),
+            Self::ConnectFailed(e) => write!(f, "connect failed: {e}"),
+            Self::Idle(after) => write!(f, "no frames from gateway for {}s", after.as_secs()),
+        }
+    }
+}
+
+/// How one gateway connection went.
+#[derive(Debug)]
+struct Session {
+    reason: Disconnect,
+    /// How long the gateway had accepted our connect; `None` if it never
+    /// did.
+    connected_for: Option<Duration>,
+    /// The gateway answered connect with NOT_PAIRED and held the
+    /// connection for pairing approval.
+    awaiting_pairing: bool,
+    /// The gateway rejected our connect for its credentials.
+    auth_failed: bool,
+}
+
+/// Paces reconnects to the gateway. Failures back off exponentially, and
+/// only an authenticated session lasting [`HEALTHY_SESSION`] resets that,
+/// so a gateway that closes us right after the handshake isn't redialled
+/// every second. A node waiting for pairing approval polls at a fixed,
+/// slower interval instead, and reconnects at once when approved. After
+/// [`AUTH_FAILURE_LIMIT`] auth rejections in a row it waits
+/// [`AUTH_FAILED_RETRY`] between attempts until a connect succeeds.
+struct ReconnectPolicy {
+    backoff: Backoff,
+    pairing_poll: Duration,
+    /// Connects rejected for auth since the last accepted one.
+    auth_failures: u32,
+}
+
+impl ReconnectPolicy {
+    fn from_config(config: &OpenClawConfig) -> Self {
+        let max_secs = config
+            .reconnect_max_secs
+            .unwrap_or(DEFAULT_RECONNECT_MAX_SECS);
+        let pairing_poll_secs = config
+            .pairing_poll_secs
+            .unwrap_or(DEFAULT_PAIRING_POLL_SECS);
+        Self {
+            backoff: Backoff::new(Duration::from_secs(1), Duration::from_secs(max_secs), None)
+                .with_healthy_after(HEALTHY_SESSION),
+            pairing_poll: Duration::from_secs(pairing_poll_secs.max(1)),
+            auth_failures: 0,
+        }
+    }
+
+    /// The delay before reconnecting after `session`, or after a connection
+    /// that failed before there was one.
+    fn next_delay(&mut self, session: Option<&Session>) -> Duration {
+        if let Some(session) = session {
+            if session.auth_failed {
+                self.auth_failures += 1;
+            } else if session.connected_for.is_some() {
+                self.auth_failures = 0;
+            }
+        }
+        if self.auth_failures >= AUTH_FAILURE_LIMIT {
+            return AUTH_FAILED_RETRY;
+        }
+        let connected_for = match session {
+            Some(Session {
+                reason: Disconnect::PairingApproved,
+                ..
+            }) => {
+                self.backoff.reset();
+                return Duration::ZERO;
+            }
+            Some(session) if session.awaiting_pairing => return self.pairing_poll,
+            Some(session) => session.connected_for.unwrap_or_default(),
+            None => Duration::ZERO,
+        };
+        self.backoff
+            .next_delay(connected_for)
+            .expect("gateway reconnects have no attempt limit")
+    }
+}
+
+/// Liveness of one gateway connection. We ping every tick interval, and
+/// any inbound frame (tick, pong, event, response) counts as a sign of
+/// life; a connection silent for two intervals is treated as dead, which
+/// catches TCP paths that died without an error.
+struct Liveness {
+    interval: Duration,
+    last_inbound: Instant,
+    next_ping: Instant,
+}
+
+impl Liveness {
+    fn new(now: Instant) -> Self {
+        Self {
+            interval: DEFAULT_TICK_INTERVAL,
+            last_inbound: now,
+            next_ping: now + DEFAULT_TICK_INTERVAL,
+        }
+    }
+
+    /// Adopt the tick interval from the gateway's HelloOk.
+    fn set_interval(&mut self, interval: Duration, now: Instant) {
+        self.interval = interval;
+        self.next_ping = now + interval;
+    }
+
+    fn saw_inbound(&mut self, now: Instant) {
+        self.last_inbound = now;
+    }
+
+    /// When the connection counts as dead if nothing else arrives.
+    fn deadline(&self) -> Instant {
+        self.last_inbound + self.interval.saturating_mul(2)
+    }
+
+    fn pinged(&mut self, now: Instant) {
+        self.next_ping = now + self.interval;
+    }
+}
+
 /// OpenClaw Gateway client
 pub struct OpenClawClient {
     config: OpenClawConfig,
+    /// CA and client certificate from `[tls]`; the pin comes from
+    /// `gateway_tls_fingerprint` or the pairing file instead.
+    tls: Option<TlsConfig>,
+    /// `proxy_url` from the config; the environment is consulted when unset.
+    proxy_url: Option<String>,
     registry: Arc<JobRegistry>,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
     approval_broadcast_tx: tokio::sync::broadcast::Sender<ahand_protocol::Envelope>,
     store: Option<Arc<RunStore>>,
     browser_mgr: Arc<BrowserManager>,
+    policy: Arc<PolicyChecker>,
+    host_info: Arc<HostInfoProbe>,
+    token_source: TokenSource,
+    /// Tell the user on the desktop when the gateway keeps rejecting our
+    /// credentials.
+    desktop_notifications: bool,
+    /// Node ID, token and pinned certificate for this gateway.
+    pairing_path: PathBuf,
+    /// The device keypair, shared by every gateway this daemon pairs with.
+    identity_path: PathBuf,
+    /// Where this gateway connection stands, for `DaemonStatus.gateways`.
+    monitor: Arc<ConnectionMonitor>,
 }
 
 impl OpenClawClient {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: OpenClawConfig,
+        tls: Option<TlsConfig>,
+        proxy_url: Option<String>,
         registry: Arc<JobRegistry>,
         session_mgr: Arc<SessionManager>,
         approval_mgr: Arc<ApprovalManager>,
         approval_broadcast_tx: tokio::sync::broadcast::Sender<ahand_protocol::Envelope>,
         store: Option<Arc<RunStore>>,
         browser_mgr: Arc<BrowserManager>,
+        policy: Arc<PolicyChecker>,
     ) -> Self {
         Self {
+            token_source: TokenSource::from_config(&config),
             config,
+            tls,
+            proxy_url,
             registry,
             session_mgr,
             approval_mgr,
             approval_broadcast_tx,
             store,
             browser_mgr,
+            policy,
+            host_info: Arc::new(HostInfoProbe::new(std::time::Instant::now())),
+            desktop_notifications: false,
+            pairing_path: default_pairing_path(),
+            identity_path: default_identity_path(),
+            monitor: Arc::default(),
         }
     }
 
+    /// Raise a desktop notification (see [`crate::notify`]) when the
+    /// gateway keeps rejecting our credentials.
+    pub fn with_desktop_notifications(mut self, enabled: bool) -> Self {
+        self.desktop_notifications = enabled;
+        self
+    }
+
+    /// Keep pairing state in `path` rather than the default pairing file;
+    /// each of several gateways needs its own.
+    pub fn with_pairing_path(mut self, path: PathBuf) -> Self {
+        self.pairing_path = path;
+        self
+    }
+
+    /// Publish this connection's state to `monitor`.
+    pub fn with_monitor(mut self, monitor: Arc<ConnectionMonitor>) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
     /// Run the client with automatic reconnection
     pub async fn run(&self) -> anyhow::Result<()> {
-        let mut backoff = 1u64;
+        let mut policy = ReconnectPolicy::from_config(&self.config);
 
         loop {
             let host = self.config.gateway_host.as_deref().unwrap_or("127.0.0.1");
@@ -76,34 +306,97 @@ impl OpenClawClient {
                 port = port,
                 "connecting to OpenClaw Gateway"
             );
+            self.monitor.connecting(&self.build_url());
 
-            match self.connect().await {
-                Ok(()) => {
-                    info!("connection closed normally");
-                    backoff = 1;
+            let (delay, reason) = match self.connect().await {
+                Ok(session) => {
+                    info!(reason = %session.reason, "connection closed");
+                    let delay = policy.next_delay(Some(&session));
+                    if session.auth_failed && policy.auth_failures == AUTH_FAILURE_LIMIT {
+                        self.report_auth_failing(delay).await;
+                    }
+                    (delay, session.reason.to_string())
                 }
                 Err(e) => {
                     warn!(error = %e, "connection failed");
+                    (policy.next_delay(None), e.to_string())
                 }
-            }
+            };
 
-            info!(backoff_secs = backoff, "reconnecting");
-            tokio::time::sleep(Duration::from_secs(backoff)).await;
-            backoff = (backoff * 2).min(30);
+            info!(delay_ms = delay.as_millis() as u64, %reason, "reconnecting");
+            self.monitor.backoff(delay);
+            tokio::time::sleep(delay).await;
         }
     }
 
-    /// Establish and maintain a single connection
-    async fn connect(&self) -> anyhow::Result<()> {
+    /// Tell the user, once per run of auth failures, that only new
+    /// credentials will get the node back.
+    async fn report_auth_failing(&self, retry: Duration) {
+        let fix = match &self.token_source {
+            TokenSource::Command(_) => {
+                "check that openclaw.auth_token_command prints a current token"
+            }
+            TokenSource::File(_) => "write a current token to openclaw.auth_token_file",
+            TokenSource::Static(_) | TokenSource::None => {
+                "update openclaw.auth_token, or set auth_token_file or auth_token_command so rotated tokens are picked up"
+            }
+        };
+        error!(
+            failures = AUTH_FAILURE_LIMIT,
+            retry_secs = retry.as_secs(),
+            "OpenClaw gateway keeps rejecting this node's credentials; {fix}"
+        );
+        if self.desktop_notifications {
+            crate::notify::show(
+                "aHand: OpenClaw gateway rejected credentials",
+                &format!("The gateway token looks out of date; {fix}."),
+            )
+            .await;
+        }
+    }
+
+    /// Establish and maintain a single connection, returning how it went.
+    async fn connect(&self) -> anyhow::Result<Session> {
+        let auth_token = self
+            .token_source
+            .token()
+            .await
+            .context("failed to get the gateway auth token")?;
+
+        // Load or create pairing state
+        let mut pairing = load_pairing_state(&self.pairing_path)?.unwrap_or_default();
+        let paired_pin = pinned_fingerprint(&self.config, &pairing);
+
         let url = self.build_url();
-        let (ws, _response) = tokio_tungstenite::connect_async(&url).await?;
+        let proxy = Proxy::for_url(&url, self.proxy_url.as_deref())?;
+        if let Some(proxy) = &proxy {
+            info!(%proxy, "connecting to Gateway through proxy");
+        }
+        let tcp = crate::proxy::connect(&url, proxy.as_ref()).await?;
+        let (connector, observed) = match self.tls_connector(paired_pin.clone())? {
+            Some((connector, observed)) => (Some(connector), Some(observed)),
+            None => (None, None),
+        };
+        let (ws, _response) =
+            tokio_tungstenite::client_async_tls_with_config(&url, tcp, None, connector).await?;
         let (mut sink, mut stream) = ws.split();
 
         info!("connected to Gateway");
 
-        // Load or create pairing state
-        let pairing_path = default_pairing_path();
-        let mut pairing = load_pairing_state(&pairing_path)?.unwrap_or_default();
+        let mut tls_fingerprint = paired_pin;
+        if tls_fingerprint.is_none()
+            && let Some(observed) = observed.and_then(|o| o.get())
+        {
+            if self.config.tls_fingerprint_tofu.unwrap_or(false) {
+                info!(fingerprint = %observed, "pinning gateway certificate on first use");
+                tls_fingerprint = Some(observed);
+            } else {
+                info!(
+                    fingerprint = %observed,
+                    "gateway certificate is not pinned; set openclaw.gateway_tls_fingerprint to pin it"
+                );
+            }
+        }
 
         // Ensure we have a node ID
         if pairing.node_id.is_empty() {
@@ -124,31 +417,38 @@ impl OpenClawClient {
                 .unwrap_or_else(|| "127.0.0.1".to_string()),
             port: self.config.gateway_port.unwrap_or(18789),
             tls: self.config.gateway_tls.unwrap_or(false),
-            tls_fingerprint: self.config.gateway_tls_fingerprint.clone(),
+            tls_fingerprint,
         });
 
         // Save pairing state
-        save_pairing_state(&pairing_path, &pairing)?;
+        save_pairing_state(&self.pairing_path, &pairing)?;
 
         let node_id = pairing.node_id.clone();
-        let display_name = pairing.display_name.clone();
+        let display_name = pairing.display_name.clone().or_else(host_name);
 
         // Load or create device identity
-        let identity_path = default_identity_path();
-        let device_identity = DeviceIdentity::load_or_create(&identity_path)?;
+        let device_identity = DeviceIdentity::load_or_create(&self.identity_path)?;
         info!(device_id = %device_identity.device_id, "loaded device identity");
 
         // Create handler - use device_id as node_id since Gateway identifies nodes by device ID
-        let handler = OpenClawHandler::new(
+        let mut handler = OpenClawHandler::new(
             device_identity.device_id.clone(),
             Arc::clone(&self.registry),
             Arc::clone(&self.session_mgr),
             Arc::clone(&self.approval_mgr),
             self.approval_broadcast_tx.clone(),
-            self.store.clone(),
+            self.store
+                .clone()
+                .filter(|_| self.config.persist_runs.unwrap_or(true)),
             self.config.exec_approvals_path.as_ref().map(PathBuf::from),
             Arc::clone(&self.browser_mgr),
-        );
+        )
+        .with_policy(Arc::clone(&self.policy))
+        .with_host_info(Arc::clone(&self.host_info));
+        if let Some(secs) = self.config.idempotency_ttl_secs {
+            handler = handler.with_idempotency_ttl(Duration::from_secs(secs));
+        }
+        let handler = Arc::new(handler);
 
         // Create channel for sending responses
         let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
@@ -163,48 +463,147 @@ impl OpenClawClient {
             }
         });
 
-        // Pending requests
-        let mut pending: HashMap<String, tokio::sync::oneshot::Sender<ResponseFrame>> =
-            HashMap::new();
+        let requests = RequestTracker::new(tx.clone(), REQUEST_TIMEOUT);
         let mut connect_nonce: Option<String> = None;
         let mut connect_sent = false;
-        let mut connected = false;
-        let mut pairing_requested = false;
+        let mut connect_response: Option<BoxFuture<'static, anyhow::Result<ResponseFrame>>> = None;
+        let mut connected_at: Option<Instant> = None;
+        let mut awaiting_pairing = false;
+        let mut auth_failed = false;
 
         // Set up connect timeout
         let connect_timeout = tokio::time::sleep(Duration::from_millis(750));
         tokio::pin!(connect_timeout);
 
+        let mut liveness = Liveness::new(Instant::now());
+        let mut exec_approvals_poll = tokio::time::interval(EXEC_APPROVALS_POLL_INTERVAL);
+        exec_approvals_poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick is due at once, so a status goes out on connect.
+        let mut status_tick = self.status_interval().map(|period| {
+            let mut tick = tokio::time::interval(period);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tick
+        });
+        let mut status_sent: Option<tokio::task::JoinHandle<()>> = None;
+
         // Process incoming messages
-        loop {
+        let reason = loop {
             tokio::select! {
+                _ = tokio::time::sleep_until(liveness.next_ping) => {
+                    let _ = tx.send(Message::Ping(Vec::new()));
+                    liveness.pinged(Instant::now());
+                }
+
+                _ = tokio::time::sleep_until(liveness.deadline()) => {
+                    let idle = liveness.interval.saturating_mul(2);
+                    warn!(
+                        timeout_secs = idle.as_secs(),
+                        "no frames from gateway (no tick, no pong) - closing dead connection",
+                    );
+                    break Disconnect::Idle(idle);
+                }
+
+                _ = exec_approvals_poll.tick(), if connected_at.is_some() => {
+                    if let Some(changed) = handler.poll_exec_approvals() {
+                        info!(hash = %changed.hash, "exec approvals changed on disk");
+                        send_node_event(&requests, "execApprovals.changed", &changed)?;
+                    }
+                }
+
+                _ = async { status_tick.as_mut().expect("guarded by is_some").tick().await },
+                    if connected_at.is_some() && status_tick.is_some() =>
+                {
+                    // Skip rather than pile up statuses behind a stalled one.
+                    if status_sent.as_ref().is_some_and(|sent| !sent.is_finished()) {
+                        debug!("previous node status still unanswered, skipping this one");
+                    } else {
+                        let status = self.node_status().await;
+                        status_sent = Some(spawn_node_event(&requests, "node.status", &status)?);
+                    }
+                }
+
                 // Connect timeout - send connect without challenge
                 _ = &mut connect_timeout, if !connect_sent => {
                     debug!("connect timeout, sending connect without nonce");
-                    self.send_connect(
-                        &tx,
-                        &node_id,
-                        &display_name,
-                        connect_nonce.as_deref(),
-                        &device_identity,
-                        &mut pending,
-                    )?;
+                    connect_response = Some(
+                        self.send_connect(
+                            &requests,
+                            &node_id,
+                            &display_name,
+                            auth_token.as_deref(),
+                            connect_nonce.as_deref(),
+                            &device_identity,
+                        )?
+                        .boxed(),
+                    );
                     connect_sent = true;
                 }
 
+                response = async { connect_response.as_mut().expect("guarded by is_some").await },
+                    if connect_response.is_some() =>
+                {
+                    connect_response = None;
+                    let res = match response {
+                        Ok(res) => res,
+                        Err(e) => break Disconnect::ConnectFailed(e.to_string()),
+                    };
+                    if res.ok {
+                        let hello = res
+                            .payload
+                            .and_then(|payload| serde_json::from_value::<HelloOk>(payload).ok());
+                        let Some(hello) = hello else {
+                            break Disconnect::ConnectFailed("no HelloOk in connect response".into());
+                        };
+                        info!("connected to Gateway successfully");
+                        if let Some(ms) = hello.policy.tick_interval_ms.filter(|&ms| ms > 0) {
+                            liveness.set_interval(Duration::from_millis(ms), Instant::now());
+                        }
+                        connected_at = Some(Instant::now());
+                        self.monitor.connected(&url, true);
+                    } else if let Some(err) = res.error {
+                        // Handle NOT_PAIRED - Gateway automatically creates pairing request,
+                        // and the connection stays open until node.pair.resolved
+                        if err.code == "NOT_PAIRED" {
+                            awaiting_pairing = true;
+                            // Extract requestId from error details if available
+                            let request_id = err.details.as_ref()
+                                .and_then(|d| d.get("requestId"))
+                                .and_then(|v| v.as_str());
+
+                            if let Some(req_id) = request_id {
+                                warn!(
+                                    request_id = %req_id,
+                                    "device not paired - approve with: openclaw nodes approve {}",
+                                    req_id
+                                );
+                            } else {
+                                warn!("device not paired - check pending requests with: openclaw nodes pending");
+                            }
+                        } else {
+                            error!(code = %err.code, message = %err.message, "connect rejected");
+                            auth_failed = is_auth_error(&err.code);
+                            break Disconnect::ConnectFailed(format!("{}: {}", err.code, err.message));
+                        }
+                    } else {
+                        break Disconnect::ConnectFailed("connect rejected without an error".into());
+                    }
+                }
+
                 // Incoming message
                 msg_result = stream.next() => {
                     let msg = match msg_result {
                         Some(Ok(m)) => m,
                         Some(Err(e)) => {
                             error!(error = %e, "websocket error");
-                            break;
+                            break Disconnect::ReadError(e.to_string());
                         }
                         None => {
                             info!("websocket stream ended");
-                            break;
+                            break Disconnect::StreamEnded;
                         }
                     };
+                    liveness.saw_inbound(Instant::now());
+                    self.monitor.saw_inbound();
 
                     match msg {
                         Message::Text(text) => {
@@ -220,43 +619,33 @@ impl OpenClawClient {
                                                 && let Some(nonce) = challenge.nonce {
                                                     connect_nonce = Some(nonce);
                                                     debug!("received connect challenge");
-                                                    self.send_connect(
-                                                        &tx,
-                                                        &node_id,
-                                                        &display_name,
-                                                        connect_nonce.as_deref(),
-                                                        &device_identity,
-                                                        &mut pending,
-                                                    )?;
+                                                    connect_response = Some(
+                                                        self.send_connect(
+                                                            &requests,
+                                                            &node_id,
+                                                            &display_name,
+                                                            auth_token.as_deref(),
+                                                            connect_nonce.as_deref(),
+                                                            &device_identity,
+                                                        )?
+                                                        .boxed(),
+                                                    );
                                                     connect_sent = true;
                                                 }
                                         }
                                         // Handle node.invoke.request
-                                        else if evt.event == "node.invoke.request" && connected {
+                                        else if evt.event == "node.invoke.request" && connected_at.is_some() {
                                             if let Ok(invoke) = serde_json::from_value::<NodeInvokeRequest>(evt.payload) {
-                                                let (result, exec_event) = handler.handle_invoke(invoke).await;
-
-                                                // Send exec event if present
-                                                if let Some(exec_event) = exec_event {
-                                                    let event = NodeEvent {
-                                                        event: exec_event.kind.as_str().to_string(),
-                                                        payload_json: serde_json::to_string(&exec_event.payload).ok(),
-                                                    };
-                                                    let req = RequestFrame::new(
-                                                        uuid::Uuid::new_v4().to_string(),
-                                                        "node.event".to_string(),
-                                                        Some(serde_json::to_value(&event)?),
-                                                    );
-                                                    let _ = tx.send(Message::Text(serde_json::to_string(&req)?));
-                                                }
-
-                                                // Send invoke result
-                                                let req = RequestFrame::new(
-                                                    uuid::Uuid::new_v4().to_string(),
-                                                    "node.invoke.result".to_string(),
-                                                    Some(serde_json::to_value(&result)?),
-                                                );
-                                                let _ = tx.send(Message::Text(serde_json::to_string(&req)?));
+                                                // Run invokes off the read loop, so a
+                                                // system.cancel can reach a running one.
+                                                let handler = Arc::clone(&handler);
+                                                let requests = Arc::clone(&requests);
+                                                tokio::spawn(async move {
+                                                    let id = invoke.id.clone();
+                                                    if let Err(e) = run_invoke(&handler, invoke, &requests).await {
+                                                        warn!(id = %id, error = %e, "failed to answer invoke");
+                                                    }
+                                                });
                                             }
                                         }
                                         // Handle tick
@@ -268,49 +657,17 @@ impl OpenClawClient {
                                             && let Some(decision) = evt.payload.get("decision").and_then(|v| v.as_str()) {
                                                 if decision == "approved" {
                                                     info!("pairing approved! reconnecting...");
-                                                    break; // Reconnect to establish authenticated session
+                                                    // Reconnect to establish authenticated session
+                                                    break Disconnect::PairingApproved;
                                                 } else {
                                                     warn!(decision = %decision, "pairing request was not approved");
                                                 }
                                             }
                                     }
                                     GatewayFrame::Response(res) => {
-                                        // Handle pending request response
-                                        if let Some(sender) = pending.remove(&res.id) {
-                                            let _ = sender.send(res.clone());
-                                        }
-
-                                        // Check if this is connect response
-                                        if res.ok {
-                                            if let Some(payload) = &res.payload
-                                                && let Ok(_hello) = serde_json::from_value::<HelloOk>(payload.clone()) {
-                                                    info!("connected to Gateway successfully");
-                                                    connected = true;
-                                                    pairing_requested = false;
-                                                }
-                                        } else {
-                                            if let Some(err) = &res.error {
-                                                // Handle NOT_PAIRED - Gateway automatically creates pairing request
-                                                if err.code == "NOT_PAIRED" && !pairing_requested {
-                                                    pairing_requested = true;
-                                                    // Extract requestId from error details if available
-                                                    let request_id = err.details.as_ref()
-                                                        .and_then(|d| d.get("requestId"))
-                                                        .and_then(|v| v.as_str());
-
-                                                    if let Some(req_id) = request_id {
-                                                        warn!(
-                                                            request_id = %req_id,
-                                                            "device not paired - approve with: openclaw nodes approve {}",
-                                                            req_id
-                                                        );
-                                                    } else {
-                                                        warn!("device not paired - check pending requests with: openclaw nodes pending");
-                                                    }
-                                                } else {
-                                                    error!(code = %err.code, message = %err.message, "request failed");
-                                                }
-                                            }
+                                        let id = res.id.clone();
+                                        if !requests.resolve(res) {
+                                            debug!(id = %id, "response to an unknown or timed-out request");
                                         }
                                     }
                                     GatewayFrame::Request(_) => {
@@ -325,42 +682,62 @@ impl OpenClawClient {
                         }
                         Message::Close(_) => {
                             info!("received close frame");
-                            break;
+                            break Disconnect::ClosedByGateway;
                         }
                         _ => {}
                     }
                 }
             }
-        }
+        };
 
+        requests.close();
+        if self.config.cancel_on_disconnect.unwrap_or(false) {
+            let cancelled = handler.cancel_runs().await;
+            if cancelled > 0 {
+                info!(
+                    cancelled,
+                    "cancelled running commands after losing the gateway"
+                );
+            }
+        }
         send_task.abort();
-        Ok(())
+        Ok(Session {
+            reason,
+            connected_for: connected_at.map(|at| at.elapsed()),
+            awaiting_pairing,
+            auth_failed,
+        })
+    }
+
+    /// How often to send `node.status`, or `None` when disabled.
+    fn status_interval(&self) -> Option<Duration> {
+        let secs = self
+            .config
+            .status_interval_secs
+            .unwrap_or(DEFAULT_STATUS_INTERVAL_SECS);
+        (secs > 0).then(|| Duration::from_secs(secs))
     }
 
-    /// Send connect request
+    async fn node_status(&self) -> NodeStatus {
+        let (pending_approvals, _oldest_age_ms) = self.approval_mgr.pending_summary().await;
+        NodeStatus {
+            active_jobs: self.registry.active_count().await as u32,
+            pending_approvals,
+            host: self.host_info.current(),
+        }
+    }
+
+    /// Send connect request, returning a future for the gateway's answer
     fn send_connect(
         &self,
-        tx: &mpsc::UnboundedSender<Message>,
+        requests: &Arc<RequestTracker>,
         node_id: &str,
         display_name: &Option<String>,
+        auth_token: Option<&str>,
         nonce: Option<&str>,
         device_identity: &DeviceIdentity,
-        pending: &mut HashMap<String, tokio::sync::oneshot::Sender<ResponseFrame>>,
-    ) -> anyhow::Result<()> {
-        let id = uuid::Uuid::new_v4().to_string();
-
-        let auth = if self.config.auth_token.is_some() || self.config.auth_password.is_some() {
-            Some(AuthParams {
-                token: self.config.auth_token.clone(),
-                password: self.config.auth_password.clone(),
-            })
-        } else {
-            None
-        };
-
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<ResponseFrame>> + Send + 'static> {
         // Build device identity params with signature
-        let role = "node";
-        let scopes: Vec<String> = vec![];
         let signed_at_ms = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -370,10 +747,10 @@ impl OpenClawClient {
             &device_identity.device_id,
             "node-host",
             "node",
-            role,
-            &scopes,
+            NODE_ROLE,
+            &[],
             signed_at_ms,
-            self.config.auth_token.as_deref(),
+            auth_token,
             nonce,
         );
 
@@ -387,53 +764,17 @@ impl OpenClawClient {
             nonce: nonce.map(|s| s.to_string()),
         };
 
-        let mut caps = vec!["system".to_string()];
-        let mut commands = vec![
-            "system.run".to_string(),
-            "system.which".to_string(),
-            "system.execApprovals.get".to_string(),
-            "system.execApprovals.set".to_string(),
-        ];
-        if self.browser_mgr.is_enabled() {
-            caps.push("browser".to_string());
-            commands.push("browser.proxy".to_string());
-        }
-
-        let params = ConnectParams {
-            min_protocol: PROTOCOL_VERSION,
-            max_protocol: PROTOCOL_VERSION,
-            client: ClientInfo {
-                id: "node-host".to_string(), // Required predefined client ID
-                display_name: display_name.clone(),
-                version: VERSION.to_string(),
-                platform: std::env::consts::OS.to_string(),
-                mode: "node".to_string(),
-                instance_id: Some(node_id.to_string()),
-            },
-            caps: Some(caps),
-            commands: Some(commands),
-            permissions: None,
-            path_env: std::env::var("PATH").ok(),
-            role: Some(role.to_string()),
-            scopes: Some(scopes),
-            device: Some(device),
-            auth,
-        };
-
-        let frame = RequestFrame::new(
-            id.clone(),
-            "connect".to_string(),
-            Some(serde_json::to_value(&params)?),
+        let params = connect_params(
+            &self.config,
+            auth_token,
+            node_id,
+            display_name,
+            self.browser_mgr.is_enabled(),
+            device,
         );
 
         debug!(device_id = %device_identity.device_id, "sending connect request with device identity");
-        tx.send(Message::Text(serde_json::to_string(&frame)?))?;
-
-        // Create oneshot channel for response
-        let (resp_tx, _resp_rx) = tokio::sync::oneshot::channel();
-        pending.insert(id, resp_tx);
-
-        Ok(())
+        requests.request("connect", Some(serde_json::to_value(&params)?))
     }
 
     /// Send pairing request when NOT_PAIRED
@@ -458,18 +799,7 @@ impl OpenClawClient {
             commands: Vec<String>,
         }
 
-        let mut caps = vec!["system".to_string()];
-        let mut commands = vec![
-            "system.run".to_string(),
-            "system.which".to_string(),
-            "system.execApprovals.get".to_string(),
-            "system.execApprovals.set".to_string(),
-        ];
-        if self.browser_mgr.is_enabled() {
-            caps.push("browser".to_string());
-            commands.push("browser.proxy".to_string());
-        }
-
+        let (caps, commands) = advertised(self.browser_mgr.is_enabled());
         let params = PairRequestParams {
             node_id: device_id.to_string(),
             display_name: display_name.clone(),
@@ -491,6 +821,24 @@ impl OpenClawClient {
         Ok(())
     }
 
+    /// rustls connector for wss://, trusting the gateway by `pin` when there
+    /// is one; `None` for plain ws://.
+    fn tls_connector(
+        &self,
+        pin: Option<String>,
+    ) -> anyhow::Result<Option<(tokio_tungstenite::Connector, ObservedFingerprint)>> {
+        if !self.config.gateway_tls.unwrap_or(false) {
+            return Ok(None);
+        }
+        let trust = match pin {
+            Some(pin) => GatewayTrust::Pinned(pin),
+            None if self.config.tls_fingerprint_tofu.unwrap_or(false) => GatewayTrust::FirstUse,
+            None => GatewayTrust::Chain,
+        };
+        let tls = self.tls.clone().unwrap_or_default();
+        crate::tls::gateway_connector(&tls, trust).map(Some)
+    }
+
     /// Build WebSocket URL
     fn build_url(&self) -> String {
         let host = self.config.gateway_host.as_deref().unwrap_or("127.0.0.1");
@@ -504,3 +852,636 @@ impl OpenClawClient {
         format!("{}://{}:{}", scheme, host, port)
     }
 }
+
+/// The caps and commands this node offers the gateway; browser ones only
+/// when browser control is enabled.
+fn advertised(browser_enabled: bool) -> (Vec<String>, Vec<String>) {
+    let mut caps = vec!["system"];
+    let mut commands = vec![
+        "system.run",
+        "system.cancel",
+        "system.which",
+        "system.info",
+        "system.execApprovals.get",
+        "system.execApprovals.set",
+    ];
+    if browser_enabled {
+        caps.push("browser");
+        commands.push("browser.proxy");
+    }
+    let owned = |names: Vec<&str>| names.into_iter().map(String::from).collect();
+    (owned(caps), owned(commands))
+}
+
+/// The connect request's params, signed by `device`.
+fn connect_params(
+    config: &OpenClawConfig,
+    auth_token: Option<&str>,
+    node_id: &str,
+    display_name: &Option<String>,
+    browser_enabled: bool,
+    device: DeviceParams,
+) -> ConnectParams {
+    let auth = if auth_token.is_some() || config.auth_password.is_some() {
+        Some(AuthParams {
+            token: auth_token.map(String::from),
+            password: config.auth_password.clone(),
+        })
+    } else {
+        None
+    };
+    let (caps, commands) = advertised(browser_enabled);
+
+    ConnectParams {
+        min_protocol: PROTOCOL_VERSION,
+        max_protocol: PROTOCOL_VERSION,
+        client: ClientInfo {
+            id: "node-host".to_string(), // Required predefined client ID
+            display_name: display_name.clone(),
+            version: VERSION.to_string(),
+            platform: std::env::consts::OS.to_string(),
+            mode: "node".to_string(),
+            instance_id: Some(node_id.to_string()),
+        },
+        caps: Some(caps),
+        commands: Some(commands),
+        permissions: None,
+        path_env: std::env::var("PATH").ok(),
+        role: Some(NODE_ROLE.to_string()),
+        scopes: Some(Vec::new()),
+        device: Some(device),
+        auth,
+    }
+}
+
+/// Whether a connect error `code` means the gateway refused our
+/// credentials, as opposed to anything else going wrong.
+fn is_auth_error(code: &str) -> bool {
+    code == "UNAUTHORIZED" || code.starts_with("AUTH")
+}
+
+/// This machine's hostname, which names nodes with no `display_name`.
+fn host_name() -> Option<String> {
+    let name = gethostname::gethostname().to_string_lossy().into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+/// The fingerprint to pin: the configured one, else one saved in the
+/// pairing file for this same gateway.
+fn pinned_fingerprint(config: &OpenClawConfig, pairing: &PairingState) -> Option<String> {
+    if let Some(pin) = &config.gateway_tls_fingerprint {
+        return Some(pin.clone());
+    }
+    let gateway = pairing.gateway.as_ref()?;
+    let host = config.gateway_host.as_deref().unwrap_or("127.0.0.1");
+    let port = config.gateway_port.unwrap_or(18789);
+    (gateway.host == host && gateway.port == port)
+        .then(|| gateway.tls_fingerprint.clone())
+        .flatten()
+}
+
+/// Run one invoke and answer it, forwarding its exec events to the gateway
+/// as `node.event` frames while it runs. Every event is sent before the
+/// `node.invoke.result`, and a gateway rejecting any of them is logged.
+async fn run_invoke(
+    handler: &OpenClawHandler,
+    invoke: NodeInvokeRequest,
+    requests: &Arc<RequestTracker>,
+) -> anyhow::Result<()> {
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    let mut invoke = std::pin::pin!(handler.handle_invoke(invoke, &events_tx));
+    let result = loop {
+        tokio::select! {
+            result = &mut invoke => break result,
+            Some(event) = events_rx.recv() => send_exec_event(requests, event)?,
+        }
+    };
+    while let Ok(event) = events_rx.try_recv() {
+        send_exec_event(requests, event)?;
+    }
+    let response = requests
+        .request("node.invoke.result", Some(serde_json::to_value(&result)?))?
+        .await?;
+    log_rejection("node.invoke.result", &response);
+    Ok(())
+}
+
+fn send_exec_event(requests: &Arc<RequestTracker>, exec_event: ExecEvent) -> anyhow::Result<()> {
+    send_node_event(requests, exec_event.kind.as_str(), &exec_event.payload)
+}
+
+/// Send a node event without holding up the ones after it; its response
+/// is checked in the background.
+fn send_node_event(
+    requests: &Arc<RequestTracker>,
+    event: &str,
+    payload: &impl serde::Serialize,
+) -> anyhow::Result<()> {
+    spawn_node_event(requests, event, payload).map(drop)
+}
+
+/// Like [`send_node_event`], returning the task that finishes once the
+/// gateway has answered (or the request timed out).
+fn spawn_node_event(
+    requests: &Arc<RequestTracker>,
+    event: &str,
+    payload: &impl serde::Serialize,
+) -> anyhow::Result<tokio::task::JoinHandle<()>> {
+    let event = NodeEvent {
+        event: event.to_string(),
+        payload_json: serde_json::to_string(payload).ok(),
+    };
+    let response = requests.request("node.event", Some(serde_json::to_value(&event)?))?;
+    Ok(tokio::spawn(async move {
+        if let Ok(response) = response.await {
+            log_rejection("node.event", &response);
+        }
+    }))
+}
+
+fn log_rejection(method: &str, response: &ResponseFrame) {
+    if response.ok {
+        return;
+    }
+    match &response.error {
+        Some(err) => warn!(
+            %method,
+            id = %response.id,
+            code = %err.code,
+            message = %err.message,
+            "gateway rejected request"
+        ),
+        None => warn!(%method, id = %response.id, "gateway rejected request"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenClawGatewayConfig;
+
+    #[test]
+    fn inbound_frames_push_the_deadline_back() {
+        let start = Instant::now();
+        let mut liveness = Liveness::new(start);
+        assert_eq!(liveness.deadline(), start + DEFAULT_TICK_INTERVAL * 2);
+
+        let later = start + Duration::from_secs(45);
+        liveness.saw_inbound(later);
+        assert_eq!(liveness.deadline(), later + DEFAULT_TICK_INTERVAL * 2);
+    }
+
+    #[test]
+    fn the_gateway_tick_interval_sets_ping_and_deadline() {
+        let start = Instant::now();
+        let mut liveness = Liveness::new(start);
+        let tick = Duration::from_secs(5);
+        liveness.set_interval(tick, start);
+
+        assert_eq!(liveness.next_ping, start + tick);
+        assert_eq!(liveness.deadline(), start + tick * 2);
+        liveness.pinged(start + tick);
+        assert_eq!(liveness.next_ping, start + tick * 2);
+    }
+
+    #[test]
+    fn configured_pin_wins_over_one_saved_on_first_use() {
+        let pairing = PairingState {
+            gateway: Some(GatewayInfo {
+                host: "gateway.lan".into(),
+                port: 18789,
+                tls: true,
+                tls_fingerprint: Some("saved".into()),
+            }),
+            ..Default::default()
+        };
+        let mut config = OpenClawConfig {
+            gateway_host: Some("gateway.lan".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            pinned_fingerprint(&config, &pairing).as_deref(),
+            Some("saved")
+        );
+
+        config.gateway_tls_fingerprint = Some("configured".into());
+        assert_eq!(
+            pinned_fingerprint(&config, &pairing).as_deref(),
+            Some("configured")
+        );
+
+        // A pin saved for another gateway doesn't carry over.
+        let other = OpenClawConfig {
+            gateway_host: Some("elsewhere.lan".into()),
+            ..Default::default()
+        };
+        assert_eq!(pinned_fingerprint(&other, &pairing), None);
+    }
+
+    fn policy(max_secs: u64) -> ReconnectPolicy {
+        let config = OpenClawConfig {
+            reconnect_max_secs: Some(max_secs),
+            ..Default::default()
+        };
+        let mut policy = ReconnectPolicy::from_config(&config);
+        policy.backoff = policy.backoff.without_jitter();
+        policy
+    }
+
+    fn session(connected_secs: u64, awaiting_pairing: bool) -> Session {
+        Session {
+            reason: Disconnect::StreamEnded,
+            connected_for: Some(Duration::from_secs(connected_secs)),
+            awaiting_pairing,
+            auth_failed: false,
+        }
+    }
+
+    fn delays(policy: &mut ReconnectPolicy, sessions: &[Option<Session>]) -> Vec<u64> {
+        sessions
+            .iter()
+            .map(|session| policy.next_delay(session.as_ref()).as_secs())
+            .collect()
+    }
+
+    #[test]
+    fn only_a_healthy_authenticated_session_resets_the_backoff() {
+        let mut policy = policy(8);
+        let sessions = [
+            None,
+            Some(session(0, false)),
+            Some(session(29, false)),
+            Some(session(0, false)),
+            Some(session(30, false)),
+            Some(session(0, false)),
+        ];
+        // Closed right after the handshake or up for under 30s: still
+        // doubling, capped at `reconnect_max_secs`. 30s resets it.
+        assert_eq!(delays(&mut policy, &sessions), vec![1, 2, 4, 8, 1, 2]);
+    }
+
+    #[test]
+    fn awaiting_pairing_polls_at_a_fixed_interval() {
+        let mut policy = policy(30);
+        let sessions = [
+            Some(session(0, true)),
+            Some(session(0, true)),
+            Some(session(0, true)),
+            Some(Session {
+                reason: Disconnect::PairingApproved,
+                connected_for: None,
+                awaiting_pairing: true,
+                auth_failed: false,
+            }),
+            Some(session(0, false)),
+        ];
+        assert_eq!(delays(&mut policy, &sessions), vec![15, 15, 15, 0, 1]);
+
+        let config = OpenClawConfig {
+            pairing_poll_secs: Some(60),
+            ..Default::default()
+        };
+        let mut policy = ReconnectPolicy::from_config(&config);
+        assert_eq!(
+            policy.next_delay(Some(&session(0, true))),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn repeated_auth_failures_slow_reconnects_until_a_connect_succeeds() {
+        let mut policy = policy(30);
+        let rejected = || {
+            Some(Session {
+                reason: Disconnect::ConnectFailed("AUTH_FAILED: bad token".into()),
+                connected_for: None,
+                awaiting_pairing: false,
+                auth_failed: true,
+            })
+        };
+        let sessions = [rejected(), rejected(), None, rejected(), rejected()];
+        // A dial error in between doesn't break the run of rejections.
+        assert_eq!(delays(&mut policy, &sessions), vec![1, 2, 4, 300, 300]);
+
+        let sessions = [Some(session(0, false)), rejected()];
+        assert_eq!(delays(&mut policy, &sessions), vec![8, 16]);
+    }
+
+    #[test]
+    fn auth_error_codes_are_recognised() {
+        assert!(is_auth_error("AUTH_FAILED"));
+        assert!(is_auth_error("AUTH_TOKEN_EXPIRED"));
+        assert!(is_auth_error("UNAUTHORIZED"));
+        assert!(!is_auth_error("NOT_PAIRED"));
+        assert!(!is_auth_error("INVALID_REQUEST"));
+    }
+
+    #[tokio::test]
+    async fn the_next_connect_carries_the_refreshed_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let config = OpenClawConfig {
+            auth_token: Some("stale".into()),
+            auth_token_file: Some(path.display().to_string()),
+            ..Default::default()
+        };
+        let source = TokenSource::from_config(&config);
+        let token_sent = |token: Option<String>| {
+            let params = connect_params(
+                &config,
+                token.as_deref(),
+                "node-1",
+                &None,
+                false,
+                test_device(),
+            );
+            serde_json::to_value(&params).unwrap()["auth"]["token"].clone()
+        };
+
+        std::fs::write(&path, "first\n").unwrap();
+        assert_eq!(token_sent(source.token().await.unwrap()), "first");
+        std::fs::write(&path, "rotated\n").unwrap();
+        assert_eq!(token_sent(source.token().await.unwrap()), "rotated");
+    }
+
+    fn test_device() -> DeviceParams {
+        DeviceParams {
+            id: "device-1".into(),
+            public_key: "key".into(),
+            signature: "sig".into(),
+            signed_at: 1,
+            nonce: None,
+        }
+    }
+
+    fn connect_json(browser_enabled: bool, display_name: Option<&str>) -> serde_json::Value {
+        let params = connect_params(
+            &OpenClawConfig::default(),
+            None,
+            "node-1",
+            &display_name.map(String::from),
+            browser_enabled,
+            test_device(),
+        );
+        serde_json::to_value(&params).unwrap()
+    }
+
+    #[test]
+    fn connect_advertises_system_commands_without_browser() {
+        let params = connect_json(false, Some("build box"));
+        assert_eq!(params["caps"], serde_json::json!(["system"]));
+        assert_eq!(
+            params["commands"],
+            serde_json::json!([
+                "system.run",
+                "system.cancel",
+                "system.which",
+                "system.info",
+                "system.execApprovals.get",
+                "system.execApprovals.set",
+            ])
+        );
+        assert_eq!(params["client"]["displayName"], "build box");
+        assert_eq!(params["client"]["instanceId"], "node-1");
+        assert_eq!(params["role"], "node");
+        assert!(params.get("auth").is_none());
+    }
+
+    #[test]
+    fn connect_advertises_browser_proxy_when_browser_is_enabled() {
+        let params = connect_json(true, None);
+        assert_eq!(params["caps"], serde_json::json!(["system", "browser"]));
+        let commands = params["commands"].as_array().unwrap();
+        assert_eq!(commands.len(), 7);
+        assert_eq!(commands.last().unwrap(), "browser.proxy");
+        assert!(params["client"].get("displayName").is_none());
+    }
+
+    #[test]
+    fn disconnect_reasons_read_well_in_logs() {
+        assert_eq!(
+            Disconnect::Idle(Duration::from_secs(60)).to_string(),
+            "no frames from gateway for 60s"
+        );
+        assert_eq!(
+            Disconnect::ReadError("connection reset".into()).to_string(),
+            "websocket error: connection reset"
+        );
+    }
+
+    /// A gateway that challenges one node and forwards every request it
+    /// sends. The connect is accepted when `paired`, else held for pairing;
+    /// other requests are acknowledged.
+    async fn mock_gateway(paired: bool) -> (u16, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (frames_tx, frames_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let challenge = serde_json::json!({
+                "type": "event",
+                "event": "connect.challenge",
+                "payload": { "nonce": "nonce-1" },
+            });
+            ws.send(Message::Text(challenge.to_string())).await.unwrap();
+            while let Some(Ok(msg)) = ws.next().await {
+                let Message::Text(text) = msg else { continue };
+                let frame: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = match frame["method"].as_str() {
+                    Some("connect") if !paired => serde_json::json!({
+                        "type": "res",
+                        "id": frame["id"],
+                        "ok": false,
+                        "error": { "code": "NOT_PAIRED", "message": "pairing required" },
+                    }),
+                    Some("connect") => serde_json::json!({
+                        "type": "res",
+                        "id": frame["id"],
+                        "ok": true,
+                        "payload": {
+                            "protocol": PROTOCOL_VERSION,
+                            "server": { "version": "test", "connId": format!("conn-{port}") },
+                        },
+                    }),
+                    _ => serde_json::json!({ "type": "res", "id": frame["id"], "ok": true }),
+                };
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+                let _ = frames_tx.send(frame);
+            }
+        });
+        (port, frames_rx)
+    }
+
+    fn gateway_client(
+        dir: &std::path::Path,
+        config: OpenClawConfig,
+        monitor: Arc<ConnectionMonitor>,
+    ) -> OpenClawClient {
+        let (approval_broadcast_tx, _) = tokio::sync::broadcast::channel(8);
+        let port = config.gateway_port.unwrap();
+        let mut client = OpenClawClient::new(
+            OpenClawConfig {
+                exec_approvals_path: Some(dir.join("exec-approvals.json").display().to_string()),
+                ..config
+            },
+            None,
+            None,
+            Arc::new(JobRegistry::new(1)),
+            Arc::new(SessionManager::new(5)),
+            Arc::new(ApprovalManager::new(60)),
+            approval_broadcast_tx,
+            None,
+            Arc::new(BrowserManager::new(Default::default())),
+            Arc::new(PolicyChecker::new(&Default::default())),
+        )
+        .with_pairing_path(dir.join(format!("pairing-{port}.json")))
+        .with_monitor(monitor);
+        client.identity_path = dir.join("device-identity.json");
+        client
+    }
+
+    #[tokio::test]
+    async fn one_daemon_pairs_with_several_gateways() {
+        let dir = tempfile::tempdir().unwrap();
+        let (home_port, mut home_frames) = mock_gateway(true).await;
+        let (work_port, mut work_frames) = mock_gateway(true).await;
+        let config = OpenClawConfig {
+            gateway_host: Some("127.0.0.1".into()),
+            display_name: Some("laptop".into()),
+            gateways: vec![
+                OpenClawGatewayConfig {
+                    gateway_port: Some(home_port),
+                    ..Default::default()
+                },
+                OpenClawGatewayConfig {
+                    gateway_port: Some(work_port),
+                    display_name: Some("laptop (work)".into()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let monitors = [Arc::<ConnectionMonitor>::default(), Arc::default()];
+        let clients: Vec<_> = config
+            .gateway_configs()
+            .into_iter()
+            .zip(&monitors)
+            .map(|(gateway, monitor)| gateway_client(dir.path(), gateway, Arc::clone(monitor)))
+            .collect();
+
+        let both_connected = async {
+            let device_id = |connect: serde_json::Value| connect["params"]["device"]["id"].clone();
+            let home = device_id(home_frames.recv().await.unwrap());
+            let work = device_id(work_frames.recv().await.unwrap());
+            while !monitors.iter().all(|m| {
+                matches!(
+                    m.state(),
+                    crate::connection::ConnectionState::Connected { .. }
+                )
+            }) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            (home, work)
+        };
+        let (home, work) = tokio::time::timeout(Duration::from_secs(10), async {
+            tokio::select! {
+                devices = both_connected => devices,
+                res = clients[0].run() => panic!("home client stopped: {res:?}"),
+                res = clients[1].run() => panic!("work client stopped: {res:?}"),
+            }
+        })
+        .await
+        .expect("both gateways should accept the node");
+
+        assert_eq!(home, work, "gateways share the device identity");
+        for (port, name) in [(home_port, "laptop"), (work_port, "laptop (work)")] {
+            let pairing = load_pairing_state(&dir.path().join(format!("pairing-{port}.json")))
+                .unwrap()
+                .unwrap();
+            assert_eq!(pairing.gateway.unwrap().port, port);
+            assert_eq!(pairing.display_name.as_deref(), Some(name));
+        }
+    }
+
+    #[tokio::test]
+    async fn node_status_is_sent_once_connected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (port, mut frames) = mock_gateway(true).await;
+        let config = OpenClawConfig {
+            gateway_port: Some(port),
+            ..Default::default()
+        };
+        let client = gateway_client(dir.path(), config, Arc::default());
+
+        let status = tokio::time::timeout(Duration::from_secs(10), async {
+            tokio::select! {
+                frames = async {
+                    let connect = frames.recv().await.unwrap();
+                    (connect, frames.recv().await.unwrap())
+                } => frames,
+                res = client.run() => panic!("client stopped: {res:?}"),
+            }
+        })
+        .await
+        .expect("a status should follow the connect");
+
+        let (connect, event) = status;
+        assert_eq!(connect["method"], "connect");
+        assert_eq!(event["method"], "node.event");
+        assert_eq!(event["params"]["event"], "node.status");
+        let payload: serde_json::Value =
+            serde_json::from_str(event["params"]["payloadJSON"].as_str().unwrap()).unwrap();
+        assert_eq!(payload["activeJobs"], 0);
+        assert_eq!(payload["pendingApprovals"], 0);
+        assert_eq!(payload["daemonVersion"], VERSION);
+        assert!(payload.get("cpuCount").is_some());
+    }
+
+    #[tokio::test]
+    async fn node_status_waits_for_pairing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (port, mut frames) = mock_gateway(false).await;
+        let config = OpenClawConfig {
+            gateway_port: Some(port),
+            ..Default::default()
+        };
+        let client = gateway_client(dir.path(), config, Arc::default());
+
+        let after_connect = async {
+            let connect = frames.recv().await.unwrap();
+            assert_eq!(connect["method"], "connect");
+            tokio::time::timeout(Duration::from_millis(500), frames.recv()).await
+        };
+        let next = tokio::select! {
+            next = after_connect => next,
+            res = client.run() => panic!("client stopped: {res:?}"),
+        };
+        assert!(next.is_err(), "sent {next:?} while awaiting pairing");
+    }
+
+    #[test]
+    fn a_zero_status_interval_disables_node_status() {
+        let client = |secs| {
+            let dir = std::path::Path::new("/nonexistent");
+            gateway_client(
+                dir,
+                OpenClawConfig {
+                    gateway_port: Some(1),
+                    status_interval_secs: secs,
+                    ..Default::default()
+                },
+                Arc::default(),
+            )
+        };
+        assert_eq!(
+            client(None).status_interval(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            client(Some(5)).status_interval(),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(client(Some(0)).status_interval(), None);
+    }
+}
@@ -7,7 +7,7 @@ use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
 
-use super::protocol::{ExecApprovalsFile, ExecApprovalsSnapshot};
+use super::protocol::{AllowlistEntry, ExecApprovalsFile, ExecApprovalsSnapshot};
 
 const EXEC_APPROVALS_FILE: &str = "exec-approvals.json";
 
@@ -80,9 +80,90 @@ pub fn redact_exec_approvals(file: ExecApprovalsFile) -> ExecApprovalsFile {
     file
 }
 
+/// What exec-approvals.json says about running a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecApprovalsDecision {
+    /// An allowlist entry matches: the command is pre-approved.
+    Allowlisted,
+    /// No pre-approval; the usual approval flow decides.
+    Ask,
+    /// Refused outright by `security`.
+    Deny(String),
+}
+
+/// Decide `command` (as it will be run) for `agent_id`; `None` is a
+/// command the allowlist can't vouch for, which never matches.
+///
+/// A matching allowlist entry pre-approves the command unless `ask` is
+/// `"always"`. `security = "deny"` refuses every command, and
+/// `security = "allowlist"` with `ask = "off"` refuses commands that don't
+/// match; anything else without a match goes to approval.
+pub fn evaluate_exec_approvals(
+    file: &ExecApprovalsFile,
+    command: Option<&str>,
+    agent_id: Option<&str>,
+) -> ExecApprovalsDecision {
+    if file.security.as_deref() == Some("deny") {
+        return ExecApprovalsDecision::Deny("exec denied by exec approvals".to_string());
+    }
+    if command.is_some_and(|command| match_allowlist(file, command, agent_id).is_some()) {
+        if file.ask.as_deref() == Some("always") {
+            return ExecApprovalsDecision::Ask;
+        }
+        return ExecApprovalsDecision::Allowlisted;
+    }
+    if file.security.as_deref() == Some("allowlist") && file.ask.as_deref() == Some("off") {
+        return ExecApprovalsDecision::Deny("command not in exec approvals allowlist".to_string());
+    }
+    ExecApprovalsDecision::Ask
+}
+
+/// Index of the first allowlist entry whose pattern matches `command` and
+/// that is either unscoped or scoped to `agent_id`.
+///
+/// Patterns are globs over the whole command string, where `*` also
+/// matches `/` and spaces.
+pub fn match_allowlist(
+    file: &ExecApprovalsFile,
+    command: &str,
+    agent_id: Option<&str>,
+) -> Option<usize> {
+    file.allowlist.as_ref()?.iter().position(|entry| {
+        let agent_matches = match &entry.agent_id {
+            Some(scope) => agent_id == Some(scope.as_str()),
+            None => true,
+        };
+        agent_matches && pattern_matches(&entry.pattern, command)
+    })
+}
+
+/// Bump the usage counters of allowlist entry `index`.
+pub fn record_allowlist_use(file: &mut ExecApprovalsFile, index: usize, now_ms: u64) {
+    if let Some(entry) = file.allowlist.as_mut().and_then(|list| list.get_mut(index)) {
+        record_use(entry, now_ms);
+    }
+}
+
+fn record_use(entry: &mut AllowlistEntry, now_ms: u64) {
+    entry.last_used_ms = Some(now_ms);
+    entry.use_count = Some(entry.use_count.unwrap_or(0).saturating_add(1));
+}
+
+fn pattern_matches(pattern: &str, command: &str) -> bool {
+    let opts = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    match glob::Pattern::new(pattern) {
+        Ok(p) => p.matches_with(command, opts),
+        // An invalid pattern only matches itself.
+        Err(_) => pattern == command,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::protocol::AllowlistEntry;
     use super::*;
 
     fn make_test_path(tag: &str) -> std::path::PathBuf {
@@ -158,4 +239,91 @@ mod tests {
         let _ = std::fs::remove_file(&path);
         let _ = std::fs::remove_dir(path.parent().unwrap());
     }
+
+    fn allowlist(entries: &[(&str, Option<&str>)]) -> ExecApprovalsFile {
+        ExecApprovalsFile {
+            allowlist: Some(
+                entries
+                    .iter()
+                    .map(|(pattern, agent_id)| AllowlistEntry {
+                        pattern: pattern.to_string(),
+                        agent_id: agent_id.map(str::to_string),
+                        last_used_ms: None,
+                        use_count: None,
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn allowlist_patterns_glob_over_the_command_and_respect_agent_scope() {
+        let file = allowlist(&[("cargo test*", None), ("git *", Some("agent-a"))]);
+
+        assert_eq!(match_allowlist(&file, "cargo test", None), Some(0));
+        assert_eq!(
+            match_allowlist(&file, "cargo test -p crates/ahandd", None),
+            Some(0)
+        );
+        assert_eq!(match_allowlist(&file, "cargo build", None), None);
+        assert_eq!(
+            match_allowlist(&file, "git status", Some("agent-a")),
+            Some(1)
+        );
+        assert_eq!(match_allowlist(&file, "git status", Some("agent-b")), None);
+        assert_eq!(match_allowlist(&file, "git status", None), None);
+
+        let invalid = allowlist(&[("ls [", None)]);
+        assert_eq!(match_allowlist(&invalid, "ls [", None), Some(0));
+        assert_eq!(match_allowlist(&invalid, "ls a", None), None);
+    }
+
+    #[test]
+    fn security_and_ask_decide_commands_off_the_allowlist() {
+        let mut file = allowlist(&[("echo *", None)]);
+        assert_eq!(
+            evaluate_exec_approvals(&file, Some("echo hi"), None),
+            ExecApprovalsDecision::Allowlisted
+        );
+        assert_eq!(
+            evaluate_exec_approvals(&file, Some("rm -rf x"), None),
+            ExecApprovalsDecision::Ask
+        );
+
+        file.security = Some("allowlist".to_string());
+        file.ask = Some("off".to_string());
+        assert!(matches!(
+            evaluate_exec_approvals(&file, Some("rm -rf x"), None),
+            ExecApprovalsDecision::Deny(_)
+        ));
+        assert_eq!(
+            evaluate_exec_approvals(&file, Some("echo hi"), None),
+            ExecApprovalsDecision::Allowlisted
+        );
+
+        file.ask = Some("always".to_string());
+        assert_eq!(
+            evaluate_exec_approvals(&file, Some("echo hi"), None),
+            ExecApprovalsDecision::Ask
+        );
+
+        file.security = Some("deny".to_string());
+        assert!(matches!(
+            evaluate_exec_approvals(&file, Some("echo hi"), None),
+            ExecApprovalsDecision::Deny(_)
+        ));
+    }
+
+    #[test]
+    fn recording_a_use_bumps_the_counters() {
+        let mut file = allowlist(&[("echo *", None)]);
+        record_allowlist_use(&mut file, 0, 1_000);
+        record_allowlist_use(&mut file, 0, 2_000);
+        record_allowlist_use(&mut file, 5, 3_000);
+
+        let entry = &file.allowlist.unwrap()[0];
+        assert_eq!(entry.last_used_ms, Some(2_000));
+        assert_eq!(entry.use_count, Some(2));
+    }
 }
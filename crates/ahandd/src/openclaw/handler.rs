@@ -3,38 +3,48 @@
 //! Dispatches incoming commands to appropriate handlers and maps results
 //! to OpenClaw protocol responses.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use ahand_protocol::{ApprovalResponse, Envelope, JobRequest, envelope};
 use serde::Deserialize;
 
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::Command;
-use tokio::sync::broadcast;
-use tracing::debug;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
+use tracing::{debug, warn};
 
-use crate::approval::ApprovalManager;
+use crate::approval::{ApprovalManager, EXPIRED_REASON};
 use crate::browser::BrowserManager;
-use crate::registry::JobRegistry;
+use crate::executor::CancelReason;
+use crate::policy::{PolicyChecker, PolicyDecision};
+use crate::registry::{JobRegistry, params_hash};
 use crate::session::{SessionDecision, SessionManager};
-use crate::store::RunStore;
+use crate::store::{OutcomeKind, RunContext, RunStore};
 
 use super::exec_approvals::{
-    default_exec_approvals_path, normalize_exec_approvals, read_exec_approvals_snapshot,
+    ExecApprovalsDecision, default_exec_approvals_path, evaluate_exec_approvals, match_allowlist,
+    normalize_exec_approvals, read_exec_approvals_snapshot, record_allowlist_use,
     redact_exec_approvals, save_exec_approvals,
 };
+use super::host_info::HostInfoProbe;
+use super::idempotency::{Claim, DEFAULT_IDEMPOTENCY_TTL, InvokeCache};
 use super::protocol::{
-    ExecApprovalsSetParams, ExecApprovalsSnapshot, ExecEventPayload, InvokeError,
-    NodeInvokeRequest, NodeInvokeResult, OUTPUT_CAP, OUTPUT_EVENT_TAIL, RunResult, SystemRunParams,
-    SystemWhichParams, SystemWhichResult,
+    ExecApprovalsChanged, ExecApprovalsSetParams, ExecApprovalsSnapshot, ExecEventPayload,
+    InvokeError, NodeInvokeRequest, NodeInvokeResult, OUTPUT_CAP, OUTPUT_EVENT_TAIL,
+    OutputEncoding, RunResult, SystemCancelParams, SystemCancelResult, SystemRunParams,
+    SystemWhichParams, SystemWhichResult, WhichEntry,
 };
+use super::which::{Lookup, VERSION_PROBE_TIMEOUT, WHICH_CACHE_TTL, WhichCache, probe_version};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum ExecEventKind {
+    /// The output tail so far, while the command is still running.
+    Output,
     Finished,
     Denied,
 }
@@ -42,6 +52,7 @@ pub(crate) enum ExecEventKind {
 impl ExecEventKind {
     pub(crate) fn as_str(self) -> &'static str {
         match self {
+            Self::Output => "exec.output",
             Self::Finished => "exec.finished",
             Self::Denied => "exec.denied",
         }
@@ -55,15 +66,31 @@ pub(crate) struct ExecEvent {
 }
 
 /// Handler for OpenClaw node invocations
-#[allow(dead_code)] // registry + store consumed via Arc; fields read in future methods
 pub struct OpenClawHandler {
     node_id: String,
+    /// Running system.run commands are registered here by run id, so
+    /// system.cancel and daemon shutdown can stop them.
     registry: Arc<JobRegistry>,
+    /// Run ids of this handler's commands still running; system.cancel
+    /// only reaches these.
+    runs: Mutex<HashSet<String>>,
+    /// Results by idempotency key, so gateway retries aren't run twice.
+    invokes: Mutex<InvokeCache>,
+    /// Daemon policy applied to system.run before any approval.
+    policy: Option<Arc<PolicyChecker>>,
+    /// Answers system.info.
+    host_info: Arc<HostInfoProbe>,
+    /// Recent system.which lookups.
+    which_cache: Mutex<WhichCache>,
+    version_probe_timeout: Duration,
     session_mgr: Arc<SessionManager>,
     approval_mgr: Arc<ApprovalManager>,
     approval_broadcast_tx: broadcast::Sender<Envelope>,
     store: Option<Arc<RunStore>>,
     exec_approvals_path: PathBuf,
+    /// Hash of exec-approvals.json as the gateway last saw it, from a get,
+    /// a set or an `execApprovals.changed` event.
+    exec_approvals_lock: Mutex<String>,
     browser_mgr: Arc<BrowserManager>,
 }
 
@@ -79,23 +106,94 @@ impl OpenClawHandler {
         exec_approvals_path: Option<PathBuf>,
         browser_mgr: Arc<BrowserManager>,
     ) -> Self {
+        let exec_approvals_path = exec_approvals_path.unwrap_or_else(default_exec_approvals_path);
+        let exec_approvals_hash = read_exec_approvals_snapshot(&exec_approvals_path)
+            .map(|snapshot| snapshot.hash)
+            .unwrap_or_default();
         Self {
             node_id,
             registry,
+            runs: Mutex::new(HashSet::new()),
+            invokes: Mutex::new(InvokeCache::new(DEFAULT_IDEMPOTENCY_TTL)),
+            policy: None,
+            host_info: Arc::new(HostInfoProbe::new(std::time::Instant::now())),
+            which_cache: Mutex::new(WhichCache::new(WHICH_CACHE_TTL)),
+            version_probe_timeout: VERSION_PROBE_TIMEOUT,
             session_mgr,
             approval_mgr,
             approval_broadcast_tx,
             store,
-            exec_approvals_path: exec_approvals_path.unwrap_or_else(default_exec_approvals_path),
+            exec_approvals_path,
+            exec_approvals_lock: Mutex::new(exec_approvals_hash),
             browser_mgr,
         }
     }
 
-    /// Handle a node.invoke.request
+    /// Check system.run commands against `policy` too: its denials refuse
+    /// the invoke outright, even for allowlisted commands, and its approval
+    /// requirements join the session's.
+    pub fn with_policy(mut self, policy: Arc<PolicyChecker>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Answer system.info from `host_info`, which outlives reconnects, so
+    /// the reported uptime is the daemon's rather than this connection's.
+    pub fn with_host_info(mut self, host_info: Arc<HostInfoProbe>) -> Self {
+        self.host_info = host_info;
+        self
+    }
+
+    /// Keep results for `ttl` (default 10 minutes) for invokes retried
+    /// with the same idempotency key.
+    pub fn with_idempotency_ttl(self, ttl: Duration) -> Self {
+        *self.invokes_lock() = InvokeCache::new(ttl);
+        self
+    }
+
+    /// Handle a node.invoke.request. Exec events go to `events` as they
+    /// happen: `exec.output` while a command runs, then one final
+    /// `exec.finished` or `exec.denied`.
+    ///
+    /// An invoke whose idempotency key was already answered gets that answer
+    /// again, and one whose key is still running waits for it; neither runs
+    /// the command a second time or emits exec events.
     pub async fn handle_invoke(
         &self,
         invoke: NodeInvokeRequest,
-    ) -> (NodeInvokeResult, Option<ExecEvent>) {
+        events: &mpsc::UnboundedSender<ExecEvent>,
+    ) -> NodeInvokeResult {
+        let Some(key) = invoke.idempotency_key.clone().filter(|k| !k.is_empty()) else {
+            return self.dispatch_invoke(invoke, events).await;
+        };
+        loop {
+            let claim = self.invokes_lock().claim(&key);
+            let original = match claim {
+                Claim::Done(result) => result,
+                Claim::Wait(mut rx) => match rx.wait_for(Option::is_some).await {
+                    Ok(result) => result.clone().expect("waited for a result"),
+                    // The original was abandoned; claim the key again.
+                    Err(_) => continue,
+                },
+                Claim::Run(running) => {
+                    let result = self.dispatch_invoke(invoke, events).await;
+                    self.invokes_lock().complete(running, &result);
+                    return result;
+                }
+            };
+            debug!(id = %invoke.id, key = %key, "answering repeated invoke from its original");
+            return NodeInvokeResult {
+                id: invoke.id.clone(),
+                ..original
+            };
+        }
+    }
+
+    async fn dispatch_invoke(
+        &self,
+        invoke: NodeInvokeRequest,
+        events: &mpsc::UnboundedSender<ExecEvent>,
+    ) -> NodeInvokeResult {
         let command = invoke.command.as_str();
 
         debug!(
@@ -104,43 +202,68 @@ impl OpenClawHandler {
             "handling invoke request"
         );
 
+        // The gateway stops waiting after `timeout_ms`. system.run fits its
+        // approval wait and command into that budget itself, so that the
+        // command is killed rather than abandoned; anything else is dropped
+        // when the budget runs out.
+        let deadline = invoke
+            .timeout_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
         let (result, event) = match command {
-            "system.run" => self.handle_system_run(&invoke).await,
-            "system.which" => {
-                let result = self.handle_system_which(&invoke).await;
-                (result, None)
-            }
-            "system.execApprovals.get" => {
-                let result = self.handle_exec_approvals_get(&invoke).await;
-                (result, None)
-            }
-            "system.execApprovals.set" => {
-                let result = self.handle_exec_approvals_set(&invoke).await;
-                (result, None)
-            }
-            "browser.proxy" => {
-                let result = self.handle_browser_proxy(&invoke).await;
-                (result, None)
-            }
+            "system.run" => self.handle_system_run(&invoke, events, deadline).await,
             _ => {
-                let result = NodeInvokeResult {
-                    id: invoke.id.clone(),
-                    node_id: self.node_id.clone(),
-                    ok: false,
-                    payload_json: None,
-                    error: Some(InvokeError::unavailable("command not supported")),
+                let handled = self.handle_command(&invoke);
+                let result = match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, handled)
+                        .await
+                        .unwrap_or_else(|_| self.invoke_timed_out(&invoke)),
+                    None => handled.await,
                 };
                 (result, None)
             }
         };
 
-        (result, event)
+        if let Some(event) = event {
+            let _ = events.send(event);
+        }
+        result
+    }
+
+    /// Dispatch every command except system.run.
+    async fn handle_command(&self, invoke: &NodeInvokeRequest) -> NodeInvokeResult {
+        match invoke.command.as_str() {
+            "system.cancel" => self.handle_system_cancel(invoke).await,
+            "system.which" => self.handle_system_which(invoke).await,
+            "system.info" => self.handle_system_info(invoke),
+            "system.execApprovals.get" => self.handle_exec_approvals_get(invoke).await,
+            "system.execApprovals.set" => self.handle_exec_approvals_set(invoke).await,
+            "browser.proxy" => self.handle_browser_proxy(invoke).await,
+            _ => NodeInvokeResult {
+                id: invoke.id.clone(),
+                node_id: self.node_id.clone(),
+                ok: false,
+                payload_json: None,
+                error: Some(InvokeError::unavailable("command not supported")),
+            },
+        }
+    }
+
+    fn invoke_timed_out(&self, invoke: &NodeInvokeRequest) -> NodeInvokeResult {
+        NodeInvokeResult {
+            id: invoke.id.clone(),
+            node_id: self.node_id.clone(),
+            ok: false,
+            payload_json: None,
+            error: Some(InvokeError::timeout("node-side timeout")),
+        }
     }
 
     /// Handle system.run command
     async fn handle_system_run(
         &self,
         invoke: &NodeInvokeRequest,
+        events: &mpsc::UnboundedSender<ExecEvent>,
+        deadline: Option<Instant>,
     ) -> (NodeInvokeResult, Option<ExecEvent>) {
         let params: SystemRunParams = match decode_params(&invoke.params_json) {
             Ok(p) => p,
@@ -178,57 +301,209 @@ impl OpenClawHandler {
         let run_id = params.run_id.clone().unwrap_or_else(|| invoke.id.clone());
         let cmd_text = format_command(&params.command);
         let request = build_job_request(invoke, &params, &run_id);
+        let context = RunContext {
+            session_mode: Some(self.session_mgr.mode_name(&session_key).await.to_string()),
+            ..RunContext::new(&session_key)
+        };
+        let allowlist_command = allowlist_command(&params);
+        let agent_id = params.agent_id.as_deref();
+
+        let allowlisted = match self.exec_approvals_decision(allowlist_command.as_deref(), agent_id)
+        {
+            ExecApprovalsDecision::Deny(reason) => {
+                return self
+                    .denied_system_run(invoke, &request, &context, &cmd_text, reason)
+                    .await;
+            }
+            ExecApprovalsDecision::Allowlisted => true,
+            ExecApprovalsDecision::Ask => false,
+        };
+
+        // Policy denials win over the allowlist; an allowlist match does
+        // stand in for the approval the policy or session asks for.
+        let policy_approval = match self
+            .policy_decision(invoke, &params, &run_id, &session_key)
+            .await
+        {
+            Some(PolicyDecision::Deny(reason)) => {
+                return self
+                    .policy_denied_system_run(invoke, &request, &context, &cmd_text, reason)
+                    .await;
+            }
+            Some(PolicyDecision::NeedsApproval { reason, .. }) => Some(reason),
+            Some(PolicyDecision::Allow) | None => None,
+        };
 
-        match self.session_mgr.check(&request, &session_key).await {
+        let approval = match self.session_mgr.check(&request, &session_key).await {
             SessionDecision::Deny(reason) => {
-                return self.denied_system_run(invoke, &session_key, &run_id, &cmd_text, reason);
+                return self
+                    .denied_system_run(invoke, &request, &context, &cmd_text, reason)
+                    .await;
+            }
+            SessionDecision::Allow => {
+                policy_approval.map(|reason| (reason, Vec::new(), Vec::new()))
             }
-            SessionDecision::Allow => {}
             SessionDecision::NeedsApproval {
                 reason,
                 previous_refusals,
-            } => match approval_disposition(&params) {
-                ApprovalDisposition::Granted => {}
+                previous_approvals,
+            } => {
+                let reason = match policy_approval {
+                    Some(policy_reason) => format!("{reason}; {policy_reason}"),
+                    None => reason,
+                };
+                Some((reason, previous_refusals, previous_approvals))
+            }
+        };
+
+        if let Some((reason, previous_refusals, previous_approvals)) = approval {
+            match approval_disposition(&params) {
+                ApprovalDisposition::Granted => {
+                    // Remembered as the policy judges it, so a command run
+                    // through the shell is seen as the shell, which isn't
+                    // remembered.
+                    if params.approval_decision.as_deref() == Some("allow-always") {
+                        let judged = policy_job_request(invoke, &params, &run_id);
+                        self.approval_mgr.remember(&session_key, &judged).await;
+                    }
+                    self.session_mgr
+                        .record_approval(&session_key, &request.tool, &request.args)
+                        .await;
+                }
                 ApprovalDisposition::Denied => {
-                    return self.denied_system_run(
-                        invoke,
-                        &session_key,
-                        &run_id,
-                        &cmd_text,
-                        "approval denied".to_string(),
-                    );
+                    return self
+                        .denied_system_run(
+                            invoke,
+                            &request,
+                            &context,
+                            &cmd_text,
+                            "approval denied".to_string(),
+                        )
+                        .await;
+                }
+                ApprovalDisposition::Missing if allowlisted => {
+                    if let Some(command) = &allowlist_command {
+                        self.record_allowlist_use(command, agent_id);
+                    }
+                    self.session_mgr
+                        .record_approval(&session_key, &request.tool, &request.args)
+                        .await;
                 }
                 ApprovalDisposition::Missing => {
+                    let wait = deadline
+                        .map(|d| d.saturating_duration_since(Instant::now()))
+                        .unwrap_or_else(|| self.approval_mgr.default_timeout());
                     let outcome = self
-                        .await_local_approval(&request, &session_key, reason, previous_refusals)
+                        .await_local_approval(
+                            &request,
+                            &session_key,
+                            reason,
+                            previous_refusals,
+                            previous_approvals,
+                            wait,
+                        )
                         .await;
                     match outcome {
                         ApprovalOutcome::Approved => {}
                         ApprovalOutcome::Denied(reason) => {
-                            return self.denied_system_run(
-                                invoke,
-                                &session_key,
-                                &run_id,
-                                &cmd_text,
-                                reason,
-                            );
+                            return self
+                                .denied_system_run(invoke, &request, &context, &cmd_text, reason)
+                                .await;
                         }
                         ApprovalOutcome::TimedOut => {
-                            return self.denied_system_run(
-                                invoke,
-                                &session_key,
-                                &run_id,
-                                &cmd_text,
-                                "approval timed out".to_string(),
-                            );
+                            return self
+                                .denied_system_run(
+                                    invoke,
+                                    &request,
+                                    &context,
+                                    &cmd_text,
+                                    "approval timed out".to_string(),
+                                )
+                                .await;
                         }
                     }
                 }
-            },
+            }
         }
 
-        let result = self.run_command(&params).await;
-        let invoke_result = invoke_result_from_run(invoke, &self.node_id, &result);
+        if self.registry.is_running(&run_id).await {
+            return (
+                NodeInvokeResult {
+                    id: invoke.id.clone(),
+                    node_id: self.node_id.clone(),
+                    ok: false,
+                    payload_json: None,
+                    error: Some(InvokeError::invalid_request(format!(
+                        "run {run_id} already running"
+                    ))),
+                },
+                None,
+            );
+        }
+        let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+        if !self
+            .registry
+            .register(
+                run_id.clone(),
+                &session_key,
+                params_hash(&request),
+                cancel_tx,
+            )
+            .await
+        {
+            return self
+                .denied_system_run(
+                    invoke,
+                    &request,
+                    &context,
+                    &cmd_text,
+                    "daemon shutting down".to_string(),
+                )
+                .await;
+        }
+        self.runs_lock().insert(run_id.clone());
+
+        // The command's own timeout, cut short to what is left of the
+        // invoke budget.
+        let requested = Duration::from_millis(params.timeout_ms.unwrap_or(DEFAULT_RUN_TIMEOUT_MS));
+        let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        let (run_timeout, clamped) = match remaining {
+            Some(remaining) if remaining < requested => (remaining, true),
+            _ => (requested, false),
+        };
+
+        if let Some(store) = &self.store {
+            store.start_run(&run_id, &session_key, &request);
+        }
+        let started = Instant::now();
+        let result = self
+            .run_command(&params, &run_id, run_timeout, &mut cancel_rx, |tail| {
+                let _ = events.send(ExecEvent {
+                    kind: ExecEventKind::Output,
+                    payload: output_event_payload(&session_key, &run_id, &cmd_text, tail),
+                });
+            })
+            .await;
+        self.runs_lock().remove(&run_id);
+        self.registry.remove(&run_id).await;
+        if let Some(store) = &self.store {
+            let (kind, error) = stored_outcome(&result);
+            store
+                .finish_run(
+                    &run_id,
+                    result.exit_code.unwrap_or(-1),
+                    error,
+                    started.elapsed().as_millis() as u64,
+                    0,
+                    &context.outcome(kind),
+                )
+                .await;
+        }
+        let invoke_result = if clamped && result.timed_out {
+            self.invoke_timed_out(invoke)
+        } else {
+            invoke_result_from_run(invoke, &self.node_id, &result)
+        };
         let event = ExecEvent {
             kind: ExecEventKind::Finished,
             payload: exec_event_payload(&session_key, &run_id, &cmd_text, &result, None),
@@ -253,14 +528,27 @@ impl OpenClawHandler {
     /// shell commands regardless of platform.  Shell builtins are not available
     /// in this form (they were unreliable before too — POSIX escaping only
     /// approximated safety on Windows cmd.exe).
-    async fn run_command(&self, params: &SystemRunParams) -> RunResult {
+    ///
+    /// While the command runs, `on_output` gets the output tail (at most
+    /// `OUTPUT_EVENT_TAIL` bytes) every `OUTPUT_EVENT_INTERVAL` or once
+    /// `OUTPUT_EVENT_BYTES` new bytes have arrived.
+    ///
+    /// With a run store, every chunk is also appended to `run_id`'s stored
+    /// stdout or stderr as it is read, past the in-memory cap.
+    ///
+    /// The command leads its own process group, which is killed after
+    /// `timeout` or when a reason arrives on `cancel_rx`.
+    async fn run_command(
+        &self,
+        params: &SystemRunParams,
+        run_id: &str,
+        timeout: Duration,
+        cancel_rx: &mut mpsc::Receiver<CancelReason>,
+        on_output: impl FnMut(String),
+    ) -> RunResult {
         let cwd = params.cwd.as_deref().filter(|s| !s.is_empty());
-        let env_overrides = params.env.as_ref();
-        let timeout_ms = params.timeout_ms.or(Some(120_000)); // default 2 minutes
-
-        let command_env = env_overrides
-            .map(sanitize_env)
-            .unwrap_or_else(|| env::vars().collect());
+        let no_overrides = HashMap::new();
+        let command_env = sanitize_env(params.env.as_ref().unwrap_or(&no_overrides));
         let path_val = crate::plugin_runtime::path_env::child_process_path(&command_env).await;
 
         // Decide between direct spawn (array with 2+ elements) and shell spawn.
@@ -280,9 +568,7 @@ impl OpenClawHandler {
                 .clone()
                 .unwrap_or_else(|| params.command.first().cloned().unwrap_or_default());
             debug!(shell_cmd = %shell_cmd, "executing command via shell");
-            let shell = ahand_platform::shell::env_shell()
-                .unwrap_or_else(|| ahand_platform::shell::default_shell().path);
-            let mut c = Command::new(shell);
+            let mut c = Command::new(command_shell());
             c.arg(ahand_platform::shell::shell_c_flag()).arg(&shell_cmd);
             c
         };
@@ -291,6 +577,8 @@ impl OpenClawHandler {
             cmd.current_dir(dir);
         }
 
+        // The child sees exactly the sanitized environment, not the daemon's.
+        cmd.env_clear();
         for (key, value) in &command_env {
             if !crate::plugin_runtime::path_env::is_path_env_key(key) {
                 cmd.env(key, value);
@@ -300,6 +588,7 @@ impl OpenClawHandler {
 
         cmd.stdout(std::process::Stdio::piped());
         cmd.stderr(std::process::Stdio::piped());
+        ahand_platform::process::configure_group(&mut cmd);
 
         let mut child = match cmd.spawn() {
             Ok(c) => c,
@@ -310,6 +599,8 @@ impl OpenClawHandler {
                     success: false,
                     stdout: String::new(),
                     stderr: String::new(),
+                    stdout_base64: None,
+                    mime: None,
                     error: Some(e.to_string()),
                 };
             }
@@ -319,70 +610,99 @@ impl OpenClawHandler {
         let stdout_pipe = child.stdout.take();
         let stderr_pipe = child.stderr.take();
 
-        let stdout_task = tokio::spawn(async move {
-            let mut output = String::new();
-            if let Some(pipe) = stdout_pipe {
-                let mut reader = BufReader::new(pipe);
-                let mut line = String::new();
-                while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                    if output.len() < OUTPUT_CAP {
-                        output.push_str(&line);
-                    }
-                    line.clear();
-                }
+        // Both readers also copy each chunk here for the progress events.
+        let (line_tx, mut line_rx) = mpsc::unbounded_channel::<String>();
+
+        // Binary stdout would only be garbled in the progress tail.
+        let stdout_lines =
+            (params.output_encoding == OutputEncoding::Text).then(|| line_tx.clone());
+        let stdout_store = self.store.clone();
+        let stdout_run_id = run_id.to_string();
+        let stdout_task = tokio::spawn(read_output(stdout_pipe, move |chunk| {
+            if let Some(store) = &stdout_store {
+                store.append_stdout(&stdout_run_id, chunk);
             }
-            output
-        });
-
-        let stderr_task = tokio::spawn(async move {
-            let mut output = String::new();
-            if let Some(pipe) = stderr_pipe {
-                let mut reader = BufReader::new(pipe);
-                let mut line = String::new();
-                while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-                    if output.len() < OUTPUT_CAP {
-                        output.push_str(&line);
-                    }
-                    line.clear();
-                }
+            if let Some(lines) = &stdout_lines {
+                let _ = lines.send(String::from_utf8_lossy(chunk).into_owned());
             }
-            output
-        });
+        }));
 
-        // Wait with timeout
-        let timeout = timeout_ms.map(Duration::from_millis);
-        let (exit_code, timed_out) = if let Some(dur) = timeout {
-            match tokio::time::timeout(dur, child.wait()).await {
-                Ok(Ok(status)) => (status.code(), false),
-                Ok(Err(_)) => (None, false),
-                Err(_) => {
-                    // Timeout - kill the process
-                    let _ = child.kill().await;
-                    (None, true)
+        let stderr_store = self.store.clone();
+        let stderr_run_id = run_id.to_string();
+        let stderr_task = tokio::spawn(read_output(stderr_pipe, move |chunk| {
+            if let Some(store) = &stderr_store {
+                store.append_stderr(&stderr_run_id, chunk);
+            }
+            let _ = line_tx.send(String::from_utf8_lossy(chunk).into_owned());
+        }));
+
+        // Wait, reporting progress, until the command exits, is cancelled
+        // or times out.
+        let pid = child.id();
+        let mut progress = OutputProgress::new(on_output);
+        let wait = async {
+            let mut exited = std::pin::pin!(child.wait());
+            let mut tick = tokio::time::interval_at(
+                tokio::time::Instant::now() + OUTPUT_EVENT_INTERVAL,
+                OUTPUT_EVENT_INTERVAL,
+            );
+            loop {
+                tokio::select! {
+                    status = &mut exited => return Ok(status),
+                    Some(reason) = cancel_rx.recv() => return Err(reason),
+                    Some(line) = line_rx.recv() => progress.push(&line),
+                    _ = tick.tick() => progress.flush(),
                 }
             }
-        } else {
-            match child.wait().await {
-                Ok(status) => (status.code(), false),
-                Err(_) => (None, false),
+        };
+        let waited = tokio::time::timeout(timeout, wait).await;
+        let mut cancelled = None;
+        let (exit_code, timed_out) = match waited {
+            Ok(Ok(Ok(status))) => (status.code(), false),
+            Ok(Ok(Err(_))) => (None, false),
+            Ok(Err(reason)) => {
+                debug!(?reason, "command cancelled, killing its process group");
+                kill_run(&mut child, pid).await;
+                cancelled = Some(reason);
+                (None, false)
+            }
+            Err(_) => {
+                // Timeout - kill the process group
+                kill_run(&mut child, pid).await;
+                (None, true)
             }
         };
 
         // Collect output from tasks
-        let mut stdout = stdout_task.await.unwrap_or_default();
-        let mut stderr = stderr_task.await.unwrap_or_default();
+        let stdout_output = stdout_task.await.unwrap_or_default();
+        let stderr_output = stderr_task.await.unwrap_or_default();
+        let mut stderr = String::from_utf8_lossy(&stderr_output.bytes).into_owned();
+        let (mut stdout, stdout_base64, mime) = match params.output_encoding {
+            OutputEncoding::Text => (
+                String::from_utf8_lossy(&stdout_output.bytes).into_owned(),
+                None,
+                None,
+            ),
+            OutputEncoding::Base64 => {
+                use base64::Engine;
+                (
+                    String::new(),
+                    Some(base64::engine::general_purpose::STANDARD.encode(&stdout_output.bytes)),
+                    Some(mime_from_magic(&stdout_output.bytes).to_string()),
+                )
+            }
+        };
 
-        let truncated = stdout.len() >= OUTPUT_CAP || stderr.len() >= OUTPUT_CAP;
-        if truncated {
+        if stdout_output.truncated || stderr_output.truncated {
             let suffix = "... (truncated)";
-            if !stderr.is_empty() {
+            if !stderr.is_empty() || stdout_base64.is_some() {
                 stderr.push_str(suffix);
             } else {
                 stdout.push_str(suffix);
             }
         }
 
-        let success = exit_code == Some(0) && !timed_out;
+        let success = exit_code == Some(0) && !timed_out && cancelled.is_none();
 
         RunResult {
             exit_code,
@@ -390,33 +710,176 @@ impl OpenClawHandler {
             success,
             stdout,
             stderr,
+            stdout_base64,
+            mime,
+            error: cancelled.map(|reason| reason.as_error().to_string()),
+        }
+    }
+
+    fn runs_lock(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.runs.lock().expect("openclaw runs mutex poisoned")
+    }
+
+    /// What exec-approvals.json says about `command`. A file that can't be
+    /// read counts as empty.
+    fn exec_approvals_decision(
+        &self,
+        command: Option<&str>,
+        agent_id: Option<&str>,
+    ) -> ExecApprovalsDecision {
+        let _guard = self.exec_approvals_lock();
+        match read_exec_approvals_snapshot(&self.exec_approvals_path) {
+            Ok(snapshot) => evaluate_exec_approvals(&snapshot.file, command, agent_id),
+            Err(e) => {
+                warn!(error = %e, "ignoring unreadable exec approvals");
+                ExecApprovalsDecision::Ask
+            }
+        }
+    }
+
+    /// Count a use of the allowlist entry that pre-approved `command`.
+    fn record_allowlist_use(&self, command: &str, agent_id: Option<&str>) {
+        let _guard = self.exec_approvals_lock();
+        let mut file = match read_exec_approvals_snapshot(&self.exec_approvals_path) {
+            Ok(snapshot) => snapshot.file,
+            Err(e) => {
+                warn!(error = %e, "failed to read exec approvals");
+                return;
+            }
+        };
+        let Some(index) = match_allowlist(&file, command, agent_id) else {
+            return;
+        };
+        record_allowlist_use(&mut file, index, now_ms());
+        if let Err(e) = save_exec_approvals(&self.exec_approvals_path, &file) {
+            warn!(error = %e, "failed to save exec approvals usage");
+        }
+    }
+
+    /// Check exec-approvals.json for changes the gateway hasn't seen, made
+    /// by other tools or by allowlist usage counters, and describe the file
+    /// as it is now if there are any. A file that can't be read is left for
+    /// the next check.
+    pub(crate) fn poll_exec_approvals(&self) -> Option<ExecApprovalsChanged> {
+        let mut seen_hash = self.exec_approvals_lock();
+        let snapshot = match read_exec_approvals_snapshot(&self.exec_approvals_path) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                debug!(error = %e, "not checking unreadable exec approvals");
+                return None;
+            }
+        };
+        if snapshot.hash == *seen_hash {
+            return None;
+        }
+        seen_hash.clone_from(&snapshot.hash);
+        Some(ExecApprovalsChanged {
+            path: snapshot.path,
+            exists: snapshot.exists,
+            hash: snapshot.hash,
+        })
+    }
+
+    /// Held across read-modify-write of exec-approvals.json.
+    fn exec_approvals_lock(&self) -> std::sync::MutexGuard<'_, String> {
+        self.exec_approvals_lock
+            .lock()
+            .expect("openclaw exec approvals mutex poisoned")
+    }
+
+    fn which_cache_lock(&self) -> std::sync::MutexGuard<'_, WhichCache> {
+        self.which_cache
+            .lock()
+            .expect("openclaw which cache mutex poisoned")
+    }
+
+    fn invokes_lock(&self) -> std::sync::MutexGuard<'_, InvokeCache> {
+        self.invokes
+            .lock()
+            .expect("openclaw invokes mutex poisoned")
+    }
+
+    /// Cancel every system.run command this handler still has running.
+    /// Returns how many were signalled.
+    pub async fn cancel_runs(&self) -> usize {
+        let runs: Vec<String> = self.runs_lock().iter().cloned().collect();
+        for run_id in &runs {
+            self.registry.cancel(run_id).await;
+        }
+        runs.len()
+    }
+
+    /// Handle system.cancel command
+    async fn handle_system_cancel(&self, invoke: &NodeInvokeRequest) -> NodeInvokeResult {
+        let params: SystemCancelParams = match decode_params(&invoke.params_json) {
+            Ok(p) => p,
+            Err(e) => {
+                return NodeInvokeResult {
+                    id: invoke.id.clone(),
+                    node_id: self.node_id.clone(),
+                    ok: false,
+                    payload_json: None,
+                    error: Some(e),
+                };
+            }
+        };
+
+        let cancelled = self.runs_lock().contains(&params.run_id);
+        if cancelled {
+            self.registry.cancel(&params.run_id).await;
+        }
+        let result = SystemCancelResult {
+            run_id: params.run_id,
+            cancelled,
+        };
+
+        NodeInvokeResult {
+            id: invoke.id.clone(),
+            node_id: self.node_id.clone(),
+            ok: true,
+            payload_json: Some(serde_json::to_string(&result).unwrap_or_default()),
             error: None,
         }
     }
 
+    /// Ask local approvers (`ahandctl approve`, desktop notifications) about
+    /// `request` and wait up to `wait` for an answer.
     async fn await_local_approval(
         &self,
         request: &JobRequest,
         caller_uid: &str,
         reason: String,
         previous_refusals: Vec<ahand_protocol::RefusalContext>,
+        previous_approvals: Vec<ahand_protocol::ApprovalContext>,
+        wait: Duration,
     ) -> ApprovalOutcome {
         let (approval_req, approval_rx) = self
             .approval_mgr
-            .submit(request.clone(), caller_uid, reason, previous_refusals)
+            .submit_with_timeout(
+                request.clone(),
+                caller_uid,
+                reason,
+                crate::policy::extract_destinations(request),
+                previous_refusals,
+                previous_approvals,
+                wait,
+            )
             .await;
-        let approval_env = Envelope {
-            device_id: self.node_id.clone(),
-            msg_id: new_msg_id(),
-            ts_ms: now_ms(),
-            payload: Some(envelope::Payload::ApprovalRequest(approval_req)),
-            ..Default::default()
-        };
-        let _ = self.approval_broadcast_tx.send(approval_env);
+        // A request attached to an identical pending one was already broadcast.
+        if approval_req.job_id == request.job_id {
+            let approval_env = Envelope {
+                device_id: self.node_id.clone(),
+                msg_id: new_msg_id(),
+                ts_ms: now_ms(),
+                payload: Some(envelope::Payload::ApprovalRequest(approval_req)),
+                ..Default::default()
+            };
+            let _ = self.approval_broadcast_tx.send(approval_env);
+        }
 
-        match tokio::time::timeout(self.approval_mgr.default_timeout(), approval_rx).await {
+        match tokio::time::timeout(wait, approval_rx).await {
             Ok(Ok(resp)) if resp.approved => ApprovalOutcome::Approved,
-            Ok(Ok(resp)) => {
+            Ok(Ok(resp)) if resp.reason != EXPIRED_REASON => {
                 if !resp.reason.is_empty() {
                     self.session_mgr
                         .record_refusal(caller_uid, &request.tool, &resp.reason)
@@ -432,19 +895,68 @@ impl OpenClawHandler {
         }
     }
 
-    fn denied_system_run(
+    /// PolicyChecker's verdict on the command, when a policy is set.
+    async fn policy_decision(
         &self,
         invoke: &NodeInvokeRequest,
-        session_key: &str,
+        params: &SystemRunParams,
         run_id: &str,
+        session_key: &str,
+    ) -> Option<PolicyDecision> {
+        let policy = self.policy.as_ref()?;
+        let request = policy_job_request(invoke, params, run_id);
+        Some(policy.check(&request, session_key).await)
+    }
+
+    /// Like [`Self::denied_system_run`], but the invoke itself fails with
+    /// `PERMISSION_DENIED`.
+    async fn policy_denied_system_run(
+        &self,
+        invoke: &NodeInvokeRequest,
+        request: &JobRequest,
+        context: &RunContext,
+        cmd_text: &str,
+        reason: String,
+    ) -> (NodeInvokeResult, Option<ExecEvent>) {
+        let (_, event) = self
+            .denied_system_run(invoke, request, context, cmd_text, reason.clone())
+            .await;
+        let result = NodeInvokeResult {
+            id: invoke.id.clone(),
+            node_id: self.node_id.clone(),
+            ok: false,
+            payload_json: None,
+            error: Some(InvokeError::new("PERMISSION_DENIED", reason)),
+        };
+        (result, event)
+    }
+
+    /// Refuse a system.run, recording it in the run store as rejected.
+    /// `context.caller_uid` is the session key.
+    async fn denied_system_run(
+        &self,
+        invoke: &NodeInvokeRequest,
+        request: &JobRequest,
+        context: &RunContext,
         cmd_text: &str,
         reason: String,
     ) -> (NodeInvokeResult, Option<ExecEvent>) {
+        if let Some(store) = &self.store {
+            store
+                .reject_run(request, &context.caller_uid, &context.rejected(&reason))
+                .await;
+        }
         let result = denied_run_result(reason.clone());
         let invoke_result = invoke_result_from_run(invoke, &self.node_id, &result);
         let event = ExecEvent {
             kind: ExecEventKind::Denied,
-            payload: exec_event_payload(session_key, run_id, cmd_text, &result, Some(reason)),
+            payload: exec_event_payload(
+                &context.caller_uid,
+                &request.job_id,
+                cmd_text,
+                &result,
+                Some(reason),
+            ),
         };
         (invoke_result, Some(event))
     }
@@ -464,25 +976,83 @@ impl OpenClawHandler {
             }
         };
 
-        let mut found: HashMap<String, String> = HashMap::new();
-        let base_path = env::var("PATH").unwrap_or_default();
-        let path_env =
-            crate::plugin_runtime::path_env::path_with_installed_runtime_bins_or_base(&base_path)
-                .await;
+        let path_env = match params.path {
+            Some(path) => path,
+            None => {
+                let base_path = env::var("PATH").unwrap_or_default();
+                crate::plugin_runtime::path_env::path_with_installed_runtime_bins_or_base(
+                    &base_path,
+                )
+                .await
+            }
+        };
         let path_dirs: Vec<PathBuf> = std::env::split_paths(&path_env).collect();
 
+        // (bin, lookup, whether it needs (re)caching)
+        let mut lookups: Vec<(String, Lookup, bool)> = Vec::new();
         for bin in &params.bins {
             let bin = bin.trim();
             if bin.is_empty() || bin.contains('/') || bin.contains('\\') {
                 continue;
             }
-
-            'dirs: for dir in &path_dirs {
-                if let Some(p) = which_in_dir(dir, bin) {
-                    found.insert(bin.to_string(), p.to_string_lossy().to_string());
-                    break 'dirs;
+            let cached = self
+                .which_cache_lock()
+                .get(bin, &path_env, std::time::Instant::now());
+            let (lookup, fresh) = match cached {
+                Some(lookup) => (lookup, false),
+                None => {
+                    let path = path_dirs
+                        .iter()
+                        .find_map(|dir| which_in_dir(dir, bin))
+                        .map(|p| p.to_string_lossy().to_string());
+                    (
+                        Lookup {
+                            path,
+                            version: None,
+                        },
+                        true,
+                    )
                 }
+            };
+            lookups.push((bin.to_string(), lookup, fresh));
+        }
+
+        // Probe every binary not probed recently at once, so a hanging one
+        // costs a single timeout.
+        if params.probe_version {
+            let timeout = self.version_probe_timeout;
+            let probes = lookups.iter_mut().filter_map(|(_, lookup, fresh)| {
+                let path = lookup.path.clone().filter(|_| lookup.version.is_none())?;
+                Some(async move {
+                    lookup.version = Some(probe_version(Path::new(&path), timeout).await);
+                    *fresh = true;
+                })
+            });
+            futures_util::future::join_all(probes).await;
+        }
+
+        let mut found: HashMap<String, WhichEntry> = HashMap::new();
+        for (bin, lookup, fresh) in lookups {
+            if fresh {
+                self.which_cache_lock().insert(
+                    &bin,
+                    &path_env,
+                    lookup.clone(),
+                    std::time::Instant::now(),
+                );
             }
+            let Some(path) = lookup.path else {
+                continue;
+            };
+            let entry = if params.probe_version {
+                WhichEntry::Probed {
+                    path,
+                    version: lookup.version.flatten(),
+                }
+            } else {
+                WhichEntry::Path(path)
+            };
+            found.insert(bin, entry);
         }
 
         let result = SystemWhichResult { bins: found };
@@ -496,10 +1066,25 @@ impl OpenClawHandler {
         }
     }
 
+    /// Handle system.info command
+    fn handle_system_info(&self, invoke: &NodeInvokeRequest) -> NodeInvokeResult {
+        NodeInvokeResult {
+            id: invoke.id.clone(),
+            node_id: self.node_id.clone(),
+            ok: true,
+            payload_json: Some(
+                serde_json::to_string(&self.host_info.current()).unwrap_or_default(),
+            ),
+            error: None,
+        }
+    }
+
     /// Handle system.execApprovals.get command
     async fn handle_exec_approvals_get(&self, invoke: &NodeInvokeRequest) -> NodeInvokeResult {
+        let mut seen_hash = self.exec_approvals_lock();
         match read_exec_approvals_snapshot(&self.exec_approvals_path) {
             Ok(snapshot) => {
+                seen_hash.clone_from(&snapshot.hash);
                 let redacted = ExecApprovalsSnapshot {
                     path: snapshot.path,
                     exists: snapshot.exists,
@@ -697,6 +1282,23 @@ impl OpenClawHandler {
                     self.browser_mgr.release_session(&session_id).await;
                 }
 
+                if let Some(store) = &self.store {
+                    let error = match (result.success, result.error.as_str()) {
+                        (true, _) => "",
+                        (false, "") => "browser command failed",
+                        (false, error) => error,
+                    };
+                    let artifact = result.output_path.as_deref().zip(result.output_mime());
+                    store.record_browser_run(
+                        &session_id,
+                        &action,
+                        &action_params_json,
+                        "openclaw",
+                        error,
+                        artifact,
+                    );
+                }
+
                 // Build OpenClaw-compatible response: { result, files }
                 // playwright-cli outputs plain text (not JSON), so we try
                 // JSON parse first (for backwards compat), then fall back to
@@ -757,13 +1359,25 @@ impl OpenClawHandler {
                     },
                 }
             }
-            Err(e) => NodeInvokeResult {
-                id: invoke.id.clone(),
-                node_id: self.node_id.clone(),
-                ok: false,
-                payload_json: None,
-                error: Some(InvokeError::new("INTERNAL", e.to_string())),
-            },
+            Err(e) => {
+                if let Some(store) = &self.store {
+                    store.record_browser_run(
+                        &session_id,
+                        &action,
+                        &action_params_json,
+                        "openclaw",
+                        &e.to_string(),
+                        None,
+                    );
+                }
+                NodeInvokeResult {
+                    id: invoke.id.clone(),
+                    node_id: self.node_id.clone(),
+                    ok: false,
+                    payload_json: None,
+                    error: Some(InvokeError::new("INTERNAL", e.to_string())),
+                }
+            }
         }
     }
 
@@ -783,6 +1397,7 @@ impl OpenClawHandler {
         };
 
         // Read current state to verify base hash
+        let mut seen_hash = self.exec_approvals_lock();
         let current = match read_exec_approvals_snapshot(&self.exec_approvals_path) {
             Ok(s) => s,
             Err(e) => {
@@ -838,6 +1453,7 @@ impl OpenClawHandler {
         // Read and return updated snapshot
         match read_exec_approvals_snapshot(&self.exec_approvals_path) {
             Ok(snapshot) => {
+                seen_hash.clone_from(&snapshot.hash);
                 let redacted = ExecApprovalsSnapshot {
                     path: snapshot.path,
                     exists: snapshot.exists,
@@ -897,7 +1513,66 @@ fn approval_disposition(params: &SystemRunParams) -> ApprovalDisposition {
     }
 }
 
-fn build_job_request(
+/// The command as the allowlist sees it: `rawCommand` or the formatted
+/// argv. `None` when it goes through the shell with control operators or
+/// substitutions, so a pattern like `git *` can't vouch for
+/// `git status; rm -rf ~`.
+fn allowlist_command(params: &SystemRunParams) -> Option<String> {
+    let direct = params.raw_command.is_none() && params.command.len() >= 2;
+    let command = match &params.raw_command {
+        Some(raw) => raw.clone(),
+        None => format_command(&params.command),
+    };
+    let chained = ['\n', ';', '&', '|', '`', '$', '<', '>', '(', ')'];
+    if !direct && command.contains(chained) {
+        return None;
+    }
+    Some(command)
+}
+
+/// The shell `system.run` runs single-string commands with.
+fn command_shell() -> String {
+    ahand_platform::shell::env_shell()
+        .unwrap_or_else(|| ahand_platform::shell::default_shell().path)
+}
+
+/// The job PolicyChecker judges for a system.run: the program and its
+/// arguments, or the shell with `-c <command>` when it runs through one.
+fn policy_job_request(
+    invoke: &NodeInvokeRequest,
+    params: &SystemRunParams,
+    run_id: &str,
+) -> JobRequest {
+    let mut request = build_job_request(invoke, params, run_id);
+    if params.raw_command.is_some() || params.command.len() < 2 {
+        let shell_cmd = params
+            .raw_command
+            .clone()
+            .unwrap_or_else(|| params.command.first().cloned().unwrap_or_default());
+        request.tool = command_shell();
+        request.args = vec![ahand_platform::shell::shell_c_flag().to_string(), shell_cmd];
+    }
+    request
+}
+
+/// How a finished system.run is recorded in the run store: its outcome
+/// kind and the error string, worded as the executor words them.
+fn stored_outcome(result: &RunResult) -> (OutcomeKind, &str) {
+    if result.timed_out {
+        return (OutcomeKind::Timeout, "timeout");
+    }
+    let error = result.error.as_deref().unwrap_or_default();
+    let cancelled = [CancelReason::Requested, CancelReason::Shutdown]
+        .iter()
+        .any(|reason| reason.as_error() == error);
+    if cancelled {
+        (OutcomeKind::Cancelled, error)
+    } else {
+        (OutcomeKind::Finished, error)
+    }
+}
+
+fn build_job_request(
     invoke: &NodeInvokeRequest,
     params: &SystemRunParams,
     run_id: &str,
@@ -921,8 +1596,13 @@ fn build_job_request(
         args,
         cwd: params.cwd.clone().unwrap_or_default(),
         env: params.env.clone().unwrap_or_default(),
-        timeout_ms: params.timeout_ms.or(invoke.timeout_ms).unwrap_or(120_000),
+        timeout_ms: params
+            .timeout_ms
+            .or(invoke.timeout_ms)
+            .unwrap_or(DEFAULT_RUN_TIMEOUT_MS),
         interactive: false,
+        priority: 0,
+        output_cap_bytes: 0,
     }
 }
 
@@ -933,6 +1613,8 @@ fn denied_run_result(reason: String) -> RunResult {
         success: false,
         stdout: String::new(),
         stderr: String::new(),
+        stdout_base64: None,
+        mime: None,
         error: Some(reason),
     }
 }
@@ -959,6 +1641,155 @@ fn invoke_result_from_run(
     }
 }
 
+/// The start of a command's stdout or stderr: at most `OUTPUT_CAP` bytes.
+#[derive(Debug, Default)]
+struct CapturedOutput {
+    bytes: Vec<u8>,
+    /// More than `OUTPUT_CAP` bytes were written.
+    truncated: bool,
+}
+
+/// Read `pipe` to the end in raw chunks, handing each to `on_chunk` and
+/// keeping the first `OUTPUT_CAP` bytes. Nothing is decoded here, so binary
+/// output can't break the read.
+async fn read_output(
+    pipe: Option<impl AsyncRead + Unpin>,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> CapturedOutput {
+    let mut output = CapturedOutput::default();
+    let Some(mut pipe) = pipe else {
+        return output;
+    };
+    let mut buf = vec![0u8; 8192];
+    loop {
+        let n = match pipe.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let chunk = &buf[..n];
+        on_chunk(chunk);
+        let room = OUTPUT_CAP.saturating_sub(output.bytes.len());
+        output.bytes.extend_from_slice(&chunk[..n.min(room)]);
+        output.truncated |= n > room;
+    }
+    output
+}
+
+/// Guess the MIME type of command output from its leading magic bytes.
+fn mime_from_magic(bytes: &[u8]) -> &'static str {
+    let ustar = bytes.get(257..262) == Some(b"ustar".as_slice());
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP".as_slice()) {
+        "image/webp"
+    } else if bytes.starts_with(b"%PDF-") {
+        "application/pdf"
+    } else if bytes.starts_with(b"\x1f\x8b") {
+        "application/gzip"
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        "application/zip"
+    } else if bytes.starts_with(b"BZh") {
+        "application/x-bzip2"
+    } else if bytes.starts_with(b"\xfd7zXZ\x00") {
+        "application/x-xz"
+    } else if bytes.starts_with(b"\x28\xb5\x2f\xfd") {
+        "application/zstd"
+    } else if ustar {
+        "application/x-tar"
+    } else if bytes.starts_with(b"\x7fELF") {
+        "application/x-executable"
+    } else if !bytes.is_empty() && std::str::from_utf8(bytes).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Kill a command's process group, falling back to the process alone when
+/// it has no pid (already reaped), then reap it.
+async fn kill_run(child: &mut tokio::process::Child, pid: Option<u32>) {
+    let killed = pid.is_some_and(|pid| {
+        ahand_platform::process::terminate_group(pid, ahand_platform::process::TerminateMode::Force)
+            .is_ok()
+    });
+    if !killed {
+        let _ = child.kill().await;
+    }
+    let _ = child.wait().await;
+}
+
+/// system.run timeout when the params don't set one.
+const DEFAULT_RUN_TIMEOUT_MS: u64 = 120_000;
+
+/// How often a running command's output tail is reported, if it has grown.
+const OUTPUT_EVENT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// New output that triggers a report before the next interval.
+const OUTPUT_EVENT_BYTES: usize = 16 * 1024;
+
+/// The output tail of a running command and how much of it is unreported.
+struct OutputProgress<F> {
+    tail: String,
+    unreported: usize,
+    on_output: F,
+}
+
+impl<F: FnMut(String)> OutputProgress<F> {
+    fn new(on_output: F) -> Self {
+        Self {
+            tail: String::new(),
+            unreported: 0,
+            on_output,
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        self.tail.push_str(line);
+        self.unreported += line.len();
+        if self.tail.len() > OUTPUT_EVENT_TAIL {
+            let mut cut = self.tail.len() - OUTPUT_EVENT_TAIL;
+            while !self.tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.tail.drain(..cut);
+        }
+        if self.unreported >= OUTPUT_EVENT_BYTES {
+            self.flush();
+        }
+    }
+
+    /// Report the tail if anything arrived since the last report.
+    fn flush(&mut self) {
+        if self.unreported > 0 {
+            self.unreported = 0;
+            (self.on_output)(self.tail.clone());
+        }
+    }
+}
+
+fn output_event_payload(
+    session_key: &str,
+    run_id: &str,
+    cmd_text: &str,
+    tail: String,
+) -> ExecEventPayload {
+    ExecEventPayload {
+        session_key: session_key.to_string(),
+        run_id: run_id.to_string(),
+        host: "node".to_string(),
+        command: Some(cmd_text.to_string()),
+        exit_code: None,
+        timed_out: None,
+        success: None,
+        output: Some(tail),
+        reason: None,
+    }
+}
+
 fn exec_event_payload(
     session_key: &str,
     run_id: &str,
@@ -1273,7 +2104,6 @@ fn which_in_dir(dir: &std::path::Path, bin: &str) -> Option<std::path::PathBuf>
     #[cfg(windows)]
     {
         // Only probe PATHEXT suffixes when the binary name has no extension.
-        use std::path::Path;
         if Path::new(bin).extension().is_none() {
             let pathext =
                 std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
@@ -1331,119 +2161,117 @@ fn truncate_output(raw: &str, max_chars: usize) -> String {
     }
 }
 
-/// Returns `true` iff `override_val` is a strict PATH-prepend of `base`:
-/// the entries of `base` must appear as a contiguous suffix of the entries
-/// in `override_val`.  Uses `std::env::split_paths` so the separator is
-/// platform-correct (`;` on Windows, `:` on Unix).
-///
-/// Note: `std::env::split_paths("")` yields a single empty-string entry on
-/// some platforms; we filter those out so that an empty-string input is
-/// treated as "no entries".
+/// The child PATH for a requested PATH override: `requested_prefix` then
+/// the daemon's own `base` PATH, so an override can only prepend.  An
+/// override already written as `prefix:base` composes to itself.  Uses
+/// `std::env::split_paths` so the separator is platform-correct (`;` on
+/// Windows, `:` on Unix).
 ///
-/// Decision for empty `base`: a non-empty `override_val` is accepted as
-/// "all entries are prepended to an empty base", mirroring the common case
-/// where the daemon starts with `PATH=""` (e.g. in a stripped sandbox).
-/// An empty `override_val` is rejected (nothing meaningful was prepended).
-///
-/// Windows note: case-variant base entries (e.g. `C:\Windows` vs `c:\windows`)
-/// fail the suffix equality check and the override is dropped, so the daemon's
-/// own PATH is used unchanged.  This is intentional fail-closed behaviour.
-fn path_override_is_prepend_only(override_val: &str, base: &str) -> bool {
+/// Returns `None` for an override without any entries (nothing to prepend).
+fn compose_path_override(override_val: &str, base: &str) -> Option<String> {
     // Filter out empty path entries produced by split_paths("").
     let non_empty = |p: &std::path::PathBuf| !p.as_os_str().is_empty();
-    let override_entries: Vec<_> = std::env::split_paths(override_val)
+    let mut prefix: Vec<_> = std::env::split_paths(override_val)
         .filter(non_empty)
         .collect();
-    let base_entries: Vec<_> = std::env::split_paths(base).filter(non_empty).collect();
-
-    if override_entries.is_empty() {
-        // An empty override adds nothing; reject.
-        return false;
+    if prefix.is_empty() {
+        return None;
     }
-
-    if base_entries.is_empty() {
-        // Empty base: any non-empty override is acceptable — all entries are
-        // "prepended" to an empty base.
-        return true;
+    let base_entries: Vec<_> = std::env::split_paths(base).filter(non_empty).collect();
+    if prefix.ends_with(&base_entries) {
+        prefix.truncate(prefix.len() - base_entries.len());
     }
+    let joined = std::env::join_paths(prefix.iter().chain(&base_entries)).ok()?;
+    Some(joined.to_string_lossy().into_owned())
+}
 
-    // The base entries must form a contiguous suffix of the override entries.
-    let base_len = base_entries.len();
-    let over_len = override_entries.len();
-    if base_len > over_len {
-        return false;
-    }
-    let suffix_start = over_len - base_len;
-    override_entries[suffix_start..] == base_entries[..]
+/// Variables a child env override may not set.
+const BLOCKED_ENV_KEYS: &[&str] = &[
+    "NODE_OPTIONS",
+    "PYTHONHOME",
+    "PYTHONPATH",
+    "PERL5LIB",
+    "PERL5OPT",
+    "RUBYOPT",
+    // Redirects the Python interpreter binary; a plugin could use it to
+    // run an attacker-controlled python executable when the daemon spawns
+    // python for a managed runtime.
+    "PYTHONEXECUTABLE",
+    // Auto-executes a script every time the Python interpreter starts;
+    // allows arbitrary code injection into any python subprocess the
+    // daemon spawns.
+    "PYTHONSTARTUP",
+    // Injects module-resolution directories into Node.js require(); a
+    // plugin could use it to preload attacker-controlled code when the
+    // daemon spawns node for a managed runtime.
+    "NODE_PATH",
+    // Auto-sourced by bash when invoked non-interactively (e.g. via $SHELL);
+    // allows arbitrary code injection into any bash subprocess the daemon spawns.
+    "BASH_ENV",
+    // On Windows, cmd.exe consults PATHEXT to resolve the extension of a bare
+    // command name; a cloud JobRequest could override it to make an approved bare
+    // command resolve to an attacker-controlled script extension instead of .EXE.
+    "PATHEXT",
+    // On Windows, %COMSPEC% names the command interpreter; a child-env override
+    // redirects shell spawns to an attacker-controlled binary when the daemon
+    // invokes the shell by that variable rather than a hard-coded path.
+    "COMSPEC",
+];
+
+const BLOCKED_ENV_PREFIXES: &[&str] = &["DYLD_", "LD_"];
+
+/// Blocked as overrides, but passed on when the daemon's own environment
+/// has them: Windows sets both, and cmd.exe needs them to run anything.
+const INHERITED_BLOCKED_ENV_KEYS: &[&str] = &["PATHEXT", "COMSPEC"];
+
+fn is_blocked_env_key(key: &str) -> bool {
+    let upper = key.to_uppercase();
+    BLOCKED_ENV_KEYS.iter().any(|k| upper == *k)
+        || BLOCKED_ENV_PREFIXES.iter().any(|p| upper.starts_with(p))
 }
 
-/// Sanitize environment variables
+/// Sanitize environment variables: the daemon's environment with
+/// `overrides` applied, as the complete environment for a child.
 fn sanitize_env(overrides: &HashMap<String, String>) -> HashMap<String, String> {
-    const BLOCKED_KEYS: &[&str] = &[
-        "NODE_OPTIONS",
-        "PYTHONHOME",
-        "PYTHONPATH",
-        "PERL5LIB",
-        "PERL5OPT",
-        "RUBYOPT",
-        // Redirects the Python interpreter binary; a plugin could use it to
-        // run an attacker-controlled python executable when the daemon spawns
-        // python for a managed runtime.
-        "PYTHONEXECUTABLE",
-        // Auto-executes a script every time the Python interpreter starts;
-        // allows arbitrary code injection into any python subprocess the
-        // daemon spawns.
-        "PYTHONSTARTUP",
-        // Injects module-resolution directories into Node.js require(); a
-        // plugin could use it to preload attacker-controlled code when the
-        // daemon spawns node for a managed runtime.
-        "NODE_PATH",
-        // Auto-sourced by bash when invoked non-interactively (e.g. via $SHELL);
-        // allows arbitrary code injection into any bash subprocess the daemon spawns.
-        "BASH_ENV",
-        // On Windows, cmd.exe consults PATHEXT to resolve the extension of a bare
-        // command name; a cloud JobRequest could override it to make an approved bare
-        // command resolve to an attacker-controlled script extension instead of .EXE.
-        "PATHEXT",
-        // On Windows, %COMSPEC% names the command interpreter; a child-env override
-        // redirects shell spawns to an attacker-controlled binary when the daemon
-        // invokes the shell by that variable rather than a hard-coded path.
-        "COMSPEC",
-    ];
-
-    const BLOCKED_PREFIXES: &[&str] = &["DYLD_", "LD_"];
-
-    let base_path = env::var("PATH").unwrap_or_default();
-    let mut result: HashMap<String, String> = env::vars().collect();
+    sanitize_env_from(env::vars(), overrides)
+}
 
-    for (key, value) in overrides {
-        let upper = key.to_uppercase();
+/// [`sanitize_env`] over an explicit `base` environment.  Blocked variables
+/// are dropped from `base` as well as from `overrides`, so a loader or
+/// interpreter hook set on the daemon itself doesn't reach the child.
+fn sanitize_env_from(
+    base: impl IntoIterator<Item = (String, String)>,
+    overrides: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut result: HashMap<String, String> = base
+        .into_iter()
+        .filter(|(key, _)| {
+            !is_blocked_env_key(key)
+                || INHERITED_BLOCKED_ENV_KEYS
+                    .iter()
+                    .any(|k| key.eq_ignore_ascii_case(k))
+        })
+        .collect();
+    let base_path = crate::plugin_runtime::path_env::path_env_value(&result)
+        .unwrap_or_default()
+        .to_string();
 
+    for (key, value) in overrides {
         // Handle PATH specially
-        if upper == "PATH" {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-            // Only allow PATH overrides that strictly prepend to the current
-            // PATH (platform-correct: split_paths uses `;` on Windows, `:` on
-            // Unix).  Overrides that replace or reorder existing entries are
-            // rejected to prevent DLL/binary hijacking.
-            if path_override_is_prepend_only(trimmed, &base_path) {
+        if crate::plugin_runtime::path_env::is_path_env_key(key) {
+            // Only ever prepend to the daemon's PATH (never replace or drop
+            // its entries) to prevent DLL/binary hijacking of system tools.
+            if let Some(path) = compose_path_override(value.trim(), &base_path) {
                 result.retain(|existing_key, _| {
                     !crate::plugin_runtime::path_env::is_path_env_key(existing_key)
                 });
-                result.insert("PATH".to_string(), value.clone());
+                result.insert("PATH".to_string(), path);
             }
             continue;
         }
 
         // Block dangerous env vars
-        if BLOCKED_KEYS.iter().any(|k| upper == *k) {
-            continue;
-        }
-
-        if BLOCKED_PREFIXES.iter().any(|p| upper.starts_with(p)) {
+        if is_blocked_env_key(key) {
             continue;
         }
 
@@ -1455,17 +2283,21 @@ fn sanitize_env(overrides: &HashMap<String, String>) -> HashMap<String, String>
 
 #[cfg(test)]
 mod tests {
-    use super::{ExecEventKind, OpenClawHandler};
+    use super::{ExecEvent, ExecEventKind, OpenClawHandler, now_ms};
     use crate::approval::ApprovalManager;
     use crate::browser::BrowserManager;
     use crate::config::BrowserConfig;
+    use crate::config::PolicyConfig;
+    use crate::openclaw::protocol::{AllowlistEntry, ExecApprovalsFile};
+    use crate::policy::PolicyChecker;
     use crate::registry::JobRegistry;
     use crate::session::SessionManager;
+    use crate::store::RunStore;
     use ahand_protocol::{ApprovalResponse, SessionMode, envelope};
     use serde_json::json;
     use std::path::PathBuf;
     use std::sync::Arc;
-    use tokio::sync::broadcast;
+    use tokio::sync::{broadcast, mpsc};
 
     fn test_handler(
         approval_timeout_secs: u64,
@@ -1474,9 +2306,24 @@ mod tests {
         Arc<SessionManager>,
         Arc<ApprovalManager>,
         broadcast::Sender<ahand_protocol::Envelope>,
+    ) {
+        handler_with(
+            ApprovalManager::new(approval_timeout_secs),
+            BrowserConfig::default(),
+        )
+    }
+
+    fn handler_with(
+        approval_mgr: ApprovalManager,
+        browser: BrowserConfig,
+    ) -> (
+        OpenClawHandler,
+        Arc<SessionManager>,
+        Arc<ApprovalManager>,
+        broadcast::Sender<ahand_protocol::Envelope>,
     ) {
         let session_mgr = Arc::new(SessionManager::new(5));
-        let approval_mgr = Arc::new(ApprovalManager::new(approval_timeout_secs));
+        let approval_mgr = Arc::new(approval_mgr);
         let (approval_broadcast_tx, _) = broadcast::channel(8);
         let handler = OpenClawHandler::new(
             "device-test".to_string(),
@@ -1485,12 +2332,35 @@ mod tests {
             approval_mgr.clone(),
             approval_broadcast_tx.clone(),
             None,
-            None,
-            Arc::new(BrowserManager::new(BrowserConfig::default())),
+            // Never the user's ~/.ahand/exec-approvals.json.
+            Some(unique_output_path().join("exec-approvals.json")),
+            Arc::new(BrowserManager::new(browser)),
         );
         (handler, session_mgr, approval_mgr, approval_broadcast_tx)
     }
 
+    fn write_exec_approvals(handler: &OpenClawHandler, file: &ExecApprovalsFile) {
+        super::save_exec_approvals(&handler.exec_approvals_path, file).unwrap();
+    }
+
+    fn read_exec_approvals(handler: &OpenClawHandler) -> ExecApprovalsFile {
+        super::read_exec_approvals_snapshot(&handler.exec_approvals_path)
+            .unwrap()
+            .file
+    }
+
+    fn allowlist_file(pattern: &str) -> ExecApprovalsFile {
+        ExecApprovalsFile {
+            allowlist: Some(vec![AllowlistEntry {
+                pattern: pattern.to_string(),
+                agent_id: None,
+                last_used_ms: None,
+                use_count: None,
+            }]),
+            ..Default::default()
+        }
+    }
+
     fn unique_output_path() -> PathBuf {
         std::env::temp_dir().join(format!("ahand-openclaw-{}", uuid::Uuid::new_v4()))
     }
@@ -1563,6 +2433,28 @@ mod tests {
         serde_json::from_str(result.payload_json.as_deref().unwrap()).unwrap()
     }
 
+    /// Run `invoke`, returning its result and every exec event it emitted.
+    async fn run_invoke_with_events(
+        handler: &OpenClawHandler,
+        invoke: super::NodeInvokeRequest,
+    ) -> (super::NodeInvokeResult, Vec<ExecEvent>) {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let result = handler.handle_invoke(invoke, &events_tx).await;
+        let events = std::iter::from_fn(|| events_rx.try_recv().ok()).collect();
+        (result, events)
+    }
+
+    /// Run an invoke expected to emit at most its final exec event.
+    async fn run_invoke(
+        handler: &OpenClawHandler,
+        invoke: super::NodeInvokeRequest,
+    ) -> (super::NodeInvokeResult, Option<ExecEvent>) {
+        let (result, mut events) = run_invoke_with_events(handler, invoke).await;
+        let last = events.pop();
+        assert!(events.is_empty(), "unexpected events: {events:?}");
+        (result, last)
+    }
+
     #[tokio::test]
     async fn inactive_session_denies_system_run_without_execution() {
         let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
@@ -1571,9 +2463,11 @@ mod tests {
         // the helper cross-platform-portable for consistency.
         let command = write_marker_command(&output_path);
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke("session-1", command, None, None))
-            .await;
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, None, None),
+        )
+        .await;
 
         assert!(!output_path.exists());
         let payload = payload_json(&result);
@@ -1604,9 +2498,11 @@ mod tests {
         let output_path = unique_output_path();
         let command = write_marker_command(&output_path);
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke("session-1", command, Some(true), None))
-            .await;
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, Some(true), None),
+        )
+        .await;
 
         assert!(output_path.exists());
         let payload = payload_json(&result);
@@ -1646,15 +2542,16 @@ mod tests {
                 .resolve(&ApprovalResponse {
                     job_id: request.job_id,
                     approved: true,
-                    remember: false,
-                    reason: String::new(),
+                    ..Default::default()
                 })
                 .await;
         });
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke("session-1", command, None, None))
-            .await;
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, None, None),
+        )
+        .await;
 
         resolver.await.unwrap();
         assert!(output_path.exists());
@@ -1687,15 +2584,17 @@ mod tests {
                 .resolve(&ApprovalResponse {
                     job_id: request.job_id,
                     approved: false,
-                    remember: false,
                     reason: "operator rejected".to_string(),
+                    ..Default::default()
                 })
                 .await;
         });
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke("session-1", command, None, None))
-            .await;
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, None, None),
+        )
+        .await;
 
         resolver.await.unwrap();
         assert!(!output_path.exists());
@@ -1712,9 +2611,11 @@ mod tests {
         let _ = std::fs::remove_file(output_path);
     }
 
+    /// The wait is bounded by the invoke's own timeout (1s here), not the
+    /// approval manager's default.
     #[tokio::test]
     async fn strict_mode_timeout_broadcasts_request_and_does_not_execute() {
-        let (handler, session_mgr, _approval_mgr, approval_broadcast_tx) = test_handler(0);
+        let (handler, session_mgr, _approval_mgr, approval_broadcast_tx) = test_handler(600);
         session_mgr
             .set_mode("session-1", SessionMode::Strict, 0)
             .await;
@@ -1724,14 +2625,23 @@ mod tests {
         // helper cross-platform-portable and consistent with sibling tests.
         let command = write_marker_command(&output_path);
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke("session-1", command, None, None))
-            .await;
+        let started = std::time::Instant::now();
+        let (result, event) = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            run_invoke(
+                &handler,
+                system_run_invoke("session-1", command, None, None),
+            ),
+        )
+        .await
+        .expect("approval wait ignored the invoke timeout");
+        assert!(started.elapsed() >= std::time::Duration::from_millis(900));
 
         let envelope = approval_rx.recv().await.unwrap();
         match envelope.payload.unwrap() {
             envelope::Payload::ApprovalRequest(request) => {
                 assert_eq!(request.caller_uid, "session-1");
+                assert!(request.expires_ms <= now_ms() + 1_000);
             }
             other => panic!("unexpected payload: {other:?}"),
         }
@@ -1746,255 +2656,1124 @@ mod tests {
         let _ = std::fs::remove_file(output_path);
     }
 
+    /// A gateway allow-always runs without asking locally. A shell command
+    /// isn't remembered: that would approve every later one.
     #[tokio::test]
-    async fn execution_failures_emit_finished_events_not_denied() {
-        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+    async fn gateway_allow_always_runs_a_shell_command_without_remembering_it() {
+        let policy = Arc::new(crate::policy::PolicyChecker::new(
+            &crate::config::PolicyConfig::default(),
+        ));
+        let (handler, session_mgr, _approval_mgr, approval_broadcast_tx) = handler_with(
+            ApprovalManager::new(600).with_policy(Arc::clone(&policy)),
+            BrowserConfig::default(),
+        );
         session_mgr
-            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .set_mode("session-1", SessionMode::Strict, 0)
             .await;
+        let mut approval_rx = approval_broadcast_tx.subscribe();
+        let output_path = unique_output_path();
+        let command = write_marker_command(&output_path);
 
-        let (result, event) = handler
-            .handle_invoke(system_run_invoke(
-                "session-1",
-                "exit 7".to_string(),
-                None,
-                None,
-            ))
-            .await;
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, None, Some("allow-always")),
+        )
+        .await;
 
-        let payload = payload_json(&result);
-        assert_eq!(payload["success"], false);
-        assert_eq!(payload["exitCode"], 7);
+        assert!(output_path.exists());
+        assert_eq!(payload_json(&result)["success"], true);
         assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+        assert!(approval_rx.try_recv().is_err());
+        assert!(policy.remembered().await.is_empty());
+
+        let _ = std::fs::remove_file(output_path);
     }
 
-    /// Build an invoke request that uses a true argv array (no rawCommand).
-    /// Used to test the direct-spawn path. Its only caller is the
-    /// unix-gated ampersand test, so gate it the same way.
+    /// A gateway allow-always for a direct argv is remembered under the
+    /// program, so later requests for it pass the policy.
     #[cfg(unix)]
-    fn array_command_invoke(
-        session_key: &str,
-        argv: Vec<&str>,
-        approved: Option<bool>,
-    ) -> super::NodeInvokeRequest {
-        let mut params = serde_json::Map::new();
-        let cmd_json: serde_json::Value = argv.iter().map(|s| json!(s)).collect();
-        params.insert("command".into(), cmd_json);
-        params.insert("sessionKey".into(), json!(session_key));
-        params.insert("runId".into(), json!("run-arr-1"));
-        if let Some(approved) = approved {
-            params.insert("approved".into(), json!(approved));
-        }
+    #[tokio::test]
+    async fn gateway_allow_always_for_a_program_is_remembered() {
+        let policy = Arc::new(crate::policy::PolicyChecker::new(
+            &crate::config::PolicyConfig::default(),
+        ));
+        let (handler, session_mgr, _approval_mgr, _approval_broadcast_tx) = handler_with(
+            ApprovalManager::new(600).with_policy(Arc::clone(&policy)),
+            BrowserConfig::default(),
+        );
+        session_mgr
+            .set_mode("session-1", SessionMode::Strict, 0)
+            .await;
+        let mut invoke = array_command_invoke("session-1", vec!["true", "--flag"], None);
+        let mut params: serde_json::Value =
+            serde_json::from_str(invoke.params_json.as_deref().unwrap()).unwrap();
+        params["approvalDecision"] = json!("allow-always");
+        invoke.params_json = Some(params.to_string());
+
+        let (result, _) = run_invoke(&handler, invoke).await;
+
+        assert_eq!(payload_json(&result)["success"], true);
+        let remembered = policy.remembered().await;
+        assert!(
+            remembered
+                .iter()
+                .any(|r| r.caller_uid == "session-1" && r.key == "tool:true"),
+            "{remembered:?}"
+        );
+    }
+
+    /// An allowlisted command runs and counts the use without asking.
+    /// Strict mode would otherwise wait for an operator.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn allowlisted_command_runs_without_approval_and_counts_the_use() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::Strict, 0)
+            .await;
+        write_exec_approvals(&handler, &allowlist_file("touch *"));
+        let output_path = unique_output_path();
+        let command = format!("touch '{}'", output_path.display());
+
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, None, None),
+        )
+        .await;
+
+        assert_eq!(payload_json(&result)["success"], true);
+        assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+        assert!(output_path.exists());
+        let entry = &read_exec_approvals(&handler).allowlist.unwrap()[0];
+        assert_eq!(entry.use_count, Some(1));
+        assert!(entry.last_used_ms.is_some());
+        let _ = std::fs::remove_file(output_path);
+    }
+
+    fn exec_approvals_invoke(command: &str, params: serde_json::Value) -> super::NodeInvokeRequest {
         super::NodeInvokeRequest {
-            id: "invoke-arr-1".to_string(),
+            id: "invoke-approvals".to_string(),
             node_id: "node-1".to_string(),
-            command: "system.run".to_string(),
-            params_json: Some(serde_json::Value::Object(params).to_string()),
-            timeout_ms: Some(5_000),
+            command: command.to_string(),
+            params_json: Some(params.to_string()),
+            timeout_ms: None,
             idempotency_key: None,
         }
     }
 
-    // ── which_in_dir tests ────────────────────────────────────────────────────
+    #[tokio::test]
+    async fn system_info_reports_host_facts() {
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let invoke = super::NodeInvokeRequest {
+            id: "invoke-info".to_string(),
+            node_id: "node-1".to_string(),
+            command: "system.info".to_string(),
+            params_json: None,
+            timeout_ms: None,
+            idempotency_key: None,
+        };
 
-    #[test]
-    fn which_in_dir_finds_exact_match() {
-        let tmp = tempfile::tempdir().unwrap();
-        let bin_path = tmp.path().join("mybinary");
-        std::fs::write(&bin_path, b"").unwrap();
-        let result = super::which_in_dir(tmp.path(), "mybinary");
-        assert_eq!(result, Some(bin_path));
-    }
+        let (result, event) = run_invoke(&handler, invoke).await;
 
-    #[test]
-    fn which_in_dir_returns_none_when_missing() {
-        let tmp = tempfile::tempdir().unwrap();
-        let result = super::which_in_dir(tmp.path(), "doesnotexist");
-        assert!(result.is_none());
+        assert!(result.ok, "{:?}", result.error);
+        assert!(event.is_none());
+        let payload = payload_json(&result);
+        assert_eq!(payload["os"], std::env::consts::OS);
+        assert_eq!(payload["arch"], std::env::consts::ARCH);
+        assert_eq!(payload["daemonVersion"], env!("CARGO_PKG_VERSION"));
+        assert!(payload["cpuCount"].as_u64().is_some_and(|n| n >= 1));
     }
 
-    /// On Windows, a binary without an extension should be found when probing
-    /// PATHEXT extensions. Gate to cfg(windows) since we need actual .exe files
-    /// to exist and PATHEXT semantics are Windows-specific.
-    #[cfg(windows)]
-    #[test]
-    fn which_in_dir_finds_pathext_extension_on_windows() {
-        let tmp = tempfile::tempdir().unwrap();
-        // Create "mybinary.exe" — PATHEXT typically contains .EXE
-        let bin_path = tmp.path().join("mybinary.exe");
-        std::fs::write(&bin_path, b"").unwrap();
-        // Query without extension. PATHEXT entries are typically upper-case
-        // (".EXE") and NTFS is case-insensitive, so the returned path may
-        // carry the PATHEXT casing — compare case-insensitively.
-        let result = super::which_in_dir(tmp.path(), "mybinary");
-        let found = result.expect("should find mybinary.exe via PATHEXT");
-        assert_eq!(
-            found.to_string_lossy().to_lowercase(),
-            bin_path.to_string_lossy().to_lowercase(),
-            "should find mybinary.exe via PATHEXT (case-insensitive)"
-        );
+    #[tokio::test]
+    async fn exec_approvals_edited_on_disk_are_served_and_reported_once() {
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        write_exec_approvals(&handler, &allowlist_file("ls *"));
+        let (result, _event) = run_invoke(
+            &handler,
+            exec_approvals_invoke("system.execApprovals.get", json!({})),
+        )
+        .await;
+        let before = payload_json(&result)["hash"].as_str().unwrap().to_string();
+        assert!(handler.poll_exec_approvals().is_none());
+
+        write_exec_approvals(&handler, &allowlist_file("git *"));
+
+        let (result, _event) = run_invoke(
+            &handler,
+            exec_approvals_invoke("system.execApprovals.get", json!({})),
+        )
+        .await;
+        let payload = payload_json(&result);
+        let after = payload["hash"].as_str().unwrap().to_string();
+        assert_ne!(after, before);
+        assert_eq!(payload["file"]["allowlist"][0]["pattern"], "git *");
+
+        write_exec_approvals(&handler, &allowlist_file("cargo *"));
+        let changed = handler.poll_exec_approvals().expect("change reported");
+        assert!(changed.exists);
+        assert_ne!(changed.hash, after);
+        assert!(handler.poll_exec_approvals().is_none());
     }
 
-    #[cfg(windows)]
-    #[test]
-    fn which_in_dir_already_has_extension_does_not_double_probe() {
-        let tmp = tempfile::tempdir().unwrap();
-        let bin_path = tmp.path().join("mybinary.exe");
-        std::fs::write(&bin_path, b"").unwrap();
-        // Query WITH extension — should find via exact match, not PATHEXT loop.
-        let result = super::which_in_dir(tmp.path(), "mybinary.exe");
-        assert_eq!(result, Some(bin_path));
+    #[tokio::test]
+    async fn exec_approvals_set_is_not_reported_as_a_change() {
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        write_exec_approvals(&handler, &allowlist_file("ls *"));
+        let (result, _event) = run_invoke(
+            &handler,
+            exec_approvals_invoke("system.execApprovals.get", json!({})),
+        )
+        .await;
+        let base_hash = payload_json(&result)["hash"].clone();
+
+        write_exec_approvals(&handler, &allowlist_file("git *"));
+        let (result, _event) = run_invoke(
+            &handler,
+            exec_approvals_invoke(
+                "system.execApprovals.set",
+                json!({ "file": allowlist_file("cargo *"), "baseHash": base_hash }),
+            ),
+        )
+        .await;
+        assert!(!result.ok, "a set against a stale hash must fail");
+        let changed = handler.poll_exec_approvals().expect("change reported");
+
+        let (result, _event) = run_invoke(
+            &handler,
+            exec_approvals_invoke(
+                "system.execApprovals.set",
+                json!({ "file": allowlist_file("cargo *"), "baseHash": changed.hash }),
+            ),
+        )
+        .await;
+        assert!(result.ok, "{:?}", result.error);
+        assert!(handler.poll_exec_approvals().is_none());
     }
 
-    /// Array-form commands spawn directly (no shell), so an argument containing
-    /// `&` is passed verbatim to the child process rather than interpreted by a
-    /// shell.  We verify by running `echo` with an arg that contains `&foo` and
-    /// asserting the output contains the literal ampersand — if it were passed
-    /// to a shell the `&` would fork a background job instead.
-    ///
-    /// This test covers Unix only because `echo` semantics on Windows differ;
-    /// the security property (no shell involved) applies on all platforms.
     #[cfg(unix)]
     #[tokio::test]
-    async fn array_command_with_ampersand_does_not_spawn_second_command() {
+    async fn explicit_denial_overrides_the_allowlist() {
         let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
         session_mgr
-            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .set_mode("session-1", SessionMode::Strict, 0)
             .await;
+        write_exec_approvals(&handler, &allowlist_file("touch *"));
+        let output_path = unique_output_path();
+        let command = format!("touch '{}'", output_path.display());
 
-        // The arg "hello&world" must arrive verbatim; a shell would interpret
-        // `&` as a command separator and the output would just be "hello".
-        let invoke = array_command_invoke("session-1", vec!["echo", "hello&world"], None);
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", command, Some(false), None),
+        )
+        .await;
+
+        assert_eq!(payload_json(&result)["error"], "approval denied");
+        assert_eq!(event.unwrap().kind, ExecEventKind::Denied);
+        assert!(!output_path.exists());
+        let entry = &read_exec_approvals(&handler).allowlist.unwrap()[0];
+        assert_eq!(entry.use_count, None);
+    }
+
+    /// With `security = "allowlist"` and `ask = "off"` misses are refused
+    /// outright, even in auto-accept sessions, and a pattern can't vouch
+    /// for a chained shell command.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn allowlist_only_security_denies_misses_and_chained_commands() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let mut file = allowlist_file("touch *");
+        file.security = Some("allowlist".to_string());
+        file.ask = Some("off".to_string());
+        write_exec_approvals(&handler, &file);
+        let output_path = unique_output_path();
+
+        for command in [
+            format!("printf X > '{}'", output_path.display()),
+            format!("touch /dev/null; touch '{}'", output_path.display()),
+        ] {
+            let (result, event) = run_invoke(
+                &handler,
+                system_run_invoke("session-1", command, None, None),
+            )
+            .await;
+            assert_eq!(
+                payload_json(&result)["error"],
+                "command not in exec approvals allowlist"
+            );
+            assert_eq!(event.unwrap().kind, ExecEventKind::Denied);
+        }
+        assert!(!output_path.exists());
+    }
+
+    fn with_policy(handler: OpenClawHandler, config: PolicyConfig) -> OpenClawHandler {
+        handler.with_policy(Arc::new(PolicyChecker::new(&config)))
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn policy_denial_wins_over_the_allowlist() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let handler = with_policy(
+            handler,
+            PolicyConfig {
+                denied_tools: vec!["touch".to_string()],
+                ..Default::default()
+            },
+        );
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        write_exec_approvals(&handler, &allowlist_file("touch *"));
+        let output_path = unique_output_path();
+        let path = output_path.display().to_string();
+
+        let (result, event) = run_invoke(
+            &handler,
+            array_command_invoke("session-1", vec!["touch", &path], None),
+        )
+        .await;
+
+        assert!(!result.ok);
+        assert_eq!(result.error.unwrap().code, "PERMISSION_DENIED");
+        assert_eq!(event.unwrap().kind, ExecEventKind::Denied);
+        assert!(!output_path.exists());
+    }
+
+    /// A tool outside `allowed_tools` needs approval even in an auto-accept
+    /// session; the allowlist or the gateway can give it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn policy_approval_is_satisfied_by_the_allowlist_or_the_gateway() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let handler = with_policy(
+            handler,
+            PolicyConfig {
+                allowed_tools: vec!["echo".to_string()],
+                ..Default::default()
+            },
+        );
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let output_path = unique_output_path();
+        let path = output_path.display().to_string();
+
+        let (result, event) = run_invoke(
+            &handler,
+            array_command_invoke("session-1", vec!["touch", &path], Some(false)),
+        )
+        .await;
+        assert_eq!(payload_json(&result)["error"], "approval denied");
+        assert_eq!(event.unwrap().kind, ExecEventKind::Denied);
+        assert!(!output_path.exists());
+
+        write_exec_approvals(&handler, &allowlist_file("touch *"));
+        let (result, event) = run_invoke(
+            &handler,
+            array_command_invoke("session-1", vec!["touch", &path], None),
+        )
+        .await;
+        assert_eq!(payload_json(&result)["success"], true);
+        assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+        assert!(output_path.exists());
+        let _ = std::fs::remove_file(output_path);
+    }
+
+    /// Output is reported while the command still runs, and the final
+    /// event keeps its usual shape after it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn long_running_command_streams_output_before_finishing() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        // Over three times OUTPUT_EVENT_BYTES, then a pause so it is
+        // reported before the command ends. Output is read in chunks, so
+        // reports may land anywhere within it.
+        let command = "printf '%050000d\\n' 0; sleep 0.5; echo done".to_string();
+
+        let (result, events) = run_invoke_with_events(
+            &handler,
+            system_run_invoke("session-1", command, None, None),
+        )
+        .await;
+
+        assert_eq!(payload_json(&result)["success"], true);
+        let (finished, progress) = events.split_last().unwrap();
+        assert_eq!(finished.kind, ExecEventKind::Finished);
+        assert!(!progress.is_empty());
+        assert!(progress.iter().all(|e| e.kind == ExecEventKind::Output));
+        let progress = &progress.last().unwrap().payload;
+        let tail = progress.output.as_deref().unwrap();
+        assert_eq!(tail.len(), super::OUTPUT_EVENT_TAIL);
+        assert!(tail.trim_end().chars().all(|c| c == '0'));
+        assert_eq!(progress.run_id, "run-1");
+        assert_eq!(progress.success, None);
+        assert!(
+            finished
+                .payload
+                .output
+                .as_deref()
+                .unwrap()
+                .ends_with("done\n")
+        );
+    }
 
-        let (result, _event) = handler.handle_invoke(invoke).await;
+    #[test]
+    fn output_progress_reports_a_bounded_tail_once_per_batch() {
+        let mut reports = Vec::new();
+        let mut progress = super::OutputProgress::new(|tail| reports.push(tail));
+        progress.flush();
+        progress.push("short\n");
+        progress.flush();
+        progress.flush();
+        // Each line is OUTPUT_EVENT_BYTES long, in two-byte characters.
+        let line = "é".repeat(super::OUTPUT_EVENT_BYTES / 2);
+        progress.push(&line);
+        progress.push(&line);
+        progress.flush();
+        drop(progress);
+
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0], "short\n");
+        assert_eq!(reports[1], format!("short\n{line}"));
+        // Cut to the tail bound without splitting a character.
+        assert_eq!(reports[2].len(), super::OUTPUT_EVENT_TAIL);
+        assert!(reports[2].chars().all(|c| c == 'é'));
+    }
+
+    fn system_cancel_invoke(run_id: &str) -> super::NodeInvokeRequest {
+        super::NodeInvokeRequest {
+            id: "invoke-cancel".to_string(),
+            node_id: "node-1".to_string(),
+            command: "system.cancel".to_string(),
+            params_json: Some(json!({ "runId": run_id }).to_string()),
+            timeout_ms: None,
+            idempotency_key: None,
+        }
+    }
+
+    async fn wait_until_running(handler: &OpenClawHandler, run_id: &str) {
+        while !handler.runs_lock().contains(run_id) {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    }
+
+    /// system.cancel stops the whole process group promptly and the run
+    /// finishes as cancelled, not timed out.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_cancel_kills_a_running_command() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let run = system_run_invoke("session-1", "sleep 100 & sleep 100".to_string(), None, None);
+
+        let started = std::time::Instant::now();
+        let ((result, event), cancel) =
+            tokio::time::timeout(std::time::Duration::from_secs(10), async {
+                tokio::join!(run_invoke(&handler, run), async {
+                    wait_until_running(&handler, "run-1").await;
+                    run_invoke(&handler, system_cancel_invoke("run-1")).await
+                })
+            })
+            .await
+            .expect("cancelled command kept running");
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(payload_json(&cancel.0)["cancelled"], true);
+        assert!(cancel.1.is_none());
         let payload = payload_json(&result);
+        assert_eq!(payload["success"], false);
+        assert_eq!(payload["timedOut"], false);
+        assert_eq!(payload["error"], "cancelled");
+        assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+        assert!(handler.runs_lock().is_empty());
+        assert!(!handler.registry.is_running("run-1").await);
+
+        // Nothing is left to cancel.
+        let (again, _) = run_invoke(&handler, system_cancel_invoke("run-1")).await;
+        assert_eq!(payload_json(&again)["cancelled"], false);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cancel_runs_stops_every_running_command() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let run = system_run_invoke("session-1", "sleep 100".to_string(), None, None);
+
+        let ((result, _), cancelled) =
+            tokio::time::timeout(std::time::Duration::from_secs(10), async {
+                tokio::join!(run_invoke(&handler, run), async {
+                    wait_until_running(&handler, "run-1").await;
+                    handler.cancel_runs().await
+                })
+            })
+            .await
+            .expect("cancelled command kept running");
+
+        assert_eq!(cancelled, 1);
+        assert_eq!(payload_json(&result)["error"], "cancelled");
+    }
+
+    /// A command outliving the invoke's budget is killed and answered with
+    /// TIMEOUT, even though its own timeout is longer.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_run_is_bounded_by_the_invoke_timeout() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let mut run = system_run_invoke("session-1", "sleep 100".to_string(), None, None);
+        run.timeout_ms = Some(300);
+
+        let started = std::time::Instant::now();
+        let (result, event) = run_invoke(&handler, run).await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(!result.ok);
+        let error = result.error.unwrap();
+        assert_eq!(error.code, "TIMEOUT");
+        assert_eq!(error.message, "node-side timeout");
+        let event = event.unwrap();
+        assert_eq!(event.kind, ExecEventKind::Finished);
+        assert_eq!(event.payload.timed_out, Some(true));
+        assert!(!handler.registry.is_running("run-1").await);
+    }
+
+    /// browser.proxy is cut off at the invoke's budget, and the CLI it
+    /// started does not outlive it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn browser_proxy_is_bounded_by_the_invoke_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("cli.pid");
+        let cli = dir.path().join("playwright-cli");
+        std::fs::write(
+            &cli,
+            format!(
+                "#!/bin/sh\necho $$ > '{}'\nexec sleep 100\n",
+                pid_file.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = handler_with(
+            ApprovalManager::new(1),
+            BrowserConfig {
+                enabled: Some(true),
+                binary_path: Some(cli.display().to_string()),
+                downloads_dir: Some(dir.path().display().to_string()),
+                ..Default::default()
+            },
+        );
+        let invoke = super::NodeInvokeRequest {
+            id: "invoke-browser".to_string(),
+            node_id: "node-1".to_string(),
+            command: "browser.proxy".to_string(),
+            params_json: Some(json!({ "method": "GET", "path": "/snapshot" }).to_string()),
+            timeout_ms: Some(500),
+            idempotency_key: None,
+        };
+
+        let started = std::time::Instant::now();
+        let (result, event) = run_invoke(&handler, invoke).await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(event.is_none());
+        assert_eq!(result.error.unwrap().code, "TIMEOUT");
+        let pid: u32 = std::fs::read_to_string(&pid_file)
+            .expect("browser CLI never started")
+            .trim()
+            .parse()
+            .unwrap();
+        for _ in 0..50 {
+            if !ahand_platform::process::is_process_running(pid) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("browser CLI {pid} outlived the invoke");
+    }
+
+    fn with_store(mut handler: OpenClawHandler, data_dir: &std::path::Path) -> OpenClawHandler {
+        handler.store = Some(Arc::new(RunStore::new(data_dir, 0, 3).unwrap()));
+        handler
+    }
+
+    fn read_run_json(data_dir: &std::path::Path, run_id: &str, name: &str) -> serde_json::Value {
+        let text = std::fs::read_to_string(data_dir.join("runs").join(run_id).join(name))
+            .unwrap_or_else(|e| panic!("{run_id}/{name}: {e}"));
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_runs_are_persisted_by_run_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let handler = with_store(handler, dir.path());
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+
+        let (result, _event) = run_invoke(
+            &handler,
+            array_command_invoke("session-1", vec!["echo", "persisted"], None),
+        )
+        .await;
+        assert_eq!(payload_json(&result)["success"], true);
+
+        let request = read_run_json(dir.path(), "run-arr-1", "request.json");
+        assert_eq!(request["tool"], "echo");
+        assert_eq!(request["args"], json!(["persisted"]));
+        assert_eq!(request["caller_uid"], "session-1");
+        let stored = handler
+            .store
+            .as_ref()
+            .unwrap()
+            .read_output("run-arr-1", "stdout");
+        assert_eq!(stored, b"persisted\n");
+        let finished = read_run_json(dir.path(), "run-arr-1", "result.json");
+        assert_eq!(finished["exit_code"], 0);
+        assert_eq!(finished["outcome"]["kind"], "finished");
+        assert_eq!(finished["outcome"]["session_mode"], "auto_accept");
+    }
+
+    fn base64_output_invoke(raw_command: &str) -> super::NodeInvokeRequest {
+        let mut invoke = system_run_invoke("session-1", raw_command.to_string(), None, None);
+        let mut params: serde_json::Value =
+            serde_json::from_str(invoke.params_json.as_deref().unwrap()).unwrap();
+        params["outputEncoding"] = json!("base64");
+        invoke.params_json = Some(params.to_string());
+        invoke
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn binary_stdout_round_trips_as_base64() {
+        use base64::Engine;
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        // A PNG signature followed by bytes that are not valid UTF-8.
+        let blob: &[u8] = b"\x89PNG\r\n\x1a\n\x00\xff\xfe\x80\n";
+
+        let (result, _event) = run_invoke(
+            &handler,
+            base64_output_invoke(r"printf '\211PNG\r\n\032\n\000\377\376\200\n'; echo warning >&2"),
+        )
+        .await;
+        let payload = payload_json(&result);
+        assert_eq!(payload["success"], true);
+        assert_eq!(payload["stdout"], "");
+        assert_eq!(payload["stderr"], "warning\n");
+        assert_eq!(payload["mime"], "image/png");
+        let stdout = base64::engine::general_purpose::STANDARD
+            .decode(payload["stdoutBase64"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(stdout, blob);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn text_output_decodes_invalid_utf8_lossily() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+
+        let (result, _event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", r"printf 'ok\377\n'".to_string(), None, None),
+        )
+        .await;
+        let payload = payload_json(&result);
+        assert_eq!(payload["stdout"], "ok\u{FFFD}\n");
+        assert!(payload.get("stdoutBase64").is_none());
+        assert!(payload.get("mime").is_none());
+    }
+
+    #[test]
+    fn output_mime_is_guessed_from_magic_bytes() {
         assert_eq!(
-            payload["success"], true,
-            "direct-spawn echo should succeed: {:?}",
-            payload
+            super::mime_from_magic(b"\x1f\x8b\x08\x00"),
+            "application/gzip"
         );
-        let stdout = payload["stdout"].as_str().unwrap_or("").trim().to_string();
+        assert_eq!(super::mime_from_magic(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(super::mime_from_magic(b"\xff\xd8\xff\xe0"), "image/jpeg");
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        tar[0] = b'a';
+        assert_eq!(super::mime_from_magic(&tar), "application/x-tar");
+        assert_eq!(super::mime_from_magic(b"hello\n"), "text/plain");
         assert_eq!(
-            stdout, "hello&world",
-            "ampersand must be passed verbatim, not shell-interpreted; got: {stdout:?}"
+            super::mime_from_magic(b"\x00\xff"),
+            "application/octet-stream"
         );
     }
 
-    // ── path_override_is_prepend_only tests ──────────────────────────────────
+    #[tokio::test]
+    async fn denied_system_runs_are_persisted_as_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let handler = with_store(handler, dir.path());
 
-    /// Build a platform-correct PATH string from a list of directory strings
-    /// using std::env::join_paths so the separator is always correct.
-    fn join_path_entries(entries: &[&str]) -> String {
-        let paths: Vec<std::path::PathBuf> = entries.iter().map(std::path::PathBuf::from).collect();
-        std::env::join_paths(&paths)
+        let (result, _event) = run_invoke(
+            &handler,
+            array_command_invoke("session-1", vec!["echo", "never"], None),
+        )
+        .await;
+        assert_eq!(payload_json(&result)["success"], false);
+
+        let finished = read_run_json(dir.path(), "run-arr-1", "result.json");
+        assert_eq!(finished["exit_code"], -1);
+        assert_eq!(finished["outcome"]["kind"], "rejected");
+        assert_eq!(
+            finished["outcome"]["rejection_reason"],
+            "session not activated"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn browser_proxy_invocations_are_persisted_as_browser_runs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cli = dir.path().join("playwright-cli");
+        std::fs::write(&cli, "#!/bin/sh\necho 'no browser here' >&2\nexit 1\n").unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = handler_with(
+            ApprovalManager::new(1),
+            BrowserConfig {
+                enabled: Some(true),
+                binary_path: Some(cli.display().to_string()),
+                downloads_dir: Some(dir.path().join("downloads").display().to_string()),
+                ..Default::default()
+            },
+        );
+        let data_dir = dir.path().join("data");
+        let handler = with_store(handler, &data_dir);
+        let invoke = super::NodeInvokeRequest {
+            id: "invoke-browser".to_string(),
+            node_id: "node-1".to_string(),
+            command: "browser.proxy".to_string(),
+            params_json: Some(json!({ "method": "GET", "path": "/snapshot" }).to_string()),
+            timeout_ms: Some(5_000),
+            idempotency_key: None,
+        };
+
+        let (result, _event) = run_invoke(&handler, invoke).await;
+        assert!(!result.ok);
+
+        let session_dir = data_dir.join("runs").join("browser").join("default");
+        let entries: Vec<_> = std::fs::read_dir(&session_dir)
             .unwrap()
-            .to_string_lossy()
-            .into_owned()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 1, "{entries:?}");
+        let run_id = format!("browser/default/{}", entries[0]);
+        let request = read_run_json(&data_dir, &run_id, "request.json");
+        assert_eq!(request["kind"], "browser");
+        assert_eq!(request["action"], "snapshot");
+        assert_eq!(request["caller_uid"], "openclaw");
+        let finished = read_run_json(&data_dir, &run_id, "result.json");
+        assert!(finished["error"].as_str().is_some_and(|e| !e.is_empty()));
     }
 
-    #[test]
-    fn path_override_prepend_single_entry_is_accepted() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        let override_val = join_path_entries(&["/custom/bin", "/usr/bin", "/bin"]);
-        assert!(
-            super::path_override_is_prepend_only(&override_val, &base),
-            "prepending one entry to base must be accepted"
+    fn keyed(
+        mut invoke: super::NodeInvokeRequest,
+        id: &str,
+        key: &str,
+    ) -> super::NodeInvokeRequest {
+        invoke.id = id.to_string();
+        invoke.idempotency_key = Some(key.to_string());
+        invoke
+    }
+
+    #[tokio::test]
+    async fn repeated_idempotency_key_returns_the_first_result_without_rerunning() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let output_path = unique_output_path();
+        let run = system_run_invoke("session-1", write_marker_command(&output_path), None, None);
+
+        let (first, event) = run_invoke(&handler, keyed(run.clone(), "invoke-1", "key-1")).await;
+        assert!(event.is_some());
+        assert!(output_path.exists());
+        std::fs::remove_file(&output_path).unwrap();
+
+        let (second, event) = run_invoke(&handler, keyed(run, "invoke-2", "key-1")).await;
+        assert!(event.is_none());
+        assert!(!output_path.exists(), "command ran twice");
+        assert_eq!(second.id, "invoke-2");
+        assert_eq!(second.ok, first.ok);
+        assert_eq!(second.payload_json, first.payload_json);
+    }
+
+    #[tokio::test]
+    async fn different_idempotency_keys_run_independently() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let output_path = unique_output_path();
+        let run = system_run_invoke("session-1", write_marker_command(&output_path), None, None);
+
+        run_invoke(&handler, keyed(run.clone(), "invoke-1", "key-1")).await;
+        std::fs::remove_file(&output_path).unwrap();
+        let (result, event) = run_invoke(&handler, keyed(run, "invoke-2", "key-2")).await;
+
+        assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+        assert_eq!(payload_json(&result)["success"], true);
+        assert!(output_path.exists());
+        let _ = std::fs::remove_file(output_path);
+    }
+
+    /// A retry arriving while the original still runs waits for it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn retry_during_a_run_attaches_to_it() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+        let output_path = unique_output_path();
+        let command = format!("sleep 0.3; echo run >> '{}'", output_path.display());
+        let run = system_run_invoke("session-1", command, None, None);
+
+        let ((first, first_event), (second, second_event)) = tokio::join!(
+            run_invoke(&handler, keyed(run.clone(), "invoke-1", "key-1")),
+            async {
+                wait_until_running(&handler, "run-1").await;
+                run_invoke(&handler, keyed(run, "invoke-2", "key-1")).await
+            }
         );
+
+        assert!(first_event.is_some() && second_event.is_none());
+        assert_eq!(second.id, "invoke-2");
+        assert_eq!(second.payload_json, first.payload_json);
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "run\n");
+        let _ = std::fs::remove_file(output_path);
     }
 
-    #[test]
-    fn path_override_prepend_multiple_entries_is_accepted() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        let override_val = join_path_entries(&["/a", "/b", "/usr/bin", "/bin"]);
-        assert!(
-            super::path_override_is_prepend_only(&override_val, &base),
-            "prepending multiple entries to base must be accepted"
+    #[tokio::test]
+    async fn execution_failures_emit_finished_events_not_denied() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+
+        let (result, event) = run_invoke(
+            &handler,
+            system_run_invoke("session-1", "exit 7".to_string(), None, None),
+        )
+        .await;
+
+        let payload = payload_json(&result);
+        assert_eq!(payload["success"], false);
+        assert_eq!(payload["exitCode"], 7);
+        assert_eq!(event.unwrap().kind, ExecEventKind::Finished);
+    }
+
+    /// Build an invoke request that uses a true argv array (no rawCommand).
+    /// Used to test the direct-spawn path. Its callers are unix-gated, so
+    /// gate it the same way.
+    #[cfg(unix)]
+    fn array_command_invoke(
+        session_key: &str,
+        argv: Vec<&str>,
+        approved: Option<bool>,
+    ) -> super::NodeInvokeRequest {
+        let mut params = serde_json::Map::new();
+        let cmd_json: serde_json::Value = argv.iter().map(|s| json!(s)).collect();
+        params.insert("command".into(), cmd_json);
+        params.insert("sessionKey".into(), json!(session_key));
+        params.insert("runId".into(), json!("run-arr-1"));
+        if let Some(approved) = approved {
+            params.insert("approved".into(), json!(approved));
+        }
+        super::NodeInvokeRequest {
+            id: "invoke-arr-1".to_string(),
+            node_id: "node-1".to_string(),
+            command: "system.run".to_string(),
+            params_json: Some(serde_json::Value::Object(params).to_string()),
+            timeout_ms: Some(5_000),
+            idempotency_key: None,
+        }
+    }
+
+    // ── system.which tests ────────────────────────────────────────────────────
+
+    async fn system_which(
+        handler: &OpenClawHandler,
+        params: serde_json::Value,
+    ) -> serde_json::Value {
+        let invoke = super::NodeInvokeRequest {
+            id: "which-1".to_string(),
+            node_id: "node-1".to_string(),
+            command: "system.which".to_string(),
+            params_json: Some(params.to_string()),
+            timeout_ms: None,
+            idempotency_key: None,
+        };
+        let (result, _event) = run_invoke(handler, invoke).await;
+        payload_json(&result)["bins"].clone()
+    }
+
+    #[cfg(unix)]
+    fn executable(dir: &std::path::Path, name: &str, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_which_searches_the_callers_path_and_probes_versions() {
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let dir = tempfile::tempdir().unwrap();
+        let node = executable(dir.path(), "node", "echo v20.11.0");
+        let path = dir.path().display().to_string();
+
+        let bins = system_which(
+            &handler,
+            json!({ "bins": ["node", "../node", "missing"], "path": path }),
+        )
+        .await;
+        assert_eq!(bins, json!({ "node": node }));
+
+        let bins = system_which(
+            &handler,
+            json!({ "bins": ["node"], "path": path, "probeVersion": true }),
+        )
+        .await;
+        assert_eq!(
+            bins,
+            json!({ "node": { "path": node, "version": "v20.11.0" } })
         );
     }
 
-    #[test]
-    fn path_override_identical_to_base_is_accepted() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        // Same entries, zero prepended — still valid (nothing harmful added).
-        assert!(
-            super::path_override_is_prepend_only(&base, &base),
-            "PATH identical to base must be accepted"
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_which_reports_no_version_when_the_probe_times_out() {
+        let (mut handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        handler.version_probe_timeout = std::time::Duration::from_millis(200);
+        let dir = tempfile::tempdir().unwrap();
+        let hang = executable(dir.path(), "hang", "exec sleep 30");
+        let quick = executable(dir.path(), "quick", "echo quick 1.0");
+
+        let started = std::time::Instant::now();
+        let bins = system_which(
+            &handler,
+            json!({
+                "bins": ["hang", "quick"],
+                "path": dir.path().display().to_string(),
+                "probeVersion": true,
+            }),
+        )
+        .await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            bins,
+            json!({
+                "hang": { "path": hang },
+                "quick": { "path": quick, "version": "quick 1.0" },
+            })
         );
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_which_reuses_recent_lookups() {
+        let (handler, _session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        let dir = tempfile::tempdir().unwrap();
+        let tool = executable(dir.path(), "tool", "echo tool 1");
+        let params = json!({
+            "bins": ["tool"],
+            "path": dir.path().display().to_string(),
+            "probeVersion": true,
+        });
+        let first = system_which(&handler, params.clone()).await;
+
+        // Neither the lookup nor the probe is repeated within the TTL.
+        executable(dir.path(), "tool", "echo tool 2");
+        assert_eq!(system_which(&handler, params.clone()).await, first);
+        std::fs::remove_file(&tool).unwrap();
+        assert_eq!(system_which(&handler, params).await, first);
+    }
+
+    // ── which_in_dir tests ────────────────────────────────────────────────────
+
     #[test]
-    fn path_override_full_replace_is_rejected() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        let override_val = join_path_entries(&["/attacker/bin", "/other"]);
-        assert!(
-            !super::path_override_is_prepend_only(&override_val, &base),
-            "full replacement of base entries must be rejected"
-        );
+    fn which_in_dir_finds_exact_match() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin_path = tmp.path().join("mybinary");
+        std::fs::write(&bin_path, b"").unwrap();
+        let result = super::which_in_dir(tmp.path(), "mybinary");
+        assert_eq!(result, Some(bin_path));
     }
 
     #[test]
-    fn path_override_middle_insert_is_rejected() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        // Insert an entry between the two base entries — this is not a pure prepend.
-        let override_val = join_path_entries(&["/usr/bin", "/injected", "/bin"]);
-        assert!(
-            !super::path_override_is_prepend_only(&override_val, &base),
-            "inserting an entry in the middle of base entries must be rejected"
-        );
+    fn which_in_dir_returns_none_when_missing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let result = super::which_in_dir(tmp.path(), "doesnotexist");
+        assert!(result.is_none());
     }
 
+    /// On Windows, a binary without an extension should be found when probing
+    /// PATHEXT extensions. Gate to cfg(windows) since we need actual .exe files
+    /// to exist and PATHEXT semantics are Windows-specific.
+    #[cfg(windows)]
     #[test]
-    fn path_override_reorder_is_rejected() {
-        let base = join_path_entries(&["/usr/bin", "/bin"]);
-        // Reversed order — base entries are present but not as a suffix.
-        let override_val = join_path_entries(&["/bin", "/usr/bin"]);
-        assert!(
-            !super::path_override_is_prepend_only(&override_val, &base),
-            "reordering base entries must be rejected"
+    fn which_in_dir_finds_pathext_extension_on_windows() {
+        let tmp = tempfile::tempdir().unwrap();
+        // Create "mybinary.exe" — PATHEXT typically contains .EXE
+        let bin_path = tmp.path().join("mybinary.exe");
+        std::fs::write(&bin_path, b"").unwrap();
+        // Query without extension. PATHEXT entries are typically upper-case
+        // (".EXE") and NTFS is case-insensitive, so the returned path may
+        // carry the PATHEXT casing — compare case-insensitively.
+        let result = super::which_in_dir(tmp.path(), "mybinary");
+        let found = result.expect("should find mybinary.exe via PATHEXT");
+        assert_eq!(
+            found.to_string_lossy().to_lowercase(),
+            bin_path.to_string_lossy().to_lowercase(),
+            "should find mybinary.exe via PATHEXT (case-insensitive)"
         );
     }
 
+    #[cfg(windows)]
     #[test]
-    fn path_override_empty_override_is_rejected() {
-        let base = join_path_entries(&["/usr/bin"]);
-        assert!(
-            !super::path_override_is_prepend_only("", &base),
-            "empty override must be rejected"
+    fn which_in_dir_already_has_extension_does_not_double_probe() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin_path = tmp.path().join("mybinary.exe");
+        std::fs::write(&bin_path, b"").unwrap();
+        // Query WITH extension — should find via exact match, not PATHEXT loop.
+        let result = super::which_in_dir(tmp.path(), "mybinary.exe");
+        assert_eq!(result, Some(bin_path));
+    }
+
+    /// Array-form commands spawn directly (no shell), so an argument containing
+    /// `&` is passed verbatim to the child process rather than interpreted by a
+    /// shell.  We verify by running `echo` with an arg that contains `&foo` and
+    /// asserting the output contains the literal ampersand — if it were passed
+    /// to a shell the `&` would fork a background job instead.
+    ///
+    /// This test covers Unix only because `echo` semantics on Windows differ;
+    /// the security property (no shell involved) applies on all platforms.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn array_command_with_ampersand_does_not_spawn_second_command() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+
+        // The arg "hello&world" must arrive verbatim; a shell would interpret
+        // `&` as a command separator and the output would just be "hello".
+        let invoke = array_command_invoke("session-1", vec!["echo", "hello&world"], None);
+
+        let (result, _event) = run_invoke(&handler, invoke).await;
+        let payload = payload_json(&result);
+        assert_eq!(
+            payload["success"], true,
+            "direct-spawn echo should succeed: {:?}",
+            payload
         );
+        let stdout = payload["stdout"].as_str().unwrap_or("").trim().to_string();
+        assert_eq!(
+            stdout, "hello&world",
+            "ampersand must be passed verbatim, not shell-interpreted; got: {stdout:?}"
+        );
+    }
+
+    // ── compose_path_override tests ──────────────────────────────────────────
+
+    /// Build a platform-correct PATH string from a list of directory strings
+    /// using std::env::join_paths so the separator is always correct.
+    fn join_path_entries(entries: &[&str]) -> String {
+        let paths: Vec<std::path::PathBuf> = entries.iter().map(std::path::PathBuf::from).collect();
+        std::env::join_paths(&paths)
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
     }
 
     #[test]
-    fn path_override_empty_base_non_empty_override_is_accepted() {
-        // Empty base: any non-empty override is treated as "all prepended".
-        let override_val = join_path_entries(&["/custom/bin"]);
-        assert!(
-            super::path_override_is_prepend_only(&override_val, ""),
-            "non-empty override with empty base must be accepted"
+    fn path_override_is_prepended_to_the_base() {
+        let base = join_path_entries(&["/usr/bin", "/bin"]);
+        let override_val = join_path_entries(&["/a", "/b"]);
+        assert_eq!(
+            super::compose_path_override(&override_val, &base),
+            Some(join_path_entries(&["/a", "/b", "/usr/bin", "/bin"]))
         );
     }
 
     #[test]
-    fn path_override_empty_base_empty_override_is_rejected() {
-        assert!(
-            !super::path_override_is_prepend_only("", ""),
-            "both empty must be rejected (empty override adds nothing)"
+    fn path_override_already_ending_in_the_base_is_kept() {
+        let base = join_path_entries(&["/usr/bin", "/bin"]);
+        let override_val = join_path_entries(&["/custom/bin", "/usr/bin", "/bin"]);
+        assert_eq!(
+            super::compose_path_override(&override_val, &base),
+            Some(override_val)
         );
+        assert_eq!(super::compose_path_override(&base, &base), Some(base));
     }
 
-    /// An override that is the base entries followed by an extra entry is an
-    /// append, not a prepend — and must be rejected.  The base entries appear
-    /// as a PREFIX of the override, not a suffix.
     #[test]
-    fn path_override_append_is_rejected() {
+    fn path_override_cannot_drop_base_entries() {
         let base = join_path_entries(&["/usr/bin", "/bin"]);
-        // base entries first, then an extra entry — this is an append, not a prepend.
-        let override_val = join_path_entries(&["/usr/bin", "/bin", "/evil"]);
-        assert!(
-            !super::path_override_is_prepend_only(&override_val, &base),
-            "appending an entry after base entries must be rejected (not a pure prepend)"
+        // An attempted full replacement, middle insert or append still ends
+        // with every base entry.
+        for entries in [
+            &["/attacker/bin", "/other"][..],
+            &["/usr/bin", "/injected", "/bin"],
+            &["/usr/bin", "/bin", "/evil"],
+        ] {
+            let composed =
+                super::compose_path_override(&join_path_entries(entries), &base).unwrap();
+            let composed: Vec<_> = std::env::split_paths(&composed).collect();
+            assert!(
+                composed.ends_with(&std::env::split_paths(&base).collect::<Vec<_>>()),
+                "{entries:?} composed to {composed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn path_override_onto_an_empty_base() {
+        let override_val = join_path_entries(&["/custom/bin"]);
+        assert_eq!(
+            super::compose_path_override(&override_val, ""),
+            Some(override_val)
         );
     }
 
+    #[test]
+    fn empty_path_override_is_ignored() {
+        let base = join_path_entries(&["/usr/bin"]);
+        assert_eq!(super::compose_path_override("", &base), None);
+        assert_eq!(super::compose_path_override("", ""), None);
+    }
+
     // ── sanitize_env blocked-key tests ───────────────────────────────────────
 
     #[test]
@@ -2291,16 +4070,13 @@ mod tests {
             // Can't meaningfully test PATH prepend without a base PATH.
             return;
         }
-        let new_dir = join_path_entries(&["/prepended/bin"]);
-        let prepended = format!(
-            "{}",
-            std::env::join_paths(
-                std::iter::once(std::path::PathBuf::from("/prepended/bin"))
-                    .chain(std::env::split_paths(&base_path))
-            )
-            .unwrap()
-            .to_string_lossy()
-        );
+        let prepended = std::env::join_paths(
+            std::iter::once(std::path::PathBuf::from("/prepended/bin"))
+                .chain(std::env::split_paths(&base_path)),
+        )
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
 
         let mut overrides = std::collections::HashMap::new();
         overrides.insert("PATH".to_string(), prepended.clone());
@@ -2310,12 +4086,12 @@ mod tests {
         assert_eq!(
             result.get("PATH").map(String::as_str),
             Some(prepended.as_str()),
-            "PATH prepend must be accepted; base_path={base_path:?} new_dir={new_dir:?}"
+            "PATH prepend must be accepted; base_path={base_path:?}"
         );
     }
 
     #[test]
-    fn sanitize_env_path_full_replace_is_rejected() {
+    fn sanitize_env_path_full_replace_is_composed_onto_the_daemon_path() {
         let base_path = std::env::var("PATH").unwrap_or_default();
         if base_path.is_empty() {
             return;
@@ -2327,11 +4103,94 @@ mod tests {
 
         let result = super::sanitize_env(&overrides);
 
-        // The PATH in result must still be the original base (not the attacker's).
-        assert_ne!(
+        // The requested entries go in front; the daemon's PATH is kept.
+        let expected = std::env::join_paths(
+            std::iter::once(std::path::PathBuf::from("/attacker/bin"))
+                .chain(std::env::split_paths(&base_path)),
+        )
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+        assert_eq!(
             result.get("PATH").map(String::as_str),
-            Some("/attacker/bin"),
-            "full PATH replacement must be rejected"
+            Some(expected.as_str())
+        );
+    }
+
+    #[test]
+    fn sanitize_env_drops_blocked_keys_inherited_from_the_daemon() {
+        let base = [
+            ("HOME", "/home/me"),
+            ("LD_PRELOAD", "/opt/hook.so"),
+            ("node_options", "--require /opt/hook.js"),
+            ("DYLD_INSERT_LIBRARIES", "/opt/hook.dylib"),
+            ("PATHEXT", ".COM;.EXE"),
+            ("PATH", "/usr/bin"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+
+        let result = super::sanitize_env_from(base, &std::collections::HashMap::new());
+
+        let mut keys: Vec<_> = result.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["HOME", "PATH", "PATHEXT"]);
+    }
+
+    #[test]
+    fn sanitize_env_composes_path_onto_a_case_variant_daemon_path() {
+        let base = [("Path".to_string(), join_path_entries(&["/usr/bin"]))];
+        let mut overrides = std::collections::HashMap::new();
+        overrides.insert("PATH".to_string(), join_path_entries(&["/tools/bin"]));
+
+        let result = super::sanitize_env_from(base, &overrides);
+
+        assert_eq!(result.len(), 1, "{result:?}");
+        assert_eq!(
+            result.get("PATH"),
+            Some(&join_path_entries(&["/tools/bin", "/usr/bin"]))
+        );
+    }
+
+    /// A system.run for `raw_command` with `env` overrides.
+    fn system_run_with_env(raw_command: &str, env: serde_json::Value) -> super::NodeInvokeRequest {
+        let mut invoke = system_run_invoke("session-1", raw_command.to_string(), None, None);
+        let mut params: serde_json::Value =
+            serde_json::from_str(invoke.params_json.as_deref().unwrap()).unwrap();
+        params["env"] = env;
+        invoke.params_json = Some(params.to_string());
+        invoke
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn system_run_children_only_see_the_sanitized_environment() {
+        let (handler, session_mgr, _approval_mgr, _broadcast_tx) = test_handler(1);
+        session_mgr
+            .set_mode("session-1", SessionMode::AutoAccept, 0)
+            .await;
+
+        let (result, _event) = run_invoke(
+            &handler,
+            system_run_with_env(
+                r#"printf '%s|%s|%s' "$SAFE_VAR" "${LD_PRELOAD-unset}" "$PATH""#,
+                json!({
+                    "SAFE_VAR": "ok",
+                    "LD_PRELOAD": "/attacker/hook.so",
+                    "PATH": "/prepended/bin",
+                }),
+            ),
+        )
+        .await;
+
+        let payload = payload_json(&result);
+        let stdout = payload["stdout"].as_str().unwrap();
+        let (vars, path) = stdout.rsplit_once('|').unwrap();
+        assert_eq!(vars, "ok|unset");
+        let daemon_path = std::env::var("PATH").unwrap_or_default();
+        let expected = format!("/prepended/bin:{daemon_path}");
+        assert!(
+            path.ends_with(&expected),
+            "child PATH {path:?} must end with {expected:?}"
         );
     }
 }
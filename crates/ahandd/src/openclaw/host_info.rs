@@ -0,0 +1,326 @@
+//! Host facts for the OpenClaw `system.info` command.
+//!
+//! What doesn't change while the daemon runs (OS, arch, CPUs, total
+//! memory) is read once; load, free memory and disk are sampled at most
+//! every `SAMPLE_TTL`, so a gateway polling every node stays cheap. The
+//! periodic `node.status` event reports the same facts.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How long a sample of load, free memory and disk is reused.
+const SAMPLE_TTL: Duration = Duration::from_secs(5);
+
+/// system.info result
+#[derive(Debug, Clone, Serialize)]
+pub struct HostInfo {
+    pub os: String,
+    #[serde(rename = "osVersion", skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<String>,
+    #[serde(rename = "kernelVersion", skip_serializing_if = "Option::is_none")]
+    pub kernel_version: Option<String>,
+    pub arch: String,
+    pub hostname: String,
+    #[serde(rename = "cpuCount")]
+    pub cpu_count: usize,
+    /// 1, 5 and 15 minute load averages.
+    #[serde(rename = "loadAverage", skip_serializing_if = "Option::is_none")]
+    pub load_average: Option<[f64; 3]>,
+    #[serde(rename = "memoryTotalBytes", skip_serializing_if = "Option::is_none")]
+    pub memory_total_bytes: Option<u64>,
+    #[serde(
+        rename = "memoryAvailableBytes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory_available_bytes: Option<u64>,
+    /// Free space on the volume holding the home directory.
+    #[serde(rename = "diskFreeBytes", skip_serializing_if = "Option::is_none")]
+    pub disk_free_bytes: Option<u64>,
+    #[serde(rename = "daemonVersion")]
+    pub daemon_version: String,
+    #[serde(rename = "daemonUptimeMs")]
+    pub daemon_uptime_ms: u64,
+}
+
+/// Load, free memory and disk, as sampled at one moment.
+#[derive(Debug, Clone, Default)]
+struct Sample {
+    load_average: Option<[f64; 3]>,
+    memory_available_bytes: Option<u64>,
+    disk_free_bytes: Option<u64>,
+}
+
+impl Sample {
+    fn take(home: &Path) -> Self {
+        Self {
+            load_average: load_average(),
+            memory_available_bytes: memory_available_bytes(),
+            disk_free_bytes: disk_free_bytes(home),
+        }
+    }
+}
+
+/// Answers system.info from facts read once and a memoized sample.
+pub struct HostInfoProbe {
+    started_at: Instant,
+    home: PathBuf,
+    facts: HostInfo,
+    sample: Mutex<Option<(Instant, Sample)>>,
+}
+
+impl HostInfoProbe {
+    /// Read the static facts now; the daemon's uptime counts from
+    /// `started_at`.
+    pub fn new(started_at: Instant) -> Self {
+        let facts = HostInfo {
+            os: std::env::consts::OS.to_string(),
+            os_version: os_version(),
+            kernel_version: kernel_version(),
+            arch: std::env::consts::ARCH.to_string(),
+            hostname: gethostname::gethostname().to_string_lossy().to_string(),
+            cpu_count: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            load_average: None,
+            memory_total_bytes: memory_total_bytes(),
+            memory_available_bytes: None,
+            disk_free_bytes: None,
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            daemon_uptime_ms: 0,
+        };
+        Self {
+            started_at,
+            home: dirs::home_dir().unwrap_or_else(|| PathBuf::from("/")),
+            facts,
+            sample: Mutex::new(None),
+        }
+    }
+
+    /// The host as of now, with load, free memory and disk at most
+    /// `SAMPLE_TTL` old.
+    pub fn current(&self) -> HostInfo {
+        let now = Instant::now();
+        let sample = self.sample_at(now);
+        HostInfo {
+            load_average: sample.load_average,
+            memory_available_bytes: sample.memory_available_bytes,
+            disk_free_bytes: sample.disk_free_bytes,
+            daemon_uptime_ms: now.duration_since(self.started_at).as_millis() as u64,
+            ..self.facts.clone()
+        }
+    }
+
+    fn sample_at(&self, now: Instant) -> Sample {
+        let mut cached = self
+            .sample
+            .lock()
+            .expect("openclaw host info mutex poisoned");
+        if let Some((at, sample)) = cached.as_ref()
+            && now.saturating_duration_since(*at) < SAMPLE_TTL
+        {
+            return sample.clone();
+        }
+        let sample = Sample::take(&self.home);
+        *cached = Some((now, sample.clone()));
+        sample
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn os_version() -> Option<String> {
+    let release = std::fs::read_to_string("/etc/os-release").ok()?;
+    release.lines().find_map(|line| {
+        let value = line.strip_prefix("PRETTY_NAME=")?;
+        Some(value.trim_matches('"').to_string())
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn os_version() -> Option<String> {
+    let output = std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !version.is_empty()).then(|| format!("macOS {version}"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn os_version() -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn kernel_version() -> Option<String> {
+    // SAFETY: `uname` fills the zeroed struct with NUL-terminated strings.
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(name.release.as_ptr()) };
+    Some(release.to_string_lossy().to_string())
+}
+
+#[cfg(not(unix))]
+fn kernel_version() -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn load_average() -> Option<[f64; 3]> {
+    let mut loads = [0f64; 3];
+    // SAFETY: `loads` has room for the 3 samples requested.
+    let n = unsafe { libc::getloadavg(loads.as_mut_ptr(), 3) };
+    (n == 3).then_some(loads)
+}
+
+#[cfg(not(unix))]
+fn load_average() -> Option<[f64; 3]> {
+    None
+}
+
+/// A `/proc/meminfo` field, in bytes.
+#[cfg(target_os = "linux")]
+fn meminfo_bytes(field: &str) -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo.lines().find_map(|line| {
+        let value = line.strip_prefix(field)?.strip_prefix(':')?;
+        let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+        Some(kib * 1024)
+    })
+}
+
+/// A numeric sysctl, read as the integer type it is declared with.
+#[cfg(target_os = "macos")]
+fn sysctl<T: Copy + Default>(name: &std::ffi::CStr) -> Option<T> {
+    let mut value = T::default();
+    let mut len = std::mem::size_of::<T>();
+    // SAFETY: `value` is a buffer of `len` bytes; sysctlbyname writes at
+    // most `len` bytes and reports how many in `len`.
+    let rc = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            (&mut value as *mut T).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (rc == 0 && len == std::mem::size_of::<T>()).then_some(value)
+}
+
+#[cfg(target_os = "linux")]
+fn memory_total_bytes() -> Option<u64> {
+    meminfo_bytes("MemTotal")
+}
+
+#[cfg(target_os = "macos")]
+fn memory_total_bytes() -> Option<u64> {
+    sysctl::<u64>(c"hw.memsize")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn memory_total_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn memory_available_bytes() -> Option<u64> {
+    meminfo_bytes("MemAvailable")
+}
+
+#[cfg(target_os = "macos")]
+fn memory_available_bytes() -> Option<u64> {
+    let pages = sysctl::<u32>(c"vm.page_free_count")?;
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(page_size)
+        .ok()
+        .map(|size| u64::from(pages) * size)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn memory_available_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn disk_free_bytes(path: &Path) -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    Some(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+#[cfg(not(unix))]
+fn disk_free_bytes(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn host_info_field_names_are_stable() {
+        let info = HostInfo {
+            os: "linux".to_string(),
+            os_version: Some("Debian GNU/Linux 12 (bookworm)".to_string()),
+            kernel_version: Some("6.1.0".to_string()),
+            arch: "x86_64".to_string(),
+            hostname: "build-1".to_string(),
+            cpu_count: 8,
+            load_average: Some([0.5, 0.25, 0.125]),
+            memory_total_bytes: Some(16 << 30),
+            memory_available_bytes: Some(4 << 30),
+            disk_free_bytes: Some(100 << 30),
+            daemon_version: "0.1.0".to_string(),
+            daemon_uptime_ms: 1_500,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&info).unwrap(),
+            json!({
+                "os": "linux",
+                "osVersion": "Debian GNU/Linux 12 (bookworm)",
+                "kernelVersion": "6.1.0",
+                "arch": "x86_64",
+                "hostname": "build-1",
+                "cpuCount": 8,
+                "loadAverage": [0.5, 0.25, 0.125],
+                "memoryTotalBytes": 17_179_869_184u64,
+                "memoryAvailableBytes": 4_294_967_296u64,
+                "diskFreeBytes": 107_374_182_400u64,
+                "daemonVersion": "0.1.0",
+                "daemonUptimeMs": 1_500,
+            })
+        );
+    }
+
+    #[test]
+    fn samples_are_reused_for_five_seconds() {
+        let start = Instant::now();
+        let probe = HostInfoProbe::new(start);
+        let sampled_at = |probe: &HostInfoProbe| probe.sample.lock().unwrap().as_ref().unwrap().0;
+
+        probe.sample_at(start);
+        probe.sample_at(start + Duration::from_secs(4));
+        assert_eq!(sampled_at(&probe), start);
+
+        let later = start + SAMPLE_TTL;
+        probe.sample_at(later);
+        assert_eq!(sampled_at(&probe), later);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_hosts_report_memory_and_disk() {
+        let info = HostInfoProbe::new(Instant::now()).current();
+        assert!(info.cpu_count >= 1);
+        assert!(info.memory_total_bytes.is_some_and(|total| total > 0));
+        assert!(info.memory_available_bytes <= info.memory_total_bytes);
+        assert!(info.disk_free_bytes.is_some());
+        assert!(info.kernel_version.is_some());
+        assert!(info.load_average.is_some());
+    }
+}
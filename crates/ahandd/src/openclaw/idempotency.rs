@@ -0,0 +1,170 @@
+//! Results of recent node invocations by idempotency key, so a gateway that
+//! retries after losing a response gets the original answer instead of
+//! running the command again.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use super::protocol::NodeInvokeResult;
+
+/// How long a completed result is kept by default.
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Most completed results kept; the oldest are dropped first.
+const MAX_COMPLETED: usize = 256;
+
+struct CompletedInvoke {
+    result: NodeInvokeResult,
+    /// Entries expire `ttl` after this.
+    completed_at: Instant,
+}
+
+/// What to do with an invoke carrying an idempotency key.
+pub enum Claim {
+    /// First time this key is seen: run it, then hand the result to
+    /// [`InvokeCache::complete`].
+    Run(Running),
+    /// Still running under another invoke; its result arrives here.
+    Wait(watch::Receiver<Option<NodeInvokeResult>>),
+    /// Already answered.
+    Done(NodeInvokeResult),
+}
+
+/// An invoke being run under its key. Dropping it without completing lets
+/// the next invoke with the key run instead.
+pub struct Running {
+    key: String,
+    tx: watch::Sender<Option<NodeInvokeResult>>,
+}
+
+pub struct InvokeCache {
+    running: HashMap<String, watch::Receiver<Option<NodeInvokeResult>>>,
+    /// Completed results in completion order (front = oldest).
+    completed: VecDeque<(String, CompletedInvoke)>,
+    ttl: Duration,
+}
+
+impl InvokeCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            running: HashMap::new(),
+            completed: VecDeque::new(),
+            ttl,
+        }
+    }
+
+    pub fn claim(&mut self, key: &str) -> Claim {
+        self.evict_expired();
+        if let Some((_, done)) = self.completed.iter().find(|(k, _)| k == key) {
+            return Claim::Done(done.result.clone());
+        }
+        // A closed channel means the invoke running the key was abandoned.
+        if let Some(rx) = self.running.get(key)
+            && rx.has_changed().is_ok()
+        {
+            return Claim::Wait(rx.clone());
+        }
+        let (tx, rx) = watch::channel(None);
+        self.running.insert(key.to_string(), rx);
+        Claim::Run(Running {
+            key: key.to_string(),
+            tx,
+        })
+    }
+
+    /// Record `running`'s result and pass it to any invokes waiting on it.
+    pub fn complete(&mut self, running: Running, result: &NodeInvokeResult) {
+        self.running.remove(&running.key);
+        running.tx.send_replace(Some(result.clone()));
+        self.completed.push_back((
+            running.key,
+            CompletedInvoke {
+                result: result.clone(),
+                completed_at: Instant::now(),
+            },
+        ));
+        self.evict_expired();
+        while self.completed.len() > MAX_COMPLETED {
+            self.completed.pop_front();
+        }
+    }
+
+    /// Drop completed entries older than the TTL. They are kept in
+    /// completion order, so expired ones are always at the front.
+    fn evict_expired(&mut self) {
+        while self
+            .completed
+            .front()
+            .is_some_and(|(_, done)| done.completed_at.elapsed() > self.ttl)
+        {
+            self.completed.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(id: &str) -> NodeInvokeResult {
+        NodeInvokeResult {
+            id: id.to_string(),
+            node_id: "node-1".to_string(),
+            ok: true,
+            payload_json: Some(format!("{{\"id\":\"{id}\"}}")),
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn waiters_get_the_result_and_later_claims_reuse_it() {
+        let mut cache = InvokeCache::new(DEFAULT_IDEMPOTENCY_TTL);
+        let Claim::Run(running) = cache.claim("key-1") else {
+            panic!("first claim should run");
+        };
+        let Claim::Wait(mut rx) = cache.claim("key-1") else {
+            panic!("second claim should wait");
+        };
+        cache.complete(running, &result("invoke-1"));
+
+        let waited = rx.wait_for(Option::is_some).await.unwrap().clone();
+        assert_eq!(
+            waited.unwrap().payload_json,
+            result("invoke-1").payload_json
+        );
+        let Claim::Done(done) = cache.claim("key-1") else {
+            panic!("completed key should be reused");
+        };
+        assert_eq!(done.id, "invoke-1");
+    }
+
+    #[test]
+    fn abandoned_and_expired_keys_run_again() {
+        let mut cache = InvokeCache::new(Duration::ZERO);
+        let Claim::Run(running) = cache.claim("key-1") else {
+            panic!("first claim should run");
+        };
+        drop(running);
+        let Claim::Run(running) = cache.claim("key-1") else {
+            panic!("abandoned key should run again");
+        };
+        cache.complete(running, &result("invoke-1"));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(matches!(cache.claim("key-1"), Claim::Run(_)));
+    }
+
+    #[test]
+    fn completed_results_are_capped_oldest_first() {
+        let mut cache = InvokeCache::new(DEFAULT_IDEMPOTENCY_TTL);
+        for n in 0..=MAX_COMPLETED {
+            let Claim::Run(running) = cache.claim(&format!("key-{n}")) else {
+                panic!("new key should run");
+            };
+            cache.complete(running, &result(&format!("invoke-{n}")));
+        }
+        assert!(matches!(cache.claim("key-0"), Claim::Run(_)));
+        assert!(matches!(cache.claim("key-1"), Claim::Done(_)));
+    }
+}
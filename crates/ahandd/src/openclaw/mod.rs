@@ -3,11 +3,16 @@
 //! This module enables ahandd to connect to an OpenClaw Gateway as a node host,
 //! providing command execution capabilities via the OpenClaw protocol.
 
+pub mod auth_token;
 pub mod client;
 pub mod device_identity;
 pub mod exec_approvals;
 pub mod handler;
+pub mod host_info;
+pub mod idempotency;
 pub mod pairing;
 pub mod protocol;
+pub mod requests;
+pub mod which;
 
 pub use client::OpenClawClient;
@@ -52,6 +52,22 @@ pub fn default_pairing_path() -> PathBuf {
         .join(PAIRING_FILE)
 }
 
+/// Pairing state file for one of several configured gateways, so each
+/// keeps its own node ID, token and pinned certificate.
+pub fn gateway_pairing_path(host: &str, port: u16) -> PathBuf {
+    let host: String = host
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    default_pairing_path().with_file_name(format!("openclaw-pairing-{host}_{port}.json"))
+}
+
 /// Load pairing state from file
 pub fn load_pairing_state(path: &Path) -> Result<Option<PairingState>> {
     if !path.exists() {
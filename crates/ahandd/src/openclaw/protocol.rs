@@ -10,6 +10,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::host_info::HostInfo;
+
 /// Protocol version
 pub const PROTOCOL_VERSION: u32 = 3;
 
@@ -203,18 +205,64 @@ pub struct SystemRunParams {
     pub approval_decision: Option<String>,
     #[serde(rename = "runId")]
     pub run_id: Option<String>,
+    #[serde(rename = "outputEncoding", default)]
+    pub output_encoding: OutputEncoding,
+}
+
+/// How system.run returns stdout: as (lossily decoded) text, or as the raw
+/// bytes in `stdoutBase64` for commands that emit binary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputEncoding {
+    #[default]
+    Text,
+    Base64,
+}
+
+/// system.cancel params
+#[derive(Debug, Clone, Deserialize)]
+pub struct SystemCancelParams {
+    #[serde(rename = "runId")]
+    pub run_id: String,
+}
+
+/// system.cancel result
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemCancelResult {
+    #[serde(rename = "runId")]
+    pub run_id: String,
+    pub cancelled: bool,
 }
 
 /// system.which params
 #[derive(Debug, Clone, Deserialize)]
 pub struct SystemWhichParams {
     pub bins: Vec<String>,
+    /// Search this PATH instead of the daemon's, e.g. a user shell's.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Also report each binary's `--version`.
+    #[serde(rename = "probeVersion", default)]
+    pub probe_version: bool,
 }
 
 /// system.which result
 #[derive(Debug, Clone, Serialize)]
 pub struct SystemWhichResult {
-    pub bins: HashMap<String, String>,
+    pub bins: HashMap<String, WhichEntry>,
+}
+
+/// Where a binary was found: a bare path, or an object with its version
+/// when `probeVersion` was set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum WhichEntry {
+    Path(String),
+    Probed {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+    },
 }
 
 /// system.execApprovals.get result
@@ -226,6 +274,15 @@ pub struct ExecApprovalsSnapshot {
     pub file: ExecApprovalsFile,
 }
 
+/// execApprovals.changed event payload: exec-approvals.json changed in a
+/// way the gateway hasn't seen, and `hash` is now the base for a set
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecApprovalsChanged {
+    pub path: String,
+    pub exists: bool,
+    pub hash: String,
+}
+
 /// system.execApprovals.set params
 #[derive(Debug, Clone, Deserialize)]
 pub struct ExecApprovalsSetParams {
@@ -314,6 +371,18 @@ pub struct NodeEvent {
     pub payload_json: Option<String>,
 }
 
+/// node.status event payload: how busy the node is, plus the system.info
+/// facts.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeStatus {
+    #[serde(rename = "activeJobs")]
+    pub active_jobs: u32,
+    #[serde(rename = "pendingApprovals")]
+    pub pending_approvals: u32,
+    #[serde(flatten)]
+    pub host: HostInfo,
+}
+
 /// Run result (same structure as OpenClaw)
 #[derive(Debug, Clone, Serialize)]
 pub struct RunResult {
@@ -324,11 +393,17 @@ pub struct RunResult {
     pub success: bool,
     pub stdout: String,
     pub stderr: String,
+    /// stdout with `outputEncoding: "base64"`; `stdout` is empty then.
+    #[serde(rename = "stdoutBase64", skip_serializing_if = "Option::is_none")]
+    pub stdout_base64: Option<String>,
+    /// MIME type guessed from the leading bytes of `stdoutBase64`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
-/// Exec event payload (for exec.denied / exec.finished events)
+/// Exec event payload (for exec.output / exec.denied / exec.finished events)
 #[derive(Debug, Clone, Serialize)]
 pub struct ExecEventPayload {
     #[serde(rename = "sessionKey")]
@@ -0,0 +1,169 @@
+//! Requests sent to the gateway and the response frames they are waiting
+//! for, matched by request id.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use super::protocol::{RequestFrame, ResponseFrame};
+
+/// How long the gateway gets to answer a request by default.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RequestTracker {
+    tx: mpsc::UnboundedSender<Message>,
+    pending: Mutex<HashMap<String, oneshot::Sender<ResponseFrame>>>,
+    timeout: Duration,
+}
+
+impl RequestTracker {
+    pub fn new(tx: mpsc::UnboundedSender<Message>, timeout: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            tx,
+            pending: Mutex::new(HashMap::new()),
+            timeout,
+        })
+    }
+
+    /// Send `method` now and return a future for its response. Sending
+    /// up front keeps requests in the order they were made even when their
+    /// responses are awaited elsewhere. The future fails if no response
+    /// arrives within the timeout or the connection closes first; a response
+    /// with `ok: false` is returned as-is.
+    pub fn request(
+        self: &Arc<Self>,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<ResponseFrame>> + Send + 'static> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let frame = RequestFrame::new(id.clone(), method.to_string(), params);
+        let text = serde_json::to_string(&frame)?;
+
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.pending_lock().insert(id.clone(), resp_tx);
+        if self.tx.send(Message::Text(text)).is_err() {
+            self.pending_lock().remove(&id);
+            return Err(anyhow!("{method} not sent: connection closed"));
+        }
+
+        let tracker = Arc::clone(self);
+        let method = method.to_string();
+        Ok(async move {
+            match tokio::time::timeout(tracker.timeout, resp_rx).await {
+                Ok(response) => response.with_context(|| {
+                    format!("connection closed before {method} {id} was answered")
+                }),
+                Err(_) => {
+                    tracker.pending_lock().remove(&id);
+                    warn!(
+                        %method,
+                        %id,
+                        timeout_secs = tracker.timeout.as_secs(),
+                        "gateway request timed out"
+                    );
+                    Err(anyhow!("{method} {id} timed out"))
+                }
+            }
+        })
+    }
+
+    /// Hand a response frame to the request waiting for it. Returns false
+    /// when nothing is waiting (unknown id, or already timed out).
+    pub fn resolve(&self, response: ResponseFrame) -> bool {
+        match self.pending_lock().remove(&response.id) {
+            Some(waiter) => {
+                let _ = waiter.send(response);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fail every request still waiting; the connection is gone.
+    pub fn close(&self) {
+        self.pending_lock().clear();
+    }
+
+    fn pending_lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<ResponseFrame>>> {
+        self.pending
+            .lock()
+            .expect("openclaw pending requests mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent_frame(rx: &mut mpsc::UnboundedReceiver<Message>) -> serde_json::Value {
+        match rx.try_recv().expect("a frame was sent") {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    fn response(id: &str, ok: bool) -> ResponseFrame {
+        ResponseFrame {
+            id: id.to_string(),
+            ok,
+            payload: None,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_are_matched_to_their_request_by_id() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let tracker = RequestTracker::new(tx, REQUEST_TIMEOUT);
+
+        let first = tracker.request("node.event", None).unwrap();
+        let second = tracker.request("node.invoke.result", None).unwrap();
+        let first_frame = sent_frame(&mut rx);
+        let second_frame = sent_frame(&mut rx);
+        assert_eq!(first_frame["method"], "node.event");
+        assert_eq!(second_frame["method"], "node.invoke.result");
+
+        assert!(tracker.resolve(response(second_frame["id"].as_str().unwrap(), false)));
+        assert!(tracker.resolve(response(first_frame["id"].as_str().unwrap(), true)));
+        assert!(first.await.unwrap().ok);
+        assert!(!second.await.unwrap().ok);
+
+        assert!(!tracker.resolve(response("unknown", true)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unanswered_requests_time_out_and_are_forgotten() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let tracker = RequestTracker::new(tx, Duration::from_secs(5));
+
+        let waiter = tracker.request("connect", None).unwrap();
+        let id = sent_frame(&mut rx)["id"].as_str().unwrap().to_string();
+        let err = waiter.await.unwrap_err().to_string();
+        assert!(
+            err.contains("connect") && err.contains("timed out"),
+            "{err}"
+        );
+        assert!(!tracker.resolve(response(&id, true)));
+    }
+
+    #[tokio::test]
+    async fn closing_fails_waiting_requests() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let tracker = RequestTracker::new(tx, REQUEST_TIMEOUT);
+
+        let waiter = tracker.request("node.invoke.result", None).unwrap();
+        tracker.close();
+        assert!(waiter.await.is_err());
+
+        drop(rx);
+        assert!(tracker.request("node.event", None).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Binary lookups for system.which. Gateways poll it aggressively, so what
+//! a lookup found (and, once probed, the binary's version) is reused for
+//! `WHICH_CACHE_TTL` per binary and PATH.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use tokio::process::Command;
+
+/// How long a lookup is reused.
+pub const WHICH_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A binary that hasn't printed its version by now reports none.
+pub const VERSION_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where a binary was found on one PATH.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lookup {
+    pub path: Option<String>,
+    /// `None` until probed; then the first line `--version` printed, if any.
+    pub version: Option<Option<String>>,
+}
+
+pub struct WhichCache {
+    /// Keyed by (binary, PATH searched).
+    entries: HashMap<(String, String), (Instant, Lookup)>,
+    ttl: Duration,
+}
+
+impl WhichCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, bin: &str, path_env: &str, now: Instant) -> Option<Lookup> {
+        let (at, lookup) = self.entries.get(&(bin.to_string(), path_env.to_string()))?;
+        (now.saturating_duration_since(*at) < self.ttl).then(|| lookup.clone())
+    }
+
+    pub fn insert(&mut self, bin: &str, path_env: &str, lookup: Lookup, now: Instant) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, (at, _)| now.saturating_duration_since(*at) < ttl);
+        self.entries
+            .insert((bin.to_string(), path_env.to_string()), (now, lookup));
+    }
+}
+
+/// Run `exe --version` and return the first non-empty line it prints
+/// (stdout, else stderr, where some tools write it), giving up after
+/// `timeout`.
+pub async fn probe_version(exe: &Path, timeout: Duration) -> Option<String> {
+    let child = Command::new(exe)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .ok()?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .ok()?
+        .ok()?;
+    let first_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(String::from)
+    };
+    first_line(&output.stdout).or_else(|| first_line(&output.stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_expire_after_the_ttl() {
+        let mut cache = WhichCache::new(WHICH_CACHE_TTL);
+        let start = Instant::now();
+        let lookup = Lookup {
+            path: Some("/usr/bin/git".into()),
+            version: None,
+        };
+        cache.insert("git", "/usr/bin", lookup.clone(), start);
+
+        assert_eq!(
+            cache.get("git", "/usr/bin", start + Duration::from_secs(59)),
+            Some(lookup)
+        );
+        assert_eq!(cache.get("git", "/opt/bin", start), None);
+        assert_eq!(cache.get("git", "/usr/bin", start + WHICH_CACHE_TTL), None);
+    }
+
+    #[cfg(unix)]
+    fn script(dir: &Path, name: &str, body: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn versions_come_from_the_first_line_printed() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout = script(dir.path(), "tool", "printf '\\ntool 1.2.3\\nextra\\n'");
+        let stderr = script(dir.path(), "legacy", "echo 'legacy version 9' >&2");
+
+        assert_eq!(
+            probe_version(&stdout, VERSION_PROBE_TIMEOUT)
+                .await
+                .as_deref(),
+            Some("tool 1.2.3")
+        );
+        assert_eq!(
+            probe_version(&stderr, VERSION_PROBE_TIMEOUT)
+                .await
+                .as_deref(),
+            Some("legacy version 9")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_hanging_version_probe_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let hang = script(dir.path(), "hang", "exec sleep 30");
+
+        let started = Instant::now();
+        assert_eq!(probe_version(&hang, Duration::from_millis(200)).await, None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
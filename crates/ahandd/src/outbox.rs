@@ -1,7 +1,36 @@
 use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use ahand_protocol::Envelope;
+use ahand_protocol::{Envelope, compression};
 use prost::Message;
+use tracing::{info, warn};
+
+/// File name of the outbox journal in the data dir.
+pub const OUTBOX_FILE_NAME: &str = "outbox.log";
+
+/// Longest an appended record may sit in the write buffer.
+pub const OUTBOX_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Received messages after which a standalone `Ack` is due.
+pub const ACK_EVERY_MESSAGES: u64 = 32;
+
+/// How long a received message may go unacknowledged when nothing else is
+/// sent that would carry the ack.
+pub const ACK_IDLE: Duration = Duration::from_millis(500);
+
+/// Journal records before compaction is considered.
+const COMPACT_AFTER_RECORDS: usize = 4096;
+
+/// Journal record tags. A record is the tag, a big-endian u64 seq and, for
+/// envelopes, a big-endian u32 length and the encoded bytes.
+const TAG_ENVELOPE: u8 = b'E';
+const TAG_ACK: u8 = b'A';
+/// Highest seq received from the peer, so replays are still recognised as
+/// duplicates after a restart.
+const TAG_RECV: u8 = b'R';
 
 /// Outbox tracks outbound seq, inbound ack, and buffers unacknowledged messages
 /// for replay on reconnect.
@@ -11,9 +40,29 @@ pub struct Outbox {
     peer_ack: u64,
     /// Highest seq we have received from the peer.
     local_ack: u64,
+    /// `local_ack` as last sent to the peer, piggybacked or in an `Ack`.
+    sent_ack: u64,
+    /// When `local_ack` first got ahead of `sent_ack`.
+    unacked_since: Option<Instant>,
     /// Buffer of (seq, encoded bytes) for unacked outbound messages.
     buffer: VecDeque<(u64, Vec<u8>)>,
     max_buffer: usize,
+    /// On-disk copy of the buffer, when opened with [`Outbox::open`].
+    journal: Option<Journal>,
+    /// Job output chunks this big or bigger are compressed; `None` = never.
+    compress_over: Option<usize>,
+    /// Whether the peer on the current connection reads compressed envelopes.
+    peer_decompresses: bool,
+}
+
+/// Append-only log of stored envelopes, peer acks and received seqs, so
+/// unacked messages and inbound dedup survive a daemon restart.
+struct Journal {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// Records in the file, live or not.
+    records: usize,
+    last_flush: Instant,
 }
 
 impl Outbox {
@@ -22,9 +71,95 @@ impl Outbox {
             next_seq: 1,
             peer_ack: 0,
             local_ack: 0,
+            sent_ack: 0,
+            unacked_since: None,
             buffer: VecDeque::new(),
             max_buffer,
+            journal: None,
+            compress_over: None,
+            peer_decompresses: false,
+        }
+    }
+
+    /// An outbox journaled to `path`, holding the messages a previous run
+    /// left unacked. Numbering continues after the highest seq it used.
+    pub fn open(path: &Path, max_buffer: usize) -> std::io::Result<Self> {
+        let mut outbox = Self::new(max_buffer);
+        let records = match File::open(path) {
+            Ok(mut file) => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                outbox.replay(&data)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        outbox.journal = Some(Journal::rewrite(
+            path,
+            outbox.peer_ack,
+            outbox.local_ack,
+            &outbox.buffer,
+        )?);
+        if records > 0 {
+            info!(
+                unacked = outbox.buffer.len(),
+                next_seq = outbox.next_seq,
+                "outbox restored from journal"
+            );
         }
+        Ok(outbox)
+    }
+
+    /// Apply journal records from `data`; returns how many were read. A
+    /// record cut short by a crash ends the replay.
+    fn replay(&mut self, mut data: &[u8]) -> usize {
+        let mut records = 0;
+        let mut max_seq = 0;
+        while let Some((&tag, rest)) = data.split_first() {
+            let Some((seq, rest)) = rest.split_first_chunk::<8>() else {
+                break;
+            };
+            let seq = u64::from_be_bytes(*seq);
+            match tag {
+                TAG_ENVELOPE => {
+                    let Some((len, rest)) = rest.split_first_chunk::<4>() else {
+                        break;
+                    };
+                    let len = u32::from_be_bytes(*len) as usize;
+                    if rest.len() < len {
+                        break;
+                    }
+                    self.buffer.push_back((seq, rest[..len].to_vec()));
+                    max_seq = max_seq.max(seq);
+                    data = &rest[len..];
+                }
+                TAG_ACK => {
+                    self.peer_ack = self.peer_ack.max(seq);
+                    data = rest;
+                }
+                TAG_RECV => {
+                    self.local_ack = self.local_ack.max(seq);
+                    data = rest;
+                }
+                _ => {
+                    warn!(
+                        tag,
+                        "outbox journal has an unknown record; ignoring the rest"
+                    );
+                    break;
+                }
+            }
+            records += 1;
+        }
+        let peer_ack = self.peer_ack;
+        self.buffer.retain(|(seq, _)| *seq > peer_ack);
+        while self.buffer.len() > self.max_buffer {
+            self.buffer.pop_front();
+        }
+        self.next_seq = max_seq.max(peer_ack) + 1;
+        // The next Hello carries local_ack, so the peer learns it anyway.
+        self.sent_ack = self.local_ack;
+        records
     }
 
     /// Assign the next seq and current local_ack to an outbound envelope.
@@ -33,12 +168,31 @@ impl Outbox {
         let seq = self.next_seq;
         self.next_seq += 1;
         envelope.seq = seq;
-        envelope.ack = self.local_ack;
+        envelope.ack = self.take_ack();
         seq
     }
 
+    /// The ack to send now; the peer is then considered up to date.
+    pub fn take_ack(&mut self) -> u64 {
+        self.sent_ack = self.local_ack;
+        self.unacked_since = None;
+        self.local_ack
+    }
+
+    /// Whether a standalone `Ack` should go out at `now`: the peer is
+    /// [`ACK_EVERY_MESSAGES`] behind, or has been behind for [`ACK_IDLE`]
+    /// without outbound traffic to carry the ack.
+    pub fn needs_ack(&self, now: Instant) -> bool {
+        let behind = self.local_ack - self.sent_ack;
+        behind >= ACK_EVERY_MESSAGES
+            || self
+                .unacked_since
+                .is_some_and(|since| now.saturating_duration_since(since) >= ACK_IDLE)
+    }
+
     /// Store an encoded message in the outbox buffer for potential replay.
     pub fn store(&mut self, seq: u64, data: Vec<u8>) {
+        with_journal(&mut self.journal, |j| j.append(TAG_ENVELOPE, seq, &data));
         self.buffer.push_back((seq, data));
         // Evict oldest if over capacity.
         while self.buffer.len() > self.max_buffer {
@@ -47,16 +201,27 @@ impl Outbox {
     }
 
     /// Called when we receive a message from the peer — update local_ack.
-    pub fn on_recv(&mut self, seq: u64) {
-        if seq > self.local_ack {
-            self.local_ack = seq;
+    /// Returns `false` for a seq already received, which must not be
+    /// processed again. Unsequenced (seq 0) messages are always new.
+    pub fn on_recv(&mut self, seq: u64) -> bool {
+        if seq == 0 {
+            return true;
         }
+        if seq <= self.local_ack {
+            return false;
+        }
+        self.local_ack = seq;
+        self.unacked_since.get_or_insert_with(Instant::now);
+        with_journal(&mut self.journal, |j| j.append(TAG_RECV, seq, &[]));
+        self.maybe_compact();
+        true
     }
 
     /// Called when we see the peer's ack field — remove acknowledged messages.
     pub fn on_peer_ack(&mut self, ack: u64) {
         if ack > self.peer_ack {
             self.peer_ack = ack;
+            with_journal(&mut self.journal, |j| j.append(TAG_ACK, ack, &[]));
         }
         while let Some((seq, _)) = self.buffer.front() {
             if *seq <= self.peer_ack {
@@ -65,11 +230,56 @@ impl Outbox {
                 break;
             }
         }
+        self.maybe_compact();
     }
 
-    /// After reconnect, drain all unacked messages for replay.
+    /// Rewrite the journal down to the live set once mostly dead records.
+    fn maybe_compact(&mut self) {
+        let live = self.buffer.len();
+        if self
+            .journal
+            .as_ref()
+            .is_some_and(|j| j.records > COMPACT_AFTER_RECORDS.max(live * 2))
+        {
+            let (peer_ack, local_ack, buffer) = (self.peer_ack, self.local_ack, &self.buffer);
+            with_journal(&mut self.journal, |j| {
+                *j = Journal::rewrite(&j.path, peer_ack, local_ack, buffer)?;
+                Ok(())
+            });
+        }
+    }
+
+    /// Write buffered journal records to disk.
+    pub fn flush(&mut self) {
+        with_journal(&mut self.journal, Journal::flush);
+    }
+
+    /// After reconnect, drain all unacked messages for replay. Messages
+    /// compressed for an earlier peer are sent plain if this one can't read
+    /// them.
     pub fn drain_unacked(&self) -> Vec<Vec<u8>> {
-        self.buffer.iter().map(|(_, data)| data.clone()).collect()
+        self.buffer
+            .iter()
+            .map(|(_, data)| {
+                if self.peer_decompresses {
+                    data.clone()
+                } else {
+                    decompressed(data)
+                }
+            })
+            .collect()
+    }
+
+    /// Compress job output chunks of at least `threshold` bytes for peers
+    /// that accept it; `None` turns compression off.
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compress_over = threshold;
+    }
+
+    /// Record whether the peer just handshaken with reads compressed
+    /// envelopes (`HelloAccepted.compression`).
+    pub fn set_peer_decompresses(&mut self, decompresses: bool) {
+        self.peer_decompresses = decompresses;
     }
 
     /// The highest seq we received from the peer, used in Hello.last_ack on reconnect.
@@ -84,10 +294,271 @@ impl Outbox {
     }
 }
 
-/// Stamp, encode, store in outbox, and return the encoded bytes.
+/// Stamp, encode, store in outbox, and return the encoded bytes. Large job
+/// output is compressed in the encoding when the peer negotiated it;
+/// `envelope` itself stays plain.
 pub fn prepare_outbound(outbox: &mut Outbox, envelope: &mut Envelope) -> Vec<u8> {
     let seq = outbox.stamp(envelope);
-    let data = envelope.encode_to_vec();
+    let data = match outbox.compress_over.filter(|_| outbox.peer_decompresses) {
+        Some(threshold) => compression::encode(envelope, threshold),
+        None => envelope.encode_to_vec(),
+    };
     outbox.store(seq, data.clone());
     data
 }
+
+/// `data` re-encoded without compression, or as is if it wasn't compressed.
+fn decompressed(data: &[u8]) -> Vec<u8> {
+    match Envelope::decode(data) {
+        Ok(mut envelope) if envelope.compressed => match compression::decompress(&mut envelope) {
+            Ok(()) => envelope.encode_to_vec(),
+            Err(e) => {
+                warn!(error = %e, seq = envelope.seq, "failed to decompress buffered envelope");
+                data.to_vec()
+            }
+        },
+        _ => data.to_vec(),
+    }
+}
+
+impl Journal {
+    /// Replace the file at `path` with the live set: the peer's ack, the
+    /// highest seq received and the still-unacked envelopes. Written via a temp file and rename so a crash
+    /// never leaves it half-written.
+    fn rewrite(
+        path: &Path,
+        peer_ack: u64,
+        local_ack: u64,
+        buffer: &VecDeque<(u64, Vec<u8>)>,
+    ) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("log.tmp");
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        write_record(&mut file, TAG_ACK, peer_ack, &[])?;
+        write_record(&mut file, TAG_RECV, local_ack, &[])?;
+        for (seq, data) in buffer {
+            write_record(&mut file, TAG_ENVELOPE, *seq, data)?;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(OpenOptions::new().append(true).open(path)?),
+            records: buffer.len() + 2,
+            last_flush: Instant::now(),
+        })
+    }
+
+    fn append(&mut self, tag: u8, seq: u64, data: &[u8]) -> std::io::Result<()> {
+        write_record(&mut self.file, tag, seq, data)?;
+        self.records += 1;
+        if self.last_flush.elapsed() >= OUTBOX_FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.last_flush = Instant::now();
+        self.file.flush()
+    }
+}
+
+/// Run `op` on `journal`. On failure the journal is dropped and the outbox
+/// carries on in memory only.
+fn with_journal(
+    journal: &mut Option<Journal>,
+    op: impl FnOnce(&mut Journal) -> std::io::Result<()>,
+) {
+    if let Some(j) = journal
+        && let Err(e) = op(j)
+    {
+        warn!(
+            path = %j.path.display(),
+            error = %e,
+            "outbox journal failed; unacked messages will not survive a restart"
+        );
+        *journal = None;
+    }
+}
+
+fn write_record(out: &mut impl Write, tag: u8, seq: u64, data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&[tag])?;
+    out.write_all(&seq.to_be_bytes())?;
+    if tag == TAG_ENVELOPE {
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        out.write_all(data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(n: u8) -> Envelope {
+        Envelope {
+            device_id: "dev-1".to_string(),
+            msg_id: format!("msg-{n}"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ack_is_due_after_enough_messages_or_enough_quiet() {
+        let mut outbox = Outbox::new(16);
+        let start = Instant::now();
+        assert!(!outbox.needs_ack(start + ACK_IDLE * 10));
+
+        outbox.on_recv(1);
+        assert!(!outbox.needs_ack(Instant::now()));
+        assert!(outbox.needs_ack(Instant::now() + ACK_IDLE));
+
+        // Outbound traffic carries the ack and restarts the clock.
+        let mut reply = envelope(1);
+        outbox.stamp(&mut reply);
+        assert_eq!(reply.ack, 1);
+        assert!(!outbox.needs_ack(Instant::now() + ACK_IDLE));
+
+        // A burst reaches the threshold before the timer would fire.
+        for seq in 2..=ACK_EVERY_MESSAGES {
+            outbox.on_recv(seq);
+        }
+        assert!(!outbox.needs_ack(Instant::now()));
+        outbox.on_recv(ACK_EVERY_MESSAGES + 1);
+        assert!(outbox.needs_ack(Instant::now()));
+        assert_eq!(outbox.take_ack(), ACK_EVERY_MESSAGES + 1);
+        assert!(!outbox.needs_ack(Instant::now() + ACK_IDLE));
+
+        // Replays of messages already received don't reopen the window.
+        outbox.on_recv(5);
+        assert!(!outbox.needs_ack(Instant::now() + ACK_IDLE));
+    }
+
+    #[test]
+    fn unacked_messages_survive_a_restart_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE_NAME);
+
+        let mut outbox = Outbox::open(&path, 16).unwrap();
+        for n in 1..=4 {
+            prepare_outbound(&mut outbox, &mut envelope(n));
+        }
+        outbox.on_peer_ack(2);
+        let before = outbox.drain_unacked();
+        outbox.flush();
+        drop(outbox);
+
+        let mut restarted = Outbox::open(&path, 16).unwrap();
+        assert_eq!(restarted.drain_unacked(), before);
+        assert_eq!(before.len(), 2);
+        let mut next = envelope(5);
+        assert_eq!(restarted.stamp(&mut next), 5);
+    }
+
+    #[test]
+    fn replayed_inbound_seqs_are_duplicates_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE_NAME);
+
+        let mut outbox = Outbox::open(&path, 16).unwrap();
+        assert!(outbox.on_recv(1));
+        assert!(outbox.on_recv(2));
+        assert!(!outbox.on_recv(2));
+        assert!(!outbox.on_recv(1));
+        assert!(
+            outbox.on_recv(0),
+            "unsequenced messages are never duplicates"
+        );
+        outbox.flush();
+        drop(outbox);
+
+        let mut restarted = Outbox::open(&path, 16).unwrap();
+        assert_eq!(restarted.local_ack(), 2);
+        assert!(!restarted.needs_ack(Instant::now() + ACK_IDLE));
+        assert!(!restarted.on_recv(2));
+        assert!(restarted.on_recv(3));
+    }
+
+    #[test]
+    fn a_torn_last_record_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE_NAME);
+        let mut outbox = Outbox::open(&path, 16).unwrap();
+        let first = prepare_outbound(&mut outbox, &mut envelope(1));
+        prepare_outbound(&mut outbox, &mut envelope(2));
+        outbox.flush();
+        drop(outbox);
+
+        // Cut the second envelope short, as a crash mid-write would.
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+        let restarted = Outbox::open(&path, 16).unwrap();
+        assert_eq!(restarted.drain_unacked(), vec![first]);
+    }
+
+    #[test]
+    fn compaction_keeps_only_the_live_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE_NAME);
+        let mut outbox = Outbox::open(&path, 16).unwrap();
+        for n in 0..COMPACT_AFTER_RECORDS {
+            let seq = outbox.stamp(&mut envelope(0));
+            outbox.store(seq, vec![n as u8]);
+            outbox.on_peer_ack(seq);
+        }
+        let kept = prepare_outbound(&mut outbox, &mut envelope(1));
+        outbox.flush();
+        assert!(outbox.journal.as_ref().unwrap().records < COMPACT_AFTER_RECORDS);
+        drop(outbox);
+
+        let mut restarted = Outbox::open(&path, 16).unwrap();
+        assert_eq!(restarted.drain_unacked(), vec![kept]);
+        assert_eq!(
+            restarted.stamp(&mut envelope(2)),
+            COMPACT_AFTER_RECORDS as u64 + 2
+        );
+    }
+
+    #[test]
+    fn job_output_is_compressed_only_for_peers_that_negotiated_it() {
+        let log: Vec<u8> = b"Compiling ahandd v0.1.0\n".repeat(400);
+        let output = || Envelope {
+            payload: Some(ahand_protocol::envelope::Payload::JobEvent(
+                ahand_protocol::JobEvent {
+                    job_id: "job-1".to_string(),
+                    event: Some(ahand_protocol::job_event::Event::StdoutChunk(log.clone())),
+                },
+            )),
+            ..envelope(1)
+        };
+        let mut outbox = Outbox::new(16);
+        outbox.set_compression(Some(1024));
+
+        // An old hub never saw `HelloAccepted.compression`: plain frames.
+        let mut plain = output();
+        let frame = prepare_outbound(&mut outbox, &mut plain);
+        assert_eq!(frame, plain.encode_to_vec());
+
+        outbox.set_peer_decompresses(true);
+        let mut sent = output();
+        let frame = prepare_outbound(&mut outbox, &mut sent);
+        assert!(frame.len() * 10 < log.len(), "{} bytes", frame.len());
+        assert!(!sent.compressed, "the caller's copy stays plain");
+        let mut received = Envelope::decode(frame.as_slice()).unwrap();
+        compression::decompress(&mut received).unwrap();
+        assert_eq!(received, sent);
+
+        // Failing over to an old hub replays both plain.
+        outbox.set_peer_decompresses(false);
+        let replayed = outbox.drain_unacked();
+        assert_eq!(replayed, vec![plain.encode_to_vec(), sent.encode_to_vec()]);
+    }
+}
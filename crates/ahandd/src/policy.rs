@@ -1,13 +1,63 @@
-// Reserved for Mode 5 (preset) — not currently called.
-#![allow(dead_code)]
+//! Job policy: tool allow/deny lists, domain rules, path restrictions and
+//! per-caller rate limits. Every cloud and IPC job is checked against it
+//! before the session mode (see [`check_job`]); `PolicyCheckRequest` dry
+//! runs follow the same order.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use ahand_protocol::{JobRequest, PolicyState, PolicyUpdate};
+use ahand_platform::paths::canonicalize_simplified;
+use ahand_protocol::{
+    JobRequest, PolicyCheckRequest, PolicyCheckResult, PolicyState, PolicyUpdate, RateLimitState,
+    RememberedApproval,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tokio::sync::{Mutex, RwLock};
-use url::Url;
+use tracing::{info, warn};
+use url::{Host, Url};
 
-use crate::config::PolicyConfig;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::config::{ArgRule, ArgRuleAction, PolicyConfig, RateLimitConfig, domain_matches};
+use crate::file_manager::lexically_normalize;
+use crate::presets;
+use crate::session::{SessionDecision, SessionManager, mode_rule};
+
+/// Upper bound for `approval_timeout_secs` set through a [`PolicyUpdate`].
+const MAX_APPROVAL_TIMEOUT_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Why a [`PolicyUpdate`] was rejected.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PolicyError {
+    #[error("{0}: entries must not be empty")]
+    EmptyEntry(&'static str),
+    #[error("{field}: duplicate entry {entry:?}")]
+    DuplicateEntry { field: &'static str, entry: String },
+    #[error("{field}: {message}")]
+    InvalidPattern {
+        field: &'static str,
+        message: String,
+    },
+    #[error(
+        "approval_timeout_secs must be between 1 and {MAX_APPROVAL_TIMEOUT_SECS} (30 days), got {0}"
+    )]
+    TimeoutOutOfRange(u64),
+}
+
+impl PolicyError {
+    /// Stable code sent in the protocol `Error` payload.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::EmptyEntry(_) => "policy.empty_entry",
+            Self::DuplicateEntry { .. } => "policy.duplicate_entry",
+            Self::InvalidPattern { .. } => "policy.invalid_pattern",
+            Self::TimeoutOutOfRange(_) => "policy.timeout_out_of_range",
+        }
+    }
+}
 
 /// Three-way policy decision.
 pub enum PolicyDecision {
@@ -18,56 +68,449 @@ pub enum PolicyDecision {
     /// Not explicitly allowed — suspend and request user approval.
     NeedsApproval {
         reason: String,
+        // Only tests read it; approval requests extract the domains
+        // themselves.
+        #[allow(dead_code)]
         detected_domains: Vec<String>,
     },
 }
 
+/// One entry of `allowed_tools` / `denied_tools`. Plain strings match
+/// exactly; `glob:` and `re:` prefixes select pattern matching.
+enum ToolMatcher {
+    Exact(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl ToolMatcher {
+    fn parse(entry: &str) -> Result<Self, String> {
+        if let Some(pattern) = entry.strip_prefix("glob:") {
+            glob::Pattern::new(pattern)
+                .map(Self::Glob)
+                .map_err(|e| format!("invalid tool pattern {entry:?}: {e}"))
+        } else if let Some(pattern) = entry.strip_prefix("re:") {
+            Regex::new(pattern)
+                .map(Self::Regex)
+                .map_err(|e| format!("invalid tool pattern {entry:?}: {e}"))
+        } else {
+            Ok(Self::Exact(entry.to_string()))
+        }
+    }
+
+    fn matches(&self, candidate: &str) -> bool {
+        match self {
+            Self::Exact(tool) => tool == candidate,
+            Self::Glob(pattern) => pattern.matches(candidate),
+            Self::Regex(re) => re.is_match(candidate),
+        }
+    }
+}
+
+/// Built-in dangerous-argument rules, disabled by `builtin_arg_rules = false`.
+/// Each entry is `(name, tool, pattern)`; all of them deny.
+const BUILTIN_ARG_RULES: &[(&str, &str, &str)] = &[
+    (
+        "builtin:rm-recursive-root",
+        "rm",
+        r"(^|\s)(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)(\s.*)?\s/\*?(\s|$)",
+    ),
+    (
+        "builtin:dd-to-block-device",
+        "dd",
+        r"(^|\s)of=/dev/(sd|hd|nvme|mmcblk|disk)",
+    ),
+    (
+        "builtin:chmod-777-root",
+        "chmod",
+        r"(^|\s)(-[a-zA-Z]*R[a-zA-Z]*|--recursive)\s(.*\s)?0?777\s(.*\s)?/(\s|$)",
+    ),
+];
+
+/// An [`ArgRule`] with its tool matcher and pattern compiled.
+struct CompiledArgRule {
+    name: String,
+    tool: ToolMatcher,
+    pattern: Regex,
+    action: ArgRuleAction,
+}
+
+impl CompiledArgRule {
+    fn compile(rule: &ArgRule) -> Result<Self, String> {
+        let name = rule
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{} /{}/", rule.tool, rule.pattern));
+        let tool = ToolMatcher::parse(&rule.tool)?;
+        let pattern = Regex::new(&rule.pattern)
+            .map_err(|e| format!("invalid argument rule {name:?}: {e}"))?;
+        Ok(Self {
+            name,
+            tool,
+            pattern,
+            action: rule.action,
+        })
+    }
+}
+
+/// Compiled form of the tool lists and argument rules, rebuilt whenever the
+/// config changes.
+#[derive(Default)]
+struct CompiledRules {
+    allowed: Vec<ToolMatcher>,
+    denied: Vec<ToolMatcher>,
+    arg_rules: Vec<CompiledArgRule>,
+    /// `denied_paths` entries alongside their normalized form.
+    denied_paths: Vec<(String, PathBuf)>,
+    /// Entries that failed to compile; they never match.
+    errors: Vec<String>,
+}
+
+impl CompiledRules {
+    fn compile(cfg: &PolicyConfig) -> Self {
+        let mut lists = Self::default();
+        for entry in &cfg.allowed_tools {
+            match ToolMatcher::parse(entry) {
+                Ok(m) => lists.allowed.push(m),
+                Err(e) => lists.errors.push(e),
+            }
+        }
+        for entry in &cfg.denied_tools {
+            match ToolMatcher::parse(entry) {
+                Ok(m) => lists.denied.push(m),
+                Err(e) => lists.errors.push(e),
+            }
+        }
+        let builtin: &[_] = if cfg.builtin_arg_rules {
+            BUILTIN_ARG_RULES
+        } else {
+            &[]
+        };
+        let builtin = builtin.iter().map(|(name, tool, pattern)| ArgRule {
+            name: Some(name.to_string()),
+            tool: tool.to_string(),
+            pattern: pattern.to_string(),
+            action: ArgRuleAction::Deny,
+        });
+        for rule in builtin.chain(cfg.arg_rules.iter().cloned()) {
+            match CompiledArgRule::compile(&rule) {
+                Ok(r) => lists.arg_rules.push(r),
+                Err(e) => lists.errors.push(e),
+            }
+        }
+        lists.denied_paths = cfg
+            .denied_paths
+            .iter()
+            .map(|p| (p.clone(), normalize_path(p)))
+            .collect();
+        lists
+    }
+
+    /// First argument rule that fires for `req`, deny rules taking
+    /// precedence over needs-approval ones.
+    fn matching_arg_rule(&self, req: &JobRequest) -> Option<&CompiledArgRule> {
+        let joined = req.args.join(" ");
+        let mut fired = self.arg_rules.iter().filter(|r| {
+            any_matches(std::slice::from_ref(&r.tool), &req.tool) && r.pattern.is_match(&joined)
+        });
+        let first = fired.next()?;
+        if first.action == ArgRuleAction::Deny {
+            return Some(first);
+        }
+        Some(
+            fired
+                .find(|r| r.action == ArgRuleAction::Deny)
+                .unwrap_or(first),
+        )
+    }
+}
+
+/// Match `tool` as sent (full path or bare name) and by its basename.
+fn any_matches(matchers: &[ToolMatcher], tool: &str) -> bool {
+    let base = tool.rsplit(['/', '\\']).next().unwrap_or(tool);
+    matchers
+        .iter()
+        .any(|m| m.matches(tool) || (base != tool && m.matches(base)))
+}
+
+/// How often expired remembered approvals are swept.
+const APPROVAL_SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// One remembered approval key, as stored in `approvals.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Remembered {
+    created_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_secs: Option<u64>,
+}
+
+impl Remembered {
+    fn expires_at_ms(&self) -> Option<u64> {
+        self.ttl_secs
+            .map(|ttl| self.created_at_ms.saturating_add(ttl.saturating_mul(1000)))
+    }
+
+    fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_at_ms().is_some_and(|at| now_ms >= at)
+    }
+}
+
+/// caller_uid -> approved `tool:`/`domain:` key -> entry.
+type Approvals = BTreeMap<String, BTreeMap<String, Remembered>>;
+
+/// Per-caller token buckets for `policy.rate_limit`. Time is passed in so
+/// tests can drive the refill.
+struct RateLimiter {
+    per_minute: u32,
+    capacity: f64,
+    buckets: HashMap<String, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(cfg: &RateLimitConfig) -> Self {
+        Self {
+            per_minute: cfg.max_jobs_per_minute,
+            capacity: f64::from(cfg.burst.unwrap_or(cfg.max_jobs_per_minute)),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Tokens in a bucket last updated at `updated`, as of `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * f64::from(self.per_minute) / 60.0).min(self.capacity)
+    }
+
+    /// Take a token for `caller_uid`; false when the bucket is empty.
+    fn try_take(&mut self, caller_uid: &str, now: Instant) -> bool {
+        let capacity = self.capacity;
+        let bucket = self.buckets.remove(caller_uid).unwrap_or(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let mut tokens = self.refilled(&bucket, now);
+        let taken = tokens >= 1.0;
+        if taken {
+            tokens -= 1.0;
+        }
+        self.buckets.insert(
+            caller_uid.to_string(),
+            Bucket {
+                tokens,
+                updated: now,
+            },
+        );
+        taken
+    }
+
+    fn state(&self, now: Instant) -> Vec<RateLimitState> {
+        let mut state: Vec<RateLimitState> = self
+            .buckets
+            .iter()
+            .map(|(caller, bucket)| RateLimitState {
+                caller_uid: caller.clone(),
+                tokens: self.refilled(bucket, now) as u32,
+            })
+            .collect();
+        state.sort_by(|a, b| a.caller_uid.cmp(&b.caller_uid));
+        state
+    }
+}
+
 pub struct PolicyChecker {
     config: RwLock<PolicyConfig>,
-    /// Per-user session approvals: caller_uid -> set of approved tool/domain keys.
-    session_approvals: Mutex<HashMap<String, HashSet<String>>>,
+    rules: RwLock<CompiledRules>,
+    /// Per-user remembered approvals.
+    session_approvals: Mutex<Approvals>,
+    /// Where remembered approvals are persisted; `None` keeps them in memory.
+    approvals_path: Option<PathBuf>,
+    audit: Option<Arc<AuditLog>>,
+    /// `None` when `policy.rate_limit` is unset.
+    rate_limiter: Option<Mutex<RateLimiter>>,
+    /// Where user presets (`<name>.toml`) are looked up.
+    presets_dir: Option<PathBuf>,
+    /// Last preset applied; empty while the config-file policy is live.
+    active_preset: Mutex<String>,
 }
 
 impl PolicyChecker {
     pub fn new(config: &PolicyConfig) -> Self {
         Self {
             config: RwLock::new(config.clone()),
-            session_approvals: Mutex::new(HashMap::new()),
+            rules: RwLock::new(CompiledRules::compile(config)),
+            session_approvals: Mutex::new(Approvals::new()),
+            approvals_path: None,
+            audit: None,
+            rate_limiter: config
+                .rate_limit
+                .as_ref()
+                .map(|cfg| Mutex::new(RateLimiter::new(cfg))),
+            presets_dir: None,
+            active_preset: Mutex::new(String::new()),
         }
     }
 
+    /// Look up user presets in `dir` in addition to the built-in ones.
+    pub fn with_presets_dir(mut self, dir: PathBuf) -> Self {
+        self.presets_dir = Some(dir);
+        self
+    }
+
+    /// Record every [`PolicyChecker::check`] decision in `audit`.
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Persist remembered approvals to `path`, loading any saved there.
+    /// Expired entries are dropped on load.
+    pub fn with_approvals_file(mut self, path: PathBuf) -> Self {
+        let mut approvals = load_approvals(&path);
+        let pruned = prune(&mut approvals, now_ms());
+        if pruned > 0 {
+            info!(pruned, "dropped expired remembered approvals");
+            save_approvals(&path, &approvals);
+        }
+        *self.session_approvals.get_mut() = approvals;
+        self.approvals_path = Some(path);
+        self
+    }
+
     /// Evaluate a job request against the current policy.
     pub async fn check(&self, req: &JobRequest, caller_uid: &str) -> PolicyDecision {
+        self.check_at(req, caller_uid, Instant::now()).await
+    }
+
+    async fn check_at(&self, req: &JobRequest, caller_uid: &str, now: Instant) -> PolicyDecision {
+        // Rate limit first: a flood must not turn into a flood of approval
+        // requests either.
+        let limited = match &self.rate_limiter {
+            Some(limiter) => {
+                let mut limiter = limiter.lock().await;
+                (!limiter.try_take(caller_uid, now)).then_some(limiter.per_minute)
+            }
+            None => None,
+        };
+        let (decision, rule) = match limited {
+            Some(per_minute) => (
+                PolicyDecision::Deny(format!("rate limit exceeded: {per_minute} jobs/min")),
+                Some("rate_limit".to_string()),
+            ),
+            None => self.evaluate(req, caller_uid).await,
+        };
+        if let Some(audit) = &self.audit {
+            let (label, reason) = match &decision {
+                PolicyDecision::Allow => ("allow", ""),
+                PolicyDecision::Deny(reason) => ("deny", reason.as_str()),
+                PolicyDecision::NeedsApproval { reason, .. } => ("needs_approval", reason.as_str()),
+            };
+            audit
+                .record(AuditEntry::for_job(
+                    "policy", req, caller_uid, label, reason, rule,
+                ))
+                .await;
+        }
+        decision
+    }
+
+    /// What [`PolicyChecker::check`] would decide, without taking a
+    /// rate-limit token or writing an audit record. Also returns the rule
+    /// that decided, if any.
+    pub async fn dry_run(
+        &self,
+        req: &JobRequest,
+        caller_uid: &str,
+    ) -> (PolicyDecision, Option<String>) {
+        self.evaluate(req, caller_uid).await
+    }
+
+    /// [`PolicyChecker::check`] without the audit record; also returns the
+    /// name of the rule that decided, if any.
+    async fn evaluate(
+        &self,
+        req: &JobRequest,
+        caller_uid: &str,
+    ) -> (PolicyDecision, Option<String>) {
         let cfg = self.config.read().await;
+        let rules = self.rules.read().await;
 
-        // 1. Denied tools — hard reject.
-        if cfg.denied_tools.contains(&req.tool) {
-            return PolicyDecision::Deny(format!("tool {:?} is in the deny list", req.tool));
+        // 1. Denied tools — hard reject. Checked first, so a deny pattern
+        // beats any allow pattern.
+        if any_matches(&rules.denied, &req.tool) {
+            return (
+                PolicyDecision::Deny(format!("tool {:?} is in the deny list", req.tool)),
+                Some("denied_tools".to_string()),
+            );
         }
 
-        // 2. Denied paths — hard reject.
+        // 2. Denied paths — hard reject. Both sides are normalized so `..`,
+        // symlinks and trailing slashes cannot dodge the prefix match.
         if !req.cwd.is_empty() {
-            for denied in &cfg.denied_paths {
-                if req.cwd.starts_with(denied) {
-                    return PolicyDecision::Deny(format!(
-                        "working directory {:?} is denied by policy",
-                        req.cwd
-                    ));
+            let cwd = normalize_path(&req.cwd);
+            for (denied, prefix) in &rules.denied_paths {
+                if cwd.starts_with(prefix) {
+                    return (
+                        PolicyDecision::Deny(format!(
+                            "working directory {:?} is denied by policy",
+                            req.cwd
+                        )),
+                        Some(format!("denied_paths:{denied}")),
+                    );
                 }
             }
         }
 
-        // 3. Extract domains from network tool arguments.
-        let detected_domains = extract_domains(&req.tool, &req.args);
+        // 3. Extract domains from network tool arguments and proxy env
+        // vars. A denied domain is a hard reject, even if it is also
+        // allowed.
+        let detected_domains = extract_destinations(req);
+        for domain in &detected_domains {
+            let host = destination_host(domain).to_lowercase();
+            if let Some(denied) = cfg
+                .denied_domains
+                .iter()
+                .find(|pattern| domain_matches(&host, pattern))
+            {
+                return (
+                    PolicyDecision::Deny(format!("domain {domain:?} is denied by policy")),
+                    Some(format!("denied_domains:{denied}")),
+                );
+            }
+        }
+
+        // 4. Argument rules — deny outright or force approval even for an
+        // allowed tool.
+        if let Some(rule) = rules.matching_arg_rule(req) {
+            let reason = format!("arguments match policy rule {:?}", rule.name);
+            let decision = match rule.action {
+                ArgRuleAction::Deny => PolicyDecision::Deny(reason),
+                ArgRuleAction::NeedsApproval => PolicyDecision::NeedsApproval {
+                    reason,
+                    detected_domains,
+                },
+            };
+            return (decision, Some(format!("arg_rule:{}", rule.name)));
+        }
 
-        // 4. Check per-user session memory.
+        // 5. Check per-user session memory.
+        // Expired entries are pruned here as well as by the periodic sweep.
         let (tool_remembered, remembered_domains) = {
-            let session = self.session_approvals.lock().await;
+            let mut session = self.session_approvals.lock().await;
+            let now = now_ms();
+            if prune(&mut session, now) > 0 {
+                self.persist(&session);
+            }
             if let Some(approvals) = session.get(caller_uid) {
-                let tr = approvals.contains(&format!("tool:{}", req.tool));
+                let remembered = |key: String| approvals.contains_key(&key);
+                let tr = remembered(format!("tool:{}", req.tool));
                 let rd: HashSet<String> = detected_domains
                     .iter()
-                    .filter(|d| approvals.contains(&format!("domain:{d}")))
+                    .filter(|d| remembered(format!("domain:{d}")))
                     .cloned()
                     .collect();
                 (tr, rd)
@@ -76,46 +519,145 @@ impl PolicyChecker {
             }
         };
 
-        // 5. Tool allowlist check.
+        // 6. Tool allowlist check.
         let tool_allowed = cfg.allowed_tools.is_empty()
-            || cfg.allowed_tools.contains(&req.tool)
+            || any_matches(&rules.allowed, &req.tool)
             || tool_remembered;
 
         if !tool_allowed {
-            return PolicyDecision::NeedsApproval {
-                reason: format!("tool {:?} is not in the allow list", req.tool),
-                detected_domains,
-            };
+            return (
+                PolicyDecision::NeedsApproval {
+                    reason: format!("tool {:?} is not in the allow list", req.tool),
+                    detected_domains,
+                },
+                Some("allowed_tools".to_string()),
+            );
+        }
+
+        // 7. Raw IP destinations — need approval when `deny_ip_literals` is
+        // set, unless explicitly allowed or remembered.
+        if cfg.deny_ip_literals {
+            let raw: Vec<String> = detected_domains
+                .iter()
+                .filter(|d| {
+                    let host = destination_host(d);
+                    ip_literal(host).is_some_and(|ip| !(cfg.allow_private_ips && is_private_ip(ip)))
+                        && !cfg.allowed_domains.iter().any(|a| a == host)
+                        && !remembered_domains.contains(*d)
+                })
+                .cloned()
+                .collect();
+
+            if !raw.is_empty() {
+                return (
+                    PolicyDecision::NeedsApproval {
+                        reason: format!("raw IP destination(s) {} need approval", raw.join(", ")),
+                        detected_domains,
+                    },
+                    Some("deny_ip_literals".to_string()),
+                );
+            }
         }
 
-        // 6. Domain allowlist check — only if domains were detected.
+        // 8. Domain allowlist check — only if domains were detected.
         if !detected_domains.is_empty() && !cfg.allowed_domains.is_empty() {
             let unapproved: Vec<String> = detected_domains
                 .iter()
-                .filter(|d| !cfg.allowed_domains.contains(d) && !remembered_domains.contains(*d))
+                .filter(|d| {
+                    !cfg.allowed_domains.iter().any(|a| a == destination_host(d))
+                        && !remembered_domains.contains(*d)
+                })
                 .cloned()
                 .collect();
 
             if !unapproved.is_empty() {
-                return PolicyDecision::NeedsApproval {
-                    reason: format!("domain(s) {} not in allowed domains", unapproved.join(", ")),
-                    detected_domains,
-                };
+                return (
+                    PolicyDecision::NeedsApproval {
+                        reason: format!(
+                            "domain(s) {} not in allowed domains",
+                            unapproved.join(", ")
+                        ),
+                        detected_domains,
+                    },
+                    Some("allowed_domains".to_string()),
+                );
             }
         }
 
-        PolicyDecision::Allow
+        let rule =
+            (tool_remembered || !remembered_domains.is_empty()).then(|| "remembered".to_string());
+        (PolicyDecision::Allow, rule)
     }
 
-    /// Record an approval in session memory for a specific user.
-    pub async fn remember_approval(&self, caller_uid: &str, tool: &str, domains: &[String]) {
+    /// Record an approval in session memory for a specific user. It lasts
+    /// `ttl_secs`, or `remembered_approval_ttl_secs` when `None`.
+    pub async fn remember_approval(
+        &self,
+        caller_uid: &str,
+        tool: &str,
+        domains: &[String],
+        ttl_secs: Option<u64>,
+    ) {
+        let entry = Remembered {
+            created_at_ms: now_ms(),
+            ttl_secs: match ttl_secs {
+                Some(ttl) => Some(ttl),
+                None => self.config.read().await.remembered_approval_ttl_secs,
+            },
+        };
         let mut session = self.session_approvals.lock().await;
-        let set = session
-            .entry(caller_uid.to_string())
-            .or_insert_with(HashSet::new);
-        set.insert(format!("tool:{tool}"));
+        let keys = session.entry(caller_uid.to_string()).or_default();
+        keys.insert(format!("tool:{tool}"), entry.clone());
         for d in domains {
-            set.insert(format!("domain:{d}"));
+            keys.insert(format!("domain:{d}"), entry.clone());
+        }
+        self.persist(&session);
+    }
+
+    /// Revoke one remembered approval. Returns false if it did not exist.
+    // No IPC or cloud message revokes a single approval yet.
+    #[allow(dead_code)]
+    pub async fn forget_approval(&self, caller_uid: &str, key: &str) -> bool {
+        let mut session = self.session_approvals.lock().await;
+        if !forget(&mut session, caller_uid, key) {
+            return false;
+        }
+        self.persist(&session);
+        true
+    }
+
+    /// Drop expired remembered approvals, returning how many were removed.
+    pub async fn prune_expired(&self) -> usize {
+        let mut session = self.session_approvals.lock().await;
+        let pruned = prune(&mut session, now_ms());
+        if pruned > 0 {
+            self.persist(&session);
+        }
+        pruned
+    }
+
+    /// Unexpired remembered approvals, ordered by caller then key.
+    pub async fn remembered(&self) -> Vec<RememberedApproval> {
+        let now = now_ms();
+        let session = self.session_approvals.lock().await;
+        session
+            .iter()
+            .flat_map(|(caller, keys)| {
+                keys.iter()
+                    .filter(move |(_, entry)| !entry.is_expired(now))
+                    .map(move |(key, entry)| RememberedApproval {
+                        caller_uid: caller.clone(),
+                        key: key.clone(),
+                        created_at_ms: entry.created_at_ms,
+                        expires_at_ms: entry.expires_at_ms().unwrap_or(0),
+                    })
+            })
+            .collect()
+    }
+
+    fn persist(&self, approvals: &Approvals) {
+        if let Some(path) = &self.approvals_path {
+            save_approvals(path, approvals);
         }
     }
 
@@ -127,41 +669,119 @@ impl PolicyChecker {
             denied_tools: cfg.denied_tools.clone(),
             denied_paths: cfg.denied_paths.clone(),
             allowed_domains: cfg.allowed_domains.clone(),
+            denied_domains: cfg.denied_domains.clone(),
             approval_timeout_secs: cfg.approval_timeout_secs,
+            pattern_errors: self.rules.read().await.errors.clone(),
+            remembered: self.remembered().await,
+            rate_limits: match &self.rate_limiter {
+                Some(limiter) => limiter.lock().await.state(Instant::now()),
+                None => Vec::new(),
+            },
+            active_preset: self.active_preset.lock().await.clone(),
+            available_presets: presets::list(self.presets_dir.as_deref()),
         }
     }
 
-    /// Apply an incremental update to the policy.
-    pub async fn apply_update(&self, update: &PolicyUpdate) {
-        let mut cfg = self.config.write().await;
+    /// Apply an incremental update to the policy and return the resulting
+    /// state. The update is validated as a whole first; if any field is
+    /// invalid nothing is changed.
+    pub async fn apply_update(&self, update: &PolicyUpdate) -> Result<PolicyState, PolicyError> {
+        let add_allowed_tools = valid_entries("add_allowed_tools", &update.add_allowed_tools)?;
+        let add_denied_tools = valid_entries("add_denied_tools", &update.add_denied_tools)?;
+        for (field, entries) in [
+            ("add_allowed_tools", &add_allowed_tools),
+            ("add_denied_tools", &add_denied_tools),
+        ] {
+            for entry in entries {
+                ToolMatcher::parse(entry)
+                    .map_err(|message| PolicyError::InvalidPattern { field, message })?;
+            }
+        }
+        let add_denied_paths = valid_entries("add_denied_paths", &update.add_denied_paths)?;
+        let add_allowed_domains =
+            valid_entries("add_allowed_domains", &update.add_allowed_domains)?;
+        let add_denied_domains = valid_entries("add_denied_domains", &update.add_denied_domains)?;
+        let timeout = update.approval_timeout_secs;
+        if timeout > MAX_APPROVAL_TIMEOUT_SECS {
+            return Err(PolicyError::TimeoutOutOfRange(timeout));
+        }
 
-        apply_list_update(
-            &mut cfg.allowed_tools,
-            &update.add_allowed_tools,
-            &update.remove_allowed_tools,
-        );
-        apply_list_update(
-            &mut cfg.denied_tools,
-            &update.add_denied_tools,
-            &update.remove_denied_tools,
-        );
-        apply_list_update(
-            &mut cfg.denied_paths,
-            &update.add_denied_paths,
-            &update.remove_denied_paths,
-        );
-        apply_list_update(
-            &mut cfg.allowed_domains,
-            &update.add_allowed_domains,
-            &update.remove_allowed_domains,
-        );
+        {
+            let mut cfg = self.config.write().await;
+            apply_list_update(
+                &mut cfg.allowed_tools,
+                &add_allowed_tools,
+                &update.remove_allowed_tools,
+            );
+            apply_list_update(
+                &mut cfg.denied_tools,
+                &add_denied_tools,
+                &update.remove_denied_tools,
+            );
+            apply_list_update(
+                &mut cfg.denied_paths,
+                &add_denied_paths,
+                &update.remove_denied_paths,
+            );
+            apply_list_update(
+                &mut cfg.allowed_domains,
+                &add_allowed_domains,
+                &update.remove_allowed_domains,
+            );
+            apply_list_update(
+                &mut cfg.denied_domains,
+                &add_denied_domains,
+                &update.remove_denied_domains,
+            );
+            if timeout > 0 {
+                cfg.approval_timeout_secs = timeout;
+            }
+            *self.rules.write().await = CompiledRules::compile(&cfg);
+        }
 
-        if update.approval_timeout_secs > 0 {
-            cfg.approval_timeout_secs = update.approval_timeout_secs;
+        if update.reset_rate_limits
+            && let Some(limiter) = &self.rate_limiter
+        {
+            limiter.lock().await.buckets.clear();
         }
+
+        if !update.forget_remembered.is_empty() {
+            let mut session = self.session_approvals.lock().await;
+            let mut changed = false;
+            for entry in &update.forget_remembered {
+                changed |= forget(&mut session, &entry.caller_uid, &entry.key);
+            }
+            if changed {
+                self.persist(&session);
+            }
+        }
+
+        Ok(self.get_state().await)
+    }
+
+    /// Replace the whole policy with preset `name`. Remembered approvals
+    /// are dropped unless `keep_approvals` is set. `rate_limit` and
+    /// `audit_max_bytes` keep the values the daemon started with.
+    pub async fn apply_preset(&self, name: &str, keep_approvals: bool) -> Result<(), String> {
+        let preset = presets::load(name, self.presets_dir.as_deref())?;
+        {
+            let mut cfg = self.config.write().await;
+            *self.rules.write().await = CompiledRules::compile(&preset);
+            *cfg = preset;
+            *self.active_preset.lock().await = name.to_string();
+        }
+        if !keep_approvals {
+            let mut session = self.session_approvals.lock().await;
+            session.clear();
+            self.persist(&session);
+        }
+        info!(preset = name, keep_approvals, "applied policy preset");
+        Ok(())
     }
 
     /// Get a clone of the current PolicyConfig (for persisting to file).
+    // Nothing writes the policy back to the config file yet.
+    #[allow(dead_code)]
     pub async fn config_snapshot(&self) -> PolicyConfig {
         self.config.read().await.clone()
     }
@@ -173,6 +793,197 @@ impl PolicyChecker {
     }
 }
 
+/// Gate a job from the cloud or an IPC client. The policy goes first and
+/// its denial is final (the session is not consulted); otherwise the
+/// caller's session mode decides, and an approval the policy asks for is
+/// required even where the session alone would allow the job.
+pub async fn check_job(
+    policy: &PolicyChecker,
+    session_mgr: &SessionManager,
+    req: &JobRequest,
+    caller_uid: &str,
+) -> SessionDecision {
+    let policy_approval = match policy.check(req, caller_uid).await {
+        PolicyDecision::Deny(reason) => return SessionDecision::Deny(reason),
+        PolicyDecision::NeedsApproval { reason, .. } => Some(reason),
+        PolicyDecision::Allow => None,
+    };
+    combine(policy_approval, session_mgr.check(req, caller_uid).await)
+}
+
+/// The session's decision, made to require approval when the policy does.
+fn combine(policy_approval: Option<String>, session: SessionDecision) -> SessionDecision {
+    let Some(policy_reason) = policy_approval else {
+        return session;
+    };
+    match session {
+        SessionDecision::Deny(reason) => SessionDecision::Deny(reason),
+        SessionDecision::Allow => SessionDecision::NeedsApproval {
+            reason: policy_reason,
+            previous_refusals: Vec::new(),
+            previous_approvals: Vec::new(),
+        },
+        SessionDecision::NeedsApproval {
+            reason,
+            previous_refusals,
+            previous_approvals,
+        } => SessionDecision::NeedsApproval {
+            reason: format!("{reason}; {policy_reason}"),
+            previous_refusals,
+            previous_approvals,
+        },
+    }
+}
+
+/// Answer a [`PolicyCheckRequest`] from `sender` with what [`check_job`]
+/// would decide. Nothing runs, no approval is created and no rate-limit
+/// token is taken.
+pub async fn dry_run_check(
+    policy: &PolicyChecker,
+    session_mgr: &SessionManager,
+    check: &PolicyCheckRequest,
+    sender: &str,
+) -> PolicyCheckResult {
+    let caller_uid = if check.caller_uid.is_empty() {
+        sender
+    } else {
+        &check.caller_uid
+    };
+    let req = JobRequest {
+        tool: check.tool.clone(),
+        args: check.args.clone(),
+        cwd: check.cwd.clone(),
+        ..Default::default()
+    };
+
+    let (policy_decision, policy_rule) = policy.dry_run(&req, caller_uid).await;
+    let policy_rule = policy_rule.unwrap_or_default();
+    let ((decision, reason), matched_rule) = match policy_decision {
+        PolicyDecision::Deny(reason) => (("deny", reason), policy_rule),
+        policy_decision => {
+            let policy_approval = match policy_decision {
+                PolicyDecision::NeedsApproval { reason, .. } => Some(reason),
+                _ => None,
+            };
+            let (session, mode) = session_mgr.dry_run(&req, caller_uid).await;
+            // The rule that made the difference: the session's mode, unless
+            // the policy alone asks for approval.
+            let matched_rule = match (&session, &policy_approval) {
+                (SessionDecision::Allow, Some(_)) => policy_rule,
+                _ => mode_rule(mode).to_string(),
+            };
+            let decision = match combine(policy_approval, session) {
+                SessionDecision::Allow => ("allow", String::new()),
+                SessionDecision::Deny(reason) => ("deny", reason),
+                SessionDecision::NeedsApproval { reason, .. } => ("needs_approval", reason),
+            };
+            (decision, matched_rule)
+        }
+    };
+    PolicyCheckResult {
+        decision: decision.to_string(),
+        reason,
+        detected_domains: extract_destinations(&req),
+        matched_rule,
+    }
+}
+
+/// Periodically drop expired remembered approvals. Runs until the process
+/// exits.
+pub async fn sweep_remembered_approvals(policy: Arc<PolicyChecker>) {
+    let mut interval = tokio::time::interval(APPROVAL_SWEEP_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let pruned = policy.prune_expired().await;
+        if pruned > 0 {
+            info!(pruned, "swept expired remembered approvals");
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Remove one key for a caller, dropping the caller once it has none left.
+fn forget(approvals: &mut Approvals, caller_uid: &str, key: &str) -> bool {
+    let Some(keys) = approvals.get_mut(caller_uid) else {
+        return false;
+    };
+    let removed = keys.remove(key).is_some();
+    if keys.is_empty() {
+        approvals.remove(caller_uid);
+    }
+    removed
+}
+
+fn prune(approvals: &mut Approvals, now_ms: u64) -> usize {
+    let mut pruned = 0;
+    approvals.retain(|_, keys| {
+        let before = keys.len();
+        keys.retain(|_, entry| !entry.is_expired(now_ms));
+        pruned += before - keys.len();
+        !keys.is_empty()
+    });
+    pruned
+}
+
+/// Read `approvals.json`. A missing file is empty; an unreadable one is
+/// logged and treated as empty.
+fn load_approvals(path: &Path) -> Approvals {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Approvals::new(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "failed to read remembered approvals");
+            return Approvals::new();
+        }
+    };
+    serde_json::from_slice(&data).unwrap_or_else(|e| {
+        warn!(path = %path.display(), error = %e, "ignoring malformed remembered approvals");
+        Approvals::new()
+    })
+}
+
+/// Write `approvals.json` via a temp file and rename so a crash never
+/// leaves it half-written.
+fn save_approvals(path: &Path, approvals: &Approvals) {
+    let result = serde_json::to_vec_pretty(approvals)
+        .map_err(std::io::Error::other)
+        .and_then(|data| {
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, data)?;
+            std::fs::rename(&tmp, path)
+        });
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "failed to persist remembered approvals");
+    }
+}
+
+/// Trim the entries of one `add_*` list, rejecting blank and repeated
+/// ones.
+fn valid_entries(field: &'static str, entries: &[String]) -> Result<Vec<String>, PolicyError> {
+    let mut out: Vec<String> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            return Err(PolicyError::EmptyEntry(field));
+        }
+        if out.iter().any(|e| e == entry) {
+            return Err(PolicyError::DuplicateEntry {
+                field,
+                entry: entry.to_string(),
+            });
+        }
+        out.push(entry.to_string());
+    }
+    Ok(out)
+}
+
 /// Apply add/remove operations to a list, deduplicating.
 fn apply_list_update(list: &mut Vec<String>, add: &[String], remove: &[String]) {
     // Remove first.
@@ -193,7 +1004,108 @@ const NETWORK_TOOLS: &[&str] = &[
     "nslookup", "http", "https", "fetch",
 ];
 
-/// Extract domain names from tool arguments using heuristics.
+/// Resolve `path` for the `denied_paths` check: symlinks are followed as
+/// far as the path exists, the rest is normalized lexically, and on macOS
+/// `/tmp`, `/var` and `/etc` are spelled with their `/private` prefix.
+fn normalize_path(path: &str) -> PathBuf {
+    let raw = Path::new(path);
+    let resolved = match canonicalize_simplified(raw) {
+        Ok(p) => p,
+        Err(_) => canonicalize_existing_prefix(&lexically_normalize(raw)),
+    };
+    private_alias(resolved)
+}
+
+/// Canonicalize the deepest existing ancestor of `path` and re-append the
+/// rest. Falls back to `path` unchanged if no ancestor resolves.
+fn canonicalize_existing_prefix(path: &Path) -> PathBuf {
+    let mut ancestor = path.to_path_buf();
+    let mut suffix = Vec::new();
+    while let Some(name) = ancestor.file_name().map(|n| n.to_os_string()) {
+        suffix.push(name);
+        if !ancestor.pop() {
+            break;
+        }
+        if let Ok(mut rebuilt) = canonicalize_simplified(&ancestor) {
+            rebuilt.extend(suffix.iter().rev());
+            return rebuilt;
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(target_os = "macos")]
+fn private_alias(path: PathBuf) -> PathBuf {
+    for alias in ["/tmp", "/var", "/etc"] {
+        if let Ok(rest) = path.strip_prefix(alias) {
+            return Path::new("/private")
+                .join(alias.trim_start_matches('/'))
+                .join(rest);
+        }
+    }
+    path
+}
+
+#[cfg(not(target_os = "macos"))]
+fn private_alias(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Marks a destination taken from a proxy env var rather than the args.
+const PROXY_PREFIX: &str = "proxy:";
+
+/// Env vars whose value routes a job's traffic through another host.
+const PROXY_ENV_VARS: &[&str] = &["http_proxy", "https_proxy", "all_proxy", "ftp_proxy"];
+
+/// All network destinations of a job: hosts from the arguments of known
+/// network tools, then `proxy:<host>` for each proxy env var set.
+pub fn extract_destinations(req: &JobRequest) -> Vec<String> {
+    let mut destinations = extract_domains(&req.tool, &req.args);
+    for host in extract_env_domains(&req.env) {
+        let host = format!("{PROXY_PREFIX}{host}");
+        if !destinations.contains(&host) {
+            destinations.push(host);
+        }
+    }
+    destinations
+}
+
+/// Hosts named by proxy env vars (`https_proxy`, `ALL_PROXY`, ...), in
+/// variable name order. Values without a scheme are read as `http://`.
+pub fn extract_env_domains(env: &HashMap<String, String>) -> Vec<String> {
+    let mut vars: Vec<(&String, &String)> = env
+        .iter()
+        .filter(|(name, _)| PROXY_ENV_VARS.contains(&name.to_ascii_lowercase().as_str()))
+        .collect();
+    vars.sort();
+
+    let mut hosts = Vec::new();
+    for (_, value) in vars {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let host = try_extract_url_host(value)
+            .or_else(|| try_extract_url_host(&format!("http://{value}")));
+        if let Some(host) = host
+            && !hosts.contains(&host)
+        {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// The host part of a detected destination (drops the `proxy:` marker).
+fn destination_host(destination: &str) -> &str {
+    destination
+        .strip_prefix(PROXY_PREFIX)
+        .unwrap_or(destination)
+}
+
+/// Extract domain names from tool arguments using heuristics. IPv4 and
+/// IPv6 literals are returned too (without brackets or port), so address
+/// destinations go through the same domain checks.
 pub fn extract_domains(tool: &str, args: &[String]) -> Vec<String> {
     // Only inspect args if the tool is a known network tool.
     let base = tool.rsplit('/').next().unwrap_or(tool);
@@ -225,6 +1137,15 @@ pub fn extract_domains(tool: &str, args: &[String]) -> Vec<String> {
             continue;
         }
 
+        // Raw IP with optional user, port and path: `10.0.0.5:8080/admin`,
+        // `root@[fe80::1]`.
+        if let Some(host) = try_extract_ip_literal(arg) {
+            if !domains.contains(&host) {
+                domains.push(host);
+            }
+            continue;
+        }
+
         // For ssh/ping/dig/nslookup: bare hostname argument (no slashes, contains a dot).
         if matches!(base, "ssh" | "ping" | "dig" | "nslookup" | "nc" | "ncat")
             && !arg.contains('/')
@@ -243,7 +1164,40 @@ pub fn extract_domains(tool: &str, args: &[String]) -> Vec<String> {
 /// Try to parse a string as a URL and extract the host.
 fn try_extract_url_host(s: &str) -> Option<String> {
     let url = Url::parse(s).ok()?;
-    url.host_str().map(|h| h.to_string())
+    match url.host()? {
+        Host::Domain(domain) => Some(domain.to_string()),
+        Host::Ipv4(ip) => Some(ip.to_string()),
+        Host::Ipv6(ip) => Some(ip.to_string()),
+    }
+}
+
+/// Try to read an IP literal from `[user@]ip[:port][/path]`, where an IPv6
+/// address with a port must be bracketed.
+fn try_extract_ip_literal(s: &str) -> Option<String> {
+    let authority = s.split('/').next()?;
+    let authority = authority.rsplit('@').next()?;
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (inner, _) = rest.split_once(']')?;
+        return inner.parse::<Ipv6Addr>().ok().map(|ip| ip.to_string());
+    }
+    if let Ok(ip) = authority.parse::<Ipv6Addr>() {
+        return Some(ip.to_string());
+    }
+    let host = authority.split(':').next()?;
+    host.parse::<Ipv4Addr>().ok().map(|ip| ip.to_string())
+}
+
+/// Parse a detected destination as an IP address.
+pub fn ip_literal(host: &str) -> Option<IpAddr> {
+    host.parse().ok()
+}
+
+/// Loopback, private (RFC 1918 / unique local) and link-local addresses.
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unique_local() || ip.is_unicast_link_local(),
+    }
 }
 
 /// Try to extract host from user@host:path or host:path patterns.
@@ -258,3 +1212,695 @@ fn try_extract_ssh_host(s: &str) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(allowed: &[&str], denied: &[&str]) -> PolicyChecker {
+        PolicyChecker::new(&PolicyConfig {
+            allowed_tools: allowed.iter().map(|s| s.to_string()).collect(),
+            denied_tools: denied.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    fn request(tool: &str) -> JobRequest {
+        JobRequest {
+            job_id: "job".to_string(),
+            tool: tool.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn decide(checker: &PolicyChecker, tool: &str) -> &'static str {
+        decide_req(checker, &request(tool)).await
+    }
+
+    async fn decide_req(checker: &PolicyChecker, req: &JobRequest) -> &'static str {
+        match checker.check(req, "uid:501").await {
+            PolicyDecision::Allow => "allow",
+            PolicyDecision::Deny(_) => "deny",
+            PolicyDecision::NeedsApproval { .. } => "approval",
+        }
+    }
+
+    #[tokio::test]
+    async fn glob_allow_matches_full_path() {
+        let c = checker(&["glob:/usr/local/bin/*"], &[]);
+        assert_eq!(decide(&c, "/usr/local/bin/rg").await, "allow");
+        assert_eq!(decide(&c, "/usr/bin/rg").await, "approval");
+    }
+
+    #[tokio::test]
+    async fn regex_and_exact_entries_match_basename() {
+        let c = checker(&["re:^kubectl(-.*)?$", "git"], &[]);
+        assert_eq!(decide(&c, "kubectl").await, "allow");
+        assert_eq!(decide(&c, "/opt/bin/kubectl-debug").await, "allow");
+        assert_eq!(decide(&c, "/usr/bin/git").await, "allow");
+        assert_eq!(decide(&c, "kubectx").await, "approval");
+    }
+
+    #[tokio::test]
+    async fn deny_pattern_beats_allow_pattern() {
+        let c = checker(&["re:^deploy"], &["glob:*-prod"]);
+        assert_eq!(decide(&c, "deploy-staging").await, "allow");
+        assert_eq!(decide(&c, "deploy-prod").await, "deny");
+        assert_eq!(decide(&c, "/srv/bin/deploy-prod").await, "deny");
+    }
+
+    #[tokio::test]
+    async fn invalid_config_pattern_is_reported_and_ignored() {
+        let c = checker(&["re:(unclosed", "git"], &[]);
+        let state = c.get_state().await;
+        assert_eq!(state.pattern_errors.len(), 1);
+        assert!(state.pattern_errors[0].contains("re:(unclosed"));
+        assert_eq!(decide(&c, "git").await, "allow");
+    }
+
+    #[tokio::test]
+    async fn update_with_invalid_pattern_is_rejected_whole() {
+        let c = checker(&[], &[]);
+        let err = c
+            .apply_update(&PolicyUpdate {
+                add_denied_tools: vec!["glob:[".to_string(), "glob:*-prod".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "policy.invalid_pattern");
+        assert!(err.to_string().contains("glob:["), "{err}");
+        assert!(c.get_state().await.denied_tools.is_empty());
+
+        let state = c
+            .apply_update(&PolicyUpdate {
+                add_denied_tools: vec!["glob:*-prod".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(state.denied_tools, vec!["glob:*-prod".to_string()]);
+        assert!(state.pattern_errors.is_empty());
+        assert_eq!(decide(&c, "db-prod").await, "deny");
+    }
+
+    #[tokio::test]
+    async fn update_validates_entries_and_timeout() {
+        let c = checker(&[], &[]);
+        let update = |f: fn(&mut PolicyUpdate)| {
+            let mut update = PolicyUpdate::default();
+            f(&mut update);
+            update
+        };
+        for (update, code) in [
+            (
+                update(|u| u.add_allowed_tools = vec!["  ".to_string()]),
+                "policy.empty_entry",
+            ),
+            (
+                update(|u| u.add_denied_paths = vec!["/etc".to_string(), "/etc ".to_string()]),
+                "policy.duplicate_entry",
+            ),
+            (
+                update(|u| u.approval_timeout_secs = 10_000_000),
+                "policy.timeout_out_of_range",
+            ),
+        ] {
+            assert_eq!(c.apply_update(&update).await.unwrap_err().code(), code);
+        }
+
+        let state = c
+            .apply_update(&update(|u| {
+                u.add_allowed_domains = vec![" github.com ".to_string()];
+                u.approval_timeout_secs = 30 * 24 * 60 * 60;
+            }))
+            .await
+            .unwrap();
+        assert_eq!(state.allowed_domains, ["github.com"]);
+        assert_eq!(state.approval_timeout_secs, 30 * 24 * 60 * 60);
+    }
+
+    fn with_args(tool: &str, args: &[&str]) -> JobRequest {
+        JobRequest {
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..request(tool)
+        }
+    }
+
+    #[tokio::test]
+    async fn builtin_arg_rules_block_dangerous_invocations() {
+        let c = checker(&[], &[]);
+        for (tool, args) in [
+            ("rm", &["-rf", "/"][..]),
+            ("/bin/rm", &["-r", "-f", "/"][..]),
+            ("rm", &["--recursive", "/*"][..]),
+            ("dd", &["if=/dev/zero", "of=/dev/sda"][..]),
+            ("chmod", &["-R", "777", "/"][..]),
+        ] {
+            assert_eq!(
+                decide_req(&c, &with_args(tool, args)).await,
+                "deny",
+                "{tool} {args:?}"
+            );
+        }
+        for (tool, args) in [
+            ("rm", &["-rf", "/tmp/build"][..]),
+            ("dd", &["if=/dev/zero", "of=disk.img"][..]),
+            ("chmod", &["-R", "755", "/srv/www"][..]),
+        ] {
+            assert_eq!(
+                decide_req(&c, &with_args(tool, args)).await,
+                "allow",
+                "{tool} {args:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn builtin_arg_rules_can_be_disabled() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            builtin_arg_rules: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            decide_req(&c, &with_args("rm", &["-rf", "/"])).await,
+            "allow"
+        );
+    }
+
+    #[tokio::test]
+    async fn arg_rule_reason_names_the_rule() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            allowed_tools: vec!["git".to_string()],
+            arg_rules: vec![ArgRule {
+                name: Some("no-force-push".to_string()),
+                tool: "git".to_string(),
+                pattern: r"^push\b.*(--force|-f\b)".to_string(),
+                action: ArgRuleAction::NeedsApproval,
+            }],
+            ..Default::default()
+        });
+        match c
+            .check(&with_args("git", &["push", "--force"]), "uid:501")
+            .await
+        {
+            PolicyDecision::NeedsApproval { reason, .. } => {
+                assert!(reason.contains("no-force-push"), "{reason}");
+            }
+            _ => panic!("expected NeedsApproval"),
+        }
+        assert_eq!(decide_req(&c, &with_args("git", &["push"])).await, "allow");
+
+        let rm = with_args("rm", &["-rf", "/"]);
+        match c.check(&rm, "uid:501").await {
+            PolicyDecision::Deny(reason) => {
+                assert!(reason.contains("builtin:rm-recursive-root"), "{reason}");
+            }
+            _ => panic!("expected Deny"),
+        }
+    }
+
+    #[tokio::test]
+    async fn remembered_approvals_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("approvals.json");
+        let c = checker(&["git"], &[]).with_approvals_file(path.clone());
+        c.remember_approval("uid:501", "jq", &["example.com".to_string()], None)
+            .await;
+
+        let reloaded = checker(&["git"], &[]).with_approvals_file(path);
+        assert_eq!(decide(&reloaded, "jq").await, "allow");
+        let keys: Vec<String> = reloaded
+            .remembered()
+            .await
+            .into_iter()
+            .map(|r| format!("{} {}", r.caller_uid, r.key))
+            .collect();
+        assert_eq!(keys, ["uid:501 domain:example.com", "uid:501 tool:jq"]);
+    }
+
+    #[tokio::test]
+    async fn expired_approvals_are_pruned_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("approvals.json");
+        std::fs::write(
+            &path,
+            r#"{"uid:501": {
+                "tool:jq": {"created_at_ms": 1000, "ttl_secs": 60},
+                "tool:yq": {"created_at_ms": 1000}
+            }}"#,
+        )
+        .unwrap();
+
+        let c = checker(&["git"], &[]).with_approvals_file(path.clone());
+        assert_eq!(decide(&c, "jq").await, "approval");
+        assert_eq!(decide(&c, "yq").await, "allow");
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("tool:jq"), "{saved}");
+    }
+
+    #[tokio::test]
+    async fn forget_revokes_a_remembered_approval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("approvals.json");
+        let c = checker(&["git"], &[]).with_approvals_file(path.clone());
+        c.remember_approval("uid:501", "jq", &[], None).await;
+        c.remember_approval("uid:501", "yq", &[], None).await;
+
+        assert!(c.forget_approval("uid:501", "tool:jq").await);
+        assert!(!c.forget_approval("uid:501", "tool:jq").await);
+        c.apply_update(&PolicyUpdate {
+            forget_remembered: vec![RememberedApproval {
+                caller_uid: "uid:501".to_string(),
+                key: "tool:yq".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(decide(&c, "jq").await, "approval");
+        assert_eq!(decide(&c, "yq").await, "approval");
+        let reloaded = checker(&["git"], &[]).with_approvals_file(path);
+        assert!(reloaded.remembered().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remembered_approval_with_ttl_stops_matching_once_expired() {
+        let c = checker(&["git"], &[]);
+        c.remember_approval("uid:501", "jq", &[], Some(1)).await;
+        c.remember_approval("uid:501", "yq", &[], None).await;
+        assert_eq!(decide(&c, "jq").await, "allow");
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(decide(&c, "jq").await, "approval");
+        assert_eq!(decide(&c, "yq").await, "allow");
+        let keys: Vec<_> = c.remembered().await.into_iter().map(|r| r.key).collect();
+        assert_eq!(keys, ["tool:yq"]);
+    }
+
+    #[tokio::test]
+    async fn check_records_decision_in_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = Arc::new(AuditLog::open(dir.path(), 0).unwrap());
+        let c = checker(&["git"], &["rm"]).with_audit_log(Arc::clone(&audit));
+        decide(&c, "rm").await;
+        decide(&c, "git").await;
+
+        let entries = crate::audit::read_tail(audit.path(), 10).unwrap();
+        let summary: Vec<(&str, &str, Option<&str>)> = entries
+            .iter()
+            .map(|e| (e.tool.as_str(), e.decision.as_str(), e.rule.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [("rm", "deny", Some("denied_tools")), ("git", "allow", None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limit_denies_past_budget_and_recovers() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            rate_limit: Some(RateLimitConfig {
+                max_jobs_per_minute: 60,
+                burst: None,
+            }),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for i in 0..60 {
+            assert!(
+                matches!(
+                    c.check_at(&request("ls"), "uid:501", start).await,
+                    PolicyDecision::Allow
+                ),
+                "job {i} should be allowed"
+            );
+        }
+        match c.check_at(&request("ls"), "uid:501", start).await {
+            PolicyDecision::Deny(reason) => {
+                assert_eq!(reason, "rate limit exceeded: 60 jobs/min")
+            }
+            _ => panic!("61st job should be denied"),
+        }
+        // Other callers have their own bucket.
+        assert!(matches!(
+            c.check_at(&request("ls"), "uid:502", start).await,
+            PolicyDecision::Allow
+        ));
+
+        // One token per second refills.
+        let later = start + Duration::from_secs(1);
+        assert!(matches!(
+            c.check_at(&request("ls"), "uid:501", later).await,
+            PolicyDecision::Allow
+        ));
+        assert!(matches!(
+            c.check_at(&request("ls"), "uid:501", later).await,
+            PolicyDecision::Deny(_)
+        ));
+
+        c.apply_update(&PolicyUpdate {
+            reset_rate_limits: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(c.get_state().await.rate_limits.is_empty());
+        assert!(matches!(
+            c.check_at(&request("ls"), "uid:501", later).await,
+            PolicyDecision::Allow
+        ));
+    }
+
+    #[tokio::test]
+    async fn denied_domain_wildcard_beats_allowlist() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            allowed_tools: vec!["curl".to_string()],
+            allowed_domains: vec!["api.internal.corp".to_string()],
+            denied_domains: vec!["*.internal.corp".to_string()],
+            ..Default::default()
+        });
+        for url in [
+            "https://api.internal.corp/v1",
+            "https://DB.Internal.Corp/",
+            "https://internal.corp",
+        ] {
+            assert_eq!(
+                decide_req(&c, &with_args("curl", &[url])).await,
+                "deny",
+                "{url}"
+            );
+        }
+        assert_eq!(
+            decide_req(&c, &with_args("curl", &["https://example.com"])).await,
+            "approval"
+        );
+
+        c.apply_update(&PolicyUpdate {
+            remove_denied_domains: vec!["*.internal.corp".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            decide_req(&c, &with_args("curl", &["https://api.internal.corp/v1"])).await,
+            "allow"
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_check_reports_strictest_outcome_without_side_effects() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            denied_tools: vec!["rm".to_string()],
+            rate_limit: Some(RateLimitConfig {
+                max_jobs_per_minute: 1,
+                burst: None,
+            }),
+            ..Default::default()
+        });
+        let sessions = SessionManager::new(60);
+        sessions
+            .set_default_mode(ahand_protocol::SessionMode::AutoAccept)
+            .await;
+        sessions.register_caller("uid:501").await;
+
+        let check = |tool: &str, args: &[&str]| PolicyCheckRequest {
+            tool: tool.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let result = dry_run_check(&c, &sessions, &check("rm", &["-f", "x"]), "uid:501").await;
+        assert_eq!(result.decision, "deny");
+        assert_eq!(result.matched_rule, "denied_tools");
+
+        for _ in 0..3 {
+            let result = dry_run_check(
+                &c,
+                &sessions,
+                &check("curl", &["https://example.com"]),
+                "uid:501",
+            )
+            .await;
+            assert_eq!(result.decision, "allow");
+            assert_eq!(result.matched_rule, "mode:auto_accept");
+            assert_eq!(result.detected_domains, ["example.com"]);
+        }
+        // Dry runs took no rate-limit tokens.
+        assert!(c.get_state().await.rate_limits.is_empty());
+
+        let mut other = check("ls", &[]);
+        other.caller_uid = "uid:999".to_string();
+        let result = dry_run_check(&c, &sessions, &other, "uid:501").await;
+        assert_eq!(result.decision, "deny");
+        assert_eq!(result.reason, "session not activated");
+        assert_eq!(result.matched_rule, "mode:inactive");
+    }
+
+    #[tokio::test]
+    async fn dry_run_check_predicts_the_job_outcome() {
+        use ahand_protocol::SessionMode;
+
+        let c = PolicyChecker::new(&PolicyConfig {
+            denied_tools: vec!["rm".to_string()],
+            allowed_tools: vec!["ls".to_string(), "rm".to_string()],
+            ..Default::default()
+        });
+        let sessions = SessionManager::new(60);
+        for (caller, mode) in [
+            ("uid:auto", SessionMode::AutoAccept),
+            ("uid:strict", SessionMode::Strict),
+            ("uid:inactive", SessionMode::Inactive),
+        ] {
+            sessions.set_mode(caller, mode, 0).await;
+        }
+
+        let label = |decision: SessionDecision| match decision {
+            SessionDecision::Allow => ("allow", String::new()),
+            SessionDecision::Deny(reason) => ("deny", reason),
+            SessionDecision::NeedsApproval { reason, .. } => ("needs_approval", reason),
+        };
+        for caller in ["uid:auto", "uid:strict", "uid:inactive"] {
+            for tool in ["rm", "ls", "curl"] {
+                let check = PolicyCheckRequest {
+                    caller_uid: caller.to_string(),
+                    tool: tool.to_string(),
+                    ..Default::default()
+                };
+                let predicted = dry_run_check(&c, &sessions, &check, "uid:other").await;
+                let req = JobRequest {
+                    tool: tool.to_string(),
+                    ..Default::default()
+                };
+                let (decision, reason) = label(check_job(&c, &sessions, &req, caller).await);
+                assert_eq!(
+                    (predicted.decision.as_str(), predicted.reason.as_str()),
+                    (decision, reason.as_str()),
+                    "{tool} for {caller}"
+                );
+            }
+        }
+    }
+
+    fn domains(tool: &str, args: &[&str]) -> Vec<String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        extract_domains(tool, &args)
+    }
+
+    #[test]
+    fn extract_domains_detects_ip_literals() {
+        assert_eq!(
+            domains("curl", &["http://10.0.0.5:8080/admin"]),
+            ["10.0.0.5"]
+        );
+        assert_eq!(domains("curl", &["10.0.0.5:8080/admin"]), ["10.0.0.5"]);
+        assert_eq!(domains("nc", &["192.168.1.1", "22"]), ["192.168.1.1"]);
+        assert_eq!(domains("curl", &["http://[::1]:3000/"]), ["::1"]);
+        assert_eq!(domains("nc", &["[fe80::1]:22"]), ["fe80::1"]);
+        assert_eq!(domains("ssh", &["root@[2001:db8::1]"]), ["2001:db8::1"]);
+        assert_eq!(domains("ping", &["2001:db8::1"]), ["2001:db8::1"]);
+        assert!(domains("cat", &["10.0.0.5"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn deny_ip_literals_requires_approval_for_raw_addresses() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            deny_ip_literals: true,
+            allowed_domains: vec!["example.com".to_string(), "203.0.113.7".to_string()],
+            ..Default::default()
+        });
+        match c
+            .check(
+                &with_args("curl", &["http://10.0.0.5:8080/admin"]),
+                "uid:501",
+            )
+            .await
+        {
+            PolicyDecision::NeedsApproval {
+                detected_domains, ..
+            } => assert_eq!(detected_domains, ["10.0.0.5"]),
+            _ => panic!("expected NeedsApproval"),
+        }
+        assert_eq!(
+            decide_req(&c, &with_args("curl", &["https://example.com"])).await,
+            "allow"
+        );
+        assert_eq!(
+            decide_req(&c, &with_args("curl", &["http://203.0.113.7/"])).await,
+            "allow"
+        );
+
+        let c = PolicyChecker::new(&PolicyConfig {
+            deny_ip_literals: true,
+            allow_private_ips: true,
+            ..Default::default()
+        });
+        for private in ["10.0.0.5", "127.0.0.1", "[::1]:22", "169.254.1.1"] {
+            assert_eq!(
+                decide_req(&c, &with_args("nc", &[private])).await,
+                "allow",
+                "{private}"
+            );
+        }
+        assert_eq!(
+            decide_req(&c, &with_args("nc", &["8.8.8.8", "53"])).await,
+            "approval"
+        );
+    }
+
+    fn in_cwd(cwd: &Path) -> JobRequest {
+        JobRequest {
+            cwd: cwd.to_string_lossy().into_owned(),
+            ..request("ls")
+        }
+    }
+
+    #[tokio::test]
+    async fn denied_paths_survive_traversal_and_trailing_slashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("secret/sub")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        std::fs::create_dir_all(root.join("secret-public")).unwrap();
+        let c = PolicyChecker::new(&PolicyConfig {
+            denied_paths: vec![format!("{}/", root.join("secret").display())],
+            ..Default::default()
+        });
+
+        for cwd in [
+            root.join("secret"),
+            root.join("secret/sub"),
+            root.join("other/../secret/sub"),
+            root.join("missing/../secret/not-yet"),
+        ] {
+            assert_eq!(decide_req(&c, &in_cwd(&cwd)).await, "deny", "{cwd:?}");
+        }
+        for cwd in [root.join("other"), root.join("secret-public")] {
+            assert_eq!(decide_req(&c, &in_cwd(&cwd)).await, "allow", "{cwd:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn denied_paths_follow_symlinked_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("secret/sub")).unwrap();
+        std::os::unix::fs::symlink(root.join("secret"), root.join("link")).unwrap();
+        let c = PolicyChecker::new(&PolicyConfig {
+            denied_paths: vec![root.join("secret").display().to_string()],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            decide_req(&c, &in_cwd(&root.join("link/sub"))).await,
+            "deny"
+        );
+        assert_eq!(decide_req(&c, &in_cwd(&root.join("link"))).await, "deny");
+    }
+
+    #[tokio::test]
+    async fn apply_preset_replaces_policy_and_optionally_keeps_approvals() {
+        let c = checker(&["git"], &["rm"]);
+        c.remember_approval("uid:501", "jq", &[], None).await;
+
+        c.apply_preset("locked-down", true).await.unwrap();
+        let state = c.get_state().await;
+        assert_eq!(state.active_preset, "locked-down");
+        assert!(state.available_presets.contains(&"developer".to_string()));
+        assert!(!state.denied_tools.contains(&"rm".to_string()));
+        assert_eq!(decide(&c, "sudo").await, "deny");
+        assert_eq!(decide(&c, "git").await, "approval");
+        assert_eq!(decide(&c, "jq").await, "allow");
+
+        c.apply_preset("open", false).await.unwrap();
+        assert!(c.remembered().await.is_empty());
+        assert_eq!(decide(&c, "sudo").await, "allow");
+
+        assert!(c.apply_preset("nope", false).await.is_err());
+        assert_eq!(c.get_state().await.active_preset, "open");
+    }
+
+    fn with_env(req: JobRequest, env: &[(&str, &str)]) -> JobRequest {
+        JobRequest {
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..req
+        }
+    }
+
+    #[test]
+    fn extract_destinations_flags_proxy_env_vars() {
+        let req = with_env(
+            with_args("curl", &["https://example.com"]),
+            &[
+                ("HTTPS_PROXY", "http://exfil.example.net:3128"),
+                ("all_proxy", "socks5h://10.0.0.9:1080"),
+                ("no_proxy", "internal.corp"),
+                ("http_proxy", "exfil.example.net:3128"),
+            ],
+        );
+        assert_eq!(
+            extract_destinations(&req),
+            ["example.com", "proxy:exfil.example.net", "proxy:10.0.0.9"]
+        );
+        // Proxies count even for tools that are not known network tools.
+        let req = with_env(request("python3"), &[("https_proxy", "proxy.corp:8080")]);
+        assert_eq!(extract_destinations(&req), ["proxy:proxy.corp"]);
+    }
+
+    #[tokio::test]
+    async fn unapproved_proxy_needs_approval_for_allowed_domain() {
+        let c = PolicyChecker::new(&PolicyConfig {
+            allowed_domains: vec!["example.com".to_string(), "proxy.corp".to_string()],
+            denied_domains: vec!["*.evil.test".to_string()],
+            ..Default::default()
+        });
+        let curl = with_args("curl", &["https://example.com"]);
+        assert_eq!(decide_req(&c, &curl).await, "allow");
+
+        let proxied = with_env(
+            curl.clone(),
+            &[("https_proxy", "http://exfil.example.net:3128")],
+        );
+        match c.check(&proxied, "uid:501").await {
+            PolicyDecision::NeedsApproval {
+                reason,
+                detected_domains,
+            } => {
+                assert!(reason.contains("proxy:exfil.example.net"), "{reason}");
+                assert_eq!(detected_domains, ["example.com", "proxy:exfil.example.net"]);
+            }
+            _ => panic!("expected NeedsApproval"),
+        }
+
+        let allowed_proxy = with_env(curl.clone(), &[("HTTPS_PROXY", "proxy.corp:8080")]);
+        assert_eq!(decide_req(&c, &allowed_proxy).await, "allow");
+        let denied_proxy = with_env(curl, &[("http_proxy", "http://relay.evil.test")]);
+        assert_eq!(decide_req(&c, &denied_proxy).await, "deny");
+    }
+}
@@ -0,0 +1,160 @@
+//! Named policy presets (Mode 5). A preset is a complete [`PolicyConfig`]
+//! that replaces the live policy in one step. Built-in presets ship with the
+//! daemon; users can add their own as `~/.ahand/presets/<name>.toml`, which
+//! take precedence over a built-in of the same name.
+
+use std::path::Path;
+
+use crate::config::{ArgRule, ArgRuleAction, PolicyConfig};
+
+const BUILTIN_PRESETS: &[&str] = &["locked-down", "developer", "network-restricted", "open"];
+
+/// Tools that only read state, allowed without approval by `locked-down`.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "ls", "cat", "head", "tail", "less", "grep", "rg", "find", "wc", "pwd", "echo", "which",
+    "stat", "du", "df",
+];
+
+/// Package registries and code hosts `network-restricted` lets through.
+const DEVELOPER_DOMAINS: &[&str] = &[
+    "github.com",
+    "api.github.com",
+    "codeload.github.com",
+    "objects.githubusercontent.com",
+    "registry.npmjs.org",
+    "crates.io",
+    "index.crates.io",
+    "static.crates.io",
+    "pypi.org",
+    "files.pythonhosted.org",
+];
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn builtin(name: &str) -> Option<PolicyConfig> {
+    let config = match name {
+        // Read-only tools run; everything else needs approval, and system
+        // administration tools are refused outright.
+        "locked-down" => PolicyConfig {
+            allowed_tools: strings(READ_ONLY_TOOLS),
+            denied_tools: strings(&[
+                "sudo",
+                "su",
+                "doas",
+                "dd",
+                "shutdown",
+                "reboot",
+                "glob:mkfs*",
+            ]),
+            deny_ip_literals: true,
+            ..Default::default()
+        },
+        // Anything goes except the built-in dangerous invocations and
+        // force-pushes, which need approval.
+        "developer" => PolicyConfig {
+            denied_tools: strings(&["shutdown", "reboot", "glob:mkfs*"]),
+            arg_rules: vec![ArgRule {
+                name: Some("force-push".to_string()),
+                tool: "git".to_string(),
+                pattern: r"^push\b.*(--force|-f\b)".to_string(),
+                action: ArgRuleAction::NeedsApproval,
+            }],
+            ..Default::default()
+        },
+        // Network tools may only reach well-known registries and code
+        // hosts without approval.
+        "network-restricted" => PolicyConfig {
+            allowed_domains: strings(DEVELOPER_DOMAINS),
+            deny_ip_literals: true,
+            allow_private_ips: true,
+            ..Default::default()
+        },
+        "open" => PolicyConfig {
+            builtin_arg_rules: false,
+            ..Default::default()
+        },
+        _ => return None,
+    };
+    Some(config)
+}
+
+/// Resolve `name` to its policy: a user preset from `dir` if one exists,
+/// otherwise the built-in preset.
+pub fn load(name: &str, dir: Option<&Path>) -> Result<PolicyConfig, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("invalid preset name {name:?}"));
+    }
+    if let Some(dir) = dir {
+        let path = dir.join(format!("{name}.toml"));
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                return toml::from_str(&content)
+                    .map_err(|e| format!("failed to parse {}: {e}", path.display()));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("failed to read {}: {e}", path.display())),
+        }
+    }
+    builtin(name).ok_or_else(|| format!("unknown preset {name:?}"))
+}
+
+/// Names of all presets available from `dir` and the built-ins, sorted.
+pub fn list(dir: Option<&Path>) -> Vec<String> {
+    let mut names = strings(BUILTIN_PRESETS);
+    if let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "toml")
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            {
+                names.push(stem.to_string());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_all_resolve() {
+        for name in BUILTIN_PRESETS {
+            assert!(load(name, None).is_ok(), "{name}");
+        }
+        assert!(load("nope", None).unwrap_err().contains("unknown preset"));
+        assert!(load("../etc/passwd", None).is_err());
+    }
+
+    #[test]
+    fn user_presets_are_listed_and_shadow_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("ci.toml"),
+            "allowed_tools = [\"cargo\"]\napproval_timeout_secs = 60\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("open.toml"), "denied_tools = [\"rm\"]\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        assert_eq!(
+            list(Some(dir.path())),
+            [
+                "ci",
+                "developer",
+                "locked-down",
+                "network-restricted",
+                "open"
+            ]
+        );
+        let ci = load("ci", Some(dir.path())).unwrap();
+        assert_eq!(ci.allowed_tools, ["cargo"]);
+        assert_eq!(ci.approval_timeout_secs, 60);
+        assert_eq!(load("open", Some(dir.path())).unwrap().denied_tools, ["rm"]);
+    }
+}
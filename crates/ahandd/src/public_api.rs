@@ -82,6 +82,9 @@ pub struct DaemonConfig {
     pub approval_timeout: Duration,
     /// Trust-mode timeout in minutes. Defaults to 60.
     pub trust_timeout_mins: u64,
+    /// Proxy for the hub connection (`http://` or `socks5://`). When `None`
+    /// the usual proxy environment variables apply.
+    pub proxy_url: Option<String>,
 }
 
 impl DaemonConfig {
@@ -102,6 +105,7 @@ impl DaemonConfig {
             max_concurrent_jobs: 8,
             approval_timeout: Duration::from_secs(86_400),
             trust_timeout_mins: 60,
+            proxy_url: None,
         }
     }
 }
@@ -118,6 +122,7 @@ pub struct DaemonConfigBuilder {
     max_concurrent_jobs: usize,
     approval_timeout: Duration,
     trust_timeout_mins: u64,
+    proxy_url: Option<String>,
 }
 
 impl DaemonConfigBuilder {
@@ -149,6 +154,10 @@ impl DaemonConfigBuilder {
         self.trust_timeout_mins = m;
         self
     }
+    pub fn proxy_url(mut self, url: impl Into<String>) -> Self {
+        self.proxy_url = Some(url.into());
+        self
+    }
     pub fn build(self) -> DaemonConfig {
         DaemonConfig {
             hub_url: self.hub_url,
@@ -161,6 +170,7 @@ impl DaemonConfigBuilder {
             max_concurrent_jobs: self.max_concurrent_jobs,
             approval_timeout: self.approval_timeout,
             trust_timeout_mins: self.trust_timeout_mins,
+            proxy_url: self.proxy_url,
         }
     }
 }
@@ -405,9 +415,7 @@ impl DaemonHandle {
     /// - deny with non-empty `reason` -> `resolve` + `record_refusal`
     /// - approve or deny without reason -> `resolve` only
     ///
-    /// The refusal log is keyed by tool name today (`SessionManager::record_refusal`
-    /// ignores the caller principal). The `"local"` principal passed internally
-    /// is a forward-looking sentinel for when per-principal tracking is added.
+    /// Refusals are recorded against the caller that submitted the job.
     ///
     /// `job_id` is [`ApprovalRequest::job_id`] verbatim (already namespaced,
     /// e.g. `"app-tool:{id}"`).
@@ -419,13 +427,15 @@ impl DaemonHandle {
             job_id: job_id.to_string(),
             approved,
             reason: reason.to_string(),
-            remember: false,
+            ..Default::default()
         };
         crate::approval::apply_approval_response(
             &self.approval_mgr,
             &self.session_mgr,
             &resp,
             "local",
+            &self.device_id,
+            &self.approval_broadcast_tx,
         )
         .await
     }
@@ -703,9 +713,11 @@ pub async fn spawn(config: DaemonConfig) -> anyhow::Result<DaemonHandle> {
     let (status_tx, status_rx) = watch::channel(DaemonStatus::Connecting);
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
 
-    let session_mgr = Arc::new(SessionManager::new(config.trust_timeout_mins));
+    let (session_events_tx, session_events_rx) = tokio::sync::mpsc::unbounded_channel();
+    let session_mgr = Arc::new(
+        SessionManager::new(config.trust_timeout_mins).with_state_events(session_events_tx.clone()),
+    );
     session_mgr.set_default_mode(config.session_mode).await;
-    let approval_mgr = Arc::new(ApprovalManager::new(config.approval_timeout.as_secs()));
     let registry = Arc::new(JobRegistry::new(config.max_concurrent_jobs));
     let (approval_broadcast_tx, _) = broadcast::channel(64);
     let browser_mgr = Arc::new(BrowserManager::new(BrowserConfig {
@@ -720,6 +732,10 @@ pub async fn spawn(config: DaemonConfig) -> anyhow::Result<DaemonHandle> {
     let file_policy_cfg = inner_config.file_policy.clone().unwrap_or_default();
     let file_mgr = Arc::new(crate::file_manager::FileManager::new(&file_policy_cfg));
     let app_tools = Arc::new(AppToolRegistry::new());
+    let policy = Arc::new(crate::policy::PolicyChecker::new(&inner_config.policy));
+    let approval_mgr = Arc::new(
+        ApprovalManager::new(config.approval_timeout.as_secs()).with_policy(Arc::clone(&policy)),
+    );
 
     let status_tx_task = status_tx.clone();
     let device_id_for_task = device_id.clone();
@@ -735,6 +751,31 @@ pub async fn spawn(config: DaemonConfig) -> anyhow::Result<DaemonHandle> {
     let app_tools_for_task = Arc::clone(&app_tools);
     let join = tokio::spawn(async move {
         let _active_guard = active_guard;
+        // Embedded callers stop the client by dropping `run_fut` below; the
+        // sender only has to outlive it so the client never sees a closed
+        // shutdown channel.
+        let (_client_shutdown_tx, client_shutdown_rx) = watch::channel(false);
+        // Trust expiry and approval expiry notifications reach handle
+        // subscribers through the approval broadcast channel; stopped
+        // together with the client, as is the stale browser session sweep.
+        let trust_watch = {
+            let watch =
+                crate::session::watch_trust_expiry(Arc::clone(&session_mgr), session_events_tx);
+            let forward = crate::session::forward_session_events(
+                session_events_rx,
+                device_id_for_task.clone(),
+                approval_broadcast_tx.clone(),
+            );
+            let sweep = crate::approval::sweep_expired_approvals(
+                Arc::clone(&approval_mgr),
+                device_id_for_task.clone(),
+                approval_broadcast_tx.clone(),
+            );
+            let browser_sweep = crate::browser::sweep_sessions(Arc::clone(&browser_mgr));
+            tokio::spawn(async move {
+                tokio::join!(watch, forward, sweep, browser_sweep);
+            })
+        };
         let run_fut = ahand_client::run_with_reporter(
             inner_config,
             device_id_for_task,
@@ -742,14 +783,17 @@ pub async fn spawn(config: DaemonConfig) -> anyhow::Result<DaemonHandle> {
             None,
             session_mgr,
             approval_mgr,
+            policy,
             approval_broadcast_tx,
             browser_mgr,
             file_mgr,
             app_tools_for_task,
             reporter,
+            Arc::default(),
+            client_shutdown_rx,
         );
 
-        tokio::select! {
+        let res = tokio::select! {
             res = run_fut => {
                 match &res {
                     Ok(()) => {
@@ -769,7 +813,9 @@ pub async fn spawn(config: DaemonConfig) -> anyhow::Result<DaemonHandle> {
                 let _ = status_tx_task.send(DaemonStatus::Offline);
                 Ok(())
             }
-        }
+        };
+        trust_watch.abort();
+        res
     });
 
     Ok(DaemonHandle {
@@ -835,14 +881,36 @@ fn build_inner_config(cfg: &DaemonConfig, identity_path: &Path) -> Config {
     Config {
         mode: Some("ahand-cloud".to_string()),
         server_url: cfg.hub_url.clone(),
+        server_urls: Vec::new(),
         device_id: cfg.device_id.clone(),
         max_concurrent_jobs: Some(cfg.max_concurrent_jobs),
-        data_dir: None,
+        queue_limit: None,
+        completed_retention_secs: None,
+        // Embedders keep nothing on disk, the outbox journal included.
+        data_dir: Some(String::new()),
+        trace_max_bytes: None,
+        trace_keep_files: None,
+        runs_retention_days: None,
+        runs_max_total_bytes: None,
+        compress_outputs_over_bytes: None,
+        run_output_cap_bytes: None,
         debug_ipc: Some(false),
         ipc_socket_path: None,
         ipc_socket_mode: None,
+        ipc_allowed_uids: None,
+        ipc_allowed_gids: None,
+        ipc_readonly_uids: None,
+        ipc_legacy_clients: None,
+        ipc_tcp_listen: None,
+        ipc_tcp_allow_remote: None,
+        ipc_tcp_token: None,
+        ipc_max_frames_per_sec: None,
         trust_timeout_mins: Some(cfg.trust_timeout_mins),
-        default_session_mode: Some(session_mode_str(cfg.session_mode).to_string()),
+        max_refusals_per_caller: None,
+        default_session_mode: Some(crate::session::mode_name(cfg.session_mode).to_string()),
+        session: None,
+        approval: None,
+        store: None,
         policy: Default::default(),
         openclaw: None,
         browser: Some(BrowserConfig {
@@ -866,12 +934,15 @@ fn build_inner_config(cfg: &DaemonConfig, identity_path: &Path) -> Config {
                     .unwrap_or(u64::MAX)
                     .max(1),
             ),
+            ..HubConfig::default()
         }),
         // Embedded library consumers currently don't expose a file-policy
         // surface. Keep the Mac app path functional by defaulting embedded
         // daemons to a permissive policy; callers that need tighter controls
         // should get an explicit builder option next.
         file_policy: Some(permissive_embedded_file_policy()),
+        tls: None,
+        proxy_url: cfg.proxy_url.clone(),
     }
 }
 
@@ -886,15 +957,6 @@ fn permissive_embedded_file_policy() -> FilePolicyConfig {
     }
 }
 
-fn session_mode_str(mode: SessionMode) -> &'static str {
-    match mode {
-        SessionMode::AutoAccept => "auto_accept",
-        SessionMode::Trust => "trust",
-        SessionMode::Strict => "strict",
-        SessionMode::Inactive => "inactive",
-    }
-}
-
 /// Derive a stable fallback device ID from the identity directory path so
 /// repeat launches with the same dir reuse the same ID. Callers that need a
 /// specific ID should set `DaemonConfig::device_id` directly.
@@ -933,7 +995,7 @@ impl StatusReporter {
 impl ClientReporter for StatusReporter {
     fn report(&self, outcome: ConnectOutcome) {
         let status = match outcome {
-            ConnectOutcome::HandshakeAccepted => DaemonStatus::Online {
+            ConnectOutcome::HandshakeAccepted { .. } => DaemonStatus::Online {
                 device_id: self.device_id.clone(),
             },
             ConnectOutcome::HandshakeRejected(msg) => DaemonStatus::Error {
@@ -1034,14 +1096,6 @@ mod tests {
         assert_eq!(classify_error(&e3), ErrorKind::Network);
     }
 
-    #[test]
-    fn session_mode_str_round_trips_known_values() {
-        assert_eq!(session_mode_str(SessionMode::AutoAccept), "auto_accept");
-        assert_eq!(session_mode_str(SessionMode::Trust), "trust");
-        assert_eq!(session_mode_str(SessionMode::Strict), "strict");
-        assert_eq!(session_mode_str(SessionMode::Inactive), "inactive");
-    }
-
     #[test]
     fn default_device_id_is_stable_for_same_dir() {
         let a = default_device_id(Path::new("/tmp/ahand-a"));
@@ -1142,7 +1196,10 @@ mod tests {
         let (tx, rx) = watch::channel(DaemonStatus::Idle);
         let reporter = StatusReporter::new(tx, "dev-42".into());
 
-        reporter.report(ConnectOutcome::HandshakeAccepted);
+        reporter.report(ConnectOutcome::HandshakeAccepted {
+            url: "ws://h/w".into(),
+            primary: true,
+        });
         assert_eq!(
             *rx.borrow(),
             DaemonStatus::Online {
@@ -0,0 +1,419 @@
+//! Reconnect pacing for the cloud connection. The OpenClaw gateway client
+//! paces its reconnects with the same [`Backoff`].
+//!
+//! Delays double from `reconnect_min_secs` up to `reconnect_max_secs`, and
+//! each sleep is drawn uniformly from zero to the current delay ("full
+//! jitter") so a fleet dropped by the same cloud deploy doesn't come back in
+//! lockstep. The delay only resets once a connection has stayed up for
+//! [`HEALTHY_CONNECTION`]; a hub that accepts and immediately closes keeps
+//! backing off instead of being redialled every second.
+
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::ahand_client::shutdown_requested;
+use crate::config::HubConfig;
+use crate::connection::ConnectionMonitor;
+
+/// A connection that lasted at least this long resets the backoff.
+pub const HEALTHY_CONNECTION: Duration = Duration::from_secs(60);
+
+/// Process exit code when `reconnect_max_attempts` is exhausted, so a
+/// supervisor can tell "gave up on the cloud" from a crash (`EX_TEMPFAIL`).
+pub const EXIT_RECONNECT_GAVE_UP: i32 = 75;
+
+const DEFAULT_MIN_SECS: u64 = 1;
+const DEFAULT_MAX_SECS: u64 = 30;
+
+/// Longest server retry hint honoured; a bigger one is clamped to this.
+const MAX_RETRY_HINT: Duration = Duration::from_secs(3600);
+
+/// How a connection attempt ended, as far as pacing the next one goes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Attempt {
+    /// The hub accepted the handshake.
+    pub accepted: bool,
+    /// The hub asked us to wait at least this long before the next try
+    /// (close code 1013 with a retry hint).
+    pub retry_after: Option<Duration>,
+}
+
+/// Returned by [`run`] when every URL has used up `reconnect_max_attempts`
+/// without a healthy connection.
+#[derive(Debug, thiserror::Error)]
+#[error("gave up on the cloud after {attempts} reconnect attempts")]
+pub struct GaveUp {
+    pub attempts: u32,
+}
+
+/// Exponential backoff state between connection attempts.
+#[derive(Clone)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+    /// Attempts since the last healthy connection.
+    attempts: u32,
+    max_attempts: Option<u32>,
+    /// A connection that lasted this long resets the backoff.
+    healthy_after: Duration,
+    jitter: fn(Duration) -> Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration, max_attempts: Option<u32>) -> Self {
+        // A zero floor would never grow and redial in a tight loop.
+        let min = min.max(Duration::from_secs(1));
+        let max = max.max(min);
+        Self {
+            min,
+            max,
+            current: min,
+            attempts: 0,
+            max_attempts,
+            healthy_after: HEALTHY_CONNECTION,
+            jitter: full_jitter,
+        }
+    }
+
+    pub fn from_config(hub: &HubConfig) -> Self {
+        Self::new(
+            Duration::from_secs(hub.reconnect_min_secs.unwrap_or(DEFAULT_MIN_SECS)),
+            Duration::from_secs(hub.reconnect_max_secs.unwrap_or(DEFAULT_MAX_SECS)),
+            hub.reconnect_max_attempts,
+        )
+    }
+
+    /// Reset after connections lasting `healthy_after` instead of
+    /// [`HEALTHY_CONNECTION`].
+    pub fn with_healthy_after(mut self, healthy_after: Duration) -> Self {
+        self.healthy_after = healthy_after;
+        self
+    }
+
+    /// Sleep exactly the backoff ceiling, for tests that assert delays.
+    #[cfg(test)]
+    pub fn without_jitter(mut self) -> Self {
+        self.jitter = |ceiling| ceiling;
+        self
+    }
+
+    /// The delay before the next attempt, given how long the last one stayed
+    /// connected, or `None` once `max_attempts` is used up.
+    pub fn next_delay(&mut self, up_for: Duration) -> Option<Duration> {
+        if up_for >= self.healthy_after {
+            self.reset();
+        }
+        if self.max_attempts.is_some_and(|max| self.attempts >= max) {
+            return None;
+        }
+        self.attempts += 1;
+        let ceiling = self.current;
+        self.current = (self.current * 2).min(self.max);
+        Some((self.jitter)(ceiling))
+    }
+
+    /// Start over from `min`, as after a healthy connection.
+    pub fn reset(&mut self) {
+        self.current = self.min;
+        self.attempts = 0;
+    }
+}
+
+fn full_jitter(ceiling: Duration) -> Duration {
+    let ms = u64::try_from(ceiling.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=ms))
+}
+
+/// Call `connect` until shutdown, sleeping per backoff in between. Each
+/// call gets an index into `urls`, should return when its connection ends,
+/// and reports how it went as an [`Attempt`]. A server retry hint
+/// lengthens that URL's next delay but never shortens it.
+///
+/// Every URL keeps its own backoff, so a dead primary doesn't hold back the
+/// others: after a failure the next URL that is due is tried. The last URL
+/// that accepted a handshake is preferred whenever it's due.
+pub async fn run<F, Fut>(
+    urls: &[String],
+    backoff: Backoff,
+    monitor: &ConnectionMonitor,
+    shutdown_rx: &mut watch::Receiver<bool>,
+    mut connect: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Attempt>,
+{
+    let mut backoffs = vec![backoff; urls.len()];
+    // When each URL may next be tried; `None` once its attempts ran out.
+    let mut due: Vec<Option<Instant>> = vec![Some(Instant::now()); urls.len()];
+    let mut preferred = 0;
+    let mut current = 0;
+    loop {
+        let started = Instant::now();
+        monitor.connecting(&urls[current]);
+        let attempt = connect(current).await;
+        let accepted = attempt.accepted;
+
+        if *shutdown_rx.borrow() {
+            info!("daemon shutting down, not reconnecting to cloud");
+            monitor.disable();
+            return Ok(());
+        }
+
+        if accepted {
+            preferred = current;
+        }
+        let delay = backoffs[current]
+            .next_delay(started.elapsed())
+            .map(|delay| match attempt.retry_after {
+                Some(hint) => delay.max(hint.min(MAX_RETRY_HINT)),
+                None => delay,
+            });
+        due[current] = delay.map(|delay| Instant::now() + delay);
+
+        let Some(next) = pick(&due, preferred, current, accepted) else {
+            monitor.disable();
+            return Err(GaveUp {
+                attempts: backoffs.iter().map(|b| b.attempts).sum(),
+            }
+            .into());
+        };
+        let wait = due[next]
+            .unwrap_or_else(Instant::now)
+            .saturating_duration_since(Instant::now());
+        info!(
+            url = %urls[next],
+            delay_ms = wait.as_millis() as u64,
+            attempt = backoffs[next].attempts + 1,
+            "reconnecting after delay"
+        );
+        monitor.backoff(wait);
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown_requested(shutdown_rx) => {
+                info!("daemon shutting down, not reconnecting to cloud");
+                monitor.disable();
+                return Ok(());
+            }
+        }
+        current = next;
+    }
+}
+
+/// The URL to try after `current`: the preferred one if it's due (or just
+/// worked), else the earliest due, counting round from `current + 1`.
+fn pick(
+    due: &[Option<Instant>],
+    preferred: usize,
+    current: usize,
+    accepted: bool,
+) -> Option<usize> {
+    let now = Instant::now();
+    if let Some(at) = due[preferred]
+        && (at <= now || (accepted && preferred == current))
+    {
+        return Some(preferred);
+    }
+    (1..=due.len())
+        .map(|offset| (current + offset) % due.len())
+        .filter_map(|i| due[i].map(|at| (at.max(now), i)))
+        .min_by_key(|&(at, _)| at)
+        .map(|(_, i)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn deterministic(min: u64, max: u64, max_attempts: Option<u32>) -> Backoff {
+        Backoff::new(
+            Duration::from_secs(min),
+            Duration::from_secs(max),
+            max_attempts,
+        )
+        .without_jitter()
+    }
+
+    struct Trace {
+        /// Which URL each call went to.
+        urls: Vec<usize>,
+        /// Seconds between one call ending and the next starting.
+        gaps: Vec<u64>,
+        result: anyhow::Result<()>,
+    }
+
+    /// Drive [`run`] over `urls` URLs with a fake connect whose n-th call
+    /// stays up for `script[n].0` seconds and reports `script[n].1` as the
+    /// handshake outcome. The call after the script ends requests shutdown.
+    async fn trace(urls: usize, backoff: Backoff, script: &[(u64, bool)]) -> Trace {
+        let script: Vec<_> = script
+            .iter()
+            .map(|&(secs, accepted)| {
+                (
+                    secs,
+                    Attempt {
+                        accepted,
+                        retry_after: None,
+                    },
+                )
+            })
+            .collect();
+        trace_attempts(urls, backoff, &script).await
+    }
+
+    /// [`trace`] with full [`Attempt`]s.
+    async fn trace_attempts(urls: usize, backoff: Backoff, script: &[(u64, Attempt)]) -> Trace {
+        let (stop_tx, mut stop_rx) = watch::channel(false);
+        let calls = Arc::new(Mutex::new(Vec::<(usize, Instant, Instant)>::new()));
+        let log = Arc::clone(&calls);
+        let script = script.to_vec();
+        let names: Vec<String> = (0..urls).map(|i| format!("ws://hub-{i}/ws")).collect();
+        let monitor = ConnectionMonitor::default();
+        let result = run(&names, backoff, &monitor, &mut stop_rx, move |url| {
+            let log = Arc::clone(&log);
+            let stop_tx = stop_tx.clone();
+            let n = log.lock().unwrap().len();
+            let step = script.get(n).copied();
+            async move {
+                let start = Instant::now();
+                let attempt = match step {
+                    Some((secs, attempt)) => {
+                        tokio::time::sleep(Duration::from_secs(secs)).await;
+                        attempt
+                    }
+                    None => {
+                        let _ = stop_tx.send(true);
+                        Attempt::default()
+                    }
+                };
+                log.lock().unwrap().push((url, start, Instant::now()));
+                attempt
+            }
+        })
+        .await;
+        let calls = calls.lock().unwrap();
+        Trace {
+            urls: calls.iter().map(|&(url, _, _)| url).collect(),
+            gaps: calls
+                .windows(2)
+                .map(|pair| (pair[1].1 - pair[0].2).as_secs())
+                .collect(),
+            result,
+        }
+    }
+
+    /// Single-URL shorthand: call n stays up `up_for[n]` seconds.
+    async fn delays(backoff: Backoff, up_for: &[u64]) -> (Vec<u64>, anyhow::Result<()>) {
+        let script: Vec<_> = up_for.iter().map(|&secs| (secs, true)).collect();
+        let trace = trace(1, backoff, &script).await;
+        (trace.gaps, trace.result)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn accept_then_close_keeps_backing_off() {
+        // Connections that end straight away never reset the backoff.
+        let (gaps, result) = delays(deterministic(1, 8, None), &[0, 0, 0, 0, 0]).await;
+        assert!(result.is_ok());
+        assert_eq!(gaps, vec![1, 2, 4, 8, 8]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn healthy_connection_resets_the_backoff() {
+        let (gaps, _) = delays(deterministic(2, 30, None), &[0, 0, 59, 60, 0]).await;
+        // 59s is not healthy: still doubling. 60s is: back to the floor.
+        assert_eq!(gaps, vec![2, 4, 8, 2, 4]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_attempts_gives_up_until_a_healthy_connection() {
+        let (gaps, result) = delays(deterministic(1, 30, Some(2)), &[0, 0, 0]).await;
+        assert_eq!(gaps, vec![1, 2]);
+        let err = result.unwrap_err();
+        assert_eq!(err.downcast_ref::<GaveUp>().unwrap().attempts, 2);
+
+        let (gaps, result) = delays(deterministic(1, 30, Some(2)), &[0, 0, 60, 0]).await;
+        assert!(result.is_ok());
+        assert_eq!(gaps, vec![1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn jitter_stays_within_the_ceiling() {
+        let mut backoff = Backoff::new(Duration::ZERO, Duration::from_secs(4), None);
+        for ceiling in [1, 2, 4, 4, 4] {
+            let delay = backoff.next_delay(Duration::ZERO).unwrap();
+            assert!(delay <= Duration::from_secs(ceiling), "{delay:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dead_primary_does_not_delay_the_secondary() {
+        // The primary refuses; the secondary accepts and drops at once.
+        let trace = trace(
+            2,
+            deterministic(4, 30, None),
+            &[(0, false), (0, true), (0, true)],
+        )
+        .await;
+        // Straight on to the secondary, then back to it (the last URL that
+        // worked) on its own 4s, 8s backoff rather than the primary's.
+        assert_eq!(trace.urls, vec![0, 1, 1, 1]);
+        assert_eq!(trace.gaps, vec![0, 4, 8]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failures_rotate_through_urls_on_their_own_backoff() {
+        let trace = trace(
+            3,
+            deterministic(1, 30, None),
+            &[(0, false), (0, false), (0, false), (0, false), (0, false)],
+        )
+        .await;
+        // Each URL is tried once straight away; after that whichever is due
+        // first, each doubling its own delay.
+        assert_eq!(trace.urls, vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(trace.gaps, vec![0, 0, 1, 0, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_last_url_that_worked_is_preferred_once_due() {
+        // Secondary works for a healthy minute, then it and the primary
+        // both fail; once due again the secondary is tried first.
+        let trace = trace(
+            2,
+            deterministic(1, 30, None),
+            &[(0, false), (60, true), (0, false), (0, false)],
+        )
+        .await;
+        assert_eq!(trace.urls, vec![0, 1, 1, 0, 1]);
+        assert_eq!(trace.gaps, vec![0, 1, 0, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_server_retry_hint_stretches_the_delay() {
+        let busy = |secs| Attempt {
+            accepted: false,
+            retry_after: Some(Duration::from_secs(secs)),
+        };
+        let trace = trace_attempts(
+            1,
+            deterministic(1, 30, None),
+            &[
+                (0, busy(20)),
+                (0, busy(1)),
+                (0, busy(7200)),
+                (0, Attempt::default()),
+            ],
+        )
+        .await;
+        // The hint wins over a shorter backoff, not over a longer one, and
+        // is clamped to an hour.
+        assert_eq!(trace.gaps, vec![20, 2, 3600, 8]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Redaction of secret-looking environment values before they are written
+//! to disk or served by the admin panel.
+//!
+//! A redacted value keeps its length and a short SHA-256 prefix, so an
+//! operator can still tell whether two runs used the same credential.
+
+use std::collections::{BTreeMap, HashMap};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Env keys redacted when `store.redact_env_patterns` isn't set.
+pub const DEFAULT_REDACT_ENV_PATTERNS: &[&str] =
+    &["*TOKEN*", "*SECRET*", "*PASSWORD*", "*API_KEY*", "AWS_*"];
+
+const REDACTED_PREFIX: &str = "<redacted:";
+
+/// Replaces the values of env vars whose key matches one of a set of glob
+/// patterns (case-insensitive).
+#[derive(Debug, Clone)]
+pub struct EnvRedactor {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl EnvRedactor {
+    /// Build a redactor from glob patterns. Invalid patterns are logged and
+    /// skipped.
+    pub fn new(patterns: &[String]) -> Self {
+        let patterns = patterns
+            .iter()
+            .filter_map(|p| match glob::Pattern::new(&p.to_ascii_uppercase()) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!(pattern = %p, error = %e, "ignoring invalid env redaction pattern");
+                    None
+                }
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Whether the value of env var `key` should be redacted.
+    pub fn is_secret(&self, key: &str) -> bool {
+        let key = key.to_ascii_uppercase();
+        self.patterns.iter().any(|p| p.matches(&key))
+    }
+
+    /// A copy of `env` with secret values redacted, sorted by key.
+    pub fn redact_env(&self, env: &HashMap<String, String>) -> BTreeMap<String, String> {
+        env.iter()
+            .map(|(key, value)| {
+                let value = if self.is_secret(key) {
+                    redacted(value)
+                } else {
+                    value.clone()
+                };
+                (key.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Redact the `env` object of a stored request.json in place. Values
+    /// that are already redacted are left alone.
+    // Bin target never calls this directly; the admin panel reaches it
+    // through the lib crate.
+    #[allow(dead_code)]
+    pub fn redact_request(&self, request: &mut serde_json::Value) {
+        let Some(env) = request.get_mut("env").and_then(|e| e.as_object_mut()) else {
+            return;
+        };
+        for (key, value) in env.iter_mut() {
+            if let Some(s) = value.as_str()
+                && self.is_secret(key)
+                && !s.starts_with(REDACTED_PREFIX)
+            {
+                *value = serde_json::Value::String(redacted(s));
+            }
+        }
+    }
+
+    /// Redact, anywhere in `value`, every string field whose key matches
+    /// the patterns — env vars inside a rendered payload as well as fields
+    /// like `bearer_token`.
+    pub fn redact_fields(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    match field {
+                        serde_json::Value::String(s)
+                            if self.is_secret(key) && !s.starts_with(REDACTED_PREFIX) =>
+                        {
+                            *s = redacted(s);
+                        }
+                        _ => self.redact_fields(field),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact_fields(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for EnvRedactor {
+    fn default() -> Self {
+        let patterns: Vec<String> = DEFAULT_REDACT_ENV_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        Self::new(&patterns)
+    }
+}
+
+/// `<redacted:N chars sha256=XXXXXXXX>` for `value`.
+fn redacted(value: &str) -> String {
+    let digest = hex::encode(Sha256::digest(value.as_bytes()));
+    format!(
+        "{REDACTED_PREFIX}{} chars sha256={}>",
+        value.chars().count(),
+        &digest[..8]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn default_patterns_redact_credentials_only() {
+        let redactor = EnvRedactor::default();
+        let out = redactor.redact_env(&env(&[
+            ("GITHUB_TOKEN", "ghp_abcdefgh"),
+            ("db_password", "hunter2"),
+            ("OPENAI_API_KEY", "sk-123"),
+            ("AWS_REGION", "us-east-1"),
+            ("PATH", "/usr/bin"),
+        ]));
+        assert_eq!(out["PATH"], "/usr/bin");
+        assert!(out["GITHUB_TOKEN"].starts_with("<redacted:12 chars sha256="));
+        assert!(out["db_password"].starts_with("<redacted:7 chars sha256="));
+        assert!(out["OPENAI_API_KEY"].starts_with("<redacted:"));
+        assert!(out["AWS_REGION"].starts_with("<redacted:"));
+    }
+
+    #[test]
+    fn same_value_redacts_to_the_same_string() {
+        assert_eq!(redacted("s3cr3t"), redacted("s3cr3t"));
+        assert_ne!(redacted("s3cr3t"), redacted("s3cr3u"));
+        assert_eq!(
+            redacted("s3cr3t").len(),
+            "<redacted:6 chars sha256=>".len() + 8
+        );
+    }
+
+    #[test]
+    fn custom_patterns_replace_the_defaults() {
+        let redactor = EnvRedactor::new(&["INTERNAL_*".to_string()]);
+        let out = redactor.redact_env(&env(&[
+            ("INTERNAL_URL", "https://example.internal"),
+            ("GITHUB_TOKEN", "ghp_abcdefgh"),
+        ]));
+        assert!(out["INTERNAL_URL"].starts_with("<redacted:"));
+        assert_eq!(out["GITHUB_TOKEN"], "ghp_abcdefgh");
+    }
+
+    #[test]
+    fn redact_request_is_idempotent() {
+        let redactor = EnvRedactor::default();
+        let mut request = serde_json::json!({
+            "tool": "git",
+            "env": { "API_TOKEN": "abc", "HOME": "/home/me" },
+        });
+        redactor.redact_request(&mut request);
+        let once = request.clone();
+        redactor.redact_request(&mut request);
+        assert_eq!(request, once);
+        assert_eq!(request["env"]["HOME"], "/home/me");
+        assert_eq!(request["env"]["API_TOKEN"], redacted("abc"));
+    }
+
+    #[test]
+    fn redact_fields_walks_nested_objects() {
+        let redactor = EnvRedactor::default();
+        let mut payload = serde_json::json!({
+            "auth": { "Bootstrap": { "bearer_token": "tok", "signed_at_ms": 1 } },
+            "jobs": [{ "env": { "GH_TOKEN": "ghp", "TERM": "xterm" } }],
+        });
+        redactor.redact_fields(&mut payload);
+        assert_eq!(
+            payload["auth"]["Bootstrap"]["bearer_token"],
+            redacted("tok")
+        );
+        assert_eq!(payload["auth"]["Bootstrap"]["signed_at_ms"], 1);
+        assert_eq!(payload["jobs"][0]["env"]["GH_TOKEN"], redacted("ghp"));
+        assert_eq!(payload["jobs"][0]["env"]["TERM"], "xterm");
+    }
+}
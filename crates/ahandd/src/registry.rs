@@ -1,14 +1,63 @@
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore, mpsc};
+use ahand_protocol::{Envelope, JobRequest, envelope, job_event};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, Notify, broadcast, mpsc, oneshot};
 use tracing::{info, warn};
 
-use crate::executor::{StdinInput, StdinSender};
+use crate::executor::{CancelReason, EnvelopeSink, StdinInput, StdinSender};
+
+/// Default time a completed job stays in the dedup cache (24h).
+pub const DEFAULT_COMPLETED_RETENTION: Duration = Duration::from_secs(86_400);
+
+/// Hard cap on dedup cache entries, applied after age-based eviction.
+const MAX_COMPLETED: usize = 10_000;
+
+/// Envelopes an observer may fall behind by before it starts missing some.
+const OBSERVER_CAPACITY: usize = 1024;
+
+/// Rejection reason sent when a known job_id arrives with other parameters.
+pub const JOB_ID_REUSE_REASON: &str = "job_id reuse with different parameters";
+
+/// SHA-256 over a job's canonical parameters; see [`params_hash`].
+pub type ParamsHash = [u8; 32];
+
+/// Hash the parameters that define what a job does (tool, args, cwd and
+/// env sorted by key), so a reused job_id can be told apart from a retry.
+/// Every field is length-prefixed to keep the encoding unambiguous.
+pub fn params_hash(req: &JobRequest) -> ParamsHash {
+    fn field(hasher: &mut Sha256, value: &str) {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    }
+
+    let mut hasher = Sha256::new();
+    field(&mut hasher, &req.tool);
+    hasher.update((req.args.len() as u64).to_le_bytes());
+    for arg in &req.args {
+        field(&mut hasher, arg);
+    }
+    field(&mut hasher, &req.cwd);
+    let mut env: Vec<_> = req.env.iter().collect();
+    env.sort();
+    hasher.update((env.len() as u64).to_le_bytes());
+    for (key, value) in env {
+        field(&mut hasher, key);
+        field(&mut hasher, value);
+    }
+    hasher.finalize().into()
+}
 
 /// Handle kept per running job, used to send a cancel signal.
 struct JobHandle {
-    cancel_tx: mpsc::Sender<()>,
+    /// Who submitted the job (IPC="uid:N", WS="cloud"), for `cancel_all`.
+    caller_uid: String,
+    params_hash: ParamsHash,
+    cancel_tx: mpsc::Sender<CancelReason>,
 }
 
 /// Cached result for a completed job (for idempotency).
@@ -16,6 +65,272 @@ struct JobHandle {
 pub struct CompletedJob {
     pub exit_code: i32,
     pub error: String,
+    pub params_hash: ParamsHash,
+    /// When the job finished; entries expire `completed_retention` later.
+    pub completed_at: Instant,
+}
+
+/// A held concurrency slot. Dropping it frees the slot for the next job.
+pub struct JobPermit {
+    _permit: DispatchPermit,
+    queued: Duration,
+}
+
+impl JobPermit {
+    /// How long the job waited before the slot was granted.
+    pub fn queued_ms(&self) -> u64 {
+        self.queued.as_millis() as u64
+    }
+}
+
+/// A job's claim on a concurrency slot, taken when it is accepted: either
+/// the slot itself or a place in the wait queue. See [`JobRegistry::admit`].
+pub struct Admission {
+    slot: Slot,
+    priority: i32,
+    admitted_at: tokio::time::Instant,
+}
+
+enum Slot {
+    Ready(DispatchPermit),
+    Queued(Ticket),
+}
+
+impl Admission {
+    /// 1-based place in the wait queue, or `None` if the job can start now.
+    pub fn position(&self) -> Option<u32> {
+        match &self.slot {
+            Slot::Ready(_) => None,
+            Slot::Queued(ticket) => ticket.dispatcher.position(&ticket.key),
+        }
+    }
+}
+
+/// Why [`JobRegistry::admit`] turned a job away.
+#[derive(Debug, thiserror::Error)]
+#[error("device at capacity, {queued} jobs queued")]
+pub struct AtCapacity {
+    pub queued: usize,
+}
+
+/// Wait-queue key: highest priority first, then arrival order.
+type WaiterKey = (Reverse<i32>, u64);
+
+/// Hands out concurrency slots, granting a freed slot to the highest-priority
+/// waiter (FIFO within a priority) instead of whoever polls first.
+struct Dispatcher {
+    state: std::sync::Mutex<DispatchState>,
+    /// Woken whenever the wait queue changes, so waiters can re-report.
+    changed: Notify,
+}
+
+struct DispatchState {
+    permits: usize,
+    available: usize,
+    next_seq: u64,
+    waiters: BTreeMap<WaiterKey, oneshot::Sender<DispatchPermit>>,
+    /// Moving average of how long a slot is held, for wait estimates.
+    avg_hold: Option<Duration>,
+}
+
+/// One slot owned by a running job; returned to the dispatcher on drop.
+struct DispatchPermit {
+    dispatcher: Option<Arc<Dispatcher>>,
+    granted_at: tokio::time::Instant,
+}
+
+/// A place in the wait queue. Dropping it before the grant arrives leaves
+/// the queue; a grant that was sent but never received is released again
+/// when the receiver drops it.
+struct Ticket {
+    key: WaiterKey,
+    granted: oneshot::Receiver<DispatchPermit>,
+    dispatcher: Arc<Dispatcher>,
+}
+
+impl Dispatcher {
+    fn new(permits: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: std::sync::Mutex::new(DispatchState {
+                permits,
+                available: permits,
+                next_seq: 0,
+                waiters: BTreeMap::new(),
+                avg_hold: None,
+            }),
+            changed: Notify::new(),
+        })
+    }
+
+    fn permit(self: &Arc<Self>) -> DispatchPermit {
+        DispatchPermit {
+            dispatcher: Some(Arc::clone(self)),
+            granted_at: tokio::time::Instant::now(),
+        }
+    }
+
+    /// Take a free slot, unless none is free or someone is already waiting;
+    /// otherwise join the wait queue, unless `limit` jobs already wait, in
+    /// which case the queue length is returned.
+    fn admit(self: &Arc<Self>, priority: i32, limit: Option<usize>) -> Result<Slot, usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.available > 0 && state.waiters.is_empty() {
+            state.available -= 1;
+            return Ok(Slot::Ready(self.permit()));
+        }
+        if limit.is_some_and(|limit| state.waiters.len() >= limit) {
+            return Err(state.waiters.len());
+        }
+        let (grant_tx, granted) = oneshot::channel();
+        let key = (Reverse(priority), state.next_seq);
+        state.next_seq += 1;
+        state.waiters.insert(key, grant_tx);
+        drop(state);
+        self.changed.notify_waiters();
+        Ok(Slot::Queued(Ticket {
+            key,
+            granted,
+            dispatcher: Arc::clone(self),
+        }))
+    }
+
+    #[cfg(test)]
+    fn try_acquire(self: &Arc<Self>) -> Option<DispatchPermit> {
+        match self.admit(0, Some(0)) {
+            Ok(Slot::Ready(permit)) => Some(permit),
+            _ => None,
+        }
+    }
+
+    /// A rough guess at how long the job at `position` will wait: one
+    /// average slot hold for every round of `permits` jobs ahead of it.
+    /// Zero until some job has finished.
+    fn estimated_wait_ms(&self, position: u32) -> u64 {
+        let state = self.state.lock().unwrap();
+        let Some(avg_hold) = state.avg_hold else {
+            return 0;
+        };
+        let rounds = (position as usize).div_ceil(state.permits.max(1));
+        avg_hold.as_millis() as u64 * rounds as u64
+    }
+
+    /// 1-based position of `key` in the wait queue, if it is still queued.
+    fn position(&self, key: &WaiterKey) -> Option<u32> {
+        let state = self.state.lock().unwrap();
+        if !state.waiters.contains_key(key) {
+            return None;
+        }
+        Some(state.waiters.range(..key).count() as u32 + 1)
+    }
+
+    #[cfg(test)]
+    fn queued_len(&self) -> usize {
+        self.state.lock().unwrap().waiters.len()
+    }
+
+    /// Hand a slot freed after being held for `held` to the best waiter,
+    /// or return it to the pool.
+    fn release(self: &Arc<Self>, held: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.avg_hold = Some(match state.avg_hold {
+            Some(avg) => (avg * 7 + held) / 8,
+            None => held,
+        });
+        while let Some((_, grant_tx)) = state.waiters.pop_first() {
+            match grant_tx.send(self.permit()) {
+                Ok(()) => {
+                    drop(state);
+                    self.changed.notify_waiters();
+                    return;
+                }
+                // The waiter is gone; disarm the permit so dropping it here
+                // does not re-enter `release` while the lock is held.
+                Err(mut permit) => permit.dispatcher = None,
+            }
+        }
+        state.available += 1;
+    }
+}
+
+impl Drop for DispatchPermit {
+    fn drop(&mut self) {
+        if let Some(dispatcher) = self.dispatcher.take() {
+            dispatcher.release(self.granted_at.elapsed());
+        }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let removed = self
+            .dispatcher
+            .state
+            .lock()
+            .unwrap()
+            .waiters
+            .remove(&self.key)
+            .is_some();
+        if removed {
+            self.dispatcher.changed.notify_waiters();
+        }
+    }
+}
+
+/// Live output of a running job as seen by its subscribers.
+#[derive(Default)]
+struct JobStream {
+    /// Bytes of stdout and stderr sent on the job's sink so far.
+    stdout_bytes: u64,
+    stderr_bytes: u64,
+    subscribers: Vec<mpsc::UnboundedSender<Envelope>>,
+}
+
+/// What [`JobRegistry::subscribe`] found for a job_id.
+pub enum Subscription {
+    /// The job is running; its events from now on go to the subscriber.
+    /// Output already sent before subscribing is `stdout_bytes` and
+    /// `stderr_bytes` long.
+    Running {
+        stdout_bytes: u64,
+        stderr_bytes: u64,
+    },
+    /// The job already finished.
+    Finished(CompletedJob),
+    /// No such job.
+    Unknown,
+}
+
+/// An [`EnvelopeSink`] for one job that also mirrors every envelope to the
+/// job's subscribers. See [`JobRegistry::tee`].
+#[derive(Clone)]
+pub struct JobTee<T> {
+    inner: T,
+    job_id: String,
+    registry: Arc<JobRegistry>,
+}
+
+impl<T: EnvelopeSink> EnvelopeSink for JobTee<T> {
+    fn send(&self, envelope: Envelope) -> Result<(), ()> {
+        self.registry.mirror(&self.job_id, &envelope);
+        self.inner.send(envelope)
+    }
+}
+
+/// An [`EnvelopeSink`] that a job request is answered on, which also
+/// publishes the `JobRejected`s sent on it. See [`JobRegistry::observed`].
+#[derive(Clone)]
+pub struct Observed<T> {
+    inner: T,
+    registry: Arc<JobRegistry>,
+}
+
+impl<T: EnvelopeSink> EnvelopeSink for Observed<T> {
+    fn send(&self, envelope: Envelope) -> Result<(), ()> {
+        if matches!(envelope.payload, Some(envelope::Payload::JobRejected(_))) {
+            self.registry.publish(&envelope);
+        }
+        self.inner.send(envelope)
+    }
 }
 
 /// Result of checking whether a job_id is known.
@@ -24,6 +339,8 @@ pub enum IsKnown {
     Running,
     /// Job already completed with this result.
     Completed(CompletedJob),
+    /// The job_id is running or completed, but with different parameters.
+    Conflict,
     /// Job is unknown (safe to start).
     Unknown,
 }
@@ -33,9 +350,23 @@ pub enum IsKnown {
 pub struct JobRegistry {
     jobs: Mutex<HashMap<String, JobHandle>>,
     stdin_senders: Mutex<HashMap<String, StdinSender>>,
-    semaphore: Arc<Semaphore>,
+    dispatcher: Arc<Dispatcher>,
+    /// Completed jobs in completion order (front = oldest).
     completed: Mutex<VecDeque<(String, CompletedJob)>>,
+    completed_retention: Duration,
     max_completed: usize,
+    /// Jobs allowed to wait for a slot before [`JobRegistry::admit`] turns
+    /// new ones away; `None` is unlimited.
+    queue_limit: Option<usize>,
+    /// Set once [`JobRegistry::shutdown`] starts; new registrations are refused.
+    shutting_down: AtomicBool,
+    /// Woken whenever a job leaves the running set, so shutdown can wait
+    /// for the set to drain without polling.
+    job_removed: Notify,
+    /// Output counters and subscribers per job, fed by [`JobTee`].
+    streams: std::sync::Mutex<HashMap<String, JobStream>>,
+    /// Every job's lifecycle, for observers. See [`JobRegistry::observe`].
+    observers: broadcast::Sender<Envelope>,
 }
 
 impl JobRegistry {
@@ -43,39 +374,167 @@ impl JobRegistry {
         Self {
             jobs: Mutex::new(HashMap::new()),
             stdin_senders: Mutex::new(HashMap::new()),
-            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            dispatcher: Dispatcher::new(max_concurrent),
             completed: Mutex::new(VecDeque::new()),
-            max_completed: 1000,
+            completed_retention: DEFAULT_COMPLETED_RETENTION,
+            max_completed: MAX_COMPLETED,
+            queue_limit: None,
+            shutting_down: AtomicBool::new(false),
+            job_removed: Notify::new(),
+            streams: std::sync::Mutex::new(HashMap::new()),
+            observers: broadcast::channel(OBSERVER_CAPACITY).0,
         }
     }
 
-    /// Acquire a concurrency permit. Blocks until one is available.
-    pub async fn acquire_permit(&self) -> OwnedSemaphorePermit {
-        self.semaphore
-            .clone()
-            .acquire_owned()
-            .await
-            .expect("semaphore closed")
+    /// Keep completed jobs in the dedup cache for `retention` instead of
+    /// [`DEFAULT_COMPLETED_RETENTION`].
+    pub fn with_completed_retention(mut self, retention: Duration) -> Self {
+        self.completed_retention = retention;
+        self
+    }
+
+    /// Let at most `limit` accepted jobs wait for a slot; see
+    /// [`JobRegistry::admit`].
+    pub fn with_queue_limit(mut self, limit: usize) -> Self {
+        self.queue_limit = Some(limit);
+        self
+    }
+
+    /// Decide whether a job about to be accepted can run now, must queue,
+    /// or should be rejected because the queue limit is reached. Call it
+    /// before registering the job and hand the result to
+    /// [`JobRegistry::wait_for_permit`].
+    pub fn admit(&self, priority: i32) -> Result<Admission, AtCapacity> {
+        let slot = self
+            .dispatcher
+            .admit(priority, self.queue_limit)
+            .map_err(|queued| AtCapacity { queued })?;
+        Ok(Admission {
+            slot,
+            priority,
+            admitted_at: tokio::time::Instant::now(),
+        })
+    }
+
+    /// Wait for `admission`'s slot.
+    ///
+    /// While waiting the job sits in the wait queue, ordered by priority
+    /// (higher runs sooner) and then by arrival. A queued job is told so on
+    /// `tx` with a `JobQueued` envelope straight away, then again each time
+    /// its position changes.
+    pub async fn wait_for_permit<T: EnvelopeSink>(
+        &self,
+        admission: Admission,
+        device_id: &str,
+        job_id: &str,
+        tx: &T,
+    ) -> JobPermit {
+        let Admission {
+            slot,
+            priority,
+            admitted_at,
+        } = admission;
+        let mut ticket = match slot {
+            Slot::Ready(permit) => {
+                return JobPermit {
+                    _permit: permit,
+                    queued: Duration::ZERO,
+                };
+            }
+            Slot::Queued(ticket) => ticket,
+        };
+        let mut reported: Option<u32> = None;
+
+        let permit = loop {
+            // Register for queue changes before reading the position so a
+            // change between the read and the select is not missed.
+            let changed = self.dispatcher.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            if let Some(position) = self.dispatcher.position(&ticket.key)
+                && reported != Some(position)
+            {
+                let waited_ms = admitted_at.elapsed().as_millis() as u64;
+                let estimated_wait_ms = self.dispatcher.estimated_wait_ms(position);
+                info!(job_id = %job_id, position, priority, waited_ms, estimated_wait_ms, "job queued waiting for a permit");
+                let _ = tx.send(crate::executor::make_queued_envelope(
+                    device_id,
+                    job_id,
+                    position,
+                    waited_ms,
+                    estimated_wait_ms,
+                ));
+                reported = Some(position);
+            }
+
+            tokio::select! {
+                permit = &mut ticket.granted => break permit.expect("dispatcher dropped"),
+                _ = changed => {}
+            }
+        };
+
+        JobPermit {
+            _permit: permit,
+            queued: admitted_at.elapsed(),
+        }
     }
 
     /// Register a running job with its cancel sender.
-    pub async fn register(&self, job_id: String, cancel_tx: mpsc::Sender<()>) {
+    ///
+    /// Returns `false` without registering once shutdown has begun; the
+    /// caller should reject the job instead of starting it.
+    #[must_use]
+    pub async fn register(
+        &self,
+        job_id: String,
+        caller_uid: &str,
+        params_hash: ParamsHash,
+        cancel_tx: mpsc::Sender<CancelReason>,
+    ) -> bool {
         let mut jobs = self.jobs.lock().await;
-        jobs.insert(job_id, JobHandle { cancel_tx });
+        if self.is_shutting_down() {
+            return false;
+        }
+        jobs.insert(
+            job_id,
+            JobHandle {
+                caller_uid: caller_uid.to_string(),
+                params_hash,
+                cancel_tx,
+            },
+        );
+        true
     }
 
     /// Register an interactive job with both cancel and stdin senders.
+    ///
+    /// Returns `false` without registering once shutdown has begun.
+    #[must_use]
     pub async fn register_interactive(
         &self,
         job_id: String,
-        cancel_tx: mpsc::Sender<()>,
+        caller_uid: &str,
+        params_hash: ParamsHash,
+        cancel_tx: mpsc::Sender<CancelReason>,
         stdin_tx: StdinSender,
-    ) {
+    ) -> bool {
         let mut jobs = self.jobs.lock().await;
-        jobs.insert(job_id.clone(), JobHandle { cancel_tx });
+        if self.is_shutting_down() {
+            return false;
+        }
+        jobs.insert(
+            job_id.clone(),
+            JobHandle {
+                caller_uid: caller_uid.to_string(),
+                params_hash,
+                cancel_tx,
+            },
+        );
         drop(jobs);
         let mut senders = self.stdin_senders.lock().await;
         senders.insert(job_id, stdin_tx);
+        true
     }
 
     /// Send stdin input to an interactive job. Returns `true` if the message
@@ -94,7 +553,7 @@ impl JobRegistry {
     pub async fn cancel(&self, job_id: &str) {
         let jobs = self.jobs.lock().await;
         if let Some(handle) = jobs.get(job_id) {
-            if handle.cancel_tx.send(()).await.is_ok() {
+            if handle.cancel_tx.send(CancelReason::Requested).await.is_ok() {
                 info!(job_id = %job_id, "cancel signal sent");
             } else {
                 warn!(job_id = %job_id, "cancel channel closed (job may have already finished)");
@@ -104,26 +563,162 @@ impl JobRegistry {
         }
     }
 
+    /// Send a cancel signal to every running job, or only to those submitted
+    /// by `caller_uid` when given. Returns how many jobs were signalled.
+    pub async fn cancel_all(&self, caller_uid: Option<&str>) -> u32 {
+        let jobs = self.jobs.lock().await;
+        let mut cancelled = 0;
+        for (job_id, handle) in jobs.iter() {
+            if caller_uid.is_some_and(|uid| uid != handle.caller_uid) {
+                continue;
+            }
+            // A full channel already holds a pending cancel for this job.
+            match handle.cancel_tx.try_send(CancelReason::Requested) {
+                Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => cancelled += 1,
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    warn!(job_id = %job_id, "cancel channel closed (job may have already finished)");
+                }
+            }
+        }
+        info!(caller_uid = ?caller_uid, cancelled, "cancel-all signal sent");
+        cancelled
+    }
+
+    /// Wrap `tx`, the sink a job's events are sent on, so that they are
+    /// also mirrored to the job's subscribers.
+    pub fn tee<T: EnvelopeSink>(self: &Arc<Self>, job_id: &str, tx: T) -> JobTee<T> {
+        JobTee {
+            inner: tx,
+            job_id: job_id.to_string(),
+            registry: Arc::clone(self),
+        }
+    }
+
+    /// Wrap `tx`, the sink a job request is answered on, so that a
+    /// rejection sent on it also reaches observers.
+    pub fn observed<T: EnvelopeSink>(self: &Arc<Self>, tx: T) -> Observed<T> {
+        Observed {
+            inner: tx,
+            registry: Arc::clone(self),
+        }
+    }
+
+    /// Receive every job's lifecycle from now on, whoever submitted it: the
+    /// accepted `JobRequest` (see [`JobRegistry::publish_started`]), then
+    /// whatever its [`JobTee`] sees, plus `JobRejected`s sent through
+    /// [`JobRegistry::observed`] sinks.
+    pub fn observe(&self) -> broadcast::Receiver<Envelope> {
+        self.observers.subscribe()
+    }
+
+    /// Mirror `envelope` to observers, if there are any.
+    pub fn publish(&self, envelope: &Envelope) {
+        if self.observers.receiver_count() > 0 {
+            let _ = self.observers.send(envelope.clone());
+        }
+    }
+
+    /// Tell observers that `req` was accepted and is about to run.
+    pub fn publish_started(&self, device_id: &str, req: &JobRequest) {
+        if self.observers.receiver_count() > 0 {
+            let _ = self
+                .observers
+                .send(crate::executor::make_started_envelope(device_id, req));
+        }
+    }
+
+    /// Send `job_id`'s future events, up to and including its
+    /// `JobFinished`, to `tx` as well.
+    ///
+    /// The subscriber is dropped when the job finishes, or at the next
+    /// event after `tx` is closed.
+    pub async fn subscribe(
+        &self,
+        job_id: &str,
+        tx: mpsc::UnboundedSender<Envelope>,
+    ) -> Subscription {
+        // Holding the jobs lock orders this against `remove`, which drops
+        // the job's subscribers after taking the job out of the set.
+        let jobs = self.jobs.lock().await;
+        if jobs.contains_key(job_id) {
+            let mut streams = self.streams.lock().unwrap();
+            let stream = streams.entry(job_id.to_string()).or_default();
+            stream.subscribers.retain(|s| !s.is_closed());
+            stream.subscribers.push(tx);
+            return Subscription::Running {
+                stdout_bytes: stream.stdout_bytes,
+                stderr_bytes: stream.stderr_bytes,
+            };
+        }
+        drop(jobs);
+
+        let mut completed = self.completed.lock().await;
+        self.evict_expired(&mut completed);
+        match completed.iter().rev().find(|(id, _)| id == job_id) {
+            Some((_, job)) => Subscription::Finished(job.clone()),
+            None => Subscription::Unknown,
+        }
+    }
+
+    /// Count `envelope`'s output and copy it to `job_id`'s subscribers.
+    fn mirror(&self, job_id: &str, envelope: &Envelope) {
+        let mut streams = self.streams.lock().unwrap();
+        let stream = streams.entry(job_id.to_string()).or_default();
+        if let Some(envelope::Payload::JobEvent(event)) = &envelope.payload {
+            match &event.event {
+                Some(job_event::Event::StdoutChunk(chunk)) => {
+                    stream.stdout_bytes += chunk.len() as u64
+                }
+                Some(job_event::Event::StderrChunk(chunk)) => {
+                    stream.stderr_bytes += chunk.len() as u64
+                }
+                _ => {}
+            }
+        }
+        stream
+            .subscribers
+            .retain(|s| s.send(envelope.clone()).is_ok());
+        if matches!(envelope.payload, Some(envelope::Payload::JobFinished(_))) {
+            streams.remove(job_id);
+        }
+        drop(streams);
+        // Rejections are published by the `Observed` sink a tee wraps.
+        if !matches!(envelope.payload, Some(envelope::Payload::JobRejected(_))) {
+            self.publish(envelope);
+        }
+    }
+
     /// Remove a completed job from the running set.
     pub async fn remove(&self, job_id: &str) {
         let mut jobs = self.jobs.lock().await;
         jobs.remove(job_id);
         drop(jobs);
+        self.streams.lock().unwrap().remove(job_id);
         let mut senders = self.stdin_senders.lock().await;
         senders.remove(job_id);
+        drop(senders);
+        self.job_removed.notify_waiters();
     }
 
-    /// Check if a job_id is already known (running or completed).
-    pub async fn is_known(&self, job_id: &str) -> IsKnown {
+    /// Check if a job_id is already known (running or completed), and
+    /// whether it was submitted with the same parameters.
+    pub async fn is_known(&self, job_id: &str, params_hash: &ParamsHash) -> IsKnown {
         let jobs = self.jobs.lock().await;
-        if jobs.contains_key(job_id) {
+        if let Some(handle) = jobs.get(job_id) {
+            if handle.params_hash != *params_hash {
+                return IsKnown::Conflict;
+            }
             return IsKnown::Running;
         }
         drop(jobs);
 
-        let completed = self.completed.lock().await;
+        let mut completed = self.completed.lock().await;
+        self.evict_expired(&mut completed);
         for (id, result) in completed.iter() {
             if id == job_id {
+                if result.params_hash != *params_hash {
+                    return IsKnown::Conflict;
+                }
                 return IsKnown::Completed(result.clone());
             }
         }
@@ -131,19 +726,723 @@ impl JobRegistry {
         IsKnown::Unknown
     }
 
-    /// Record a completed job for idempotency. Evicts the oldest entry
-    /// when over capacity.
-    pub async fn mark_completed(&self, job_id: String, exit_code: i32, error: String) {
+    /// Record a completed job for idempotency. Evicts entries older than
+    /// the retention window, then the oldest entries while over capacity.
+    pub async fn mark_completed(
+        &self,
+        job_id: String,
+        params_hash: ParamsHash,
+        exit_code: i32,
+        error: String,
+    ) {
         let mut completed = self.completed.lock().await;
-        completed.push_back((job_id, CompletedJob { exit_code, error }));
+        completed.push_back((
+            job_id,
+            CompletedJob {
+                exit_code,
+                error,
+                params_hash,
+                completed_at: Instant::now(),
+            },
+        ));
+        self.evict_expired(&mut completed);
         while completed.len() > self.max_completed {
             completed.pop_front();
         }
     }
 
+    /// Drop completed entries older than the retention window. Entries are
+    /// kept in completion order, so expired ones are always at the front.
+    fn evict_expired(&self, completed: &mut VecDeque<(String, CompletedJob)>) {
+        while completed
+            .front()
+            .is_some_and(|(_, job)| job.completed_at.elapsed() > self.completed_retention)
+        {
+            completed.pop_front();
+        }
+    }
+
     /// Number of currently running jobs.
     pub async fn active_count(&self) -> usize {
         let jobs = self.jobs.lock().await;
         jobs.len()
     }
+
+    /// Whether `job_id` is in the running set.
+    pub async fn is_running(&self, job_id: &str) -> bool {
+        self.jobs.lock().await.contains_key(job_id)
+    }
+
+    /// Whether [`JobRegistry::shutdown`] has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Stop accepting new jobs, cancel every running job with
+    /// [`CancelReason::Shutdown`], and wait up to `grace` for them to finish.
+    ///
+    /// Returns the ids of jobs still registered when the grace period ran
+    /// out (i.e. jobs whose executors did not wind down in time).
+    pub async fn shutdown(&self, grace: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + grace;
+        {
+            // Flip the flag under the jobs lock so no registration can slip
+            // in between the flag check and the cancel sweep below.
+            let jobs = self.jobs.lock().await;
+            self.shutting_down.store(true, Ordering::SeqCst);
+            info!(
+                running = jobs.len(),
+                "registry shutting down, cancelling running jobs"
+            );
+            for (job_id, handle) in jobs.iter() {
+                // A full channel already holds a pending cancel; that is enough.
+                if handle.cancel_tx.try_send(CancelReason::Shutdown).is_ok() {
+                    info!(job_id = %job_id, "shutdown cancel signal sent");
+                }
+            }
+        }
+
+        loop {
+            let removed = self.job_removed.notified();
+            tokio::pin!(removed);
+            removed.as_mut().enable();
+
+            let remaining: Vec<String> = self.jobs.lock().await.keys().cloned().collect();
+            if remaining.is_empty() {
+                return remaining;
+            }
+            if tokio::time::timeout_at(deadline, removed).await.is_err() {
+                warn!(job_ids = ?remaining, "jobs still running after shutdown grace period");
+                return remaining;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahand_protocol::{Envelope, envelope};
+
+    async fn acquire(
+        registry: &JobRegistry,
+        job_id: &str,
+        priority: i32,
+        tx: &mpsc::UnboundedSender<Envelope>,
+    ) -> JobPermit {
+        let admission = registry.admit(priority).expect("admitted");
+        registry
+            .wait_for_permit(admission, "dev-1", job_id, tx)
+            .await
+    }
+
+    fn sleep_request(job_id: &str) -> JobRequest {
+        JobRequest {
+            job_id: job_id.to_string(),
+            tool: "sleep".to_string(),
+            args: vec!["30".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shutdown_cancels_running_job_with_shutdown_error() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        let (cancel_tx, cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("job-1".to_string(), "uid:501", [0; 32], cancel_tx)
+                .await
+        );
+
+        let reg = Arc::clone(&registry);
+        tokio::spawn(async move {
+            let permit = acquire(&reg, "job-1", 0, &tx).await;
+            let (exit_code, error) = crate::executor::run_job(
+                "dev-1".to_string(),
+                sleep_request("job-1"),
+                crate::store::RunContext::new("uid:501"),
+                tx,
+                cancel_rx,
+                None,
+                permit.queued_ms(),
+            )
+            .await;
+            reg.remove("job-1").await;
+            reg.mark_completed("job-1".to_string(), [0; 32], exit_code, error)
+                .await;
+        });
+
+        // Give the executor a moment to spawn the child.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let stuck = registry.shutdown(Duration::from_secs(5)).await;
+        assert!(stuck.is_empty(), "unexpected stuck jobs: {stuck:?}");
+
+        let mut finished = None;
+        while let Some(env) = rx.recv().await {
+            if let Some(envelope::Payload::JobFinished(f)) = env.payload {
+                finished = Some(f);
+                break;
+            }
+        }
+        let finished = finished.expect("expected JobFinished");
+        assert_eq!(finished.job_id, "job-1");
+        assert_eq!(finished.exit_code, -1);
+        assert_eq!(finished.error, "cancelled: daemon shutting down");
+    }
+
+    #[tokio::test]
+    async fn register_refused_after_shutdown() {
+        let registry = JobRegistry::new(1);
+        assert!(
+            registry
+                .shutdown(Duration::from_millis(10))
+                .await
+                .is_empty()
+        );
+        assert!(registry.is_shutting_down());
+
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            !registry
+                .register("late".to_string(), "uid:501", [0; 32], cancel_tx)
+                .await
+        );
+        assert_eq!(registry.active_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn shutdown_reports_jobs_that_outlive_grace() {
+        let registry = JobRegistry::new(1);
+        // Nobody listens on the cancel channel, so the job never finishes.
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("stuck".to_string(), "uid:501", [0; 32], cancel_tx)
+                .await
+        );
+
+        let stuck = registry.shutdown(Duration::from_millis(50)).await;
+        assert_eq!(stuck, vec!["stuck".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn queued_job_reports_its_position_right_away() {
+        let registry = Arc::new(JobRegistry::new(1));
+        let running = registry.dispatcher.try_acquire().unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        let first = {
+            let reg = Arc::clone(&registry);
+            let tx = tx.clone();
+            tokio::spawn(async move { acquire(&reg, "job-a", 0, &tx).await })
+        };
+        // Make sure job-a is queued before job-b.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let second = {
+            let reg = Arc::clone(&registry);
+            tokio::spawn(async move { acquire(&reg, "job-b", 0, &tx).await })
+        };
+
+        let mut positions = std::collections::HashMap::new();
+        for _ in 0..2 {
+            let env = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("queued notice")
+                .expect("channel open");
+            match env.payload {
+                Some(envelope::Payload::JobQueued(q)) => {
+                    // Nothing has finished yet to estimate from.
+                    assert_eq!(q.estimated_wait_ms, 0);
+                    positions.insert(q.job_id, q.position);
+                }
+                other => panic!("expected JobQueued, got {other:?}"),
+            }
+        }
+        assert_eq!(positions["job-a"], 1);
+        assert_eq!(positions["job-b"], 2);
+
+        // Once job-a gets the permit, job-b moves up to the front.
+        drop(running);
+        let permit_a = first.await.unwrap();
+        let env = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("updated notice")
+            .expect("channel open");
+        match env.payload {
+            Some(envelope::Payload::JobQueued(q)) => {
+                assert_eq!(q.job_id, "job-b");
+                assert_eq!(q.position, 1);
+            }
+            other => panic!("expected JobQueued, got {other:?}"),
+        }
+
+        drop(permit_a);
+        let _permit_b = second.await.unwrap();
+        assert_eq!(registry.dispatcher.queued_len(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queue_limit_turns_jobs_away_and_queued_ones_run_in_order() {
+        let registry = Arc::new(JobRegistry::new(1).with_queue_limit(2));
+        let running = registry.admit(0).expect("free slot");
+        assert_eq!(running.position(), None);
+        let first = registry.admit(0).expect("queued");
+        let second = registry.admit(0).expect("queued");
+        assert_eq!((first.position(), second.position()), (Some(1), Some(2)));
+        match registry.admit(0) {
+            Err(full) => assert_eq!(full.to_string(), "device at capacity, 2 jobs queued"),
+            Ok(_) => panic!("admitted past the queue limit"),
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        let (granted_tx, mut granted_rx) = mpsc::unbounded_channel();
+        for (job_id, admission) in [("job-a", first), ("job-b", second)] {
+            let reg = Arc::clone(&registry);
+            let tx = tx.clone();
+            let granted_tx = granted_tx.clone();
+            tokio::spawn(async move {
+                let permit = reg.wait_for_permit(admission, "dev-1", job_id, &tx).await;
+                let _ = granted_tx.send((job_id, permit));
+            });
+        }
+        let mut next_queued = async || match rx.recv().await.and_then(|env| env.payload) {
+            Some(envelope::Payload::JobQueued(q)) => q,
+            other => panic!("expected JobQueued, got {other:?}"),
+        };
+        let mut positions = HashMap::new();
+        for _ in 0..2 {
+            let q = next_queued().await;
+            positions.insert(q.job_id, q.position);
+        }
+        assert_eq!(
+            positions,
+            HashMap::from([("job-a".into(), 1), ("job-b".into(), 2)])
+        );
+
+        // The running job's 10s hold is what later estimates go on.
+        tokio::time::advance(Duration::from_secs(10)).await;
+        drop(running);
+        let (job_id, permit_a) = granted_rx.recv().await.unwrap();
+        assert_eq!(job_id, "job-a");
+        let q = next_queued().await;
+        assert_eq!((q.job_id.as_str(), q.position), ("job-b", 1));
+        assert_eq!(q.estimated_wait_ms, 10_000);
+
+        // Starting job-a made room in the queue again.
+        let third = registry.admit(0).expect("room in the queue");
+        assert_eq!(third.position(), Some(2));
+
+        drop(permit_a);
+        let (job_id, _permit_b) = granted_rx.recv().await.unwrap();
+        assert_eq!(job_id, "job-b");
+        assert_eq!(third.position(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn high_priority_job_jumps_queued_low_priority_jobs() {
+        let registry = Arc::new(JobRegistry::new(1));
+        let running = registry.dispatcher.try_acquire().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel::<Envelope>();
+        let (order_tx, mut order_rx) = mpsc::unbounded_channel::<&'static str>();
+
+        let spawn_job = |job_id: &'static str, priority: i32| {
+            let reg = Arc::clone(&registry);
+            let tx = tx.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = acquire(&reg, job_id, priority, &tx).await;
+                order_tx.send(job_id).unwrap();
+            })
+        };
+
+        let mut handles = Vec::new();
+        for job_id in ["low-1", "low-2", "low-3"] {
+            handles.push(spawn_job(job_id, 0));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        handles.push(spawn_job("high", 10));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(registry.dispatcher.queued_len(), 4);
+
+        drop(running);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let mut order = Vec::new();
+        while let Ok(job_id) = order_rx.try_recv() {
+            order.push(job_id);
+        }
+        assert_eq!(order, vec!["high", "low-1", "low-2", "low-3"]);
+    }
+
+    #[tokio::test]
+    async fn abandoned_waiter_does_not_leak_a_slot() {
+        let registry = Arc::new(JobRegistry::new(1));
+        let running = registry.dispatcher.try_acquire().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel::<Envelope>();
+
+        let waiter = {
+            let reg = Arc::clone(&registry);
+            let tx = tx.clone();
+            tokio::spawn(async move { acquire(&reg, "gone", 0, &tx).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        waiter.abort();
+        let _ = waiter.await;
+        assert_eq!(registry.dispatcher.queued_len(), 0);
+
+        drop(running);
+        assert!(registry.dispatcher.try_acquire().is_some());
+    }
+
+    #[tokio::test]
+    async fn uncontended_permit_sends_no_queued_notice() {
+        let registry = JobRegistry::new(1);
+        let (tx, mut rx) = mpsc::unbounded_channel::<Envelope>();
+        let permit = acquire(&registry, "job-a", 0, &tx).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(permit.queued_ms(), 0);
+    }
+
+    #[tokio::test]
+    async fn completed_entries_expire_by_age() {
+        let registry = JobRegistry::new(1).with_completed_retention(Duration::from_millis(50));
+        registry
+            .mark_completed("old".to_string(), [0; 32], 0, String::new())
+            .await;
+        assert!(matches!(
+            registry.is_known("old", &[0; 32]).await,
+            IsKnown::Completed(_)
+        ));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        registry
+            .mark_completed("new".to_string(), [0; 32], 1, String::new())
+            .await;
+
+        assert!(matches!(
+            registry.is_known("old", &[0; 32]).await,
+            IsKnown::Unknown
+        ));
+        match registry.is_known("new", &[0; 32]).await {
+            IsKnown::Completed(job) => assert_eq!(job.exit_code, 1),
+            _ => panic!("expected fresh entry to be cached"),
+        }
+        assert_eq!(registry.completed.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn is_known_removes_expired_entries_lazily() {
+        let registry = JobRegistry::new(1).with_completed_retention(Duration::from_millis(50));
+        registry
+            .mark_completed("job".to_string(), [0; 32], 0, String::new())
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(matches!(
+            registry.is_known("job", &[0; 32]).await,
+            IsKnown::Unknown
+        ));
+        assert!(registry.completed.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn completed_cache_is_capped_by_count() {
+        let mut registry = JobRegistry::new(1);
+        registry.max_completed = 3;
+        for i in 0..5 {
+            registry
+                .mark_completed(format!("job-{i}"), [0; 32], 0, String::new())
+                .await;
+        }
+
+        assert!(matches!(
+            registry.is_known("job-0", &[0; 32]).await,
+            IsKnown::Unknown
+        ));
+        assert!(matches!(
+            registry.is_known("job-1", &[0; 32]).await,
+            IsKnown::Unknown
+        ));
+        for i in 2..5 {
+            assert!(matches!(
+                registry.is_known(&format!("job-{i}"), &[0; 32]).await,
+                IsKnown::Completed(_)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_all_filters_by_caller() {
+        let registry = JobRegistry::new(4);
+        let (tx_a, mut rx_a) = mpsc::channel(1);
+        let (tx_b, mut rx_b) = mpsc::channel(1);
+        let (tx_c, mut rx_c) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("a".to_string(), "uid:501", [0; 32], tx_a)
+                .await
+        );
+        assert!(
+            registry
+                .register("b".to_string(), "uid:501", [0; 32], tx_b)
+                .await
+        );
+        assert!(
+            registry
+                .register("c".to_string(), "cloud", [0; 32], tx_c)
+                .await
+        );
+
+        assert_eq!(registry.cancel_all(Some("uid:501")).await, 2);
+        assert_eq!(rx_a.try_recv().unwrap(), CancelReason::Requested);
+        assert_eq!(rx_b.try_recv().unwrap(), CancelReason::Requested);
+        assert!(rx_c.try_recv().is_err());
+
+        assert_eq!(registry.cancel_all(Some("uid:999")).await, 0);
+    }
+
+    #[tokio::test]
+    async fn cancel_all_without_filter_hits_every_job() {
+        let registry = JobRegistry::new(4);
+        let (tx_a, mut rx_a) = mpsc::channel(1);
+        let (tx_b, mut rx_b) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("a".to_string(), "uid:501", [0; 32], tx_a)
+                .await
+        );
+        assert!(
+            registry
+                .register("b".to_string(), "cloud", [0; 32], tx_b)
+                .await
+        );
+
+        assert_eq!(registry.cancel_all(None).await, 2);
+        assert_eq!(rx_a.try_recv().unwrap(), CancelReason::Requested);
+        assert_eq!(rx_b.try_recv().unwrap(), CancelReason::Requested);
+    }
+
+    fn hashed_request(args: &[&str], env: &[(&str, &str)]) -> JobRequest {
+        JobRequest {
+            job_id: "job".to_string(),
+            tool: "git".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            cwd: "/tmp".to_string(),
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn params_hash_ignores_env_order_and_timeout() {
+        let a = hashed_request(&["status"], &[("A", "1"), ("B", "2")]);
+        let mut b = hashed_request(&["status"], &[("B", "2"), ("A", "1")]);
+        b.timeout_ms = 5_000;
+        assert_eq!(params_hash(&a), params_hash(&b));
+    }
+
+    #[test]
+    fn params_hash_distinguishes_arg_boundaries() {
+        let a = hashed_request(&["ab", "c"], &[]);
+        let b = hashed_request(&["a", "bc"], &[]);
+        assert_ne!(params_hash(&a), params_hash(&b));
+    }
+
+    #[tokio::test]
+    async fn is_known_reports_conflict_on_hash_mismatch() {
+        let registry = JobRegistry::new(4);
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("running".to_string(), "uid:501", [1; 32], cancel_tx)
+                .await
+        );
+        registry
+            .mark_completed("done".to_string(), [1; 32], 0, String::new())
+            .await;
+
+        assert!(matches!(
+            registry.is_known("running", &[1; 32]).await,
+            IsKnown::Running
+        ));
+        assert!(matches!(
+            registry.is_known("running", &[2; 32]).await,
+            IsKnown::Conflict
+        ));
+        assert!(matches!(
+            registry.is_known("done", &[1; 32]).await,
+            IsKnown::Completed(_)
+        ));
+        assert!(matches!(
+            registry.is_known("done", &[2; 32]).await,
+            IsKnown::Conflict
+        ));
+    }
+
+    fn stdout_event(job_id: &str, chunk: &[u8]) -> Envelope {
+        crate::executor::make_event_envelope("dev-1", job_id, Some(chunk.to_vec()), None)
+    }
+
+    fn finished(job_id: &str) -> Envelope {
+        Envelope {
+            payload: Some(envelope::Payload::JobFinished(
+                ahand_protocol::JobFinished {
+                    job_id: job_id.to_string(),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_events_sent_after_they_subscribe() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("job-1".to_string(), "cloud", [0; 32], cancel_tx)
+                .await
+        );
+        let (owner_tx, mut owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        let tee = registry.tee("job-1", owner_tx);
+        tee.send(stdout_event("job-1", b"early")).unwrap();
+
+        let (sub_tx, mut sub_rx) = mpsc::unbounded_channel();
+        match registry.subscribe("job-1", sub_tx).await {
+            Subscription::Running {
+                stdout_bytes,
+                stderr_bytes,
+            } => assert_eq!((stdout_bytes, stderr_bytes), (5, 0)),
+            _ => panic!("expected a running subscription"),
+        }
+
+        tee.send(stdout_event("job-1", b"late")).unwrap();
+        tee.send(finished("job-1")).unwrap();
+        assert!(matches!(
+            sub_rx.recv().await.unwrap().payload,
+            Some(envelope::Payload::JobEvent(_))
+        ));
+        assert!(matches!(
+            sub_rx.recv().await.unwrap().payload,
+            Some(envelope::Payload::JobFinished(_))
+        ));
+        // The registry let go of the subscriber once the job finished.
+        assert!(sub_rx.recv().await.is_none());
+        assert!(registry.streams.lock().unwrap().is_empty());
+
+        let mut owner_events = 0;
+        while owner_rx.try_recv().is_ok() {
+            owner_events += 1;
+        }
+        assert_eq!(owner_events, 3);
+    }
+
+    #[tokio::test]
+    async fn closed_subscribers_are_dropped_and_removal_clears_the_rest() {
+        let registry = Arc::new(JobRegistry::new(4));
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        assert!(
+            registry
+                .register("job-1".to_string(), "cloud", [0; 32], cancel_tx)
+                .await
+        );
+        let (owner_tx, _owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        let tee = registry.tee("job-1", owner_tx);
+
+        let (gone_tx, gone_rx) = mpsc::unbounded_channel();
+        let (kept_tx, mut kept_rx) = mpsc::unbounded_channel();
+        let _ = registry.subscribe("job-1", gone_tx).await;
+        let _ = registry.subscribe("job-1", kept_tx).await;
+        drop(gone_rx);
+        tee.send(stdout_event("job-1", b"x")).unwrap();
+        assert_eq!(
+            registry.streams.lock().unwrap()["job-1"].subscribers.len(),
+            1
+        );
+
+        registry.remove("job-1").await;
+        assert!(kept_rx.recv().await.is_some());
+        assert!(kept_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn observers_see_every_jobs_lifecycle_without_its_env() {
+        let registry = Arc::new(JobRegistry::new(4));
+        // Nobody is observing yet: nothing is kept for later.
+        registry.publish(&finished("early"));
+        let mut observer = registry.observe();
+
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "echo".to_string(),
+            env: [("TOKEN".to_string(), "s3cret".to_string())].into(),
+            ..Default::default()
+        };
+        registry.publish_started("dev-1", &req);
+        let (owner_tx, _owner_rx) = mpsc::unbounded_channel::<Envelope>();
+        let tee = registry.tee("job-1", owner_tx.clone());
+        tee.send(stdout_event("job-1", b"hi")).unwrap();
+        tee.send(finished("job-1")).unwrap();
+        let observed = registry.observed(owner_tx);
+        observed
+            .send(stdout_event("job-2", b"not mirrored"))
+            .unwrap();
+        observed
+            .send(Envelope {
+                payload: Some(envelope::Payload::JobRejected(
+                    ahand_protocol::JobRejected {
+                        job_id: "job-2".to_string(),
+                        reason: "denied".to_string(),
+                    },
+                )),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut seen = Vec::new();
+        while let Ok(env) = observer.try_recv() {
+            seen.push(env.payload.unwrap());
+        }
+        assert_eq!(seen.len(), 4, "{seen:?}");
+        match &seen[0] {
+            envelope::Payload::JobRequest(started) => {
+                assert_eq!(started.job_id, "job-1");
+                assert!(started.env.is_empty());
+            }
+            other => panic!("expected JobRequest, got {other:?}"),
+        }
+        assert!(matches!(seen[1], envelope::Payload::JobEvent(_)));
+        assert!(matches!(seen[2], envelope::Payload::JobFinished(_)));
+        assert!(matches!(seen[3], envelope::Payload::JobRejected(_)));
+    }
+
+    #[tokio::test]
+    async fn subscribe_reports_finished_and_unknown_jobs() {
+        let registry = JobRegistry::new(4);
+        registry
+            .mark_completed("done".to_string(), [0; 32], 3, "boom".to_string())
+            .await;
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        match registry.subscribe("done", tx.clone()).await {
+            Subscription::Finished(job) => {
+                assert_eq!((job.exit_code, job.error.as_str()), (3, "boom"))
+            }
+            _ => panic!("expected a finished subscription"),
+        }
+        assert!(matches!(
+            registry.subscribe("missing", tx).await,
+            Subscription::Unknown
+        ));
+        assert!(registry.streams.lock().unwrap().is_empty());
+    }
 }
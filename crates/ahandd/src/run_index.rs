@@ -0,0 +1,617 @@
+//! SQLite index over `<data_dir>/runs/`, so listing and looking up runs
+//! doesn't have to walk and stat every run directory.
+//!
+//! The daemon writes a row when a run starts and fills in the outcome when
+//! it finishes. `ahandctl` and the admin panel open the same database to
+//! read it. Opening an index whose schema predates the table backfills it
+//! once from the run directories already on disk.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+pub const RUNS_DB_FILE_NAME: &str = "runs.db";
+
+/// Schema version stored in `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 2;
+
+/// Subdirectory of `<data_dir>/runs` holding browser artifact records, as
+/// `browser/<session_id>/<entry>/`. Their job_id is that relative path.
+pub const BROWSER_RUNS_DIR: &str = "browser";
+
+/// How long a reader waits for the daemon to release a write lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What produced a run record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    /// A job executed by the daemon.
+    #[default]
+    Exec,
+    /// A file left by a browser command (screenshot, pdf, download, ...).
+    Browser,
+}
+
+impl RunKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            RunKind::Exec => "exec",
+            RunKind::Browser => "browser",
+        }
+    }
+}
+
+/// One run as recorded in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub job_id: String,
+    #[serde(default)]
+    pub kind: RunKind,
+    /// The executable, or the action for browser records.
+    pub tool: String,
+    /// Empty for runs backfilled from directories written before the index
+    /// existed.
+    pub caller_uid: String,
+    pub started_ms: u64,
+    /// `None` while the run is in progress (or if the daemon died first).
+    pub finished_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub bytes_stdout: u64,
+    pub bytes_stderr: u64,
+}
+
+/// Restricts [`RunIndex::search`]. Unset fields match everything.
+// The daemon only writes the index; `ahandctl runs` and the admin panel read
+// it through the lib crate, so the query side is dead code in the bin target.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct RunQuery {
+    /// Substring of the tool name.
+    pub tool: Option<String>,
+    pub caller_uid: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Only finished runs with a non-zero exit code.
+    pub failed: bool,
+    /// Only runs started at or after this time (ms since the epoch).
+    pub since_ms: Option<u64>,
+    /// Only runs started before this time (ms since the epoch).
+    pub until_ms: Option<u64>,
+}
+
+/// Handle on `<data_dir>/runs.db`.
+pub struct RunIndex {
+    conn: Mutex<Connection>,
+}
+
+impl RunIndex {
+    /// Open (or create) the index in `data_dir`, migrating it to the current
+    /// schema. The first open after an upgrade backfills rows from the run
+    /// directories already under `<data_dir>/runs`.
+    pub fn open(data_dir: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(data_dir.join(RUNS_DB_FILE_NAME))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version < 1 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS runs (
+                    job_id       TEXT PRIMARY KEY,
+                    tool         TEXT NOT NULL,
+                    caller_uid   TEXT NOT NULL,
+                    started_ms   INTEGER NOT NULL,
+                    finished_ms  INTEGER,
+                    exit_code    INTEGER,
+                    error        TEXT,
+                    bytes_stdout INTEGER NOT NULL DEFAULT 0,
+                    bytes_stderr INTEGER NOT NULL DEFAULT 0
+                );
+                CREATE INDEX IF NOT EXISTS runs_started_ms ON runs (started_ms);",
+            )?;
+            let backfilled = backfill(&conn, &data_dir.join("runs"))?;
+            conn.pragma_update(None, "user_version", 1)?;
+            if backfilled > 0 {
+                info!(
+                    runs = backfilled,
+                    "backfilled runs index from run directories"
+                );
+            }
+        }
+        if version < 2 {
+            conn.execute_batch("ALTER TABLE runs ADD COLUMN kind TEXT NOT NULL DEFAULT 'exec';")?;
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Record a run that just started, replacing any earlier row for the
+    /// same job_id.
+    pub fn insert_started(
+        &self,
+        job_id: &str,
+        tool: &str,
+        caller_uid: &str,
+        started_ms: u64,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO runs (job_id, tool, caller_uid, started_ms)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![job_id, tool, caller_uid, started_ms as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Record a browser action: a run of `kind = browser` that finished
+    /// when it was recorded, with exit code 0 unless it failed with `error`.
+    pub fn insert_browser_run(
+        &self,
+        job_id: &str,
+        action: &str,
+        caller_uid: &str,
+        recorded_ms: u64,
+        error: &str,
+    ) -> rusqlite::Result<()> {
+        let exit_code = if error.is_empty() { 0 } else { -1 };
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO runs
+             (job_id, kind, tool, caller_uid, started_ms, finished_ms, exit_code, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7)",
+            params![
+                job_id,
+                RunKind::Browser.as_str(),
+                action,
+                caller_uid,
+                recorded_ms as i64,
+                exit_code,
+                error
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Fill in the outcome of a run.
+    pub fn update_finished(
+        &self,
+        job_id: &str,
+        finished_ms: u64,
+        exit_code: i32,
+        error: &str,
+        bytes_stdout: u64,
+        bytes_stderr: u64,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE runs
+             SET finished_ms = ?2, exit_code = ?3, error = ?4,
+                 bytes_stdout = ?5, bytes_stderr = ?6
+             WHERE job_id = ?1",
+            params![
+                job_id,
+                finished_ms as i64,
+                exit_code,
+                error,
+                bytes_stdout as i64,
+                bytes_stderr as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Forget runs whose directories were deleted.
+    pub fn remove(&self, job_ids: &[String]) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("DELETE FROM runs WHERE job_id = ?1")?;
+            for job_id in job_ids {
+                stmt.execute(params![job_id])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Runs matching `query`, newest first, plus the total number of
+    /// matches before `limit`/`offset` are applied. Runs that started in the
+    /// same millisecond are ordered by job_id, so paging never repeats or
+    /// skips a run.
+    #[allow(dead_code)]
+    pub fn search(
+        &self,
+        query: &RunQuery,
+        limit: usize,
+        offset: usize,
+    ) -> rusqlite::Result<(usize, Vec<RunRecord>)> {
+        const WHERE: &str = "WHERE (?1 IS NULL OR instr(tool, ?1) > 0)
+              AND (?2 IS NULL OR caller_uid = ?2)
+              AND (?3 IS NULL OR exit_code = ?3)
+              AND (?4 = 0 OR exit_code != 0)
+              AND (?5 IS NULL OR started_ms >= ?5)
+              AND (?6 IS NULL OR started_ms < ?6)";
+        let filter = params![
+            query.tool,
+            query.caller_uid,
+            query.exit_code,
+            query.failed,
+            query.since_ms.map(|ms| ms as i64),
+            query.until_ms.map(|ms| ms as i64),
+        ];
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM runs {WHERE}"),
+            filter,
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM runs {WHERE}
+             ORDER BY started_ms DESC, job_id DESC LIMIT ?7 OFFSET ?8"
+        ))?;
+        let mut page = filter.to_vec();
+        let (limit, offset) = (limit as i64, offset as i64);
+        page.push(&limit);
+        page.push(&offset);
+        let runs = stmt
+            .query_map(page.as_slice(), record_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok((total as usize, runs))
+    }
+
+    /// Look up one run.
+    #[allow(dead_code)]
+    pub fn get_run(&self, job_id: &str) -> rusqlite::Result<Option<RunRecord>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM runs WHERE job_id = ?1"),
+                params![job_id],
+                record_from_row,
+            )
+            .optional()
+    }
+}
+
+const COLUMNS: &str = "job_id, tool, caller_uid, started_ms, finished_ms, exit_code, error, \
+                       bytes_stdout, bytes_stderr, kind";
+
+fn record_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RunRecord> {
+    Ok(RunRecord {
+        job_id: row.get(0)?,
+        tool: row.get(1)?,
+        caller_uid: row.get(2)?,
+        started_ms: row.get::<_, i64>(3)? as u64,
+        finished_ms: row.get::<_, Option<i64>>(4)?.map(|ms| ms as u64),
+        exit_code: row.get(5)?,
+        error: row.get(6)?,
+        bytes_stdout: row.get::<_, i64>(7)? as u64,
+        bytes_stderr: row.get::<_, i64>(8)? as u64,
+        kind: match row.get::<_, String>(9)?.as_str() {
+            "browser" => RunKind::Browser,
+            _ => RunKind::Exec,
+        },
+    })
+}
+
+/// Insert a row for every directory under `runs_dir`, reading what it can
+/// from `request.json` and `result.json`. Unreadable directories are
+/// skipped with a warning.
+fn backfill(conn: &Connection, runs_dir: &Path) -> rusqlite::Result<usize> {
+    let entries = match std::fs::read_dir(runs_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            warn!(error = %e, "failed to list runs dir for index backfill");
+            return Ok(0);
+        }
+    };
+    let mut count = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() || entry.file_name() == BROWSER_RUNS_DIR {
+            continue;
+        }
+        let Some(record) = record_from_dir(&path) else {
+            warn!(run_dir = %path.display(), "skipping unreadable run dir in index backfill");
+            continue;
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO runs
+             (job_id, tool, caller_uid, started_ms, finished_ms, exit_code, error,
+              bytes_stdout, bytes_stderr)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                record.job_id,
+                record.tool,
+                record.caller_uid,
+                record.started_ms as i64,
+                record.finished_ms.map(|ms| ms as i64),
+                record.exit_code,
+                record.error,
+                record.bytes_stdout as i64,
+                record.bytes_stderr as i64
+            ],
+        )?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn record_from_dir(run_dir: &Path) -> Option<RunRecord> {
+    let job_id = run_dir.file_name()?.to_string_lossy().into_owned();
+    let request = read_json(&run_dir.join("request.json"));
+    let result = read_json(&run_dir.join("result.json"));
+    let str_field = |v: &Option<serde_json::Value>, key: &str| {
+        v.as_ref()
+            .and_then(|v| v.get(key))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    let u64_field = |v: &Option<serde_json::Value>, key: &str| {
+        v.as_ref().and_then(|v| v.get(key)).and_then(|v| v.as_u64())
+    };
+    // Fall back to the directory's mtime for runs without request.json.
+    let started_ms = match u64_field(&request, "start_ms") {
+        Some(ms) => ms,
+        None => std::fs::metadata(run_dir)
+            .ok()?
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64,
+    };
+    Some(RunRecord {
+        tool: str_field(&request, "tool").unwrap_or_default(),
+        caller_uid: str_field(&request, "caller_uid").unwrap_or_default(),
+        started_ms,
+        finished_ms: u64_field(&result, "end_ms"),
+        exit_code: result
+            .as_ref()
+            .and_then(|v| v.get("exit_code"))
+            .and_then(|v| v.as_i64())
+            .map(|c| c as i32),
+        error: str_field(&result, "error"),
+        bytes_stdout: file_len(&run_dir.join("stdout")),
+        bytes_stderr: file_len(&run_dir.join("stderr")),
+        job_id,
+        kind: RunKind::Exec,
+    })
+}
+
+fn read_json(path: &Path) -> Option<serde_json::Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Size of `path`, or 0 if it doesn't exist.
+pub(crate) fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(job_id: &str, tool: &str, caller_uid: &str, started_ms: u64) -> RunRecord {
+        RunRecord {
+            job_id: job_id.to_string(),
+            tool: tool.to_string(),
+            caller_uid: caller_uid.to_string(),
+            started_ms,
+            finished_ms: None,
+            exit_code: None,
+            error: None,
+            bytes_stdout: 0,
+            bytes_stderr: 0,
+            kind: RunKind::Exec,
+        }
+    }
+
+    #[test]
+    fn search_is_newest_first_and_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        index.insert_started("a", "git", "uid:501", 100).unwrap();
+        index.insert_started("b", "rg", "uid:501", 200).unwrap();
+        index.insert_started("c", "git", "cloud", 300).unwrap();
+
+        let (total, runs) = index.search(&RunQuery::default(), 2, 0).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(
+            runs,
+            vec![
+                record("c", "git", "cloud", 300),
+                record("b", "rg", "uid:501", 200)
+            ]
+        );
+
+        let (total, runs) = index
+            .search(
+                &RunQuery {
+                    tool: Some("gi".to_string()),
+                    caller_uid: Some("uid:501".to_string()),
+                    ..Default::default()
+                },
+                10,
+                0,
+            )
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(runs, vec![record("a", "git", "uid:501", 100)]);
+    }
+
+    #[test]
+    fn search_filters_on_outcome_and_time_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        for (job_id, started_ms, exit_code) in [
+            ("ok", 100, Some(0)),
+            ("failed", 200, Some(7)),
+            ("running", 300, None),
+        ] {
+            index
+                .insert_started(job_id, "curl", "uid:501", started_ms)
+                .unwrap();
+            if let Some(code) = exit_code {
+                index
+                    .update_finished(job_id, started_ms + 10, code, "", 0, 0)
+                    .unwrap();
+            }
+        }
+        let ids = |query: RunQuery| -> Vec<String> {
+            let (_, runs) = index.search(&query, 10, 0).unwrap();
+            runs.into_iter().map(|r| r.job_id).collect()
+        };
+
+        assert_eq!(
+            ids(RunQuery {
+                failed: true,
+                ..Default::default()
+            }),
+            vec!["failed"]
+        );
+        assert_eq!(
+            ids(RunQuery {
+                exit_code: Some(0),
+                ..Default::default()
+            }),
+            vec!["ok"]
+        );
+        assert_eq!(
+            ids(RunQuery {
+                since_ms: Some(200),
+                until_ms: Some(300),
+                ..Default::default()
+            }),
+            vec!["failed"]
+        );
+    }
+
+    #[test]
+    fn search_pages_runs_with_equal_start_times_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        for job_id in ["a", "b", "c", "d", "e"] {
+            index.insert_started(job_id, "ls", "uid:501", 100).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        for offset in (0..5).step_by(2) {
+            let (total, runs) = index.search(&RunQuery::default(), 2, offset).unwrap();
+            assert_eq!(total, 5);
+            seen.extend(runs.into_iter().map(|r| r.job_id));
+        }
+        assert_eq!(seen, vec!["e", "d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn update_finished_records_the_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        index.insert_started("a", "git", "uid:501", 100).unwrap();
+        index.update_finished("a", 150, 1, "boom", 12, 3).unwrap();
+
+        let run = index.get_run("a").unwrap().unwrap();
+        assert_eq!(run.finished_ms, Some(150));
+        assert_eq!(run.exit_code, Some(1));
+        assert_eq!(run.error.as_deref(), Some("boom"));
+        assert_eq!((run.bytes_stdout, run.bytes_stderr), (12, 3));
+        assert_eq!(index.get_run("missing").unwrap(), None);
+
+        index.remove(&["a".to_string()]).unwrap();
+        assert_eq!(index.get_run("a").unwrap(), None);
+    }
+
+    #[test]
+    fn browser_artifacts_are_indexed_as_finished_browser_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        index.insert_started("a", "git", "uid:501", 100).unwrap();
+        index
+            .insert_browser_run("browser/s1/200_screenshot", "screenshot", "cloud", 200, "")
+            .unwrap();
+
+        let (_, runs) = index.search(&RunQuery::default(), 10, 0).unwrap();
+        let kinds: Vec<_> = runs.iter().map(|r| (r.job_id.as_str(), r.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("browser/s1/200_screenshot", RunKind::Browser),
+                ("a", RunKind::Exec)
+            ]
+        );
+        assert_eq!(runs[0].finished_ms, Some(200));
+        assert_eq!(runs[0].exit_code, Some(0));
+        assert_eq!(serde_json::to_value(&runs[0]).unwrap()["kind"], "browser");
+    }
+
+    #[test]
+    fn version_1_index_gains_the_kind_column() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let conn = Connection::open(dir.path().join(RUNS_DB_FILE_NAME)).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE runs (
+                    job_id TEXT PRIMARY KEY, tool TEXT NOT NULL,
+                    caller_uid TEXT NOT NULL, started_ms INTEGER NOT NULL,
+                    finished_ms INTEGER, exit_code INTEGER, error TEXT,
+                    bytes_stdout INTEGER NOT NULL DEFAULT 0,
+                    bytes_stderr INTEGER NOT NULL DEFAULT 0
+                );
+                INSERT INTO runs (job_id, tool, caller_uid, started_ms)
+                VALUES ('old', 'ls', 'uid:501', 100);
+                PRAGMA user_version = 1;",
+            )
+            .unwrap();
+        }
+
+        let index = RunIndex::open(dir.path()).unwrap();
+        assert_eq!(index.get_run("old").unwrap().unwrap().kind, RunKind::Exec);
+        drop(index);
+        // Re-opening at the current version leaves the schema alone.
+        RunIndex::open(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn first_open_backfills_existing_run_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let run_dir = dir.path().join("runs").join("old-job");
+        std::fs::create_dir_all(&run_dir).unwrap();
+        std::fs::write(
+            run_dir.join("request.json"),
+            r#"{"job_id":"old-job","tool":"ls","start_ms":1000}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            run_dir.join("result.json"),
+            r#"{"job_id":"old-job","exit_code":0,"error":"","end_ms":1500}"#,
+        )
+        .unwrap();
+        std::fs::write(run_dir.join("stdout"), "hello\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("runs").join(BROWSER_RUNS_DIR)).unwrap();
+
+        let index = RunIndex::open(dir.path()).unwrap();
+        assert_eq!(index.get_run(BROWSER_RUNS_DIR).unwrap(), None);
+        let run = index.get_run("old-job").unwrap().unwrap();
+        assert_eq!(run.tool, "ls");
+        assert_eq!(run.started_ms, 1000);
+        assert_eq!(run.finished_ms, Some(1500));
+        assert_eq!(run.exit_code, Some(0));
+        assert_eq!(run.bytes_stdout, 6);
+        drop(index);
+
+        // Backfill only runs once: a later directory is not picked up by a
+        // re-open, the daemon indexes new runs itself.
+        std::fs::create_dir_all(dir.path().join("runs").join("new-job")).unwrap();
+        let index = RunIndex::open(dir.path()).unwrap();
+        assert_eq!(index.get_run("new-job").unwrap(), None);
+        assert!(index.get_run("old-job").unwrap().is_some());
+    }
+}
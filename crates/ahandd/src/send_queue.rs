@@ -0,0 +1,242 @@
+//! Bounded queue feeding the task that owns the cloud WebSocket sink.
+//!
+//! A peer that stops reading must not make the daemon buffer without limit,
+//! but most of what goes out can't just be dropped: a lost `JobFinished` or
+//! `ApprovalRequest` leaves the job hanging on the other side. So only items
+//! that are [`Sheddable`] (job output chunks) are ever dropped, oldest
+//! first, to make room. Anything else is queued even past capacity.
+
+use std::collections::VecDeque;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+/// Whether an item may be dropped when the queue is full.
+pub trait Sheddable {
+    fn sheddable(&self) -> bool;
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    /// Wakes the receiver: an item was queued or the last sender left.
+    item_ready: Notify,
+    /// Wakes [`Sender::closed`] waiters once the receiver is gone.
+    receiver_gone: Notify,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+/// A queue holding about `capacity` items; see the module docs.
+pub fn channel<T: Sheddable>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            items: VecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        capacity: capacity.max(1),
+        item_ready: Notify::new(),
+        receiver_gone: Notify::new(),
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Sheddable> Sender<T> {
+    /// Queue `item`. When the queue is full, the oldest sheddable item
+    /// already queued is dropped to make room, or `item` itself if it is
+    /// sheddable and nothing queued is. Returns the dropped item, or `item`
+    /// back as the error once the receiver is gone.
+    pub fn send(&self, item: T) -> Result<Option<T>, T> {
+        let mut state = self.shared.state.lock().expect("send queue mutex poisoned");
+        if !state.receiver_alive {
+            return Err(item);
+        }
+        let mut shed = None;
+        if state.items.len() >= self.shared.capacity {
+            match state.items.iter().position(Sheddable::sheddable) {
+                Some(oldest) => shed = state.items.remove(oldest),
+                None if item.sheddable() => return Ok(Some(item)),
+                None => {}
+            }
+        }
+        state.items.push_back(item);
+        drop(state);
+        self.shared.item_ready.notify_one();
+        Ok(shed)
+    }
+
+    /// Resolve once the receiver has been dropped.
+    pub async fn closed(&self) {
+        loop {
+            let mut gone = pin!(self.shared.receiver_gone.notified());
+            gone.as_mut().enable();
+            if !self
+                .shared
+                .state
+                .lock()
+                .expect("send queue mutex poisoned")
+                .receiver_alive
+            {
+                return;
+            }
+            gone.await;
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared
+            .state
+            .lock()
+            .expect("send queue mutex poisoned")
+            .senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().expect("send queue mutex poisoned");
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.item_ready.notify_one();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// The next item, or `None` once every sender is gone and the queue
+    /// is drained.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            // `notify_one` leaves a permit, so a wakeup between the check
+            // and the await isn't lost.
+            {
+                let mut state = self.shared.state.lock().expect("send queue mutex poisoned");
+                if let Some(item) = state.items.pop_front() {
+                    return Some(item);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            self.shared.item_ready.notified().await;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.shared
+            .state
+            .lock()
+            .expect("send queue mutex poisoned")
+            .items
+            .pop_front()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().expect("send queue mutex poisoned");
+        state.receiver_alive = false;
+        state.items.clear();
+        drop(state);
+        self.shared.receiver_gone.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Item {
+        Chunk(u32),
+        Finished(u32),
+    }
+    use Item::{Chunk, Finished};
+
+    impl Sheddable for Item {
+        fn sheddable(&self) -> bool {
+            matches!(self, Chunk(_))
+        }
+    }
+
+    fn drain(rx: &mut Receiver<Item>) -> Vec<Item> {
+        std::iter::from_fn(|| rx.try_recv()).collect()
+    }
+
+    #[test]
+    fn full_queue_sheds_the_oldest_chunk_and_keeps_order() {
+        let (tx, mut rx) = channel(4);
+        for item in [Chunk(1), Finished(2), Chunk(3), Chunk(4)] {
+            assert_eq!(tx.send(item), Ok(None));
+        }
+        assert_eq!(tx.send(Finished(5)), Ok(Some(Chunk(1))));
+        assert_eq!(tx.send(Chunk(6)), Ok(Some(Chunk(3))));
+        assert_eq!(
+            drain(&mut rx),
+            vec![Finished(2), Chunk(4), Finished(5), Chunk(6)]
+        );
+    }
+
+    #[test]
+    fn other_items_are_never_shed() {
+        let (tx, mut rx) = channel(2);
+        for n in 1..=4 {
+            assert_eq!(tx.send(Finished(n)), Ok(None));
+        }
+        // Nothing queued may go, so the new chunk is the one dropped.
+        assert_eq!(tx.send(Chunk(5)), Ok(Some(Chunk(5))));
+        assert_eq!(
+            drain(&mut rx),
+            vec![Finished(1), Finished(2), Finished(3), Finished(4)]
+        );
+    }
+
+    #[tokio::test]
+    async fn closing_either_end_is_seen_by_the_other() {
+        let (tx, mut rx) = channel(4);
+        let tx2 = tx.clone();
+        tx.send(Finished(1)).unwrap();
+        drop(tx);
+        drop(tx2);
+        assert_eq!(rx.recv().await, Some(Finished(1)));
+        assert_eq!(rx.recv().await, None);
+
+        let (tx, rx) = channel::<Item>(4);
+        let waiter = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.closed().await }
+        });
+        drop(rx);
+        tokio::time::timeout(std::time::Duration::from_secs(2), waiter)
+            .await
+            .expect("closed() did not resolve")
+            .unwrap();
+        assert_eq!(tx.send(Chunk(2)), Err(Chunk(2)));
+    }
+}
@@ -1,9 +1,58 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
-use ahand_protocol::{JobRequest, RefusalContext, SessionMode, SessionState};
-use tokio::sync::Mutex;
-use tracing::info;
+use ahand_protocol::{
+    ApprovalContext, Envelope, JobRequest, RefusalContext, SessionMode, SessionState, envelope,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::audit::{AuditEntry, AuditLog};
+
+/// [`SessionState::origin`] of changes made over the cloud connection.
+pub const ORIGIN_CLOUD: &str = "cloud";
+/// [`SessionState::origin`] of changes made over IPC.
+pub const ORIGIN_IPC: &str = "ipc";
+/// [`SessionState::origin`] of changes the daemon makes itself.
+pub const ORIGIN_DAEMON: &str = "daemon";
+
+/// Tools ReadOnly mode runs without approval unless configured otherwise.
+pub const DEFAULT_READ_ONLY_TOOLS: &[&str] = &[
+    "ls",
+    "cat",
+    "head",
+    "tail",
+    "grep",
+    "find",
+    "ps",
+    "df",
+    "uname",
+    "git status",
+    "git log",
+    "git diff",
+];
+
+/// Default per-caller cap on the refusal log.
+pub const DEFAULT_MAX_REFUSALS_PER_CALLER: usize = 200;
+
+/// How long refusals and approvals are kept as approval context.
+const HISTORY_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+/// How often [`watch_trust_expiry`] checks trust deadlines.
+const TRUST_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often [`flush_sessions`] saves trust activity.
+const SESSIONS_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often [`sweep_idle_sessions`] looks for idle sessions.
+const IDLE_SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How long before trust expires the warning notification goes out.
+const TRUST_EXPIRY_WARNING: Duration = Duration::from_secs(5 * 60);
 
 /// Session-level decision for a job request.
 pub enum SessionDecision {
@@ -15,6 +64,7 @@ pub enum SessionDecision {
     NeedsApproval {
         reason: String,
         previous_refusals: Vec<RefusalContext>,
+        previous_approvals: Vec<ApprovalContext>,
     },
 }
 
@@ -24,6 +74,73 @@ struct CallerSession {
     trust_expires: Option<Instant>,
     /// Configured trust timeout in minutes.
     trust_timeout_mins: u64,
+    /// Whether the expiry warning went out for the current trust period.
+    expiry_warned: bool,
+    /// Jobs left before budgeted trust runs out; `None` when unlimited.
+    trust_jobs_remaining: Option<u32>,
+    /// Mode restored when trust runs out, by timer or job budget.
+    revert_mode: SessionMode,
+    /// Last registration, mode change or job check; drives the idle sweep.
+    last_active: Instant,
+}
+
+impl CallerSession {
+    fn new(mode: SessionMode, trust_expires: Option<Instant>, trust_timeout_mins: u64) -> Self {
+        Self {
+            mode,
+            trust_expires,
+            trust_timeout_mins,
+            expiry_warned: false,
+            trust_jobs_remaining: None,
+            revert_mode: SessionMode::Inactive,
+            last_active: Instant::now(),
+        }
+    }
+
+    /// Leave Trust for the mode it was entered from.
+    fn end_trust(&mut self) {
+        self.mode = self.revert_mode;
+        self.trust_expires = None;
+        self.trust_jobs_remaining = None;
+        self.revert_mode = SessionMode::Inactive;
+    }
+
+    fn state(&self, caller_uid: &str) -> SessionState {
+        let now = Instant::now();
+        let trust_expires_ms = self
+            .trust_expires
+            .filter(|exp| *exp > now)
+            .map(|exp| now_ms() + exp.duration_since(now).as_millis() as u64)
+            .unwrap_or(0);
+        SessionState {
+            caller_uid: caller_uid.to_string(),
+            mode: self.mode.into(),
+            trust_expires_ms,
+            trust_timeout_mins: self.trust_timeout_mins,
+            trust_jobs_remaining: self.trust_jobs_remaining.unwrap_or(0),
+            ..Default::default()
+        }
+    }
+}
+
+/// On-disk form of a [`CallerSession`] in `sessions.json`. Trust expiry is
+/// stored as an absolute unix timestamp since an `Instant` does not
+/// survive a restart.
+#[derive(Serialize, Deserialize)]
+struct PersistedSession {
+    mode: String,
+    /// Unix ms when trust expires; 0 when not applicable.
+    #[serde(default)]
+    trust_expires_ms: u64,
+    trust_timeout_mins: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trust_jobs_remaining: Option<u32>,
+    /// Mode to restore when budgeted trust runs out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_mode: Option<String>,
+    /// Unix ms of the last activity; 0 (older files) counts as now.
+    #[serde(default)]
+    last_active_ms: u64,
 }
 
 struct RefusalEntry {
@@ -35,24 +152,179 @@ struct RefusalEntry {
     refused_at_ms: u64,
 }
 
+struct ApprovalEntry {
+    tool: String,
+    args: Vec<String>,
+    /// When this entry expires (approved_at + 24h).
+    expires_at: Instant,
+    /// Absolute timestamp for the proto message.
+    approved_at_ms: u64,
+}
+
 pub struct SessionManager {
     sessions: Mutex<HashMap<String, CallerSession>>,
-    refusal_log: Mutex<Vec<RefusalEntry>>,
+    /// Recent refusals keyed by caller_uid, oldest first.
+    refusal_log: Mutex<HashMap<String, Vec<RefusalEntry>>>,
+    /// Recent approvals keyed by caller_uid, oldest first.
+    approval_log: Mutex<HashMap<String, Vec<ApprovalEntry>>>,
+    /// Per-caller cap on `refusal_log` and `approval_log` each; the oldest
+    /// entries are evicted.
+    max_refusals_per_caller: usize,
     default_trust_timeout_mins: u64,
     /// Default mode applied to new callers on registration.
     default_mode: Mutex<SessionMode>,
+    /// Allowlist for ReadOnly mode (see [`SessionManager::with_read_only_tools`]).
+    read_only_tools: Vec<String>,
+    /// Cap job priority at 0 for callers not in Trust or AutoAccept mode.
+    clamp_untrusted_priority: bool,
+    audit: Option<Arc<AuditLog>>,
+    /// Where session modes are persisted; `None` keeps them in memory.
+    sessions_path: Option<PathBuf>,
+    /// Trust activity (deadline, job budget) changed since the last save.
+    /// Saved by [`SessionManager::flush`] rather than on every job.
+    unsaved_activity: AtomicBool,
+    /// Snapshots taken so far; numbered under the sessions lock so a
+    /// slower write of an older one can't overwrite a newer one.
+    snapshots: AtomicU64,
+    /// Number of the last snapshot written to `sessions_path`.
+    saved_snapshot: Arc<std::sync::Mutex<u64>>,
+    /// Receives states the manager changes while checking jobs, i.e. when
+    /// trust runs out.
+    state_events: Option<mpsc::UnboundedSender<SessionState>>,
 }
 
 impl SessionManager {
     pub fn new(default_trust_timeout_mins: u64) -> Self {
         Self {
             sessions: Mutex::new(HashMap::new()),
-            refusal_log: Mutex::new(Vec::new()),
+            refusal_log: Mutex::new(HashMap::new()),
+            approval_log: Mutex::new(HashMap::new()),
+            max_refusals_per_caller: DEFAULT_MAX_REFUSALS_PER_CALLER,
+            read_only_tools: DEFAULT_READ_ONLY_TOOLS
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
             default_trust_timeout_mins,
             default_mode: Mutex::new(SessionMode::Inactive),
+            clamp_untrusted_priority: false,
+            audit: None,
+            sessions_path: None,
+            unsaved_activity: AtomicBool::new(false),
+            snapshots: AtomicU64::new(0),
+            saved_snapshot: Arc::new(std::sync::Mutex::new(0)),
+            state_events: None,
         }
     }
 
+    /// Send states changed by [`SessionManager::check`] (trust running out)
+    /// to `events`.
+    pub fn with_state_events(mut self, events: mpsc::UnboundedSender<SessionState>) -> Self {
+        self.state_events = Some(events);
+        self
+    }
+
+    /// Persist session modes to `path`, loading any saved there. Trust
+    /// that expired while the daemon was down loads as its pre-trust mode.
+    pub fn with_sessions_file(mut self, path: PathBuf) -> Self {
+        *self.sessions.get_mut() = load_sessions(&path);
+        self.sessions_path = Some(path);
+        self
+    }
+
+    fn notify(&self, state: SessionState) {
+        if let Some(events) = &self.state_events {
+            let _ = events.send(state);
+        }
+    }
+
+    /// Write `sessions` now. Used for mode changes; trust activity waits
+    /// for [`Self::flush`].
+    fn persist(&self, sessions: &HashMap<String, CallerSession>) {
+        if let Some(snapshot) = self.snapshot(sessions) {
+            snapshot.save();
+        }
+    }
+
+    /// Capture `sessions` for writing. Call with the sessions lock held.
+    fn snapshot(&self, sessions: &HashMap<String, CallerSession>) -> Option<SessionsSnapshot> {
+        let path = self.sessions_path.clone()?;
+        self.unsaved_activity.store(false, Ordering::Relaxed);
+        Some(SessionsSnapshot {
+            path,
+            number: self.snapshots.fetch_add(1, Ordering::Relaxed) + 1,
+            data: encode_sessions(sessions),
+            saved: Arc::clone(&self.saved_snapshot),
+        })
+    }
+
+    /// Save trust activity recorded since the last write, outside the
+    /// sessions lock and off the async workers. Called periodically by
+    /// [`flush_sessions`] and at shutdown.
+    pub async fn flush(&self) {
+        if !self.unsaved_activity.load(Ordering::Relaxed) {
+            return;
+        }
+        let snapshot = self.snapshot(&*self.sessions.lock().await);
+        if let Some(snapshot) = snapshot {
+            let _ = tokio::task::spawn_blocking(move || snapshot.save()).await;
+        }
+    }
+
+    /// Record every [`SessionManager::check`] decision in `audit`.
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Keep at most `max` refusals, and as many approvals, per caller (default
+    /// [`DEFAULT_MAX_REFUSALS_PER_CALLER`]).
+    pub fn with_max_refusals_per_caller(mut self, max: usize) -> Self {
+        self.max_refusals_per_caller = max;
+        self
+    }
+
+    /// Replace the tools ReadOnly mode runs without approval. An entry is a
+    /// tool name, optionally followed by the subcommand its arguments must
+    /// start with (`"git status"`).
+    pub fn with_read_only_tools(mut self, tools: Vec<String>) -> Self {
+        self.read_only_tools = tools;
+        self
+    }
+
+    /// Whether `req` matches an entry of the ReadOnly allowlist.
+    fn is_read_only(&self, req: &JobRequest) -> bool {
+        self.read_only_tools.iter().any(|entry| {
+            let mut words = entry.split_whitespace();
+            words.next() == Some(req.tool.as_str())
+                && words
+                    .enumerate()
+                    .all(|(i, word)| req.args.get(i).is_some_and(|arg| arg == word))
+        })
+    }
+
+    /// Cap the job priority of untrusted callers at 0 (see
+    /// [`SessionManager::effective_priority`]).
+    pub fn with_priority_clamp(mut self, clamp_untrusted_priority: bool) -> Self {
+        self.clamp_untrusted_priority = clamp_untrusted_priority;
+        self
+    }
+
+    /// The priority a caller's job actually runs with. When clamping is on,
+    /// only callers in Trust (unexpired) or AutoAccept mode may jump the
+    /// queue; everyone else is capped at 0.
+    pub async fn effective_priority(&self, caller_uid: &str, requested: i32) -> i32 {
+        if !self.clamp_untrusted_priority || requested <= 0 {
+            return requested;
+        }
+        let sessions = self.sessions.lock().await;
+        let trusted = sessions.get(caller_uid).is_some_and(|s| match s.mode {
+            SessionMode::AutoAccept => true,
+            SessionMode::Trust => s.trust_expires.is_none_or(|e| Instant::now() < e),
+            _ => false,
+        });
+        if trusted { requested } else { 0 }
+    }
+
     /// Set the default session mode for all new callers.
     pub async fn set_default_mode(&self, mode: SessionMode) {
         info!(mode = ?mode, "setting default session mode for new callers");
@@ -64,69 +336,169 @@ impl SessionManager {
         let default_mode = *self.default_mode.lock().await;
         let default_timeout = self.default_trust_timeout_mins;
         let mut sessions = self.sessions.lock().await;
-        sessions.entry(caller_uid.to_string()).or_insert_with(|| {
-            let trust_expires = if default_mode == SessionMode::Trust {
-                Some(Instant::now() + Duration::from_secs(default_timeout * 60))
-            } else {
-                None
-            };
-            info!(caller_uid, mode = ?default_mode, "registering new caller");
-            CallerSession {
-                mode: default_mode,
-                trust_expires,
-                trust_timeout_mins: default_timeout,
-            }
-        });
+        sessions
+            .entry(caller_uid.to_string())
+            .and_modify(|session| session.last_active = Instant::now())
+            .or_insert_with(|| {
+                let trust_expires = if default_mode == SessionMode::Trust {
+                    Some(Instant::now() + Duration::from_secs(default_timeout * 60))
+                } else {
+                    None
+                };
+                info!(caller_uid, mode = ?default_mode, "registering new caller");
+                CallerSession::new(default_mode, trust_expires, default_timeout)
+            });
     }
 
     /// Evaluate a job request against the caller's session mode.
     pub async fn check(&self, req: &JobRequest, caller_uid: &str) -> SessionDecision {
+        let (decision, mode) = self.evaluate(req, caller_uid, true).await;
+        if let Some(audit) = &self.audit {
+            let (label, reason) = match &decision {
+                SessionDecision::Allow => ("allow", ""),
+                SessionDecision::Deny(reason) => ("deny", reason.as_str()),
+                SessionDecision::NeedsApproval { reason, .. } => {
+                    ("needs_approval", reason.as_str())
+                }
+            };
+            audit
+                .record(AuditEntry::for_job(
+                    "session",
+                    req,
+                    caller_uid,
+                    label,
+                    reason,
+                    Some(mode_rule(mode).to_string()),
+                ))
+                .await;
+        }
+        decision
+    }
+
+    /// What [`SessionManager::check`] would decide, without auditing or
+    /// touching the caller's trust timer. Also returns the mode used.
+    pub async fn dry_run(
+        &self,
+        req: &JobRequest,
+        caller_uid: &str,
+    ) -> (SessionDecision, SessionMode) {
+        self.evaluate(req, caller_uid, false).await
+    }
+
+    /// [`SessionManager::check`] without the audit record; also returns the
+    /// mode the decision was based on. With `touch` unset the session is
+    /// left as is (no trust reset or expiry).
+    async fn evaluate(
+        &self,
+        req: &JobRequest,
+        caller_uid: &str,
+        touch: bool,
+    ) -> (SessionDecision, SessionMode) {
         let mut sessions = self.sessions.lock().await;
 
         let session = match sessions.get_mut(caller_uid) {
             Some(s) => s,
             None => {
                 // No session exists → Inactive.
-                return SessionDecision::Deny("session not activated".to_string());
+                return (
+                    SessionDecision::Deny("session not activated".to_string()),
+                    SessionMode::Inactive,
+                );
             }
         };
 
-        match session.mode {
+        if touch {
+            session.last_active = Instant::now();
+        }
+        let mode = session.mode;
+        let decision = match session.mode {
             SessionMode::Inactive => SessionDecision::Deny("session not activated".to_string()),
             SessionMode::Strict => {
-                // Drop the sessions lock before acquiring refusal_log lock.
+                // Drop the sessions lock before acquiring the history locks.
                 drop(sessions);
-                let refusals = self.get_refusals(&req.tool).await;
                 SessionDecision::NeedsApproval {
                     reason: format!("strict mode: approval required for {:?}", req.tool),
-                    previous_refusals: refusals,
+                    previous_refusals: self.get_refusals(caller_uid, &req.tool).await,
+                    previous_approvals: self.get_approvals(caller_uid, &req.tool).await,
                 }
             }
             SessionMode::Trust => {
                 if let Some(expires) = session.trust_expires {
                     if Instant::now() >= expires {
-                        // Trust expired → revert to Inactive.
-                        info!(caller_uid, "trust expired, reverting to inactive");
-                        session.mode = SessionMode::Inactive;
-                        session.trust_expires = None;
-                        return SessionDecision::Deny("trust expired".to_string());
+                        if touch {
+                            // Trust expired → revert to the pre-trust mode.
+                            info!(caller_uid, revert_mode = ?session.revert_mode, "trust expired, reverting");
+                            session.end_trust();
+                            self.notify(session.state(caller_uid));
+                            self.persist(&sessions);
+                        }
+                        return (SessionDecision::Deny("trust expired".to_string()), mode);
+                    }
+                    if touch {
+                        // Reset the inactivity timer on activity.
+                        session.trust_expires = Some(
+                            Instant::now() + Duration::from_secs(session.trust_timeout_mins * 60),
+                        );
+                        session.expiry_warned = false;
+                    }
+                }
+                if touch {
+                    let mut ended = false;
+                    if let Some(remaining) = &mut session.trust_jobs_remaining {
+                        *remaining = remaining.saturating_sub(1);
+                        if *remaining == 0 {
+                            info!(caller_uid, revert_mode = ?session.revert_mode, "trust job budget used up, reverting");
+                            session.end_trust();
+                            self.notify(session.state(caller_uid));
+                            ended = true;
+                        }
+                    }
+                    if ended {
+                        self.persist(&sessions);
+                    } else {
+                        self.unsaved_activity.store(true, Ordering::Relaxed);
                     }
-                    // Reset the inactivity timer on activity.
-                    session.trust_expires =
-                        Some(Instant::now() + Duration::from_secs(session.trust_timeout_mins * 60));
                 }
                 SessionDecision::Allow
             }
             SessionMode::AutoAccept => SessionDecision::Allow,
-        }
+            SessionMode::ReadOnly => {
+                if self.is_read_only(req) {
+                    SessionDecision::Allow
+                } else {
+                    drop(sessions);
+                    SessionDecision::NeedsApproval {
+                        reason: "read-only session".to_string(),
+                        previous_refusals: self.get_refusals(caller_uid, &req.tool).await,
+                        previous_approvals: self.get_approvals(caller_uid, &req.tool).await,
+                    }
+                }
+            }
+        };
+        (decision, mode)
     }
 
     /// Set the session mode for a caller. Returns the new SessionState.
+    #[allow(dead_code)] // the daemon binary sets modes with a budget; kept for SDK and tests
     pub async fn set_mode(
         &self,
         caller_uid: &str,
         mode: SessionMode,
         trust_timeout_mins: u64,
+    ) -> SessionState {
+        self.set_mode_with_budget(caller_uid, mode, trust_timeout_mins, 0)
+            .await
+    }
+
+    /// [`SessionManager::set_mode`] with a job budget for Trust: after
+    /// `trust_job_budget` allowed jobs (0 = unlimited) the caller reverts to
+    /// the mode it was in before, or Inactive if that mode auto-approved.
+    pub async fn set_mode_with_budget(
+        &self,
+        caller_uid: &str,
+        mode: SessionMode,
+        trust_timeout_mins: u64,
+        trust_job_budget: u32,
     ) -> SessionState {
         let timeout = if trust_timeout_mins == 0 {
             self.default_trust_timeout_mins
@@ -140,59 +512,142 @@ impl SessionManager {
             None
         };
 
-        let trust_expires_ms = trust_expires
-            .map(|exp| {
-                let remaining = exp.duration_since(Instant::now());
-                now_ms() + remaining.as_millis() as u64
-            })
-            .unwrap_or(0);
-
-        let session = CallerSession {
-            mode,
-            trust_expires,
-            trust_timeout_mins: timeout,
-        };
+        let mut sessions = self.sessions.lock().await;
+        let mut session = CallerSession::new(mode, trust_expires, timeout);
+        if mode == SessionMode::Trust && trust_job_budget > 0 {
+            // Re-trusting keeps the original fallback rather than Trust.
+            let previous = sessions
+                .get(caller_uid)
+                .map(|s| {
+                    if s.mode == SessionMode::Trust {
+                        s.revert_mode
+                    } else {
+                        s.mode
+                    }
+                })
+                .unwrap_or(SessionMode::Inactive);
+            session.revert_mode = match previous {
+                SessionMode::AutoAccept | SessionMode::Trust => SessionMode::Inactive,
+                other => other,
+            };
+            session.trust_jobs_remaining = Some(trust_job_budget);
+        }
 
         info!(
             caller_uid,
             mode = ?mode,
             trust_timeout_mins = timeout,
+            trust_job_budget,
             "session mode set"
         );
 
-        self.sessions
-            .lock()
-            .await
-            .insert(caller_uid.to_string(), session);
+        let state = session.state(caller_uid);
+        sessions.insert(caller_uid.to_string(), session);
+        self.persist(&sessions);
+        state
+    }
 
-        SessionState {
-            caller_uid: caller_uid.to_string(),
-            mode: mode.into(),
-            trust_expires_ms,
-            trust_timeout_mins: timeout,
+    /// Revert Trust sessions whose deadline has passed to their pre-trust
+    /// mode (Inactive unless a job budget was set) and
+    /// return the notifications to send: the new state of each expired
+    /// session, plus an `expiry_warning` state once per trust period when
+    /// expiry is within [`TRUST_EXPIRY_WARNING`].
+    pub async fn expire_trust(&self) -> Vec<SessionState> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().await;
+        let mut events = Vec::new();
+        let mut expired_any = false;
+        for (caller_uid, session) in sessions.iter_mut() {
+            let Some(expires) = session.trust_expires else {
+                continue;
+            };
+            if session.mode != SessionMode::Trust {
+                continue;
+            }
+            if now >= expires {
+                info!(caller_uid, revert_mode = ?session.revert_mode, "trust expired, reverting");
+                session.end_trust();
+                expired_any = true;
+                events.push(session.state(caller_uid));
+            } else if !session.expiry_warned && expires - now <= TRUST_EXPIRY_WARNING {
+                session.expiry_warned = true;
+                events.push(SessionState {
+                    expiry_warning: true,
+                    ..session.state(caller_uid)
+                });
+            }
+        }
+        if expired_any {
+            self.persist(&sessions);
         }
+        events
     }
 
-    /// Record a refusal with reason (stored for 24h).
-    pub async fn record_refusal(&self, _caller_uid: &str, tool: &str, reason: &str) {
+    /// Record a refusal with reason for `caller_uid` (stored for 24h).
+    pub async fn record_refusal(&self, caller_uid: &str, tool: &str, reason: &str) {
         let entry = RefusalEntry {
             tool: tool.to_string(),
             reason: reason.to_string(),
-            expires_at: Instant::now() + Duration::from_secs(24 * 3600),
+            expires_at: Instant::now() + HISTORY_RETENTION,
             refused_at_ms: now_ms(),
         };
-        self.refusal_log.lock().await.push(entry);
+        let mut log = self.refusal_log.lock().await;
+        push_capped(&mut log, caller_uid, entry, self.max_refusals_per_caller);
+    }
+
+    /// Record an approval for `caller_uid` (stored for 24h).
+    pub async fn record_approval(&self, caller_uid: &str, tool: &str, args: &[String]) {
+        let entry = ApprovalEntry {
+            tool: tool.to_string(),
+            args: args.to_vec(),
+            expires_at: Instant::now() + HISTORY_RETENTION,
+            approved_at_ms: now_ms(),
+        };
+        let mut log = self.approval_log.lock().await;
+        push_capped(&mut log, caller_uid, entry, self.max_refusals_per_caller);
+    }
+
+    /// Get `caller_uid`'s recent approvals for a specific tool (within 24h).
+    pub async fn get_approvals(&self, caller_uid: &str, tool: &str) -> Vec<ApprovalContext> {
+        let mut log = self.approval_log.lock().await;
+        let now = Instant::now();
+
+        // Prune expired entries.
+        log.retain(|_, entries| {
+            entries.retain(|e| e.expires_at > now);
+            !entries.is_empty()
+        });
+
+        let Some(entries) = log.get(caller_uid) else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .filter(|e| e.tool == tool)
+            .map(|e| ApprovalContext {
+                tool: e.tool.clone(),
+                args: e.args.clone(),
+                approved_at_ms: e.approved_at_ms,
+            })
+            .collect()
     }
 
-    /// Get recent refusals for a specific tool (within 24h).
-    pub async fn get_refusals(&self, tool: &str) -> Vec<RefusalContext> {
+    /// Get `caller_uid`'s recent refusals for a specific tool (within 24h).
+    pub async fn get_refusals(&self, caller_uid: &str, tool: &str) -> Vec<RefusalContext> {
         let mut log = self.refusal_log.lock().await;
         let now = Instant::now();
 
         // Prune expired entries.
-        log.retain(|e| e.expires_at > now);
+        log.retain(|_, entries| {
+            entries.retain(|e| e.expires_at > now);
+            !entries.is_empty()
+        });
 
-        log.iter()
+        let Some(entries) = log.get(caller_uid) else {
+            return Vec::new();
+        };
+        entries
+            .iter()
             .filter(|e| e.tool == tool)
             .map(|e| RefusalContext {
                 tool: e.tool.clone(),
@@ -206,35 +661,23 @@ impl SessionManager {
     pub async fn get_session_state(&self, caller_uid: &str) -> SessionState {
         let sessions = self.sessions.lock().await;
         match sessions.get(caller_uid) {
-            Some(session) => {
-                let trust_expires_ms = session
-                    .trust_expires
-                    .and_then(|exp| {
-                        let now = Instant::now();
-                        if exp > now {
-                            Some(now_ms() + exp.duration_since(now).as_millis() as u64)
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or(0);
-
-                SessionState {
-                    caller_uid: caller_uid.to_string(),
-                    mode: session.mode.into(),
-                    trust_expires_ms,
-                    trust_timeout_mins: session.trust_timeout_mins,
-                }
-            }
+            Some(session) => session.state(caller_uid),
             None => SessionState {
                 caller_uid: caller_uid.to_string(),
                 mode: SessionMode::Inactive.into(),
                 trust_expires_ms: 0,
                 trust_timeout_mins: self.default_trust_timeout_mins,
+                ..Default::default()
             },
         }
     }
 
+    /// Config name of the caller's current session mode (e.g. `"strict"`).
+    pub async fn mode_name(&self, caller_uid: &str) -> &'static str {
+        let mode = self.get_session_state(caller_uid).await.mode;
+        mode_name(SessionMode::try_from(mode).unwrap_or(SessionMode::Inactive))
+    }
+
     /// Get session states for all callers (or a specific one if caller_uid is non-empty).
     pub async fn query_sessions(&self, caller_uid: &str) -> Vec<SessionState> {
         if !caller_uid.is_empty() {
@@ -242,30 +685,135 @@ impl SessionManager {
         }
 
         let sessions = self.sessions.lock().await;
-        sessions
-            .keys()
-            .map(|uid| {
-                let session = &sessions[uid];
-                let trust_expires_ms = session
-                    .trust_expires
-                    .and_then(|exp| {
-                        let now = Instant::now();
-                        if exp > now {
-                            Some(now_ms() + exp.duration_since(now).as_millis() as u64)
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or(0);
-
-                SessionState {
-                    caller_uid: uid.clone(),
-                    mode: session.mode.into(),
-                    trust_expires_ms,
-                    trust_timeout_mins: session.trust_timeout_mins,
-                }
+        let mut states: Vec<_> = sessions
+            .iter()
+            .map(|(uid, session)| session.state(uid))
+            .collect();
+        states.sort_by(|a, b| a.caller_uid.cmp(&b.caller_uid));
+        states
+    }
+
+    /// Forget `caller_uid`'s session and approval history. Returns whether
+    /// a session existed.
+    pub async fn clear_session(&self, caller_uid: &str) -> bool {
+        let mut sessions = self.sessions.lock().await;
+        let removed = sessions.remove(caller_uid).is_some();
+        if removed {
+            info!(caller_uid, "session cleared");
+            self.persist(&sessions);
+        }
+        drop(sessions);
+        self.refusal_log.lock().await.remove(caller_uid);
+        self.approval_log.lock().await.remove(caller_uid);
+        removed
+    }
+
+    /// Forget Inactive sessions, and their approval history, with no activity
+    /// for `max_idle`. Returns the removed callers.
+    pub async fn sweep_idle(&self, max_idle: Duration) -> Vec<String> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().await;
+        let idle: Vec<String> = sessions
+            .iter()
+            .filter(|(_, s)| {
+                s.mode == SessionMode::Inactive && now.duration_since(s.last_active) >= max_idle
             })
-            .collect()
+            .map(|(uid, _)| uid.clone())
+            .collect();
+        if idle.is_empty() {
+            return idle;
+        }
+        for uid in &idle {
+            sessions.remove(uid);
+        }
+        self.persist(&sessions);
+        drop(sessions);
+        let mut refusals = self.refusal_log.lock().await;
+        let mut approvals = self.approval_log.lock().await;
+        for uid in &idle {
+            refusals.remove(uid);
+            approvals.remove(uid);
+        }
+        idle
+    }
+}
+
+/// Expire trust as deadlines pass and hand the resulting notifications to
+/// `events`. Runs until the receiver is dropped.
+pub async fn watch_trust_expiry(
+    session_mgr: Arc<SessionManager>,
+    events: mpsc::UnboundedSender<SessionState>,
+) {
+    let mut interval = tokio::time::interval(TRUST_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        for state in session_mgr.expire_trust().await {
+            if events.send(state).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Run [`SessionManager::flush`] every 30 seconds. Runs until the process
+/// exits.
+pub async fn flush_sessions(session_mgr: Arc<SessionManager>) {
+    let mut interval = tokio::time::interval(SESSIONS_FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        session_mgr.flush().await;
+    }
+}
+
+/// Run [`SessionManager::sweep_idle`] hourly with `max_idle`.
+pub async fn sweep_idle_sessions(session_mgr: Arc<SessionManager>, max_idle: Duration) {
+    let mut interval = tokio::time::interval(IDLE_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let removed = session_mgr.sweep_idle(max_idle).await;
+        if !removed.is_empty() {
+            info!(callers = ?removed, "dropped idle inactive sessions");
+        }
+    }
+}
+
+/// Push trust notifications from [`watch_trust_expiry`] onto the approval
+/// broadcast channel, which reaches IPC clients and the cloud connection.
+pub async fn forward_session_events(
+    mut events: mpsc::UnboundedReceiver<SessionState>,
+    device_id: String,
+    broadcast_tx: broadcast::Sender<Envelope>,
+) {
+    while let Some(state) = events.recv().await {
+        broadcast_session_state(&broadcast_tx, &device_id, state, ORIGIN_DAEMON);
+    }
+}
+
+/// Announce a session change to every connected surface, tagged with the
+/// surface it came from.
+pub fn broadcast_session_state(
+    broadcast_tx: &broadcast::Sender<Envelope>,
+    device_id: &str,
+    mut state: SessionState,
+    origin: &str,
+) {
+    state.origin = origin.to_string();
+    let _ = broadcast_tx.send(Envelope {
+        device_id: device_id.to_string(),
+        msg_id: format!("session-{}-{}", state.caller_uid, now_ms()),
+        ts_ms: now_ms(),
+        payload: Some(envelope::Payload::SessionState(state)),
+        ..Default::default()
+    });
+}
+
+/// Append `entry` to `caller_uid`'s log, evicting the oldest beyond `max`.
+fn push_capped<T>(log: &mut HashMap<String, Vec<T>>, caller_uid: &str, entry: T, max: usize) {
+    let entries = log.entry(caller_uid.to_string()).or_default();
+    entries.push(entry);
+    if entries.len() > max {
+        let excess = entries.len() - max;
+        entries.drain(..excess);
     }
 }
 
@@ -275,3 +823,519 @@ fn now_ms() -> u64 {
         .unwrap()
         .as_millis() as u64
 }
+
+fn load_sessions(path: &Path) -> HashMap<String, CallerSession> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "failed to read session modes");
+            return HashMap::new();
+        }
+    };
+    let persisted: BTreeMap<String, PersistedSession> = match serde_json::from_slice(&data) {
+        Ok(persisted) => persisted,
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "ignoring malformed session modes");
+            return HashMap::new();
+        }
+    };
+
+    let now = now_ms();
+    persisted
+        .into_iter()
+        .map(|(caller_uid, p)| {
+            let mode = SessionMode::from_str_name(&p.mode).unwrap_or(SessionMode::Inactive);
+            let mut session = CallerSession::new(mode, None, p.trust_timeout_mins);
+            if p.last_active_ms > 0 {
+                let idle = Duration::from_millis(now.saturating_sub(p.last_active_ms));
+                if let Some(last_active) = Instant::now().checked_sub(idle) {
+                    session.last_active = last_active;
+                }
+            }
+            if mode == SessionMode::Trust {
+                session.trust_jobs_remaining = p.trust_jobs_remaining;
+                session.revert_mode = p
+                    .revert_mode
+                    .as_deref()
+                    .and_then(SessionMode::from_str_name)
+                    .unwrap_or(SessionMode::Inactive);
+                if p.trust_expires_ms > now {
+                    let remaining = Duration::from_millis(p.trust_expires_ms - now);
+                    session.trust_expires = Some(Instant::now() + remaining);
+                } else {
+                    info!(caller_uid, "trust expired while stopped, reverting");
+                    session.end_trust();
+                }
+            }
+            (caller_uid, session)
+        })
+        .collect()
+}
+
+/// `sessions` captured by [`SessionManager::snapshot`], ready to write.
+struct SessionsSnapshot {
+    path: PathBuf,
+    number: u64,
+    data: serde_json::Result<Vec<u8>>,
+    saved: Arc<std::sync::Mutex<u64>>,
+}
+
+impl SessionsSnapshot {
+    /// Write `sessions.json` via a temp file and rename so a crash never
+    /// leaves it half-written. Skipped if a newer snapshot was written
+    /// meanwhile.
+    fn save(self) {
+        let mut saved = self.saved.lock().unwrap();
+        if self.number <= *saved {
+            return;
+        }
+        let result = self.data.map_err(std::io::Error::other).and_then(|data| {
+            let tmp = self.path.with_extension("json.tmp");
+            std::fs::write(&tmp, data)?;
+            std::fs::rename(&tmp, &self.path)
+        });
+        match result {
+            Ok(()) => *saved = self.number,
+            Err(e) => {
+                warn!(path = %self.path.display(), error = %e, "failed to persist session modes")
+            }
+        }
+    }
+}
+
+/// `sessions` as the contents of `sessions.json`.
+fn encode_sessions(sessions: &HashMap<String, CallerSession>) -> serde_json::Result<Vec<u8>> {
+    let now = Instant::now();
+    let persisted: BTreeMap<&str, PersistedSession> = sessions
+        .iter()
+        .map(|(caller_uid, session)| {
+            let trust_expires_ms = session
+                .trust_expires
+                .map(|exp| now_ms() + exp.saturating_duration_since(now).as_millis() as u64)
+                .unwrap_or(0);
+            let persisted = PersistedSession {
+                mode: session.mode.as_str_name().to_string(),
+                trust_expires_ms,
+                trust_timeout_mins: session.trust_timeout_mins,
+                trust_jobs_remaining: session.trust_jobs_remaining,
+                revert_mode: session
+                    .trust_jobs_remaining
+                    .map(|_| session.revert_mode.as_str_name().to_string()),
+                last_active_ms: now_ms().saturating_sub(
+                    now.saturating_duration_since(session.last_active)
+                        .as_millis() as u64,
+                ),
+            };
+            (caller_uid.as_str(), persisted)
+        })
+        .collect();
+    serde_json::to_vec_pretty(&persisted)
+}
+
+/// Config name of `mode`, as accepted by `default_session_mode`.
+pub(crate) fn mode_name(mode: SessionMode) -> &'static str {
+    match mode {
+        SessionMode::AutoAccept => "auto_accept",
+        SessionMode::Trust => "trust",
+        SessionMode::Strict => "strict",
+        SessionMode::Inactive => "inactive",
+        SessionMode::ReadOnly => "read_only",
+    }
+}
+
+/// Audit `rule` label for a session decision.
+pub(crate) fn mode_rule(mode: SessionMode) -> &'static str {
+    match mode {
+        SessionMode::AutoAccept => "mode:auto_accept",
+        SessionMode::Trust => "mode:trust",
+        SessionMode::Strict => "mode:strict",
+        SessionMode::Inactive => "mode:inactive",
+        SessionMode::ReadOnly => "mode:read_only",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_name_round_trips_known_values() {
+        assert_eq!(mode_name(SessionMode::AutoAccept), "auto_accept");
+        assert_eq!(mode_name(SessionMode::Trust), "trust");
+        assert_eq!(mode_name(SessionMode::Strict), "strict");
+        assert_eq!(mode_name(SessionMode::Inactive), "inactive");
+        assert_eq!(mode_name(SessionMode::ReadOnly), "read_only");
+    }
+
+    #[tokio::test]
+    async fn trust_round_trips_with_remaining_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mgr = SessionManager::new(60).with_sessions_file(path.clone());
+        mgr.set_mode("uid:501", SessionMode::Trust, 30).await;
+
+        let restarted = SessionManager::new(60).with_sessions_file(path);
+        let state = restarted.get_session_state("uid:501").await;
+        assert_eq!(state.mode, SessionMode::Trust as i32);
+        assert_eq!(state.trust_timeout_mins, 30);
+        let remaining_ms = state.trust_expires_ms.saturating_sub(now_ms());
+        assert!(
+            (29 * 60_000..=30 * 60_000).contains(&remaining_ms),
+            "{remaining_ms}"
+        );
+    }
+
+    /// Trusted jobs only mark the activity unsaved; `flush` writes it,
+    /// while the mode change at the end of the budget is written at once.
+    #[tokio::test]
+    async fn trust_activity_is_saved_on_flush_and_mode_changes_at_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mgr = SessionManager::new(60).with_sessions_file(path.clone());
+        mgr.set_mode("uid:501", SessionMode::Strict, 0).await;
+        mgr.set_mode_with_budget("uid:501", SessionMode::Trust, 30, 3)
+            .await;
+        let saved_budget = || {
+            let saved: BTreeMap<String, serde_json::Value> =
+                serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            saved["uid:501"]["trust_jobs_remaining"].clone()
+        };
+        let req = JobRequest::default();
+
+        assert!(matches!(
+            mgr.check(&req, "uid:501").await,
+            SessionDecision::Allow
+        ));
+        assert_eq!(saved_budget(), 3);
+        mgr.flush().await;
+        assert_eq!(saved_budget(), 2);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                mgr.check(&req, "uid:501").await,
+                SessionDecision::Allow
+            ));
+        }
+        let restarted = SessionManager::new(60).with_sessions_file(path.clone());
+        assert_eq!(
+            restarted.get_session_state("uid:501").await.mode,
+            SessionMode::Strict as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn auto_accept_survives_restart_and_expired_trust_does_not() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mgr = SessionManager::new(60).with_sessions_file(path.clone());
+        mgr.set_mode("uid:501", SessionMode::AutoAccept, 0).await;
+        mgr.set_mode("uid:502", SessionMode::Trust, 30).await;
+
+        // Pretend uid:502's trust ran out while the daemon was down.
+        let mut saved: BTreeMap<String, serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        saved.get_mut("uid:502").unwrap()["trust_expires_ms"] = (now_ms() - 1000).into();
+        std::fs::write(&path, serde_json::to_vec(&saved).unwrap()).unwrap();
+
+        let restarted = SessionManager::new(60).with_sessions_file(path);
+        restarted.register_caller("uid:501").await;
+        let req = JobRequest {
+            tool: "ls".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            restarted.check(&req, "uid:501").await,
+            SessionDecision::Allow
+        ));
+        assert_eq!(
+            restarted.get_session_state("uid:502").await.mode,
+            SessionMode::Inactive as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn expire_trust_warns_once_then_expires() {
+        let mgr = SessionManager::new(60);
+        mgr.set_mode("uid:501", SessionMode::Trust, 3).await;
+        mgr.set_mode("uid:502", SessionMode::Trust, 60).await;
+
+        let events = mgr.expire_trust().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].caller_uid, "uid:501");
+        assert!(events[0].expiry_warning);
+        assert!(mgr.expire_trust().await.is_empty());
+
+        mgr.sessions
+            .lock()
+            .await
+            .get_mut("uid:501")
+            .unwrap()
+            .trust_expires = Some(Instant::now() - Duration::from_secs(1));
+        let events = mgr.expire_trust().await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mode, SessionMode::Inactive as i32);
+        assert!(!events[0].expiry_warning);
+        assert_eq!(
+            mgr.get_session_state("uid:501").await.mode,
+            SessionMode::Inactive as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn refusals_are_scoped_to_the_caller() {
+        let mgr = SessionManager::new(60);
+        mgr.record_refusal("uid:501", "rm", "not in my home dir")
+            .await;
+        mgr.record_refusal("uid:502", "curl", "no network").await;
+
+        let refusals = mgr.get_refusals("uid:501", "rm").await;
+        assert_eq!(refusals.len(), 1);
+        assert_eq!(refusals[0].reason, "not in my home dir");
+        assert!(mgr.get_refusals("uid:502", "rm").await.is_empty());
+        assert!(mgr.get_refusals("uid:501", "curl").await.is_empty());
+
+        mgr.set_mode("uid:502", SessionMode::Strict, 0).await;
+        let req = JobRequest {
+            tool: "rm".to_string(),
+            ..Default::default()
+        };
+        match mgr.check(&req, "uid:502").await {
+            SessionDecision::NeedsApproval {
+                previous_refusals, ..
+            } => assert!(previous_refusals.is_empty()),
+            _ => panic!("strict mode should need approval"),
+        }
+    }
+
+    #[tokio::test]
+    async fn refusal_log_evicts_oldest_beyond_cap() {
+        let mgr = SessionManager::new(60).with_max_refusals_per_caller(2);
+        for reason in ["first", "second", "third"] {
+            mgr.record_refusal("uid:501", "rm", reason).await;
+        }
+        mgr.record_refusal("uid:502", "rm", "other caller").await;
+
+        let reasons: Vec<_> = mgr
+            .get_refusals("uid:501", "rm")
+            .await
+            .into_iter()
+            .map(|r| r.reason)
+            .collect();
+        assert_eq!(reasons, ["second", "third"]);
+        assert_eq!(mgr.get_refusals("uid:502", "rm").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_limits_git_to_listed_subcommands() {
+        let mgr = SessionManager::new(60);
+        mgr.set_mode("uid:501", SessionMode::ReadOnly, 0).await;
+        let job = |tool: &str, args: &[&str]| JobRequest {
+            tool: tool.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+
+        for req in [
+            job("ls", &["-la"]),
+            job("git", &["status"]),
+            job("git", &["log", "--oneline"]),
+        ] {
+            assert!(
+                matches!(mgr.check(&req, "uid:501").await, SessionDecision::Allow),
+                "{} {:?}",
+                req.tool,
+                req.args
+            );
+        }
+        for req in [
+            job("git", &[]),
+            job("git", &["push"]),
+            job("git", &["-C", "/tmp", "status"]),
+            job("rm", &["-rf", "build"]),
+        ] {
+            match mgr.check(&req, "uid:501").await {
+                SessionDecision::NeedsApproval { reason, .. } => {
+                    assert_eq!(reason, "read-only session")
+                }
+                _ => panic!("{} {:?} should need approval", req.tool, req.args),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn read_only_tools_are_configurable() {
+        let mgr = SessionManager::new(60).with_read_only_tools(vec!["cargo tree".to_string()]);
+        mgr.set_mode("uid:501", SessionMode::ReadOnly, 0).await;
+        let req = |args: &[&str]| JobRequest {
+            tool: "cargo".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            mgr.check(&req(&["tree"]), "uid:501").await,
+            SessionDecision::Allow
+        ));
+        assert!(matches!(
+            mgr.check(&req(&["build"]), "uid:501").await,
+            SessionDecision::NeedsApproval { .. }
+        ));
+    }
+
+    /// Strict caller trusted for `budget` jobs, with state events captured.
+    async fn budgeted_trust(
+        budget: u32,
+    ) -> (SessionManager, mpsc::UnboundedReceiver<SessionState>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mgr = SessionManager::new(60).with_state_events(tx);
+        mgr.set_mode("uid:501", SessionMode::Strict, 0).await;
+        let state = mgr
+            .set_mode_with_budget("uid:501", SessionMode::Trust, 0, budget)
+            .await;
+        assert_eq!(state.trust_jobs_remaining, budget);
+        (mgr, rx)
+    }
+
+    #[tokio::test]
+    async fn trust_budget_runs_out_before_timer() {
+        let (mgr, mut events) = budgeted_trust(2).await;
+        let req = JobRequest::default();
+
+        assert!(matches!(
+            mgr.check(&req, "uid:501").await,
+            SessionDecision::Allow
+        ));
+        assert_eq!(
+            mgr.get_session_state("uid:501").await.trust_jobs_remaining,
+            1
+        );
+        assert!(events.try_recv().is_err());
+
+        assert!(matches!(
+            mgr.check(&req, "uid:501").await,
+            SessionDecision::Allow
+        ));
+        let state = events.try_recv().unwrap();
+        assert_eq!(state.mode, SessionMode::Strict as i32);
+        assert_eq!(state.trust_jobs_remaining, 0);
+        assert_eq!(state.trust_expires_ms, 0);
+
+        assert!(matches!(
+            mgr.check(&req, "uid:501").await,
+            SessionDecision::NeedsApproval { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn trust_timer_runs_out_before_budget() {
+        let (mgr, mut events) = budgeted_trust(5).await;
+        let req = JobRequest::default();
+        assert!(matches!(
+            mgr.check(&req, "uid:501").await,
+            SessionDecision::Allow
+        ));
+
+        mgr.sessions
+            .lock()
+            .await
+            .get_mut("uid:501")
+            .unwrap()
+            .trust_expires = Some(Instant::now() - Duration::from_secs(1));
+        match mgr.check(&req, "uid:501").await {
+            SessionDecision::Deny(reason) => assert_eq!(reason, "trust expired"),
+            _ => panic!("expired trust should deny"),
+        }
+        let state = events.try_recv().unwrap();
+        assert_eq!(state.mode, SessionMode::Strict as i32);
+        assert_eq!(state.trust_jobs_remaining, 0);
+        assert_eq!(
+            mgr.get_session_state("uid:501").await.mode,
+            SessionMode::Strict as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn clear_session_forgets_mode_and_refusals() {
+        let mgr = SessionManager::new(60);
+        mgr.set_mode("ctl-42", SessionMode::Strict, 0).await;
+        mgr.set_mode("uid:501", SessionMode::Strict, 0).await;
+        mgr.record_refusal("ctl-42", "rm", "no").await;
+
+        assert!(mgr.clear_session("ctl-42").await);
+        assert!(!mgr.clear_session("ctl-42").await);
+        assert!(mgr.get_refusals("ctl-42", "rm").await.is_empty());
+        let callers: Vec<_> = mgr
+            .query_sessions("")
+            .await
+            .into_iter()
+            .map(|s| s.caller_uid)
+            .collect();
+        assert_eq!(callers, ["uid:501"]);
+    }
+
+    #[tokio::test]
+    async fn sweep_idle_drops_only_inactive_sessions() {
+        let mgr = SessionManager::new(60);
+        for caller in ["ctl-2", "ctl-1", "uid:501"] {
+            mgr.register_caller(caller).await;
+        }
+        mgr.set_mode("uid:501", SessionMode::Strict, 0).await;
+        mgr.record_refusal("ctl-1", "rm", "no").await;
+
+        let callers: Vec<_> = mgr
+            .query_sessions("")
+            .await
+            .into_iter()
+            .map(|s| s.caller_uid)
+            .collect();
+        assert_eq!(callers, ["ctl-1", "ctl-2", "uid:501"]);
+
+        assert!(mgr.sweep_idle(Duration::from_secs(3600)).await.is_empty());
+        let mut removed = mgr.sweep_idle(Duration::ZERO).await;
+        removed.sort();
+        assert_eq!(removed, ["ctl-1", "ctl-2"]);
+        assert!(mgr.get_refusals("ctl-1", "rm").await.is_empty());
+        assert_eq!(mgr.query_sessions("").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn approval_context_mixes_approvals_and_refusals_per_tool() {
+        let mgr = SessionManager::new(60);
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        mgr.record_approval("uid:501", "curl", &args(&["api.stripe.com"]))
+            .await;
+        mgr.record_refusal("uid:501", "curl", "not that host").await;
+        mgr.record_approval("uid:501", "curl", &args(&["api.github.com"]))
+            .await;
+        mgr.record_approval("uid:501", "git", &args(&["push"]))
+            .await;
+        mgr.record_approval("uid:502", "curl", &args(&["example.com"]))
+            .await;
+
+        mgr.set_mode("uid:501", SessionMode::Strict, 0).await;
+        let req = JobRequest {
+            tool: "curl".to_string(),
+            ..Default::default()
+        };
+        match mgr.check(&req, "uid:501").await {
+            SessionDecision::NeedsApproval {
+                previous_refusals,
+                previous_approvals,
+                ..
+            } => {
+                assert_eq!(previous_refusals.len(), 1);
+                assert_eq!(previous_refusals[0].reason, "not that host");
+                let approved: Vec<_> = previous_approvals
+                    .iter()
+                    .map(|a| a.args.join(" "))
+                    .collect();
+                assert_eq!(approved, ["api.stripe.com", "api.github.com"]);
+            }
+            _ => panic!("strict mode should need approval"),
+        }
+
+        mgr.clear_session("uid:501").await;
+        assert!(mgr.get_approvals("uid:501", "curl").await.is_empty());
+        assert_eq!(mgr.get_approvals("uid:502", "curl").await.len(), 1);
+    }
+}
@@ -1,11 +1,21 @@
+use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError, SyncSender, TrySendError};
+use std::time::{Duration, Instant, SystemTime};
 
-use ahand_protocol::{Envelope, JobRequest};
+use ahand_protocol::{Envelope, JobRequest, RunsGcStats};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::Mutex;
-use tracing::warn;
+use tracing::{debug, info, warn};
+
+use crate::redact::EnvRedactor;
+use crate::registry::JobRegistry;
+use crate::run_index::{BROWSER_RUNS_DIR, RunIndex, file_len};
 
 /// Direction of an envelope (for trace logging).
 #[derive(Clone, Copy)]
@@ -23,31 +33,423 @@ impl Direction {
     }
 }
 
+pub const TRACE_FILE_NAME: &str = "trace.jsonl";
+
+/// Directory under the data dir that [`export_run`] writes tarballs to.
+pub const EXPORTS_DIR_NAME: &str = "exports";
+
+/// Longest trace.jsonl line; larger payload bodies are cut to fit.
+const TRACE_LINE_MAX_BYTES: usize = 8 * 1024;
+
+/// How much of each payload a trace line carries in its `body`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TracePayloads {
+    /// Payload type only.
+    #[default]
+    None,
+    /// Key fields per payload type (see [`summarize_payload`]).
+    Summary,
+    /// The whole payload as JSON, env values redacted.
+    Full,
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeKind {
+    /// The process exited on its own (any exit code) or failed to start.
+    #[default]
+    Finished,
+    Cancelled,
+    Timeout,
+    /// Never started: refused by the session mode or the approver.
+    Rejected,
+}
+
+/// How a run ended and the approval/session context it ran under, stored
+/// as `outcome` in result.json.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOutcome {
+    pub kind: OutcomeKind,
+    /// Principal that approved the job, when it needed approval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approved_by: Option<String>,
+    /// Time from the approval request to the answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_latency_ms: Option<u64>,
+    /// The caller's session mode when the job was admitted or rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejection_reason: Option<String>,
+}
+
+/// Who submitted a run and how it was admitted. Handed to the executor so
+/// the run's outcome can be recorded with it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunContext {
+    pub caller_uid: String,
+    pub session_mode: Option<String>,
+    pub approved_by: Option<String>,
+    pub approval_latency_ms: Option<u64>,
+}
+
+impl RunContext {
+    pub fn new(caller_uid: impl Into<String>) -> Self {
+        Self {
+            caller_uid: caller_uid.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The outcome of a run in this context that ended as `kind`.
+    pub fn outcome(&self, kind: OutcomeKind) -> RunOutcome {
+        RunOutcome {
+            kind,
+            approved_by: self.approved_by.clone(),
+            approval_latency_ms: self.approval_latency_ms,
+            session_mode: self.session_mode.clone(),
+            rejection_reason: None,
+        }
+    }
+
+    /// The outcome of a run in this context rejected for `reason`.
+    pub fn rejected(&self, reason: &str) -> RunOutcome {
+        RunOutcome {
+            rejection_reason: Some(reason.to_string()),
+            ..self.outcome(OutcomeKind::Rejected)
+        }
+    }
+}
+
+/// How often [`sweep_runs`] applies the runs retention policy.
+const RUNS_GC_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Output chunks queued for the writer thread. When the queue is full (the
+/// disk can't keep up) further chunks are dropped from the run's files and
+/// counted in its result.json, rather than stalling delivery of the output
+/// to the caller.
+const OUTPUT_QUEUE_CAPACITY: usize = 4096;
+
+/// The writer thread closes output files that haven't been written for
+/// this long (e.g. a job killed before `finish_run`).
+const OUTPUT_IDLE_CLOSE: Duration = Duration::from_secs(30);
+
+/// Per-stream byte budget for a run's persisted output.
+struct OutputBudget {
+    /// 0 = unlimited.
+    cap: u64,
+    stdout: StreamBudget,
+    stderr: StreamBudget,
+    /// Chunks dropped because the writer queue was full.
+    dropped_chunks: u64,
+}
+
+#[derive(Default)]
+struct StreamBudget {
+    written: u64,
+    truncated: bool,
+}
+
+impl OutputBudget {
+    fn new(cap: u64) -> Self {
+        Self {
+            cap,
+            stdout: StreamBudget::default(),
+            stderr: StreamBudget::default(),
+            dropped_chunks: 0,
+        }
+    }
+
+    /// The part of `chunk` to persist to stream `name`, ending in the
+    /// truncation marker when it crosses the cap. `None` once the stream
+    /// has been truncated.
+    fn admit(&mut self, name: &str, chunk: &[u8]) -> Option<Vec<u8>> {
+        let cap = self.cap;
+        let stream = if name == "stderr" {
+            &mut self.stderr
+        } else {
+            &mut self.stdout
+        };
+        if stream.truncated {
+            return None;
+        }
+        let len = chunk.len() as u64;
+        if cap == 0 || stream.written + len <= cap {
+            stream.written += len;
+            return Some(chunk.to_vec());
+        }
+        let keep = (cap - stream.written) as usize;
+        stream.written = cap;
+        stream.truncated = true;
+        let mut admitted = chunk[..keep].to_vec();
+        admitted.extend_from_slice(format!("\n--- truncated at {cap} bytes ---\n").as_bytes());
+        Some(admitted)
+    }
+}
+
+struct TraceFile {
+    writer: BufWriter<File>,
+    len: u64,
+}
+
 /// Persists trace logs and per-job run artifacts to disk.
+///
+/// Past `trace_max_bytes` the trace rotates to `trace.jsonl.1`, older
+/// archives shift up and anything beyond `trace_keep_files` is deleted.
+/// Finished run directories are pruned by [`RunStore::gc_runs`]. Every run
+/// is also recorded in the [`RunIndex`] for listing and lookup.
+///
+/// Job output goes through a dedicated writer thread that keeps each run's
+/// files open and batches writes, so appending a chunk is only a queue send.
 pub struct RunStore {
     data_dir: PathBuf,
-    trace_file: Mutex<BufWriter<File>>,
+    index: RunIndex,
+    output_tx: SyncSender<OutputOp>,
+    dropped_output_chunks: AtomicU64,
+    run_output_cap_bytes: u64,
+    output_budgets: std::sync::Mutex<HashMap<String, OutputBudget>>,
+    trace_path: PathBuf,
+    trace_max_bytes: u64,
+    trace_keep_files: u32,
+    trace_file: Mutex<TraceFile>,
+    trace_payloads: TracePayloads,
+    runs_max_age: Duration,
+    runs_max_total_bytes: u64,
+    last_runs_gc: Mutex<Option<RunsGcStats>>,
+    compress_outputs_over_bytes: u64,
+    env_redactor: EnvRedactor,
+    browser_downloads: Option<PathBuf>,
 }
 
 impl RunStore {
-    /// Create or open the store at the given directory.
-    pub fn new(data_dir: &Path) -> anyhow::Result<Self> {
+    /// Create or open the store at the given directory. `trace_max_bytes ==
+    /// 0` disables trace rotation.
+    pub fn new(
+        data_dir: &Path,
+        trace_max_bytes: u64,
+        trace_keep_files: u32,
+    ) -> anyhow::Result<Self> {
+        Self::with_output_writer(
+            data_dir,
+            trace_max_bytes,
+            trace_keep_files,
+            Duration::ZERO,
+            OUTPUT_QUEUE_CAPACITY,
+        )
+    }
+
+    /// [`RunStore::new`] with a writer thread that sleeps `write_delay`
+    /// before each write and a queue of `queue_capacity` chunks, standing
+    /// in for a slow disk in tests.
+    fn with_output_writer(
+        data_dir: &Path,
+        trace_max_bytes: u64,
+        trace_keep_files: u32,
+        write_delay: Duration,
+        queue_capacity: usize,
+    ) -> anyhow::Result<Self> {
         fs::create_dir_all(data_dir)?;
         fs::create_dir_all(data_dir.join("runs"))?;
 
-        let trace_path = data_dir.join("trace.jsonl");
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&trace_path)?;
+        let trace_path = data_dir.join(TRACE_FILE_NAME);
+        let file = open_append(&trace_path)?;
+        let len = file.metadata()?.len();
+        let index = RunIndex::open(data_dir)?;
+
+        let (output_tx, output_rx) = std_mpsc::sync_channel(queue_capacity);
+        let runs_dir = data_dir.join("runs");
+        std::thread::Builder::new()
+            .name("run-output-writer".to_string())
+            .spawn(move || write_outputs(&runs_dir, output_rx, write_delay))?;
 
         Ok(Self {
             data_dir: data_dir.to_path_buf(),
-            trace_file: Mutex::new(BufWriter::new(file)),
+            index,
+            output_tx,
+            dropped_output_chunks: AtomicU64::new(0),
+            run_output_cap_bytes: 0,
+            output_budgets: std::sync::Mutex::new(HashMap::new()),
+            trace_path,
+            trace_max_bytes,
+            trace_keep_files,
+            trace_file: Mutex::new(TraceFile {
+                writer: BufWriter::new(file),
+                len,
+            }),
+            trace_payloads: TracePayloads::None,
+            runs_max_age: Duration::ZERO,
+            runs_max_total_bytes: 0,
+            last_runs_gc: Mutex::new(None),
+            compress_outputs_over_bytes: 0,
+            env_redactor: EnvRedactor::default(),
+            browser_downloads: None,
         })
     }
 
-    /// Append an envelope record to trace.jsonl.
+    /// Let [`RunStore::gc_runs`] delete finished runs older than `max_age`
+    /// and, oldest first, while `runs/` is larger than `max_total_bytes`.
+    /// A zero value disables that limit.
+    pub fn with_runs_retention(mut self, max_age: Duration, max_total_bytes: u64) -> Self {
+        self.runs_max_age = max_age;
+        self.runs_max_total_bytes = max_total_bytes;
+        self
+    }
+
+    /// Gzip a finished run's `stdout`/`stderr` into `<name>.gz` once larger
+    /// than `threshold` bytes. 0 keeps outputs uncompressed.
+    pub fn with_output_compression(mut self, threshold: u64) -> Self {
+        self.compress_outputs_over_bytes = threshold;
+        self
+    }
+
+    /// Stop persisting each of a run's output streams past `cap` bytes,
+    /// ending the file with a truncation marker. A [`JobRequest`] can ask
+    /// for a lower cap but not a higher one. 0 disables the cap.
+    pub fn with_run_output_cap(mut self, cap: u64) -> Self {
+        self.run_output_cap_bytes = cap;
+        self
+    }
+
+    /// Redact env values in request.json with `redactor` instead of the
+    /// default patterns.
+    pub fn with_env_redaction(mut self, redactor: EnvRedactor) -> Self {
+        self.env_redactor = redactor;
+        self
+    }
+
+    /// Let [`RunStore::gc_runs`] delete the files browser artifact records
+    /// point to under `root`, and stray files there older than the runs
+    /// retention age.
+    pub fn with_browser_downloads(mut self, root: PathBuf) -> Self {
+        self.browser_downloads = Some(root);
+        self
+    }
+
+    /// Record payload bodies in trace.jsonl at `level` of detail.
+    pub fn with_trace_payloads(mut self, level: TracePayloads) -> Self {
+        self.trace_payloads = level;
+        self
+    }
+
+    /// Pack a run into a tarball for sharing. See [`export_run`].
+    // The daemon itself doesn't export runs; tools reading the data dir
+    // call the free function.
+    #[allow(dead_code)]
+    pub fn export_run(&self, job_id: &str) -> std::io::Result<PathBuf> {
+        export_run(&self.data_dir, job_id, &self.env_redactor)
+    }
+
+    /// Delete the oldest finished run directories until both retention
+    /// limits hold. Runs for jobs still in `registry` are never touched,
+    /// though their size counts towards the cap.
+    pub async fn gc_runs(&self, registry: &JobRegistry) -> RunsGcStats {
+        let runs_dir = self.data_dir.join("runs");
+        let mut runs = Vec::new();
+        let mut retained_bytes = 0;
+        for (job_id, path) in run_dirs(&runs_dir) {
+            let (mut bytes, modified) = match dir_usage(&path) {
+                Ok(usage) => usage,
+                Err(e) => {
+                    warn!(run_dir = %path.display(), error = %e, "failed to size run dir");
+                    continue;
+                }
+            };
+            let artifact = self.browser_artifact(&job_id, &path);
+            bytes += artifact.as_deref().map(file_len).unwrap_or(0);
+            retained_bytes += bytes;
+            if !registry.is_running(&job_id).await {
+                runs.push((modified, bytes, job_id, path, artifact));
+            }
+        }
+        runs.sort_by_key(|(modified, _, _, _, _)| *modified);
+
+        let now = SystemTime::now();
+        let mut stats = RunsGcStats {
+            ran_at_ms: now_ms(),
+            ..Default::default()
+        };
+        let mut deleted = Vec::new();
+        for (modified, bytes, job_id, path, artifact) in runs {
+            let too_old = !self.runs_max_age.is_zero()
+                && now.duration_since(modified).unwrap_or_default() > self.runs_max_age;
+            let over_cap =
+                self.runs_max_total_bytes > 0 && retained_bytes > self.runs_max_total_bytes;
+            if !too_old && !over_cap {
+                // Oldest first: every later run is newer and the cap holds.
+                break;
+            }
+            match fs::remove_dir_all(&path) {
+                Ok(()) => {
+                    if let Some(artifact) = artifact
+                        && let Err(e) = fs::remove_file(&artifact)
+                        && e.kind() != std::io::ErrorKind::NotFound
+                    {
+                        warn!(path = %artifact.display(), error = %e, "failed to delete browser artifact");
+                    }
+                    if job_id.starts_with(BROWSER_RUNS_DIR)
+                        && let Some(session_dir) = path.parent()
+                    {
+                        // Only succeeds once the session's last record is gone.
+                        let _ = fs::remove_dir(session_dir);
+                    }
+                    stats.deleted_runs += 1;
+                    stats.freed_bytes += bytes;
+                    retained_bytes -= bytes;
+                    deleted.push(job_id);
+                }
+                Err(e) => warn!(run_dir = %path.display(), error = %e, "failed to delete run dir"),
+            }
+        }
+        stats.retained_bytes = retained_bytes;
+        if !self.runs_max_age.is_zero()
+            && let Some(root) = &self.browser_downloads
+        {
+            stats.freed_bytes += prune_downloads(root, now - self.runs_max_age);
+        }
+        if let Err(e) = self.index.remove(&deleted) {
+            warn!(error = %e, "failed to drop pruned runs from the index");
+        }
+
+        if stats.deleted_runs > 0 {
+            info!(
+                deleted_runs = stats.deleted_runs,
+                freed_bytes = stats.freed_bytes,
+                retained_bytes = stats.retained_bytes,
+                "pruned run directories"
+            );
+        } else {
+            debug!(retained_bytes, "runs retention pass deleted nothing");
+        }
+        *self.last_runs_gc.lock().await = Some(stats);
+        stats
+    }
+
+    /// The downloaded file a browser record points to, if `job_id` is one
+    /// and the file is under the configured downloads root.
+    fn browser_artifact(&self, job_id: &str, run_dir: &Path) -> Option<PathBuf> {
+        if !job_id.starts_with(BROWSER_RUNS_DIR) {
+            return None;
+        }
+        let root = self.browser_downloads.as_ref()?;
+        let result: serde_json::Value =
+            serde_json::from_slice(&fs::read(run_dir.join("result.json")).ok()?).ok()?;
+        let path = PathBuf::from(result.get("path")?.as_str()?);
+        let safe = path.starts_with(root)
+            && !path
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir));
+        safe.then_some(path)
+    }
+
+    /// Outcome of the most recent [`RunStore::gc_runs`], if any.
+    pub async fn last_runs_gc(&self) -> Option<RunsGcStats> {
+        *self.last_runs_gc.lock().await
+    }
+
+    /// Append an envelope record to trace.jsonl, rotating first if it would
+    /// overflow the size cap.
     pub async fn log_envelope(&self, envelope: &Envelope, direction: Direction) {
         let payload_type = describe_payload(envelope);
         let record = json!({
@@ -59,35 +461,228 @@ impl RunStore {
             "ack": envelope.ack,
             "payload": payload_type,
         });
+        let line = trace_line(record, self.payload_body(envelope));
 
-        let mut file = self.trace_file.lock().await;
-        if let Err(e) = writeln!(file, "{}", record) {
-            warn!(error = %e, "failed to write trace");
+        let mut out = self.trace_file.lock().await;
+        if self.trace_max_bytes > 0
+            && out.len > 0
+            && out.len + line.len() as u64 > self.trace_max_bytes
+            && let Err(e) = self.rotate_trace(&mut out)
+        {
+            warn!(error = %e, "failed to rotate trace");
         }
-        let _ = file.flush();
+        match out.writer.write_all(line.as_bytes()) {
+            Ok(()) => out.len += line.len() as u64,
+            Err(e) => warn!(error = %e, "failed to write trace"),
+        }
+        let _ = out.writer.flush();
+    }
+
+    /// The `body` of an envelope's trace line at the configured detail.
+    fn payload_body(&self, envelope: &Envelope) -> Option<serde_json::Value> {
+        match self.trace_payloads {
+            TracePayloads::None => None,
+            TracePayloads::Summary => summarize_payload(envelope),
+            TracePayloads::Full => {
+                // `{"JobRequest": {...}}` → the message itself.
+                let tagged = serde_json::to_value(envelope.payload.as_ref()?).ok()?;
+                let mut body = tagged.as_object()?.values().next()?.clone();
+                self.env_redactor.redact_fields(&mut body);
+                Some(body)
+            }
+        }
+    }
+
+    /// Flush buffered trace and job output to disk. Called on daemon
+    /// shutdown.
+    pub async fn flush(&self) {
+        let mut out = self.trace_file.lock().await;
+        if let Err(e) = out.writer.flush() {
+            warn!(error = %e, "failed to flush trace");
+        }
+        drop(out);
+
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let output_tx = self.output_tx.clone();
+        let queued = tokio::task::spawn_blocking(move || {
+            output_tx.send(OutputOp::Flush { done: done_tx }).is_ok()
+        })
+        .await;
+        if matches!(queued, Ok(true)) {
+            let _ = done_rx.await;
+        }
+    }
+
+    fn rotate_trace(&self, out: &mut TraceFile) -> std::io::Result<()> {
+        out.writer.flush()?;
+        // Drop the oldest archive, plus any left over from a larger
+        // `trace_keep_files`.
+        let mut n = self.trace_keep_files.max(1);
+        while rotated_path(&self.trace_path, n).exists() {
+            fs::remove_file(rotated_path(&self.trace_path, n))?;
+            n += 1;
+        }
+        for n in (1..self.trace_keep_files).rev() {
+            let from = rotated_path(&self.trace_path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.trace_path, n + 1))?;
+            }
+        }
+        if self.trace_keep_files > 0 {
+            fs::rename(&self.trace_path, rotated_path(&self.trace_path, 1))?;
+        } else {
+            fs::remove_file(&self.trace_path)?;
+        }
+        out.writer = BufWriter::new(open_append(&self.trace_path)?);
+        out.len = 0;
+        Ok(())
     }
 
-    /// Create the run directory and write request.json.
-    pub fn start_run(&self, job_id: &str, req: &JobRequest) {
+    /// Create the run directory, write request.json and index the run.
+    pub fn start_run(&self, job_id: &str, caller_uid: &str, req: &JobRequest) {
+        let cap = match (self.run_output_cap_bytes, req.output_cap_bytes) {
+            (configured, 0) => configured,
+            (0, requested) => requested,
+            (configured, requested) => configured.min(requested),
+        };
+        self.output_budgets
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), OutputBudget::new(cap));
+
         let run_dir = self.data_dir.join("runs").join(job_id);
         if let Err(e) = fs::create_dir_all(&run_dir) {
             warn!(job_id = %job_id, error = %e, "failed to create run dir");
             return;
         }
 
+        let start_ms = now_ms();
         let request = json!({
             "job_id": req.job_id,
+            "caller_uid": caller_uid,
             "tool": req.tool,
             "args": req.args,
             "cwd": req.cwd,
-            "env": req.env,
+            "env": self.env_redactor.redact_env(&req.env),
             "timeout_ms": req.timeout_ms,
-            "start_ms": now_ms(),
+            "start_ms": start_ms,
         });
 
         if let Err(e) = write_json(&run_dir.join("request.json"), &request) {
             warn!(job_id = %job_id, error = %e, "failed to write request.json");
         }
+        if let Err(e) = self
+            .index
+            .insert_started(job_id, &req.tool, caller_uid, start_ms)
+        {
+            warn!(job_id = %job_id, error = %e, "failed to index run");
+        }
+    }
+
+    /// Record a file a browser command left in its session's downloads
+    /// directory as a `browser` run under `runs/browser/<session_id>/`, so it
+    /// is listed alongside exec runs and pruned with them. Secret-looking
+    /// fields of `params_json` are redacted.
+    pub fn record_browser_artifact(
+        &self,
+        session_id: &str,
+        action: &str,
+        params_json: &str,
+        caller_uid: &str,
+        path: &Path,
+        mime: &str,
+    ) {
+        self.record_browser_run(
+            session_id,
+            action,
+            params_json,
+            caller_uid,
+            "",
+            Some((path, mime)),
+        );
+    }
+
+    /// Record a browser action as a `browser` run, like
+    /// [`RunStore::record_browser_artifact`] but also for actions that left
+    /// no file or failed with `error`.
+    pub fn record_browser_run(
+        &self,
+        session_id: &str,
+        action: &str,
+        params_json: &str,
+        caller_uid: &str,
+        error: &str,
+        artifact: Option<(&Path, &str)>,
+    ) {
+        if !is_valid_job_id(session_id) || !is_valid_job_id(action) {
+            warn!(
+                session_id,
+                action, "not recording browser run with an unsafe name"
+            );
+            return;
+        }
+        let recorded_ms = now_ms();
+        let session_dir = self
+            .data_dir
+            .join("runs")
+            .join(BROWSER_RUNS_DIR)
+            .join(session_id);
+        if let Err(e) = fs::create_dir_all(&session_dir) {
+            warn!(session_id, error = %e, "failed to create browser session dir");
+            return;
+        }
+        let mut entry = format!("{recorded_ms}_{action}");
+        let mut attempt = 1;
+        let run_dir = loop {
+            let dir = session_dir.join(&entry);
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    attempt += 1;
+                    entry = format!("{recorded_ms}_{action}_{attempt}");
+                }
+                Err(e) => {
+                    warn!(session_id, error = %e, "failed to create browser run dir");
+                    return;
+                }
+            }
+        };
+        let job_id = format!("{BROWSER_RUNS_DIR}/{session_id}/{entry}");
+
+        let mut params = serde_json::from_str(params_json).unwrap_or_else(|_| json!(params_json));
+        self.env_redactor.redact_fields(&mut params);
+        let request = json!({
+            "job_id": job_id,
+            "kind": "browser",
+            "caller_uid": caller_uid,
+            "session_id": session_id,
+            "action": action,
+            "params": params,
+            "start_ms": recorded_ms,
+        });
+        let mut result = json!({
+            "job_id": job_id,
+            "end_ms": recorded_ms,
+        });
+        if let Some((path, mime)) = artifact {
+            result["path"] = json!(path);
+            result["size"] = json!(file_len(path));
+            result["mime"] = json!(mime);
+        }
+        if !error.is_empty() {
+            result["error"] = json!(error);
+        }
+        for (name, value) in [("request.json", &request), ("result.json", &result)] {
+            if let Err(e) = write_json(&run_dir.join(name), value) {
+                warn!(job_id = %job_id, error = %e, "failed to write browser run {name}");
+            }
+        }
+        if let Err(e) =
+            self.index
+                .insert_browser_run(&job_id, action, caller_uid, recorded_ms, error)
+        {
+            warn!(job_id = %job_id, error = %e, "failed to index browser run");
+        }
     }
 
     /// Append a chunk to the stdout file for a run.
@@ -100,33 +695,374 @@ impl RunStore {
         self.append_to_file(job_id, "stderr", chunk);
     }
 
-    /// Write the final result.json for a completed run.
-    pub fn finish_run(&self, job_id: &str, exit_code: i32, error: &str) {
+    /// The stored stdout or stderr (`name`) of a run, empty if the run has
+    /// none.
+    pub fn read_output(&self, job_id: &str, name: &str) -> Vec<u8> {
+        if !is_valid_job_id(job_id) {
+            return Vec::new();
+        }
+        read_run_output(&self.data_dir.join("runs").join(job_id), name).unwrap_or_default()
+    }
+
+    /// Record a job that was refused before it started: request.json plus
+    /// a result.json whose `outcome` says why. Indexed like any other run.
+    pub async fn reject_run(&self, req: &JobRequest, caller_uid: &str, outcome: &RunOutcome) {
+        let reason = outcome.rejection_reason.as_deref().unwrap_or_default();
+        self.start_run(&req.job_id, caller_uid, req);
+        self.finish_run(&req.job_id, -1, reason, 0, 0, outcome)
+            .await;
+    }
+
+    /// Flush and close the run's output files, then write the final
+    /// result.json.
+    pub async fn finish_run(
+        &self,
+        job_id: &str,
+        exit_code: i32,
+        error: &str,
+        duration_ms: u64,
+        queued_ms: u64,
+        outcome: &RunOutcome,
+    ) {
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let close = OutputOp::Close {
+            job_id: job_id.to_string(),
+            done: done_tx,
+        };
+        let output_tx = self.output_tx.clone();
+        let queued = tokio::task::spawn_blocking(move || output_tx.send(close).is_ok()).await;
+        if matches!(queued, Ok(true)) {
+            let _ = done_rx.await;
+        }
+        let budget = self.output_budgets.lock().unwrap().remove(job_id);
+        let (stdout_truncated, stderr_truncated, dropped_output_chunks) = budget
+            .map(|b| (b.stdout.truncated, b.stderr.truncated, b.dropped_chunks))
+            .unwrap_or_default();
+
         let run_dir = self.data_dir.join("runs").join(job_id);
+        let end_ms = now_ms();
         let result = json!({
             "job_id": job_id,
             "exit_code": exit_code,
             "error": error,
-            "end_ms": now_ms(),
+            "end_ms": end_ms,
+            "duration_ms": duration_ms,
+            "queued_ms": queued_ms,
+            "outcome": outcome,
+            "stdout_truncated": stdout_truncated,
+            "stderr_truncated": stderr_truncated,
+            "dropped_output_chunks": dropped_output_chunks,
         });
 
         if let Err(e) = write_json(&run_dir.join("result.json"), &result) {
             warn!(job_id = %job_id, error = %e, "failed to write result.json");
         }
+        if let Err(e) = self.index.update_finished(
+            job_id,
+            end_ms,
+            exit_code,
+            error,
+            file_len(&run_dir.join("stdout")),
+            file_len(&run_dir.join("stderr")),
+        ) {
+            warn!(job_id = %job_id, error = %e, "failed to index run result");
+        }
+
+        // The executor has drained the output pipes by now, so nothing
+        // appends to stdout/stderr any more.
+        if self.compress_outputs_over_bytes > 0 {
+            let threshold = self.compress_outputs_over_bytes;
+            let job_id = job_id.to_string();
+            let compress = move || {
+                if let Err(e) = compress_outputs(&run_dir, threshold) {
+                    warn!(job_id = %job_id, error = %e, "failed to compress run outputs");
+                }
+            };
+            tokio::task::spawn_blocking(compress);
+        }
+    }
+
+    /// Queue `chunk` for the run's `name` file. Chunks for a run that
+    /// wasn't started or has already finished have no budget and are
+    /// dropped, so a late chunk can't reopen a closed run's files.
+    fn append_to_file(&self, job_id: &str, name: &'static str, chunk: &[u8]) {
+        let chunk = {
+            let mut budgets = self.output_budgets.lock().unwrap();
+            let Some(budget) = budgets.get_mut(job_id) else {
+                debug!(job_id = %job_id, file = name, "dropping output for a run that isn't open");
+                return;
+            };
+            match budget.admit(name, chunk) {
+                Some(chunk) => chunk,
+                None => return,
+            }
+        };
+        let op = OutputOp::Append {
+            job_id: job_id.to_string(),
+            name,
+            chunk,
+        };
+        match self.output_tx.try_send(op) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                if let Some(budget) = self.output_budgets.lock().unwrap().get_mut(job_id) {
+                    budget.dropped_chunks += 1;
+                }
+                let dropped = self.dropped_output_chunks.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped.is_multiple_of(1000) {
+                    warn!(job_id = %job_id, dropped, "run output writer is behind, dropping output chunks");
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!(job_id = %job_id, file = name, "run output writer has stopped");
+            }
+        }
+    }
+}
+
+/// Work for the output writer thread. Ops are handled in order, so chunks
+/// for a job land in the order they were appended.
+enum OutputOp {
+    Append {
+        job_id: String,
+        name: &'static str,
+        chunk: Vec<u8>,
+    },
+    /// Flush and close a job's files, then signal `done`.
+    Close {
+        job_id: String,
+        done: tokio::sync::oneshot::Sender<()>,
+    },
+    /// Flush every open file, then signal `done`.
+    Flush {
+        done: tokio::sync::oneshot::Sender<()>,
+    },
+}
+
+struct OutputFile {
+    writer: BufWriter<File>,
+    last_write: Instant,
+}
+
+/// Body of the output writer thread. Runs until the [`RunStore`] is
+/// dropped. Everything already queued is written before open files are
+/// flushed, so a burst of chunks becomes a few large writes.
+fn write_outputs(runs_dir: &Path, rx: std_mpsc::Receiver<OutputOp>, write_delay: Duration) {
+    let mut files: HashMap<(String, &'static str), OutputFile> = HashMap::new();
+    loop {
+        let op = match rx.recv_timeout(OUTPUT_IDLE_CLOSE) {
+            Ok(op) => op,
+            Err(RecvTimeoutError::Timeout) => {
+                files.retain(|_, file| {
+                    file.last_write.elapsed() < OUTPUT_IDLE_CLOSE || {
+                        let _ = file.writer.flush();
+                        false
+                    }
+                });
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        handle_output_op(runs_dir, &mut files, op, write_delay);
+        while let Ok(op) = rx.try_recv() {
+            handle_output_op(runs_dir, &mut files, op, write_delay);
+        }
+        flush_outputs(&mut files);
+    }
+    flush_outputs(&mut files);
+}
+
+fn handle_output_op(
+    runs_dir: &Path,
+    files: &mut HashMap<(String, &'static str), OutputFile>,
+    op: OutputOp,
+    write_delay: Duration,
+) {
+    match op {
+        OutputOp::Append {
+            job_id,
+            name,
+            chunk,
+        } => {
+            if !write_delay.is_zero() {
+                std::thread::sleep(write_delay);
+            }
+            let key = (job_id, name);
+            if !files.contains_key(&key) {
+                let path = runs_dir.join(&key.0).join(name);
+                match OpenOptions::new().create(true).append(true).open(&path) {
+                    Ok(file) => {
+                        files.insert(
+                            key.clone(),
+                            OutputFile {
+                                writer: BufWriter::new(file),
+                                last_write: Instant::now(),
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        warn!(job_id = %key.0, file = name, error = %e, "failed to append");
+                        return;
+                    }
+                }
+            }
+            let file = files.get_mut(&key).expect("output file was just opened");
+            if let Err(e) = file.writer.write_all(&chunk) {
+                warn!(job_id = %key.0, file = name, error = %e, "failed to append");
+            }
+            file.last_write = Instant::now();
+        }
+        OutputOp::Close { job_id, done } => {
+            files.retain(|(id, name), file| {
+                id != &job_id || {
+                    if let Err(e) = file.writer.flush() {
+                        warn!(job_id = %id, file = *name, error = %e, "failed to flush output");
+                    }
+                    false
+                }
+            });
+            let _ = done.send(());
+        }
+        OutputOp::Flush { done } => {
+            flush_outputs(files);
+            let _ = done.send(());
+        }
+    }
+}
+
+fn flush_outputs(files: &mut HashMap<(String, &'static str), OutputFile>) {
+    for ((job_id, name), file) in files.iter_mut() {
+        if let Err(e) = file.writer.flush() {
+            warn!(job_id = %job_id, file = *name, error = %e, "failed to flush output");
+        }
     }
+}
 
-    fn append_to_file(&self, job_id: &str, name: &str, chunk: &[u8]) {
-        let path = self.data_dir.join("runs").join(job_id).join(name);
-        let result = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&path)
-            .and_then(|mut f| f.write_all(chunk));
+/// Apply the runs retention policy at startup and then hourly. Runs until
+/// the process exits.
+pub async fn sweep_runs(store: Arc<RunStore>, registry: Arc<JobRegistry>) {
+    let mut interval = tokio::time::interval(RUNS_GC_INTERVAL);
+    loop {
+        interval.tick().await;
+        store.gc_runs(&registry).await;
+    }
+}
 
-        if let Err(e) = result {
-            warn!(job_id = %job_id, file = name, error = %e, "failed to append");
+/// Total size of the files under `dir` and the newest modification time
+/// among them (the directory's own when it is empty).
+/// Every run directory under `runs_dir` with its job_id: exec runs at the
+/// top level and browser records at `browser/<session_id>/<entry>`.
+fn run_dirs(runs_dir: &Path) -> Vec<(String, PathBuf)> {
+    let subdirs = |dir: &Path| -> Vec<(String, PathBuf)> {
+        match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().into_owned(),
+                        entry.path(),
+                    )
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!(dir = %dir.display(), error = %e, "failed to list runs dir");
+                Vec::new()
+            }
+        }
+    };
+    let mut dirs = Vec::new();
+    for (name, path) in subdirs(runs_dir) {
+        if name != BROWSER_RUNS_DIR {
+            dirs.push((name, path));
+            continue;
+        }
+        for (session_id, session_dir) in subdirs(&path) {
+            for (entry, entry_dir) in subdirs(&session_dir) {
+                dirs.push((
+                    format!("{BROWSER_RUNS_DIR}/{session_id}/{entry}"),
+                    entry_dir,
+                ));
+            }
         }
     }
+    dirs
+}
+
+/// Delete files in the per-session directories under `root` last modified
+/// before `cutoff`. Returns the bytes freed.
+fn prune_downloads(root: &Path, cutoff: SystemTime) -> u64 {
+    let Ok(sessions) = fs::read_dir(root) else {
+        return 0;
+    };
+    let mut freed = 0;
+    for session in sessions.flatten() {
+        let Ok(files) = fs::read_dir(session.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let Ok(meta) = file.metadata() else {
+                continue;
+            };
+            if !meta.is_file() || meta.modified().is_ok_and(|m| m >= cutoff) {
+                continue;
+            }
+            match fs::remove_file(file.path()) {
+                Ok(()) => freed += meta.len(),
+                Err(e) => {
+                    warn!(path = %file.path().display(), error = %e, "failed to delete old download")
+                }
+            }
+        }
+    }
+    freed
+}
+
+fn dir_usage(dir: &Path) -> std::io::Result<(u64, SystemTime)> {
+    let mut bytes = 0;
+    let mut modified = fs::metadata(dir)?.modified()?;
+    let mut first = true;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let (entry_bytes, entry_modified) = if meta.is_dir() {
+            dir_usage(&entry.path())?
+        } else {
+            (meta.len(), meta.modified()?)
+        };
+        bytes += entry_bytes;
+        if first || entry_modified > modified {
+            modified = entry_modified;
+            first = false;
+        }
+    }
+    Ok((bytes, modified))
+}
+
+/// The live trace file followed by its archives, newest first.
+// Bin target never calls this directly; the admin panel reads the trace
+// through the lib crate.
+#[allow(dead_code)]
+pub fn trace_files(data_dir: &Path) -> Vec<PathBuf> {
+    let live = data_dir.join(TRACE_FILE_NAME);
+    let mut files = vec![live.clone()];
+    let mut n = 1;
+    while rotated_path(&live, n).exists() {
+        files.push(rotated_path(&live, n));
+        n += 1;
+    }
+    files
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
 }
 
 fn describe_payload(envelope: &Envelope) -> &'static str {
@@ -160,10 +1096,289 @@ fn describe_payload(envelope: &Envelope) -> &'static str {
         Some(Payload::AppToolsUpdate(_)) => "AppToolsUpdate",
         Some(Payload::AppToolRequest(_)) => "AppToolRequest",
         Some(Payload::AppToolResponse(_)) => "AppToolResponse",
+        Some(Payload::CancelAll(_)) => "CancelAll",
+        Some(Payload::CancelAllResult(_)) => "CancelAllResult",
+        Some(Payload::JobQueued(_)) => "JobQueued",
+        Some(Payload::PolicyCheckRequest(_)) => "PolicyCheckRequest",
+        Some(Payload::PolicyCheckResult(_)) => "PolicyCheckResult",
+        Some(Payload::SetPolicyPreset(_)) => "SetPolicyPreset",
+        Some(Payload::Error(_)) => "Error",
+        Some(Payload::ClearSession(_)) => "ClearSession",
+        Some(Payload::PendingApprovalsQuery(_)) => "PendingApprovalsQuery",
+        Some(Payload::PendingApprovalsState(_)) => "PendingApprovalsState",
+        Some(Payload::ApprovalExpired(_)) => "ApprovalExpired",
+        Some(Payload::ApprovalResolveResult(_)) => "ApprovalResolveResult",
+        Some(Payload::ApprovalBulkResponse(_)) => "ApprovalBulkResponse",
+        Some(Payload::ApprovalBulkResult(_)) => "ApprovalBulkResult",
+        Some(Payload::DaemonStatusQuery(_)) => "DaemonStatusQuery",
+        Some(Payload::DaemonStatus(_)) => "DaemonStatus",
+        Some(Payload::ApprovalResolved(_)) => "ApprovalResolved",
+        Some(Payload::JobSubscribe(_)) => "JobSubscribe",
+        Some(Payload::Shutdown(_)) => "Shutdown",
+        Some(Payload::Ping(_)) => "Ping",
+        Some(Payload::Pong(_)) => "Pong",
+        Some(Payload::Subscribe(_)) => "Subscribe",
+        Some(Payload::StandaloneAck(_)) => "StandaloneAck",
         None => "none",
     }
 }
 
+/// Key fields of an envelope's payload for a summary trace line: ids, tool,
+/// the first few args, reasons and exit codes. `None` for payloads without
+/// anything worth summarizing.
+fn summarize_payload(envelope: &Envelope) -> Option<serde_json::Value> {
+    use ahand_protocol::envelope::Payload;
+    const ARGS: usize = 3;
+    let summary = match envelope.payload.as_ref()? {
+        Payload::JobRequest(req) => json!({
+            "job_id": req.job_id,
+            "tool": req.tool,
+            "args": req.args.iter().take(ARGS).collect::<Vec<_>>(),
+            "arg_count": req.args.len(),
+            "interactive": req.interactive,
+        }),
+        Payload::JobEvent(event) => {
+            use ahand_protocol::job_event::Event;
+            match &event.event {
+                Some(Event::StdoutChunk(chunk)) => {
+                    json!({ "job_id": event.job_id, "stdout_bytes": chunk.len() })
+                }
+                Some(Event::StderrChunk(chunk)) => {
+                    json!({ "job_id": event.job_id, "stderr_bytes": chunk.len() })
+                }
+                Some(Event::Progress(progress)) => {
+                    json!({ "job_id": event.job_id, "progress": progress })
+                }
+                None => json!({ "job_id": event.job_id }),
+            }
+        }
+        Payload::JobFinished(finished) => json!({
+            "job_id": finished.job_id,
+            "exit_code": finished.exit_code,
+            "error": finished.error,
+        }),
+        Payload::JobRejected(rejected) => json!({
+            "job_id": rejected.job_id,
+            "reason": rejected.reason,
+        }),
+        Payload::JobQueued(queued) => json!({
+            "job_id": queued.job_id,
+            "position": queued.position,
+        }),
+        Payload::CancelJob(cancel) => json!({ "job_id": cancel.job_id }),
+        Payload::ApprovalRequest(req) => json!({
+            "job_id": req.job_id,
+            "tool": req.tool,
+            "args": req.args.iter().take(ARGS).collect::<Vec<_>>(),
+            "reason": req.reason,
+        }),
+        Payload::ApprovalResponse(resp) => json!({
+            "job_id": resp.job_id,
+            "approved": resp.approved,
+            "reason": resp.reason,
+        }),
+        Payload::BrowserRequest(req) => json!({
+            "request_id": req.request_id,
+            "action": req.action,
+        }),
+        Payload::FileRequest(req) => json!({ "request_id": req.request_id }),
+        Payload::AppToolRequest(req) => json!({
+            "tool_call_id": req.tool_call_id,
+            "name": req.name,
+        }),
+        Payload::Error(err) => json!({
+            "code": err.code,
+            "message": err.message,
+        }),
+        _ => return None,
+    };
+    Some(summary)
+}
+
+/// Render a trace record, with `body` when there is one, as one line of at
+/// most [`TRACE_LINE_MAX_BYTES`]. An oversized body is replaced by its
+/// JSON text cut short and the record marked `truncated`.
+fn trace_line(mut record: serde_json::Value, body: Option<serde_json::Value>) -> String {
+    let Some(body) = body else {
+        return format!("{record}\n");
+    };
+    record["body"] = body;
+    let mut line = record.to_string();
+    if line.len() > TRACE_LINE_MAX_BYTES {
+        let text = record["body"].to_string();
+        let mut keep = text.len();
+        record["truncated"] = json!(true);
+        // Escaping makes the cut body's size hard to predict; shrink until
+        // the line fits.
+        while line.len() > TRACE_LINE_MAX_BYTES && keep > 0 {
+            keep = keep.saturating_sub(line.len() - TRACE_LINE_MAX_BYTES);
+            while !text.is_char_boundary(keep) {
+                keep -= 1;
+            }
+            record["body"] = json!(format!("{}…", &text[..keep]));
+            line = record.to_string();
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Gzip `stdout`/`stderr` in a finished run directory that are larger than
+/// `threshold` bytes, replacing each with `<name>.gz`, and record
+/// `compressed: true` in result.json.
+fn compress_outputs(run_dir: &Path, threshold: u64) -> std::io::Result<()> {
+    let mut compressed = false;
+    for name in ["stdout", "stderr"] {
+        let path = run_dir.join(name);
+        if file_len(&path) <= threshold {
+            continue;
+        }
+        let tmp = run_dir.join(format!("{name}.gz.tmp"));
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&tmp)?, flate2::Compression::default());
+        std::io::copy(&mut File::open(&path)?, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        fs::rename(&tmp, run_dir.join(format!("{name}.gz")))?;
+        fs::remove_file(&path)?;
+        compressed = true;
+    }
+    if compressed {
+        let result_path = run_dir.join("result.json");
+        let mut result: serde_json::Value = serde_json::from_slice(&fs::read(&result_path)?)?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("compressed".to_string(), json!(true));
+        }
+        write_json(&result_path, &result)?;
+    }
+    Ok(())
+}
+
+/// Read a run's `stdout` or `stderr`, transparently decompressing
+/// `<name>.gz` when the output was compressed after the run finished.
+// Bin target never calls this directly; `ahandctl runs show` and the admin
+// panel read outputs through the lib crate.
+#[allow(dead_code)]
+pub fn read_run_output(run_dir: &Path, name: &str) -> std::io::Result<Vec<u8>> {
+    match fs::read(run_dir.join(name)) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let file = File::open(run_dir.join(format!("{name}.gz")))?;
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(file).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        other => other,
+    }
+}
+
+/// The `outcome` recorded in a run's result.json, if it has one. Runs
+/// recorded before outcomes existed, and unfinished runs, have none.
+// Bin target never calls this directly; `ahandctl runs show` reads it
+// through the lib crate.
+#[allow(dead_code)]
+pub fn read_run_outcome(run_dir: &Path) -> Option<RunOutcome> {
+    let content = fs::read(run_dir.join("result.json")).ok()?;
+    let mut result: serde_json::Value = serde_json::from_slice(&content).ok()?;
+    serde_json::from_value(result.get_mut("outcome")?.take()).ok()
+}
+
+/// Whether `job_id` can name a run directory: non-empty, no path
+/// separators and not `.` or `..`.
+pub fn is_valid_job_id(job_id: &str) -> bool {
+    !job_id.is_empty() && job_id != "." && job_id != ".." && !job_id.contains(['/', '\\', '\0'])
+}
+
+/// Pack everything recorded for `job_id` — request.json (env redacted by
+/// `redactor`), result.json, outputs and any other files in the run
+/// directory — plus a manifest.json into `<data_dir>/exports/job_<id>.tar.gz`,
+/// and return its path. The caller removes the tarball once delivered.
+// Bin target never calls this directly; `ahandctl runs export` and the
+// admin panel reach it through the lib crate.
+#[allow(dead_code)]
+pub fn export_run(
+    data_dir: &Path,
+    job_id: &str,
+    redactor: &EnvRedactor,
+) -> std::io::Result<PathBuf> {
+    if !is_valid_job_id(job_id) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid job id: {job_id:?}"),
+        ));
+    }
+    let run_dir = data_dir.join("runs").join(job_id);
+    if !run_dir.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no run recorded for {job_id}"),
+        ));
+    }
+    let export_dir = data_dir.join(EXPORTS_DIR_NAME);
+    fs::create_dir_all(&export_dir)?;
+
+    let mut names: Vec<String> = fs::read_dir(&run_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    let prefix = format!("job_{job_id}");
+    let path = export_dir.join(format!("{prefix}.tar.gz"));
+    let tmp = export_dir.join(format!("{prefix}.tar.gz.tmp"));
+    let exported_ms = now_ms();
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        File::create(&tmp)?,
+        flate2::Compression::default(),
+    ));
+    let read_json = |name: &str| -> Option<serde_json::Value> {
+        serde_json::from_slice(&fs::read(run_dir.join(name)).ok()?).ok()
+    };
+    for name in &names {
+        let archived = format!("{prefix}/{name}");
+        if name == "request.json"
+            && let Some(mut request) = read_json(name)
+        {
+            redactor.redact_request(&mut request);
+            let body = serde_json::to_vec_pretty(&request)?;
+            append_bytes(&mut tar, &archived, &body, exported_ms)?;
+        } else {
+            tar.append_path_with_name(run_dir.join(name), &archived)?;
+        }
+    }
+    let field = |file: &str, key: &str| read_json(file).and_then(|v| v.get(key)?.as_u64());
+    let manifest = json!({
+        "job_id": job_id,
+        "daemon_version": env!("CARGO_PKG_VERSION"),
+        "start_ms": field("request.json", "start_ms"),
+        "end_ms": field("result.json", "end_ms"),
+        "exported_ms": exported_ms,
+        "files": names,
+    });
+    let body = serde_json::to_vec_pretty(&manifest)?;
+    append_bytes(
+        &mut tar,
+        &format!("{prefix}/manifest.json"),
+        &body,
+        exported_ms,
+    )?;
+    tar.into_inner()?.finish()?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+fn append_bytes<W: Write>(
+    tar: &mut tar::Builder<W>,
+    path: &str,
+    body: &[u8],
+    mtime_ms: u64,
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(body.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime_ms / 1000);
+    header.set_cksum();
+    tar.append_data(&mut header, path, body)
+}
+
 fn write_json(path: &Path, value: &serde_json::Value) -> std::io::Result<()> {
     let file = File::create(path)?;
     serde_json::to_writer_pretty(file, value)?;
@@ -179,9 +1394,19 @@ fn now_ms() -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use super::describe_payload;
+    use super::{
+        Direction, OUTPUT_QUEUE_CAPACITY, OutcomeKind, RunContext, RunOutcome, RunStore,
+        TRACE_FILE_NAME, TRACE_LINE_MAX_BYTES, TracePayloads, describe_payload, read_run_outcome,
+        read_run_output, rotated_path, trace_files,
+    };
+    use crate::redact::EnvRedactor;
+    use crate::registry::{JobRegistry, params_hash};
+    use crate::run_index::{RunIndex, RunKind};
     use ahand_protocol::envelope::Payload;
     use ahand_protocol::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
 
     /// Build an envelope wrapping the given payload and assert
     /// `describe_payload` returns `expected`. Pinning every Payload
@@ -278,6 +1503,74 @@ mod tests {
             Payload::AppToolResponse(AppToolResponse::default()),
             "AppToolResponse",
         );
+        check(Payload::CancelAll(CancelAll::default()), "CancelAll");
+        check(
+            Payload::CancelAllResult(CancelAllResult::default()),
+            "CancelAllResult",
+        );
+        check(Payload::JobQueued(JobQueued::default()), "JobQueued");
+        check(
+            Payload::PolicyCheckRequest(PolicyCheckRequest::default()),
+            "PolicyCheckRequest",
+        );
+        check(
+            Payload::PolicyCheckResult(PolicyCheckResult::default()),
+            "PolicyCheckResult",
+        );
+        check(
+            Payload::SetPolicyPreset(SetPolicyPreset::default()),
+            "SetPolicyPreset",
+        );
+        check(Payload::Error(Error::default()), "Error");
+        check(
+            Payload::ClearSession(ClearSession::default()),
+            "ClearSession",
+        );
+        check(
+            Payload::PendingApprovalsQuery(PendingApprovalsQuery {}),
+            "PendingApprovalsQuery",
+        );
+        check(
+            Payload::PendingApprovalsState(PendingApprovalsState::default()),
+            "PendingApprovalsState",
+        );
+        check(
+            Payload::ApprovalExpired(ApprovalExpired::default()),
+            "ApprovalExpired",
+        );
+        check(
+            Payload::ApprovalResolveResult(ApprovalResolveResult::default()),
+            "ApprovalResolveResult",
+        );
+        check(
+            Payload::ApprovalBulkResponse(ApprovalBulkResponse::default()),
+            "ApprovalBulkResponse",
+        );
+        check(
+            Payload::ApprovalBulkResult(ApprovalBulkResult::default()),
+            "ApprovalBulkResult",
+        );
+        check(
+            Payload::DaemonStatusQuery(DaemonStatusQuery {}),
+            "DaemonStatusQuery",
+        );
+        check(
+            Payload::DaemonStatus(DaemonStatus::default()),
+            "DaemonStatus",
+        );
+        check(
+            Payload::ApprovalResolved(ApprovalResolved::default()),
+            "ApprovalResolved",
+        );
+        check(
+            Payload::JobSubscribe(JobSubscribe::default()),
+            "JobSubscribe",
+        );
+        check(Payload::Shutdown(Shutdown::default()), "Shutdown");
+        check(Payload::Ping(Ping {}), "Ping");
+        check(Payload::Pong(Pong::default()), "Pong");
+        check(Payload::Subscribe(Subscribe::default()), "Subscribe");
+        check(Payload::StandaloneAck(Ack::default()), "StandaloneAck");
     }
 
     #[test]
@@ -289,4 +1582,738 @@ mod tests {
         let envelope = Envelope::default();
         assert_eq!(describe_payload(&envelope), "none");
     }
+
+    fn heartbeat(seq: u64) -> Envelope {
+        Envelope {
+            device_id: "dev-1".to_string(),
+            msg_id: format!("msg-{seq}"),
+            seq,
+            payload: Some(Payload::Heartbeat(Default::default())),
+            ..Default::default()
+        }
+    }
+
+    fn line_count(path: &Path) -> usize {
+        fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn trace_rotates_past_size_cap_and_keeps_logging() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 1024, 2).unwrap();
+        for seq in 0..100 {
+            store
+                .log_envelope(&heartbeat(seq), Direction::Outbound)
+                .await;
+        }
+
+        let live = dir.path().join(TRACE_FILE_NAME);
+        assert!(fs::metadata(&live).unwrap().len() <= 1024);
+        assert!(rotated_path(&live, 1).exists());
+        assert!(rotated_path(&live, 2).exists());
+        assert!(!rotated_path(&live, 3).exists());
+        assert_eq!(
+            trace_files(dir.path()),
+            vec![live.clone(), rotated_path(&live, 1), rotated_path(&live, 2)]
+        );
+
+        // The newest record lands in the live file; archives hold older ones.
+        let last = fs::read_to_string(&live).unwrap();
+        assert!(last.lines().last().unwrap().contains("\"msg-99\""));
+        let archived = fs::read_to_string(rotated_path(&live, 1)).unwrap();
+        assert!(!archived.contains("\"msg-99\""));
+
+        store
+            .log_envelope(&heartbeat(100), Direction::Inbound)
+            .await;
+        assert!(fs::read_to_string(&live).unwrap().contains("\"msg-100\""));
+    }
+
+    #[tokio::test]
+    async fn zero_max_bytes_never_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        for seq in 0..50 {
+            store
+                .log_envelope(&heartbeat(seq), Direction::Outbound)
+                .await;
+        }
+        let live = dir.path().join(TRACE_FILE_NAME);
+        assert_eq!(line_count(&live), 50);
+        assert_eq!(trace_files(dir.path()), vec![live]);
+    }
+
+    fn job_request(args: Vec<String>) -> Envelope {
+        Envelope {
+            device_id: "dev-1".to_string(),
+            msg_id: "msg-1".to_string(),
+            payload: Some(Payload::JobRequest(JobRequest {
+                job_id: "job-1".to_string(),
+                tool: "git".to_string(),
+                args,
+                env: [
+                    ("GH_TOKEN".to_string(), "ghp_secret".to_string()),
+                    ("TERM".to_string(), "xterm".to_string()),
+                ]
+                .into(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    async fn traced(level: TracePayloads, envelope: &Envelope) -> serde_json::Value {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 0)
+            .unwrap()
+            .with_trace_payloads(level);
+        store.log_envelope(envelope, Direction::Inbound).await;
+        let line = fs::read_to_string(dir.path().join(TRACE_FILE_NAME)).unwrap();
+        assert!(line.len() <= TRACE_LINE_MAX_BYTES + 1);
+        serde_json::from_str(line.trim_end()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn trace_payloads_default_to_type_only() {
+        let args = vec!["status".to_string()];
+        let record = traced(TracePayloads::None, &job_request(args)).await;
+        assert_eq!(record["payload"], "JobRequest");
+        assert!(record.get("body").is_none());
+    }
+
+    #[tokio::test]
+    async fn trace_payload_summary_keeps_key_fields() {
+        let args = ["log", "-n", "5", "--oneline"].map(String::from).to_vec();
+        let record = traced(TracePayloads::Summary, &job_request(args)).await;
+        let body = &record["body"];
+        assert_eq!(body["job_id"], "job-1");
+        assert_eq!(body["tool"], "git");
+        assert_eq!(body["args"], serde_json::json!(["log", "-n", "5"]));
+        assert_eq!(body["arg_count"], 4);
+        assert!(body.get("env").is_none());
+    }
+
+    #[tokio::test]
+    async fn trace_payload_full_redacts_env() {
+        let args = vec!["status".to_string()];
+        let record = traced(TracePayloads::Full, &job_request(args)).await;
+        let body = &record["body"];
+        assert_eq!(body["args"], serde_json::json!(["status"]));
+        assert_eq!(body["env"]["TERM"], "xterm");
+        let token = body["env"]["GH_TOKEN"].as_str().unwrap();
+        assert!(token.starts_with("<redacted:"), "{token}");
+        assert!(record.get("truncated").is_none());
+    }
+
+    #[tokio::test]
+    async fn trace_payload_full_truncates_oversized_bodies() {
+        let args = vec!["é\"".repeat(10_000)];
+        let record = traced(TracePayloads::Full, &job_request(args)).await;
+        assert_eq!(record["truncated"], true);
+        assert!(record["body"].as_str().unwrap().ends_with('…'));
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+    /// Write a finished run of `bytes` bytes whose files were last touched
+    /// `age` ago.
+    fn fake_run(data_dir: &Path, job_id: &str, bytes: usize, age: Duration) {
+        let run_dir = data_dir.join("runs").join(job_id);
+        fs::create_dir_all(&run_dir).unwrap();
+        let stdout = run_dir.join("stdout");
+        fs::write(&stdout, vec![b'x'; bytes]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&stdout)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    fn run_exists(data_dir: &Path, job_id: &str) -> bool {
+        data_dir.join("runs").join(job_id).exists()
+    }
+
+    #[test]
+    fn request_json_redacts_secret_env_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_env_redaction(EnvRedactor::new(&["*_KEY".to_string()]));
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "curl".to_string(),
+            env: [
+                ("STRIPE_KEY".to_string(), "sk_live_123".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "", &req);
+
+        let path = dir.path().join("runs").join("job-1").join("request.json");
+        let content = fs::read_to_string(path).unwrap();
+        assert!(!content.contains("sk_live_123"));
+        let request: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(request["env"]["LANG"], "C");
+        assert!(
+            request["env"]["STRIPE_KEY"]
+                .as_str()
+                .unwrap()
+                .starts_with("<redacted:11 chars sha256=")
+        );
+    }
+
+    #[tokio::test]
+    async fn result_json_records_the_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "git".to_string(),
+            ..Default::default()
+        };
+        let context = RunContext {
+            session_mode: Some("strict".to_string()),
+            approved_by: Some("uid:501".to_string()),
+            approval_latency_ms: Some(1200),
+            ..RunContext::new("cloud")
+        };
+        store.start_run("job-1", "cloud", &req);
+        store
+            .finish_run(
+                "job-1",
+                -1,
+                "cancelled",
+                5,
+                0,
+                &context.outcome(OutcomeKind::Cancelled),
+            )
+            .await;
+
+        let run_dir = dir.path().join("runs").join("job-1");
+        let result: serde_json::Value =
+            serde_json::from_slice(&fs::read(run_dir.join("result.json")).unwrap()).unwrap();
+        assert_eq!(result["outcome"]["kind"], "cancelled");
+        assert_eq!(result["outcome"]["approved_by"], "uid:501");
+        assert!(result["outcome"].get("rejection_reason").is_none());
+        assert_eq!(
+            read_run_outcome(&run_dir).unwrap(),
+            RunOutcome {
+                kind: OutcomeKind::Cancelled,
+                approved_by: Some("uid:501".to_string()),
+                approval_latency_ms: Some(1200),
+                session_mode: Some("strict".to_string()),
+                rejection_reason: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_runs_are_recorded_and_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "rm".to_string(),
+            ..Default::default()
+        };
+        let context = RunContext {
+            session_mode: Some("strict".to_string()),
+            ..RunContext::new("uid:501")
+        };
+        store
+            .reject_run(&req, "uid:501", &context.rejected("approval denied"))
+            .await;
+
+        let run_dir = dir.path().join("runs").join("job-1");
+        assert!(run_dir.join("request.json").exists());
+        let outcome = read_run_outcome(&run_dir).unwrap();
+        assert_eq!(outcome.kind, OutcomeKind::Rejected);
+        assert_eq!(outcome.rejection_reason.as_deref(), Some("approval denied"));
+        assert_eq!(outcome.session_mode.as_deref(), Some("strict"));
+
+        let index = crate::run_index::RunIndex::open(dir.path()).unwrap();
+        let run = index.get_run("job-1").unwrap().unwrap();
+        assert_eq!(run.exit_code, Some(-1));
+        assert_eq!(run.error.as_deref(), Some("approval denied"));
+    }
+
+    #[tokio::test]
+    async fn started_and_finished_runs_are_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "git".to_string(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "uid:501", &req);
+        store.append_stdout("job-1", b"hello\n");
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+
+        let index = crate::run_index::RunIndex::open(dir.path()).unwrap();
+        let run = index.get_run("job-1").unwrap().unwrap();
+        assert_eq!(run.tool, "git");
+        assert_eq!(run.caller_uid, "uid:501");
+        assert_eq!(run.exit_code, Some(0));
+        assert_eq!(run.bytes_stdout, 6);
+        assert!(run.finished_ms.unwrap() >= run.started_ms);
+    }
+
+    #[tokio::test]
+    async fn large_outputs_are_compressed_once_the_run_finishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_output_compression(64);
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "make".to_string(),
+            ..Default::default()
+        };
+        let stdout = "building target\n".repeat(100);
+        store.start_run("job-1", "uid:501", &req);
+        store.append_stdout("job-1", stdout.as_bytes());
+        store.append_stderr("job-1", b"warning\n");
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+
+        // Compression runs on the blocking pool after finish_run returns;
+        // marking result.json is its last step.
+        let run_dir = dir.path().join("runs").join("job-1");
+        let mut result = serde_json::Value::Null;
+        for _ in 0..200 {
+            result =
+                serde_json::from_slice(&fs::read(run_dir.join("result.json")).unwrap()).unwrap();
+            if result["compressed"] == true {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(result["compressed"], true);
+        assert!(!run_dir.join("stdout").exists());
+        assert!(run_dir.join("stdout.gz").exists());
+        assert!(run_dir.join("stderr").exists());
+        assert!(!run_dir.join("stderr.gz").exists());
+
+        assert_eq!(
+            read_run_output(&run_dir, "stdout").unwrap(),
+            stdout.as_bytes()
+        );
+        assert_eq!(read_run_output(&run_dir, "stderr").unwrap(), b"warning\n");
+        assert_eq!(
+            read_run_output(&run_dir, "missing").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn appends_do_not_wait_for_the_disk_and_finish_run_flushes_them_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::with_output_writer(
+            dir.path(),
+            0,
+            3,
+            Duration::from_millis(20),
+            OUTPUT_QUEUE_CAPACITY,
+        )
+        .unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "echo".to_string(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "uid:501", &req);
+
+        let started = std::time::Instant::now();
+        let mut expected = Vec::new();
+        for i in 0..20 {
+            let line = format!("line {i}\n");
+            store.append_stdout("job-1", line.as_bytes());
+            expected.extend_from_slice(line.as_bytes());
+        }
+        // The writer sleeps 20ms per chunk; the sends must not.
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+        let run_dir = dir.path().join("runs").join("job-1");
+        assert_eq!(fs::read(run_dir.join("stdout")).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn chunks_dropped_by_a_full_writer_queue_are_counted_in_result_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let store =
+            RunStore::with_output_writer(dir.path(), 0, 3, Duration::from_millis(50), 2).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "echo".to_string(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "uid:501", &req);
+        for i in 0..10 {
+            store.append_stdout("job-1", format!("line {i}\n").as_bytes());
+        }
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+
+        let run_dir = dir.path().join("runs").join("job-1");
+        let written = fs::read(run_dir.join("stdout")).unwrap();
+        let dropped = read_result(&run_dir)["dropped_output_chunks"]
+            .as_u64()
+            .unwrap();
+        assert!(dropped > 0);
+        assert_eq!(
+            written.iter().filter(|&&b| b == b'\n').count() as u64,
+            10 - dropped
+        );
+    }
+
+    #[tokio::test]
+    async fn output_after_finish_run_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "echo".to_string(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "uid:501", &req);
+        store.append_stdout("job-1", b"kept\n");
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+        store.append_stdout("job-1", b"late\n");
+        store.append_stderr("job-2", b"never started\n");
+        store.flush().await;
+
+        let runs_dir = dir.path().join("runs");
+        assert_eq!(
+            fs::read(runs_dir.join("job-1").join("stdout")).unwrap(),
+            b"kept\n"
+        );
+        assert!(!runs_dir.join("job-2").exists());
+        assert!(store.output_budgets.lock().unwrap().is_empty());
+    }
+
+    /// Write `2 * cap` bytes of stdout in 1000-byte chunks and return the
+    /// run directory.
+    async fn write_twice_the_cap(store: &RunStore, req: &JobRequest, cap: usize) -> PathBuf {
+        store.start_run(&req.job_id, "", req);
+        for _ in 0..(2 * cap / 1000) {
+            store.append_stdout(&req.job_id, &[b'x'; 1000]);
+        }
+        store.append_stderr(&req.job_id, b"small");
+        store
+            .finish_run(&req.job_id, 0, "", 5, 0, &RunOutcome::default())
+            .await;
+        store.data_dir.join("runs").join(&req.job_id)
+    }
+
+    fn read_result(run_dir: &Path) -> serde_json::Value {
+        serde_json::from_slice(&fs::read(run_dir.join("result.json")).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn output_past_the_cap_is_truncated_with_a_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_run_output_cap(10_500);
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            ..Default::default()
+        };
+        let run_dir = write_twice_the_cap(&store, &req, 10_500).await;
+
+        let stdout = fs::read(run_dir.join("stdout")).unwrap();
+        let marker = b"\n--- truncated at 10500 bytes ---\n";
+        assert_eq!(stdout.len(), 10_500 + marker.len());
+        assert!(stdout.ends_with(marker));
+        assert!(stdout[..10_500].iter().all(|&b| b == b'x'));
+        assert_eq!(fs::read(run_dir.join("stderr")).unwrap(), b"small");
+
+        let result = read_result(&run_dir);
+        assert_eq!(result["stdout_truncated"], true);
+        assert_eq!(result["stderr_truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn job_request_can_lower_the_output_cap_but_not_raise_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_run_output_cap(4_000);
+
+        let lower = JobRequest {
+            job_id: "lower".to_string(),
+            output_cap_bytes: 2_000,
+            ..Default::default()
+        };
+        let run_dir = write_twice_the_cap(&store, &lower, 2_000).await;
+        let stdout = fs::read(run_dir.join("stdout")).unwrap();
+        assert!(stdout.ends_with(b"--- truncated at 2000 bytes ---\n"));
+
+        let higher = JobRequest {
+            job_id: "higher".to_string(),
+            output_cap_bytes: 1_000_000,
+            ..Default::default()
+        };
+        let run_dir = write_twice_the_cap(&store, &higher, 4_000).await;
+        let stdout = fs::read(run_dir.join("stdout")).unwrap();
+        assert!(stdout.ends_with(b"--- truncated at 4000 bytes ---\n"));
+        assert_eq!(read_result(&run_dir)["stdout_truncated"], true);
+    }
+
+    /// Unpack a tarball into `(path, contents)` pairs.
+    fn untar(path: &Path) -> Vec<(String, Vec<u8>)> {
+        use std::io::Read;
+        let gz = flate2::read::GzDecoder::new(fs::File::open(path).unwrap());
+        let mut archive = tar::Archive::new(gz);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut body = Vec::new();
+                entry.read_to_end(&mut body).unwrap();
+                (name, body)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn export_run_packs_the_run_with_a_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            tool: "env".to_string(),
+            env: [("API_TOKEN".to_string(), "hunter2".to_string())].into(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "uid:501", &req);
+        store.append_stdout("job-1", b"hello\n");
+        store
+            .finish_run("job-1", 0, "", 5, 0, &RunOutcome::default())
+            .await;
+        // Written before request env was redacted on the way in.
+        let request_path = dir.path().join("runs/job-1/request.json");
+        let mut request: serde_json::Value =
+            serde_json::from_slice(&fs::read(&request_path).unwrap()).unwrap();
+        request["env"]["API_TOKEN"] = "hunter2".into();
+        fs::write(&request_path, request.to_string()).unwrap();
+        fs::write(dir.path().join("runs/job-1/screenshot.png"), b"png").unwrap();
+
+        let tarball = store.export_run("job-1").unwrap();
+        assert_eq!(tarball, dir.path().join("exports/job_job-1.tar.gz"));
+        let entries = untar(&tarball);
+        let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "job_job-1/request.json",
+                "job_job-1/result.json",
+                "job_job-1/screenshot.png",
+                "job_job-1/stdout",
+                "job_job-1/manifest.json",
+            ]
+        );
+        let file = |name: &str| &entries.iter().find(|(n, _)| n.ends_with(name)).unwrap().1;
+        assert_eq!(file("stdout"), b"hello\n");
+        let request: serde_json::Value = serde_json::from_slice(file("request.json")).unwrap();
+        assert!(
+            request["env"]["API_TOKEN"]
+                .as_str()
+                .unwrap()
+                .starts_with("<redacted:")
+        );
+        let manifest: serde_json::Value = serde_json::from_slice(file("manifest.json")).unwrap();
+        assert_eq!(manifest["job_id"], "job-1");
+        assert_eq!(manifest["daemon_version"], env!("CARGO_PKG_VERSION"));
+        assert!(manifest["start_ms"].as_u64().unwrap() <= manifest["end_ms"].as_u64().unwrap());
+        assert!(manifest["exported_ms"].is_u64());
+    }
+
+    #[test]
+    fn export_run_rejects_path_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        fs::write(dir.path().join("secret"), b"x").unwrap();
+        for job_id in ["..", ".", "", "../secret", "a/b", "a\\b"] {
+            let err = store.export_run(job_id).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{job_id:?}");
+        }
+        assert!(!dir.path().join("exports").exists());
+        assert_eq!(
+            store.export_run("missing").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_writes_output_of_unfinished_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3).unwrap();
+        let req = JobRequest {
+            job_id: "job-1".to_string(),
+            ..Default::default()
+        };
+        store.start_run("job-1", "", &req);
+        store.append_stderr("job-1", b"partial");
+        store.flush().await;
+
+        let path = dir.path().join("runs").join("job-1").join("stderr");
+        assert_eq!(fs::read(path).unwrap(), b"partial");
+    }
+
+    #[tokio::test]
+    async fn gc_runs_deletes_runs_past_the_retention_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_runs_retention(30 * DAY, 0);
+        fake_run(dir.path(), "old", 100, 40 * DAY);
+        fake_run(dir.path(), "recent", 100, 2 * DAY);
+
+        let stats = store.gc_runs(&JobRegistry::new(4)).await;
+
+        assert!(!run_exists(dir.path(), "old"));
+        assert!(run_exists(dir.path(), "recent"));
+        assert_eq!(stats.deleted_runs, 1);
+        assert_eq!(stats.freed_bytes, 100);
+        assert_eq!(stats.retained_bytes, 100);
+        assert_eq!(store.last_runs_gc().await, Some(stats));
+    }
+
+    fn backdate(path: &Path, age: Duration) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn browser_artifacts_are_recorded_and_pruned_with_their_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloads = dir.path().join("downloads");
+        let store = RunStore::new(&dir.path().join("data"), 0, 3)
+            .unwrap()
+            .with_runs_retention(30 * DAY, 0)
+            .with_browser_downloads(downloads.clone());
+        fs::create_dir_all(downloads.join("s1")).unwrap();
+        let shot = downloads.join("s1").join("1_screenshot.png");
+        fs::write(&shot, b"png!").unwrap();
+
+        store.record_browser_artifact(
+            "s1",
+            "screenshot",
+            r#"{"ref":"e1","api_token":"hunter2"}"#,
+            "uid:501",
+            &shot,
+            "image/png",
+        );
+        let index = RunIndex::open(&dir.path().join("data")).unwrap();
+        let (_, runs) = index.search(&Default::default(), 10, 0).unwrap();
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert!(run.job_id.starts_with("browser/s1/"), "{}", run.job_id);
+        assert_eq!(run.kind, RunKind::Browser);
+        assert_eq!(run.tool, "screenshot");
+        let run_dir = dir.path().join("data/runs").join(&run.job_id);
+        let read = |name: &str| -> serde_json::Value {
+            serde_json::from_slice(&fs::read(run_dir.join(name)).unwrap()).unwrap()
+        };
+        let request = read("request.json");
+        assert_eq!(request["params"]["ref"], "e1");
+        assert!(
+            request["params"]["api_token"]
+                .as_str()
+                .unwrap()
+                .starts_with("<redacted:")
+        );
+        let result = read("result.json");
+        assert_eq!(result["size"], 4);
+        assert_eq!(result["mime"], "image/png");
+
+        // Unsafe names never reach the filesystem.
+        store.record_browser_artifact("../x", "screenshot", "{}", "", &shot, "image/png");
+        assert!(!dir.path().join("data/runs/x").exists());
+
+        // A fresh record survives; an expired one takes its file with it,
+        // and old files nothing points to are swept too.
+        let stray = downloads.join("s1").join("old.pdf");
+        fs::write(&stray, b"pdf").unwrap();
+        backdate(&stray, 40 * DAY);
+        let stats = store.gc_runs(&JobRegistry::new(4)).await;
+        assert_eq!(stats.deleted_runs, 0);
+        assert!(shot.exists() && !stray.exists());
+
+        for name in ["request.json", "result.json"] {
+            backdate(&run_dir.join(name), 40 * DAY);
+        }
+        let stats = store.gc_runs(&JobRegistry::new(4)).await;
+        assert_eq!(stats.deleted_runs, 1);
+        assert!(!run_dir.exists() && !shot.exists());
+        assert!(!dir.path().join("data/runs/browser/s1").exists());
+        assert_eq!(index.get_run(&run.job_id).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn gc_runs_deletes_oldest_runs_until_under_the_size_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_runs_retention(Duration::ZERO, 250);
+        fake_run(dir.path(), "a", 100, 3 * DAY);
+        fake_run(dir.path(), "b", 100, 2 * DAY);
+        fake_run(dir.path(), "c", 100, DAY);
+
+        let stats = store.gc_runs(&JobRegistry::new(4)).await;
+
+        assert!(!run_exists(dir.path(), "a"));
+        assert!(run_exists(dir.path(), "b"));
+        assert!(run_exists(dir.path(), "c"));
+        assert_eq!(stats.deleted_runs, 1);
+        assert_eq!(stats.retained_bytes, 200);
+    }
+
+    #[tokio::test]
+    async fn gc_runs_never_deletes_runs_of_registered_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RunStore::new(dir.path(), 0, 3)
+            .unwrap()
+            .with_runs_retention(30 * DAY, 150);
+        fake_run(dir.path(), "running", 100, 40 * DAY);
+        fake_run(dir.path(), "done", 100, 40 * DAY);
+        let registry = JobRegistry::new(4);
+        let (cancel_tx, _cancel_rx) = tokio::sync::mpsc::channel(1);
+        let req = JobRequest {
+            job_id: "running".to_string(),
+            ..Default::default()
+        };
+        assert!(
+            registry
+                .register("running".to_string(), "uid:1", params_hash(&req), cancel_tx)
+                .await
+        );
+
+        let stats = store.gc_runs(&registry).await;
+
+        assert!(run_exists(dir.path(), "running"));
+        assert!(!run_exists(dir.path(), "done"));
+        assert_eq!(stats.deleted_runs, 1);
+        assert_eq!(stats.retained_bytes, 100);
+    }
 }
@@ -0,0 +1,470 @@
+//! rustls client configuration for wss:// connections, built from the
+//! `[tls]` config section: extra CA certificates, a client certificate for
+//! mutual TLS, and an optional pin on the server's leaf certificate.
+//!
+//! Without a `[tls]` section connections keep tokio-tungstenite's default
+//! (native-tls with the system trust store). OpenClaw gateways, which are
+//! usually self-signed, use [`gateway_connector`] instead.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, bail};
+use base64::Engine;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use tokio_tungstenite::Connector;
+use tracing::{error, warn};
+
+use crate::config::TlsConfig;
+
+/// The connector to hand to `client_async_tls_with_config`.
+pub fn connector(tls: &TlsConfig) -> anyhow::Result<Connector> {
+    Ok(Connector::Rustls(Arc::new(client_config(tls)?)))
+}
+
+/// Build the rustls client config described by `tls`.
+pub fn client_config(tls: &TlsConfig) -> anyhow::Result<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = server_verifier(tls, provider.clone())?;
+    build_client_config(tls, verifier, provider)
+}
+
+/// How an OpenClaw gateway's certificate is trusted.
+pub enum GatewayTrust {
+    /// The leaf must have this SHA-256 fingerprint (hex or base64). The pin
+    /// replaces chain validation, so self-signed gateways can be pinned.
+    Pinned(String),
+    /// Accept whatever the gateway presents, to be pinned afterwards.
+    FirstUse,
+    /// Usual chain validation against the system roots and `ca_file`.
+    Chain,
+}
+
+/// Fingerprint of the leaf certificate a gateway presented, filled in
+/// during the handshake.
+#[derive(Debug, Clone, Default)]
+pub struct ObservedFingerprint(Arc<Mutex<Option<String>>>);
+
+impl ObservedFingerprint {
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().expect("fingerprint mutex poisoned").clone()
+    }
+
+    fn set(&self, fingerprint: String) {
+        *self.0.lock().expect("fingerprint mutex poisoned") = Some(fingerprint);
+    }
+}
+
+/// The connector for an OpenClaw gateway, and where the fingerprint of the
+/// certificate it presents ends up. `[tls]` still supplies the CA file and
+/// client certificate; its `pin_sha256` is not used here.
+pub fn gateway_connector(
+    tls: &TlsConfig,
+    trust: GatewayTrust,
+) -> anyhow::Result<(Connector, ObservedFingerprint)> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = Arc::new(gateway_verifier(tls, trust, provider.clone())?);
+    let observed = verifier.observed.clone();
+    let config = build_client_config(tls, verifier, provider)?;
+    Ok((Connector::Rustls(Arc::new(config)), observed))
+}
+
+fn gateway_verifier(
+    tls: &TlsConfig,
+    trust: GatewayTrust,
+    provider: Arc<CryptoProvider>,
+) -> anyhow::Result<GatewayVerifier> {
+    Ok(GatewayVerifier {
+        pin: match &trust {
+            GatewayTrust::Pinned(pin) => Some(parse_pin(pin, "gateway_tls_fingerprint")?),
+            GatewayTrust::FirstUse | GatewayTrust::Chain => None,
+        },
+        first_use: matches!(trust, GatewayTrust::FirstUse),
+        inner: webpki_verifier(tls, provider)?,
+        observed: ObservedFingerprint::default(),
+    })
+}
+
+fn build_client_config(
+    tls: &TlsConfig,
+    verifier: Arc<dyn ServerCertVerifier>,
+    provider: Arc<CryptoProvider>,
+) -> anyhow::Result<ClientConfig> {
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+    let config = match (&tls.client_cert_file, &tls.client_key_file) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+            .context("client certificate and key don't match")?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => bail!("tls.client_cert_file and tls.client_key_file must be set together"),
+    };
+    Ok(config)
+}
+
+/// How the server's certificate is checked: against the system roots plus
+/// `ca_file`, and against `pin_sha256` when set.
+fn server_verifier(
+    tls: &TlsConfig,
+    provider: Arc<CryptoProvider>,
+) -> anyhow::Result<Arc<dyn ServerCertVerifier>> {
+    let webpki = webpki_verifier(tls, provider)?;
+    Ok(match &tls.pin_sha256 {
+        Some(pin) => Arc::new(PinnedVerifier {
+            pin: parse_pin(pin, "tls.pin_sha256")?,
+            inner: webpki,
+        }),
+        None => webpki,
+    })
+}
+
+/// Chain validation against the system roots plus `ca_file`.
+fn webpki_verifier(
+    tls: &TlsConfig,
+    provider: Arc<CryptoProvider>,
+) -> anyhow::Result<Arc<WebPkiServerVerifier>> {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for err in &native.errors {
+        warn!(error = %err, "could not load some system CA certificates");
+    }
+    roots.add_parsable_certificates(native.certs);
+    if let Some(path) = &tls.ca_file {
+        for cert in load_certs(path)? {
+            roots
+                .add(cert)
+                .with_context(|| format!("invalid CA certificate in {path}"))?;
+        }
+    }
+
+    Ok(WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider).build()?)
+}
+
+/// Lowercase hex SHA-256 of a DER certificate, the form `pin_sha256` takes.
+pub fn fingerprint(cert: &[u8]) -> String {
+    hex::encode(Sha256::digest(cert))
+}
+
+/// Parse the pin in `setting`. Hex pins ignore colons and case so the
+/// output of `openssl x509 -fingerprint -sha256` can be pasted as-is;
+/// base64 (standard or URL-safe, padding optional) is accepted too.
+fn parse_pin(pin: &str, setting: &str) -> anyhow::Result<[u8; 32]> {
+    let hex_digits: String = pin.chars().filter(|c| *c != ':').collect();
+    hex::decode(hex_digits.to_ascii_lowercase())
+        .ok()
+        .or_else(|| {
+            let unpadded = pin.trim_end_matches('=');
+            base64::engine::general_purpose::STANDARD_NO_PAD
+                .decode(unpadded)
+                .or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(unpadded))
+                .ok()
+        })
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .with_context(|| format!("{setting} is not a SHA-256 fingerprint: {pin:?}"))
+}
+
+/// Refuse `cert` unless its fingerprint is `pin`. The error names the
+/// fingerprint seen so it can be copied into the config.
+fn check_pin(pin: &[u8; 32], cert: &[u8]) -> Result<(), rustls::Error> {
+    if Sha256::digest(cert).as_slice() == pin {
+        return Ok(());
+    }
+    let expected = hex::encode(pin);
+    let observed = fingerprint(cert);
+    error!(
+        expected = %expected,
+        observed = %observed,
+        "server certificate does not match the pinned fingerprint; if the new certificate is expected, pin \"{observed}\" instead"
+    );
+    Err(rustls::Error::General(format!(
+        "server certificate sha256 {observed} does not match the pinned fingerprint {expected}"
+    )))
+}
+
+/// Normal chain validation, plus the leaf must match the pin.
+#[derive(Debug)]
+struct PinnedVerifier {
+    pin: [u8; 32],
+    inner: Arc<WebPkiServerVerifier>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        check_pin(&self.pin, end_entity)?;
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Trust for an OpenClaw gateway: the pin alone when there is one,
+/// anything on first use, otherwise the usual chain validation. Records
+/// the fingerprint presented either way.
+#[derive(Debug)]
+struct GatewayVerifier {
+    pin: Option<[u8; 32]>,
+    first_use: bool,
+    inner: Arc<WebPkiServerVerifier>,
+    observed: ObservedFingerprint,
+}
+
+impl ServerCertVerifier for GatewayVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.observed.set(fingerprint(end_entity));
+        if let Some(pin) = &self.pin {
+            check_pin(pin, end_entity)?;
+            return Ok(ServerCertVerified::assertion());
+        }
+        if self.first_use {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn load_certs(path: &str) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read certificates from {path}"))?;
+    if certs.is_empty() {
+        bail!("no PEM certificates in {path}");
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("failed to read private key from {path}"))?
+        .with_context(|| format!("no PEM private key in {path}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER_SHA256: &str = "8d3812d5077cb5ed8ed4f4ee67ffafb351987ff71e4dfd2836b54df2adfe6a6d";
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn server_cert() -> CertificateDer<'static> {
+        load_certs(&fixture("server.pem")).unwrap().remove(0)
+    }
+
+    fn verify(tls: &TlsConfig) -> Result<ServerCertVerified, rustls::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = server_verifier(tls, provider).unwrap();
+        verifier.verify_server_cert(
+            &server_cert(),
+            &[],
+            &ServerName::try_from("localhost").unwrap(),
+            &[],
+            UnixTime::now(),
+        )
+    }
+
+    #[test]
+    fn private_ca_and_client_certificate_are_used() {
+        let tls = TlsConfig {
+            ca_file: Some(fixture("ca.pem")),
+            client_cert_file: Some(fixture("client.pem")),
+            client_key_file: Some(fixture("client.key")),
+            pin_sha256: None,
+        };
+        let config = client_config(&tls).unwrap();
+        assert!(config.client_auth_cert_resolver.has_certs());
+        verify(&tls).expect("server cert chains to the private CA");
+
+        let system_only = TlsConfig::default();
+        let config = client_config(&system_only).unwrap();
+        assert!(!config.client_auth_cert_resolver.has_certs());
+        assert!(verify(&system_only).is_err());
+    }
+
+    #[test]
+    fn bad_tls_config_is_rejected() {
+        let half_pair = client_config(&TlsConfig {
+            client_cert_file: Some(fixture("client.pem")),
+            ..Default::default()
+        });
+        assert!(half_pair.unwrap_err().to_string().contains("set together"));
+
+        let not_a_ca = client_config(&TlsConfig {
+            ca_file: Some(fixture("client.key")),
+            ..Default::default()
+        });
+        assert!(
+            not_a_ca
+                .unwrap_err()
+                .to_string()
+                .contains("no PEM certificates")
+        );
+
+        let bad_pin = client_config(&TlsConfig {
+            pin_sha256: Some("abcd".into()),
+            ..Default::default()
+        });
+        assert!(bad_pin.unwrap_err().to_string().contains("pin_sha256"));
+    }
+
+    #[test]
+    fn pins_accept_openssl_formatting() {
+        let openssl = SERVER_SHA256
+            .to_ascii_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(
+            parse_pin(&openssl, "tls.pin_sha256").unwrap(),
+            parse_pin(SERVER_SHA256, "tls.pin_sha256").unwrap()
+        );
+        assert_eq!(fingerprint(&server_cert()), SERVER_SHA256);
+    }
+
+    #[test]
+    fn pinned_server_must_match() {
+        let pinned = |pin: &str| TlsConfig {
+            ca_file: Some(fixture("ca.pem")),
+            pin_sha256: Some(pin.to_string()),
+            ..Default::default()
+        };
+        verify(&pinned(SERVER_SHA256)).expect("matching pin");
+
+        let other = "00".repeat(32);
+        let err = verify(&pinned(&other)).unwrap_err();
+        assert!(
+            err.to_string().contains(SERVER_SHA256),
+            "error should name the observed fingerprint: {err}"
+        );
+    }
+
+    const SELF_SIGNED_SHA256: &str =
+        "04ace7f7c81f7811e2d9462c8c5d83fedf1c426dfadf3fd50fe9bd6b7e41367e";
+
+    fn verify_gateway(
+        trust: GatewayTrust,
+    ) -> (Result<ServerCertVerified, rustls::Error>, Option<String>) {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = gateway_verifier(&TlsConfig::default(), trust, provider).unwrap();
+        let cert = load_certs(&fixture("self-signed.pem")).unwrap().remove(0);
+        let result = verifier.verify_server_cert(
+            &cert,
+            &[],
+            &ServerName::try_from("localhost").unwrap(),
+            &[],
+            UnixTime::now(),
+        );
+        (result, verifier.observed.get())
+    }
+
+    #[test]
+    fn pinned_self_signed_gateway_is_trusted_by_hex_or_base64() {
+        let base64 = "BKzn98gfeBHi2UYsjF2D/t8cQm363z/VD+m9a35BNn4=";
+        for pin in [SELF_SIGNED_SHA256, base64, base64.trim_end_matches('=')] {
+            let (result, observed) = verify_gateway(GatewayTrust::Pinned(pin.into()));
+            result.unwrap_or_else(|e| panic!("pin {pin} should match: {e}"));
+            assert_eq!(observed.as_deref(), Some(SELF_SIGNED_SHA256));
+        }
+        // Without a pin a self-signed gateway still fails chain validation.
+        assert!(verify_gateway(GatewayTrust::Chain).0.is_err());
+    }
+
+    #[test]
+    fn mismatched_gateway_pin_names_expected_and_actual() {
+        let expected = "11".repeat(32);
+        let (result, _) = verify_gateway(GatewayTrust::Pinned(expected.clone()));
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains(&expected), "{err}");
+        assert!(err.contains(SELF_SIGNED_SHA256), "{err}");
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let bad = gateway_verifier(
+            &TlsConfig::default(),
+            GatewayTrust::Pinned("not-a-pin".into()),
+            provider,
+        );
+        assert!(
+            bad.unwrap_err()
+                .to_string()
+                .contains("gateway_tls_fingerprint")
+        );
+    }
+
+    #[test]
+    fn first_use_accepts_and_records_the_gateway_certificate() {
+        let (result, observed) = verify_gateway(GatewayTrust::FirstUse);
+        result.expect("trust on first use");
+        assert_eq!(observed.as_deref(), Some(SELF_SIGNED_SHA256));
+    }
+}
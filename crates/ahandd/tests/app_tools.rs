@@ -20,6 +20,7 @@
 //!   * 5 concurrent slow calls → exactly one CONCURRENCY_LIMIT.
 //!   * Duplicate tool_call_id while running → ignored; after completion →
 //!     cached response re-sent with identical payload.
+//!   * Envelopes replayed with an already-seen seq → dropped and re-acked.
 //!
 //! Task 6 session-mode gate matrix:
 //! ┌─────────────────────────────┬───────────────────────────────────────────────┐
@@ -44,7 +45,7 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
-use ahand_protocol::app_tool_response;
+use ahand_protocol::{AppToolRequest, Envelope, app_tool_response, envelope};
 use ahandd::{AppToolDef, AppToolHandler, DaemonConfig, DaemonStatus, SessionMode, spawn};
 use serde_json::json;
 use tempfile::TempDir;
@@ -874,6 +875,55 @@ async fn duplicate_call_id_while_running_is_ignored() {
     handle.shutdown().await.expect("shutdown clean");
 }
 
+/// A retransmitted stream (as the hub replays after a reconnect) with
+/// duplicated segments: every request runs once and gets one response, and
+/// each duplicate is answered with an `Ack` instead of being processed.
+#[tokio::test]
+async fn replayed_seqs_are_processed_once() {
+    let (mock, handle, _tmp) = setup_dispatch_daemon().await;
+    let run_count = Arc::new(AtomicUsize::new(0));
+    register_counted_echo(&handle, "counted", false, Arc::clone(&run_count)).await;
+    mock.wait_for_app_tools_updates(2, Duration::from_secs(5))
+        .await
+        .expect("tool snapshot");
+
+    for seq in [1, 2, 1, 2, 3, 3, 2, 4] {
+        mock.inject(Envelope {
+            device_id: "mock-hub".into(),
+            msg_id: format!("replay-{seq}"),
+            seq,
+            payload: Some(envelope::Payload::AppToolRequest(AppToolRequest {
+                tool_call_id: format!("replay-{seq}"),
+                name: "counted".into(),
+                args_json: "{}".into(),
+                timeout_ms: 5000,
+            })),
+            ..Default::default()
+        })
+        .expect("inject ok");
+    }
+
+    mock.wait_for_app_tool_responses(4, Duration::from_secs(5))
+        .await
+        .expect("4 AppToolResponses not received within 5s");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut call_ids: Vec<_> = mock
+        .captured_app_tool_responses()
+        .into_iter()
+        .map(|r| r.tool_call_id)
+        .collect();
+    call_ids.sort();
+    assert_eq!(call_ids, ["replay-1", "replay-2", "replay-3", "replay-4"]);
+    assert_eq!(run_count.load(Ordering::SeqCst), 4);
+    assert!(
+        mock.captured_acks().contains(&3),
+        "duplicates must be re-acked: {:?}",
+        mock.captured_acks()
+    );
+
+    handle.shutdown().await.expect("shutdown clean");
+}
+
 // ── Task 6: session-mode gate + tighten-only approval tests ──────────────────
 
 /// Build a DaemonConfig with a specific session mode, short approval_timeout,
@@ -58,6 +58,11 @@ fn fixture_hello() -> Hello {
         os: FIXTURE_OS.into(),
         capabilities: vec!["exec".into(), "browser-playwright-cli".into()],
         last_ack: FIXTURE_LAST_ACK,
+        protocol_version: 0,
+        ipc_token: String::new(),
+        client_name: String::new(),
+        instance_id: String::new(),
+        accepts: vec![],
         auth: None,
     }
 }
@@ -211,6 +211,7 @@ async fn watchdog_detects_silent_zombie_and_reconnects() {
     })
     .await
     .expect("daemon never reached Online");
+    let online_at = tokio::time::Instant::now();
 
     // Step 2: with no Pong ever arriving, the read-loop watchdog must
     // expire and the outer reconnect loop must report Disconnected,
@@ -233,6 +234,50 @@ async fn watchdog_detects_silent_zombie_and_reconnects() {
          tokio::time::timeout wrapper are still both wired in.",
     );
 
+    // The watchdog waits out its full 2× heartbeat window, but not much
+    // longer: the daemon is back to dialing well inside the budget.
+    let offline_after = online_at.elapsed();
+    assert!(
+        offline_after >= Duration::from_millis(300),
+        "watchdog fired early, after {offline_after:?}"
+    );
+    assert!(
+        offline_after < Duration::from_secs(2),
+        "watchdog took {offline_after:?} to notice the silent hub"
+    );
+
+    handle.shutdown().await.expect("shutdown clean");
+}
+
+/// The other direction: the hub pings the daemon, and the Pong has to come
+/// back promptly even when the daemon has nothing of its own to send.
+/// tungstenite only flushes its queued Pong on the next read or write, so a
+/// read loop that stopped polling would leave it waiting for the daemon's
+/// next heartbeat, 30s away here, and the hub would take it for dead.
+#[tokio::test]
+async fn idle_daemon_answers_hub_pings_promptly() {
+    let mock = mock_hub::start_pinging_after_handshake(3).await;
+    let tmp = TempDir::new().unwrap();
+    let config = DaemonConfig::builder(mock.ws_url(), mock.valid_jwt(), tmp.path())
+        .heartbeat_interval(Duration::from_secs(30))
+        .build();
+    let handle = spawn(config).await.expect("spawn ok");
+
+    let rtts = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let rtts = mock.captured_pong_rtts();
+            if rtts.len() >= 3 {
+                break rtts;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+    })
+    .await
+    .expect("daemon never answered the hub's pings");
+    for rtt in rtts {
+        assert!(rtt < Duration::from_secs(1), "Pong took {rtt:?}");
+    }
+
     handle.shutdown().await.expect("shutdown clean");
 }
 
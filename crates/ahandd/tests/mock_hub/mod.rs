@@ -9,6 +9,8 @@
 //!   * [`start_silent_after_handshake`] — accepts handshake then stops
 //!     reading, leaving the WS in a half-zombie state for the watchdog to
 //!     catch.
+//!   * [`start_pinging_after_handshake`] — accepts handshake, then sends WS
+//!     Pings and records how long each Pong takes to come back.
 //!   * [`start_with_file_request`] — accepts handshake, immediately injects
 //!     a [`FileRequest`] envelope and captures the daemon's [`FileResponse`].
 //!     Used to exercise the daemon's `handle_file_request` glue end-to-end
@@ -17,6 +19,9 @@
 //!     but drops the first connection after receiving `n` `AppToolsUpdate`
 //!     snapshots, letting the daemon reconnect.
 //!
+//! Accepting mocks can push arbitrary envelopes with [`Mock::inject`] and
+//! record the daemon's standalone acks.
+//!
 //! Keep this module small and self-contained — it exists so the daemon's
 //! status state machine has something to race against, not to model the
 //! full hub protocol.
@@ -53,6 +58,10 @@ pub struct Mock {
     app_tool_responses: Arc<Mutex<Vec<AppToolResponse>>>,
     /// Captured ApprovalRequest envelopes received from the daemon.
     approval_requests: Arc<Mutex<Vec<ApprovalRequest>>>,
+    /// `ack` of every standalone `Ack` received from the daemon.
+    acks: Arc<Mutex<Vec<u64>>>,
+    /// Round-trip time of every Ping answered by the daemon.
+    pong_rtts: Arc<Mutex<Vec<std::time::Duration>>>,
     inject_tx: InjectSlot,
     _shutdown: oneshot::Sender<()>,
     _task: JoinHandle<()>,
@@ -99,6 +108,26 @@ impl Mock {
         self.approval_requests.lock().unwrap().clone()
     }
 
+    /// Every standalone `Ack` value received from connected daemons.
+    pub fn captured_acks(&self) -> Vec<u64> {
+        self.acks.lock().unwrap().clone()
+    }
+
+    /// Round-trip time of every WS Ping the daemon answered, in order.
+    pub fn captured_pong_rtts(&self) -> Vec<std::time::Duration> {
+        self.pong_rtts.lock().unwrap().clone()
+    }
+
+    /// Push `envelope` to the connected daemon as-is, seq included. Returns
+    /// `Err` if no daemon is currently connected (no inject channel active).
+    pub fn inject(&self, envelope: Envelope) -> Result<(), String> {
+        let guard = self.inject_tx.lock().unwrap();
+        match guard.as_ref() {
+            Some((_gen, tx)) => tx.send(envelope).map_err(|e| e.to_string()),
+            None => Err("no active connection inject channel".to_string()),
+        }
+    }
+
     /// Wait until at least `n` `ApprovalRequest` envelopes have been received,
     /// then return all of them. Returns `None` on timeout.
     pub async fn wait_for_approval_requests(
@@ -136,14 +165,11 @@ impl Mock {
                 approved,
                 reason: reason.into(),
                 remember: false,
+                ..Default::default()
             })),
             ..Default::default()
         };
-        let guard = self.inject_tx.lock().unwrap();
-        match guard.as_ref() {
-            Some((_gen, tx)) => tx.send(env).map_err(|e| e.to_string()),
-            None => Err("no active connection inject channel".to_string()),
-        }
+        self.inject(env)
     }
 
     /// Wait until at least `n` `AppToolsUpdate` envelopes have been
@@ -188,11 +214,7 @@ impl Mock {
             })),
             ..Default::default()
         };
-        let guard = self.inject_tx.lock().unwrap();
-        match guard.as_ref() {
-            Some((_gen, tx)) => tx.send(env).map_err(|e| e.to_string()),
-            None => Err("no active connection inject channel".to_string()),
-        }
+        self.inject(env)
     }
 
     /// Wait until at least `n` `AppToolResponse` envelopes have been received,
@@ -245,6 +267,13 @@ pub async fn start_silent_after_handshake() -> Mock {
     start(Behavior::SilentAfterHandshake).await
 }
 
+/// Start a mock hub that accepts the handshake, lets the daemon go quiet,
+/// then sends `count` WS Pings one at a time, recording each Pong's
+/// round-trip time in `Mock::captured_pong_rtts()`.
+pub async fn start_pinging_after_handshake(count: usize) -> Mock {
+    start(Behavior::PingAfterHandshake { count }).await
+}
+
 /// Start a mock hub that accepts the handshake, then immediately injects
 /// the supplied `FileRequest` over the WebSocket. Captures every inbound
 /// `FileResponse` the daemon sends back into `Mock::captured_file_responses()`.
@@ -278,6 +307,9 @@ enum Behavior {
     },
     RejectAuth,
     SilentAfterHandshake,
+    PingAfterHandshake {
+        count: usize,
+    },
     SendFileRequest(Arc<FileRequest>),
 }
 
@@ -290,6 +322,8 @@ async fn start(behavior: Behavior) -> Mock {
     let app_tools_updates: Arc<Mutex<Vec<AppToolsUpdate>>> = Arc::new(Mutex::new(Vec::new()));
     let app_tool_responses: Arc<Mutex<Vec<AppToolResponse>>> = Arc::new(Mutex::new(Vec::new()));
     let approval_requests: Arc<Mutex<Vec<ApprovalRequest>>> = Arc::new(Mutex::new(Vec::new()));
+    let acks: Arc<Mutex<Vec<u64>>> = Arc::new(Mutex::new(Vec::new()));
+    let pong_rtts = Arc::new(Mutex::new(Vec::new()));
     let inject_tx: InjectSlot = Arc::new(Mutex::new(None));
     // Monotonically increasing connection generation counter.
     let conn_gen: Arc<std::sync::atomic::AtomicU64> =
@@ -300,6 +334,8 @@ async fn start(behavior: Behavior) -> Mock {
     let app_tools_updates_for_task = app_tools_updates.clone();
     let app_tool_responses_for_task = app_tool_responses.clone();
     let approval_requests_for_task = approval_requests.clone();
+    let acks_for_task = acks.clone();
+    let pong_rtts_for_task = pong_rtts.clone();
     let inject_tx_for_task = inject_tx.clone();
     let conn_gen_for_task = conn_gen.clone();
     let task = tokio::spawn(async move {
@@ -322,6 +358,8 @@ async fn start(behavior: Behavior) -> Mock {
                         app_tools_updates_for_task.clone(),
                         app_tool_responses_for_task.clone(),
                         approval_requests_for_task.clone(),
+                        acks_for_task.clone(),
+                        pong_rtts_for_task.clone(),
                         inject_tx_for_task.clone(),
                         conn_gen_id,
                     ));
@@ -337,6 +375,8 @@ async fn start(behavior: Behavior) -> Mock {
         app_tools_updates,
         app_tool_responses,
         approval_requests,
+        acks,
+        pong_rtts,
         inject_tx,
         _shutdown: shutdown_tx,
         _task: task,
@@ -352,6 +392,8 @@ async fn handle_conn(
     app_tools_updates: Arc<Mutex<Vec<AppToolsUpdate>>>,
     app_tool_responses: Arc<Mutex<Vec<AppToolResponse>>>,
     approval_requests: Arc<Mutex<Vec<ApprovalRequest>>>,
+    acks: Arc<Mutex<Vec<u64>>>,
+    pong_rtts: Arc<Mutex<Vec<std::time::Duration>>>,
     inject_tx: InjectSlot,
     conn_generation: u64,
 ) {
@@ -399,6 +441,7 @@ async fn handle_conn(
                 payload: Some(envelope::Payload::HelloAccepted(HelloAccepted {
                     auth_method: "bootstrap".into(),
                     update_suggestion: None,
+                    ..Default::default()
                 })),
                 ..Default::default()
             };
@@ -463,6 +506,9 @@ async fn handle_conn(
                             Some(envelope::Payload::ApprovalRequest(req)) => {
                                 approval_requests.lock().unwrap().push(req);
                             }
+                            Some(envelope::Payload::StandaloneAck(ack)) => {
+                                acks.lock().unwrap().push(ack.ack);
+                            }
                             _ => {}
                         }
                     }
@@ -504,6 +550,7 @@ async fn handle_conn(
                 payload: Some(envelope::Payload::HelloAccepted(HelloAccepted {
                     auth_method: "bootstrap".into(),
                     update_suggestion: None,
+                    ..Default::default()
                 })),
                 ..Default::default()
             };
@@ -517,6 +564,52 @@ async fn handle_conn(
             let _keep_alive = (sink, src);
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
         }
+        Behavior::PingAfterHandshake { count } => {
+            let accepted = Envelope {
+                device_id: "mock-hub".into(),
+                msg_id: "accepted-ping".into(),
+                ts_ms: 0,
+                payload: Some(envelope::Payload::HelloAccepted(HelloAccepted {
+                    auth_method: "bootstrap".into(),
+                    update_suggestion: None,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            };
+            let _ = sink.send(WsMessage::Binary(accepted.encode_to_vec())).await;
+
+            // Drain what the daemon sends right after the handshake, so a
+            // Pong can't ride out on the back of one of its own writes.
+            let settle = tokio::time::sleep(std::time::Duration::from_millis(300));
+            tokio::pin!(settle);
+            loop {
+                tokio::select! {
+                    _ = &mut settle => break,
+                    msg = src.next() => if !matches!(msg, Some(Ok(_))) {
+                        return;
+                    },
+                }
+            }
+
+            for n in 0..count {
+                let payload = (n as u64).to_be_bytes().to_vec();
+                let sent_at = tokio::time::Instant::now();
+                if sink.send(WsMessage::Ping(payload.clone())).await.is_err() {
+                    return;
+                }
+                loop {
+                    match src.next().await {
+                        Some(Ok(WsMessage::Pong(p))) if p == payload => break,
+                        Some(Ok(_)) => continue,
+                        _ => return,
+                    }
+                }
+                pong_rtts.lock().unwrap().push(sent_at.elapsed());
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            // Hold the connection open while the test inspects the results.
+            while let Some(Ok(_)) = src.next().await {}
+        }
         Behavior::SendFileRequest(req) => {
             let accepted = Envelope {
                 device_id: "mock-hub".into(),
@@ -525,6 +618,7 @@ async fn handle_conn(
                 payload: Some(envelope::Payload::HelloAccepted(HelloAccepted {
                     auth_method: "bootstrap".into(),
                     update_suggestion: None,
+                    ..Default::default()
                 })),
                 ..Default::default()
             };